@@ -0,0 +1,56 @@
+//! The fallback extractor, which handles every message while the LLM is
+//! down or unconfigured; a burst of email then goes through it back to
+//! back.
+//!
+//! ```sh
+//! cargo bench -p ai-agent-service --bench extraction
+//! ```
+
+use ai_agent_service::language::Language;
+use ai_agent_service::llm_client::LLMClient;
+use common::{config::ServiceConfig, reload::LiveConfig};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const MESSAGES: &[&str] = &[
+    "Hi team, please review the quarterly report and call the auditor tomorrow. #finance",
+    "Need to buy milk, eggs and bread on the way home",
+    "Can you schedule a meeting with the design team next week? Should take 45 min.",
+    "URGENT: fix the login outage and email the customers affected today",
+    "Necesito comprar leche, huevos y pan para mañana",
+    "Bonjour, il faut appeler le fournisseur demain et envoyer la facture",
+    "Thanks for the update, sounds good to me!",
+];
+
+/// A burst of email, as after the collector catches up on a full inbox.
+const BURST: usize = 100;
+
+fn client() -> LLMClient {
+    LLMClient::new(LiveConfig::init(&ServiceConfig::for_tests("ai-agent-service")))
+}
+
+fn fallback_extraction(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let _runtime = runtime.enter();
+    let client = client();
+    let messages: Vec<(&str, Language)> = MESSAGES.iter().map(|message| (*message, Language::detect(message))).collect();
+
+    let mut group = c.benchmark_group("fallback_extraction");
+    for (i, (message, language)) in messages.iter().enumerate() {
+        group.bench_with_input(BenchmarkId::new("message", i), message, |b, message| {
+            b.iter(|| client.fallback_extraction(black_box(message), *language))
+        });
+    }
+
+    group.sample_size(10).throughput(Throughput::Elements(BURST as u64));
+    group.bench_function("burst", |b| {
+        b.iter(|| {
+            for (message, language) in messages.iter().cycle().take(BURST) {
+                black_box(client.fallback_extraction(black_box(message), *language));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, fallback_extraction);
+criterion_main!(benches);
@@ -0,0 +1,262 @@
+//! The tools the agent can use, and the steps of the agent loop.
+//!
+//! Instead of extracting tasks in one shot, the model is shown the message
+//! and a set of tools. Each step it either calls some of them, and sees
+//! their results in the next step, or writes its reply, which ends the
+//! loop. The service runs at most a configured number of steps, so a
+//! model that keeps calling tools still gets an answer out.
+
+use chrono::{DateTime, Utc};
+use models::{CaseStatus, Priority, TaskStatus};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::LazyLock;
+use uuid::Uuid;
+
+use crate::language::Language;
+use crate::llm_client::{PromptContext, TaskData};
+
+pub const DEFAULT_MAX_STEPS: usize = 6;
+
+/// A tool call as the model made it. The arguments are kept as sent so
+/// the call can be replayed to the model in later steps.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// JSON object of arguments.
+    pub arguments: String,
+}
+
+impl ToolCall {
+    pub fn new(id: impl Into<String>, tool: &Tool) -> Self {
+        let value = serde_json::to_value(tool).expect("tools serialize");
+        Self {
+            id: id.into(),
+            name: value["name"].as_str().unwrap_or_default().to_string(),
+            arguments: value["arguments"].to_string(),
+        }
+    }
+
+    /// The call's tool, or why it isn't a valid one.
+    pub fn tool(&self) -> Result<Tool, String> {
+        let arguments: Value = serde_json::from_str(&self.arguments).map_err(|e| format!("Arguments are not JSON: {}", e))?;
+        serde_json::from_value(json!({ "name": self.name, "arguments": arguments }))
+            .map_err(|e| format!("Invalid call of {}: {}", self.name, e))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "name", content = "arguments", rename_all = "snake_case")]
+pub enum Tool {
+    CreateTask(TaskData),
+    UpdateTask(UpdateTaskArgs),
+    CloseCase(CloseCaseArgs),
+    ScheduleMeeting(MeetingArgs),
+    LookupHistory(LookupArgs),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateTaskArgs {
+    pub task_id: Uuid,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub status: Option<TaskStatus>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub due_date: Option<DateTime<Utc>>,
+    /// Replaces a meeting's attendees.
+    #[serde(default)]
+    pub attendees: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloseCaseArgs {
+    /// The case the message was filed under when not given.
+    #[serde(default)]
+    pub case_id: Option<Uuid>,
+    /// `Resolved` or `Closed`; `Closed` when not given.
+    #[serde(default)]
+    pub status: Option<CaseStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeetingArgs {
+    pub title: String,
+    pub starts_at: DateTime<Utc>,
+    #[serde(default)]
+    pub duration_minutes: Option<i32>,
+    #[serde(default)]
+    pub attendees: Vec<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LookupArgs {
+    /// Words the case's title or description should contain; the latest
+    /// cases when empty.
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Include resolved and closed cases.
+    #[serde(default)]
+    pub include_closed: bool,
+}
+
+/// What happened so far in a run, replayed to the model every step.
+#[derive(Debug, Clone)]
+pub enum AgentTurn {
+    /// Text the model wrote along with its calls.
+    Calls { text: String, calls: Vec<ToolCall> },
+    /// The result of a call, as JSON.
+    Result { call_id: String, content: String },
+}
+
+/// One message being handled by the agent.
+#[derive(Debug, Clone)]
+pub struct AgentRun {
+    pub message: String,
+    pub case_id: Uuid,
+    pub language: Language,
+    pub context: PromptContext,
+    pub turns: Vec<AgentTurn>,
+}
+
+impl AgentRun {
+    pub fn new(message: String, case_id: Uuid, language: Language, context: PromptContext) -> Self {
+        Self { message, case_id, language, context, turns: Vec::new() }
+    }
+
+    /// The calls made so far, oldest first.
+    pub fn calls(&self) -> impl Iterator<Item = &ToolCall> {
+        self.turns.iter().flat_map(|turn| match turn {
+            AgentTurn::Calls { calls, .. } => calls.as_slice(),
+            AgentTurn::Result { .. } => &[],
+        })
+    }
+}
+
+/// What the model decided to do in a step.
+#[derive(Debug, Clone)]
+pub enum AgentStep {
+    /// Make these calls and ask again with their results. `text` is
+    /// anything the model wrote along with them.
+    Calls { text: String, calls: Vec<ToolCall> },
+    /// The reply to the user; the run is over.
+    Reply(String),
+}
+
+/// The tools in OpenAI's function calling format.
+pub fn definitions() -> &'static Value {
+    &DEFINITIONS
+}
+
+static DEFINITIONS: LazyLock<Value> = LazyLock::new(|| {
+    let priority = json!({ "type": "string", "enum": ["Low", "Medium", "High", "Critical"] });
+    let date = |description: &str| json!({ "type": "string", "description": description });
+    json!([
+        function(
+            "create_task",
+            "Create a task in the current case for something the user has to do.",
+            json!({
+                "title": { "type": "string" },
+                "description": { "type": "string" },
+                "task_type": { "description": "Meeting, Shopping, Work, Personal, Research, Communication or Other, or {\"Other\": \"<name>\"} for one of the organization's task types" },
+                "priority": priority,
+                "due_date": date("ISO 8601 date and time, if mentioned or implied"),
+                "tags": { "type": "array", "items": { "type": "string" }, "description": "Up to three short lowercase tags: projects, people, topics" },
+                "estimate_minutes": { "type": "integer", "description": "Effort, when stated or reasonably inferred (a quick call is about 15)" },
+                "confidence": { "type": "number", "description": "0.0 to 1.0: how sure you are this is a real, correctly understood task" },
+                "metadata": { "type": "object", "description": "{\"items\": [...]} for Shopping tasks, or the fields of an organization task type" }
+            }),
+            &["title", "task_type", "priority", "confidence"],
+        ),
+        function(
+            "update_task",
+            "Change an existing task, e.g. mark it completed, move its due date or set a meeting's attendees. Find task ids with lookup_history.",
+            json!({
+                "task_id": { "type": "string" },
+                "title": { "type": "string" },
+                "description": { "type": "string" },
+                "status": { "type": "string", "enum": ["Pending", "InProgress", "Completed", "Cancelled", "OnHold"] },
+                "priority": priority,
+                "due_date": date("ISO 8601 date and time"),
+                "attendees": { "type": "array", "items": { "type": "string" }, "description": "A meeting's attendees" }
+            }),
+            &["task_id"],
+        ),
+        function(
+            "close_case",
+            "Close a case the user is done with. Find other cases' ids with lookup_history.",
+            json!({
+                "case_id": { "type": "string", "description": "Defaults to the current case" },
+                "status": { "type": "string", "enum": ["Resolved", "Closed"] }
+            }),
+            &[],
+        ),
+        function(
+            "schedule_meeting",
+            "Add a meeting to the user's calendar in the current case.",
+            json!({
+                "title": { "type": "string" },
+                "starts_at": date("ISO 8601 date and time"),
+                "duration_minutes": { "type": "integer" },
+                "attendees": { "type": "array", "items": { "type": "string" } },
+                "description": { "type": "string" }
+            }),
+            &["title", "starts_at"],
+        ),
+        function(
+            "lookup_history",
+            "Find the user's earlier cases, with their tasks, by words in their title or description.",
+            json!({
+                "query": { "type": "string" },
+                "include_closed": { "type": "boolean" }
+            }),
+            &[],
+        ),
+    ])
+});
+
+fn function(name: &str, description: &str, properties: Value, required: &[&str]) -> Value {
+    json!({
+        "type": "function",
+        "function": {
+            "name": name,
+            "description": description,
+            "parameters": { "type": "object", "properties": properties, "required": required }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_round_trip_through_their_json_arguments() {
+        let tool = Tool::CloseCase(CloseCaseArgs { case_id: Some(Uuid::nil()), status: Some(CaseStatus::Resolved) });
+        let call = ToolCall::new("call-1", &tool);
+        assert_eq!(call.name, "close_case");
+        assert!(matches!(call.tool(), Ok(Tool::CloseCase(args)) if args.status == Some(CaseStatus::Resolved)));
+    }
+
+    #[test]
+    fn invalid_calls_are_explained() {
+        let call = ToolCall { id: "call-1".to_string(), name: "update_task".to_string(), arguments: "{}".to_string() };
+        assert!(call.tool().unwrap_err().contains("task_id"));
+
+        let call = ToolCall { id: "call-2".to_string(), name: "delete_everything".to_string(), arguments: "{}".to_string() };
+        assert!(call.tool().is_err());
+    }
+
+    #[test]
+    fn every_tool_is_defined() {
+        let names: Vec<&str> = definitions().as_array().unwrap().iter().map(|tool| tool["function"]["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["create_task", "update_task", "close_case", "schedule_meeting", "lookup_history"]);
+    }
+}
@@ -0,0 +1,57 @@
+//! Few-shot examples for the extraction prompt.
+//!
+//! Users keep a library of messages with the tasks that should come out of
+//! them.  The examples sharing the most words with an incoming message are
+//! added to the prompt, so the LLM picks up the user's own vocabulary
+//! (project names, shorthand, who "the usual call" is with).
+
+use common::{auth::SessionToken, http_client::HttpClient};
+use models::ExtractionExample;
+use std::collections::HashSet;
+use tracing::warn;
+
+pub const DEFAULT_MAX_EXAMPLES: usize = 3;
+
+/// The sender's examples most relevant to `message`, at most `max` of
+/// them.  Messages without a session have no examples, and a failed lookup
+/// only costs the examples.
+pub async fn for_message(
+    client: &HttpClient,
+    session: &SessionToken,
+    persistence_url: &str,
+    message: &str,
+    max: usize,
+) -> Vec<ExtractionExample> {
+    if session.0.is_none() || max == 0 {
+        return Vec::new();
+    }
+    let url = format!("{}/api/v1/extraction-examples", persistence_url);
+    match client.get::<Vec<ExtractionExample>>(&url).await {
+        Ok(examples) => select(examples, message, max),
+        Err(e) => {
+            warn!("Could not load extraction examples: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Picks the examples sharing the most words with `message`.  Examples are
+/// listed newest first, which breaks ties in favour of recent ones.
+fn select(examples: Vec<ExtractionExample>, message: &str, max: usize) -> Vec<ExtractionExample> {
+    let message_words = words(message);
+    let mut scored: Vec<(usize, ExtractionExample)> = examples
+        .into_iter()
+        .map(|example| (message_words.intersection(&words(&example.message)).count(), example))
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().take(max).map(|(_, example)| example).collect()
+}
+
+/// Lowercase words of three or more letters, which skips most function
+/// words.
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .collect()
+}
@@ -0,0 +1,276 @@
+//! Message language detection.
+//!
+//! Languages are told apart by counting common function words, which is
+//! enough for the short messages the agent sees.  The detected language is
+//! passed to the LLM so it replies in kind, stored on new cases and picks
+//! the keyword set used by the fallback extractor.
+
+use models::TaskType;
+use regex::Regex;
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Spanish,
+    French,
+    German,
+    Portuguese,
+}
+
+/// Words the fallback extractor looks for in one language.
+pub struct Keywords {
+    /// Patterns whose second group is the task title.
+    pub task_patterns: &'static [(&'static str, TaskType)],
+    pub critical: &'static [&'static str],
+    pub high: &'static [&'static str],
+    pub low: &'static [&'static str],
+    pub today: &'static [&'static str],
+    pub tomorrow: &'static [&'static str],
+    pub next_week: &'static [&'static str],
+    /// Monday to Sunday, lowercase.
+    pub weekdays: [&'static str; 7],
+    /// Reply when no task was found.
+    pub noted: &'static str,
+    /// Reply for a single task; `{}` is its title.
+    pub one_task: &'static str,
+    /// Reply for several tasks; the first `{}` is the count, the second
+    /// the titles.
+    pub many_tasks: &'static str,
+    /// Follow-up questions about a task; `{}` is its title.
+    pub ask_due: &'static str,
+    pub ask_meeting_time: &'static str,
+    pub ask_attendees: &'static str,
+}
+
+/// A message needs this many function words of a language before it is
+/// taken to be in that language rather than the default.
+const MIN_SCORE: usize = 2;
+
+const ALL: [Language; 5] = [
+    Language::English,
+    Language::Spanish,
+    Language::French,
+    Language::German,
+    Language::Portuguese,
+];
+
+/// The task patterns of each language in [`ALL`], compiled once.
+static TASK_PATTERNS: LazyLock<[Vec<(Regex, TaskType)>; 5]> = LazyLock::new(|| {
+    ALL.map(|language| {
+        language
+            .keywords()
+            .task_patterns
+            .iter()
+            .map(|(pattern, task_type)| (Regex::new(pattern).expect("task pattern is valid"), task_type.clone()))
+            .collect()
+    })
+});
+
+impl Language {
+    /// The language `text` is most likely written in, English when unsure.
+    pub fn detect(text: &str) -> Self {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphabetic() && c != '\'')
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+
+        let mut best = (Language::English, 0);
+        for language in ALL {
+            let stopwords = language.stopwords();
+            let score = words.iter().filter(|word| stopwords.contains(&word.as_str())).count();
+            if score > best.1 {
+                best = (language, score);
+            }
+        }
+        if best.1 >= MIN_SCORE {
+            best.0
+        } else {
+            Language::English
+        }
+    }
+
+    /// ISO 639-1 code, as stored on cases.
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+            Language::French => "fr",
+            Language::German => "de",
+            Language::Portuguese => "pt",
+        }
+    }
+
+    /// English name, as used in prompts.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Spanish",
+            Language::French => "French",
+            Language::German => "German",
+            Language::Portuguese => "Portuguese",
+        }
+    }
+
+    fn stopwords(self) -> &'static [&'static str] {
+        match self {
+            Language::English => &[
+                "the", "and", "to", "of", "is", "you", "that", "it", "for", "please", "with", "this", "we", "i",
+                "on", "need", "my", "can",
+            ],
+            Language::Spanish => &[
+                "el", "los", "las", "que", "y", "en", "por", "para", "con", "una", "es", "del", "favor", "tengo",
+                "necesito", "mañana", "hoy", "hay",
+            ],
+            Language::French => &[
+                "le", "les", "des", "et", "est", "pour", "une", "avec", "vous", "je", "pas", "du", "il", "faut",
+                "dois", "demain", "aujourd'hui", "merci",
+            ],
+            Language::German => &[
+                "der", "die", "das", "und", "ist", "nicht", "ich", "zu", "mit", "den", "ein", "eine", "bitte",
+                "wir", "auf", "muss", "morgen", "heute",
+            ],
+            Language::Portuguese => &[
+                "os", "do", "da", "para", "com", "uma", "não", "você", "preciso", "tenho", "amanhã", "hoje",
+                "obrigado", "obrigada", "e", "pra",
+            ],
+        }
+    }
+
+    /// [`Keywords::task_patterns`], compiled.
+    pub fn task_patterns(self) -> &'static [(Regex, TaskType)] {
+        let index = ALL.iter().position(|language| *language == self).unwrap_or(0);
+        &TASK_PATTERNS[index]
+    }
+
+    pub fn keywords(self) -> &'static Keywords {
+        match self {
+            Language::English => &ENGLISH,
+            Language::Spanish => &SPANISH,
+            Language::French => &FRENCH,
+            Language::German => &GERMAN,
+            Language::Portuguese => &PORTUGUESE,
+        }
+    }
+}
+
+static ENGLISH: Keywords = Keywords {
+    task_patterns: &[
+        (r"(?i)\b(schedule|book|arrange)\s+(.+?)(?:\s+(?:for|on|at)\s+(.+?))?(?:\.|$)", TaskType::Meeting),
+        (r"(?i)\b(buy|purchase|get|shop for)\s+(.+?)(?:\.|$)", TaskType::Shopping),
+        (r"(?i)\b(call|email|contact|reach out to)\s+(.+?)(?:\.|$)", TaskType::Communication),
+        (r"(?i)\b(research|look into|investigate|find out about)\s+(.+?)(?:\.|$)", TaskType::Research),
+        (r"(?i)\b(complete|finish|work on|do)\s+(.+?)(?:\.|$)", TaskType::Work),
+        (r"(?i)\b(remind me to|need to|have to|must)\s+(.+?)(?:\.|$)", TaskType::Personal),
+    ],
+    critical: &["urgent", "asap", "immediately"],
+    high: &["important", "priority"],
+    low: &["when you can", "no rush"],
+    today: &["today"],
+    tomorrow: &["tomorrow"],
+    next_week: &["next week"],
+    weekdays: ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"],
+    noted: "I've noted your message. How can I help you further?",
+    one_task: "I've created a task for you: '{}'. Is there anything else you need help with?",
+    many_tasks: "I've created {} tasks based on your message. They include: {}. Let me know if you need any adjustments!",
+    ask_due: "When is '{}' due?",
+    ask_meeting_time: "When should '{}' take place?",
+    ask_attendees: "Who should attend '{}'?",
+};
+
+static SPANISH: Keywords = Keywords {
+    task_patterns: &[
+        (r"(?i)\b(programar|agendar|reservar|organizar)\s+(.+?)(?:\.|$)", TaskType::Meeting),
+        (r"(?i)\b(comprar|conseguir)\s+(.+?)(?:\.|$)", TaskType::Shopping),
+        (r"(?i)\b(llamar a|escribir a|contactar a|contactar con|enviar un correo a)\s+(.+?)(?:\.|$)", TaskType::Communication),
+        (r"(?i)\b(investigar|averiguar|informarme sobre)\s+(.+?)(?:\.|$)", TaskType::Research),
+        (r"(?i)\b(completar|terminar|acabar|trabajar en)\s+(.+?)(?:\.|$)", TaskType::Work),
+        (r"(?i)\b(recuérdame|tengo que|necesito|debo|hay que)\s+(.+?)(?:\.|$)", TaskType::Personal),
+    ],
+    critical: &["urgente", "inmediatamente", "cuanto antes"],
+    high: &["importante", "prioridad", "prioritario"],
+    low: &["sin prisa", "cuando puedas"],
+    today: &["hoy"],
+    tomorrow: &["mañana"],
+    next_week: &["próxima semana", "semana que viene"],
+    weekdays: ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"],
+    noted: "He tomado nota de tu mensaje. ¿En qué más puedo ayudarte?",
+    one_task: "He creado una tarea para ti: '{}'. ¿Necesitas algo más?",
+    many_tasks: "He creado {} tareas a partir de tu mensaje: {}. ¡Avísame si hay que ajustar algo!",
+    ask_due: "¿Para cuándo es '{}'?",
+    ask_meeting_time: "¿Cuándo debería ser '{}'?",
+    ask_attendees: "¿Quién debería asistir a '{}'?",
+};
+
+static FRENCH: Keywords = Keywords {
+    task_patterns: &[
+        (r"(?i)\b(planifier|programmer|organiser|réserver|fixer)\s+(.+?)(?:\.|$)", TaskType::Meeting),
+        (r"(?i)\b(acheter)\s+(.+?)(?:\.|$)", TaskType::Shopping),
+        (r"(?i)\b(appeler|téléphoner à|écrire à|contacter|envoyer un mail à)\s+(.+?)(?:\.|$)", TaskType::Communication),
+        (r"(?i)\b(rechercher|se renseigner sur|étudier)\s+(.+?)(?:\.|$)", TaskType::Research),
+        (r"(?i)\b(terminer|finir|compléter|travailler sur)\s+(.+?)(?:\.|$)", TaskType::Work),
+        (r"(?i)\b(rappelle-moi de|je dois|il faut|j'ai besoin de)\s+(.+?)(?:\.|$)", TaskType::Personal),
+    ],
+    critical: &["urgent", "immédiatement", "dès que possible", "au plus vite"],
+    high: &["important", "prioritaire", "priorité"],
+    low: &["pas pressé", "quand tu peux", "quand vous pouvez"],
+    today: &["aujourd'hui"],
+    tomorrow: &["demain"],
+    next_week: &["semaine prochaine"],
+    weekdays: ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+    noted: "J'ai bien noté votre message. Comment puis-je vous aider ?",
+    one_task: "J'ai créé une tâche pour vous : '{}'. Puis-je vous aider pour autre chose ?",
+    many_tasks: "J'ai créé {} tâches à partir de votre message : {}. Dites-moi s'il faut les ajuster !",
+    ask_due: "Pour quand est '{}' ?",
+    ask_meeting_time: "Quand doit avoir lieu '{}' ?",
+    ask_attendees: "Qui doit participer à '{}' ?",
+};
+
+static GERMAN: Keywords = Keywords {
+    task_patterns: &[
+        (r"(?i)\b(plane|vereinbare|buche|organisiere)\s+(.+?)(?:\.|$)", TaskType::Meeting),
+        (r"(?i)\b(kaufe|kauf|besorge|besorg)\s+(.+?)(?:\.|$)", TaskType::Shopping),
+        (r"(?i)\b(rufe|ruf|schreibe|schreib|kontaktiere)\s+(.+?)(?:\.|$)", TaskType::Communication),
+        (r"(?i)\b(recherchiere|untersuche|finde heraus)\s+(.+?)(?:\.|$)", TaskType::Research),
+        (r"(?i)\b(erledige|beende|arbeite an)\s+(.+?)(?:\.|$)", TaskType::Work),
+        (r"(?i)\b(erinnere mich daran|ich muss|wir müssen|ich sollte)\s+(.+?)(?:\.|$)", TaskType::Personal),
+    ],
+    critical: &["dringend", "sofort", "asap"],
+    high: &["wichtig", "priorität"],
+    low: &["keine eile", "wenn du zeit hast"],
+    today: &["heute"],
+    tomorrow: &["morgen"],
+    next_week: &["nächste woche", "nächsten woche"],
+    weekdays: ["montag", "dienstag", "mittwoch", "donnerstag", "freitag", "samstag", "sonntag"],
+    noted: "Ich habe deine Nachricht notiert. Wie kann ich dir sonst helfen?",
+    one_task: "Ich habe eine Aufgabe für dich erstellt: '{}'. Kann ich sonst noch etwas tun?",
+    many_tasks: "Ich habe {} Aufgaben aus deiner Nachricht erstellt: {}. Sag Bescheid, wenn etwas angepasst werden soll!",
+    ask_due: "Bis wann ist '{}' fällig?",
+    ask_meeting_time: "Wann soll '{}' stattfinden?",
+    ask_attendees: "Wer soll an '{}' teilnehmen?",
+};
+
+static PORTUGUESE: Keywords = Keywords {
+    task_patterns: &[
+        (r"(?i)\b(agendar|marcar|reservar|organizar)\s+(.+?)(?:\.|$)", TaskType::Meeting),
+        (r"(?i)\b(comprar)\s+(.+?)(?:\.|$)", TaskType::Shopping),
+        (r"(?i)\b(ligar para|telefonar para|escrever para|enviar email para|contatar)\s+(.+?)(?:\.|$)", TaskType::Communication),
+        (r"(?i)\b(pesquisar|investigar|descobrir)\s+(.+?)(?:\.|$)", TaskType::Research),
+        (r"(?i)\b(completar|terminar|concluir|trabalhar em)\s+(.+?)(?:\.|$)", TaskType::Work),
+        (r"(?i)\b(lembre-me de|me lembre de|preciso|tenho que|devo)\s+(.+?)(?:\.|$)", TaskType::Personal),
+    ],
+    critical: &["urgente", "imediatamente", "o quanto antes"],
+    high: &["importante", "prioridade"],
+    low: &["sem pressa", "quando puder"],
+    today: &["hoje"],
+    tomorrow: &["amanhã"],
+    next_week: &["próxima semana", "semana que vem"],
+    weekdays: ["segunda", "terça", "quarta", "quinta", "sexta", "sábado", "domingo"],
+    noted: "Anotei sua mensagem. Como mais posso ajudar?",
+    one_task: "Criei uma tarefa para você: '{}'. Precisa de mais alguma coisa?",
+    many_tasks: "Criei {} tarefas a partir da sua mensagem: {}. Avise se algo precisar de ajuste!",
+    ask_due: "Para quando é '{}'?",
+    ask_meeting_time: "Quando deve acontecer '{}'?",
+    ask_attendees: "Quem deve participar de '{}'?",
+};
@@ -0,0 +1,14 @@
+//! The agent's tools and model client, as a library so task extraction
+//! can be benchmarked (see `benches/`).
+
+pub mod agent;
+pub mod examples;
+pub mod language;
+pub mod llm_client;
+pub mod minutes;
+pub mod pii;
+pub mod questions;
+pub mod rules;
+pub mod summary;
+pub mod urgency;
+pub mod usage;
@@ -1,29 +1,136 @@
+use async_trait::async_trait;
+use futures::{stream::BoxStream, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use models::{TaskType, Priority};
-use uuid::Uuid;
+use models::{CustomTaskType, ExtractionExample, FollowUpQuestion, TaskType, Priority};
 use chrono::{DateTime, Utc};
+use common::{config::LlmProvider, reload::LiveConfig, secrets};
 use regex::Regex;
+use std::sync::{Arc, LazyLock};
 use tracing::{info, warn, error};
 
+use crate::agent::{self, AgentRun, AgentStep, AgentTurn, Tool, ToolCall};
+use crate::language::Language;
+use crate::minutes::MinutesDraft;
+use crate::pii::{PiiScrubber, Redaction};
+use crate::rules;
+use crate::summary::CaseActivity;
+use crate::usage::LlmAccount;
+
+/// Default budget for the message part of a prompt, leaving room in the
+/// model's context for the system prompt and the reply.
+const DEFAULT_MAX_INPUT_TOKENS: usize = 6000;
+
+/// Rough size of a token, used to estimate token counts without a
+/// tokenizer.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Summary rounds before an oversized message is truncated instead.
+const MAX_SUMMARY_ROUNDS: usize = 3;
+
+/// Prefix of the ids of calls made by the fallback extractor.
+const FALLBACK_CALL: &str = "fallback-";
+
+/// Added to the system prompt when personal data was redacted.
+const REDACTION_NOTE: &str = "\nValues such as [EMAIL_1] or [NAME_1] stand for personal data kept from you. \
+                              Use them exactly as written wherever the value is needed.\n";
+
+// The fallback extractor handles every message while the LLM is down, so
+// its patterns are compiled once rather than per message.
+static HASHTAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?:^|\s)#([\w-]+)").unwrap());
+static ESTIMATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(\d+(?:\.\d+)?)\s*(minutes?|mins?|hours?|hrs?|h)\b").unwrap());
+static LIST_SEPARATOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i),|\s(?:and|y|et|und|e)\s").unwrap());
+
+/// The model driving the agent loop: [`LLMClient`], or a mock in tests.
+#[async_trait]
+pub trait Llm: Send + Sync {
+    /// `message`, shortened if it would not fit the model's context.
+    async fn condense(&self, message: &str) -> String {
+        message.to_string()
+    }
+
+    /// The next step of `run`. Any text the model writes is streamed as
+    /// tokens first; the last event is always the step.
+    fn next_step(&self, run: &AgentRun) -> BoxStream<'static, LLMStreamEvent>;
+
+    /// A short summary of a case's week for its owner.
+    async fn weekly_summary(&self, activity: &CaseActivity) -> String {
+        activity.report()
+    }
+
+    /// The minutes of a meeting from its transcript. Relative dates in it
+    /// count from `held_at`.
+    async fn minutes(&self, transcript: &str, language: Language, held_at: DateTime<Utc>) -> MinutesDraft {
+        MinutesDraft::fallback(transcript, language, held_at)
+    }
+
+    /// The model as configured for an organization, counting the tokens
+    /// its requests use on `account`. `None` when it isn't configurable.
+    fn for_account(&self, _account: &Arc<LlmAccount>) -> Option<Arc<dyn Llm>> {
+        None
+    }
+}
+
 #[derive(Clone)]
 pub struct LLMClient {
-    api_key: Option<String>,
+    /// The OpenAI key, model and input budget are read on every request,
+    /// so a rotated key or reloaded settings are picked up.
+    live: LiveConfig,
     client: reqwest::Client,
+    /// The organization the requests are made for, which may use its own
+    /// provider and is charged their tokens.
+    account: Option<Arc<LlmAccount>>,
+    /// Redacts what is sent to an external provider, as set by
+    /// `AI_PII_REDACTION`.
+    scrubber: PiiScrubber,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// What the prompt is tailored with for the sender: their few-shot
+/// examples, their organization's task types and the questions still open
+/// on the case.
+#[derive(Debug, Clone, Default)]
+pub struct PromptContext {
+    pub examples: Vec<ExtractionExample>,
+    pub task_types: Vec<CustomTaskType>,
+    /// Questions asked earlier in the case that the message may answer.
+    pub open_questions: Vec<FollowUpQuestion>,
+}
+
+/// Tasks found by the fallback extractor, and its reply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AIResponse {
     pub response: String,
     pub tasks: Vec<TaskData>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskData {
     pub title: String,
     pub description: Option<String>,
     pub task_type: TaskType,
     pub priority: Priority,
     pub due_date: Option<DateTime<Utc>>,
+    /// Suggested labels, e.g. a project or topic named in the message.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Expected effort, when the message says or implies it.
+    #[serde(default)]
+    pub estimate_minutes: Option<i32>,
+    /// How sure the extraction is that this is a real task, from 0 to 1.
+    /// `None` when the LLM didn't say.
+    #[serde(default)]
+    pub confidence: Option<f32>,
+    /// Fields of a custom task type.
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Output of [`Llm::next_step`]: text fragments as they are generated,
+/// followed by the step.
+#[derive(Debug)]
+pub enum LLMStreamEvent {
+    Token(String),
+    Done(AgentStep),
 }
 
 #[derive(Serialize)]
@@ -31,17 +138,61 @@ struct OpenAIRequest {
     model: String,
     messages: Vec<OpenAIMessage>,
     temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<&'static serde_json::Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    /// Asks for the token usage in the last chunk of a stream.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct OpenAIMessage {
     role: String,
-    content: String,
+    content: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OpenAIToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+impl OpenAIMessage {
+    fn new(role: &str, content: impl Into<String>) -> Self {
+        Self { role: role.to_string(), content: Some(content.into()), tool_calls: Vec::new(), tool_call_id: None }
+    }
+
+    /// Characters of the message, for estimating its tokens.
+    fn chars(&self) -> usize {
+        self.content.as_ref().map_or(0, String::len)
+            + self.tool_calls.iter().map(|call| call.function.name.len() + call.function.arguments.len()).sum::<usize>()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenAIToolCall {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    function: OpenAIFunctionCall,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenAIFunctionCall {
+    name: String,
+    arguments: String,
 }
 
 #[derive(Deserialize)]
 struct OpenAIResponse {
     choices: Vec<OpenAIChoice>,
+    #[serde(default)]
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIUsage {
+    total_tokens: i64,
 }
 
 #[derive(Deserialize)]
@@ -49,127 +200,536 @@ struct OpenAIChoice {
     message: OpenAIMessage,
 }
 
+#[derive(Deserialize)]
+struct OpenAIStreamChunk {
+    #[serde(default)]
+    choices: Vec<OpenAIStreamChoice>,
+    #[serde(default)]
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIStreamChoice {
+    delta: OpenAIDelta,
+}
+
+#[derive(Deserialize)]
+struct OpenAIDelta {
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<OpenAIToolCallDelta>,
+}
+
+/// A fragment of a streamed tool call; the id and name come in the first
+/// fragment of a call, the arguments in pieces.
+#[derive(Deserialize)]
+struct OpenAIToolCallDelta {
+    index: usize,
+    id: Option<String>,
+    function: Option<OpenAIFunctionDelta>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIFunctionDelta {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+/// Confidence of a fallback task matched by one of the task patterns.
+const PATTERN_CONFIDENCE: f32 = 0.6;
+
+/// Confidence of the fallback's catch-all task made from the whole message.
+const GENERAL_TASK_CONFIDENCE: f32 = 0.3;
 
+const SYSTEM_PROMPT: &str = r#"
+You are a task management agent. The user's message was filed under the current case. Use the tools to act on it:
+1. Create a task for each actionable request in the message with create_task, classifying it by type (Meeting, Shopping, Work, Personal, Research, Communication, Other) and priority (Low, Medium, High, Critical)
+2. Suggest due dates if mentioned or implied, up to three short lowercase tags (projects, people, topics) and the effort in minutes when it is stated or can be reasonably inferred
+3. For Shopping tasks, list the things to buy in "metadata": {"items": [...]}
+4. Rate your confidence that each task is a real, correctly understood task from 0.0 to 1.0 (use a low value when the message is vague or you are guessing)
+5. Use schedule_meeting for meetings with a known start time
+6. When the user refers to earlier work ("close the case about the invoice", "the report is done"), find it with lookup_history first, then use update_task or close_case
+7. Do not create tasks for things that are already done
+
+When you are finished, reply to the user in a few sentences saying what you did, without calling tools.
+"#;
+
+const SUMMARY_PROMPT: &str = r#"
+You are summarizing one part of a long message so tasks can be extracted from the summary.
+Keep every request, commitment, deadline, date, meeting, person and amount mentioned.
+Leave out greetings, signatures, disclaimers and repeated content.
+Respond with the summary as plain text.
+"#;
+
+const WEEKLY_SUMMARY_PROMPT: &str = r#"
+You are writing the weekly summary of a case for its owner, from the list of its tasks below.
+In three to six sentences, say what progressed, what is new and what is stuck and needs attention.
+Name tasks by their titles and do not invent anything that is not in the list.
+Respond with the summary as plain text.
+"#;
+
+const MINUTES_PROMPT: &str = r#"
+You are writing the minutes of a meeting from its transcript, which may have been summarized in parts.
+Respond with JSON only, in this format:
+{"summary": "three to six sentences", "decisions": ["..."], "action_items": [{"title": "...", "owner": "name or null", "due_date": "ISO 8601 date and time or null"}]}
+An action item is something a participant agreed to do. Start its title with a verb, name its owner as they were named in the meeting, and resolve relative dates against the meeting date.
+Do not invent anything that is not in the transcript.
+"#;
 
 impl LLMClient {
-    pub fn new(api_key: Option<String>) -> Self {
+    pub fn new(live: LiveConfig) -> Self {
+        let scrubber = PiiScrubber::from_config(&live.current().agent);
         Self {
-            api_key,
+            live,
             client: reqwest::Client::new(),
+            account: None,
+            scrubber,
+        }
+    }
+
+    /// The organization the requests are made for, unless the deployment
+    /// is offline and only uses its own model.
+    fn account(&self) -> Option<&LlmAccount> {
+        self.account.as_deref().filter(|_| !self.live.current().llm.provider.is_offline())
+    }
+
+    /// The organization's key, or the service's unless the organization
+    /// uses its own provider; empty for a local model, which needs none.
+    /// `None` when there is no model to use, or once the organization used
+    /// up its budget, so requests fall back to keyword extraction.
+    fn api_key(&self) -> Option<String> {
+        let config = self.live.current();
+        match config.llm.provider {
+            LlmProvider::Rules => return None,
+            LlmProvider::Local => return Some(String::new()),
+            LlmProvider::OpenAi => {}
+        }
+        match self.account() {
+            Some(account) if account.exhausted() => None,
+            Some(account) if account.api_key.is_some() || account.base_url.is_some() => account.api_key.clone(),
+            _ => config.secret_values.get(secrets::OPENAI_API_KEY),
         }
     }
 
-    pub async fn process_message(&self, message: &str, case_id: Uuid) -> Result<AIResponse, Box<dyn std::error::Error + Send + Sync>> {
-        if let Some(api_key) = &self.api_key {
-            self.process_with_openai(message, case_id, api_key).await
+    /// A chat completions request, authorized with `api_key` unless it is
+    /// empty.
+    fn completions_request(&self, api_key: &str) -> reqwest::RequestBuilder {
+        let request = self.client.post(self.completions_url()).header("Content-Type", "application/json");
+        if api_key.is_empty() {
+            request
         } else {
-            warn!("No OpenAI API key available, using fallback extraction");
-            Ok(self.fallback_extraction(message))
+            request.header("Authorization", format!("Bearer {}", api_key))
         }
     }
 
-    async fn process_with_openai(&self, message: &str, case_id: Uuid, api_key: &str) -> Result<AIResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let system_prompt = r#"
-You are an intelligent task extraction agent. Analyze the user's message and:
-1. Extract actionable tasks from the message
-2. Classify each task by type (Meeting, Shopping, Work, Personal, Research, Communication, Other)
-3. Assign priority (Low, Medium, High, Critical)
-4. Suggest due dates if mentioned or implied
-5. Provide a helpful response to the user
+    fn completions_url(&self) -> String {
+        let base_url = match self.account().and_then(|account| account.base_url.clone()) {
+            Some(base_url) => base_url,
+            None => self.live.current().llm.base_url.clone(),
+        };
+        format!("{}/chat/completions", base_url.trim_end_matches('/'))
+    }
 
-Respond in JSON format:
-{
-    "response": "Your helpful response to the user",
-    "tasks": [
-        {
-            "title": "Task title",
-            "description": "Optional description",
-            "task_type": "Work|Meeting|Shopping|Personal|Research|Communication|Other",
-            "priority": "Low|Medium|High|Critical",
-            "due_date": "2024-01-01T10:00:00Z" // optional ISO format
+    /// The OpenAI chat model used for the agent and summaries.
+    fn model(&self) -> String {
+        match self.account().and_then(|account| account.model.clone()) {
+            Some(model) => model,
+            None => self.live.current().llm.model.clone(),
         }
-    ]
-}
-"#;
+    }
 
+    /// Charges the organization for a request: the tokens the provider
+    /// reported, or an estimate from the characters sent and received.
+    fn count_tokens(&self, reported: Option<OpenAIUsage>, chars: usize) {
+        if let Some(account) = self.account() {
+            account.record(reported.map_or((chars / CHARS_PER_TOKEN) as i64, |usage| usage.total_tokens));
+        }
+    }
+
+    /// Redacts the personal data in `messages`, unless the model is local
+    /// and nothing leaves the deployment.
+    fn redact(&self, messages: &mut [OpenAIMessage]) -> Redaction {
+        if self.live.current().llm.provider != LlmProvider::OpenAi {
+            return Redaction::default();
+        }
+        let texts = messages
+            .iter_mut()
+            .flat_map(|message| message.content.iter_mut().chain(message.tool_calls.iter_mut().map(|call| &mut call.function.arguments)))
+            .collect();
+        let redaction = self.scrubber.redact_all(texts);
+        if !redaction.is_empty() {
+            if let Some(system) = messages.first_mut().and_then(|message| message.content.as_mut()) {
+                system.push_str(REDACTION_NOTE);
+            }
+            info!("Redacted {} personal values before calling the LLM", redaction.len());
+        }
+        redaction
+    }
+
+    /// The token budget for a message.  Longer messages are summarized
+    /// chunk by chunk before the agent sees them.
+    fn max_input_tokens(&self) -> usize {
+        self.live.current().llm.max_input_tokens.unwrap_or(DEFAULT_MAX_INPUT_TOKENS).max(1)
+    }
+
+    /// Asks the model for the next step of `run`, streaming what it
+    /// writes. Without a key, or when the request fails, the fallback
+    /// extractor takes the step instead.
+    pub fn next_step(&self, run: &AgentRun) -> impl Stream<Item = LLMStreamEvent> + Send + 'static {
+        let this = self.clone();
+        let run = run.clone();
+
+        async_stream::stream! {
+            let Some(api_key) = this.api_key() else {
+                match this.account() {
+                    Some(account) if account.exhausted() => {
+                        warn!("Organization {} used up its LLM token budget, using fallback extraction", account.organization)
+                    }
+                    _ if this.live.current().llm.provider == LlmProvider::Rules => info!("Using rule-based extraction"),
+                    _ => warn!("No OpenAI API key available, using fallback extraction"),
+                }
+                for event in this.fallback_step(&run) {
+                    yield event;
+                }
+                return;
+            };
+
+            let mut request = this.openai_request(&run);
+            let redaction = this.redact(&mut request.messages);
+            let request_chars: usize = request.messages.iter().map(OpenAIMessage::chars).sum();
+            let response = this
+                .completions_request(&api_key)
+                .json(&request)
+                .send()
+                .await;
+
+            let response = match response {
+                Ok(response) if response.status().is_success() => response,
+                Ok(response) => {
+                    error!("OpenAI API error: {}", response.status());
+                    for event in this.fallback_step(&run) {
+                        yield event;
+                    }
+                    return;
+                }
+                Err(e) => {
+                    error!("OpenAI request failed: {}", e);
+                    for event in this.fallback_step(&run) {
+                        yield event;
+                    }
+                    return;
+                }
+            };
+
+            let mut body = response.bytes_stream();
+            let mut buffer = String::new();
+            let mut content = String::new();
+            let mut calls: Vec<ToolCall> = Vec::new();
+            let mut usage = None;
+            let mut restorer = redaction.restorer();
+
+            'read: while let Some(chunk) = body.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        warn!("OpenAI stream interrupted: {}", e);
+                        break;
+                    }
+                };
+                buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+                while let Some(newline) = buffer.find('\n') {
+                    let line = buffer[..newline].trim().to_string();
+                    buffer.drain(..=newline);
+
+                    let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                        continue;
+                    };
+                    if data == "[DONE]" {
+                        break 'read;
+                    }
+
+                    let Ok(parsed) = serde_json::from_str::<OpenAIStreamChunk>(data) else {
+                        continue;
+                    };
+                    if parsed.usage.is_some() {
+                        usage = parsed.usage;
+                    }
+                    let Some(delta) = parsed.choices.into_iter().next().map(|choice| choice.delta) else {
+                        continue;
+                    };
+                    for fragment in delta.tool_calls {
+                        if calls.len() <= fragment.index {
+                            calls.resize_with(fragment.index + 1, ToolCall::default);
+                        }
+                        let call = &mut calls[fragment.index];
+                        if let Some(id) = fragment.id {
+                            call.id = id;
+                        }
+                        if let Some(function) = fragment.function {
+                            call.name.push_str(function.name.as_deref().unwrap_or_default());
+                            call.arguments.push_str(function.arguments.as_deref().unwrap_or_default());
+                        }
+                    }
+                    if let Some(text) = delta.content.filter(|text| !text.is_empty()) {
+                        content.push_str(&text);
+                        let text = restorer.push(&text);
+                        if !text.is_empty() {
+                            yield LLMStreamEvent::Token(text);
+                        }
+                    }
+                }
+            }
+            let rest = restorer.finish();
+            if !rest.is_empty() {
+                yield LLMStreamEvent::Token(rest);
+            }
+
+            calls.retain(|call| !call.name.is_empty());
+            let response_chars = content.len() + calls.iter().map(|call| call.name.len() + call.arguments.len()).sum::<usize>();
+            this.count_tokens(usage, request_chars + response_chars);
+            info!("OpenAI step with {} tool calls", calls.len());
+            let content = redaction.restore(&content);
+            for call in &mut calls {
+                call.arguments = redaction.restore_json(&call.arguments);
+            }
+            yield LLMStreamEvent::Done(if calls.is_empty() {
+                AgentStep::Reply(content)
+            } else {
+                AgentStep::Calls { text: content, calls }
+            });
+        }
+    }
+
+    /// The step the fallback extractor takes: create the tasks it finds,
+    /// then reply with what it created.
+    fn fallback_step(&self, run: &AgentRun) -> Vec<LLMStreamEvent> {
+        if run.turns.is_empty() {
+            let extraction = self.fallback_extraction(&run.message, run.language);
+            if !extraction.tasks.is_empty() {
+                let calls = extraction
+                    .tasks
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, task)| ToolCall::new(format!("{}{}", FALLBACK_CALL, index), &Tool::CreateTask(task)))
+                    .collect();
+                return vec![LLMStreamEvent::Done(AgentStep::Calls { text: String::new(), calls })];
+            }
+        }
+
+        // Only the fallback's own calls are described; when the model
+        // failed part way, its calls were already reported as actions.
+        let created: Vec<TaskData> = run
+            .calls()
+            .filter(|call| call.id.starts_with(FALLBACK_CALL))
+            .filter_map(|call| match call.tool() {
+                Ok(Tool::CreateTask(task)) => Some(task),
+                _ => None,
+            })
+            .collect();
+        let reply = self.generate_response(&created, run.language);
+        vec![LLMStreamEvent::Token(reply.clone()), LLMStreamEvent::Done(AgentStep::Reply(reply))]
+    }
+
+    /// Returns `message` as is when it fits the token budget.  Otherwise it
+    /// is split into chunks that do, each chunk is summarized and the
+    /// summaries are joined, repeating while the result is still too long.
+    /// Chunks that fail to summarize are kept as they are; if the budget
+    /// still isn't met the text is truncated.
+    async fn condense_with(&self, message: &str, api_key: &str) -> String {
+        let max_input_tokens = self.max_input_tokens();
+        let budget = max_input_tokens * CHARS_PER_TOKEN;
+        let mut text = message.to_string();
+
+        for round in 1..=MAX_SUMMARY_ROUNDS {
+            if text.len() <= budget {
+                return text;
+            }
+            let chunks = split_into_chunks(&text, budget);
+            info!(
+                "Message of about {} tokens exceeds the budget of {}, summarizing {} chunks (round {})",
+                text.len() / CHARS_PER_TOKEN,
+                max_input_tokens,
+                chunks.len(),
+                round
+            );
+
+            let mut summaries = Vec::with_capacity(chunks.len());
+            for (index, chunk) in chunks.iter().enumerate() {
+                match self.summarize(chunk, index + 1, chunks.len(), api_key).await {
+                    Ok(summary) => summaries.push(summary),
+                    Err(e) => {
+                        warn!("Failed to summarize chunk {} of {}: {}", index + 1, chunks.len(), e);
+                        summaries.push(chunk.clone());
+                    }
+                }
+            }
+            text = summaries.join("\n\n");
+        }
+
+        if text.len() > budget {
+            warn!("Summary still exceeds the token budget, truncating");
+            text.truncate(floor_char_boundary(&text, budget));
+        }
+        text
+    }
+
+    async fn summarize(&self, chunk: &str, part: usize, parts: usize, api_key: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        self.complete(SUMMARY_PROMPT, format!("Part {} of {}:\n{}", part, parts, chunk), api_key).await
+    }
+
+    /// The model's answer to a single prompt, without tools.
+    async fn complete(&self, system: &str, user: String, api_key: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let mut messages = vec![OpenAIMessage::new("system", system), OpenAIMessage::new("user", user)];
+        let redaction = self.redact(&mut messages);
         let request = OpenAIRequest {
-            model: "gpt-3.5-turbo".to_string(),
-            messages: vec![
-                OpenAIMessage {
-                    role: "system".to_string(),
-                    content: system_prompt.to_string(),
-                },
-                OpenAIMessage {
-                    role: "user".to_string(),
-                    content: format!("Case ID: {}\nMessage: {}", case_id, message),
-                },
-            ],
-            temperature: 0.7,
+            model: self.model(),
+            messages,
+            temperature: 0.2,
+            tools: None,
+            stream: false,
+            stream_options: None,
         };
+        let request_chars: usize = request.messages.iter().map(OpenAIMessage::chars).sum();
 
-        let response = self.client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
+        let response = self
+            .completions_request(api_key)
             .json(&request)
             .send()
-            .await?;
-
-        if !response.status().is_success() {
-            error!("OpenAI API error: {}", response.status());
-            return Ok(self.fallback_extraction(message));
-        }
+            .await?
+            .error_for_status()?;
 
         let openai_response: OpenAIResponse = response.json().await?;
-        
-        if let Some(choice) = openai_response.choices.first() {
-            match serde_json::from_str::<AIResponse>(&choice.message.content) {
-                Ok(ai_response) => {
-                    info!("Successfully parsed OpenAI response");
-                    Ok(ai_response)
-                }
-                Err(e) => {
-                    warn!("Failed to parse OpenAI response: {}, using fallback", e);
-                    Ok(self.fallback_extraction(message))
+        let answer = openai_response.choices.into_iter().next().and_then(|choice| choice.message.content);
+        self.count_tokens(openai_response.usage, request_chars + answer.as_ref().map_or(0, String::len));
+        answer.map(|answer| redaction.restore(&answer)).ok_or_else(|| "No choices in OpenAI response".into())
+    }
+
+    fn openai_request(&self, run: &AgentRun) -> OpenAIRequest {
+        let context = &run.context;
+        let mut system_prompt = SYSTEM_PROMPT.to_string();
+        if !context.task_types.is_empty() {
+            system_prompt.push_str(
+                "\nBesides the types above, this organization uses the task types below. \
+                 For a task of one of them, set \"task_type\" to {\"Other\": \"<name>\"} \
+                 and put its fields in a \"metadata\" object.\n",
+            );
+            for task_type in &context.task_types {
+                system_prompt.push_str(&format!(
+                    "- {}{}\n",
+                    task_type.name,
+                    task_type.description.as_deref().map(|d| format!(": {}", d)).unwrap_or_default()
+                ));
+                for field in &task_type.fields {
+                    system_prompt.push_str(&format!(
+                        "  - {} ({:?}{}){}\n",
+                        field.name,
+                        field.kind,
+                        if field.required { ", required" } else { "" },
+                        field.description.as_deref().map(|d| format!(": {}", d)).unwrap_or_default()
+                    ));
                 }
             }
-        } else {
-            warn!("No choices in OpenAI response, using fallback");
-            Ok(self.fallback_extraction(message))
         }
-    }
+        if !context.examples.is_empty() {
+            system_prompt.push_str("\nExamples of messages from this user and the tasks to create for them:\n");
+            for example in &context.examples {
+                system_prompt.push_str(&format!(
+                    "\nMessage: {}\nTasks: {}\n",
+                    example.message,
+                    serde_json::Value::Array(example.expected_tasks.clone())
+                ));
+            }
+        }
 
-    fn fallback_extraction(&self, message: &str) -> AIResponse {
-        let mut tasks = Vec::new();
-        let message_lower = message.to_lowercase();
+        if !context.open_questions.is_empty() {
+            system_prompt.push_str(
+                "\nEarlier in this case you asked the questions below. If the message answers \
+                 one, fill the task in with update_task instead of creating a new one.\n",
+            );
+            for question in &context.open_questions {
+                system_prompt.push_str(&format!("- Task {}: {}\n", question.task_id, question.question));
+            }
+        }
 
-        // Simple keyword-based task extraction
-        let task_patterns = vec![
-            (r"(?i)\b(schedule|book|arrange)\s+(.+?)(?:\s+(?:for|on|at)\s+(.+?))?(?:\.|$)", TaskType::Meeting),
-            (r"(?i)\b(buy|purchase|get|shop for)\s+(.+?)(?:\.|$)", TaskType::Shopping),
-            (r"(?i)\b(call|email|contact|reach out to)\s+(.+?)(?:\.|$)", TaskType::Communication),
-            (r"(?i)\b(research|look into|investigate|find out about)\s+(.+?)(?:\.|$)", TaskType::Research),
-            (r"(?i)\b(complete|finish|work on|do)\s+(.+?)(?:\.|$)", TaskType::Work),
-            (r"(?i)\b(remind me to|need to|have to|must)\s+(.+?)(?:\.|$)", TaskType::Personal),
+        let mut messages = vec![
+            OpenAIMessage::new("system", system_prompt),
+            OpenAIMessage::new(
+                "user",
+                format!(
+                    "Case ID: {}\nToday: {}\nLanguage: {} (write the reply and task titles in this language)\nMessage: {}",
+                    run.case_id,
+                    Utc::now().format("%A %Y-%m-%d %H:%M UTC"),
+                    run.language.name(),
+                    run.message
+                ),
+            ),
         ];
+        for turn in &run.turns {
+            messages.push(match turn {
+                AgentTurn::Calls { text, calls } => OpenAIMessage {
+                    role: "assistant".to_string(),
+                    content: Some(text.clone()).filter(|text| !text.is_empty()),
+                    tool_calls: calls
+                        .iter()
+                        .map(|call| OpenAIToolCall {
+                            id: call.id.clone(),
+                            kind: "function".to_string(),
+                            function: OpenAIFunctionCall { name: call.name.clone(), arguments: call.arguments.clone() },
+                        })
+                        .collect(),
+                    tool_call_id: None,
+                },
+                AgentTurn::Result { call_id, content } => OpenAIMessage {
+                    tool_call_id: Some(call_id.clone()),
+                    ..OpenAIMessage::new("tool", content.clone())
+                },
+            });
+        }
 
-        for (pattern, task_type) in task_patterns {
-            let re = Regex::new(pattern).unwrap();
-            for cap in re.captures_iter(message) {
-                if let Some(task_desc) = cap.get(2) {
-                    let title = task_desc.as_str().trim().to_string();
-                    if !title.is_empty() && title.len() > 2 {
-                        let priority = self.determine_priority(&message_lower);
-                        
-                        tasks.push(TaskData {
-                            title: self.clean_task_title(&title),
-                            description: Some(message.to_string()),
-                            task_type: task_type.clone(),
-                            priority,
-                            due_date: self.extract_due_date(message),
-                        });
-                    }
+        OpenAIRequest {
+            model: self.model(),
+            messages,
+            temperature: 0.7,
+            tools: Some(agent::definitions()),
+            stream: true,
+            stream_options: Some(serde_json::json!({ "include_usage": true })),
+        }
+    }
+
+    /// Extracts tasks with keyword patterns, for when the LLM can't be used.
+    /// Each task takes the priority, due date and estimate of its sentence,
+    /// or else of the sentences without tasks ("It's urgent."); see
+    /// [`rules`].
+    pub fn fallback_extraction(&self, message: &str, language: Language) -> AIResponse {
+        let mut tasks: Vec<TaskData> = Vec::new();
+        let now = Utc::now();
+        let sentences: Vec<(&str, Vec<rules::Found>)> =
+            rules::sentences(message).into_iter().map(|sentence| (sentence, rules::tasks(sentence, language))).collect();
+        let context = sentences.iter().filter(|(_, found)| found.is_empty()).map(|(sentence, _)| *sentence).collect::<Vec<_>>().join(". ");
+        let message_priority = self.determine_priority(&context.to_lowercase(), language);
+        let message_due = rules::due_date(&context, language, now);
+        let message_estimate = self.extract_estimate(&context);
+
+        for (sentence, found_tasks) in sentences {
+            let due = rules::due_date(sentence, language, now);
+            for found in found_tasks {
+                let title = self.clean_task_title(&rules::without_due(&found.title, due.as_ref()));
+                if title.len() <= 2 || tasks.iter().any(|task| task.title.eq_ignore_ascii_case(&title)) {
+                    continue;
                 }
+                tasks.push(TaskData {
+                    description: Some(message.to_string()),
+                    priority: self.determine_priority(&sentence.to_lowercase(), language).or(message_priority.clone()).unwrap_or(Priority::Medium),
+                    due_date: due.as_ref().or(message_due.as_ref()).map(|due| due.at),
+                    tags: self.extract_hashtags(message),
+                    estimate_minutes: self.extract_estimate(sentence).or(message_estimate),
+                    confidence: Some(PATTERN_CONFIDENCE),
+                    metadata: (found.task_type == TaskType::Shopping)
+                        .then(|| serde_json::json!({ "items": self.shopping_items(&title) })),
+                    task_type: found.task_type,
+                    title,
+                });
             }
         }
 
@@ -179,71 +739,220 @@ Respond in JSON format:
                 title: self.extract_general_task_title(message),
                 description: Some(message.to_string()),
                 task_type: TaskType::Personal,
-                priority: Priority::Medium,
-                due_date: None,
+                priority: message_priority.unwrap_or(Priority::Medium),
+                due_date: message_due.map(|due| due.at),
+                tags: self.extract_hashtags(message),
+                estimate_minutes: self.extract_estimate(message),
+                confidence: Some(GENERAL_TASK_CONFIDENCE),
+                metadata: None,
             });
         }
 
         AIResponse {
-            response: self.generate_response(&tasks, message),
+            response: self.generate_response(&tasks, language),
             tasks,
         }
     }
 
-    fn determine_priority(&self, message: &str) -> Priority {
-        if message.contains("urgent") || message.contains("asap") || message.contains("immediately") {
-            Priority::Critical
-        } else if message.contains("important") || message.contains("priority") {
-            Priority::High
-        } else if message.contains("when you can") || message.contains("no rush") {
-            Priority::Low
-        } else {
-            Priority::Medium
-        }
+    /// `#hashtags` in the message, suggested as tags without an LLM.
+    fn extract_hashtags(&self, message: &str) -> Vec<String> {
+        let mut tags: Vec<String> = HASHTAG
+            .captures_iter(message)
+            .map(|cap| cap[1].to_lowercase())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
     }
 
-    fn extract_due_date(&self, message: &str) -> Option<DateTime<Utc>> {
-        // Simple date extraction - in a real implementation, use a proper date parsing library
-        let today = Utc::now();
-        
-        if message.to_lowercase().contains("today") {
-            Some(today)
-        } else if message.to_lowercase().contains("tomorrow") {
-            Some(today + chrono::Duration::days(1))
-        } else if message.to_lowercase().contains("next week") {
-            Some(today + chrono::Duration::weeks(1))
+    /// Durations such as `30 min` or `1.5 hours` in the message, in minutes.
+    fn extract_estimate(&self, message: &str) -> Option<i32> {
+        let cap = ESTIMATE.captures(message)?;
+        let amount: f64 = cap[1].parse().ok()?;
+        let minutes = if cap[2].to_lowercase().starts_with('m') { amount } else { amount * 60.0 };
+        Some(minutes.round() as i32)
+    }
+
+    /// The priority `message` (lowercase) asks for, if it asks for one.
+    fn determine_priority(&self, message: &str, language: Language) -> Option<Priority> {
+        let keywords = language.keywords();
+        let mentions = |words: &[&str]| words.iter().any(|word| message.contains(word));
+        if mentions(keywords.critical) {
+            Some(Priority::Critical)
+        } else if mentions(keywords.high) {
+            Some(Priority::High)
+        } else if mentions(keywords.low) {
+            Some(Priority::Low)
         } else {
             None
         }
     }
 
     fn clean_task_title(&self, title: &str) -> String {
-        title.trim_end_matches(&['.', ',', ';', ':', '!', '?'])
+        title.trim_end_matches(['.', ',', ';', ':', '!', '?'])
             .trim()
             .to_string()
     }
 
+    /// Items of a shopping list like "milk, eggs and bread".
+    fn shopping_items(&self, list: &str) -> Vec<String> {
+        LIST_SEPARATOR
+            .split(&self.clean_task_title(list))
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
     fn extract_general_task_title(&self, message: &str) -> String {
         let words: Vec<&str> = message.split_whitespace().take(8).collect();
         let title = words.join(" ");
         
-        if title.len() > 60 {
-            format!("{}...", &title[..57])
+        if title.chars().count() > 60 {
+            format!("{}...", title.chars().take(57).collect::<String>())
         } else {
             title
         }
     }
 
-    fn generate_response(&self, tasks: &[TaskData], original_message: &str) -> String {
+    fn generate_response(&self, tasks: &[TaskData], language: Language) -> String {
+        let keywords = language.keywords();
         if tasks.is_empty() {
-            "I've noted your message. How can I help you further?".to_string()
+            keywords.noted.to_string()
         } else if tasks.len() == 1 {
-            format!("I've created a task for you: '{}'. Is there anything else you need help with?", tasks[0].title)
+            keywords.one_task.replacen("{}", &tasks[0].title, 1)
+        } else {
+            let titles = tasks.iter().map(|t| t.title.as_str()).collect::<Vec<_>>().join(", ");
+            keywords
+                .many_tasks
+                .replacen("{}", &tasks.len().to_string(), 1)
+                .replacen("{}", &titles, 1)
+        }
+    }
+}
+
+
+#[async_trait]
+impl Llm for LLMClient {
+    async fn condense(&self, message: &str) -> String {
+        match self.api_key() {
+            Some(api_key) => self.condense_with(message, &api_key).await,
+            None => message.to_string(),
+        }
+    }
+
+    fn next_step(&self, run: &AgentRun) -> BoxStream<'static, LLMStreamEvent> {
+        LLMClient::next_step(self, run).boxed()
+    }
+
+    fn for_account(&self, account: &Arc<LlmAccount>) -> Option<Arc<dyn Llm>> {
+        if self.live.current().llm.provider.is_offline() {
+            return None;
+        }
+        Some(Arc::new(LLMClient { account: Some(account.clone()), ..self.clone() }))
+    }
+
+    /// The model's summary; the activity report itself without a key or
+    /// when the request fails.
+    async fn weekly_summary(&self, activity: &CaseActivity) -> String {
+        let report = activity.report();
+        let Some(api_key) = self.api_key() else {
+            return report;
+        };
+        match self.complete(WEEKLY_SUMMARY_PROMPT, report.clone(), &api_key).await {
+            Ok(summary) => summary,
+            Err(e) => {
+                warn!("Failed to summarize case {}: {}", activity.case.id, e);
+                report
+            }
+        }
+    }
+
+    /// Condenses the transcript the way long messages are, then asks the
+    /// model for the minutes. Without a key, or when the request fails or
+    /// the answer isn't valid, the minutes are picked out by the fallback.
+    async fn minutes(&self, transcript: &str, language: Language, held_at: DateTime<Utc>) -> MinutesDraft {
+        let Some(api_key) = self.api_key() else {
+            return MinutesDraft::fallback(transcript, language, held_at);
+        };
+        let condensed = self.condense_with(transcript, &api_key).await;
+        let prompt = format!("Meeting date: {}\n\n{}", held_at.to_rfc3339(), condensed);
+        match self.complete(MINUTES_PROMPT, prompt, &api_key).await.map(|answer| MinutesDraft::parse(&answer)) {
+            Ok(Ok(minutes)) => minutes,
+            Ok(Err(e)) => {
+                warn!("Model's minutes are not valid JSON: {}", e);
+                MinutesDraft::fallback(transcript, language, held_at)
+            }
+            Err(e) => {
+                warn!("Failed to write minutes: {}", e);
+                MinutesDraft::fallback(transcript, language, held_at)
+            }
+        }
+    }
+}
+
+/// Splits `text` into chunks of at most `max_len` bytes, preferring to
+/// break between paragraphs, then between lines.
+fn split_into_chunks(text: &str, max_len: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for paragraph in text.split("\n\n") {
+        let pieces = if paragraph.len() <= max_len {
+            vec![paragraph.to_string()]
         } else {
-            format!("I've created {} tasks based on your message. They include: {}. Let me know if you need any adjustments!", 
-                tasks.len(),
-                tasks.iter().map(|t| t.title.as_str()).collect::<Vec<_>>().join(", ")
-            )
+            split_long(paragraph, max_len)
+        };
+        for piece in pieces {
+            if !current.is_empty() && current.len() + 2 + piece.len() > max_len {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str("\n\n");
+            }
+            current.push_str(&piece);
         }
     }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Splits a paragraph longer than `max_len` at line breaks, or anywhere
+/// when a single line is too long.
+fn split_long(paragraph: &str, max_len: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for line in paragraph.lines() {
+        let mut line = line;
+        while line.len() > max_len {
+            let end = floor_char_boundary(line, max_len);
+            if !current.is_empty() {
+                pieces.push(std::mem::take(&mut current));
+            }
+            pieces.push(line[..end].to_string());
+            line = &line[end..];
+        }
+        if !current.is_empty() && current.len() + 1 + line.len() > max_len {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// Largest char boundary in `text` not after `index`.
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
 }
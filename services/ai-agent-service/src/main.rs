@@ -1,55 +1,115 @@
 use axum::{
-    extract::State,
-    response::Json,
-    routing::{get, post},
+    extract::{Path, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Json,
+    },
+    routing::{delete, get, post},
     Router,
 };
 use chrono::Utc;
-use common::{config::ServiceConfig, http_client::HttpClient, HealthResponse, ServiceResult};
+use common::{
+    auth::{ServiceState, SessionToken}, config::ServiceConfig, jobs::{HttpJobQueue, Worker}, logging, reload::LiveConfig,
+    http_client::HttpClient, HealthResponse, ServiceResult,
+};
+use futures::{Stream, StreamExt};
 use models::{
-    ConversationEntry, MessageRequest, MessageResponse, MessageSender, 
-    CreateTaskRequest, CreateCaseRequest, Priority, Case, Task
+    AgentStreamEvent, ConversationEntry, MessageRequest, MessageResponse, MessageSender, 
+    CreateTaskRequest, CreateCaseRequest, Priority, Case, CaseStatus, Task, TaskStatus, TaskType, EmailThread,
+    ExtractionExample, ExtractionExampleRequest, CustomTaskType, UpdateCaseRequest, UpdateTaskRequest,
+    FollowUpQuestion, QuestionStatus, AnswerQuestionsRequest, SendEmailRequest, UserProfile,
+    TranscriptRequest, MeetingMinutes, ActionItem, InviteRequest, MeetingInvite,
 };
-use std::sync::Arc;
+use serde_json::{json, Value};
+use std::{convert::Infallible, sync::Arc};
 use tower::ServiceBuilder;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 use uuid::Uuid;
 
-mod llm_client;
-use llm_client::LLMClient;
+use agent::{AgentRun, AgentStep, AgentTurn, CloseCaseArgs, LookupArgs, Tool, ToolCall, UpdateTaskArgs};
+use ai_agent_service::{agent, examples, language, llm_client, questions, summary::CaseActivity, urgency::UrgencyScorer, usage};
+use language::Language;
+use llm_client::{LLMClient, LLMStreamEvent, Llm, PromptContext, TaskData};
 
 #[derive(Clone)]
 struct AppState {
     config: ServiceConfig,
     http_client: HttpClient,
-    llm_client: LLMClient,
+    llm_client: Arc<dyn Llm>,
+    /// Extracted tasks less confident than this go to the review queue.
+    review_threshold: f32,
+    /// Few-shot examples added to the extraction prompt.
+    max_examples: usize,
+    /// Steps of the agent loop before it has to stop calling tools.
+    max_steps: usize,
+    /// Scores new cases for the priority inbox.
+    urgency: UrgencyScorer,
+}
+
+impl ServiceState for AppState {
+    fn config(&self) -> &ServiceConfig {
+        &self.config
+    }
 }
 
+const DEFAULT_REVIEW_CONFIDENCE_THRESHOLD: f32 = 0.5;
+
+/// Tag of the case newsletters and notifications are filed in for review.
+const NOISE_REVIEW_TAG: &str = "email-review";
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
     
-    let config = ServiceConfig::from_env("ai-agent-service", 8004);
+    let config = ServiceConfig::load("ai-agent-service", 8004).await?;
     
-    tracing_subscriber::fmt()
-        .with_env_filter(&config.log_level)
-        .init();
+    let live = LiveConfig::init(&config);
+    if config.llm.provider.is_offline() {
+        info!("Offline extraction with the {:?} provider; message content is not sent to external APIs", config.llm.provider);
+    }
+
+    config.start_refresh();
 
-    let state = AppState {
+    let state = Arc::new(AppState {
         config: config.clone(),
-        http_client: HttpClient::new(),
-        llm_client: LLMClient::new(config.openai_api_key.clone()),
-    };
+        http_client: HttpClient::for_services(&config),
+        llm_client: Arc::new(LLMClient::new(live.clone())),
+        review_threshold: config.agent.review_confidence_threshold.unwrap_or(DEFAULT_REVIEW_CONFIDENCE_THRESHOLD),
+        max_examples: config.agent.max_few_shot_examples.unwrap_or(examples::DEFAULT_MAX_EXAMPLES),
+        max_steps: config.agent.max_steps.unwrap_or(agent::DEFAULT_MAX_STEPS),
+        urgency: UrgencyScorer::from_config(&config.agent),
+    });
+
+    if config.agent.weekly_summary_enabled {
+        let every = config.agent.weekly_summary_interval_secs;
+        let summary_state = state.clone();
+        Worker::new(Arc::new(HttpJobQueue::new(config.clone())), &config.service_name, &config.jobs)
+            .recurring("cases.weekly_summary", std::time::Duration::from_secs(every), move |_| {
+                let state = summary_state.clone();
+                async move {
+                    send_weekly_summaries(&state, chrono::Duration::seconds(every as i64)).await?;
+                    Ok(())
+                }
+            })
+            .start();
+    }
 
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/api/v1/process", post(process_message))
-        .with_state(Arc::new(state))
+        .route("/api/v1/process/stream", post(process_message_stream))
+        .route("/api/v1/transcripts", post(process_transcript))
+        .route("/api/v1/invites", post(process_invite))
+        .route("/api/v1/noise", post(process_noise))
+        .route("/api/v1/examples", get(list_examples).post(create_example))
+        .route("/api/v1/examples/:id", delete(delete_example))
+        .with_state(state)
+        .merge(live.router())
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
+                .layer(logging::request_layers(&config.service_name))
+                .layer(config.cors.layer()),
         );
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port)).await?;
@@ -64,122 +124,1144 @@ async fn health_check() -> Json<HealthResponse> {
     Json(HealthResponse::new("ai-agent-service"))
 }
 
-#[instrument(skip(state))]
+/// Summarizes the last `period` of every open case with something to
+/// report, run by the `cases.weekly_summary` job. Each summary is added to
+/// the case's conversation and emailed to owners who opted in.
+async fn send_weekly_summaries(state: &AppState, period: chrono::Duration) -> ServiceResult<()> {
+    let persistence = state.config.service_url("persistence");
+    // Internal calls without a session, so cases of every user are returned.
+    let cases = state
+        .http_client
+        .get::<Vec<Case>>(&format!("{}/api/v1/cases", persistence))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    let until = Utc::now();
+    let mut summarized = 0;
+    for case in cases {
+        if !matches!(case.status, CaseStatus::Open | CaseStatus::InProgress | CaseStatus::Waiting) {
+            continue;
+        }
+        let case_id = case.id;
+        match send_weekly_summary(state, &persistence, case, until - period, until).await {
+            Ok(true) => summarized += 1,
+            Ok(false) => {}
+            Err(e) => warn!("Failed to summarize case {}: {}", case_id, e),
+        }
+    }
+    if summarized > 0 {
+        info!("Sent weekly summaries of {} cases", summarized);
+    }
+    Ok(())
+}
+
+/// Returns whether the case had anything to summarize.
+async fn send_weekly_summary(
+    state: &AppState,
+    persistence: &str,
+    case: Case,
+    since: chrono::DateTime<Utc>,
+    until: chrono::DateTime<Utc>,
+) -> ServiceResult<bool> {
+    let client = &state.http_client;
+    let tasks = client
+        .get::<Vec<Task>>(&format!("{}/api/v1/cases/{}/tasks", persistence, case.id))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    let activity = CaseActivity::new(case, tasks, since, until);
+    if activity.is_quiet() {
+        return Ok(false);
+    }
+
+    let (llm, account) = llm_for(state, &SessionToken(None), Some(activity.case.user_id)).await;
+    let summary = llm.weekly_summary(&activity).await;
+    settle_usage(state, account).await;
+    let case = &activity.case;
+    let entry = ConversationEntry {
+        id: Uuid::new_v4(),
+        user_id: Uuid::nil(), // Assigned by persistence from the case owner
+        case_id: case.id,
+        message: summary.clone(),
+        sender: MessageSender::System,
+        timestamp: until,
+        metadata: json!({ "event": "weekly_summary", "since": since }),
+    };
+    client
+        .post::<ConversationEntry, ConversationEntry>(&format!("{}/api/v1/cases/{}/history", persistence, case.id), &entry)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    // The summary is recorded either way; the email is a courtesy.
+    let owner = match client.get::<UserProfile>(&format!("{}/api/v1/cases/{}/owner", persistence, case.id)).await {
+        Ok(owner) => owner,
+        Err(e) => {
+            warn!("Could not look up the owner of case {}: {}", case.id, e);
+            return Ok(true);
+        }
+    };
+    if owner.preferences.notifications.weekly_summary_email {
+        let email = SendEmailRequest {
+            to: owner.email,
+            subject: format!("Weekly summary: {}", case.title),
+            body: summary,
+            user_id: Some(owner.id),
+        };
+        let url = format!("{}/api/v1/email/send", state.config.service_url("email-collector"));
+        // The collector answers 202 without a body.
+        if let Err(e) = client.post_stream(&url, &email).await {
+            warn!("Could not email the weekly summary of case {}: {}", case.id, e);
+        }
+    }
+    Ok(true)
+}
+
+/// The caller's few-shot examples (proxied to persistence).
+#[instrument(skip(state, session))]
+async fn list_examples(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+) -> ServiceResult<Json<Vec<ExtractionExample>>> {
+    info!("Listing extraction examples");
+
+    let url = format!("{}/api/v1/extraction-examples", state.config.service_url("persistence"));
+    let examples = session
+        .client(&state.http_client)
+        .get::<Vec<ExtractionExample>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(examples))
+}
+
+#[instrument(skip(state, session, request))]
+async fn create_example(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<ExtractionExampleRequest>,
+) -> ServiceResult<Json<ExtractionExample>> {
+    info!("Creating extraction example with {} tasks", request.expected_tasks.len());
+
+    let url = format!("{}/api/v1/extraction-examples", state.config.service_url("persistence"));
+    let example = session
+        .client(&state.http_client)
+        .post::<ExtractionExampleRequest, ExtractionExample>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(example))
+}
+
+#[instrument(skip(state, session))]
+async fn delete_example(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    session: SessionToken,
+) -> ServiceResult<StatusCode> {
+    info!("Deleting extraction example: {}", id);
+
+    let url = format!("{}/api/v1/extraction-examples/{}", state.config.service_url("persistence"), id);
+    session
+        .client(&state.http_client)
+        .delete(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip(state, session))]
 async fn process_message(
     State(state): State<Arc<AppState>>,
+    session: SessionToken,
     Json(request): Json<MessageRequest>,
 ) -> ServiceResult<Json<MessageResponse>> {
     info!("Processing message: {:?}", request);
 
-    let mut case_id = request.case_id;
+    let mut events = std::pin::pin!(run_agent(state, session, request));
+    while let Some(event) = events.next().await {
+        if let AgentStreamEvent::Done { response } = event? {
+            info!("Message processed successfully: {:?}", response);
+            return Ok(Json(response));
+        }
+    }
+    Err(common::ServiceError::Internal(anyhow::anyhow!("AI processing ended without a response")))
+}
+
+/// Same pipeline as [`process_message`], reported as server-sent events:
+/// `case` first, then `token`s as the reply is generated, a `task` per
+/// created task and finally `done` (or `error`).
+#[instrument(skip(state, session))]
+async fn process_message_stream(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<MessageRequest>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    info!("Streaming message: {:?}", request);
+
+    let events = run_agent(state, session, request).map(|event| {
+        let event = event.unwrap_or_else(|e| AgentStreamEvent::Error { message: e.to_string() });
+        let sse = Event::default().event(event.name());
+        Ok(sse.json_data(&event).unwrap_or_else(|_| Event::default().event("error").data("serialization failed")))
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// Turns a meeting transcript into minutes, creating a task for each
+/// action item under the referenced case or a new one. The minutes are
+/// recorded in the case's conversation.
+#[instrument(skip(state, session, request))]
+async fn process_transcript(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<TranscriptRequest>,
+) -> ServiceResult<Json<MeetingMinutes>> {
+    if request.transcript.trim().is_empty() {
+        return Err(common::ServiceError::BadRequest("Transcript is empty".to_string()));
+    }
+    info!("Processing transcript of {} characters", request.transcript.len());
+
+    let client = session.client(&state.http_client);
+    let held_at = request.held_at.unwrap_or_else(Utc::now);
+    let language = Language::detect(&request.transcript);
+    let title = request
+        .title
+        .filter(|title| !title.trim().is_empty())
+        .unwrap_or_else(|| format!("Meeting notes {}", held_at.format("%Y-%m-%d")));
+    let (llm, account) = llm_for(&state, &session, None).await;
+    let draft = llm.minutes(&request.transcript, language, held_at).await;
+    settle_usage(&state, account).await;
+    let minutes_text = draft.render(&title);
+
+    let case_mgmt_url = state.config.service_url("case-management");
+    let case_id = match request.case_id {
+        Some(case_id) => case_id,
+        None => {
+            let create_case_request = CreateCaseRequest {
+                title: title.clone(),
+                description: Some(draft.summary.clone()),
+                priority: Priority::Medium,
+                assigned_to: None,
+                tags: vec!["meeting".to_string()],
+                language: Some(language.code().to_string()),
+                urgency: None,
+                user_id: None,
+            };
+            client
+                .post::<CreateCaseRequest, Case>(&format!("{}/api/v1/cases", case_mgmt_url), &create_case_request)
+                .await
+                .map_err(common::ServiceError::HttpClient)?
+                .id
+        }
+    };
+
+    let mut action_items = Vec::with_capacity(draft.action_items.len());
+    for item in draft.action_items {
+        let task_data = TaskData {
+            title: item.title.clone(),
+            description: None,
+            task_type: TaskType::Work,
+            priority: Priority::Medium,
+            due_date: item.due_date,
+            tags: Vec::new(),
+            estimate_minutes: None,
+            confidence: None,
+            metadata: item.owner.as_ref().map(|owner| json!({ "owner": owner })),
+        };
+        let task = create_task(&state, &client, case_id, &minutes_text, task_data).await?;
+        action_items.push(ActionItem { title: item.title, owner: item.owner, due_date: item.due_date, task_id: Some(task.id) });
+    }
+
+    let entry = ConversationEntry {
+        id: Uuid::new_v4(),
+        user_id: Uuid::nil(), // Assigned by persistence from the case owner
+        case_id,
+        message: minutes_text,
+        sender: MessageSender::Agent,
+        timestamp: Utc::now(),
+        metadata: json!({ "event": "meeting_minutes", "title": title, "held_at": held_at }),
+    };
+    client
+        .post::<ConversationEntry, ConversationEntry>(&format!("{}/api/v1/cases/{}/history", case_mgmt_url, case_id), &entry)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    info!("Created {} action items from the transcript in case {}", action_items.len(), case_id);
+    Ok(Json(MeetingMinutes { case_id, title, summary: draft.summary, decisions: draft.decisions, action_items }))
+}
+
+/// Files a meeting invitation as a Meeting task under the case of its email
+/// thread or a new case. An update of an invitation already filed, known by
+/// its UID, reschedules the task instead.
+#[instrument(skip(state, session, request), fields(sender = %request.sender_id))]
+async fn process_invite(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<InviteRequest>,
+) -> ServiceResult<Json<MessageResponse>> {
+    let invite = &request.invite;
+    if invite.title.trim().is_empty() {
+        return Err(common::ServiceError::BadRequest("The invitation has no title".to_string()));
+    }
+    info!("Processing invitation to {:?} at {}", invite.title, invite.starts_at);
+
+    let client = session.client(&state.http_client);
+    let case_mgmt_url = state.config.service_url("case-management");
     let mut actions_taken = Vec::new();
+    let case_id = match find_email_thread_case(&state, &client, request.email_thread.as_ref(), request.owner_id).await? {
+        Some(case_id) => {
+            actions_taken.push("Added invitation to the case of its email thread".to_string());
+            case_id
+        }
+        None => {
+            let language = Language::detect(&format!("{}\n{}", invite.title, invite.description.as_deref().unwrap_or("")));
+            let mut tags = vec!["meeting".to_string()];
+            tags.extend(request.tags.iter().cloned());
+            let create_case_request = CreateCaseRequest {
+                title: invite.title.clone(),
+                description: invite.description.clone(),
+                priority: request.priority.clone().unwrap_or(Priority::Medium),
+                assigned_to: Some(request.sender_id.clone()),
+                tags,
+                language: Some(language.code().to_string()),
+                urgency: None,
+                user_id: request.owner_id,
+            };
+            let case = client
+                .post::<CreateCaseRequest, Case>(&format!("{}/api/v1/cases", case_mgmt_url), &create_case_request)
+                .await
+                .map_err(common::ServiceError::HttpClient)?;
+            actions_taken.push("Created new case".to_string());
+            case.id
+        }
+    };
+
+    if let Some(thread) = request.email_thread.as_ref().filter(|thread| !thread.is_empty()) {
+        let thread_url = format!("{}/api/v1/cases/{}/email-thread", case_mgmt_url, case_id);
+        client
+            .post::<EmailThread, Case>(&thread_url, thread)
+            .await
+            .map_err(common::ServiceError::HttpClient)?;
+    }
+
+    let summary = invite_summary(invite);
+    let entry = ConversationEntry {
+        id: Uuid::new_v4(),
+        user_id: Uuid::nil(), // Assigned by persistence from the case owner
+        case_id,
+        message: summary.clone(),
+        sender: MessageSender::User,
+        timestamp: Utc::now(),
+        metadata: json!({ "channel": models::MessageChannel::Email, "sender_id": request.sender_id, "event": "meeting_invite" }),
+    };
+    client
+        .post::<ConversationEntry, ConversationEntry>(&format!("{}/api/v1/cases/{}/history", case_mgmt_url, case_id), &entry)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    let estimate_minutes = invite
+        .ends_at
+        .map(|ends_at| (ends_at - invite.starts_at).num_minutes())
+        .filter(|minutes| *minutes > 0)
+        .and_then(|minutes| i32::try_from(minutes).ok());
+    let mut metadata = json!({ "attendees": invite.attendees });
+    for (key, value) in [("location", &invite.location), ("organizer", &invite.organizer), ("invite_uid", &invite.uid)] {
+        if let Some(value) = value {
+            metadata[key] = json!(value);
+        }
+    }
+
     let mut tasks_created = Vec::new();
     let mut tasks_updated = Vec::new();
+    match filed_invite(&state, &client, case_id, invite).await {
+        Some(task) => {
+            let mut task_metadata = task.metadata;
+            merge_metadata(&mut task_metadata, metadata);
+            let update = UpdateTaskRequest {
+                title: Some(invite.title.clone()),
+                description: invite.description.clone(),
+                status: None,
+                priority: None,
+                due_date: Some(invite.starts_at),
+                tags: None,
+                metadata: Some(task_metadata),
+                estimate_minutes,
+            };
+            let url = format!("{}/api/v1/tasks/{}", state.config.service_url("task-management"), task.id);
+            let task = client.put::<UpdateTaskRequest, Task>(&url, &update).await.map_err(common::ServiceError::HttpClient)?;
+            actions_taken.push(format!("Updated meeting: {}", task.title));
+            tasks_updated.push(task.id);
+        }
+        None => {
+            let task_data = TaskData {
+                title: invite.title.clone(),
+                description: invite.description.clone(),
+                task_type: TaskType::Meeting,
+                priority: request.priority.clone().unwrap_or(Priority::Medium),
+                due_date: Some(invite.starts_at),
+                tags: request.tags.clone(),
+                estimate_minutes,
+                confidence: None,
+                metadata: Some(metadata),
+            };
+            let task = create_task(&state, &client, case_id, &summary, task_data).await?;
+            actions_taken.push(task_action(&task));
+            tasks_created.push(task.id);
+        }
+    }
+
+    info!("Invitation to {:?} filed under case {}", invite.title, case_id);
+    Ok(Json(MessageResponse {
+        case_id,
+        response: summary,
+        actions_taken,
+        tasks_created,
+        tasks_updated,
+        questions: Vec::new(),
+    }))
+}
 
-    // Step 1: Determine if this is a new case or existing case
-    if case_id.is_none() {
-        // Check if we can find an existing case based on context
-        case_id = find_or_create_case(&state, &request.message, &request.sender_id).await?;
-        actions_taken.push("Created new case".to_string());
+/// The Meeting task of the case an earlier version of the invitation was
+/// filed as, if any.
+async fn filed_invite(state: &AppState, client: &HttpClient, case_id: Uuid, invite: &MeetingInvite) -> Option<Task> {
+    let uid = invite.uid.as_deref()?;
+    let url = format!("{}/api/v1/cases/{}/tasks", state.config.service_url("task-management"), case_id);
+    let tasks = client.get::<Vec<Task>>(&url).await.unwrap_or_else(|e| {
+        warn!("Could not load the tasks of case {}: {}", case_id, e);
+        Vec::new()
+    });
+    tasks
+        .into_iter()
+        .find(|task| task.task_type == TaskType::Meeting && task.metadata.get("invite_uid").and_then(Value::as_str) == Some(uid))
+}
+
+fn merge_metadata(metadata: &mut Value, update: Value) {
+    match (metadata.as_object_mut(), update) {
+        (Some(metadata), Value::Object(update)) => metadata.extend(update),
+        (_, update) => *metadata = update,
+    }
+}
+
+/// The invitation as recorded in the case's conversation.
+fn invite_summary(invite: &MeetingInvite) -> String {
+    let mut summary = format!("Meeting invitation: {} on {}", invite.title, invite.starts_at.format("%Y-%m-%d %H:%M UTC"));
+    if let Some(ends_at) = invite.ends_at {
+        summary.push_str(&format!(" until {}", ends_at.format("%H:%M UTC")));
+    }
+    if let Some(location) = &invite.location {
+        summary.push_str(&format!(" at {}", location));
     }
+    if let Some(organizer) = &invite.organizer {
+        summary.push_str(&format!(", organized by {}", organizer));
+    }
+    if !invite.attendees.is_empty() {
+        summary.push_str(&format!(", with {}", invite.attendees.join(", ")));
+    }
+    summary
+}
+
+/// Files a newsletter or notification in the owner's review case, opening
+/// a low-priority one if there is none, without extracting tasks.
+#[instrument(skip(state, session, request), fields(sender = %request.sender_id))]
+async fn process_noise(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<MessageRequest>,
+) -> ServiceResult<Json<MessageResponse>> {
+    info!("Filing email from {} for review", request.sender_id);
+
+    let client = session.client(&state.http_client);
+    let case_mgmt_url = state.config.service_url("case-management");
+    let mut actions_taken = Vec::new();
+    let case_id = match review_case(&state, &client, request.owner_id).await? {
+        Some(case_id) => case_id,
+        None => {
+            let create_case_request = CreateCaseRequest {
+                title: "Newsletters and notifications".to_string(),
+                description: Some("Email that looked like a newsletter or notification, filed here instead of being processed as work.".to_string()),
+                priority: Priority::Low,
+                assigned_to: None,
+                tags: vec![NOISE_REVIEW_TAG.to_string()],
+                language: None,
+                urgency: None,
+                user_id: request.owner_id,
+            };
+            let case = client
+                .post::<CreateCaseRequest, Case>(&format!("{}/api/v1/cases", case_mgmt_url), &create_case_request)
+                .await
+                .map_err(common::ServiceError::HttpClient)?;
+            actions_taken.push("Created review case".to_string());
+            case.id
+        }
+    };
+
+    let entry = ConversationEntry {
+        id: Uuid::new_v4(),
+        user_id: Uuid::nil(), // Assigned by persistence from the case owner
+        case_id,
+        message: request.message.clone(),
+        sender: MessageSender::User,
+        timestamp: Utc::now(),
+        metadata: json!({ "channel": request.channel, "sender_id": request.sender_id, "event": "filed_for_review", "tags": request.tags }),
+    };
+    client
+        .post::<ConversationEntry, ConversationEntry>(&format!("{}/api/v1/cases/{}/history", case_mgmt_url, case_id), &entry)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    actions_taken.push("Filed for review".to_string());
+
+    Ok(Json(MessageResponse {
+        case_id,
+        response: "Filed for review".to_string(),
+        actions_taken,
+        tasks_created: Vec::new(),
+        tasks_updated: Vec::new(),
+        questions: Vec::new(),
+    }))
+}
+
+/// The owner's review case still open, if any.
+async fn review_case(state: &AppState, client: &HttpClient, owner_id: Option<Uuid>) -> ServiceResult<Option<Uuid>> {
+    let url = format!("{}/api/v1/cases", state.config.service_url("case-management"));
+    let cases = client
+        .get_with_query::<Vec<Case>, _>(&url, &[("tag", NOISE_REVIEW_TAG)])
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(cases
+        .into_iter()
+        .filter(|case| matches!(case.status, CaseStatus::Open | CaseStatus::InProgress | CaseStatus::Waiting))
+        .find(|case| owner_id.is_none_or(|owner_id| case.user_id == owner_id))
+        .map(|case| case.id))
+}
+
+/// What the agent did while handling a message.
+#[derive(Default)]
+struct Outcome {
+    actions_taken: Vec<String>,
+    tasks_created: Vec<Uuid>,
+    tasks_updated: Vec<Uuid>,
+    /// Warnings appended to the reply.
+    notes: Vec<String>,
+}
 
-    let case_id = case_id.unwrap();
+/// Files the message under a case, then lets the model act on it with its
+/// tools for at most `max_steps` steps. Yields the events of the streaming
+/// API; an error ends the stream.
+fn run_agent(
+    state: Arc<AppState>,
+    session: SessionToken,
+    request: MessageRequest,
+) -> impl Stream<Item = ServiceResult<AgentStreamEvent>> {
+    async_stream::stream! {
+        // Everything created while handling the message belongs to the sender.
+        let client = session.client(&state.http_client);
+        let mut outcome = Outcome::default();
+
+        // Steps 1-2: Resolve the case and record the incoming message
+        let language = Language::detect(&request.message);
+        let (case_id, created) = match open_case(&state, &client, &request, language, &mut outcome.actions_taken).await {
+            Ok(opened) => opened,
+            Err(e) => {
+                yield Err(e);
+                return;
+            }
+        };
+        yield Ok(AgentStreamEvent::Case { case_id });
+
+        // Step 3: Let the model act on the message, one step at a time
+        let mut context = prompt_context(&state, &client, &session, &request.message).await;
+        if !created {
+            context.open_questions = open_questions(&state, &client, case_id).await;
+        }
+        let (llm, account) = llm_for(&state, &session, request.owner_id).await;
+        let message = llm.condense(&request.message).await;
+        let mut run = AgentRun::new(message, case_id, language, context);
+        let tools = ToolContext { state: &state, client: &client, session: &session, request: &request, case_id };
+        let mut reply = String::new();
+        let mut replied = false;
+        let mut created_tasks = Vec::new();
+        for _ in 0..state.max_steps {
+            let mut step = None;
+            let mut llm_events = llm.next_step(&run);
+            while let Some(event) = llm_events.next().await {
+                match event {
+                    LLMStreamEvent::Token(text) => {
+                        reply.push_str(&text);
+                        yield Ok(AgentStreamEvent::Token { text });
+                    }
+                    LLMStreamEvent::Done(done) => step = Some(done),
+                }
+            }
+
+            let (text, calls) = match step {
+                Some(AgentStep::Reply(_)) => {
+                    replied = true;
+                    break;
+                }
+                Some(AgentStep::Calls { text, calls }) => (text, calls),
+                None => {
+                    settle_usage(&state, account).await;
+                    yield Err(common::ServiceError::Internal(anyhow::anyhow!("AI processing ended without a response")));
+                    return;
+                }
+            };
+
+            // Step 4: Carry out the calls; the model sees the results next step
+            let mut results = Vec::with_capacity(calls.len());
+            for call in &calls {
+                match run_tool(&tools, call, &mut outcome).await {
+                    Ok((content, task)) => {
+                        results.push(AgentTurn::Result { call_id: call.id.clone(), content: content.to_string() });
+                        if let Some(task) = task {
+                            created_tasks.push(task.clone());
+                            yield Ok(AgentStreamEvent::Task { task });
+                        }
+                    }
+                    Err(e) => {
+                        settle_usage(&state, account).await;
+                        yield Err(e);
+                        return;
+                    }
+                }
+            }
+            run.turns.push(AgentTurn::Calls { text, calls });
+            run.turns.extend(results);
+        }
+
+        settle_usage(&state, account).await;
+        if !replied {
+            warn!("Agent ran out of steps for case {}", case_id);
+            if reply.trim().is_empty() {
+                let text = format!("Done: {}.", outcome.actions_taken.join("; "));
+                reply.push_str(&text);
+                yield Ok(AgentStreamEvent::Token { text });
+            }
+        }
+        for note in std::mem::take(&mut outcome.notes) {
+            reply.push_str(&note);
+            yield Ok(AgentStreamEvent::Token { text: note });
+        }
+
+        // Step 5: Ask for what the created tasks are missing. The message
+        // was the model's chance to answer the questions already open.
+        if !run.context.open_questions.is_empty() {
+            let question_ids = run.context.open_questions.iter().map(|question| question.id).collect();
+            answer_questions(&state, &client, case_id, question_ids).await;
+        }
+        let questions = ask_follow_ups(&state, &client, &request, case_id, language, &created_tasks).await;
+        if !questions.is_empty() {
+            let text = format!(
+                "\n\n{}",
+                questions.iter().map(|question| question.question.as_str()).collect::<Vec<_>>().join("\n")
+            );
+            reply.push_str(&text);
+            yield Ok(AgentStreamEvent::Token { text });
+        }
+
+        // Step 6: Add AI response to conversation
+        if let Err(e) = record_agent_reply(&state, &client, case_id, &reply).await {
+            yield Err(e);
+            return;
+        }
+
+        yield Ok(AgentStreamEvent::Done {
+            response: MessageResponse {
+                case_id,
+                response: reply,
+                actions_taken: outcome.actions_taken,
+                tasks_created: outcome.tasks_created,
+                tasks_updated: outcome.tasks_updated,
+                questions,
+            },
+        });
+    }
+}
+
+/// Resolves the case for an incoming message, creating one when needed, and
+/// records the message in its conversation history. Returns the case and
+/// whether it was created for the message.
+async fn open_case(
+    state: &AppState,
+    client: &HttpClient,
+    request: &MessageRequest,
+    language: Language,
+    actions_taken: &mut Vec<String>,
+) -> ServiceResult<(Uuid, bool)> {
+    let (case_id, created) = match request.case_id {
+        Some(case_id) => (case_id, false),
+        None => match find_email_thread_case(state, client, request.email_thread.as_ref(), request.owner_id).await? {
+            Some(case_id) => {
+                actions_taken.push("Added reply to the case of its email thread".to_string());
+                (case_id, false)
+            }
+            None => {
+                // Check if we can find an existing case based on context
+                let case_id = find_or_create_case(state, client, request, language).await?;
+                actions_taken.push("Created new case".to_string());
+                (case_id.unwrap(), true)
+            }
+        },
+    };
+
+    if let Some(thread) = request.email_thread.as_ref().filter(|thread| !thread.is_empty()) {
+        let thread_url = format!("{}/api/v1/cases/{}/email-thread", state.config.service_url("case-management"), case_id);
+        client
+            .post::<EmailThread, Case>(&thread_url, thread)
+            .await
+            .map_err(common::ServiceError::HttpClient)?;
+    }
 
-    // Step 2: Add conversation entry
     let conversation_entry = ConversationEntry {
         id: Uuid::new_v4(),
-        user_id: Uuid::new_v4(), // TODO: Extract from session token
+        user_id: Uuid::nil(), // Assigned by persistence from the case owner
         case_id,
         message: request.message.clone(),
         sender: MessageSender::User,
         timestamp: Utc::now(),
         metadata: serde_json::json!({
             "channel": request.channel,
-            "sender_id": request.sender_id
+            "sender_id": request.sender_id,
+            "language": language.code()
         }),
     };
 
     let case_mgmt_url = format!("{}/api/v1/cases/{}/history", state.config.service_url("case-management"), case_id);
-    state.http_client
+    client
         .post::<ConversationEntry, ConversationEntry>(&case_mgmt_url, &conversation_entry)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     actions_taken.push("Added conversation entry".to_string());
+    Ok((case_id, created))
+}
 
-    // Step 3: Process message with LLM to extract tasks and actions
-    let ai_response = state.llm_client.process_message(&request.message, case_id).await
-        .map_err(|e| common::ServiceError::Internal(anyhow::anyhow!("AI processing failed: {}", e)))?;
-    
-    // Step 4: Create tasks based on AI analysis
-    for task_data in ai_response.tasks {
-        let create_task_request = CreateTaskRequest {
-            title: task_data.title,
-            description: task_data.description,
-            task_type: task_data.task_type,
-            priority: task_data.priority,
-            due_date: task_data.due_date,
+/// The model for requests made for the user behind `session`, or for
+/// `owner_id` without one: their organization's, if it has LLM settings,
+/// with the account its tokens are counted on.
+async fn llm_for(state: &AppState, session: &SessionToken, owner_id: Option<Uuid>) -> (Arc<dyn Llm>, Option<Arc<usage::LlmAccount>>) {
+    // Offline deployments only use their own model.
+    if state.config.llm.provider.is_offline() {
+        return (state.llm_client.clone(), None);
+    }
+    let persistence_url = state.config.service_url("persistence");
+    let account = usage::account(&state.http_client, &persistence_url, session, owner_id).await;
+    let llm = account.as_ref().and_then(|account| state.llm_client.for_account(account));
+    (llm.unwrap_or_else(|| state.llm_client.clone()), account)
+}
+
+/// Reports the tokens a request used to its organization's account.
+async fn settle_usage(state: &AppState, account: Option<Arc<usage::LlmAccount>>) {
+    if let Some(account) = account {
+        usage::report(&state.http_client, &state.config.service_url("persistence"), &account).await;
+    }
+}
+
+/// The sender's examples and task types. Messages without a session get
+/// neither, and a failed lookup only costs the tailoring.
+async fn prompt_context(state: &AppState, client: &HttpClient, session: &SessionToken, message: &str) -> PromptContext {
+    let persistence_url = state.config.service_url("persistence");
+    let examples = examples::for_message(client, session, &persistence_url, message, state.max_examples).await;
+    let task_types = match session.0 {
+        Some(_) => client
+            .get::<Vec<CustomTaskType>>(&format!("{}/api/v1/task-types", persistence_url))
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Could not load task types: {}", e);
+                Vec::new()
+            }),
+        None => Vec::new(),
+    };
+    PromptContext { examples, task_types, open_questions: Vec::new() }
+}
+
+/// Questions still waiting for an answer on the case. A failed lookup only
+/// means the model isn't told about them.
+async fn open_questions(state: &AppState, client: &HttpClient, case_id: Uuid) -> Vec<FollowUpQuestion> {
+    let url = format!("{}/api/v1/cases/{}/questions?status=Pending", state.config.service_url("case-management"), case_id);
+    client.get::<Vec<FollowUpQuestion>>(&url).await.unwrap_or_else(|e| {
+        warn!("Could not load open questions of case {}: {}", case_id, e);
+        Vec::new()
+    })
+}
+
+async fn answer_questions(state: &AppState, client: &HttpClient, case_id: Uuid, question_ids: Vec<Uuid>) {
+    let url = format!("{}/api/v1/cases/{}/questions/answered", state.config.service_url("case-management"), case_id);
+    if let Err(e) = client
+        .post::<AnswerQuestionsRequest, Vec<FollowUpQuestion>>(&url, &AnswerQuestionsRequest { question_ids })
+        .await
+    {
+        warn!("Could not mark questions of case {} answered: {}", case_id, e);
+    }
+}
+
+/// Stores a question for each created task that lacks key details, to be
+/// asked on the channel the message came from. Questions that could not be
+/// stored are left out.
+async fn ask_follow_ups(
+    state: &AppState,
+    client: &HttpClient,
+    request: &MessageRequest,
+    case_id: Uuid,
+    language: Language,
+    tasks: &[Task],
+) -> Vec<FollowUpQuestion> {
+    let url = format!("{}/api/v1/cases/{}/questions", state.config.service_url("case-management"), case_id);
+    let mut asked = Vec::new();
+    for task in tasks {
+        let missing = questions::missing_details(task);
+        if missing.is_empty() {
+            continue;
+        }
+        let question = FollowUpQuestion {
+            id: Uuid::new_v4(),
+            case_id,
+            task_id: task.id,
+            user_id: Uuid::nil(), // Assigned by persistence from the case owner
+            question: questions::question(task, &missing, language),
+            missing,
+            channel: request.channel.clone(),
+            recipient: request.sender_id.clone(),
+            status: QuestionStatus::Pending,
+            created_at: Utc::now(),
+            answered_at: None,
         };
+        match client.post::<FollowUpQuestion, FollowUpQuestion>(&url, &question).await {
+            Ok(question) => asked.push(question),
+            Err(e) => warn!("Could not store follow-up question about task {}: {}", task.id, e),
+        }
+    }
+    asked
+}
+
+/// The case an earlier email of the same thread was added to, if any.
+/// Without a session the lookup sees every user's cases, so only a case of
+/// the mailbox's owner counts: message ids are shared by every recipient of
+/// an email, and a reply must not end up in someone else's case.
+async fn find_email_thread_case(
+    state: &AppState,
+    client: &HttpClient,
+    thread: Option<&EmailThread>,
+    owner_id: Option<Uuid>,
+) -> ServiceResult<Option<Uuid>> {
+    let Some(thread) = thread.filter(|thread| !thread.is_empty()) else {
+        return Ok(None);
+    };
+    let url = format!("{}/api/v1/cases/by-email-thread", state.config.service_url("case-management"));
+    let cases = client
+        .get_with_query::<Vec<Case>, _>(&url, thread)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(cases
+        .into_iter()
+        .find(|case| owner_id.is_none_or(|owner_id| case.user_id == owner_id))
+        .map(|case| case.id))
+}
 
-        let task_mgmt_url = format!("{}/api/v1/cases/{}/tasks", state.config.service_url("task-management"), case_id);
-        let created_task = state.http_client
-            .post::<CreateTaskRequest, Task>(&task_mgmt_url, &create_task_request)
+async fn create_task(
+    state: &AppState,
+    client: &HttpClient,
+    case_id: Uuid,
+    message: &str,
+    task_data: TaskData,
+) -> ServiceResult<Task> {
+    let create_task_request = CreateTaskRequest {
+        title: task_data.title,
+        description: task_data.description,
+        task_type: task_data.task_type,
+        priority: task_data.priority,
+        due_date: task_data.due_date,
+        tags: task_data.tags,
+        estimate_minutes: task_data.estimate_minutes,
+        confidence: task_data.confidence,
+        // Tasks without a confidence are trusted, as before scores existed.
+        needs_review: task_data.confidence.is_some_and(|confidence| confidence < state.review_threshold),
+        source_message: Some(message.to_string()),
+        metadata: task_data.metadata,
+    };
+
+    let task_mgmt_url = format!("{}/api/v1/cases/{}/tasks", state.config.service_url("task-management"), case_id);
+    client
+        .post::<CreateTaskRequest, Task>(&task_mgmt_url, &create_task_request)
+        .await
+        .map_err(common::ServiceError::HttpClient)
+}
+
+/// What tools act on: the sender's session and the case of the message.
+struct ToolContext<'a> {
+    state: &'a AppState,
+    client: &'a HttpClient,
+    session: &'a SessionToken,
+    request: &'a MessageRequest,
+    case_id: Uuid,
+}
+
+impl ToolContext<'_> {
+    /// Messages without a session come from collectors acting for any
+    /// user, so their tools only reach the case of the message.
+    fn confined(&self) -> bool {
+        self.session.0.is_none()
+    }
+}
+
+/// Cases `lookup_history` returns.
+const LOOKUP_LIMIT: usize = 5;
+
+/// Runs one call and returns its result for the model, with the task it
+/// created, if any. Mistakes the model can correct, such as bad arguments
+/// or an unknown id, are reported to it rather than failing the message.
+async fn run_tool(tools: &ToolContext<'_>, call: &ToolCall, outcome: &mut Outcome) -> ServiceResult<(Value, Option<Task>)> {
+    let tool = match call.tool() {
+        Ok(tool) => tool,
+        Err(e) => return Ok((json!({ "error": e }), None)),
+    };
+    info!("Agent calls {}", call.name);
+
+    let result = match tool {
+        Tool::CreateTask(task_data) => create_task_tool(tools, task_data, outcome).await,
+        Tool::ScheduleMeeting(meeting) => {
+            let task_data = TaskData {
+                title: meeting.title,
+                description: meeting.description,
+                task_type: TaskType::Meeting,
+                priority: Priority::Medium,
+                due_date: Some(meeting.starts_at),
+                tags: Vec::new(),
+                estimate_minutes: meeting.duration_minutes,
+                confidence: None,
+                metadata: (!meeting.attendees.is_empty()).then(|| json!({ "attendees": meeting.attendees })),
+            };
+            create_task_tool(tools, task_data, outcome).await
+        }
+        Tool::UpdateTask(update) => update_task_tool(tools, update, outcome).await.map(|result| (result, None)),
+        Tool::CloseCase(close) => close_case_tool(tools, close, outcome).await.map(|result| (result, None)),
+        Tool::LookupHistory(lookup) => lookup_history_tool(tools, lookup).await.map(|result| (result, None)),
+    };
+
+    match result {
+        Err(e) if correctable(&e) => Ok((json!({ "error": e.to_string() }), None)),
+        result => result,
+    }
+}
+
+fn correctable(error: &common::ServiceError) -> bool {
+    match error {
+        common::ServiceError::BadRequest(_) | common::ServiceError::NotFound(_) | common::ServiceError::Validation(_) => true,
+        common::ServiceError::HttpClient(e) => e.status().is_some_and(|status| status.is_client_error()),
+        _ => false,
+    }
+}
+
+async fn create_task_tool(tools: &ToolContext<'_>, mut task_data: TaskData, outcome: &mut Outcome) -> ServiceResult<(Value, Option<Task>)> {
+    // What the message's source says about it, e.g. an email marked as
+    // important, sets the floor.
+    if let Some(priority) = tools.request.priority.clone() {
+        task_data.priority = task_data.priority.max(priority);
+    }
+    task_data.tags.extend(tools.request.tags.iter().cloned());
+    let task = create_task(tools.state, tools.client, tools.case_id, &tools.request.message, task_data).await?;
+    outcome.tasks_created.push(task.id);
+    outcome.actions_taken.push(task_action(&task));
+    outcome.notes.extend(conflict_note(&task));
+
+    let conflicts: Vec<String> = task.meeting_conflicts().into_iter().map(|conflict| conflict.title).collect();
+    let result = json!({ "task_id": task.id, "title": task.title, "status": task.status, "overlapping_meetings": conflicts });
+    Ok((result, Some(task)))
+}
+
+async fn update_task_tool(tools: &ToolContext<'_>, update: UpdateTaskArgs, outcome: &mut Outcome) -> ServiceResult<Value> {
+    let task_url = format!("{}/api/v1/tasks/{}", tools.state.config.service_url("task-management"), update.task_id);
+    let mut metadata = None;
+    if tools.confined() || update.attendees.is_some() {
+        let task = tools.client.get::<Task>(&task_url).await.map_err(common::ServiceError::HttpClient)?;
+        if tools.confined() && task.case_id != tools.case_id {
+            return Err(common::ServiceError::BadRequest("Only tasks of the current case can be changed".to_string()));
+        }
+        // Metadata is replaced as a whole, so the attendees go into the task's.
+        if let Some(attendees) = update.attendees {
+            let mut task_metadata = task.metadata;
+            task_metadata["attendees"] = json!(attendees);
+            metadata = Some(task_metadata);
+        }
+    }
+
+    let request = UpdateTaskRequest {
+        title: update.title,
+        description: update.description,
+        status: update.status,
+        priority: update.priority,
+        due_date: update.due_date,
+        tags: None,
+        metadata,
+        estimate_minutes: None,
+    };
+    let task = tools
+        .client
+        .put::<UpdateTaskRequest, Task>(&task_url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    outcome.tasks_updated.push(task.id);
+    outcome.actions_taken.push(format!("Updated task: {}", task.title));
+    Ok(json!({ "task_id": task.id, "title": task.title, "status": task.status, "due_date": task.due_date }))
+}
+
+async fn close_case_tool(tools: &ToolContext<'_>, close: CloseCaseArgs, outcome: &mut Outcome) -> ServiceResult<Value> {
+    let case_id = close.case_id.unwrap_or(tools.case_id);
+    if tools.confined() && case_id != tools.case_id {
+        return Err(common::ServiceError::BadRequest("Only the current case can be closed".to_string()));
+    }
+    let status = close.status.unwrap_or(CaseStatus::Closed);
+    if !matches!(status, CaseStatus::Resolved | CaseStatus::Closed) {
+        return Err(common::ServiceError::BadRequest("A case can only be Resolved or Closed".to_string()));
+    }
+
+    let request = UpdateCaseRequest {
+        title: None,
+        description: None,
+        status: Some(status.clone()),
+        priority: None,
+        assigned_to: None,
+        tags: None,
+    };
+    let url = format!("{}/api/v1/cases/{}/state", tools.state.config.service_url("case-management"), case_id);
+    let case = tools
+        .client
+        .put::<UpdateCaseRequest, Case>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    outcome.actions_taken.push(format!("{:?} case: {}", status, case.title));
+    Ok(json!({ "case_id": case.id, "title": case.title, "status": case.status }))
+}
+
+/// The sender's cases best matching the query, with their tasks.
+async fn lookup_history_tool(tools: &ToolContext<'_>, lookup: LookupArgs) -> ServiceResult<Value> {
+    let case_url = format!("{}/api/v1/cases", tools.state.config.service_url("case-management"));
+    let cases = if tools.confined() {
+        let case = tools
+            .client
+            .get::<Case>(&format!("{}/{}", case_url, tools.case_id))
             .await
-            .map_err(|e| common::ServiceError::HttpClient(e))?;
+            .map_err(common::ServiceError::HttpClient)?;
+        vec![case]
+    } else {
+        tools.client.get::<Vec<Case>>(&case_url).await.map_err(common::ServiceError::HttpClient)?
+    };
+
+    let words: Vec<String> = lookup
+        .query
+        .as_deref()
+        .unwrap_or_default()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 2)
+        .map(str::to_lowercase)
+        .collect();
+    let mut matches: Vec<(usize, Case)> = cases
+        .into_iter()
+        .filter(|case| lookup.include_closed || !matches!(case.status, CaseStatus::Resolved | CaseStatus::Closed))
+        .map(|case| {
+            let text = format!("{} {}", case.title, case.description.as_deref().unwrap_or_default()).to_lowercase();
+            (words.iter().filter(|word| text.contains(word.as_str())).count(), case)
+        })
+        .filter(|(score, _)| words.is_empty() || *score > 0)
+        .collect();
+    // Newest first among equally good matches.
+    matches.sort_by(|(a, a_case), (b, b_case)| b.cmp(a).then(b_case.created_at.cmp(&a_case.created_at)));
 
-        tasks_created.push(created_task.id);
-        actions_taken.push(format!("Created task: {}", created_task.title));
+    let task_url = tools.state.config.service_url("task-management");
+    let mut found = Vec::new();
+    for (_, case) in matches.into_iter().take(LOOKUP_LIMIT) {
+        let tasks = tools
+            .client
+            .get::<Vec<Task>>(&format!("{}/api/v1/cases/{}/tasks", task_url, case.id))
+            .await
+            .map_err(common::ServiceError::HttpClient)?;
+        found.push(json!({
+            "case_id": case.id,
+            "title": case.title,
+            "status": case.status,
+            "created_at": case.created_at,
+            "current": case.id == tools.case_id,
+            "tasks": tasks
+                .iter()
+                .map(|task| json!({ "task_id": task.id, "title": task.title, "status": task.status, "due_date": task.due_date }))
+                .collect::<Vec<_>>(),
+        }));
     }
+    Ok(Value::Array(found))
+}
 
-    // Step 5: Add AI response to conversation
+fn task_action(task: &Task) -> String {
+    if task.status == TaskStatus::NeedsReview {
+        format!("Queued task for review: {}", task.title)
+    } else {
+        format!("Created task: {}", task.title)
+    }
+}
+
+/// Warning appended to the reply when a created meeting overlaps others.
+fn conflict_note(task: &Task) -> Option<String> {
+    let conflicts = task.meeting_conflicts();
+    if conflicts.is_empty() {
+        return None;
+    }
+    let others = conflicts
+        .iter()
+        .map(|conflict| format!("'{}' ({} UTC)", conflict.title, conflict.starts_at.format("%Y-%m-%d %H:%M")))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!("\n\nNote: '{}' overlaps with {}.", task.title, others))
+}
+
+async fn record_agent_reply(
+    state: &AppState,
+    client: &HttpClient,
+    case_id: Uuid,
+    reply: &str,
+) -> ServiceResult<()> {
     let ai_conversation_entry = ConversationEntry {
         id: Uuid::new_v4(),
-        user_id: Uuid::new_v4(), // TODO: Extract from session token
+        user_id: Uuid::nil(), // Assigned by persistence from the case owner
         case_id,
-        message: ai_response.response.clone(),
+        message: reply.to_string(),
         sender: MessageSender::Agent,
         timestamp: Utc::now(),
         metadata: serde_json::json!({}),
     };
 
-    state.http_client
+    let case_mgmt_url = format!("{}/api/v1/cases/{}/history", state.config.service_url("case-management"), case_id);
+    client
         .post::<ConversationEntry, ConversationEntry>(&case_mgmt_url, &ai_conversation_entry)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
-
-    let response = MessageResponse {
-        case_id,
-        response: ai_response.response,
-        actions_taken,
-        tasks_created,
-        tasks_updated,
-    };
-
-    info!("Message processed successfully: {:?}", response);
-    Ok(Json(response))
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(())
 }
 
 async fn find_or_create_case(
     state: &AppState,
-    message: &str,
-    sender_id: &str,
+    client: &HttpClient,
+    request: &MessageRequest,
+    language: Language,
 ) -> ServiceResult<Option<Uuid>> {
     // For now, always create a new case
     // In a real implementation, you might search for existing cases based on context
     
+    let (message, sender_id) = (request.message.as_str(), request.sender_id.as_str());
     let case_title = extract_case_title(message);
     let create_case_request = CreateCaseRequest {
         title: case_title,
         description: Some(message.to_string()),
-        priority: Priority::Medium,
+        priority: request.priority.clone().unwrap_or(Priority::Medium),
         assigned_to: Some(sender_id.to_string()),
+        tags: request.tags.clone(),
+        language: Some(language.code().to_string()),
+        urgency: Some(state.urgency.score(message, sender_id, language)),
+        user_id: request.owner_id,
     };
 
     let case_mgmt_url = format!("{}/api/v1/cases", state.config.service_url("case-management"));
-    let created_case = state.http_client
+    let created_case = client
         .post::<CreateCaseRequest, Case>(&case_mgmt_url, &create_case_request)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(Some(created_case.id))
 }
@@ -189,9 +1271,603 @@ fn extract_case_title(message: &str) -> String {
     let words: Vec<&str> = message.split_whitespace().take(6).collect();
     let title = words.join(" ");
     
-    if title.len() > 50 {
-        format!("{}...", &title[..47])
+    if title.chars().count() > 50 {
+        format!("{}...", title.chars().take(47).collect::<String>())
     } else {
         title
     }
 }
+
+/// The message pipeline against a mock LLM and mock services.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use common::testing::MockTransport;
+    use futures::stream::BoxStream;
+    use models::TaskType;
+    use reqwest::Method;
+
+    /// Takes scripted steps, replying "Done." once they run out, and
+    /// remembers the runs it was shown.
+    struct MockLlm {
+        steps: std::sync::Mutex<std::collections::VecDeque<AgentStep>>,
+        runs: Arc<std::sync::Mutex<Vec<AgentRun>>>,
+    }
+
+    impl MockLlm {
+        fn new(steps: Vec<AgentStep>) -> Self {
+            Self { steps: std::sync::Mutex::new(steps.into()), runs: Default::default() }
+        }
+
+        /// Creates the tasks in one step, then replies.
+        fn creating(tasks: Vec<TaskData>, reply: &str) -> Self {
+            let tools: Vec<Tool> = tasks.into_iter().map(Tool::CreateTask).collect();
+            Self::new(vec![calls(&tools), AgentStep::Reply(reply.to_string())])
+        }
+    }
+
+    fn calls(tools: &[Tool]) -> AgentStep {
+        let calls = tools.iter().enumerate().map(|(i, tool)| ToolCall::new(format!("call-{}", i), tool)).collect();
+        AgentStep::Calls { text: String::new(), calls }
+    }
+
+    #[async_trait]
+    impl Llm for MockLlm {
+        fn next_step(&self, run: &AgentRun) -> BoxStream<'static, LLMStreamEvent> {
+            self.runs.lock().unwrap().push(run.clone());
+            let step = self.steps.lock().unwrap().pop_front().unwrap_or_else(|| AgentStep::Reply("Done.".to_string()));
+            let mut events = Vec::new();
+            if let AgentStep::Reply(text) = &step {
+                events.push(LLMStreamEvent::Token(text.clone()));
+            }
+            events.push(LLMStreamEvent::Done(step));
+            futures::stream::iter(events).boxed()
+        }
+    }
+
+    const CASE_ID: &str = "00000000-0000-0000-0000-00000000000c";
+
+    fn extracted(title: &str, confidence: f32) -> TaskData {
+        TaskData {
+            title: title.to_string(),
+            description: None,
+            task_type: TaskType::Work,
+            priority: Priority::High,
+            due_date: None,
+            tags: Vec::new(),
+            estimate_minutes: None,
+            confidence: Some(confidence),
+            metadata: None,
+        }
+    }
+
+    fn case() -> Value {
+        json!({
+            "id": CASE_ID,
+            "user_id": Uuid::nil(),
+            "title": "Budget review",
+            "description": null,
+            "status": "Open",
+            "priority": "Medium",
+            "created_at": Utc::now(),
+            "updated_at": Utc::now(),
+            "assigned_to": null,
+            "metadata": {},
+        })
+    }
+
+    fn task(title: &str, status: &str) -> Value {
+        json!({
+            "id": Uuid::new_v4(),
+            "user_id": Uuid::nil(),
+            "case_id": CASE_ID,
+            "title": title,
+            "description": null,
+            "task_type": "Work",
+            "status": status,
+            "priority": "High",
+            "due_date": null,
+            "created_at": Utc::now(),
+            "updated_at": Utc::now(),
+            "completed_at": null,
+            "metadata": {},
+        })
+    }
+
+    /// Services that accept everything the pipeline sends.
+    fn services() -> Arc<MockTransport> {
+        let mock = MockTransport::new();
+        mock.reply(Method::POST, "/api/v1/cases", case())
+            .reply(Method::GET, "/api/v1/cases/by-email-thread", json!([]))
+            .reply(Method::POST, "/api/v1/cases/*/email-thread", case())
+            .reply(Method::POST, "/api/v1/cases/*/history", json!({
+                "id": Uuid::new_v4(),
+                "user_id": Uuid::nil(),
+                "case_id": CASE_ID,
+                "message": "",
+                "sender": "User",
+                "timestamp": Utc::now(),
+                "metadata": {},
+            }))
+            .reply(Method::POST, "/api/v1/cases/*/tasks", task("Send the budget review", "Pending"))
+            .reply(Method::GET, "/api/v1/extraction-examples", json!([]))
+            .reply(Method::GET, "/api/v1/task-types", json!([]));
+        mock
+    }
+
+    fn state(mock: &Arc<MockTransport>, llm: MockLlm) -> Arc<AppState> {
+        Arc::new(AppState {
+            config: ServiceConfig::for_tests("ai-agent-service"),
+            http_client: mock.client(),
+            llm_client: Arc::new(llm),
+            review_threshold: DEFAULT_REVIEW_CONFIDENCE_THRESHOLD,
+            max_examples: examples::DEFAULT_MAX_EXAMPLES,
+            max_steps: agent::DEFAULT_MAX_STEPS,
+            urgency: UrgencyScorer::new("lead@company.com"),
+        })
+    }
+
+    fn message(text: &str, email_thread: Option<EmailThread>) -> MessageRequest {
+        MessageRequest {
+            case_id: None,
+            message: text.to_string(),
+            sender_id: "lead@company.com".to_string(),
+            channel: models::MessageChannel::Email,
+            email_thread,
+            owner_id: None,
+            priority: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn message_becomes_a_case_with_its_tasks() {
+        let mock = services();
+        let llm = MockLlm::creating(vec![extracted("Send the budget review", 0.9), extracted("Maybe call Sam", 0.2)], "Added two tasks.");
+        let state = state(&mock, llm);
+
+        let Json(response) = process_message(
+            State(state.clone()),
+            SessionToken(Some("session-1".to_string())),
+            Json(MessageRequest { owner_id: Some(Uuid::from_u128(9)), ..message("Send the budget review, maybe call Sam", None) }),
+        )
+        .await
+        .expect("message processed");
+
+        assert_eq!(response.case_id.to_string(), CASE_ID);
+        assert_eq!(response.tasks_created.len(), 2);
+        let opened = mock.requests_to(Method::POST, "/api/v1/cases");
+        assert_eq!(opened.len(), 1);
+        assert_eq!(opened[0].body["user_id"], Uuid::from_u128(9).to_string());
+        assert_eq!(opened[0].body["urgency"], json!({ "score": 30, "reasons": ["From an important sender"] }));
+
+        // The unsure task goes to the review queue.
+        let created: Vec<Value> = mock.requests_to(Method::POST, "/api/v1/cases/*/tasks").into_iter().map(|r| r.body).collect();
+        assert_eq!(created[0]["needs_review"], false);
+        assert_eq!(created[1]["needs_review"], true);
+        assert_eq!(created[1]["source_message"], "Send the budget review, maybe call Sam");
+
+        // The message and the reply are recorded in the conversation.
+        let history = mock.requests_to(Method::POST, "/api/v1/cases/*/history");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].body["message"], "Added two tasks.");
+
+        // Everything is done on behalf of the sender, except looking up
+        // their organization's LLM settings, which include its key.
+        let (lookups, rest): (Vec<_>, Vec<_>) = mock.requests().into_iter().partition(|request| request.path == "/api/v1/llm/account");
+        assert_eq!(lookups.len(), 1);
+        assert!(lookups[0].bearer.is_none() && lookups[0].body["session_token"] == "session-1");
+        assert!(rest.iter().all(|request| request.bearer.as_deref() == Some("session-1")));
+    }
+
+    #[tokio::test]
+    async fn important_email_raises_the_priority_of_its_case_and_tasks() {
+        let mock = services();
+        let low = TaskData { priority: Priority::Low, tags: vec!["budget".to_string()], ..extracted("Send the budget review", 0.9) };
+        let critical = TaskData { priority: Priority::Critical, ..extracted("Fix the outage", 0.9) };
+        let state = state(&mock, MockLlm::creating(vec![low, critical], "Added two tasks."));
+
+        let request = MessageRequest {
+            priority: Some(Priority::High),
+            tags: vec!["Finance".to_string()],
+            ..message("Send the budget review and fix the outage", None)
+        };
+        let Json(response) = process_message(State(state), SessionToken(None), Json(request)).await.expect("message processed");
+        assert_eq!(response.tasks_created.len(), 2);
+
+        let opened = mock.requests_to(Method::POST, "/api/v1/cases");
+        assert_eq!(opened[0].body["priority"], "High");
+        assert_eq!(opened[0].body["tags"], json!(["Finance"]));
+        let created = mock.requests_to(Method::POST, "/api/v1/cases/*/tasks");
+        assert_eq!(created[0].body["priority"], "High");
+        assert_eq!(created[0].body["tags"], json!(["budget", "Finance"]));
+        assert_eq!(created[1].body["priority"], "Critical");
+    }
+
+    #[tokio::test]
+    async fn transcript_becomes_minutes_with_a_task_per_action_item() {
+        let mock = services();
+        let state = state(&mock, MockLlm::new(Vec::new()));
+        let transcript = TranscriptRequest {
+            transcript: "Ana: I'll send the deck by 2024-03-04.\nDecision: launch moves to April\nAction: Bob to book the room".to_string(),
+            title: Some("Launch sync".to_string()),
+            case_id: None,
+            held_at: None,
+        };
+
+        let Json(minutes) = process_transcript(State(state), SessionToken(Some("session-1".to_string())), Json(transcript))
+            .await
+            .expect("transcript processed");
+
+        assert_eq!(minutes.case_id.to_string(), CASE_ID);
+        assert_eq!(minutes.decisions, ["launch moves to April"]);
+        assert_eq!(minutes.action_items.len(), 2);
+        assert!(minutes.action_items.iter().all(|item| item.task_id.is_some()));
+
+        let opened = mock.requests_to(Method::POST, "/api/v1/cases");
+        assert_eq!(opened[0].body["title"], "Launch sync");
+        let created = mock.requests_to(Method::POST, "/api/v1/cases/*/tasks");
+        assert_eq!(created[0].body["title"], "Book the room");
+        assert_eq!(created[0].body["metadata"], json!({ "owner": "Bob" }));
+        assert_eq!(created[1].body["due_date"], "2024-03-04T17:00:00Z");
+
+        let history = mock.requests_to(Method::POST, "/api/v1/cases/*/history");
+        assert_eq!(history[0].body["metadata"]["event"], "meeting_minutes");
+        assert!(history[0].body["message"].as_str().unwrap().starts_with("Minutes: Launch sync"));
+    }
+
+    fn invite() -> InviteRequest {
+        InviteRequest {
+            invite: MeetingInvite {
+                title: "Budget review".to_string(),
+                starts_at: "2024-03-04T15:00:00Z".parse().unwrap(),
+                ends_at: Some("2024-03-04T15:30:00Z".parse().unwrap()),
+                location: Some("Room 4".to_string()),
+                organizer: Some("lead@company.com".to_string()),
+                attendees: vec!["ana@company.com".to_string()],
+                description: None,
+                uid: Some("uid-1@company.com".to_string()),
+            },
+            sender_id: "lead@company.com".to_string(),
+            email_thread: None,
+            owner_id: Some(Uuid::from_u128(9)),
+            priority: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn invitation_becomes_a_meeting_without_extraction() {
+        let mock = services();
+        let llm = MockLlm::new(Vec::new());
+        let runs = llm.runs.clone();
+        let state = state(&mock, llm);
+
+        let Json(response) = process_invite(State(state), SessionToken(None), Json(invite())).await.expect("invitation filed");
+
+        assert_eq!(response.tasks_created.len(), 1);
+        assert!(runs.lock().unwrap().is_empty(), "no extraction");
+        let opened = mock.requests_to(Method::POST, "/api/v1/cases");
+        assert_eq!(opened[0].body["tags"], json!(["meeting"]));
+        assert_eq!(opened[0].body["user_id"], Uuid::from_u128(9).to_string());
+        let created = mock.requests_to(Method::POST, "/api/v1/cases/*/tasks");
+        assert_eq!(created[0].body["task_type"], "Meeting");
+        assert_eq!(created[0].body["due_date"], "2024-03-04T15:00:00Z");
+        assert_eq!(created[0].body["estimate_minutes"], 30);
+        assert_eq!(
+            created[0].body["metadata"],
+            json!({ "attendees": ["ana@company.com"], "location": "Room 4", "organizer": "lead@company.com", "invite_uid": "uid-1@company.com" })
+        );
+    }
+
+    #[tokio::test]
+    async fn updated_invitation_reschedules_its_meeting() {
+        let mock = services();
+        let mut filed = task("Budget review", "Pending");
+        filed["task_type"] = json!("Meeting");
+        filed["metadata"] = json!({ "invite_uid": "uid-1@company.com", "meeting_conflicts": [] });
+        mock.reply(Method::GET, "/api/v1/cases/*/tasks", json!([filed]))
+            .reply(Method::PUT, "/api/v1/tasks/*", filed.clone());
+        let state = state(&mock, MockLlm::new(Vec::new()));
+
+        let mut request = invite();
+        request.invite.starts_at = "2024-03-05T09:00:00Z".parse().unwrap();
+        request.invite.ends_at = None;
+        let Json(response) = process_invite(State(state), SessionToken(None), Json(request)).await.expect("invitation filed");
+
+        assert!(response.tasks_created.is_empty());
+        assert_eq!(response.tasks_updated.len(), 1);
+        let updated = mock.requests_to(Method::PUT, "/api/v1/tasks/*");
+        assert_eq!(updated[0].body["due_date"], "2024-03-05T09:00:00Z");
+        assert_eq!(updated[0].body["metadata"]["meeting_conflicts"], json!([]));
+        assert_eq!(updated[0].body["metadata"]["location"], "Room 4");
+    }
+
+    #[tokio::test]
+    async fn newsletters_are_filed_for_review_without_extraction() {
+        let mock = services();
+        mock.reply(Method::GET, "/api/v1/cases", json!([]));
+        let llm = MockLlm::new(Vec::new());
+        let runs = llm.runs.clone();
+        let state = state(&mock, llm);
+
+        let request = MessageRequest { owner_id: Some(Uuid::from_u128(9)), tags: vec!["newsletter".to_string()], ..message("Our spring sale", None) };
+        let Json(response) = process_noise(State(state.clone()), SessionToken(None), Json(request.clone())).await.expect("email filed");
+
+        assert!(response.tasks_created.is_empty());
+        assert!(runs.lock().unwrap().is_empty(), "no extraction");
+        let opened = mock.requests_to(Method::POST, "/api/v1/cases");
+        assert_eq!(opened[0].body["priority"], "Low");
+        assert_eq!(opened[0].body["tags"], json!([NOISE_REVIEW_TAG]));
+        let history = mock.requests_to(Method::POST, "/api/v1/cases/*/history");
+        assert_eq!(history[0].body["metadata"]["tags"], json!(["newsletter"]));
+
+        // Later ones join the open review case of the same owner.
+        let mut review = case();
+        review["user_id"] = json!(Uuid::from_u128(9));
+        review["tags"] = json!([NOISE_REVIEW_TAG]);
+        mock.reply(Method::GET, "/api/v1/cases", json!([review]));
+        let Json(again) = process_noise(State(state), SessionToken(None), Json(request)).await.expect("email filed");
+        assert_eq!(again.actions_taken, ["Filed for review"]);
+        assert_eq!(mock.requests_to(Method::POST, "/api/v1/cases").len(), 1);
+        assert_eq!(mock.requests_to(Method::POST, "/api/v1/cases/*/history").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn empty_transcripts_are_rejected() {
+        let mock = services();
+        let state = state(&mock, MockLlm::new(Vec::new()));
+        let transcript = TranscriptRequest { transcript: " \n".to_string(), title: None, case_id: None, held_at: None };
+        let result = process_transcript(State(state), SessionToken(None), Json(transcript)).await;
+        assert!(matches!(result, Err(common::ServiceError::BadRequest(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn email_reply_joins_the_case_of_its_thread() {
+        let mock = services();
+        mock.reply(Method::GET, "/api/v1/cases/by-email-thread", json!([case()]));
+        let llm = MockLlm::new(vec![AgentStep::Reply("Noted.".to_string())]);
+        let state = state(&mock, llm);
+
+        let thread = EmailThread {
+            message_id: Some("<reply@company.com>".to_string()),
+            conversation_id: None,
+            in_reply_to: Some("<kickoff@company.com>".to_string()),
+        };
+        let Json(response) = process_message(State(state), SessionToken(None), Json(message("Sounds good", Some(thread))))
+            .await
+            .expect("reply processed");
+
+        assert_eq!(response.case_id.to_string(), CASE_ID);
+        assert!(mock.requests_to(Method::POST, "/api/v1/cases").is_empty(), "no new case");
+        let recorded = mock.requests_to(Method::POST, "/api/v1/cases/*/email-thread");
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].body["message_id"], "<reply@company.com>");
+    }
+
+    #[tokio::test]
+    async fn email_reply_only_joins_a_thread_case_of_its_owner() {
+        let mock = services();
+        let (alice, bob) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let mut alices = case();
+        alices["user_id"] = json!(alice);
+        mock.reply(Method::GET, "/api/v1/cases/by-email-thread", json!([alices]));
+        let state = state(&mock, MockLlm::new(vec![AgentStep::Reply("Noted.".to_string())]));
+
+        let thread = EmailThread { in_reply_to: Some("<kickoff@company.com>".to_string()), ..Default::default() };
+        let to_bob = MessageRequest { owner_id: Some(bob), ..message("Sounds good", Some(thread.clone())) };
+        let Json(response) = process_message(State(state.clone()), SessionToken(None), Json(to_bob)).await.expect("reply processed");
+        assert_eq!(response.actions_taken[0], "Created new case");
+        let opened = mock.requests_to(Method::POST, "/api/v1/cases");
+        assert_eq!(opened.len(), 1, "not filed in Alice's case");
+        assert_eq!(opened[0].body["user_id"], bob.to_string());
+
+        let to_alice = MessageRequest { owner_id: Some(alice), ..message("Sounds good", Some(thread)) };
+        let Json(response) = process_message(State(state), SessionToken(None), Json(to_alice)).await.expect("reply processed");
+        assert_eq!(response.actions_taken[0], "Added reply to the case of its email thread");
+        assert_eq!(mock.requests_to(Method::POST, "/api/v1/cases").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failing_service_fails_the_message() {
+        let mock = services();
+        mock.reply_with_status(Method::POST, "/api/v1/cases", 503, json!({ "error": "unavailable" }));
+        let llm = MockLlm::creating(vec![extracted("Anything", 0.9)], "Added.");
+        let state = state(&mock, llm);
+
+        let result = process_message(State(state.clone()), SessionToken(None), Json(message("Do something", None))).await;
+
+        assert!(matches!(result, Err(common::ServiceError::HttpClient(e)) if e.status().map(|s| s.as_u16()) == Some(503)));
+        assert!(mock.requests_to(Method::POST, "/api/v1/cases/*/tasks").is_empty());
+    }
+
+    #[tokio::test]
+    async fn stream_reports_case_tokens_tasks_and_done() {
+        let mock = services();
+        let llm = MockLlm::creating(vec![extracted("Send the budget review", 0.9)], "Added one task.");
+        let state = state(&mock, llm);
+
+        let sse = process_message_stream(State(state), SessionToken(None), Json(message("Send the budget review", None))).await;
+        let body = axum::response::IntoResponse::into_response(sse).into_body();
+        let body = String::from_utf8(axum::body::to_bytes(body, usize::MAX).await.unwrap().to_vec()).unwrap();
+
+        let events: Vec<&str> = body.lines().filter_map(|line| line.strip_prefix("event: ")).collect();
+        assert_eq!(events, ["case", "task", "token", "done"]);
+    }
+
+    #[tokio::test]
+    async fn missing_details_are_asked_and_then_offered_as_answered() {
+        let question = json!({
+            "id": Uuid::nil(),
+            "case_id": CASE_ID,
+            "task_id": Uuid::nil(),
+            "user_id": Uuid::nil(),
+            "question": "When is 'Send the budget review' due?",
+            "missing": ["DueDate"],
+            "channel": "Email",
+            "recipient": "lead@company.com",
+            "status": "Pending",
+            "created_at": Utc::now(),
+            "answered_at": null,
+        });
+        let mock = services();
+        mock.reply(Method::POST, "/api/v1/cases/*/questions", question.clone());
+        let llm = MockLlm::creating(vec![extracted("Send the budget review", 0.9)], "Added one task.");
+
+        let Json(response) = process_message(State(state(&mock, llm)), SessionToken(None), Json(message("Send the budget review asap", None)))
+            .await
+            .expect("message processed");
+
+        // Urgent work without a due date gets a question, asked in the reply.
+        let asked = mock.requests_to(Method::POST, "/api/v1/cases/*/questions");
+        assert_eq!(asked.len(), 1);
+        assert_eq!(asked[0].body["missing"], json!(["DueDate"]));
+        assert_eq!(asked[0].body["recipient"], "lead@company.com");
+        assert_eq!(response.questions.len(), 1);
+        assert!(response.response.ends_with("\n\nWhen is 'Send the budget review' due?"));
+
+        // The next message on the case is shown the question and answers it.
+        mock.reply(Method::GET, "/api/v1/cases/*/questions", json!([question]))
+            .reply(Method::POST, "/api/v1/cases/*/questions/answered", json!([]));
+        let llm = MockLlm::new(vec![AgentStep::Reply("Noted.".to_string())]);
+        let runs = llm.runs.clone();
+        let mut answer = message("By Friday", None);
+        answer.case_id = Some(CASE_ID.parse().unwrap());
+
+        let Json(response) = process_message(State(state(&mock, llm)), SessionToken(None), Json(answer)).await.expect("answer processed");
+
+        assert_eq!(response.response, "Noted.");
+        assert_eq!(runs.lock().unwrap()[0].context.open_questions.len(), 1);
+        let answered = mock.requests_to(Method::POST, "/api/v1/cases/*/questions/answered");
+        assert_eq!(answered.len(), 1);
+        assert_eq!(answered[0].body["question_ids"], json!([Uuid::nil()]));
+    }
+
+    #[tokio::test]
+    async fn weekly_summary_is_recorded_and_emailed_to_owners_who_opted_in() {
+        let mock = services();
+        let owner = |email_summaries: bool| {
+            json!({
+                "id": Uuid::nil(),
+                "email": "owner@company.com",
+                "full_name": "Case Owner",
+                "organization": null,
+                "is_active": true,
+                "created_at": Utc::now(),
+                "last_login": null,
+                "preferences": { "notifications": { "weekly_summary_email": email_summaries } },
+            })
+        };
+        mock.reply(Method::GET, "/api/v1/cases", json!([case(), invoice_case("Closed")]))
+            .reply(Method::GET, "/api/v1/cases/*/tasks", json!([task("Send the budget review", "Pending")]))
+            .reply(Method::GET, "/api/v1/cases/*/owner", owner(true))
+            .reply_with_status(Method::POST, "/api/v1/email/send", 202, json!(null));
+        let state = state(&mock, MockLlm::new(Vec::new()));
+
+        send_weekly_summaries(&state, chrono::Duration::days(7)).await.expect("summaries sent");
+
+        // Only the open case is summarized; without a model the activity
+        // report is the summary.
+        let recorded = mock.requests_to(Method::POST, "/api/v1/cases/*/history");
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].body["sender"], "System");
+        assert_eq!(recorded[0].body["metadata"]["event"], "weekly_summary");
+        let summary = recorded[0].body["message"].as_str().unwrap().to_string();
+        assert!(summary.contains("New tasks:\n- Send the budget review"));
+        let emailed = mock.requests_to(Method::POST, "/api/v1/email/send");
+        assert_eq!(emailed.len(), 1);
+        assert_eq!(emailed[0].body["to"], "owner@company.com");
+        assert_eq!(emailed[0].body["body"], summary.as_str());
+
+        mock.reply(Method::GET, "/api/v1/cases/*/owner", owner(false));
+        send_weekly_summaries(&state, chrono::Duration::days(7)).await.expect("summaries sent");
+        assert_eq!(mock.requests_to(Method::POST, "/api/v1/cases/*/history").len(), 2);
+        assert_eq!(mock.requests_to(Method::POST, "/api/v1/email/send").len(), 1, "no email without opting in");
+    }
+
+    const INVOICE_CASE: &str = "00000000-0000-0000-0000-0000000000aa";
+
+    fn invoice_case(status: &str) -> Value {
+        let mut case = case();
+        case["id"] = json!(INVOICE_CASE);
+        case["title"] = json!("Invoice 1042 from Acme");
+        case["status"] = json!(status);
+        case
+    }
+
+    #[tokio::test]
+    async fn agent_finds_and_closes_an_earlier_case() {
+        let mock = services();
+        mock.reply(Method::GET, "/api/v1/cases", json!([case(), invoice_case("Open")]))
+            .reply(Method::GET, "/api/v1/cases/*/tasks", json!([]))
+            .reply(Method::PUT, "/api/v1/cases/*/state", invoice_case("Closed"));
+        let invoice: Uuid = INVOICE_CASE.parse().unwrap();
+        let llm = MockLlm::new(vec![
+            calls(&[Tool::LookupHistory(LookupArgs { query: Some("the invoice".to_string()), include_closed: false })]),
+            calls(&[Tool::CloseCase(CloseCaseArgs { case_id: Some(invoice), status: None })]),
+            AgentStep::Reply("Closed the invoice case.".to_string()),
+        ]);
+        let runs = llm.runs.clone();
+        let state = state(&mock, llm);
+
+        let Json(response) = process_message(
+            State(state.clone()),
+            SessionToken(Some("session-1".to_string())),
+            Json(message("Please close the case about the invoice", None)),
+        )
+        .await
+        .expect("message processed");
+
+        // The lookup found only the invoice case, and the model saw it.
+        let runs = runs.lock().unwrap();
+        let AgentTurn::Result { content, .. } = &runs[1].turns[1] else { panic!("lookup result") };
+        let found: Value = serde_json::from_str(content).unwrap();
+        assert_eq!(found.as_array().unwrap().len(), 1);
+        assert_eq!(found[0]["case_id"], INVOICE_CASE);
+
+        let closed = mock.requests_to(Method::PUT, "/api/v1/cases/*/state");
+        assert_eq!(closed.len(), 1);
+        assert!(closed[0].path.contains(INVOICE_CASE));
+        assert_eq!(closed[0].body["status"], "Closed");
+        assert!(response.actions_taken.contains(&"Closed case: Invoice 1042 from Acme".to_string()));
+        assert_eq!(response.response, "Closed the invoice case.");
+    }
+
+    #[tokio::test]
+    async fn without_a_session_tools_stay_in_the_messages_case() {
+        let mock = services();
+        let invoice: Uuid = INVOICE_CASE.parse().unwrap();
+        let llm = MockLlm::new(vec![calls(&[Tool::CloseCase(CloseCaseArgs { case_id: Some(invoice), status: None })])]);
+        let runs = llm.runs.clone();
+        let state = state(&mock, llm);
+
+        let Json(response) = process_message(State(state.clone()), SessionToken(None), Json(message("Close the invoice case", None)))
+            .await
+            .expect("message processed");
+
+        assert!(mock.requests_to(Method::PUT, "/api/v1/cases/*/state").is_empty());
+        assert!(!response.actions_taken.iter().any(|action| action.contains("case: ")));
+        let runs = runs.lock().unwrap();
+        let AgentTurn::Result { content, .. } = &runs[1].turns[1] else { panic!("close result") };
+        assert!(content.contains("Only the current case can be closed"));
+    }
+
+    #[tokio::test]
+    async fn agent_stops_after_its_step_budget() {
+        let mock = services();
+        let looping: Vec<AgentStep> = (0..10).map(|_| calls(&[Tool::LookupHistory(LookupArgs::default())])).collect();
+        mock.reply(Method::GET, "/api/v1/cases/*", case()).reply(Method::GET, "/api/v1/cases/*/tasks", json!([]));
+        let llm = MockLlm::new(looping);
+        let runs = llm.runs.clone();
+        let state = state(&mock, llm);
+
+        let Json(response) = process_message(State(state.clone()), SessionToken(None), Json(message("Anything?", None)))
+            .await
+            .expect("message processed");
+
+        assert_eq!(runs.lock().unwrap().len(), agent::DEFAULT_MAX_STEPS);
+        assert!(response.response.starts_with("Done: "));
+    }
+}
@@ -0,0 +1,177 @@
+//! Meeting minutes from transcripts.
+//!
+//! A transcript is usually far longer than a message, so it is condensed
+//! chunk by chunk first, keeping commitments, owners and dates. The model
+//! then writes the minutes as JSON: a summary, the decisions taken and the
+//! action items with who took them on and by when. Each action item becomes
+//! a task. Without a model, labelled lines (`Action: ...`, `Decision: ...`)
+//! and commitments such as `Ana: I'll send the deck by 2024-03-01` are
+//! picked out instead.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+use crate::language::Language;
+
+static ACTION_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?im)^\s*(?:[-*]\s*)?(?:action(?:\s+item)?|todo|to-do)\s*[:\-]\s*(.+?)\s*$").expect("action pattern is valid")
+});
+static COMMITMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?im)^\s*([A-Za-z][\w .'-]{0,40}?)\s*:\s*(?:i'll|i will|i'm going to|i am going to)\s+(.+?)\s*$")
+        .expect("commitment pattern is valid")
+});
+static DECISION_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?im)^\s*(?:[-*]\s*)?(?:decision|decided|agreed)\s*[:\-]\s*(.+?)\s*$").expect("decision pattern is valid")
+});
+/// `Bob to book the room`, `Bob will book the room`.
+static ASSIGNED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^@?([A-Z][\w.-]*)\s+(?:to|will)\s+(.+)$").expect("assignment pattern is valid"));
+static ISO_DATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(\d{4}-\d{2}-\d{2})\b").expect("date pattern is valid"));
+/// A trailing `by`, left over once the date after it is removed.
+static DANGLING_BY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\s+(?:by|on|before|until)$").expect("preposition pattern is valid"));
+
+/// The minutes as the model writes them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MinutesDraft {
+    pub summary: String,
+    #[serde(default)]
+    pub decisions: Vec<String>,
+    #[serde(default)]
+    pub action_items: Vec<ActionItemDraft>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionItemDraft {
+    pub title: String,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub due_date: Option<DateTime<Utc>>,
+}
+
+impl MinutesDraft {
+    /// Reads the model's answer, which may be wrapped in a code fence.
+    pub fn parse(answer: &str) -> Result<Self, serde_json::Error> {
+        let json = answer.trim().trim_start_matches("```json").trim_start_matches("```").trim_end_matches("```");
+        serde_json::from_str(json.trim())
+    }
+
+    /// Minutes from labelled lines and commitments alone. Relative due
+    /// dates count from `held_at`.
+    pub fn fallback(transcript: &str, language: Language, held_at: DateTime<Utc>) -> Self {
+        let mut action_items = Vec::new();
+        for captures in ACTION_LINE.captures_iter(transcript) {
+            let item = &captures[1];
+            action_items.push(match ASSIGNED.captures(item) {
+                Some(assigned) => action_item(&assigned[2], Some(&assigned[1]), language, held_at),
+                None => action_item(item, None, language, held_at),
+            });
+        }
+        for captures in COMMITMENT.captures_iter(transcript) {
+            action_items.push(action_item(&captures[2], Some(&captures[1]), language, held_at));
+        }
+        let decisions: Vec<String> = DECISION_LINE.captures_iter(transcript).map(|captures| captures[1].to_string()).collect();
+
+        Self {
+            summary: format!("{} action items and {} decisions noted.", action_items.len(), decisions.len()),
+            decisions,
+            action_items,
+        }
+    }
+
+    /// The minutes as text, for the case's conversation.
+    pub fn render(&self, title: &str) -> String {
+        let mut text = format!("Minutes: {}\n\n{}", title, self.summary);
+        if !self.decisions.is_empty() {
+            text.push_str("\n\nDecisions:");
+            for decision in &self.decisions {
+                text.push_str(&format!("\n- {}", decision));
+            }
+        }
+        if !self.action_items.is_empty() {
+            text.push_str("\n\nAction items:");
+            for item in &self.action_items {
+                text.push_str(&format!("\n- {}", item.title));
+                let details: Vec<String> = item
+                    .owner
+                    .clone()
+                    .into_iter()
+                    .chain(item.due_date.map(|due| format!("due {}", due.format("%Y-%m-%d"))))
+                    .collect();
+                if !details.is_empty() {
+                    text.push_str(&format!(" ({})", details.join(", ")));
+                }
+            }
+        }
+        text
+    }
+}
+
+fn action_item(text: &str, owner: Option<&str>, language: Language, held_at: DateTime<Utc>) -> ActionItemDraft {
+    let keywords = language.keywords();
+    let lower = text.to_lowercase();
+    let relative = [(keywords.today, 0), (keywords.tomorrow, 1), (keywords.next_week, 7)]
+        .into_iter()
+        .find_map(|(words, days)| words.iter().find(|word| lower.contains(**word)).map(|word| (*word, days)));
+    // The date is cut from the title when it ends it.
+    let (due_date, said) = match ISO_DATE.captures(text) {
+        Some(captures) => {
+            let due = NaiveDate::parse_from_str(&captures[1], "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(17, 0, 0));
+            (due.map(|due| due.and_utc()), Some(captures[1].to_string()))
+        }
+        None => match relative {
+            Some((word, days)) => (Some(held_at + Duration::days(days)), Some(word.to_string())),
+            None => (None, None),
+        },
+    };
+
+    let mut title = text.trim().trim_end_matches(['.', ',', ';', '!']).to_string();
+    let ends_title = |said: &String| title.to_lowercase().ends_with(said.as_str());
+    if let Some(cut) = said.filter(ends_title).and_then(|said| title.len().checked_sub(said.len())).filter(|cut| title.is_char_boundary(*cut)) {
+        title.truncate(cut);
+        title = DANGLING_BY.replace(title.trim_end(), "").into_owned();
+    }
+    let title = title.trim().trim_end_matches(['.', ',', ';', '!']);
+    let mut chars = title.chars();
+    let title = chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default();
+    ActionItemDraft { title, owner: owner.map(|owner| owner.trim().to_string()), due_date }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn labelled_lines_and_commitments_become_action_items() {
+        let held_at = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let transcript = "Ana: Thanks for joining.\n\
+                          Ana: I'll send the deck by 2024-03-04.\n\
+                          Decision: launch moves to April\n\
+                          Action: Bob to book the room tomorrow\n\
+                          - TODO: update the budget sheet";
+        let minutes = MinutesDraft::fallback(transcript, Language::English, held_at);
+
+        let items: Vec<(&str, Option<&str>)> =
+            minutes.action_items.iter().map(|item| (item.title.as_str(), item.owner.as_deref())).collect();
+        assert_eq!(
+            items,
+            [("Book the room", Some("Bob")), ("Update the budget sheet", None), ("Send the deck", Some("Ana"))]
+        );
+        assert_eq!(minutes.action_items[0].due_date, Some(held_at + Duration::days(1)));
+        assert_eq!(minutes.action_items[2].due_date, Some(Utc.with_ymd_and_hms(2024, 3, 4, 17, 0, 0).unwrap()));
+        assert_eq!(minutes.decisions, ["launch moves to April"]);
+        assert!(minutes.render("Launch sync").contains("\n- Send the deck (Ana, due 2024-03-04)"));
+    }
+
+    #[test]
+    fn model_answers_may_be_fenced() {
+        let answer = "```json\n{\"summary\": \"Agreed on the launch.\", \"action_items\": [{\"title\": \"Book the room\", \"owner\": \"Bob\"}]}\n```";
+        let minutes = MinutesDraft::parse(answer).unwrap();
+        assert_eq!(minutes.action_items[0].owner.as_deref(), Some("Bob"));
+        assert!(minutes.decisions.is_empty());
+    }
+}
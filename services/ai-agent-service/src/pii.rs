@@ -0,0 +1,341 @@
+//! Personal data redaction before message content goes to an external LLM.
+//!
+//! Email addresses, phone numbers, payment card numbers and names are
+//! replaced with placeholders such as `[EMAIL_1]` in everything sent to the
+//! model, and put back in what it answers, so the tasks it creates still say
+//! whom to call.  The originals never leave the service: the placeholders of
+//! a request only exist while it is made.  Local models get the content as
+//! it is.
+//!
+//! Names are recognized by their context rather than a trained model:
+//! honorifics (`Dr. Ana Ruiz`), greetings (`Hi Ana`), sign-offs and verbs
+//! such as `call` or `meet` before a capitalized name.  Once found, every
+//! other mention of the name or its parts is replaced too.
+
+use common::config::AgentConfig;
+use regex::Regex;
+use std::sync::LazyLock;
+use tracing::warn;
+
+/// Redacted when `AI_PII_REDACTION` is unset.
+const DEFAULT_KINDS: &str = "email,phone,card";
+
+/// The longest placeholder a streamed fragment may end in the middle of.
+const MAX_PLACEHOLDER_LEN: usize = 16;
+
+static EMAIL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}").expect("email pattern is valid"));
+static CARD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(?:\d[ -]?){12,18}\d\b").expect("card pattern is valid"));
+static PHONE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\+?\(?\d[\d ().-]{6,}\d").expect("phone pattern is valid"));
+/// Dates and year ranges, which look like phone numbers.
+static NOT_PHONE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:\d{4}-\d{2}-\d{2}|\d{1,2}[./-]\d{1,2}[./-]\d{2,4}|\d{4}\s*-\s*\d{4})$").expect("date pattern is valid")
+});
+static NAME_CUE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?:(?i:\b(?:hi|hello|dear|hey|call|email|meet|ask|tell|ping|text|remind|with|from|cc))[ \t]+(?:(?i:mr|mrs|ms|dr|prof)\.?[ \t]+)?|(?i:\b(?:mr|mrs|ms|dr|prof))\.?[ \t]+)(\p{Lu}\p{Ll}+(?:[ \t]+\p{Lu}\p{Ll}+)?)",
+    )
+    .expect("name pattern is valid")
+});
+static SIGN_OFF: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?im)^[ \t]*(?:thanks|thank you|regards|best|best regards|kind regards|cheers)[,.!]?[ \t]*\r?\n[ \t]*(\p{Lu}\p{Ll}+(?:[ \t]+\p{Lu}\p{Ll}+)?)[ \t]*$")
+        .expect("sign-off pattern is valid")
+});
+
+/// Capitalized words after a cue that aren't names.
+const NOT_NAMES: &[&str] = &[
+    "monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday", "january", "february", "march",
+    "april", "may", "june", "july", "august", "september", "october", "november", "december", "the", "team", "all",
+    "everyone", "there", "them", "him", "her", "me", "us", "you", "support", "sales", "finance", "legal", "it", "hr",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiKind {
+    Email,
+    Phone,
+    Card,
+    Name,
+}
+
+impl PiiKind {
+    fn label(self) -> &'static str {
+        match self {
+            PiiKind::Email => "EMAIL",
+            PiiKind::Phone => "PHONE",
+            PiiKind::Card => "CARD",
+            PiiKind::Name => "NAME",
+        }
+    }
+}
+
+/// Redacts the configured kinds of personal data.
+#[derive(Debug, Clone, Default)]
+pub struct PiiScrubber {
+    kinds: Vec<PiiKind>,
+}
+
+impl PiiScrubber {
+    /// `kinds` is a comma-separated list of `email`, `phone`, `card` and
+    /// `name`, as in `AI_PII_REDACTION`; `none` turns redaction off.
+    pub fn new(kinds: &str) -> Self {
+        let kinds = kinds
+            .split(',')
+            .map(|kind| kind.trim().to_lowercase())
+            .filter(|kind| !kind.is_empty() && kind != "none")
+            .filter_map(|kind| match kind.as_str() {
+                "email" | "emails" => Some(PiiKind::Email),
+                "phone" | "phones" => Some(PiiKind::Phone),
+                "card" | "cards" => Some(PiiKind::Card),
+                "name" | "names" => Some(PiiKind::Name),
+                other => {
+                    warn!("Ignoring unknown kind of personal data {:?}", other);
+                    None
+                }
+            })
+            .collect();
+        Self { kinds }
+    }
+
+    /// The kinds of `agent.pii_redaction`, or the defaults when unset.
+    pub fn from_config(config: &AgentConfig) -> Self {
+        match &config.pii_redaction {
+            Some(kinds) => Self::new(&kinds.join(",")),
+            None => Self::new(DEFAULT_KINDS),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.kinds.is_empty()
+    }
+
+    /// Redacts every text of a request in place, with the same placeholder
+    /// for the same value throughout.
+    pub fn redact_all(&self, texts: Vec<&mut String>) -> Redaction {
+        let mut redaction = Redaction::default();
+        if !self.is_enabled() {
+            return redaction;
+        }
+        // Names are collected first, so a name introduced late in the
+        // request is also replaced where it was mentioned earlier.
+        if self.kinds.contains(&PiiKind::Name) {
+            for text in &texts {
+                redaction.learn_names(text);
+            }
+        }
+        for text in texts {
+            *text = self.redact(text, &mut redaction);
+        }
+        redaction
+    }
+
+    fn redact(&self, text: &str, redaction: &mut Redaction) -> String {
+        // Earlier kinds win where matches overlap: the digits of an email
+        // address aren't a phone number, nor is a card number.
+        let mut spans: Vec<(std::ops::Range<usize>, PiiKind)> = Vec::new();
+        let mut take = |range: std::ops::Range<usize>, kind: PiiKind| {
+            if !spans.iter().any(|(taken, _)| taken.start < range.end && range.start < taken.end) {
+                spans.push((range, kind));
+            }
+        };
+        for kind in [PiiKind::Email, PiiKind::Card, PiiKind::Phone, PiiKind::Name] {
+            if !self.kinds.contains(&kind) {
+                continue;
+            }
+            match kind {
+                PiiKind::Email => EMAIL.find_iter(text).for_each(|found| take(found.range(), kind)),
+                PiiKind::Card => CARD
+                    .find_iter(text)
+                    .filter(|found| luhn(found.as_str()))
+                    .for_each(|found| take(found.range(), kind)),
+                PiiKind::Phone => PHONE
+                    .find_iter(text)
+                    .filter(|found| is_phone(found.as_str()))
+                    .for_each(|found| take(found.range(), kind)),
+                PiiKind::Name => {
+                    for name in &redaction.names {
+                        for (start, _) in text.match_indices(name.as_str()) {
+                            let end = start + name.len();
+                            if !is_letter(text[..start].chars().next_back()) && !is_letter(text[end..].chars().next()) {
+                                take(start..end, kind);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        spans.sort_by_key(|(range, _)| range.start);
+
+        let mut redacted = String::with_capacity(text.len());
+        let mut last = 0;
+        for (range, kind) in spans {
+            redacted.push_str(&text[last..range.start]);
+            redacted.push_str(&redaction.placeholder(kind, &text[range.clone()]));
+            last = range.end;
+        }
+        redacted.push_str(&text[last..]);
+        redacted
+    }
+}
+
+/// The placeholders of one request and the values they stand for.
+#[derive(Debug, Default)]
+pub struct Redaction {
+    values: Vec<(String, String)>,
+    /// Names found in the request, longest first.
+    names: Vec<String>,
+}
+
+impl Redaction {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn learn_names(&mut self, text: &str) {
+        let found = NAME_CUE.captures_iter(text).chain(SIGN_OFF.captures_iter(text)).map(|captures| captures[1].to_string());
+        for name in found {
+            let parts: Vec<&str> = name.split_whitespace().collect();
+            if parts.iter().any(|part| NOT_NAMES.contains(&part.to_lowercase().as_str())) {
+                continue;
+            }
+            let candidates = std::iter::once(parts.join(" ")).chain(parts.iter().filter(|part| part.len() > 1).map(|part| part.to_string()));
+            for candidate in candidates {
+                if !self.names.contains(&candidate) {
+                    self.names.push(candidate);
+                }
+            }
+        }
+        self.names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+    }
+
+    fn placeholder(&mut self, kind: PiiKind, value: &str) -> String {
+        if let Some((placeholder, _)) = self.values.iter().find(|(_, original)| original == value) {
+            return placeholder.clone();
+        }
+        let number = self.values.iter().filter(|(placeholder, _)| placeholder[1..].starts_with(kind.label())).count() + 1;
+        let placeholder = format!("[{}_{}]", kind.label(), number);
+        self.values.push((placeholder.clone(), value.to_string()));
+        placeholder
+    }
+
+    /// `text` with the original values back in place of the placeholders.
+    pub fn restore(&self, text: &str) -> String {
+        self.values.iter().fold(text.to_string(), |text, (placeholder, original)| text.replace(placeholder.as_str(), original))
+    }
+
+    /// [`restore`](Self::restore) inside JSON strings, such as tool call
+    /// arguments.
+    pub fn restore_json(&self, json: &str) -> String {
+        self.values.iter().fold(json.to_string(), |json, (placeholder, original)| {
+            let escaped = serde_json::to_string(original).unwrap_or_default();
+            json.replace(placeholder.as_str(), escaped.trim_matches('"'))
+        })
+    }
+
+    /// Restores streamed text, which may split a placeholder.
+    pub fn restorer(&self) -> Restorer<'_> {
+        Restorer { redaction: self, pending: String::new() }
+    }
+}
+
+pub struct Restorer<'a> {
+    redaction: &'a Redaction,
+    /// The end of the text so far, when it may be the start of a
+    /// placeholder.
+    pending: String,
+}
+
+impl Restorer<'_> {
+    /// The restored text that can be shown so far.
+    pub fn push(&mut self, fragment: &str) -> String {
+        self.pending.push_str(fragment);
+        let hold = self
+            .pending
+            .rfind('[')
+            .filter(|open| !self.pending[*open..].contains(']') && self.pending.len() - open < MAX_PLACEHOLDER_LEN)
+            .unwrap_or(self.pending.len());
+        let ready: String = self.pending.drain(..hold).collect();
+        self.redaction.restore(&ready)
+    }
+
+    /// The rest of the text, once the stream ends.
+    pub fn finish(self) -> String {
+        self.redaction.restore(&self.pending)
+    }
+}
+
+fn is_letter(c: Option<char>) -> bool {
+    c.is_some_and(char::is_alphanumeric)
+}
+
+fn is_phone(candidate: &str) -> bool {
+    let digits = candidate.chars().filter(char::is_ascii_digit).count();
+    (8..=15).contains(&digits) && !NOT_PHONE.is_match(candidate.trim())
+}
+
+/// Whether the digits pass the Luhn check all card numbers do.
+fn luhn(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| match index % 2 {
+            0 => *digit,
+            _ if *digit * 2 > 9 => *digit * 2 - 9,
+            _ => *digit * 2,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contact_details_and_cards_are_replaced_and_restored() {
+        let scrubber = PiiScrubber::new(DEFAULT_KINDS);
+        let mut message = "Email ana.ruiz@example.com or call +1 (555) 010-7788 about card 4111 1111 1111 1111, \
+                           due 2026-10-20. Order 1234 5678 9012 3456 is fine. Copy ana.ruiz@example.com."
+            .to_string();
+        let redaction = scrubber.redact_all(vec![&mut message]);
+        assert_eq!(
+            message,
+            "Email [EMAIL_1] or call [PHONE_1] about card [CARD_1], due 2026-10-20. \
+             Order 1234 5678 9012 3456 is fine. Copy [EMAIL_1]."
+        );
+        assert_eq!(redaction.len(), 3);
+        assert_eq!(redaction.restore("Call [PHONE_1]"), "Call +1 (555) 010-7788");
+    }
+
+    #[test]
+    fn names_are_found_by_their_context() {
+        let scrubber = PiiScrubber::new("name");
+        let mut examples = "Ana said the deck is late".to_string();
+        let mut message = "Hi team, please meet Dr. Ana Ruiz on Monday and call Bob.\n\nThanks,\nCarla".to_string();
+        let redaction = scrubber.redact_all(vec![&mut examples, &mut message]);
+        assert_eq!(examples, "[NAME_1] said the deck is late");
+        assert_eq!(message, "Hi team, please meet Dr. [NAME_2] on Monday and call [NAME_3].\n\nThanks,\n[NAME_4]");
+        assert_eq!(redaction.restore("[NAME_2] and [NAME_1]"), "Ana Ruiz and Ana");
+        assert!(!PiiScrubber::new("none").is_enabled());
+    }
+
+    #[test]
+    fn placeholders_split_across_stream_fragments_are_restored() {
+        let scrubber = PiiScrubber::new(DEFAULT_KINDS);
+        let mut message = "Write to sam@example.com".to_string();
+        let redaction = scrubber.redact_all(vec![&mut message]);
+        let mut restorer = redaction.restorer();
+        let shown: Vec<String> = ["I'll email [EM", "AIL_1] now [see", " notes] [", "x"].into_iter().map(|part| restorer.push(part)).collect();
+        assert_eq!(shown, ["I'll email ", "sam@example.com now ", "[see notes] ", ""]);
+        assert_eq!(restorer.finish(), "[x");
+        assert_eq!(redaction.restore_json(r#"{"title": "Email [EMAIL_1]"}"#), r#"{"title": "Email sam@example.com"}"#);
+    }
+}
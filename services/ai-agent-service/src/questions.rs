@@ -0,0 +1,114 @@
+//! Follow-up questions about tasks created without key details.
+//!
+//! A meeting needs a time and attendees, and urgent work needs a due date.
+//! When the agent creates a task without them it asks the sender, in the
+//! reply and on the channel the message came from. The question waits on
+//! the case, and the next message there is shown to the model as a likely
+//! answer so it can fill the task in.
+
+use crate::language::Language;
+use models::{MissingDetail, Priority, Task, TaskStatus, TaskType};
+
+/// The details `task` should have but doesn't. Tasks waiting for review
+/// are left alone; the reviewer fills them in.
+pub fn missing_details(task: &Task) -> Vec<MissingDetail> {
+    if task.status == TaskStatus::NeedsReview {
+        return Vec::new();
+    }
+    let mut missing = Vec::new();
+    match task.task_type {
+        TaskType::Meeting => {
+            if task.due_date.is_none() {
+                missing.push(MissingDetail::MeetingTime);
+            }
+            let attendees = task.metadata.get("attendees").and_then(|attendees| attendees.as_array());
+            if attendees.is_none_or(|attendees| attendees.is_empty()) {
+                missing.push(MissingDetail::Attendees);
+            }
+        }
+        TaskType::Work | TaskType::Research | TaskType::Communication
+            if task.due_date.is_none() && matches!(task.priority, Priority::High | Priority::Critical) =>
+        {
+            missing.push(MissingDetail::DueDate);
+        }
+        _ => {}
+    }
+    missing
+}
+
+/// The question asking for `missing`, in the message's language.
+pub fn question(task: &Task, missing: &[MissingDetail], language: Language) -> String {
+    let keywords = language.keywords();
+    missing
+        .iter()
+        .map(|detail| {
+            let template = match detail {
+                MissingDetail::DueDate => keywords.ask_due,
+                MissingDetail::MeetingTime => keywords.ask_meeting_time,
+                MissingDetail::Attendees => keywords.ask_attendees,
+            };
+            template.replacen("{}", &task.title, 1)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn task(task_type: TaskType, priority: Priority) -> Task {
+        Task {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            case_id: Uuid::nil(),
+            title: "Budget review".to_string(),
+            description: None,
+            task_type,
+            status: TaskStatus::Pending,
+            priority,
+            due_date: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            completed_at: None,
+            metadata: json!({}),
+            tags: Vec::new(),
+            estimate_minutes: None,
+        }
+    }
+
+    #[test]
+    fn meetings_need_a_time_and_attendees() {
+        let mut meeting = task(TaskType::Meeting, Priority::Medium);
+        assert_eq!(missing_details(&meeting), [MissingDetail::MeetingTime, MissingDetail::Attendees]);
+
+        meeting.due_date = Some(Utc::now());
+        meeting.metadata = json!({ "attendees": ["ana@company.com"] });
+        assert!(missing_details(&meeting).is_empty());
+    }
+
+    #[test]
+    fn only_urgent_work_needs_a_due_date() {
+        assert_eq!(missing_details(&task(TaskType::Work, Priority::High)), [MissingDetail::DueDate]);
+        assert!(missing_details(&task(TaskType::Work, Priority::Medium)).is_empty());
+        assert!(missing_details(&task(TaskType::Shopping, Priority::Critical)).is_empty());
+
+        let mut queued = task(TaskType::Work, Priority::Critical);
+        queued.status = TaskStatus::NeedsReview;
+        assert!(missing_details(&queued).is_empty());
+    }
+
+    #[test]
+    fn questions_are_asked_in_the_message_language() {
+        let meeting = task(TaskType::Meeting, Priority::Medium);
+        let missing = missing_details(&meeting);
+        assert_eq!(
+            question(&meeting, &missing, Language::English),
+            "When should 'Budget review' take place? Who should attend 'Budget review'?"
+        );
+        assert_eq!(question(&meeting, &missing[..1], Language::Spanish), "¿Cuándo debería ser 'Budget review'?");
+    }
+}
@@ -0,0 +1,186 @@
+//! How the rule-based extractor reads a message, for when no model is
+//! used: the LLM is down or unconfigured, the organization used up its
+//! budget, or the deployment runs with `LLM_PROVIDER=rules`.
+//!
+//! A message is read sentence by sentence, so each task gets the due date
+//! and priority of its own sentence ("Buy milk today. Call Sam on
+//! Friday.").  A task pattern matching inside the title of another match
+//! is the same task ("need to call Sam"); the more specific one is kept.
+//! Due dates are `today`, `tomorrow`, `next week`, a weekday or an ISO
+//! date, and are cut from the title.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use models::TaskType;
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::language::Language;
+
+static ISO_DATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(\d{4}-\d{2}-\d{2})\b").expect("date pattern is valid"));
+/// A preposition left at the end of a title once its date is cut.
+static DANGLING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\s+(?:by|on|before|until|next|this|para|el|pour|le|avant|bis|am|até|na|no)$").expect("preposition pattern is valid")
+});
+
+/// A task a pattern found in a sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    pub title: String,
+    pub task_type: TaskType,
+}
+
+/// When a sentence says something is due, and the words saying it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Due {
+    pub at: DateTime<Utc>,
+    pub said: String,
+}
+
+/// The sentences of a message: split at line breaks, `;`, `!`, `?` and at
+/// full stops followed by a space, so `1.5 hours` stays whole.
+pub fn sentences(message: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = message.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let ends = match c {
+            '\n' | ';' | '!' | '?' => true,
+            '.' => chars.peek().is_none_or(|(_, next)| next.is_whitespace()),
+            _ => false,
+        };
+        if ends {
+            sentences.push(&message[start..index]);
+            start = index + c.len_utf8();
+        }
+    }
+    sentences.push(&message[start..]);
+    sentences.into_iter().map(str::trim).filter(|sentence| !sentence.is_empty()).collect()
+}
+
+/// The tasks the language's patterns find in a sentence, in order.
+pub fn tasks(sentence: &str, language: Language) -> Vec<Found> {
+    // (start of the match, span of the title, task)
+    let mut matches: Vec<(usize, std::ops::Range<usize>, Found)> = Vec::new();
+    for (re, task_type) in language.task_patterns() {
+        for cap in re.captures_iter(sentence) {
+            let (Some(whole), Some(title)) = (cap.get(0), cap.get(2)) else { continue };
+            let found = Found { title: title.as_str().trim().to_string(), task_type: task_type.clone() };
+            matches.push((whole.start(), title.range(), found));
+        }
+    }
+    matches.sort_by_key(|(start, _, _)| *start);
+
+    let mut kept: Vec<(usize, std::ops::Range<usize>, Found)> = Vec::new();
+    for candidate in matches {
+        match kept.iter_mut().find(|(_, title, _)| title.contains(&candidate.0)) {
+            // "need to call Sam" is a call, not a personal task.
+            Some(outer) if outer.2.task_type == TaskType::Personal && candidate.2.task_type != TaskType::Personal => {
+                *outer = candidate
+            }
+            Some(_) => {}
+            None => kept.push(candidate),
+        }
+    }
+    kept.into_iter().map(|(_, _, found)| found).collect()
+}
+
+/// When `text` says something is due, counting from `now`.  ISO dates are
+/// due at 17:00 UTC; relative dates at the time of `now`.
+pub fn due_date(text: &str, language: Language, now: DateTime<Utc>) -> Option<Due> {
+    if let Some(captures) = ISO_DATE.captures(text) {
+        let date = NaiveDate::parse_from_str(&captures[1], "%Y-%m-%d").ok()?;
+        let at = date.and_hms_opt(17, 0, 0)?.and_utc();
+        return Some(Due { at, said: captures[1].to_string() });
+    }
+
+    let keywords = language.keywords();
+    let lower = text.to_lowercase();
+    let relative = [(keywords.today, 0), (keywords.tomorrow, 1), (keywords.next_week, 7)]
+        .into_iter()
+        .find_map(|(words, days)| words.iter().find(|word| lower.contains(**word)).map(|word| (*word, days)));
+    if let Some((word, days)) = relative {
+        return Some(Due { at: now + Duration::days(days), said: word.to_string() });
+    }
+
+    let words: Vec<&str> = lower.split(|c: char| !c.is_alphabetic()).collect();
+    let (weekday, name) = keywords.weekdays.iter().enumerate().find(|(_, name)| words.contains(name))?;
+    // The coming one; today's when it is that day.
+    let days = (weekday as i64 - now.weekday().num_days_from_monday() as i64).rem_euclid(7);
+    Some(Due { at: now + Duration::days(days), said: name.to_string() })
+}
+
+/// `title` without the date in it, unless the date is all there is.
+pub fn without_due(title: &str, due: Option<&Due>) -> String {
+    let title = title.trim().trim_end_matches(['.', ',', ';', ':', '!', '?']).trim_end();
+    let Some(due) = due else { return title.to_string() };
+    // Positions in the lowercase title are only valid in the title when
+    // lowercasing kept every length.
+    let lower = title.to_lowercase();
+    if lower.len() != title.len() {
+        return title.to_string();
+    }
+    let is_letter = |c: Option<char>| c.is_some_and(char::is_alphabetic);
+    let said = lower.match_indices(due.said.as_str()).map(|(start, said)| start..start + said.len()).find(|said| {
+        title.is_char_boundary(said.start)
+            && title.is_char_boundary(said.end)
+            && !is_letter(title[..said.start].chars().next_back())
+            && !is_letter(title[said.end..].chars().next())
+    });
+    let Some(said) = said else { return title.to_string() };
+    let before = DANGLING.replace(title[..said.start].trim_end(), "");
+    if before.is_empty() {
+        return title.to_string();
+    }
+    let after = title[said.end..].trim_end_matches(['.', ',', ';', ':', '!', '?']);
+    format!("{}{}", before.trim_end(), after).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn messages_are_read_sentence_by_sentence() {
+        assert_eq!(
+            sentences("Buy milk today. Takes 1.5 hours!\nCall Sam; thanks"),
+            ["Buy milk today", "Takes 1.5 hours", "Call Sam", "thanks"]
+        );
+        assert_eq!(sentences("  "), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn matches_inside_another_task_are_the_same_task() {
+        let found = tasks("Need to buy milk, eggs and bread and call Sam", Language::English);
+        assert_eq!(found, [Found { title: "milk, eggs and bread and call Sam".to_string(), task_type: TaskType::Shopping }]);
+
+        let found = tasks("Remind me to call the bank and research mortgage rates", Language::English);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].task_type, TaskType::Communication);
+        assert!(tasks("Thanks, sounds good", Language::English).is_empty());
+    }
+
+    #[test]
+    fn due_dates_come_from_words_weekdays_and_iso_dates() {
+        // A Friday.
+        let now = Utc.with_ymd_and_hms(2026, 10, 16, 9, 30, 0).unwrap();
+        let due = |text| due_date(text, Language::English, now).map(|due| due.at);
+        assert_eq!(due("call Sam tomorrow"), Some(now + Duration::days(1)));
+        assert_eq!(due("send it on Monday"), Some(now + Duration::days(3)));
+        assert_eq!(due("by friday"), Some(now));
+        assert_eq!(due("by 2026-11-02"), Some(Utc.with_ymd_and_hms(2026, 11, 2, 17, 0, 0).unwrap()));
+        assert_eq!(due("sundays are quiet"), None);
+        assert_eq!(due_date("llamar el jueves", Language::Spanish, now).map(|due| due.at), Some(now + Duration::days(6)));
+    }
+
+    #[test]
+    fn titles_lose_their_due_date() {
+        let now = Utc::now();
+        let due = due_date("the auditor on Monday", Language::English, now);
+        assert_eq!(without_due("the auditor on Monday.", due.as_ref()), "the auditor");
+        assert_eq!(without_due("Sam on Monday, about the audit", due.as_ref()), "Sam, about the audit");
+        assert_eq!(without_due("Monday", due.as_ref()), "Monday");
+        assert_eq!(without_due("the mondays report", due.as_ref()), "the mondays report");
+        assert_eq!(without_due("the report", None), "the report");
+    }
+}
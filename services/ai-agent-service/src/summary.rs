@@ -0,0 +1,149 @@
+//! Weekly case summaries.
+//!
+//! Once a week a job collects what happened in each open case over the
+//! week: tasks created, tasks completed and open tasks that have stalled,
+//! either overdue or untouched all week. The model writes a short summary
+//! of it, which is added to the case's conversation as a System entry and
+//! emailed to owners who asked for it. Cases with nothing to report are
+//! left alone.
+
+use chrono::{DateTime, Utc};
+use models::{Case, Task, TaskStatus};
+
+/// What happened in a case between `since` and `until`.
+#[derive(Debug, Clone)]
+pub struct CaseActivity {
+    pub case: Case,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub created: Vec<Task>,
+    pub completed: Vec<Task>,
+    /// Open tasks that are overdue or were not changed in the period.
+    pub stalled: Vec<Task>,
+}
+
+impl CaseActivity {
+    pub fn new(case: Case, tasks: Vec<Task>, since: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        let mut activity = Self { case, since, until, created: Vec::new(), completed: Vec::new(), stalled: Vec::new() };
+        for task in tasks {
+            let open = matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress | TaskStatus::OnHold);
+            if task.completed_at.is_some_and(|completed_at| completed_at >= since) {
+                activity.completed.push(task);
+            } else if task.created_at >= since {
+                activity.created.push(task);
+            } else if open && (task.updated_at < since || task.due_date.is_some_and(|due| due < until)) {
+                activity.stalled.push(task);
+            }
+        }
+        activity
+    }
+
+    /// Nothing was created or completed and nothing is stuck.
+    pub fn is_quiet(&self) -> bool {
+        self.created.is_empty() && self.completed.is_empty() && self.stalled.is_empty()
+    }
+
+    /// The activity as a list, which the model is given to summarize and
+    /// which stands in for the summary without one.
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "Case '{}' ({:?}), {} to {}",
+            self.case.title,
+            self.case.status,
+            self.since.format("%Y-%m-%d"),
+            self.until.format("%Y-%m-%d")
+        );
+        let mut section = |heading: &str, tasks: &[Task], describe: &dyn Fn(&Task) -> String| {
+            if tasks.is_empty() {
+                return;
+            }
+            report.push_str(&format!("\n\n{}:", heading));
+            for task in tasks {
+                report.push_str(&format!("\n- {}", describe(task)));
+            }
+        };
+        let titled = |task: &Task| task.title.clone();
+        section("New tasks", &self.created, &titled);
+        section("Completed", &self.completed, &titled);
+        section("Stalled", &self.stalled, &|task: &Task| match task.due_date {
+            Some(due) if due < self.until => format!("{} (overdue since {})", task.title, due.format("%Y-%m-%d")),
+            _ => format!("{} (no change since {})", task.title, task.updated_at.format("%Y-%m-%d")),
+        });
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use models::{CaseStatus, Priority, TaskType};
+    use uuid::Uuid;
+
+    fn case() -> Case {
+        serde_json::from_value(serde_json::json!({
+            "id": Uuid::nil(),
+            "user_id": Uuid::nil(),
+            "title": "Office move",
+            "description": null,
+            "status": CaseStatus::Open,
+            "priority": Priority::Medium,
+            "created_at": Utc::now(),
+            "updated_at": Utc::now(),
+            "assigned_to": null,
+            "metadata": {},
+        }))
+        .unwrap()
+    }
+
+    fn task(title: &str, age_days: i64) -> Task {
+        let at = Utc::now() - Duration::days(age_days);
+        Task {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            case_id: Uuid::nil(),
+            title: title.to_string(),
+            description: None,
+            task_type: TaskType::Work,
+            status: TaskStatus::Pending,
+            priority: Priority::Medium,
+            due_date: None,
+            created_at: at,
+            updated_at: at,
+            completed_at: None,
+            metadata: serde_json::json!({}),
+            tags: Vec::new(),
+            estimate_minutes: None,
+        }
+    }
+
+    #[test]
+    fn tasks_are_sorted_into_the_weeks_activity() {
+        let now = Utc::now();
+        let mut done = task("Book movers", 10);
+        done.status = TaskStatus::Completed;
+        done.completed_at = Some(now - Duration::days(2));
+        let mut overdue = task("Order boxes", 3);
+        overdue.created_at = now - Duration::days(9);
+        overdue.due_date = Some(now - Duration::days(1));
+        let mut cancelled = task("Old plan", 30);
+        cancelled.status = TaskStatus::Cancelled;
+
+        let tasks = vec![task("Label desks", 1), done, overdue, task("Measure the room", 20), cancelled];
+        let activity = CaseActivity::new(case(), tasks, now - Duration::days(7), now);
+
+        let titles = |tasks: &[Task]| tasks.iter().map(|task| task.title.clone()).collect::<Vec<_>>();
+        assert_eq!(titles(&activity.created), ["Label desks"]);
+        assert_eq!(titles(&activity.completed), ["Book movers"]);
+        assert_eq!(titles(&activity.stalled), ["Order boxes", "Measure the room"]);
+        assert!(activity.report().contains("- Order boxes (overdue since "));
+    }
+
+    #[test]
+    fn quiet_weeks_have_nothing_to_report() {
+        let now = Utc::now();
+        let mut recent = task("Call the landlord", 20);
+        recent.updated_at = now - Duration::days(1);
+        assert!(CaseActivity::new(case(), vec![recent], now - Duration::days(7), now).is_quiet());
+    }
+}
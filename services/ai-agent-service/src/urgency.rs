@@ -0,0 +1,123 @@
+//! Urgency scoring of new cases, for the priority inbox.
+//!
+//! A new case is scored from 0 to 100 on the message that opened it: who
+//! sent it, urgent words and how soon a deadline it mentions is. The score
+//! and the reasons for it are kept in the case's `metadata.urgency`, and
+//! the dashboard lists open cases by it so the most pressing come first.
+
+use common::config::AgentConfig;
+use crate::language::Language;
+use models::CaseUrgency;
+use regex::Regex;
+use std::sync::LazyLock;
+
+const IMPORTANT_SENDER: u8 = 30;
+const CRITICAL_WORDS: u8 = 35;
+const HIGH_WORDS: u8 = 20;
+const DUE_TODAY: u8 = 25;
+const DUE_TOMORROW: u8 = 15;
+const DUE_NEXT_WEEK: u8 = 5;
+const DATED: u8 = 10;
+
+/// Dates such as `2024-03-01`, `1/3` or `01.03.2024`.
+static DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(\d{4}-\d{2}-\d{2}|\d{1,2}[./]\d{1,2}([./]\d{2,4})?)\b").expect("date pattern is valid")
+});
+
+/// Scores messages against the senders configured as important.
+#[derive(Debug, Clone, Default)]
+pub struct UrgencyScorer {
+    /// Lowercase addresses, or domains starting with `@`.
+    important_senders: Vec<String>,
+}
+
+impl UrgencyScorer {
+    /// `senders` is a comma-separated list of addresses and `@domain`s, as
+    /// in `AI_IMPORTANT_SENDERS`.
+    pub fn new(senders: &str) -> Self {
+        Self {
+            important_senders: senders
+                .split(',')
+                .map(|sender| sender.trim().to_lowercase())
+                .filter(|sender| !sender.is_empty())
+                .collect(),
+        }
+    }
+
+    /// The senders of `agent.important_senders`.
+    pub fn from_config(config: &AgentConfig) -> Self {
+        Self::new(&config.important_senders.join(","))
+    }
+
+    pub fn score(&self, message: &str, sender_id: &str, language: Language) -> CaseUrgency {
+        let keywords = language.keywords();
+        let message = message.to_lowercase();
+        let mentions = |words: &[&str]| words.iter().any(|word| message.contains(word));
+
+        let mut urgency = CaseUrgency { score: 0, reasons: Vec::new() };
+        let mut add = |points: u8, reason: &str| {
+            urgency.score = urgency.score.saturating_add(points).min(100);
+            urgency.reasons.push(reason.to_string());
+        };
+
+        if self.is_important(sender_id) {
+            add(IMPORTANT_SENDER, "From an important sender");
+        }
+        if mentions(keywords.critical) {
+            add(CRITICAL_WORDS, "Marked urgent");
+        } else if mentions(keywords.high) {
+            add(HIGH_WORDS, "Marked important");
+        }
+        if mentions(keywords.today) {
+            add(DUE_TODAY, "Due today");
+        } else if mentions(keywords.tomorrow) {
+            add(DUE_TOMORROW, "Due tomorrow");
+        } else if mentions(keywords.next_week) {
+            add(DUE_NEXT_WEEK, "Due next week");
+        } else if DATE.is_match(&message) {
+            add(DATED, "Mentions a date");
+        }
+        urgency
+    }
+
+    fn is_important(&self, sender_id: &str) -> bool {
+        let sender = sender_id.trim().to_lowercase();
+        self.important_senders.iter().any(|important| {
+            if important.starts_with('@') {
+                sender.ends_with(important.as_str())
+            } else {
+                sender == *important
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UrgencyScorer;
+    use crate::language::Language;
+
+    #[test]
+    fn routine_messages_score_nothing() {
+        let urgency = UrgencyScorer::default().score("Notes from the weekly sync", "ana@company.com", Language::English);
+        assert_eq!(urgency.score, 0);
+        assert!(urgency.reasons.is_empty());
+    }
+
+    #[test]
+    fn senders_words_and_deadlines_add_up() {
+        let scorer = UrgencyScorer::new("ceo@company.com, @bigclient.com");
+        let urgency = scorer.score("URGENT: the contract has to be signed today", "Legal@BigClient.com", Language::English);
+        assert_eq!(urgency.score, 90);
+        assert_eq!(urgency.reasons, ["From an important sender", "Marked urgent", "Due today"]);
+
+        let urgency = scorer.score("Important: budget review on 2024-03-01", "ana@company.com", Language::English);
+        assert_eq!(urgency.score, 30);
+    }
+
+    #[test]
+    fn keywords_follow_the_message_language() {
+        let urgency = UrgencyScorer::default().score("Es urgente, necesito el informe mañana", "", Language::Spanish);
+        assert_eq!(urgency.reasons, ["Marked urgent", "Due tomorrow"]);
+    }
+}
@@ -0,0 +1,171 @@
+//! Per-organization LLM settings and token accounting.
+//!
+//! An administrator can point an organization at its own OpenAI-compatible
+//! provider, model and key, and give it a monthly token budget.  The
+//! settings are looked up for each request, the tokens the request uses are
+//! counted on its [`LlmAccount`] and reported to persistence when it is done.
+//! Once the month's budget is used up the organization's requests fall back
+//! to keyword extraction, and persistence alerts its administrator.
+
+use common::{auth::SessionToken, http_client::HttpClient};
+use models::{LlmAccountRequest, LlmUsage, OrganizationLlm, RecordLlmUsageRequest};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// The LLM settings of the organization a request is made for, and the
+/// tokens the request has used so far.
+#[derive(Debug)]
+pub struct LlmAccount {
+    pub organization: String,
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    pub api_key: Option<String>,
+    budget: Option<i64>,
+    /// Tokens the organization used this month before the request.
+    used_before: i64,
+    used: AtomicI64,
+}
+
+impl LlmAccount {
+    pub fn new(llm: OrganizationLlm) -> Self {
+        let settings = llm.settings;
+        LlmAccount {
+            organization: settings.organization,
+            base_url: settings.base_url,
+            model: settings.model,
+            api_key: settings.api_key,
+            budget: settings.monthly_token_budget,
+            used_before: llm.usage.tokens_used,
+            used: AtomicI64::new(0),
+        }
+    }
+
+    pub fn record(&self, tokens: i64) {
+        self.used.fetch_add(tokens.max(0), Ordering::Relaxed);
+    }
+
+    /// Tokens used by the request.
+    pub fn tokens_used(&self) -> i64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Whether the month's budget is used up, counting the request.
+    pub fn exhausted(&self) -> bool {
+        self.budget.is_some_and(|budget| self.used_before + self.tokens_used() >= budget)
+    }
+}
+
+/// The account of the organization of the user behind `session`, or of
+/// `owner_id` for messages without a session.  `None` when the user's
+/// organization has no LLM settings, or when they can't be looked up; the
+/// service's own LLM is used then.
+pub async fn account(
+    client: &HttpClient,
+    persistence_url: &str,
+    session: &SessionToken,
+    owner_id: Option<Uuid>,
+) -> Option<Arc<LlmAccount>> {
+    let request = LlmAccountRequest { session_token: session.0.clone(), user_id: owner_id };
+    if request.session_token.is_none() && request.user_id.is_none() {
+        return None;
+    }
+    // Without the session's bearer: the settings include the API key, so
+    // persistence only hands them to internal calls.
+    let url = format!("{}/api/v1/llm/account", persistence_url);
+    match client.post::<LlmAccountRequest, Option<OrganizationLlm>>(&url, &request).await {
+        Ok(llm) => llm.map(|llm| Arc::new(LlmAccount::new(llm))),
+        Err(e) => {
+            warn!("Could not load the organization's LLM settings: {}", e);
+            None
+        }
+    }
+}
+
+/// Adds the tokens the request used to its organization's monthly usage.
+pub async fn report(client: &HttpClient, persistence_url: &str, account: &LlmAccount) {
+    let tokens = account.tokens_used();
+    if tokens == 0 {
+        return;
+    }
+    let request = RecordLlmUsageRequest { organization: account.organization.clone(), tokens };
+    let url = format!("{}/api/v1/llm/usage", persistence_url);
+    match client.post::<RecordLlmUsageRequest, LlmUsage>(&url, &request).await {
+        Ok(usage) if usage.exhausted() => {
+            info!("Organization {} used up its monthly LLM token budget", usage.organization)
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Could not record {} LLM tokens of {}: {}", tokens, account.organization, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, Utc};
+    use common::testing::MockTransport;
+    use models::OrganizationLlmSettings;
+    use reqwest::Method;
+
+    fn organization_llm(budget: Option<i64>, used: i64) -> OrganizationLlm {
+        OrganizationLlm {
+            settings: OrganizationLlmSettings {
+                organization: "acme".to_string(),
+                base_url: None,
+                model: Some("gpt-4o".to_string()),
+                api_key: Some("sk-acme".to_string()),
+                has_api_key: true,
+                monthly_token_budget: budget,
+                alert_user_id: None,
+                updated_at: Utc::now(),
+            },
+            usage: LlmUsage {
+                organization: "acme".to_string(),
+                month: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+                tokens_used: used,
+                monthly_token_budget: budget,
+            },
+        }
+    }
+
+    #[test]
+    fn requests_count_toward_the_budget() {
+        let account = LlmAccount::new(organization_llm(Some(1000), 900));
+        assert!(!account.exhausted());
+        account.record(60);
+        account.record(-5);
+        assert!(!account.exhausted());
+        account.record(40);
+        assert_eq!(account.tokens_used(), 100);
+        assert!(account.exhausted());
+
+        let unlimited = LlmAccount::new(organization_llm(None, 1_000_000));
+        unlimited.record(1_000_000);
+        assert!(!unlimited.exhausted());
+    }
+
+    #[tokio::test]
+    async fn accounts_are_looked_up_and_reported_without_the_session_bearer() {
+        let mock = MockTransport::new();
+        mock.reply(Method::POST, "/api/v1/llm/account", organization_llm(Some(1000), 0));
+        mock.reply(Method::POST, "/api/v1/llm/usage", organization_llm(Some(1000), 120).usage);
+        let client = mock.client();
+
+        let session = SessionToken(Some("session-1".to_string()));
+        let account = account(&client, "http://persistence", &session, None).await.expect("account");
+        assert_eq!(account.api_key.as_deref(), Some("sk-acme"));
+        report(&client, "http://persistence", &account).await;
+        assert!(mock.requests_to(Method::POST, "/api/v1/llm/usage").is_empty());
+
+        account.record(120);
+        report(&client, "http://persistence", &account).await;
+        let requests = mock.requests();
+        assert_eq!(requests[0].body["session_token"], "session-1");
+        assert_eq!(requests[1].body, serde_json::json!({ "organization": "acme", "tokens": 120 }));
+        assert!(requests.iter().all(|request| request.bearer.is_none()));
+
+        assert!(super::account(&client, "http://persistence", &SessionToken(None), None).await.is_none());
+        assert_eq!(mock.requests().len(), 2);
+    }
+}
@@ -1,57 +1,151 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    extract::{Path, Query, RawQuery, State},
+    http::header,
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put},
     Router,
 };
-use common::{config::ServiceConfig, http_client::HttpClient, HealthResponse, ServiceResult};
+use common::{
+    auth::{ServiceState, SessionToken},
+    config::ServiceConfig, logging, reload::LiveConfig,
+    export::{export_response, ExportQuery},
+    http_client::HttpClient,
+    jobs::{HttpJobQueue, Worker},
+    HealthResponse, ServiceResult,
+};
 use models::{
-    Case, CaseStatus, ConversationEntry, CreateCaseRequest, MessageSender, 
-    Priority, UpdateCaseRequest, CaseWorkflow, WorkflowStep, StepStatus
+    Case, CaseStatus, ConversationEntry, CreateCaseRequest, UpdateCaseRequest, CaseWorkflow,
+    CaseProductivity, ProductivityStats, EmailThread, CaseEvent, Task, TimelineEntry, CaseReadReceipt,
+    SlaPolicy, SlaStatus, Revision, TransferCaseRequest, CreateShareLinkRequest, ShareLink, SharedCase,
+    FollowUpQuestion, AnswerQuestionsRequest, NotionExport, NotionExportRequest,
 };
 use std::sync::Arc;
 use tower::ServiceBuilder;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing::{info, instrument};
 use uuid::Uuid;
 use chrono::Utc;
 
+mod markdown;
+mod notion;
+mod sla;
+mod timeline;
+
 #[derive(Clone)]
 struct AppState {
     config: ServiceConfig,
     http_client: HttpClient,
+    sla: Arc<sla::SlaPolicies>,
+    notion: notion::Notion,
+}
+
+impl ServiceState for AppState {
+    fn config(&self) -> &ServiceConfig {
+        &self.config
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CaseQuery {
+    status: Option<CaseStatus>,
+    tag: Option<String>,
+    #[serde(default)]
+    include_archived: bool,
+    /// Applied here after the SLA is computed, so not sent on.
+    #[serde(skip_serializing)]
+    sla: Option<SlaStatus>,
+    /// Order of the list; newest first by default.
+    #[serde(default, skip_serializing)]
+    sort: CaseSort,
+}
+
+#[derive(Debug, Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CaseSort {
+    #[default]
+    Newest,
+    /// Most urgent first, then newest; unscored cases last.
+    Urgency,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TimelineQuery {
+    /// Return only the latest `limit` entries.
+    limit: Option<usize>,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
     
-    let config = ServiceConfig::from_env("case-management-service", 8002);
+    let config = ServiceConfig::load("case-management-service", 8002).await?;
     
-    tracing_subscriber::fmt()
-        .with_env_filter(&config.log_level)
-        .init();
+    let live = LiveConfig::init(&config);
+
+    config.start_refresh();
 
-    let state = AppState {
+    let state = Arc::new(AppState {
         config: config.clone(),
-        http_client: HttpClient::new(),
-    };
+        http_client: HttpClient::for_services(&config),
+        sla: Arc::new(sla::SlaPolicies::from_env()?),
+        notion: notion::Notion::new(&config.notion),
+    });
+
+    let sla_sweep_enabled = std::env::var("SLA_SWEEP_ENABLED")
+        .map(|value| value != "false" && value != "0")
+        .unwrap_or(true);
+    if sla_sweep_enabled {
+        let every = std::env::var("SLA_SWEEP_INTERVAL_SECS")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(300)
+            .max(1);
+        let sla_state = state.clone();
+        Worker::new(Arc::new(HttpJobQueue::new(config.clone())), &config.service_name, &config.jobs)
+            .recurring("cases.sla", std::time::Duration::from_secs(every), move |_| {
+                let state = sla_state.clone();
+                async move {
+                    sla::sweep(&state).await?;
+                    Ok(())
+                }
+            })
+            .start();
+    }
 
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/api/v1/cases", post(create_case))
+        .route("/api/v1/cases", get(list_cases))
+        .route("/api/v1/cases/export", get(export_cases))
+        .route("/api/v1/cases/by-email-thread", get(find_cases_by_email_thread))
         .route("/api/v1/cases/:id", get(get_case))
         .route("/api/v1/cases/:id/state", put(update_case_state))
         .route("/api/v1/cases/:id/history", get(get_conversation_history))
         .route("/api/v1/cases/:id/history", post(add_conversation_entry))
+        .route("/api/v1/cases/:id/timeline", get(get_case_timeline))
+        .route("/api/v1/cases/:id/export.md", get(export_case_markdown))
+        .route("/api/v1/cases/:id/export/notion", post(export_case_to_notion))
+        .route("/api/v1/cases/:id/read", post(mark_case_read))
+        .route("/api/v1/cases/:id/restore", post(restore_archived_case))
+        .route("/api/v1/cases/:id/revisions", get(list_case_revisions))
+        .route("/api/v1/cases/:id/revisions/:revision_id/restore", post(restore_case_revision))
+        .route("/api/v1/cases/:id/transfer", post(transfer_case))
+        .route("/api/v1/cases/:id/share", post(create_share_link))
+        .route("/api/v1/shared/:token", get(get_shared_case))
         .route("/api/v1/cases/:id/workflow", get(get_case_workflow))
         .route("/api/v1/cases/:id/workflow", put(update_case_workflow))
-        .with_state(Arc::new(state))
+        .route("/api/v1/cases/:id/email-thread", post(record_email_thread))
+        .route("/api/v1/cases/:id/questions", get(list_follow_up_questions))
+        .route("/api/v1/cases/:id/questions", post(create_follow_up_question))
+        .route("/api/v1/cases/:id/questions/answered", post(answer_follow_up_questions))
+        .route("/api/v1/cases/:id/stats", get(get_case_stats))
+        .route("/api/v1/stats/productivity", get(get_productivity_stats))
+        .route("/api/v1/sla-policies", get(list_sla_policies))
+        .with_state(state)
+        .merge(live.router())
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
+                .layer(logging::request_layers(&config.service_name))
+                .layer(config.cors.layer()),
         );
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port)).await?;
@@ -66,19 +160,29 @@ async fn health_check() -> Json<HealthResponse> {
     Json(HealthResponse::new("case-management-service"))
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, session))]
 async fn create_case(
     State(state): State<Arc<AppState>>,
+    session: SessionToken,
     Json(request): Json<CreateCaseRequest>,
 ) -> ServiceResult<Json<Case>> {
     info!("Creating new case: {:?}", request);
 
     let case_id = Uuid::new_v4();
     let now = Utc::now();
+    let mut metadata = serde_json::json!({});
+    if let Some(language) = request.language {
+        metadata["language"] = serde_json::json!(language);
+    }
+    if let Some(urgency) = request.urgency {
+        metadata["urgency"] = serde_json::json!(urgency);
+    }
 
     let case = Case {
         id: case_id,
-        user_id: Uuid::new_v4(), // TODO: Extract from session token
+        // Persistence assigns the owner from the forwarded session; calls
+        // without one name it.
+        user_id: request.user_id.unwrap_or_else(Uuid::nil),
         title: request.title,
         description: request.description,
         status: CaseStatus::Open,
@@ -86,76 +190,283 @@ async fn create_case(
         created_at: now,
         updated_at: now,
         assigned_to: request.assigned_to,
-        metadata: serde_json::json!({}),
+        metadata,
+        tags: request.tags,
+        unread_count: None,
+        first_response_at: None,
+        resolved_at: None,
+        sla: None,
     };
 
     // Forward to persistence service
     let persistence_url = format!("{}/api/v1/cases", state.config.service_url("persistence"));
-    let saved_case = state
-        .http_client
+    let saved_case = session
+        .client(&state.http_client)
         .post::<Case, Case>(&persistence_url, &case)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     info!("Case created with ID: {}", saved_case.id);
     Ok(Json(saved_case))
 }
 
+#[instrument(skip(state, session))]
+async fn list_cases(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Query(query): Query<CaseQuery>,
+) -> ServiceResult<Json<Vec<Case>>> {
+    info!("Listing cases with query: {:?}", query.status);
+    let cases = fetch_cases(&state, &session, &query).await?;
+    Ok(Json(cases))
+}
+
+#[instrument(skip(state, session))]
+async fn export_cases(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Query(query): Query<CaseQuery>,
+    Query(export): Query<ExportQuery>,
+) -> ServiceResult<Response> {
+    info!("Exporting cases as {:?} with query: {:?}", export.format, query.status);
+    let cases = fetch_cases(&state, &session, &query).await?;
+    export_response(cases, &export, "cases")
+}
+
+async fn fetch_cases(state: &AppState, session: &SessionToken, query: &CaseQuery) -> ServiceResult<Vec<Case>> {
+    let url = format!("{}/api/v1/cases", state.config.service_url("persistence"));
+
+    let mut cases = session
+        .client(&state.http_client)
+        .get_with_query::<Vec<Case>, _>(&url, query)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    let now = Utc::now();
+    for case in &mut cases {
+        state.sla.annotate(case, now);
+    }
+    if let Some(status) = query.sla {
+        cases.retain(|case| case.sla.as_ref().is_some_and(|sla| sla.status == status));
+    }
+    if let CaseSort::Urgency = query.sort {
+        // Stable, so equally urgent cases stay newest first.
+        cases.sort_by_key(|case| std::cmp::Reverse(case.urgency().map(|urgency| urgency.score)));
+    }
+    Ok(cases)
+}
+
+/// Target response and resolution times by priority.
 #[instrument(skip(state))]
+async fn list_sla_policies(State(state): State<Arc<AppState>>) -> Json<Vec<SlaPolicy>> {
+    Json(state.sla.list())
+}
+
+#[instrument(skip(state, session))]
 async fn get_case(
     State(state): State<Arc<AppState>>,
+    session: SessionToken,
     Path(id): Path<Uuid>,
 ) -> ServiceResult<Json<Case>> {
     info!("Getting case: {}", id);
 
     let persistence_url = format!("{}/api/v1/cases/{}", state.config.service_url("persistence"), id);
-    let case = state
-        .http_client
+    let mut case = session
+        .client(&state.http_client)
         .get::<Case>(&persistence_url)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
+    state.sla.annotate(&mut case, Utc::now());
 
     Ok(Json(case))
 }
 
-#[instrument(skip(state))]
+/// Cases an email belongs to by its thread identifiers, most recently
+/// updated first.
+#[instrument(skip(state, session))]
+async fn find_cases_by_email_thread(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Query(thread): Query<EmailThread>,
+) -> ServiceResult<Json<Vec<Case>>> {
+    info!("Finding cases for email thread: {:?}", thread);
+
+    let persistence_url = format!("{}/api/v1/cases/by-email-thread", state.config.service_url("persistence"));
+    let cases = session
+        .client(&state.http_client)
+        .get_with_query::<Vec<Case>, _>(&persistence_url, &thread)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(cases))
+}
+
+#[instrument(skip(state, session))]
+async fn record_email_thread(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+    Json(thread): Json<EmailThread>,
+) -> ServiceResult<Json<Case>> {
+    info!("Recording email thread for case {}: {:?}", id, thread);
+
+    let persistence_url = format!("{}/api/v1/cases/{}/email-thread", state.config.service_url("persistence"), id);
+    let case = session
+        .client(&state.http_client)
+        .post::<EmailThread, Case>(&persistence_url, &thread)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(case))
+}
+
+#[instrument(skip(state, session))]
+async fn get_case_stats(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<CaseProductivity>> {
+    info!("Getting stats for case: {}", id);
+
+    let persistence_url = format!("{}/api/v1/stats/cases/{}", state.config.service_url("persistence"), id);
+    let stats = session
+        .client(&state.http_client)
+        .get::<CaseProductivity>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(stats))
+}
+
+/// Productivity rollup for the caller. The `from`/`to` query is passed to
+/// persistence unchanged.
+#[instrument(skip(state, session))]
+async fn get_productivity_stats(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    RawQuery(query): RawQuery,
+) -> ServiceResult<Json<ProductivityStats>> {
+    info!("Getting productivity stats: {:?}", query);
+
+    let mut persistence_url = format!("{}/api/v1/stats/productivity", state.config.service_url("persistence"));
+    if let Some(query) = query {
+        persistence_url = format!("{}?{}", persistence_url, query);
+    }
+    let stats = session
+        .client(&state.http_client)
+        .get::<ProductivityStats>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(stats))
+}
+
+#[instrument(skip(state, session))]
 async fn update_case_state(
     State(state): State<Arc<AppState>>,
+    session: SessionToken,
     Path(id): Path<Uuid>,
     Json(request): Json<UpdateCaseRequest>,
 ) -> ServiceResult<Json<Case>> {
     info!("Updating case state: {} with {:?}", id, request);
 
     let persistence_url = format!("{}/api/v1/cases/{}", state.config.service_url("persistence"), id);
-    let updated_case = state
-        .http_client
+    let mut updated_case = session
+        .client(&state.http_client)
         .put::<UpdateCaseRequest, Case>(&persistence_url, &request)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
+    state.sla.annotate(&mut updated_case, Utc::now());
 
     Ok(Json(updated_case))
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, session))]
 async fn get_conversation_history(
     State(state): State<Arc<AppState>>,
+    session: SessionToken,
     Path(id): Path<Uuid>,
+    RawQuery(query): RawQuery,
 ) -> ServiceResult<Json<Vec<ConversationEntry>>> {
-    info!("Getting conversation history for case: {}", id);
+    info!("Getting conversation history for case: {} with {:?}", id, query);
 
-    let persistence_url = format!("{}/api/v1/cases/{}/history", state.config.service_url("persistence"), id);
-    let history = state
-        .http_client
+    let mut persistence_url = format!("{}/api/v1/cases/{}/history", state.config.service_url("persistence"), id);
+    if let Some(query) = query {
+        persistence_url = format!("{}?{}", persistence_url, query);
+    }
+    let history = session
+        .client(&state.http_client)
         .get::<Vec<ConversationEntry>>(&persistence_url)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(Json(history))
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, session))]
+async fn list_follow_up_questions(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+    RawQuery(query): RawQuery,
+) -> ServiceResult<Json<Vec<FollowUpQuestion>>> {
+    info!("Listing follow-up questions for case: {} with {:?}", id, query);
+
+    let mut persistence_url = format!("{}/api/v1/cases/{}/questions", state.config.service_url("persistence"), id);
+    if let Some(query) = query {
+        persistence_url = format!("{}?{}", persistence_url, query);
+    }
+    let questions = session
+        .client(&state.http_client)
+        .get::<Vec<FollowUpQuestion>>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(questions))
+}
+
+#[instrument(skip(state, session, question))]
+async fn create_follow_up_question(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+    Json(question): Json<FollowUpQuestion>,
+) -> ServiceResult<Json<FollowUpQuestion>> {
+    info!("Adding follow-up question for case: {}", id);
+
+    let persistence_url = format!("{}/api/v1/cases/{}/questions", state.config.service_url("persistence"), id);
+    let question = session
+        .client(&state.http_client)
+        .post::<FollowUpQuestion, FollowUpQuestion>(&persistence_url, &question)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(question))
+}
+
+#[instrument(skip(state, session))]
+async fn answer_follow_up_questions(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+    Json(request): Json<AnswerQuestionsRequest>,
+) -> ServiceResult<Json<Vec<FollowUpQuestion>>> {
+    info!("Marking follow-up questions answered for case: {}", id);
+
+    let persistence_url = format!("{}/api/v1/cases/{}/questions/answered", state.config.service_url("persistence"), id);
+    let questions = session
+        .client(&state.http_client)
+        .post::<AnswerQuestionsRequest, Vec<FollowUpQuestion>>(&persistence_url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(questions))
+}
+
+#[instrument(skip(state, session))]
 async fn add_conversation_entry(
     State(state): State<Arc<AppState>>,
+    session: SessionToken,
     Path(id): Path<Uuid>,
     Json(mut entry): Json<ConversationEntry>,
 ) -> ServiceResult<Json<ConversationEntry>> {
@@ -166,46 +477,268 @@ async fn add_conversation_entry(
     entry.timestamp = Utc::now();
 
     let persistence_url = format!("{}/api/v1/cases/{}/history", state.config.service_url("persistence"), id);
-    let saved_entry = state
-        .http_client
+    let saved_entry = session
+        .client(&state.http_client)
         .post::<ConversationEntry, ConversationEntry>(&persistence_url, &entry)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(Json(saved_entry))
 }
 
-#[instrument(skip(state))]
+/// The conversation, task lifecycle and recorded changes of a case as one
+/// feed. With a `limit` only that many of the latest messages are fetched,
+/// which is all the trimmed timeline can hold.
+#[instrument(skip(state, session))]
+async fn get_case_timeline(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+    Query(query): Query<TimelineQuery>,
+) -> ServiceResult<Json<Vec<TimelineEntry>>> {
+    info!("Getting timeline for case: {}", id);
+
+    let case_url = format!("{}/api/v1/cases/{}", state.config.service_url("persistence"), id);
+    let history_url = match query.limit {
+        Some(limit) => format!("{}/history?limit={}", case_url, limit),
+        None => format!("{}/history", case_url),
+    };
+    let events_url = format!("{}/events", case_url);
+    let tasks_url = format!("{}/tasks", case_url);
+
+    let client = session.client(&state.http_client);
+    let (conversation, events, tasks) = tokio::try_join!(
+        client.get::<Vec<ConversationEntry>>(&history_url),
+        client.get::<Vec<CaseEvent>>(&events_url),
+        client.get::<Vec<Task>>(&tasks_url),
+    )
+    .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(timeline::build(conversation, tasks, events, query.limit)))
+}
+
+/// The case, its tasks and its conversation as a Markdown note with
+/// frontmatter, e.g. for an Obsidian vault.
+#[instrument(skip(state, session))]
+async fn export_case_markdown(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Response> {
+    info!("Exporting case {} as Markdown", id);
+    let (case, tasks, conversation) = fetch_case_contents(&state, &session, id).await?;
+    let disposition = format!("attachment; filename=\"{}\"", markdown::file_name(&case));
+    Ok((
+        [(header::CONTENT_TYPE, "text/markdown; charset=utf-8".to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        markdown::render(&case, &tasks, &conversation),
+    )
+        .into_response())
+}
+
+/// Adds the case as a page of a Notion database.
+#[instrument(skip(state, session))]
+async fn export_case_to_notion(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+    Json(request): Json<NotionExportRequest>,
+) -> ServiceResult<Json<NotionExport>> {
+    info!("Exporting case {} to Notion", id);
+    let (case, tasks, conversation) = fetch_case_contents(&state, &session, id).await?;
+    let export = state.notion.export(request.database_id.as_deref(), &case, &tasks, &conversation).await?;
+    info!("Case {} exported to Notion page {}", id, export.page_id);
+    Ok(Json(export))
+}
+
+async fn fetch_case_contents(
+    state: &AppState,
+    session: &SessionToken,
+    id: Uuid,
+) -> ServiceResult<(Case, Vec<Task>, Vec<ConversationEntry>)> {
+    let case_url = format!("{}/api/v1/cases/{}", state.config.service_url("persistence"), id);
+    let history_url = format!("{}/history", case_url);
+    let tasks_url = format!("{}/tasks", case_url);
+
+    let client = session.client(&state.http_client);
+    tokio::try_join!(
+        client.get::<Case>(&case_url),
+        client.get::<Vec<Task>>(&tasks_url),
+        client.get::<Vec<ConversationEntry>>(&history_url),
+    )
+    .map_err(common::ServiceError::HttpClient)
+}
+
+#[instrument(skip(state, session))]
+async fn restore_archived_case(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<Case>> {
+    info!("Restoring archived case: {}", id);
+
+    let persistence_url = format!("{}/api/v1/cases/{}/restore", state.config.service_url("persistence"), id);
+    let mut case = session
+        .client(&state.http_client)
+        .post::<serde_json::Value, Case>(&persistence_url, &serde_json::json!({}))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    state.sla.annotate(&mut case, Utc::now());
+
+    Ok(Json(case))
+}
+
+/// Hands the case to the user with the given email.
+#[instrument(skip(state, session))]
+async fn transfer_case(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+    Json(request): Json<TransferCaseRequest>,
+) -> ServiceResult<Json<Case>> {
+    info!("Transferring case: {}", id);
+
+    let persistence_url = format!("{}/api/v1/cases/{}/transfer", state.config.service_url("persistence"), id);
+    let mut case = session
+        .client(&state.http_client)
+        .post::<TransferCaseRequest, Case>(&persistence_url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    state.sla.annotate(&mut case, Utc::now());
+
+    Ok(Json(case))
+}
+
+#[instrument(skip(state, session))]
+async fn create_share_link(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+    Json(request): Json<CreateShareLinkRequest>,
+) -> ServiceResult<Json<ShareLink>> {
+    info!("Creating share link for case: {}", id);
+
+    let persistence_url = format!("{}/api/v1/cases/{}/share", state.config.service_url("persistence"), id);
+    let link = session
+        .client(&state.http_client)
+        .post::<CreateShareLinkRequest, ShareLink>(&persistence_url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(link))
+}
+
+/// The read-only view behind a share link; needs no session.
+#[instrument(skip(state, token))]
+async fn get_shared_case(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> ServiceResult<Json<SharedCase>> {
+    let persistence_url = format!("{}/api/v1/shared/{}", state.config.service_url("persistence"), token);
+    let mut shared = state
+        .http_client
+        .get::<SharedCase>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    state.sla.annotate(&mut shared.case, Utc::now());
+
+    Ok(Json(shared))
+}
+
+/// The case's revisions, newest first.
+#[instrument(skip(state, session))]
+async fn list_case_revisions(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<Vec<Revision>>> {
+    info!("Listing revisions of case: {}", id);
+
+    let persistence_url = format!("{}/api/v1/cases/{}/revisions", state.config.service_url("persistence"), id);
+    let revisions = session
+        .client(&state.http_client)
+        .get::<Vec<Revision>>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(revisions))
+}
+
+/// Rolls the case back to how it was right after the given revision.
+#[instrument(skip(state, session))]
+async fn restore_case_revision(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path((id, revision_id)): Path<(Uuid, Uuid)>,
+) -> ServiceResult<Json<Case>> {
+    info!("Restoring case {} to revision {}", id, revision_id);
+
+    let persistence_url = format!(
+        "{}/api/v1/cases/{}/revisions/{}/restore",
+        state.config.service_url("persistence"),
+        id,
+        revision_id
+    );
+    let mut case = session
+        .client(&state.http_client)
+        .post::<serde_json::Value, Case>(&persistence_url, &serde_json::json!({}))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    state.sla.annotate(&mut case, Utc::now());
+
+    Ok(Json(case))
+}
+
+#[instrument(skip(state, session))]
+async fn mark_case_read(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<CaseReadReceipt>> {
+    info!("Marking case read: {}", id);
+
+    let persistence_url = format!("{}/api/v1/cases/{}/read", state.config.service_url("persistence"), id);
+    let receipt = session
+        .client(&state.http_client)
+        .post::<serde_json::Value, CaseReadReceipt>(&persistence_url, &serde_json::json!({}))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(receipt))
+}
+
+#[instrument(skip(state, session))]
 async fn get_case_workflow(
     State(state): State<Arc<AppState>>,
+    session: SessionToken,
     Path(id): Path<Uuid>,
 ) -> ServiceResult<Json<CaseWorkflow>> {
     info!("Getting workflow for case: {}", id);
 
     let persistence_url = format!("{}/api/v1/cases/{}/workflow", state.config.service_url("persistence"), id);
-    let workflow = state
-        .http_client
+    let workflow = session
+        .client(&state.http_client)
         .get::<CaseWorkflow>(&persistence_url)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(Json(workflow))
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, session))]
 async fn update_case_workflow(
     State(state): State<Arc<AppState>>,
+    session: SessionToken,
     Path(id): Path<Uuid>,
     Json(workflow): Json<CaseWorkflow>,
 ) -> ServiceResult<Json<CaseWorkflow>> {
     info!("Updating workflow for case: {}", id);
 
     let persistence_url = format!("{}/api/v1/cases/{}/workflow", state.config.service_url("persistence"), id);
-    let updated_workflow = state
-        .http_client
+    let updated_workflow = session
+        .client(&state.http_client)
         .put::<CaseWorkflow, CaseWorkflow>(&persistence_url, &workflow)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(Json(updated_workflow))
 }
@@ -0,0 +1,88 @@
+//! Cases as Markdown notes.
+//!
+//! A case is written as one Markdown document: YAML frontmatter with its
+//! id, status, priority, tags and dates, then its description, its tasks
+//! as a checklist and its conversation, oldest first. The frontmatter
+//! follows the properties convention of Obsidian, so a note dropped into a
+//! vault is searchable by status and tag.
+
+use models::{Case, ConversationEntry, Task, TaskStatus};
+
+/// The case, its tasks and its conversation as a note.
+pub fn render(case: &Case, tasks: &[Task], conversation: &[ConversationEntry]) -> String {
+    // JSON strings are valid YAML scalars, quoting included.
+    let quoted = |text: &str| serde_json::Value::from(text).to_string();
+    let mut note = String::from("---\n");
+    note.push_str(&format!("title: {}\n", quoted(&case.title)));
+    note.push_str(&format!("case_id: {}\n", case.id));
+    note.push_str(&format!("status: {:?}\n", case.status));
+    note.push_str(&format!("priority: {:?}\n", case.priority));
+    if !case.tags.is_empty() {
+        note.push_str("tags:\n");
+        for tag in &case.tags {
+            note.push_str(&format!("  - {}\n", quoted(tag)));
+        }
+    }
+    note.push_str(&format!("created: {}\n", case.created_at.to_rfc3339()));
+    note.push_str(&format!("updated: {}\n", case.updated_at.to_rfc3339()));
+    note.push_str("---\n\n");
+
+    note.push_str(&format!("# {}\n", case.title));
+    if let Some(description) = case.description.as_deref().filter(|description| !description.trim().is_empty()) {
+        note.push_str(&format!("\n{}\n", description.trim()));
+    }
+
+    if !tasks.is_empty() {
+        note.push_str("\n## Tasks\n\n");
+        for task in tasks {
+            note.push_str(&format!("{}\n", task_line(task)));
+        }
+    }
+
+    if !conversation.is_empty() {
+        note.push_str("\n## Conversation\n");
+        for entry in conversation {
+            note.push_str(&format!(
+                "\n### {:?}, {}\n\n{}\n",
+                entry.sender,
+                entry.timestamp.format("%Y-%m-%d %H:%M UTC"),
+                entry.message.trim()
+            ));
+        }
+    }
+    note
+}
+
+/// A checklist item: done when completed, struck through when cancelled.
+pub fn task_line(task: &Task) -> String {
+    let (checkbox, title) = match task.status {
+        TaskStatus::Completed => ("[x]", task.title.clone()),
+        TaskStatus::Cancelled => ("[ ]", format!("~~{}~~", task.title)),
+        _ => ("[ ]", task.title.clone()),
+    };
+    format!("- {} {} ({})", checkbox, title, task_details(task))
+}
+
+/// Type, priority and due date of a task.
+pub fn task_details(task: &Task) -> String {
+    let mut details = vec![task.task_type.label(), format!("{:?}", task.priority)];
+    if let Some(due) = task.due_date {
+        details.push(format!("due {}", due.format("%Y-%m-%d")));
+    }
+    details.join(", ")
+}
+
+/// A file name for the note, from the case title.
+pub fn file_name(case: &Case) -> String {
+    let slug: String = case
+        .title
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let slug = slug.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-");
+    if slug.is_empty() {
+        format!("case-{}.md", case.id)
+    } else {
+        format!("{}.md", slug)
+    }
+}
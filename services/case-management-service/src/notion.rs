@@ -0,0 +1,142 @@
+//! Export of cases to a Notion database.
+//!
+//! A case becomes a page of the database, titled after the case, with the
+//! same sections as its Markdown note: the description, the tasks as to-do
+//! blocks and the conversation. Notion takes at most 100 blocks with a page
+//! and 2000 characters per text, so the end of a long conversation is left
+//! out, with a note saying so.
+//!
+//! Configured in the `notion` section of the service configuration:
+//!
+//! - `api_token` (`NOTION_API_TOKEN`) - token of a Notion integration the
+//!   database is shared with; export is off without it
+//! - `database_id` (`NOTION_DATABASE_ID`) - database used when a request
+//!   names none
+//! - `title_property` (`NOTION_TITLE_PROPERTY`) - the database's title
+//!   property (default `Name`)
+//! - `api_url` (`NOTION_API_URL`) - default `https://api.notion.com/v1`
+
+use common::{config::NotionConfig, ServiceError, ServiceResult};
+use models::{Case, ConversationEntry, NotionExport, Task, TaskStatus};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::markdown;
+
+const NOTION_VERSION: &str = "2022-06-28";
+
+/// Notion's limits on a page creation request.
+const MAX_BLOCKS: usize = 100;
+const MAX_TEXT_CHARS: usize = 2000;
+
+#[derive(Clone)]
+pub struct Notion {
+    api_token: Option<String>,
+    database_id: Option<String>,
+    title_property: String,
+    api_url: String,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct CreatedPage {
+    id: String,
+    url: String,
+}
+
+impl Notion {
+    pub fn new(config: &NotionConfig) -> Self {
+        Self {
+            api_token: config.api_token.clone(),
+            database_id: config.database_id.clone(),
+            title_property: config.title_property.clone(),
+            api_url: config.api_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .expect("Failed to create HTTP client"),
+        }
+    }
+
+    /// Adds the case as a page of `database_id`, or of the default
+    /// database.
+    pub async fn export(
+        &self,
+        database_id: Option<&str>,
+        case: &Case,
+        tasks: &[Task],
+        conversation: &[ConversationEntry],
+    ) -> ServiceResult<NotionExport> {
+        let api_token = self
+            .api_token
+            .as_deref()
+            .ok_or_else(|| ServiceError::BadRequest("Notion export is not configured".to_string()))?;
+        let database_id = database_id
+            .or(self.database_id.as_deref())
+            .ok_or_else(|| ServiceError::BadRequest("No Notion database to export to".to_string()))?;
+
+        let page = json!({
+            "parent": { "database_id": database_id },
+            "properties": { self.title_property.as_str(): { "title": rich_text(&case.title) } },
+            "children": blocks(case, tasks, conversation),
+        });
+        let created: CreatedPage = self
+            .client
+            .post(format!("{}/pages", self.api_url))
+            .bearer_auth(api_token)
+            .header("Notion-Version", NOTION_VERSION)
+            .json(&page)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(ServiceError::HttpClient)?
+            .json()
+            .await
+            .map_err(ServiceError::HttpClient)?;
+        Ok(NotionExport { page_id: created.id, url: created.url })
+    }
+}
+
+/// The page content, within Notion's block limit.
+fn blocks(case: &Case, tasks: &[Task], conversation: &[ConversationEntry]) -> Vec<Value> {
+    let mut blocks = Vec::new();
+    if let Some(description) = case.description.as_deref().filter(|description| !description.trim().is_empty()) {
+        blocks.push(block("paragraph", json!({ "rich_text": rich_text(description.trim()) })));
+    }
+    if !tasks.is_empty() {
+        blocks.push(heading("Tasks"));
+        for task in tasks {
+            let text = format!("{} ({})", task.title, markdown::task_details(task));
+            let checked = task.status == TaskStatus::Completed;
+            blocks.push(block("to_do", json!({ "rich_text": rich_text(&text), "checked": checked })));
+        }
+    }
+    if !conversation.is_empty() {
+        blocks.push(heading("Conversation"));
+        for entry in conversation {
+            let text = format!("{:?}, {}: {}", entry.sender, entry.timestamp.format("%Y-%m-%d %H:%M UTC"), entry.message.trim());
+            blocks.push(block("paragraph", json!({ "rich_text": rich_text(&text) })));
+        }
+    }
+
+    if blocks.len() > MAX_BLOCKS {
+        blocks.truncate(MAX_BLOCKS - 1);
+        blocks.push(block("paragraph", json!({ "rich_text": rich_text("The rest of the case was left out.") })));
+    }
+    blocks
+}
+
+fn heading(text: &str) -> Value {
+    block("heading_2", json!({ "rich_text": rich_text(text) }))
+}
+
+fn block(kind: &str, content: Value) -> Value {
+    json!({ "object": "block", "type": kind, kind: content })
+}
+
+/// `text` as rich text, cut short at Notion's length limit.
+fn rich_text(text: &str) -> Value {
+    let content: String = text.chars().take(MAX_TEXT_CHARS).collect();
+    json!([{ "type": "text", "text": { "content": content } }])
+}
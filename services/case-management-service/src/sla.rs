@@ -0,0 +1,172 @@
+//! Case SLAs.
+//!
+//! Each priority has a target time for the first response, which is the
+//! case leaving `Open`, and one for its resolution, both counted from the
+//! case's creation. A case is at risk once most of a target's time has
+//! passed. A recurring job records an SLA warning event for cases at risk
+//! and an SLA breach event for cases that missed a target, each at most
+//! once per case; persistence turns them into notifications.
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use common::{ServiceError, ServiceResult};
+use models::{Case, CaseEvent, CaseEventKind, CaseEventRequest, CaseSla, Priority, SlaPolicy, SlaStatus};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use crate::AppState;
+
+/// Share of a target's time after which a case is at risk, unless
+/// `SLA_WARNING_RATIO` says otherwise.
+const DEFAULT_WARNING_RATIO: f64 = 0.8;
+
+pub struct SlaPolicies {
+    policies: HashMap<Priority, SlaPolicy>,
+    warning_ratio: f64,
+}
+
+fn default_policies() -> Vec<SlaPolicy> {
+    let policy = |priority, response_minutes, resolution_minutes| SlaPolicy {
+        priority,
+        response_minutes,
+        resolution_minutes,
+    };
+    vec![
+        policy(Priority::Critical, 60, 4 * 60),
+        policy(Priority::High, 4 * 60, 24 * 60),
+        policy(Priority::Medium, 8 * 60, 3 * 24 * 60),
+        policy(Priority::Low, 24 * 60, 7 * 24 * 60),
+    ]
+}
+
+impl SlaPolicies {
+    /// The default policies, with those in the JSON list named by
+    /// `SLA_POLICIES_FILE` replacing the defaults for their priorities.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut policies: HashMap<Priority, SlaPolicy> =
+            default_policies().into_iter().map(|policy| (policy.priority.clone(), policy)).collect();
+
+        if let Ok(path) = std::env::var("SLA_POLICIES_FILE") {
+            let contents = std::fs::read_to_string(&path).with_context(|| format!("Reading {}", path))?;
+            let overrides: Vec<SlaPolicy> =
+                serde_json::from_str(&contents).with_context(|| format!("Parsing {}", path))?;
+            for policy in overrides {
+                if policy.response_minutes <= 0 || policy.resolution_minutes <= 0 {
+                    anyhow::bail!("SLA targets for {:?} must be positive", policy.priority);
+                }
+                policies.insert(policy.priority.clone(), policy);
+            }
+        }
+
+        let warning_ratio = match std::env::var("SLA_WARNING_RATIO") {
+            Ok(value) => value.parse::<f64>().with_context(|| format!("Parsing SLA_WARNING_RATIO '{}'", value))?,
+            Err(_) => DEFAULT_WARNING_RATIO,
+        };
+        if !(0.0..=1.0).contains(&warning_ratio) {
+            anyhow::bail!("SLA_WARNING_RATIO must be between 0 and 1");
+        }
+
+        Ok(Self { policies, warning_ratio })
+    }
+
+    /// The policies, most urgent priority first.
+    pub fn list(&self) -> Vec<SlaPolicy> {
+        [Priority::Critical, Priority::High, Priority::Medium, Priority::Low]
+            .iter()
+            .filter_map(|priority| self.policies.get(priority).cloned())
+            .collect()
+    }
+
+    pub fn evaluate(&self, case: &Case, now: DateTime<Utc>) -> Option<CaseSla> {
+        let policy = self.policies.get(&case.priority)?;
+        let response_due = case.created_at + Duration::minutes(policy.response_minutes);
+        let resolution_due = case.created_at + Duration::minutes(policy.resolution_minutes);
+        let response_status = self.target_status(case.created_at, response_due, case.first_response_at, now);
+        let resolution_status = self.target_status(case.created_at, resolution_due, case.resolved_at, now);
+        Some(CaseSla {
+            status: response_status.max(resolution_status),
+            response_status,
+            response_due,
+            resolution_status,
+            resolution_due,
+        })
+    }
+
+    /// Sets `case.sla`.
+    pub fn annotate(&self, case: &mut Case, now: DateTime<Utc>) {
+        case.sla = self.evaluate(case, now);
+    }
+
+    fn target_status(
+        &self,
+        start: DateTime<Utc>,
+        due: DateTime<Utc>,
+        reached_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> SlaStatus {
+        let allowed = (due - start).num_seconds() as f64;
+        let at_risk = start + Duration::seconds((allowed * self.warning_ratio) as i64);
+        match reached_at {
+            Some(reached_at) if reached_at <= due => SlaStatus::Met,
+            Some(_) => SlaStatus::Breached,
+            None if now > due => SlaStatus::Breached,
+            None if now >= at_risk => SlaStatus::AtRisk,
+            None => SlaStatus::OnTrack,
+        }
+    }
+}
+
+/// Checks every case once; run by the `cases.sla` job.
+pub async fn sweep(state: &AppState) -> ServiceResult<()> {
+    let persistence = state.config.service_url("persistence");
+    // Internal call without a session, so cases of every user are returned.
+    let cases = state
+        .http_client
+        .get::<Vec<Case>>(&format!("{}/api/v1/cases", persistence))
+        .await
+        .map_err(ServiceError::HttpClient)?;
+
+    let now = Utc::now();
+    let mut recorded = 0;
+    for case in cases {
+        let Some(sla) = state.sla.evaluate(&case, now) else {
+            continue;
+        };
+        let Some(request) = event_for(&case, &sla) else {
+            continue;
+        };
+        let url = format!("{}/api/v1/cases/{}/events", persistence, case.id);
+        match state.http_client.post::<CaseEventRequest, Option<CaseEvent>>(&url, &request).await {
+            Ok(Some(_)) => recorded += 1,
+            Ok(None) => {}
+            Err(e) => warn!("Failed to record SLA event for case {}: {}", case.id, e),
+        }
+    }
+    if recorded > 0 {
+        info!("Recorded {} SLA events", recorded);
+    }
+    Ok(())
+}
+
+/// The event to record for a case in `sla`, if it is at risk or breached.
+fn event_for(case: &Case, sla: &CaseSla) -> Option<CaseEventRequest> {
+    // The response target comes first, so it is the one reported when both
+    // are in the same state.
+    let (target, due) = if sla.response_status == sla.status {
+        ("response", sla.response_due)
+    } else {
+        ("resolution", sla.resolution_due)
+    };
+    let (kind, summary) = match sla.status {
+        SlaStatus::AtRisk => (
+            CaseEventKind::SlaWarning,
+            format!("Case '{}' is close to its {} target, due {}", case.title, target, due.format("%Y-%m-%d %H:%M UTC")),
+        ),
+        SlaStatus::Breached => (
+            CaseEventKind::SlaBreached,
+            format!("Case '{}' missed its {} target, due {}", case.title, target, due.format("%Y-%m-%d %H:%M UTC")),
+        ),
+        SlaStatus::Met | SlaStatus::OnTrack => return None,
+    };
+    Some(CaseEventRequest { kind, summary, subject_id: None, once: true })
+}
@@ -0,0 +1,75 @@
+//! Case activity timelines.
+//!
+//! A case's timeline merges its conversation, the creation and completion
+//! of its tasks and the recorded changes to the case, its tasks and its
+//! workflow into one feed, oldest first.
+
+use models::{CaseEvent, CaseEventKind, ConversationEntry, Task, TimelineEntry, TimelineSource};
+
+/// Merges the activity of a case, keeping only the latest `limit` entries
+/// when given.
+pub fn build(
+    conversation: Vec<ConversationEntry>,
+    tasks: Vec<Task>,
+    events: Vec<CaseEvent>,
+    limit: Option<usize>,
+) -> Vec<TimelineEntry> {
+    let mut timeline: Vec<TimelineEntry> = conversation.into_iter().map(message).collect();
+    for task in tasks {
+        timeline.extend(task_lifecycle(task));
+    }
+    timeline.extend(events.into_iter().map(event));
+
+    // Stable, so entries sharing a timestamp keep their source order
+    timeline.sort_by_key(|entry| entry.timestamp);
+    if let Some(limit) = limit {
+        timeline = timeline.split_off(timeline.len().saturating_sub(limit));
+    }
+    timeline
+}
+
+fn message(entry: ConversationEntry) -> TimelineEntry {
+    TimelineEntry {
+        timestamp: entry.timestamp,
+        source: TimelineSource::Conversation,
+        summary: format!("Message from {:?}", entry.sender),
+        detail: Some(entry.message),
+        sender: Some(entry.sender),
+        subject_id: None,
+    }
+}
+
+fn task_lifecycle(task: Task) -> Vec<TimelineEntry> {
+    let entry = |timestamp, summary| TimelineEntry {
+        timestamp,
+        source: TimelineSource::Task,
+        summary,
+        detail: None,
+        sender: None,
+        subject_id: Some(task.id),
+    };
+    let mut entries = vec![entry(task.created_at, format!("Task '{}' created", task.title))];
+    if let Some(completed_at) = task.completed_at {
+        entries.push(entry(completed_at, format!("Task '{}' completed", task.title)));
+    }
+    entries
+}
+
+fn event(event: CaseEvent) -> TimelineEntry {
+    let source = match event.kind {
+        CaseEventKind::TaskStatusChanged | CaseEventKind::TaskDeleted => TimelineSource::Task,
+        CaseEventKind::WorkflowStepChanged => TimelineSource::Workflow,
+        CaseEventKind::CaseStatusChanged
+        | CaseEventKind::SlaWarning
+        | CaseEventKind::SlaBreached
+        | CaseEventKind::CaseTransferred => TimelineSource::Audit,
+    };
+    TimelineEntry {
+        timestamp: event.created_at,
+        source,
+        summary: event.summary,
+        detail: None,
+        sender: None,
+        subject_id: event.subject_id,
+    }
+}
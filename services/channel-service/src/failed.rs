@@ -0,0 +1,213 @@
+//! Dead-lettering of messages that could not be processed.
+//!
+//! When the AI agent, or the persistence service behind it, is down or
+//! failing, a message is recorded in persistence instead of being lost and
+//! the caller is told it will be processed later. Messages from webhooks
+//! and collectors are processed again by the recurring `messages.reprocess`
+//! job, with exponential backoff, until they succeed or run out of
+//! attempts. Messages sent with a session are retried by their user
+//! through `POST /api/v1/failed-messages/:id/retry`, as background work
+//! has no session to act under.
+//!
+//! Only failures worth retrying are dead-lettered: the agent unreachable,
+//! timing out or answering with a server error. A rejected message fails
+//! the request as before.
+
+use common::{auth::SessionToken, http_client::HttpClient, ServiceError, ServiceResult};
+use models::{CreateFailedMessageRequest, FailedMessage, FailedMessageAttempt, MessageRequest, MessageResponse};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::AppState;
+
+pub const REPROCESS_JOB: &str = "messages.reprocess";
+
+/// Messages taken per round of the reprocessing job.
+const REPROCESS_BATCH: usize = 10;
+
+/// Whether `e` is a failure that may go away on its own.
+fn is_retryable(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => status.is_server_error(),
+        None => e.is_connect() || e.is_timeout() || e.is_request(),
+    }
+}
+
+async fn send(state: &AppState, client: &HttpClient, request: &MessageRequest) -> Result<MessageResponse, reqwest::Error> {
+    let ai_agent_url = format!("{}/api/v1/process", state.config.service_url("ai-agent"));
+    client.post::<MessageRequest, MessageResponse>(&ai_agent_url, request).await
+}
+
+/// Sends `request` to the AI agent, dead-lettering it if the agent is
+/// unavailable. Should persistence be unavailable too, the original error
+/// is returned so the sender tries again.
+pub async fn process(state: &AppState, session: &SessionToken, request: &MessageRequest) -> ServiceResult<MessageResponse> {
+    let client = session.client(&state.http_client);
+    let e = match send(state, &client, request).await {
+        Ok(response) => return Ok(response),
+        Err(e) if is_retryable(&e) => e,
+        Err(e) => return Err(ServiceError::HttpClient(e)),
+    };
+
+    let failed_url = format!("{}/api/v1/failed-messages", state.config.service_url("persistence"));
+    let failed = CreateFailedMessageRequest { message: request.clone(), error: e.to_string() };
+    let failed = match client.post::<CreateFailedMessageRequest, FailedMessage>(&failed_url, &failed).await {
+        Ok(failed) => failed,
+        Err(record_error) => {
+            error!("Could not record failed message from {}: {}", request.sender_id, record_error);
+            return Err(ServiceError::HttpClient(e));
+        }
+    };
+    warn!("Processing of message from {} failed, kept as {}: {}", request.sender_id, failed.id, e);
+
+    Ok(MessageResponse {
+        case_id: Uuid::nil(),
+        response: "Your message could not be processed right now and will be retried.".to_string(),
+        actions_taken: vec![format!("Kept failed message {} for another attempt", failed.id)],
+        tasks_created: Vec::new(),
+        tasks_updated: Vec::new(),
+        questions: Vec::new(),
+    })
+}
+
+/// Processes a failed message again on behalf of `session`'s user.
+pub async fn retry(state: &AppState, session: &SessionToken, id: Uuid) -> ServiceResult<MessageResponse> {
+    let client = session.client(&state.http_client);
+    let persistence = state.config.service_url("persistence");
+    let failed = client
+        .post::<(), FailedMessage>(&format!("{}/api/v1/failed-messages/{}/claim", persistence, id), &())
+        .await
+        .map_err(ServiceError::HttpClient)?;
+
+    let result = send(state, &client, &failed.message).await;
+    let attempt = FailedMessageAttempt { error: result.as_ref().err().map(ToString::to_string) };
+    client
+        .post::<FailedMessageAttempt, FailedMessage>(&format!("{}/api/v1/failed-messages/{}/attempts", persistence, id), &attempt)
+        .await
+        .map_err(ServiceError::HttpClient)?;
+    result.map_err(ServiceError::HttpClient)
+}
+
+/// Processes the failed messages due for another attempt; run by the
+/// `messages.reprocess` job.
+pub async fn reprocess_due(state: &AppState) -> anyhow::Result<()> {
+    let persistence = state.config.service_url("persistence");
+    loop {
+        let due = state
+            .http_client
+            .post::<(), Vec<FailedMessage>>(
+                &format!("{}/api/v1/failed-messages/claim?limit={}", persistence, REPROCESS_BATCH),
+                &(),
+            )
+            .await?;
+        let batch = due.len();
+
+        for failed in due {
+            let result = send(state, &state.http_client, &failed.message).await;
+            let attempt = FailedMessageAttempt { error: result.as_ref().err().map(ToString::to_string) };
+            let recorded = state
+                .http_client
+                .post::<FailedMessageAttempt, FailedMessage>(
+                    &format!("{}/api/v1/failed-messages/{}/attempts", persistence, failed.id),
+                    &attempt,
+                )
+                .await?;
+            match result {
+                Ok(response) => info!("Reprocessed failed message {} into case {}", failed.id, response.case_id),
+                Err(e) => warn!(
+                    "Failed message {} failed again ({} attempts, now {:?}): {}",
+                    failed.id, recorded.attempts, recorded.status, e
+                ),
+            }
+        }
+
+        if batch < REPROCESS_BATCH {
+            return Ok(());
+        }
+    }
+}
+
+/// Dead-lettering against a mock AI agent and persistence.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spam::SpamFilter;
+    use common::{config::ServiceConfig, testing::MockTransport};
+    use models::MessageChannel;
+    use reqwest::Method;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn state(mock: &Arc<MockTransport>) -> AppState {
+        AppState {
+            config: ServiceConfig::for_tests("channel-service"),
+            http_client: mock.client(),
+            spam_filter: SpamFilter::from_env(),
+            transcriber: Arc::new(crate::voice::LocalWhisper::new("http://localhost:8080/inference".to_string())),
+            whatsapp: Default::default(),
+        }
+    }
+
+    fn message() -> MessageRequest {
+        MessageRequest {
+            case_id: None,
+            message: "Renew the support contract".to_string(),
+            sender_id: "lead@company.com".to_string(),
+            channel: MessageChannel::API,
+            email_thread: None,
+            owner_id: None,
+            priority: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn failed_message() -> serde_json::Value {
+        json!({
+            "id": Uuid::from_u128(7),
+            "user_id": null,
+            "message": message(),
+            "error": "503",
+            "status": "Pending",
+            "attempts": 1,
+            "next_attempt_at": null,
+            "created_at": chrono::Utc::now(),
+            "updated_at": chrono::Utc::now(),
+        })
+    }
+
+    #[tokio::test]
+    async fn unavailable_agent_dead_letters_the_message() {
+        let mock = MockTransport::new();
+        mock.reply_with_status(Method::POST, "/api/v1/process", 503, json!({ "error": "unavailable" }))
+            .reply(Method::POST, "/api/v1/failed-messages", failed_message());
+
+        let response = process(&state(&mock), &SessionToken(None), &message()).await.expect("message kept");
+
+        assert!(response.case_id.is_nil());
+        let recorded = mock.requests_to(Method::POST, "/api/v1/failed-messages");
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].body["message"]["message"], "Renew the support contract");
+    }
+
+    #[tokio::test]
+    async fn rejected_message_is_not_dead_lettered() {
+        let mock = MockTransport::new();
+        mock.reply_with_status(Method::POST, "/api/v1/process", 400, json!({ "error": "bad request" }));
+
+        let result = process(&state(&mock), &SessionToken(None), &message()).await;
+
+        assert!(matches!(result, Err(ServiceError::HttpClient(_))));
+        assert!(mock.requests_to(Method::POST, "/api/v1/failed-messages").is_empty());
+    }
+
+    #[tokio::test]
+    async fn unrecorded_failure_fails_the_request() {
+        let mock = MockTransport::new();
+        mock.reply_with_status(Method::POST, "/api/v1/process", 502, json!({}))
+            .reply_with_status(Method::POST, "/api/v1/failed-messages", 500, json!({}));
+
+        let result = process(&state(&mock), &SessionToken(None), &message()).await;
+
+        assert!(matches!(result, Err(ServiceError::HttpClient(e)) if e.status().map(|s| s.as_u16()) == Some(502)));
+    }
+}
@@ -1,47 +1,109 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
-    routing::{get, post},
+    body::Body,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, RawQuery, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
     Router,
 };
-use common::{config::ServiceConfig, http_client::HttpClient, HealthResponse, ServiceResult};
-use models::{MessageRequest, MessageResponse};
+use common::{
+    auth::{ServiceState, SessionToken},
+    config::ServiceConfig,
+    http_client::HttpClient,
+    jobs::{HttpJobQueue, Worker},
+    logging,
+    reload::LiveConfig,
+    HealthResponse, ServiceResult,
+};
+use models::{
+    AgentStreamEvent, FailedMessage, InviteRequest, MeetingMinutes, MessageChannel, MessageRequest, MessageResponse, QuarantineStatus,
+    QuarantinedMessage, ReviewQuarantineRequest, TranscriptRequest, VoiceNoteResponse, WebhookIntegration,
+    WebhookIntegrationRequest,
+};
 use std::sync::Arc;
 use tower::ServiceBuilder;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+mod failed;
+mod spam;
+mod voice;
+mod webhook;
+mod whatsapp;
+use spam::SpamFilter;
+use voice::{Transcriber, VoiceNote};
+use whatsapp::WhatsApp;
 
 #[derive(Clone)]
 struct AppState {
     config: ServiceConfig,
     http_client: HttpClient,
+    spam_filter: SpamFilter,
+    transcriber: Arc<dyn Transcriber>,
+    whatsapp: WhatsApp,
+}
+
+impl ServiceState for AppState {
+    fn config(&self) -> &ServiceConfig {
+        &self.config
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
     
-    let config = ServiceConfig::from_env("channel-service", 8001);
+    let config = ServiceConfig::load("channel-service", 8001).await?;
     
-    tracing_subscriber::fmt()
-        .with_env_filter(&config.log_level)
-        .init();
+    let live = LiveConfig::init(&config);
+
+    config.start_refresh();
 
-    let state = AppState {
+    let state = Arc::new(AppState {
         config: config.clone(),
-        http_client: HttpClient::new(),
-    };
+        http_client: HttpClient::for_services(&config),
+        spam_filter: SpamFilter::from_env(),
+        transcriber: voice::from_config(&config.voice, live.clone()),
+        whatsapp: WhatsApp::new(&config.whatsapp),
+    });
+
+    let retry_secs = config.jobs.failed_message_retry_secs;
+    let reprocess_state = state.clone();
+    Worker::new(Arc::new(HttpJobQueue::new(config.clone())), &config.service_name, &config.jobs)
+        .recurring(failed::REPROCESS_JOB, std::time::Duration::from_secs(retry_secs), move |_| {
+            let state = reprocess_state.clone();
+            async move { failed::reprocess_due(&state).await }
+        })
+        .start();
 
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/api/v1/message", post(handle_message))
+        .route("/api/v1/message/stream", post(handle_message_stream))
         .route("/api/v1/email", post(handle_email))
-        .with_state(Arc::new(state))
+        .route("/api/v1/transcripts", post(handle_transcript))
+        .route("/api/v1/invites", post(handle_invite))
+        .route("/api/v1/noise", post(handle_noise))
+        .route(
+            "/api/v1/voice",
+            post(handle_voice_note).layer(DefaultBodyLimit::max(config.voice.max_upload_bytes)),
+        )
+        .route("/api/v1/whatsapp/webhook", get(verify_whatsapp_webhook).post(handle_whatsapp_webhook))
+        .route("/api/v1/webhooks/:token", post(handle_webhook))
+        .route("/api/v1/webhook-integrations", get(list_webhook_integrations).post(create_webhook_integration))
+        .route("/api/v1/webhook-integrations/:id", delete(delete_webhook_integration))
+        .route("/api/v1/quarantine", get(list_quarantine))
+        .route("/api/v1/quarantine/:id/release", post(release_quarantined))
+        .route("/api/v1/quarantine/:id/discard", post(discard_quarantined))
+        .route("/api/v1/failed-messages", get(list_failed_messages))
+        .route("/api/v1/failed-messages/:id/retry", post(retry_failed_message))
+        .with_state(state)
+        .merge(live.router())
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
+                .layer(logging::request_layers(&config.service_name))
+                .layer(config.cors.layer()),
         );
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port)).await?;
@@ -56,29 +118,64 @@ async fn health_check() -> Json<HealthResponse> {
     Json(HealthResponse::new("channel-service"))
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, session))]
 async fn handle_message(
     State(state): State<Arc<AppState>>,
+    session: SessionToken,
     Json(request): Json<MessageRequest>,
 ) -> ServiceResult<Json<MessageResponse>> {
     info!("Received message: {:?}", request);
 
+    if let Some(held) = quarantine_if_suspicious(&state, &session, &request).await? {
+        return Ok(Json(held));
+    }
+
     // Forward to AI Agent Service for processing
-    let ai_agent_url = format!("{}/api/v1/process", state.config.service_url("ai-agent"));
-    
-    let response = state
-        .http_client
-        .post::<MessageRequest, MessageResponse>(&ai_agent_url, &request)
-        .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+    let response = failed::process(&state, &session, &request).await?;
 
     info!("AI Agent response: {:?}", response);
     Ok(Json(response))
 }
 
-#[instrument(skip(state))]
+/// Streaming variant of [`handle_message`]; the AI agent's server-sent
+/// events are relayed to the caller unchanged as they arrive.
+#[instrument(skip(state, session))]
+async fn handle_message_stream(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<MessageRequest>,
+) -> ServiceResult<Response> {
+    info!("Received streaming message: {:?}", request);
+
+    if let Some(response) = quarantine_if_suspicious(&state, &session, &request).await? {
+        let event = AgentStreamEvent::Done { response };
+        let data = serde_json::to_string(&event).map_err(common::ServiceError::Serialization)?;
+        return Ok((
+            [(header::CONTENT_TYPE, "text/event-stream"), (header::CACHE_CONTROL, "no-cache")],
+            format!("event: {}\ndata: {}\n\n", event.name(), data),
+        )
+            .into_response());
+    }
+
+    let ai_agent_url = format!("{}/api/v1/process/stream", state.config.service_url("ai-agent"));
+
+    let upstream = session
+        .client(&state.http_client)
+        .post_stream(&ai_agent_url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/event-stream"), (header::CACHE_CONTROL, "no-cache")],
+        Body::from_stream(upstream.bytes_stream()),
+    )
+        .into_response())
+}
+
+#[instrument(skip(state, session))]
 async fn handle_email(
     State(state): State<Arc<AppState>>,
+    session: SessionToken,
     Json(mut request): Json<MessageRequest>,
 ) -> ServiceResult<Json<MessageResponse>> {
     info!("Received email: {:?}", request);
@@ -86,15 +183,402 @@ async fn handle_email(
     // Set channel type to Email
     request.channel = models::MessageChannel::Email;
 
+    if let Some(held) = quarantine_if_suspicious(&state, &session, &request).await? {
+        return Ok(Json(held));
+    }
+
     // Forward to AI Agent Service for processing
-    let ai_agent_url = format!("{}/api/v1/process", state.config.service_url("ai-agent"));
-    
-    let response = state
-        .http_client
+    let response = failed::process(&state, &session, &request).await?;
+
+    info!("AI Agent response for email: {:?}", response);
+    Ok(Json(response))
+}
+
+/// A meeting transcript, forwarded to the AI agent to be turned into
+/// minutes and action items.
+#[instrument(skip(state, session, request))]
+async fn handle_transcript(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<TranscriptRequest>,
+) -> ServiceResult<Json<MeetingMinutes>> {
+    info!("Received transcript of {} characters", request.transcript.len());
+
+    let ai_agent_url = format!("{}/api/v1/transcripts", state.config.service_url("ai-agent"));
+    let minutes = session
+        .client(&state.http_client)
+        .post::<TranscriptRequest, MeetingMinutes>(&ai_agent_url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    info!("Transcript filed under case {} with {} action items", minutes.case_id, minutes.action_items.len());
+    Ok(Json(minutes))
+}
+
+/// A meeting invitation, forwarded to the AI agent to be filed as a
+/// Meeting task.
+#[instrument(skip(state, session, request), fields(sender = %request.sender_id))]
+async fn handle_invite(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<InviteRequest>,
+) -> ServiceResult<Json<MessageResponse>> {
+    info!("Received invitation to {:?} at {}", request.invite.title, request.invite.starts_at);
+
+    let ai_agent_url = format!("{}/api/v1/invites", state.config.service_url("ai-agent"));
+    let response = session
+        .client(&state.http_client)
+        .post::<InviteRequest, MessageResponse>(&ai_agent_url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    info!("Invitation filed under case {}", response.case_id);
+    Ok(Json(response))
+}
+
+/// Forwards a newsletter or notification to the AI agent, which files it
+/// for review instead of extracting tasks.
+#[instrument(skip(state, session, request), fields(sender = %request.sender_id))]
+async fn handle_noise(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<MessageRequest>,
+) -> ServiceResult<Json<MessageResponse>> {
+    info!("Received email from {} for review", request.sender_id);
+
+    let ai_agent_url = format!("{}/api/v1/noise", state.config.service_url("ai-agent"));
+    let response = session
+        .client(&state.http_client)
         .post::<MessageRequest, MessageResponse>(&ai_agent_url, &request)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
-    info!("AI Agent response for email: {:?}", response);
+    info!("Email filed for review under case {}", response.case_id);
+    Ok(Json(response))
+}
+
+/// A recorded voice note, transcribed and then handled like a message.
+#[instrument(skip(state, session, multipart))]
+async fn handle_voice_note(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    multipart: Multipart,
+) -> ServiceResult<Json<VoiceNoteResponse>> {
+    let note = VoiceNote::from_multipart(multipart).await?;
+    info!("Received voice note of {} bytes from {}", note.audio.len(), note.sender_id);
+
+    let transcript = state
+        .transcriber
+        .transcribe(&note)
+        .await
+        .map_err(|e| common::ServiceError::Internal(anyhow::anyhow!("Transcription failed: {}", e)))?;
+    if transcript.is_empty() {
+        return Err(common::ServiceError::BadRequest("No speech was recognized in the recording".to_string()));
+    }
+
+    let request = MessageRequest {
+        case_id: note.case_id,
+        message: transcript.clone(),
+        sender_id: note.sender_id,
+        channel: MessageChannel::Voice,
+        email_thread: None,
+        owner_id: None,
+        priority: None,
+        tags: Vec::new(),
+    };
+    let response = match quarantine_if_suspicious(&state, &session, &request).await? {
+        Some(held) => held,
+        None => failed::process(&state, &session, &request).await?,
+    };
+
+    info!("AI Agent response for voice note: {:?}", response);
+    Ok(Json(VoiceNoteResponse { transcript, response }))
+}
+
+/// Meta's check that the webhook is ours, answered with its challenge.
+#[instrument(skip(state, verification))]
+async fn verify_whatsapp_webhook(
+    State(state): State<Arc<AppState>>,
+    Query(verification): Query<whatsapp::Verification>,
+) -> ServiceResult<String> {
+    let challenge = state.whatsapp.verify(verification)?;
+    info!("WhatsApp webhook verified");
+    Ok(challenge)
+}
+
+/// Inbound WhatsApp messages, each processed for the owner of the business
+/// number in the open case of its sender's conversation and answered on
+/// WhatsApp. Meta redelivers events
+/// that aren't acknowledged, so a message that fails is logged rather than
+/// failing the others.
+#[instrument(skip(state, headers, body))]
+async fn handle_whatsapp_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ServiceResult<StatusCode> {
+    let signature = headers.get("x-hub-signature-256").and_then(|value| value.to_str().ok());
+    state.whatsapp.check_signature(signature, &body)?;
+    let event: whatsapp::Event = serde_json::from_slice(&body).map_err(common::ServiceError::Serialization)?;
+
+    for value in event.entry.into_iter().flat_map(|entry| entry.changes).map(|change| change.value) {
+        let phone_number_id = value.metadata.map(|metadata| metadata.phone_number_id).unwrap_or_default();
+        for message in value.messages {
+            if let Err(e) = handle_whatsapp_message(&state, &phone_number_id, &message).await {
+                warn!("Failed to handle WhatsApp message {}: {}", message.id, e);
+            }
+        }
+    }
+    Ok(StatusCode::OK)
+}
+
+async fn handle_whatsapp_message(state: &AppState, phone_number_id: &str, message: &whatsapp::InboundMessage) -> ServiceResult<()> {
+    let Some(owner_id) = state.whatsapp.owner(phone_number_id) else {
+        warn!("Ignoring WhatsApp message {} to {}, a number without an owner", message.id, phone_number_id);
+        return Ok(());
+    };
+    let Some(text) = message.body() else {
+        info!("Ignoring WhatsApp {} message {}", message.kind, message.id);
+        return Ok(());
+    };
+    info!("Received WhatsApp message {} from {}", message.id, message.from);
+
+    // Webhooks carry no session, like the email collector's messages.
+    let session = SessionToken(None);
+    let thread = message.thread();
+    let case_id = whatsapp::conversation_case(state, &thread, owner_id).await?;
+    let request = MessageRequest {
+        case_id,
+        message: text,
+        sender_id: message.from.clone(),
+        channel: MessageChannel::WhatsApp,
+        email_thread: None,
+        owner_id: Some(owner_id),
+        priority: None,
+        tags: Vec::new(),
+    };
+    if quarantine_if_suspicious(state, &session, &request).await?.is_some() {
+        return Ok(());
+    }
+    let response = failed::process(state, &session, &request).await?;
+
+    if !response.case_id.is_nil() && case_id != Some(response.case_id) {
+        whatsapp::record_conversation(state, response.case_id, &thread).await;
+    }
+    if let Err(e) = state.whatsapp.send_reply(&state.http_client, phone_number_id, &message.from, &response.response).await {
+        warn!("Could not reply to WhatsApp message {}: {}", message.id, e);
+    }
+    Ok(())
+}
+
+/// An event from a tool posting to a user's webhook integration, mapped to
+/// a message by the integration's rules and processed for its owner.
+#[instrument(skip(state, token, payload))]
+async fn handle_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> ServiceResult<Json<MessageResponse>> {
+    let integration = webhook::resolve(&state, &token).await?;
+    info!("Received event for webhook integration {}", integration.id);
+    let request = webhook::message(&integration, &payload)?;
+
+    // The URL's token stands in for a session; the integration names the
+    // case's owner.
+    let session = SessionToken(None);
+    if let Some(held) = quarantine_if_suspicious(&state, &session, &request).await? {
+        return Ok(Json(held));
+    }
+    let response = failed::process(&state, &session, &request).await?;
+
+    info!("AI Agent response for webhook event: {:?}", response);
+    Ok(Json(response))
+}
+
+/// The caller's webhook integrations.
+#[instrument(skip(state, session))]
+async fn list_webhook_integrations(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+) -> ServiceResult<Json<Vec<WebhookIntegration>>> {
+    let persistence_url = format!("{}/api/v1/webhook-integrations", state.config.service_url("persistence"));
+    let integrations = session
+        .client(&state.http_client)
+        .get::<Vec<WebhookIntegration>>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(integrations))
+}
+
+/// Sets up an integration. Its URL token is only in this response.
+#[instrument(skip(state, session))]
+async fn create_webhook_integration(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<WebhookIntegrationRequest>,
+) -> ServiceResult<Json<WebhookIntegration>> {
+    info!("Creating webhook integration: {}", request.name);
+    let persistence_url = format!("{}/api/v1/webhook-integrations", state.config.service_url("persistence"));
+    let integration = session
+        .client(&state.http_client)
+        .post::<WebhookIntegrationRequest, WebhookIntegration>(&persistence_url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(integration))
+}
+
+#[instrument(skip(state, session))]
+async fn delete_webhook_integration(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    info!("Deleting webhook integration: {}", id);
+    let persistence_url = format!("{}/api/v1/webhook-integrations/{}", state.config.service_url("persistence"), id);
+    session
+        .client(&state.http_client)
+        .delete(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Runs the spam filter over `request` and quarantines it when it looks
+/// suspicious. Returns the reply to send in place of the agent's, so
+/// callers such as the email collector treat the message as handled.
+async fn quarantine_if_suspicious(
+    state: &AppState,
+    session: &SessionToken,
+    request: &MessageRequest,
+) -> ServiceResult<Option<MessageResponse>> {
+    if !state.spam_filter.applies_to(session, request) {
+        return Ok(None);
+    }
+    let reasons = state.spam_filter.check(&state.http_client, request).await;
+    if reasons.is_empty() {
+        return Ok(None);
+    }
+
+    warn!("Quarantining message from {}: {}", request.sender_id, reasons.join("; "));
+    let quarantined = QuarantinedMessage {
+        id: Uuid::new_v4(),
+        // Persistence assigns the owner from the forwarded session.
+        user_id: None,
+        message: request.clone(),
+        reasons,
+        status: QuarantineStatus::Pending,
+        created_at: chrono::Utc::now(),
+        reviewed_at: None,
+    };
+    let persistence_url = format!("{}/api/v1/quarantine", state.config.service_url("persistence"));
+    let quarantined = session
+        .client(&state.http_client)
+        .post::<QuarantinedMessage, QuarantinedMessage>(&persistence_url, &quarantined)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Some(MessageResponse {
+        case_id: Uuid::nil(),
+        response: "Your message has been held for review.".to_string(),
+        actions_taken: vec![format!("Quarantined message {} for review", quarantined.id)],
+        tasks_created: Vec::new(),
+        tasks_updated: Vec::new(),
+        questions: Vec::new(),
+    }))
+}
+
+/// Quarantined messages, filtered by `?status=`. Signed-in users see their
+/// own; internal calls see all of them.
+#[instrument(skip(state, session))]
+async fn list_quarantine(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    RawQuery(query): RawQuery,
+) -> ServiceResult<Json<Vec<QuarantinedMessage>>> {
+    let mut persistence_url = format!("{}/api/v1/quarantine", state.config.service_url("persistence"));
+    if let Some(query) = query {
+        persistence_url = format!("{}?{}", persistence_url, query);
+    }
+    let messages = session
+        .client(&state.http_client)
+        .get::<Vec<QuarantinedMessage>>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(messages))
+}
+
+/// Sends a quarantined message on to the AI agent. The message is marked
+/// released first, so it is processed at most once, or dead-lettered.
+#[instrument(skip(state, session))]
+async fn release_quarantined(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<MessageResponse>> {
+    info!("Releasing quarantined message: {}", id);
+    let client = session.client(&state.http_client);
+    let released = review_quarantined(&state, &client, id, QuarantineStatus::Released).await?;
+
+    let response = failed::process(&state, &session, &released.message).await?;
+
+    info!("AI Agent response for released message: {:?}", response);
+    Ok(Json(response))
+}
+
+#[instrument(skip(state, session))]
+async fn discard_quarantined(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<QuarantinedMessage>> {
+    info!("Discarding quarantined message: {}", id);
+    let client = session.client(&state.http_client);
+    let discarded = review_quarantined(&state, &client, id, QuarantineStatus::Discarded).await?;
+    Ok(Json(discarded))
+}
+
+async fn review_quarantined(
+    state: &AppState,
+    client: &HttpClient,
+    id: Uuid,
+    status: QuarantineStatus,
+) -> ServiceResult<QuarantinedMessage> {
+    let review_url = format!("{}/api/v1/quarantine/{}/review", state.config.service_url("persistence"), id);
+    client
+        .post::<ReviewQuarantineRequest, QuarantinedMessage>(&review_url, &ReviewQuarantineRequest { status })
+        .await
+        .map_err(common::ServiceError::HttpClient)
+}
+
+/// Messages whose processing failed, filtered by `?status=`. Signed-in
+/// users see their own; internal calls see all of them.
+#[instrument(skip(state, session))]
+async fn list_failed_messages(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    RawQuery(query): RawQuery,
+) -> ServiceResult<Json<Vec<FailedMessage>>> {
+    let mut persistence_url = format!("{}/api/v1/failed-messages", state.config.service_url("persistence"));
+    if let Some(query) = query {
+        persistence_url = format!("{}?{}", persistence_url, query);
+    }
+    let messages = session
+        .client(&state.http_client)
+        .get::<Vec<FailedMessage>>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(messages))
+}
+
+/// Processes a failed message again now, abandoned ones included.
+#[instrument(skip(state, session))]
+async fn retry_failed_message(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<MessageResponse>> {
+    info!("Retrying failed message: {}", id);
+    let response = failed::retry(&state, &session, id).await?;
+    info!("AI Agent response for retried message: {:?}", response);
     Ok(Json(response))
 }
@@ -0,0 +1,166 @@
+//! Spam and abuse pre-filter.
+//!
+//! Messages from webhooks and collectors, and every email, are checked
+//! before they reach the AI agent. Suspicious ones are quarantined in
+//! persistence for review instead of spending LLM credits on them and
+//! opening junk cases. Chat messages from signed-in users are trusted.
+//!
+//! The heuristics are configured through the environment:
+//!
+//! - `SPAM_FILTER_ENABLED` - `false` turns the filter off (default on)
+//! - `SPAM_BLOCKED_SENDERS` - comma separated addresses, or `@domain`s
+//! - `SPAM_BLOCKED_KEYWORDS` - comma separated phrases, replacing the
+//!   built-in list
+//! - `SPAM_MAX_LINKS` - most links a message may contain (default 10)
+//! - `SPAM_MAX_MESSAGE_CHARS` - longest message accepted (default 50000)
+//! - `SPAM_CHECK_URL` - optional external checker, see [`SpamCheckRequest`]
+
+use common::{auth::SessionToken, http_client::HttpClient};
+use models::{MessageChannel, MessageRequest};
+use serde::{Deserialize, Serialize};
+use std::env;
+use tracing::warn;
+
+const DEFAULT_BLOCKED_KEYWORDS: &[&str] = &[
+    "viagra",
+    "casino",
+    "lottery winner",
+    "you have won",
+    "claim your prize",
+    "crypto giveaway",
+    "bitcoin investment",
+    "nigerian prince",
+    "wire the funds",
+    "unsubscribe from this list",
+];
+
+const DEFAULT_MAX_LINKS: usize = 10;
+const DEFAULT_MAX_MESSAGE_CHARS: usize = 50_000;
+
+/// Shouting is only judged on messages with at least this many letters.
+const MIN_LETTERS_FOR_SHOUTING: usize = 40;
+
+#[derive(Debug, Clone)]
+pub struct SpamFilter {
+    enabled: bool,
+    blocked_senders: Vec<String>,
+    blocked_keywords: Vec<String>,
+    max_links: usize,
+    max_message_chars: usize,
+    check_url: Option<String>,
+}
+
+/// Body POSTed to `SPAM_CHECK_URL`.
+#[derive(Debug, Serialize)]
+pub struct SpamCheckRequest<'a> {
+    pub message: &'a str,
+    pub sender_id: &'a str,
+    pub channel: &'a MessageChannel,
+}
+
+/// Expected reply from `SPAM_CHECK_URL`.
+#[derive(Debug, Deserialize)]
+pub struct SpamCheckResponse {
+    pub spam: bool,
+    pub reason: Option<String>,
+}
+
+impl SpamFilter {
+    pub fn from_env() -> Self {
+        let list = |name: &str| {
+            env::var(name).ok().map(|value| {
+                value
+                    .split(',')
+                    .map(|entry| entry.trim().to_lowercase())
+                    .filter(|entry| !entry.is_empty())
+                    .collect::<Vec<_>>()
+            })
+        };
+        let number = |name: &str, default: usize| {
+            env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+        };
+
+        Self {
+            enabled: !matches!(
+                env::var("SPAM_FILTER_ENABLED").as_deref().map(str::trim),
+                Ok("false") | Ok("0")
+            ),
+            blocked_senders: list("SPAM_BLOCKED_SENDERS").unwrap_or_default(),
+            blocked_keywords: list("SPAM_BLOCKED_KEYWORDS")
+                .unwrap_or_else(|| DEFAULT_BLOCKED_KEYWORDS.iter().map(|k| k.to_string()).collect()),
+            max_links: number("SPAM_MAX_LINKS", DEFAULT_MAX_LINKS),
+            max_message_chars: number("SPAM_MAX_MESSAGE_CHARS", DEFAULT_MAX_MESSAGE_CHARS),
+            check_url: env::var("SPAM_CHECK_URL").ok().filter(|url| !url.trim().is_empty()),
+        }
+    }
+
+    /// Whether `request` is checked at all.
+    pub fn applies_to(&self, session: &SessionToken, request: &MessageRequest) -> bool {
+        self.enabled && (session.0.is_none() || matches!(request.channel, MessageChannel::Email))
+    }
+
+    /// Reasons to quarantine `request`; empty when it looks fine. A failing
+    /// external check is logged and ignored rather than blocking mail.
+    pub async fn check(&self, client: &HttpClient, request: &MessageRequest) -> Vec<String> {
+        let mut reasons = self.heuristics(request);
+
+        if let Some(url) = &self.check_url {
+            let check = SpamCheckRequest {
+                message: &request.message,
+                sender_id: &request.sender_id,
+                channel: &request.channel,
+            };
+            match client.post::<SpamCheckRequest, SpamCheckResponse>(url, &check).await {
+                Ok(response) if response.spam => reasons.push(
+                    response.reason.unwrap_or_else(|| "Flagged by the external spam check".to_string()),
+                ),
+                Ok(_) => {}
+                Err(e) => warn!("External spam check failed: {}", e),
+            }
+        }
+
+        reasons
+    }
+
+    fn heuristics(&self, request: &MessageRequest) -> Vec<String> {
+        let mut reasons = Vec::new();
+        let sender = request.sender_id.trim().to_lowercase();
+        let message = request.message.to_lowercase();
+
+        if self
+            .blocked_senders
+            .iter()
+            .any(|blocked| if blocked.starts_with('@') { sender.ends_with(blocked.as_str()) } else { &sender == blocked })
+        {
+            reasons.push(format!("Sender {} is blocked", request.sender_id));
+        }
+
+        let keywords: Vec<&str> = self
+            .blocked_keywords
+            .iter()
+            .filter(|keyword| message.contains(keyword.as_str()))
+            .map(String::as_str)
+            .collect();
+        if !keywords.is_empty() {
+            reasons.push(format!("Contains blocked phrases: {}", keywords.join(", ")));
+        }
+
+        let links = message.matches("http://").count() + message.matches("https://").count();
+        if links > self.max_links {
+            reasons.push(format!("Contains {} links (limit {})", links, self.max_links));
+        }
+
+        let length = request.message.chars().count();
+        if length > self.max_message_chars {
+            reasons.push(format!("Is {} characters long (limit {})", length, self.max_message_chars));
+        }
+
+        let letters: Vec<char> = request.message.chars().filter(|c| c.is_alphabetic()).collect();
+        let uppercase = letters.iter().filter(|c| c.is_uppercase()).count();
+        if letters.len() >= MIN_LETTERS_FOR_SHOUTING && uppercase * 10 > letters.len() * 7 {
+            reasons.push("Written mostly in capitals".to_string());
+        }
+
+        reasons
+    }
+}
@@ -0,0 +1,270 @@
+//! Voice notes.
+//!
+//! `POST /api/v1/voice` takes a recording as `multipart/form-data`: the
+//! audio in an `audio` field, with optional `sender_id` and `case_id`
+//! fields. It is transcribed and the transcript goes through the message
+//! pipeline on the `Voice` channel, so tasks can be captured by voice from
+//! a phone. The reply includes the transcript, so the app can show what
+//! was heard.
+//!
+//! Transcription is configured in the `voice` section of the service
+//! configuration:
+//!
+//! - `provider` (`TRANSCRIPTION_PROVIDER`) - `openai` (default) for
+//!   OpenAI's Whisper API, using the same key and base URL as the AI agent,
+//!   or `local` for a Whisper server such as whisper.cpp's
+//! - `local_url` (`TRANSCRIPTION_URL`) - the local server's endpoint
+//!   (default `http://localhost:8080/inference`)
+//! - `model` (`TRANSCRIPTION_MODEL`) - the OpenAI model (default
+//!   `whisper-1`)
+//! - `max_upload_bytes` (`VOICE_MAX_UPLOAD_BYTES`) - largest recording
+//!   accepted (default 25 MB, OpenAI's limit)
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use axum::extract::Multipart;
+use common::config::{TranscriptionProvider, VoiceConfig};
+use common::{reload::LiveConfig, secrets, ServiceError, ServiceResult};
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
+use uuid::Uuid;
+
+/// Sender of notes uploaded without a `sender_id`.
+const DEFAULT_SENDER: &str = "voice-note";
+
+/// Transcription takes a while for long recordings.
+const TRANSCRIPTION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// An uploaded recording.
+#[derive(Debug, Clone)]
+pub struct VoiceNote {
+    pub audio: Bytes,
+    pub file_name: String,
+    pub content_type: String,
+    pub sender_id: String,
+    pub case_id: Option<Uuid>,
+}
+
+impl VoiceNote {
+    pub async fn from_multipart(mut multipart: Multipart) -> ServiceResult<Self> {
+        let invalid = |e: axum::extract::multipart::MultipartError| ServiceError::BadRequest(format!("Invalid upload: {}", e));
+        let mut note = VoiceNote {
+            audio: Bytes::new(),
+            file_name: "voice-note".to_string(),
+            content_type: "application/octet-stream".to_string(),
+            sender_id: DEFAULT_SENDER.to_string(),
+            case_id: None,
+        };
+
+        while let Some(field) = multipart.next_field().await.map_err(invalid)? {
+            match field.name() {
+                Some("audio") => {
+                    if let Some(file_name) = field.file_name() {
+                        note.file_name = file_name.to_string();
+                    }
+                    if let Some(content_type) = field.content_type() {
+                        note.content_type = content_type.to_string();
+                    }
+                    note.audio = field.bytes().await.map_err(invalid)?;
+                }
+                Some("sender_id") => note.sender_id = field.text().await.map_err(invalid)?,
+                Some("case_id") => {
+                    let case_id = field.text().await.map_err(invalid)?;
+                    note.case_id = Some(
+                        case_id.trim().parse().map_err(|_| ServiceError::BadRequest(format!("Invalid case id: {}", case_id)))?,
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        if note.audio.is_empty() {
+            return Err(ServiceError::BadRequest("The upload has no audio field".to_string()));
+        }
+        Ok(note)
+    }
+}
+
+/// Turns speech into text: [`WhisperApi`], [`LocalWhisper`], or a fake in
+/// tests.
+#[async_trait]
+pub trait Transcriber: Send + Sync {
+    async fn transcribe(&self, note: &VoiceNote) -> anyhow::Result<String>;
+}
+
+/// The transcriber `voice.provider` selects.
+pub fn from_config(config: &VoiceConfig, live: LiveConfig) -> Arc<dyn Transcriber> {
+    match config.provider {
+        TranscriptionProvider::Local => Arc::new(LocalWhisper::new(config.local_url.clone())),
+        TranscriptionProvider::OpenAi => Arc::new(WhisperApi::new(live, config.model.clone())),
+    }
+}
+
+/// OpenAI's `/audio/transcriptions`. The key and base URL are read on
+/// every request, like the AI agent's, so rotated keys are picked up.
+pub struct WhisperApi {
+    live: LiveConfig,
+    model: String,
+    client: reqwest::Client,
+}
+
+impl WhisperApi {
+    pub fn new(live: LiveConfig, model: String) -> Self {
+        Self { live, model, client: client() }
+    }
+}
+
+#[async_trait]
+impl Transcriber for WhisperApi {
+    async fn transcribe(&self, note: &VoiceNote) -> anyhow::Result<String> {
+        let config = self.live.current();
+        let api_key = config
+            .secret_values
+            .get(secrets::OPENAI_API_KEY)
+            .ok_or_else(|| anyhow::anyhow!("No OpenAI key is configured for transcription"))?;
+        let url = format!("{}/audio/transcriptions", config.llm.base_url.trim_end_matches('/'));
+        let request = self.client.post(url).bearer_auth(api_key).multipart(form(note)?.text("model", self.model.clone()));
+        send(request).await
+    }
+}
+
+/// A Whisper server on the network, such as whisper.cpp's `/inference`.
+pub struct LocalWhisper {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl LocalWhisper {
+    pub fn new(url: String) -> Self {
+        Self { url, client: client() }
+    }
+}
+
+#[async_trait]
+impl Transcriber for LocalWhisper {
+    async fn transcribe(&self, note: &VoiceNote) -> anyhow::Result<String> {
+        send(self.client.post(&self.url).multipart(form(note)?)).await
+    }
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(TRANSCRIPTION_TIMEOUT)
+        .build()
+        .expect("Failed to create HTTP client")
+}
+
+/// The form both APIs take: the recording as `file`, answered with JSON.
+fn form(note: &VoiceNote) -> anyhow::Result<Form> {
+    let file = Part::stream_with_length(note.audio.clone(), note.audio.len() as u64)
+        .file_name(note.file_name.clone())
+        .mime_str(&note.content_type)?;
+    Ok(Form::new().part("file", file).text("response_format", "json"))
+}
+
+#[derive(Deserialize)]
+struct Transcription {
+    text: String,
+}
+
+async fn send(request: reqwest::RequestBuilder) -> anyhow::Result<String> {
+    let transcription: Transcription = request.send().await?.error_for_status()?.json().await?;
+    Ok(transcription.text.trim().to_string())
+}
+
+/// Voice notes against a fake transcriber and a mock AI agent.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handle_voice_note, spam::SpamFilter, AppState};
+    use axum::{
+        body::Body,
+        extract::{FromRequest, Request, State},
+        http::header,
+        Json,
+    };
+    use common::{auth::SessionToken, config::ServiceConfig, testing::MockTransport};
+    use reqwest::Method;
+    use serde_json::json;
+
+    /// Hears the same words in every recording.
+    struct Hears(&'static str);
+
+    #[async_trait]
+    impl Transcriber for Hears {
+        async fn transcribe(&self, _note: &VoiceNote) -> anyhow::Result<String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    fn state(mock: &Arc<MockTransport>, heard: &'static str) -> Arc<AppState> {
+        Arc::new(AppState {
+            config: ServiceConfig::for_tests("channel-service"),
+            http_client: mock.client(),
+            spam_filter: SpamFilter::from_env(),
+            transcriber: Arc::new(Hears(heard)),
+            whatsapp: Default::default(),
+        })
+    }
+
+    async fn upload(fields: &[(&str, &str)]) -> Multipart {
+        let mut body = String::new();
+        for (name, value) in fields {
+            body.push_str("--boundary\r\n");
+            if *name == "audio" {
+                body.push_str("Content-Disposition: form-data; name=\"audio\"; filename=\"memo.m4a\"\r\nContent-Type: audio/mp4\r\n\r\n");
+            } else {
+                body.push_str(&format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name));
+            }
+            body.push_str(value);
+            body.push_str("\r\n");
+        }
+        body.push_str("--boundary--\r\n");
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+            .body(Body::from(body))
+            .unwrap();
+        Multipart::from_request(request, &()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn voice_note_is_processed_as_a_message() {
+        let mock = MockTransport::new();
+        mock.reply(Method::POST, "/api/v1/process", json!({
+            "case_id": Uuid::from_u128(3),
+            "response": "Added a task to call the plumber.",
+            "actions_taken": [],
+            "tasks_created": [Uuid::from_u128(4)],
+            "tasks_updated": [],
+        }));
+        let case_id = Uuid::from_u128(3).to_string();
+        let multipart = upload(&[("audio", "RIFF...."), ("sender_id", "ana@company.com"), ("case_id", &case_id)]).await;
+
+        let Json(reply) = handle_voice_note(
+            State(state(&mock, "Call the plumber tomorrow")),
+            SessionToken(Some("session-1".to_string())),
+            multipart,
+        )
+        .await
+        .expect("voice note processed");
+
+        assert_eq!(reply.transcript, "Call the plumber tomorrow");
+        assert_eq!(reply.response.tasks_created, [Uuid::from_u128(4)]);
+        let sent = mock.requests_to(Method::POST, "/api/v1/process");
+        assert_eq!(sent[0].body["message"], "Call the plumber tomorrow");
+        assert_eq!(sent[0].body["channel"], "Voice");
+        assert_eq!(sent[0].body["case_id"], case_id);
+    }
+
+    #[tokio::test]
+    async fn uploads_without_audio_or_speech_are_rejected() {
+        let mock = MockTransport::new();
+        let missing = handle_voice_note(State(state(&mock, "Hello")), SessionToken(None), upload(&[("sender_id", "ana")]).await).await;
+        assert!(matches!(missing, Err(ServiceError::BadRequest(_))));
+
+        let silent = handle_voice_note(State(state(&mock, "")), SessionToken(None), upload(&[("audio", "....")]).await).await;
+        assert!(matches!(silent, Err(ServiceError::BadRequest(_))));
+        assert!(mock.requests().is_empty());
+    }
+}
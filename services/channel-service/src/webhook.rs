@@ -0,0 +1,174 @@
+//! Generic inbound webhooks.
+//!
+//! Users set up an integration per tool that should open cases, such as a
+//! form builder or a monitoring system, with rules saying where the sender,
+//! subject and body are in the JSON the tool posts. Each integration gets a
+//! URL, `POST /api/v1/webhooks/<token>`, whose token is the only secret:
+//! the tool posts there without a session and the message is processed on
+//! the `Webhook` channel, in a case owned by the integration's user.
+//!
+//! Paths are JSONPath limited to fields and array indexes, with or without
+//! the leading `$`: `$.alert.title`, `answers[0].text`. Values that aren't
+//! strings are used as their JSON.
+
+use common::{ServiceError, ServiceResult};
+use models::{MessageChannel, MessageRequest, ResolveWebhookRequest, WebhookIntegration};
+use serde_json::Value;
+
+use crate::AppState;
+
+/// The integration a webhook URL's token belongs to.
+pub async fn resolve(state: &AppState, token: &str) -> ServiceResult<WebhookIntegration> {
+    let url = format!("{}/api/v1/webhook-integrations/resolve", state.config.service_url("persistence"));
+    let request = ResolveWebhookRequest { token: token.to_string() };
+    state.http_client.post(&url, &request).await.map_err(|e| match e.status() {
+        Some(reqwest::StatusCode::NOT_FOUND) => ServiceError::NotFound("Unknown webhook".to_string()),
+        _ => ServiceError::HttpClient(e),
+    })
+}
+
+/// The message `payload` maps to under the integration's rules. The
+/// subject, when there is one, heads the message; the integration's name
+/// stands in for a missing sender.
+pub fn message(integration: &WebhookIntegration, payload: &Value) -> ServiceResult<MessageRequest> {
+    let mapping = &integration.mapping;
+    let body = text_at(payload, &mapping.body)
+        .ok_or_else(|| ServiceError::BadRequest(format!("The event has nothing at {}", mapping.body)))?;
+    let message = match mapping.subject.as_deref().and_then(|path| text_at(payload, path)) {
+        Some(subject) => format!("{}\n\n{}", subject, body),
+        None => body,
+    };
+
+    Ok(MessageRequest {
+        case_id: None,
+        message,
+        sender_id: text_at(payload, &mapping.sender).unwrap_or_else(|| integration.name.clone()),
+        channel: MessageChannel::Webhook,
+        email_thread: None,
+        owner_id: Some(integration.user_id),
+        priority: None,
+        tags: Vec::new(),
+    })
+}
+
+/// The non-empty text at `path`.
+fn text_at(payload: &Value, path: &str) -> Option<String> {
+    let text = match json_path(payload, path)? {
+        Value::Null => return None,
+        Value::String(text) => text.trim().to_string(),
+        other => other.to_string(),
+    };
+    Some(text).filter(|text| !text.is_empty())
+}
+
+/// The value at `path`, e.g. `$.answers[0].text`.
+pub fn json_path<'a>(payload: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.trim();
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut value = payload;
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        let (field, indexes) = segment.split_once('[').map_or((segment, ""), |(field, rest)| (field, rest));
+        if !field.is_empty() {
+            value = value.get(field)?;
+        }
+        for index in indexes.split('[').filter(|index| !index.is_empty()) {
+            value = value.get(index.strip_suffix(']')?.trim().parse::<usize>().ok()?)?;
+        }
+    }
+    Some(value)
+}
+
+/// Events posted to an integration's URL, against a mock persistence and
+/// AI agent.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handle_webhook, spam::SpamFilter, voice::LocalWhisper, AppState};
+    use axum::{
+        extract::{Path, State},
+        Json,
+    };
+    use chrono::Utc;
+    use common::{config::ServiceConfig, testing::MockTransport};
+    use models::WebhookMapping;
+    use reqwest::Method;
+    use serde_json::json;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn integration() -> WebhookIntegration {
+        WebhookIntegration {
+            id: Uuid::from_u128(1),
+            user_id: Uuid::from_u128(2),
+            name: "Uptime alerts".to_string(),
+            mapping: WebhookMapping {
+                sender: "$.monitor.owner".to_string(),
+                body: "$.alert.details[0]".to_string(),
+                subject: Some("alert.title".to_string()),
+            },
+            created_at: Utc::now(),
+            token: None,
+        }
+    }
+
+    fn state(mock: &Arc<MockTransport>) -> Arc<AppState> {
+        Arc::new(AppState {
+            config: ServiceConfig::for_tests("channel-service"),
+            http_client: mock.client(),
+            spam_filter: SpamFilter::from_env(),
+            transcriber: Arc::new(LocalWhisper::new(String::new())),
+            whatsapp: Default::default(),
+        })
+    }
+
+    #[test]
+    fn paths_reach_into_objects_and_arrays() {
+        let payload = json!({ "answers": [{ "text": "Fix the login page" }, { "score": 4 }] });
+        assert_eq!(json_path(&payload, "$.answers[0].text"), Some(&json!("Fix the login page")));
+        assert_eq!(json_path(&payload, "answers[1].score"), Some(&json!(4)));
+        assert_eq!(json_path(&payload, "$"), Some(&payload));
+        assert_eq!(json_path(&payload, "$.answers[2].text"), None);
+        assert_eq!(json_path(&payload, "$.answers[x]"), None);
+    }
+
+    #[tokio::test]
+    async fn events_become_messages_of_the_integrations_owner() {
+        let mock = MockTransport::new();
+        mock.reply(Method::POST, "/api/v1/webhook-integrations/resolve", integration());
+        mock.reply(Method::POST, "/api/v1/process", json!({
+            "case_id": Uuid::from_u128(3),
+            "response": "Opened a case for the outage.",
+            "actions_taken": [],
+            "tasks_created": [],
+            "tasks_updated": [],
+        }));
+        let event = json!({
+            "monitor": { "owner": "ops@company.com" },
+            "alert": { "title": "API is down", "details": ["Health check failed 3 times"] },
+        });
+
+        let Json(reply) = handle_webhook(State(state(&mock)), Path("secret".to_string()), Json(event))
+            .await
+            .expect("event processed");
+
+        assert_eq!(reply.case_id, Uuid::from_u128(3));
+        assert_eq!(mock.requests_to(Method::POST, "/api/v1/webhook-integrations/resolve")[0].body["token"], "secret");
+        let sent = &mock.requests_to(Method::POST, "/api/v1/process")[0].body;
+        assert_eq!(sent["message"], "API is down\n\nHealth check failed 3 times");
+        assert_eq!(sent["sender_id"], "ops@company.com");
+        assert_eq!(sent["channel"], "Webhook");
+        assert_eq!(sent["owner_id"], Uuid::from_u128(2).to_string());
+    }
+
+    #[tokio::test]
+    async fn unknown_tokens_and_unmapped_events_are_rejected() {
+        let mock = MockTransport::new();
+        mock.reply_with_status(Method::POST, "/api/v1/webhook-integrations/resolve", 404, json!({}));
+        let unknown = handle_webhook(State(state(&mock)), Path("guessed".to_string()), Json(json!({}))).await;
+        assert!(matches!(unknown, Err(ServiceError::NotFound(_))));
+
+        let unmapped = message(&integration(), &json!({ "alert": { "title": "No details" } }));
+        assert!(matches!(unmapped, Err(ServiceError::BadRequest(_))));
+        assert!(mock.requests_to(Method::POST, "/api/v1/process").is_empty());
+    }
+}
@@ -0,0 +1,416 @@
+//! WhatsApp Business channel, through Meta's WhatsApp Cloud API.
+//!
+//! Meta verifies the webhook with a `GET` carrying `hub.verify_token`,
+//! answered with `hub.challenge`, then `POST`s events to it, signed with
+//! the app secret in `X-Hub-Signature-256`. Text messages, and the replies
+//! picked from buttons and lists, are processed like any other message on
+//! the `WhatsApp` channel and the agent's reply is sent back to the sender.
+//!
+//! Each business number belongs to a user, and the messages it receives
+//! go to that user's cases; messages to a number without an owner are
+//! ignored. Messages from one number stay in one case while it is open:
+//! the case remembers the conversation as its thread
+//! (`whatsapp:<number>`), the way email cases remember theirs, and the
+//! next message after the case is resolved or closed opens a new one.
+//!
+//! Configured in the `whatsapp` section of the service configuration:
+//!
+//! - `verify_token` (`WHATSAPP_VERIFY_TOKEN`) - the token entered when
+//!   subscribing the webhook
+//! - `app_secret` (`WHATSAPP_APP_SECRET`) - checks the signature of events;
+//!   without it every event is rejected
+//! - `access_token` (`WHATSAPP_ACCESS_TOKEN`) - sends replies; without it
+//!   none are sent
+//! - `api_url` (`WHATSAPP_API_URL`) - default
+//!   `https://graph.facebook.com/v19.0`
+//! - `reply_template` (`WHATSAPP_REPLY_TEMPLATE`) - an approved message
+//!   template with one body parameter, filled with the reply, so replies
+//!   reach users outside the 24 hour service window; plain text replies
+//!   when not set
+//! - `template_language` (`WHATSAPP_TEMPLATE_LANGUAGE`) - the template's
+//!   language (default `en`)
+//! - `owners` (`WHATSAPP_OWNERS`) - the owning user of each business
+//!   number, by its `phone_number_id`, e.g. `1055=<user id>`
+
+use common::{config::WhatsAppConfig, http_client::HttpClient, ServiceError, ServiceResult};
+use hmac::{Hmac, Mac};
+use models::{Case, CaseStatus, EmailThread};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::AppState;
+
+#[derive(Debug, Clone, Default)]
+pub struct WhatsApp {
+    verify_token: Option<String>,
+    app_secret: Option<String>,
+    access_token: Option<String>,
+    api_url: String,
+    reply_template: Option<String>,
+    template_language: String,
+    owners: BTreeMap<String, Uuid>,
+}
+
+/// Query of Meta's verification request.
+#[derive(Debug, Deserialize)]
+pub struct Verification {
+    #[serde(rename = "hub.mode")]
+    pub mode: Option<String>,
+    #[serde(rename = "hub.verify_token")]
+    pub verify_token: Option<String>,
+    #[serde(rename = "hub.challenge")]
+    pub challenge: Option<String>,
+}
+
+/// A webhook event; only what inbound messages need is read.
+#[derive(Debug, Deserialize)]
+pub struct Event {
+    #[serde(default)]
+    pub entry: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Entry {
+    #[serde(default)]
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Change {
+    pub value: ChangeValue,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeValue {
+    pub metadata: Option<PhoneNumber>,
+    /// Absent from delivery status updates.
+    #[serde(default)]
+    pub messages: Vec<InboundMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PhoneNumber {
+    pub phone_number_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InboundMessage {
+    pub from: String,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub text: Option<Value>,
+    pub button: Option<Value>,
+    pub interactive: Option<Value>,
+}
+
+impl InboundMessage {
+    /// The text of the message; `None` for media, locations and the like.
+    pub fn body(&self) -> Option<String> {
+        let text = match self.kind.as_str() {
+            "text" => self.text.as_ref()?.get("body"),
+            "button" => self.button.as_ref()?.get("text"),
+            "interactive" => {
+                let interactive = self.interactive.as_ref()?;
+                interactive.get("button_reply").or_else(|| interactive.get("list_reply"))?.get("title")
+            }
+            _ => None,
+        };
+        text.and_then(Value::as_str).map(str::trim).filter(|text| !text.is_empty()).map(str::to_string)
+    }
+
+    /// The thread every message from the sender shares.
+    pub fn thread(&self) -> EmailThread {
+        EmailThread { conversation_id: Some(format!("whatsapp:{}", self.from)), message_id: Some(self.id.clone()), in_reply_to: None }
+    }
+}
+
+impl WhatsApp {
+    pub fn new(config: &WhatsAppConfig) -> Self {
+        Self {
+            verify_token: config.verify_token.clone(),
+            app_secret: config.app_secret.clone(),
+            access_token: config.access_token.clone(),
+            api_url: config.api_url.clone(),
+            reply_template: config.reply_template.clone(),
+            template_language: config.template_language.clone(),
+            owners: config.owners.clone(),
+        }
+    }
+
+    /// The user the business number `phone_number_id` belongs to.
+    pub fn owner(&self, phone_number_id: &str) -> Option<Uuid> {
+        self.owners.get(phone_number_id).copied()
+    }
+
+    /// The challenge to echo when the verification request carries our
+    /// token.
+    pub fn verify(&self, verification: Verification) -> ServiceResult<String> {
+        let subscribing = verification.mode.as_deref() == Some("subscribe");
+        match (&self.verify_token, verification.verify_token, verification.challenge) {
+            (Some(expected), Some(token), Some(challenge)) if subscribing && *expected == token => Ok(challenge),
+            _ => Err(ServiceError::Unauthorized("Invalid WhatsApp webhook verification".to_string())),
+        }
+    }
+
+    /// Checks `X-Hub-Signature-256` against the raw body. Every event is
+    /// rejected while no app secret is configured.
+    pub fn check_signature(&self, signature: Option<&str>, body: &[u8]) -> ServiceResult<()> {
+        let Some(secret) = &self.app_secret else {
+            return Err(ServiceError::Unauthorized("The WhatsApp webhook is not configured".to_string()));
+        };
+        let signature = signature
+            .and_then(|signature| signature.strip_prefix("sha256="))
+            .and_then(from_hex)
+            .ok_or_else(|| ServiceError::Unauthorized("Missing WhatsApp signature".to_string()))?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+        mac.update(body);
+        mac.verify_slice(&signature)
+            .map_err(|_| ServiceError::Unauthorized("Invalid WhatsApp signature".to_string()))
+    }
+
+    /// Sends `reply` to `to` from the business number `phone_number_id`.
+    pub async fn send_reply(&self, client: &HttpClient, phone_number_id: &str, to: &str, reply: &str) -> anyhow::Result<()> {
+        let Some(access_token) = &self.access_token else {
+            return Ok(());
+        };
+        let mut message = match &self.reply_template {
+            Some(template) => json!({
+                "type": "template",
+                "template": {
+                    "name": template,
+                    "language": { "code": self.template_language },
+                    "components": [{ "type": "body", "parameters": [{ "type": "text", "text": reply }] }]
+                }
+            }),
+            None => json!({ "type": "text", "text": { "body": reply } }),
+        };
+        message["messaging_product"] = json!("whatsapp");
+        message["to"] = json!(to);
+
+        // The Cloud API takes the access token as the bearer token.
+        let url = format!("{}/{}/messages", self.api_url.trim_end_matches('/'), phone_number_id);
+        client.with_session_token(access_token).post::<Value, Value>(&url, &message).await?;
+        Ok(())
+    }
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+/// The open case of `owner_id` for the sender's conversation, if any.
+pub async fn conversation_case(state: &AppState, thread: &EmailThread, owner_id: Uuid) -> ServiceResult<Option<Uuid>> {
+    let url = format!("{}/api/v1/cases/by-email-thread", state.config.service_url("case-management"));
+    let cases = state
+        .http_client
+        .get_with_query::<Vec<Case>, _>(&url, &EmailThread { message_id: None, ..thread.clone() })
+        .await
+        .map_err(ServiceError::HttpClient)?;
+    Ok(cases
+        .into_iter()
+        .filter(|case| case.user_id == owner_id)
+        .find(|case| matches!(case.status, CaseStatus::Open | CaseStatus::InProgress | CaseStatus::Waiting))
+        .map(|case| case.id))
+}
+
+/// Makes `case_id` the case of the sender's conversation.
+pub async fn record_conversation(state: &AppState, case_id: Uuid, thread: &EmailThread) {
+    let url = format!("{}/api/v1/cases/{}/email-thread", state.config.service_url("case-management"), case_id);
+    match state.http_client.post::<EmailThread, Case>(&url, thread).await {
+        Ok(_) => info!("WhatsApp conversation {:?} now continues case {}", thread.conversation_id, case_id),
+        Err(e) => warn!("Could not record the WhatsApp conversation of case {}: {}", case_id, e),
+    }
+}
+
+/// Webhook handling against a mock case management, AI agent and Cloud API.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handle_whatsapp_webhook, spam::SpamFilter, voice::LocalWhisper};
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+    };
+    use common::{config::ServiceConfig, testing::MockTransport};
+    use reqwest::Method;
+    use std::sync::Arc;
+
+    fn whatsapp() -> WhatsApp {
+        WhatsApp::new(&WhatsAppConfig {
+            verify_token: Some("verify-me".to_string()),
+            app_secret: Some("app-secret".to_string()),
+            access_token: Some("access-token".to_string()),
+            owners: BTreeMap::from([("1055".to_string(), owner())]),
+            ..WhatsAppConfig::default()
+        })
+    }
+
+    fn owner() -> Uuid {
+        Uuid::from_u128(1)
+    }
+
+    fn state(mock: &Arc<MockTransport>) -> Arc<AppState> {
+        Arc::new(AppState {
+            config: ServiceConfig::for_tests("channel-service"),
+            http_client: mock.client(),
+            spam_filter: SpamFilter::from_env(),
+            transcriber: Arc::new(LocalWhisper::new("http://localhost:8080/inference".to_string())),
+            whatsapp: whatsapp(),
+        })
+    }
+
+    fn case(id: u128, user_id: Uuid, status: &str) -> Value {
+        json!({
+            "id": Uuid::from_u128(id),
+            "user_id": user_id,
+            "title": "Plumbing",
+            "description": null,
+            "status": status,
+            "priority": "Medium",
+            "created_at": chrono::Utc::now(),
+            "updated_at": chrono::Utc::now(),
+            "assigned_to": null,
+            "metadata": {},
+        })
+    }
+
+    fn event(body: &str) -> Bytes {
+        event_to("1055", body)
+    }
+
+    fn event_to(phone_number_id: &str, body: &str) -> Bytes {
+        Bytes::from(
+            json!({
+                "object": "whatsapp_business_account",
+                "entry": [{ "changes": [{ "field": "messages", "value": {
+                    "messaging_product": "whatsapp",
+                    "metadata": { "phone_number_id": phone_number_id },
+                    "contacts": [{ "profile": { "name": "Ana" }, "wa_id": "34600111222" }],
+                    "messages": [{ "from": "34600111222", "id": "wamid.1", "type": "text", "text": { "body": body } }]
+                } }] }]
+            })
+            .to_string(),
+        )
+    }
+
+    fn signed(body: &[u8]) -> HeaderMap {
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"app-secret").unwrap();
+        mac.update(body);
+        let hex: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-hub-signature-256", format!("sha256={}", hex).parse().unwrap());
+        headers
+    }
+
+    fn services(existing: Vec<Value>) -> Arc<MockTransport> {
+        let mock = MockTransport::new();
+        mock.reply(Method::GET, "/api/v1/cases/by-email-thread", json!(existing))
+            .reply(Method::POST, "/api/v1/cases/*/email-thread", case(9, owner(), "Open"))
+            .reply(Method::POST, "/api/v1/process", json!({
+                "case_id": Uuid::from_u128(9),
+                "response": "Added a task to call the plumber.",
+                "actions_taken": [],
+                "tasks_created": [],
+                "tasks_updated": [],
+            }))
+            .reply(Method::POST, "/v19.0/1055/messages", json!({ "messages": [{ "id": "wamid.2" }] }));
+        mock
+    }
+
+    #[test]
+    fn verification_echoes_the_challenge_for_our_token() {
+        let verification = |token: &str| Verification {
+            mode: Some("subscribe".to_string()),
+            verify_token: Some(token.to_string()),
+            challenge: Some("1158201444".to_string()),
+        };
+        assert_eq!(whatsapp().verify(verification("verify-me")).unwrap(), "1158201444");
+        assert!(whatsapp().verify(verification("guess")).is_err());
+        assert!(WhatsApp::default().verify(verification("verify-me")).is_err());
+    }
+
+    #[tokio::test]
+    async fn message_opens_a_case_for_the_conversation_and_is_answered() {
+        let mock = services(vec![case(5, owner(), "Closed")]);
+        let body = event("Call the plumber tomorrow");
+
+        let status = handle_whatsapp_webhook(State(state(&mock)), signed(&body), body).await.expect("event handled");
+        assert_eq!(status, StatusCode::OK);
+
+        // The conversation's earlier case is closed, so a new one is opened.
+        let sent = mock.requests_to(Method::POST, "/api/v1/process");
+        assert_eq!(sent[0].body["case_id"], Value::Null);
+        assert_eq!(sent[0].body["owner_id"], owner().to_string());
+        assert_eq!(sent[0].body["channel"], "WhatsApp");
+        assert_eq!(sent[0].body["sender_id"], "34600111222");
+        let threads = mock.requests_to(Method::POST, "/api/v1/cases/*/email-thread");
+        assert_eq!(threads[0].path, format!("/api/v1/cases/{}/email-thread", Uuid::from_u128(9)));
+        assert_eq!(threads[0].body["conversation_id"], "whatsapp:34600111222");
+
+        let replies = mock.requests_to(Method::POST, "/v19.0/1055/messages");
+        assert_eq!(replies[0].bearer.as_deref(), Some("access-token"));
+        assert_eq!(replies[0].body["to"], "34600111222");
+        assert_eq!(replies[0].body["text"]["body"], "Added a task to call the plumber.");
+    }
+
+    #[tokio::test]
+    async fn message_continues_the_open_case_of_its_conversation() {
+        let mock = services(vec![case(9, owner(), "InProgress")]);
+        let body = event("Also buy a new tap");
+
+        handle_whatsapp_webhook(State(state(&mock)), signed(&body), body).await.expect("event handled");
+
+        let sent = mock.requests_to(Method::POST, "/api/v1/process");
+        assert_eq!(sent[0].body["case_id"], Uuid::from_u128(9).to_string());
+        assert!(mock.requests_to(Method::POST, "/api/v1/cases/*/email-thread").is_empty());
+    }
+
+    #[tokio::test]
+    async fn message_does_not_continue_a_case_of_another_user() {
+        let mock = services(vec![case(7, Uuid::from_u128(2), "Open")]);
+        let body = event("Also buy a new tap");
+
+        handle_whatsapp_webhook(State(state(&mock)), signed(&body), body).await.expect("event handled");
+
+        let sent = mock.requests_to(Method::POST, "/api/v1/process");
+        assert_eq!(sent[0].body["case_id"], Value::Null);
+        assert_eq!(sent[0].body["owner_id"], owner().to_string());
+    }
+
+    #[tokio::test]
+    async fn messages_to_a_number_without_an_owner_are_ignored() {
+        let mock = services(Vec::new());
+        let body = event_to("2077", "Call the plumber tomorrow");
+
+        let status = handle_whatsapp_webhook(State(state(&mock)), signed(&body), body).await.expect("event handled");
+        assert_eq!(status, StatusCode::OK);
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unsigned_events_are_rejected() {
+        let mock = services(Vec::new());
+        let result = handle_whatsapp_webhook(State(state(&mock)), HeaderMap::new(), event("Hi")).await;
+        assert!(matches!(result, Err(ServiceError::Unauthorized(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[test]
+    fn events_are_rejected_without_an_app_secret() {
+        let body = event("Hi");
+        let signature = signed(&body)["x-hub-signature-256"].to_str().unwrap().to_string();
+        assert!(whatsapp().check_signature(Some(&signature), &body).is_ok());
+        let unconfigured = WhatsApp { app_secret: None, ..whatsapp() };
+        assert!(matches!(unconfigured.check_signature(Some(&signature), &body), Err(ServiceError::Unauthorized(_))));
+        assert!(matches!(unconfigured.check_signature(None, &body), Err(ServiceError::Unauthorized(_))));
+    }
+}
@@ -0,0 +1,39 @@
+use askama::Template;
+use models::{Task, TaskStatus, UserProfile};
+
+use crate::templates::filters;
+
+/// Board columns in workflow order. Cancelled tasks are not shown.
+const COLUMNS: [(TaskStatus, &str); 4] = [
+    (TaskStatus::Pending, "Pending"),
+    (TaskStatus::InProgress, "In Progress"),
+    (TaskStatus::OnHold, "On Hold"),
+    (TaskStatus::Completed, "Completed"),
+];
+
+struct BoardColumn<'a> {
+    status: TaskStatus,
+    label: &'static str,
+    tasks: Vec<&'a Task>,
+}
+
+#[derive(Template)]
+#[template(path = "board.html")]
+pub struct BoardPage<'a> {
+    user: &'a UserProfile,
+    columns: Vec<BoardColumn<'a>>,
+}
+
+impl<'a> BoardPage<'a> {
+    pub fn new(user: &'a UserProfile, tasks: &'a [Task]) -> Self {
+        let columns = COLUMNS
+            .into_iter()
+            .map(|(status, label)| BoardColumn {
+                tasks: tasks.iter().filter(|task| task.status == status).collect(),
+                status,
+                label,
+            })
+            .collect();
+        Self { user, columns }
+    }
+}
@@ -0,0 +1,61 @@
+use askama::Template;
+use models::{Case, CaseWorkflow, ConversationEntry, SharedCase, Task, TaskStatus, TimelineEntry, UserProfile};
+use serde::{Deserialize, Serialize};
+
+use crate::templates::filters;
+
+/// Everything shown on the case detail page, aggregated server-side from the
+/// case and task management services.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CaseDetail {
+    pub case: Case,
+    pub tasks: Vec<Task>,
+    pub conversation: Vec<ConversationEntry>,
+    pub workflow: CaseWorkflow,
+    /// Conversation, task and workflow activity merged, oldest first.
+    pub timeline: Vec<TimelineEntry>,
+}
+
+/// Most recent conversation entries and timeline entries loaded for the
+/// case page.
+pub const HISTORY_LIMIT: usize = 100;
+
+/// Statuses offered in each task's status picker.
+const STATUSES: [TaskStatus; 5] = [
+    TaskStatus::Pending,
+    TaskStatus::InProgress,
+    TaskStatus::OnHold,
+    TaskStatus::Completed,
+    TaskStatus::Cancelled,
+];
+
+#[derive(Template)]
+#[template(path = "case.html")]
+pub struct CasePage<'a> {
+    user: &'a UserProfile,
+    detail: &'a CaseDetail,
+    statuses: &'static [TaskStatus],
+}
+
+impl<'a> CasePage<'a> {
+    pub fn new(user: &'a UserProfile, detail: &'a CaseDetail) -> Self {
+        Self { user, detail, statuses: &STATUSES }
+    }
+
+    /// Whether older activity was left out of the timeline.
+    fn history_truncated(&self) -> bool {
+        self.detail.timeline.len() >= HISTORY_LIMIT
+    }
+
+    fn is_current(&self, status: &TaskStatus, task: &Task) -> bool {
+        *status == task.status
+    }
+}
+
+/// A case opened from a share link; `None` when the link is invalid or
+/// expired.
+#[derive(Template)]
+#[template(path = "shared_case.html")]
+pub struct SharedCasePage<'a> {
+    pub shared: Option<&'a SharedCase>,
+}
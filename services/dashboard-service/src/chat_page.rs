@@ -0,0 +1,27 @@
+use askama::Template;
+use models::{Task, UserProfile};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct ChatRequest {
+    pub message: String,
+    /// Continues the conversation of an existing case when set.
+    pub case_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChatReply {
+    pub case_id: Uuid,
+    pub response: String,
+    pub actions_taken: Vec<String>,
+    pub tasks_created: Vec<Task>,
+}
+
+#[derive(Template)]
+#[template(path = "chat.html")]
+pub struct ChatPage<'a> {
+    pub user: &'a UserProfile,
+    /// Continues this case's conversation instead of starting a new one.
+    pub case_id: Option<Uuid>,
+}
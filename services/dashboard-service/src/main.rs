@@ -1,54 +1,76 @@
 use axum::{
-    extract::{Query, State},
-    response::{Html, Json, Redirect},
-    routing::{get, post},
+    body::Body,
+    extract::{ConnectInfo, Path, Query, RawQuery, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Json, Redirect, Response},
+    routing::{delete, get, post, put},
     Router,
 };
 use axum_extra::extract::cookie::{Cookie, CookieJar};
-use common::{config::ServiceConfig, http_client::HttpClient, HealthResponse, ServiceResult};
-use models::{Task, RegisterRequest, LoginRequest, LoginResponse, UserProfile};
+use common::{
+    config::ServiceConfig, logging, reload::LiveConfig,
+    export::{export_response, ExportQuery},
+    http_client::HttpClient,
+    HealthResponse, ServiceResult,
+};
+use models::{
+    Case, CaseWorkflow, CaseReadReceipt, ConversationEntry, Priority, Task, TimelineEntry, UpdateTaskRequest,
+    UpdateTaskStatusRequest, MessageChannel, MessageRequest, MessageResponse,
+    RegisterRequest, LoginRequest, LoginResponse, UserProfile, TaskStats, ProductivityStats,
+    Notification, NotificationKind, NotificationList, CreateNotificationRequest,
+    PushSubscription, PushSubscriptionRequest, UnsubscribePushRequest, VapidPublicKey,
+    TaskSync, UserPreferences, UpdatePreferencesRequest, SavedFilter, SavedFilterRequest,
+    WebhookIntegration, WebhookIntegrationRequest, NotionExport, NotionExportRequest, SsoIdentity, SsoLoginRequest, ClientInfo, LoginEvent,
+    ApiToken, CreateApiTokenRequest, EmailAccount, EmailProvider, AddEmailAccountRequest,
+    WorkloadDay, CreateShareLinkRequest, ShareLink, SharedCase, TransferCaseRequest,
+};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tower::ServiceBuilder;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use tower_http::services::{ServeDir, ServeFile};
 use tower_cookies::CookieManagerLayer;
-use tracing::{info, instrument, error};
+use tracing::{info, instrument, error, warn};
 use serde::Deserialize;
-use std::collections::HashMap;
-use std::sync::Mutex;
+use uuid::Uuid;
 
+mod board_page;
+mod case_page;
+mod chat_page;
 mod oauth;
+mod reports_page;
+mod sso;
+mod state_store;
+mod templates;
 
 #[derive(Clone)]
 struct AppState {
     config: ServiceConfig,
     http_client: HttpClient,
     oauth_manager: Option<oauth::OAuthManager>,
-    oauth_states: Arc<Mutex<HashMap<String, oauth::AuthState>>>,
+    sso: sso::Sso,
+    /// Started email connections and SSO sign-ins.
+    states: state_store::StateStore,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
 
-    let config = ServiceConfig::from_env("dashboard-service", 8006);
+    let config = ServiceConfig::load("dashboard-service", 8006).await?;
 
-    tracing_subscriber::fmt()
-        .with_env_filter(&config.log_level)
-        .init();
+    let live = LiveConfig::init(&config);
+
+    config.start_refresh();
 
     // Initialize OAuth manager if credentials are provided
-    let oauth_manager = if let (Ok(client_id), Ok(client_secret), Ok(tenant_id)) = (
-        std::env::var("AZURE_CLIENT_ID"),
-        std::env::var("AZURE_CLIENT_SECRET"),
-        std::env::var("AZURE_TENANT_ID"),
-    ) {
+    let oauth_manager = if let Some(azure) = &config.email.azure {
         let oauth_config = oauth::OAuthConfig {
-            client_id,
-            client_secret,
+            client_id: azure.client_id.clone(),
+            client_secret: azure.client_secret.clone(),
             redirect_uri: format!("http://localhost:{}/oauth/callback", config.port),
-            tenant_id,
+            tenant_id: azure.tenant_id.clone(),
         };
-        match oauth::OAuthManager::new(oauth_config) {
+        match oauth::OAuthManager::new(oauth_config, config.secret_values.clone()) {
             Ok(manager) => Some(manager),
             Err(e) => {
                 error!("Failed to initialize OAuth manager: {}", e);
@@ -62,11 +84,16 @@ async fn main() -> anyhow::Result<()> {
 
     let state = AppState {
         config: config.clone(),
-        http_client: HttpClient::new(),
+        http_client: HttpClient::for_services(&config),
         oauth_manager,
-        oauth_states: Arc::new(Mutex::new(HashMap::new())),
+        sso: sso::Sso::new(&config.sso, config.port),
+        states: state_store::StateStore::new(&config, HttpClient::for_services(&config)),
     };
 
+    // Page scripts are served from disk; templates are compiled in.
+    let static_dir = std::env::var("DASHBOARD_STATIC_DIR")
+        .unwrap_or_else(|_| concat!(env!("CARGO_MANIFEST_DIR"), "/static").to_string());
+
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/", get(dashboard_home))
@@ -75,23 +102,66 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/auth/login", post(handle_login))
         .route("/api/auth/register", post(handle_register))
         .route("/api/auth/logout", post(handle_logout))
+        .route("/auth/sso/:provider", get(sso_login))
+        .route("/auth/sso/:provider/callback", get(sso_callback))
         .route("/dashboard", get(show_pending_tasks))
         .route("/config", get(show_config_page))
+        .route("/security", get(show_security_page))
+        .route("/cases/:id", get(show_case_detail))
+        .route("/shared/:token", get(show_shared_case))
+        .route("/reports", get(show_reports))
+        .route("/board", get(show_board))
+        .route("/chat", get(show_chat))
         .route("/ui/api/tasks", get(get_pending_tasks_api))
+        .route("/ui/api/tasks/export", get(export_pending_tasks))
+        .route("/ui/api/sync", get(sync_tasks_api))
+        .route("/ui/api/preferences", get(get_preferences_api).put(update_preferences_api))
+        .route("/ui/api/saved-filters", get(list_saved_filters_api).post(create_saved_filter_api))
+        .route("/ui/api/saved-filters/:id", delete(delete_saved_filter_api))
+        .route("/ui/api/webhook-integrations", get(list_webhook_integrations_api).post(create_webhook_integration_api))
+        .route("/ui/api/webhook-integrations/:id", delete(delete_webhook_integration_api))
+        .route("/ui/api/sso-identities/:id", delete(delete_sso_identity_api))
+        .route("/ui/api/api-tokens", get(list_api_tokens_api).post(create_api_token_api))
+        .route("/ui/api/api-tokens/:id", delete(revoke_api_token_api))
+        .route("/ui/api/email-accounts/:id", delete(remove_email_account_api))
+        .route("/ui/api/email-accounts/:id/disconnect", post(disconnect_email_account_api))
+        .route("/ui/api/cases/:id", get(get_case_detail_api))
+        .route("/ui/api/cases/:id/share", post(share_case_api))
+        .route("/ui/api/cases/:id/transfer", post(transfer_case_api))
+        .route("/ui/api/cases/:id/export.md", get(export_case_markdown_api))
+        .route("/ui/api/cases/:id/export/notion", post(export_case_to_notion_api))
+        .route("/ui/api/chat", post(send_chat_message))
+        .route("/ui/api/chat/stream", post(stream_chat_message))
+        .route("/ui/api/tasks/:id/complete", put(complete_task_api))
+        .route("/ui/api/tasks/:id/status", put(update_task_status_api).patch(update_task_status_api))
+        .route("/ui/api/tasks/:id", put(edit_task_api))
+        .route("/ui/api/tasks/:id/snooze", put(snooze_task_api))
+        .route("/ui/api/review-queue/:id/:decision", put(review_task_api))
+        .route("/ui/api/notifications", get(get_notifications_api))
+        .route("/ui/api/notifications/read-all", post(mark_all_notifications_read_api))
+        .route("/ui/api/notifications/:id/read", post(mark_notification_read_api))
+        .route("/ui/api/push/vapid-key", get(get_vapid_key_api))
+        .route("/ui/api/push/subscribe", post(subscribe_push_api))
+        .route("/ui/api/push/unsubscribe", post(unsubscribe_push_api))
+        // Service workers only control pages at or below their own path.
+        .route_service("/sw.js", ServeFile::new(format!("{}/sw.js", static_dir)))
+        .route_service("/manifest.webmanifest", ServeFile::new(format!("{}/manifest.webmanifest", static_dir)))
         .route("/oauth/login", get(oauth_login))
         .route("/oauth/callback", get(oauth_callback))
+        .nest_service("/static", ServeDir::new(static_dir))
         .with_state(Arc::new(state))
+        .merge(live.router())
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
+                .layer(logging::request_layers(&config.service_name))
                 .layer(CookieManagerLayer::new())
-                .layer(CorsLayer::permissive()),
+                .layer(config.cors.layer()),
         );
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port)).await?;
     info!("Dashboard Service listening on port {}", config.port);
 
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     Ok(())
 }
 
@@ -107,9 +177,16 @@ async fn get_current_user(state: &AppState, cookies: &CookieJar) -> Option<UserP
     let url = format!("{}/api/v1/auth/validate", state.config.service_url("persistence"));
     let request = serde_json::json!({ "session_token": session_token });
     
-    match state.http_client.post::<serde_json::Value, UserProfile>(&url, &request).await {
-        Ok(user) => Some(user),
-        Err(_) => None,
+    state.http_client.post::<serde_json::Value, UserProfile>(&url, &request).await.ok()
+}
+
+/// Client that calls downstream services on behalf of the logged-in user by
+/// forwarding their session token.  Without a session the calls go out
+/// anonymously, never as internal calls.
+fn session_client(state: &AppState, cookies: &CookieJar) -> HttpClient {
+    match cookies.get("session_token") {
+        Some(cookie) => state.http_client.with_session_token(cookie.value()),
+        None => state.http_client.without_service_credentials(),
     }
 }
 
@@ -119,187 +196,26 @@ async fn dashboard_home(
     State(state): State<Arc<AppState>>,
     cookies: CookieJar,
 ) -> ServiceResult<Html<String>> {
-    if let Some(_user) = get_current_user(&state, &cookies).await {
-        // User is logged in, redirect to dashboard
-        Ok(Html(r#"<script>window.location.href = '/dashboard';</script>"#.to_string()))
+    if let Some(user) = get_current_user(&state, &cookies).await {
+        // User is logged in, redirect to their preferred view
+        Ok(Html(format!(
+            r#"<script>window.location.href = '{}';</script>"#,
+            user.preferences.default_view.path()
+        )))
     } else {
         // User not logged in, redirect to login
         Ok(Html(r#"<script>window.location.href = '/login';</script>"#.to_string()))
     }
 }
 
-#[instrument]
-async fn show_login_page() -> Html<String> {
-    let html = r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="utf-8"/>
-    <meta name="viewport" content="width=device-width, initial-scale=1"/>
-    <title>Login • Task Manager</title>
-    <script src="https://cdn.tailwindcss.com"></script>
-</head>
-<body class="min-h-screen bg-gradient-to-br from-blue-50 to-indigo-100">
-    <div class="min-h-screen flex items-center justify-center py-12 px-4 sm:px-6 lg:px-8">
-        <div class="max-w-md w-full space-y-8">
-            <div class="text-center">
-                <div class="mx-auto h-16 w-16 rounded-full bg-blue-600 text-white flex items-center justify-center text-2xl font-bold">TM</div>
-                <h2 class="mt-6 text-3xl font-extrabold text-gray-900">Sign in to your account</h2>
-                <p class="mt-2 text-sm text-gray-600">
-                    Or <a href="/register" class="font-medium text-blue-600 hover:text-blue-500">create a new account</a>
-                </p>
-            </div>
-            <form class="mt-8 space-y-6" id="loginForm">
-                <div class="rounded-md shadow-sm -space-y-px">
-                    <div>
-                        <label for="email" class="sr-only">Email address</label>
-                        <input id="email" name="email" type="email" required 
-                               class="relative block w-full px-3 py-2 border border-gray-300 placeholder-gray-500 text-gray-900 rounded-t-md focus:outline-none focus:ring-blue-500 focus:border-blue-500 focus:z-10 sm:text-sm" 
-                               placeholder="Email address">
-                    </div>
-                    <div>
-                        <label for="password" class="sr-only">Password</label>
-                        <input id="password" name="password" type="password" required 
-                               class="relative block w-full px-3 py-2 border border-gray-300 placeholder-gray-500 text-gray-900 rounded-b-md focus:outline-none focus:ring-blue-500 focus:border-blue-500 focus:z-10 sm:text-sm" 
-                               placeholder="Password">
-                    </div>
-                </div>
-                <div>
-                    <button type="submit" 
-                            class="group relative w-full flex justify-center py-2 px-4 border border-transparent text-sm font-medium rounded-md text-white bg-blue-600 hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-blue-500">
-                        Sign in
-                    </button>
-                </div>
-                <div id="error-message" class="hidden text-red-600 text-sm text-center"></div>
-            </form>
-        </div>
-    </div>
-    
-    <script>
-        document.getElementById('loginForm').addEventListener('submit', async (e) => {
-            e.preventDefault();
-            const formData = new FormData(e.target);
-            const data = {
-                email: formData.get('email'),
-                password: formData.get('password')
-            };
-            
-            try {
-                const response = await fetch('/api/auth/login', {
-                    method: 'POST',
-                    headers: { 'Content-Type': 'application/json' },
-                    body: JSON.stringify(data)
-                });
-                
-                if (response.ok) {
-                    window.location.href = '/dashboard';
-                } else {
-                    const error = await response.text();
-                    document.getElementById('error-message').textContent = error || 'Login failed';
-                    document.getElementById('error-message').classList.remove('hidden');
-                }
-            } catch (error) {
-                document.getElementById('error-message').textContent = 'Network error';
-                document.getElementById('error-message').classList.remove('hidden');
-            }
-        });
-    </script>
-</body>
-</html>"#;
-    Html(html.to_string())
+#[instrument(skip(state))]
+async fn show_login_page(State(state): State<Arc<AppState>>) -> ServiceResult<Html<String>> {
+    templates::render(&templates::LoginPage { providers: state.sso.providers() })
 }
 
 #[instrument]
-async fn show_register_page() -> Html<String> {
-    let html = r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="utf-8"/>
-    <meta name="viewport" content="width=device-width, initial-scale=1"/>
-    <title>Register • Task Manager</title>
-    <script src="https://cdn.tailwindcss.com"></script>
-</head>
-<body class="min-h-screen bg-gradient-to-br from-blue-50 to-indigo-100">
-    <div class="min-h-screen flex items-center justify-center py-12 px-4 sm:px-6 lg:px-8">
-        <div class="max-w-md w-full space-y-8">
-            <div class="text-center">
-                <div class="mx-auto h-16 w-16 rounded-full bg-blue-600 text-white flex items-center justify-center text-2xl font-bold">TM</div>
-                <h2 class="mt-6 text-3xl font-extrabold text-gray-900">Create your account</h2>
-                <p class="mt-2 text-sm text-gray-600">
-                    Or <a href="/login" class="font-medium text-blue-600 hover:text-blue-500">sign in to existing account</a>
-                </p>
-            </div>
-            <form class="mt-8 space-y-6" id="registerForm">
-                <div class="space-y-4">
-                    <div>
-                        <label for="full_name" class="block text-sm font-medium text-gray-700">Full Name</label>
-                        <input id="full_name" name="full_name" type="text" required 
-                               class="mt-1 block w-full px-3 py-2 border border-gray-300 rounded-md shadow-sm placeholder-gray-400 focus:outline-none focus:ring-blue-500 focus:border-blue-500 sm:text-sm" 
-                               placeholder="Your full name">
-                    </div>
-                    <div>
-                        <label for="email" class="block text-sm font-medium text-gray-700">Email Address</label>
-                        <input id="email" name="email" type="email" required 
-                               class="mt-1 block w-full px-3 py-2 border border-gray-300 rounded-md shadow-sm placeholder-gray-400 focus:outline-none focus:ring-blue-500 focus:border-blue-500 sm:text-sm" 
-                               placeholder="your@email.com">
-                    </div>
-                    <div>
-                        <label for="organization" class="block text-sm font-medium text-gray-700">Organization (Optional)</label>
-                        <input id="organization" name="organization" type="text" 
-                               class="mt-1 block w-full px-3 py-2 border border-gray-300 rounded-md shadow-sm placeholder-gray-400 focus:outline-none focus:ring-blue-500 focus:border-blue-500 sm:text-sm" 
-                               placeholder="Your company or organization">
-                    </div>
-                    <div>
-                        <label for="password" class="block text-sm font-medium text-gray-700">Password</label>
-                        <input id="password" name="password" type="password" required 
-                               class="mt-1 block w-full px-3 py-2 border border-gray-300 rounded-md shadow-sm placeholder-gray-400 focus:outline-none focus:ring-blue-500 focus:border-blue-500 sm:text-sm" 
-                               placeholder="Choose a strong password">
-                    </div>
-                </div>
-                <div>
-                    <button type="submit" 
-                            class="group relative w-full flex justify-center py-2 px-4 border border-transparent text-sm font-medium rounded-md text-white bg-blue-600 hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-blue-500">
-                        Create Account
-                    </button>
-                </div>
-                <div id="error-message" class="hidden text-red-600 text-sm text-center"></div>
-            </form>
-        </div>
-    </div>
-    
-    <script>
-        document.getElementById('registerForm').addEventListener('submit', async (e) => {
-            e.preventDefault();
-            const formData = new FormData(e.target);
-            const data = {
-                full_name: formData.get('full_name'),
-                email: formData.get('email'),
-                organization: formData.get('organization') || null,
-                password: formData.get('password')
-            };
-            
-            try {
-                const response = await fetch('/api/auth/register', {
-                    method: 'POST',
-                    headers: { 'Content-Type': 'application/json' },
-                    body: JSON.stringify(data)
-                });
-                
-                if (response.ok) {
-                    window.location.href = '/login?registered=true';
-                } else {
-                    const error = await response.text();
-                    document.getElementById('error-message').textContent = error || 'Registration failed';
-                    document.getElementById('error-message').classList.remove('hidden');
-                }
-            } catch (error) {
-                document.getElementById('error-message').textContent = 'Network error';
-                document.getElementById('error-message').classList.remove('hidden');
-            }
-        });
-    </script>
-</body>
-</html>"#;
-    Html(html.to_string())
+async fn show_register_page() -> ServiceResult<Html<String>> {
+    templates::render(&templates::RegisterPage)
 }
 
 // Authentication API handlers
@@ -319,24 +235,20 @@ async fn handle_register(
     }
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, headers))]
 async fn handle_login(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     cookies: CookieJar,
-    Json(request): Json<LoginRequest>,
+    Json(mut request): Json<LoginRequest>,
 ) -> ServiceResult<(CookieJar, Json<UserProfile>)> {
+    request.client = client_info(&headers, peer);
     let url = format!("{}/api/v1/auth/login", state.config.service_url("persistence"));
     
     match state.http_client.post::<LoginRequest, LoginResponse>(&url, &request).await {
         Ok(login_response) => {
-            // Set session cookie
-            let cookie = Cookie::build(("session_token", login_response.session_token))
-                .path("/")
-                .http_only(true)
-                .max_age(tower_cookies::cookie::time::Duration::hours(24))
-                .build();
-            
-            let updated_cookies = cookies.add(cookie);
+            let updated_cookies = cookies.add(session_cookie(login_response.session_token));
             Ok((updated_cookies, Json(login_response.user)))
         }
         Err(e) => {
@@ -346,6 +258,136 @@ async fn handle_login(
     }
 }
 
+/// The browser's address and user agent; whatever the browser claims in
+/// the login request is replaced. Behind a proxy, the address is the
+/// rightmost of `X-Forwarded-For`, the one the proxy saw.
+fn client_info(headers: &HeaderMap, peer: SocketAddr) -> ClientInfo {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let forwarded = header("x-forwarded-for")
+        .and_then(|forwarded| forwarded.rsplit(',').next())
+        .map(|address| address.trim().to_string())
+        .filter(|address| address.parse::<IpAddr>().is_ok());
+    ClientInfo {
+        ip_address: Some(forwarded.unwrap_or_else(|| peer.ip().to_string())),
+        user_agent: header("user-agent").map(str::to_string),
+    }
+}
+
+fn session_cookie(session_token: String) -> Cookie<'static> {
+    Cookie::build(("session_token", session_token))
+        .path("/")
+        .http_only(true)
+        .max_age(tower_cookies::cookie::time::Duration::hours(24))
+        .build()
+}
+
+/// Flows of the state store.
+const SSO_FLOW: &str = "sso";
+const EMAIL_FLOW: &str = "email";
+
+#[derive(Debug, Deserialize)]
+struct SsoLoginQuery {
+    /// Links the provider's account to the signed-in user.
+    #[serde(default)]
+    link: bool,
+}
+
+/// Starts signing in with an OpenID Connect provider.
+#[instrument(skip(state, cookies))]
+async fn sso_login(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(provider): Path<String>,
+    Query(query): Query<SsoLoginQuery>,
+) -> ServiceResult<Redirect> {
+    if query.link && get_current_user(&state, &cookies).await.is_none() {
+        return Ok(Redirect::to("/login"));
+    }
+    let sso_state = state.states.new_state(SSO_FLOW);
+    let (auth_url, login) = state.sso.authorization_url(&provider, query.link, sso_state.clone()).await?;
+    state.states.save(&sso_state, &login).await?;
+
+    info!("SSO login with {} initiated", provider);
+    Ok(Redirect::to(auth_url.as_str()))
+}
+
+/// Completes a sign-in: starts a session, or links the account to the
+/// signed-in user.
+#[instrument(skip(state, headers, cookies, params))]
+async fn sso_callback(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(provider): Path<String>,
+    Query(params): Query<CallbackQuery>,
+) -> ServiceResult<Response> {
+    let failure = |message: String| {
+        templates::render(&templates::OAuthResultPage {
+            success: false,
+            heading: "Sign-in Failed".to_string(),
+            message,
+            link_href: "/login",
+            link_label: "Back to Sign In",
+        })
+        .map(IntoResponse::into_response)
+    };
+
+    if let Some(error) = params.error {
+        return failure(format!("The provider reported an error: {}", error));
+    }
+    let code = params.code
+        .ok_or_else(|| common::ServiceError::BadRequest("Missing authorization code".to_string()))?;
+    let state_param = params.state
+        .ok_or_else(|| common::ServiceError::BadRequest("Missing state parameter".to_string()))?;
+    let login: sso::LoginState = state.states.take(SSO_FLOW, &state_param).await?;
+    if login.provider != provider {
+        return Err(common::ServiceError::BadRequest("Invalid or expired state parameter".to_string()));
+    }
+    let link = login.link;
+
+    let mut claims = match state.sso.verify(code, login).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            error!("SSO verification with {} failed: {}", provider, e);
+            return failure("Your sign-in could not be verified. Please try again.".to_string());
+        }
+    };
+    let persistence = state.config.service_url("persistence");
+
+    if link {
+        let url = format!("{}/api/v1/auth/identities", persistence);
+        return match session_client(&state, &cookies).post::<SsoLoginRequest, SsoIdentity>(&url, &claims).await {
+            Ok(_) => Ok(Redirect::to("/config").into_response()),
+            Err(e) => {
+                error!("Linking {} account failed: {}", provider, e);
+                failure("The account could not be linked. It may already be linked to another user.".to_string())
+            }
+        };
+    }
+
+    claims.client = client_info(&headers, peer);
+    let url = format!("{}/api/v1/auth/sso", persistence);
+    match state.http_client.post::<SsoLoginRequest, LoginResponse>(&url, &claims).await {
+        Ok(login_response) => {
+            let path = login_response.user.preferences.default_view.path();
+            let updated_cookies = cookies.add(session_cookie(login_response.session_token));
+            Ok((updated_cookies, Redirect::to(path)).into_response())
+        }
+        Err(e) if e.status() == Some(reqwest::StatusCode::UNAUTHORIZED) => {
+            let label = state.sso.providers().iter().find(|p| p.name == provider).map_or(provider.as_str(), |p| p.label.as_str());
+            failure(format!(
+                "{} can't sign in with {}. If you have an account with this email, sign in with its password and link {} from Settings.",
+                claims.email, label, label
+            ))
+        }
+        Err(e) => {
+            error!("SSO login failed: {}", e);
+            failure("Sign-in failed. Please try again.".to_string())
+        }
+    }
+}
+
 #[instrument]
 async fn handle_logout(cookies: CookieJar) -> ServiceResult<(CookieJar, Json<serde_json::Value>)> {
     let cookie = Cookie::build(("session_token", ""))
@@ -361,6 +403,7 @@ async fn handle_logout(cookies: CookieJar) -> ServiceResult<(CookieJar, Json<ser
 async fn show_pending_tasks(
     State(state): State<Arc<AppState>>,
     cookies: CookieJar,
+    Query(query): Query<DashboardQuery>,
 ) -> ServiceResult<Html<String>> {
     // Check if user is authenticated
     let user = match get_current_user(&state, &cookies).await {
@@ -369,174 +412,991 @@ async fn show_pending_tasks(
             return Ok(Html(r#"<script>window.location.href = '/login';</script>"#.to_string()));
         }
     };
+    let client = session_client(&state, &cookies);
+    let task_url = state.config.service_url("task-management");
+    let filters = client
+        .get::<Vec<SavedFilter>>(&format!("{}/api/v1/saved-filters", task_url))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    let tasks = match query.filter {
+        Some(filter_id) => client
+            .get::<Vec<Task>>(&format!("{}/api/v1/saved-filters/{}/tasks", task_url, filter_id))
+            .await,
+        None => client
+            .get_with_query::<Vec<Task>, _>(
+                &format!("{}/api/v1/tasks?status=Pending", task_url),
+                &TagFilter { tag: query.tag.as_deref() },
+            )
+            .await,
+    }
+    .map_err(common::ServiceError::HttpClient)?;
+
+    let review_queue = client
+        .get::<Vec<Task>>(&format!("{}/api/v1/review-queue", task_url))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    // Due in the coming week, for the overcommitment warning.
+    let workload = client
+        .get::<Vec<WorkloadDay>>(&format!("{}/api/v1/stats/workload", task_url))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    // Open cases the agent scored as urgent, most urgent first.
+    let mut inbox = client
+        .get::<Vec<Case>>(&format!("{}/api/v1/cases?status=Open&sort=urgency", state.config.service_url("case-management")))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    inbox.retain(|case| case.urgency().is_some_and(|urgency| urgency.score > 0));
+    inbox.truncate(PRIORITY_INBOX_SIZE);
+
+    let active_filter = query.filter.and_then(|id| filters.iter().find(|f| f.id == id));
+    templates::render(&templates::DashboardPage::new(
+        &user,
+        &tasks,
+        query.page,
+        query.tag.as_deref(),
+        &filters,
+        active_filter,
+        &workload,
+    )
+    .with_review_queue(&review_queue)
+    .with_priority_inbox(&inbox))
+}
+
+/// Urgent cases listed above the pending tasks.
+const PRIORITY_INBOX_SIZE: usize = 5;
+
+#[derive(Debug, Deserialize)]
+struct DashboardQuery {
+    #[serde(default = "first_page")]
+    page: usize,
+    tag: Option<String>,
+    /// Saved filter to list instead of the pending tasks.
+    filter: Option<Uuid>,
+}
+
+/// Restricts a task listing to one tag; omitted from the URL when unset.
+#[derive(Debug, serde::Serialize)]
+struct TagFilter<'a> {
+    tag: Option<&'a str>,
+}
+
+fn first_page() -> usize {
+    1
+}
+
+#[instrument(skip(state))]
+async fn show_config_page(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+) -> ServiceResult<Html<String>> {
+    let user = match get_current_user(&state, &cookies).await {
+        Some(user) => user,
+        None => {
+            return Ok(Html(r#"<script>window.location.href = '/login';</script>"#.to_string()));
+        }
+    };
+    let client = session_client(&state, &cookies);
+    let url = format!("{}/api/v1/saved-filters", state.config.service_url("task-management"));
+    let filters = client
+        .get::<Vec<SavedFilter>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    let url = format!("{}/api/v1/webhook-integrations", state.config.service_url("channel"));
+    let integrations = client
+        .get::<Vec<WebhookIntegration>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    let url = format!("{}/api/v1/auth/identities", state.config.service_url("persistence"));
+    let identities = client
+        .get::<Vec<SsoIdentity>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    let url = format!("{}/api/v1/users/me/tokens", state.config.service_url("persistence"));
+    let tokens = client
+        .get::<Vec<ApiToken>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    let url = format!("{}/api/v1/email-accounts", state.config.service_url("persistence"));
+    let email_accounts = client
+        .get::<Vec<EmailAccount>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    templates::render(&templates::ConfigPage {
+        user: &user,
+        filters: &filters,
+        integrations: &integrations,
+        identities: &identities,
+        tokens: &tokens,
+        providers: state.sso.providers(),
+        email_accounts: &email_accounts,
+        email_enabled: state.oauth_manager.is_some(),
+    })
+}
+
+/// Recent sign-ins to the account, failed attempts included.
+#[instrument(skip(state))]
+async fn show_security_page(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+) -> ServiceResult<Html<String>> {
+    let user = match get_current_user(&state, &cookies).await {
+        Some(user) => user,
+        None => {
+            return Ok(Html(r#"<script>window.location.href = '/login';</script>"#.to_string()));
+        }
+    };
+    let url = format!("{}/api/v1/users/me/logins", state.config.service_url("persistence"));
+    let events = session_client(&state, &cookies)
+        .get::<Vec<LoginEvent>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    templates::render(&templates::SecurityPage { user: &user, events: &events })
+}
+
+#[instrument(skip(state))]
+async fn get_preferences_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+) -> ServiceResult<Json<UserPreferences>> {
+    let url = format!("{}/api/v1/users/me/preferences", state.config.service_url("persistence"));
+    let preferences = session_client(&state, &cookies)
+        .get::<UserPreferences>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(preferences))
+}
+
+#[instrument(skip(state))]
+async fn update_preferences_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Json(request): Json<UpdatePreferencesRequest>,
+) -> ServiceResult<Json<UserPreferences>> {
+    let url = format!("{}/api/v1/users/me/preferences", state.config.service_url("persistence"));
+    let preferences = session_client(&state, &cookies)
+        .put::<UpdatePreferencesRequest, UserPreferences>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(preferences))
+}
+
+#[instrument(skip(state))]
+async fn list_saved_filters_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+) -> ServiceResult<Json<Vec<SavedFilter>>> {
+    let url = format!("{}/api/v1/saved-filters", state.config.service_url("task-management"));
+    let filters = session_client(&state, &cookies)
+        .get::<Vec<SavedFilter>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(filters))
+}
+
+#[instrument(skip(state))]
+async fn create_saved_filter_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Json(request): Json<SavedFilterRequest>,
+) -> ServiceResult<Json<SavedFilter>> {
+    let url = format!("{}/api/v1/saved-filters", state.config.service_url("task-management"));
+    let saved = session_client(&state, &cookies)
+        .post::<SavedFilterRequest, SavedFilter>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(saved))
+}
+
+#[instrument(skip(state))]
+async fn delete_saved_filter_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    let url = format!("{}/api/v1/saved-filters/{}", state.config.service_url("task-management"), id);
+    session_client(&state, &cookies)
+        .delete(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip(state))]
+async fn list_webhook_integrations_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+) -> ServiceResult<Json<Vec<WebhookIntegration>>> {
+    let url = format!("{}/api/v1/webhook-integrations", state.config.service_url("channel"));
+    let integrations = session_client(&state, &cookies)
+        .get::<Vec<WebhookIntegration>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(integrations))
+}
+
+#[instrument(skip(state))]
+async fn create_webhook_integration_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Json(request): Json<WebhookIntegrationRequest>,
+) -> ServiceResult<Json<WebhookIntegration>> {
+    let url = format!("{}/api/v1/webhook-integrations", state.config.service_url("channel"));
+    let integration = session_client(&state, &cookies)
+        .post::<WebhookIntegrationRequest, WebhookIntegration>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(integration))
+}
+
+#[instrument(skip(state))]
+async fn delete_webhook_integration_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    let url = format!("{}/api/v1/webhook-integrations/{}", state.config.service_url("channel"), id);
+    session_client(&state, &cookies)
+        .delete(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip(state))]
+async fn delete_sso_identity_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    let url = format!("{}/api/v1/auth/identities/{}", state.config.service_url("persistence"), id);
+    session_client(&state, &cookies)
+        .delete(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Stops polling a mailbox and drops its tokens. Microsoft has no way to
+/// revoke one app's tokens, so the consent itself is withdrawn by the user
+/// at My Apps, which the settings page links to.
+#[instrument(skip(state, cookies))]
+async fn disconnect_email_account_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<EmailAccount>> {
+    let url = format!("{}/api/v1/email-accounts/{}/disconnect", state.config.service_url("persistence"), id);
+    let account = session_client(&state, &cookies)
+        .post(&url, &serde_json::json!({}))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(account))
+}
+
+/// Removes a mailbox along with what the email collector fetched from it.
+#[instrument(skip(state, cookies))]
+async fn remove_email_account_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    let url = format!("{}/api/v1/email-accounts/{}", state.config.service_url("persistence"), id);
+    session_client(&state, &cookies)
+        .delete(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip(state))]
+async fn list_api_tokens_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+) -> ServiceResult<Json<Vec<ApiToken>>> {
+    let url = format!("{}/api/v1/users/me/tokens", state.config.service_url("persistence"));
+    let tokens = session_client(&state, &cookies)
+        .get::<Vec<ApiToken>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(tokens))
+}
+
+#[instrument(skip(state))]
+async fn create_api_token_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Json(request): Json<CreateApiTokenRequest>,
+) -> ServiceResult<Json<ApiToken>> {
+    let url = format!("{}/api/v1/users/me/tokens", state.config.service_url("persistence"));
+    let token = session_client(&state, &cookies)
+        .post::<CreateApiTokenRequest, ApiToken>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(token))
+}
+
+#[instrument(skip(state))]
+async fn revoke_api_token_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    let url = format!("{}/api/v1/users/me/tokens/{}", state.config.service_url("persistence"), id);
+    session_client(&state, &cookies)
+        .delete(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip(state))]
+async fn get_pending_tasks_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+) -> ServiceResult<Json<Vec<Task>>> {
     let url = format!(
         "{}/api/v1/tasks?status=Pending",
         state.config.service_url("task-management")
     );
-    let tasks = state
-        .http_client
+    let tasks = session_client(&state, &cookies)
         .get::<Vec<Task>>(&url)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
-
-    let html = format!(r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="utf-8"/>
-    <meta name="viewport" content="width=device-width, initial-scale=1"/>
-    <title>Task Manager • Dashboard</title>
-    <script src="https://cdn.tailwindcss.com"></script>
-</head>
-<body class="min-h-screen bg-gray-50">
-    <div class="max-w-7xl mx-auto px-4 py-8">
-        <header class="mb-8">
-            <div class="flex items-center justify-between">
-                <div class="flex items-center gap-3">
-                    <div class="h-10 w-10 rounded-lg bg-blue-600 text-white flex items-center justify-center font-bold">TM</div>
-                    <div>
-                        <h1 class="text-2xl font-bold text-gray-900">Task Manager</h1>
-                        <p class="text-gray-600">Welcome, {} • Dashboard</p>
-                    </div>
-                </div>
-                <div class="flex items-center gap-3">
-                    <div class="flex items-center gap-2 text-sm text-gray-600">
-                        <div class="h-8 w-8 rounded-full bg-gray-300 flex items-center justify-center text-xs font-medium">
-                            {}
-                        </div>
-                        <span>{}</span>
-                    </div>
-                    <a href="/config" class="bg-gray-600 hover:bg-gray-700 text-white rounded-md px-4 py-2 text-sm font-medium flex items-center gap-2">
-                        ⚙️ Settings
-                    </a>
-                    <a href="/oauth/login" class="bg-green-600 hover:bg-green-700 text-white rounded-md px-4 py-2 text-sm font-medium flex items-center gap-2">
-                        📧 Connect Email
-                    </a>
-                    <button onclick="location.reload()" class="bg-white border border-gray-300 rounded-md px-4 py-2 text-sm font-medium text-gray-700 hover:bg-gray-50">
-                        Refresh
-                    </button>
-                    <button onclick="logout()" class="bg-red-600 hover:bg-red-700 text-white rounded-md px-4 py-2 text-sm font-medium">
-                        Logout
-                    </button>
-                </div>
-            </div>
-        </header>
-        
-        <main>
-            <div class="mb-6">
-                <h2 class="text-3xl font-bold text-gray-900 mb-2">Pending Tasks</h2>
-                <p class="text-gray-600 mb-4">You have {} pending tasks</p>
-                <input type="search" placeholder="Search tasks..." class="w-full max-w-md border border-gray-300 rounded-lg px-4 py-2 text-sm focus:ring-2 focus:ring-blue-500 focus:border-transparent"/>
-            </div>
-            
-            <div class="grid grid-cols-1 md:grid-cols-2 lg:grid-cols-3 gap-6">
-                {}
-            </div>
-        </main>
-    </div>
-    
-    <script>
-        async function logout() {{
-            try {{
-                await fetch('/api/auth/logout', {{ method: 'POST' }});
-                window.location.href = '/login';
-            }} catch (error) {{
-                console.error('Logout failed:', error);
-                window.location.href = '/login';
-            }}
-        }}
-    </script>
-</body>
-</html>"#, 
-        user.full_name,
-        user.full_name.chars().next().unwrap_or('U').to_uppercase().collect::<String>(),
-        user.email,
-        tasks.len(),
-        if tasks.is_empty() {
-            r#"<div class="col-span-full text-center py-12">
-                <div class="text-gray-400 text-lg mb-2">🎉</div>
-                <h3 class="text-lg font-medium text-gray-900 mb-1">All caught up!</h3>
-                <p class="text-gray-500">No pending tasks right now.</p>
-            </div>"#.to_string()
-        } else {
-            tasks.iter().map(|task| {
-                format!(r#"<div class="bg-white rounded-xl border border-gray-200 p-6 shadow-sm hover:shadow-md transition-shadow">
-                    <div class="flex items-start justify-between mb-4">
-                        <h3 class="text-lg font-semibold text-gray-900">{}</h3>
-                        <span class="bg-yellow-100 text-yellow-800 text-xs font-medium px-2.5 py-0.5 rounded-full">Pending</span>
-                    </div>
-                    <div class="flex items-center justify-between">
-                        <div class="flex items-center text-sm text-gray-500">
-                            <svg class="w-4 h-4 mr-2" fill="none" stroke="currentColor" viewBox="0 0 24 24">
-                                <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M12 8v4l3 3m6-3a9 9 0 11-18 0 9 9 0 0118 0z"/>
-                            </svg>
-                            Awaiting action
-                        </div>
-                        <button class="text-blue-600 hover:text-blue-700 text-sm font-medium">View</button>
-                    </div>
-                </div>"#, html_escape::encode_text(&task.title))
-            }).collect::<Vec<_>>().join("")
-        }
-    );
-    
-    Ok(Html(html))
-}
-
-async fn show_config_page() -> Html<String> {
-    let html = r#"<!DOCTYPE html>
-    <html lang="en">
-    <head>
-        <meta charset="UTF-8">
-        <meta name="viewport" content="width=device-width, initial-scale=1.0">
-        <title>User Configuration</title>
-        <script src="https://cdn.tailwindcss.com"></script>
-    </head>
-    <body class="bg-gray-50">
-        <div class="min-h-screen flex items-center justify-center py-12 px-4 sm:px-6 lg:px-8">
-            <div class="max-w-md w-full space-y-8">
-                <div>
-                    <h2 class="mt-6 text-center text-3xl font-extrabold text-gray-900">
-                        User Configuration
-                    </h2>
-                    <p class="mt-2 text-center text-sm text-gray-600">
-                        Manage your account settings and email connections
-                    </p>
-                </div>
-                <div class="mt-8 space-y-6">
-                    <div class="bg-white p-6 rounded-lg shadow">
-                        <h3 class="text-lg font-medium text-gray-900 mb-4">Email Accounts</h3>
-                        <button class="w-full flex justify-center py-2 px-4 border border-transparent rounded-md shadow-sm text-sm font-medium text-white bg-blue-600 hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-blue-500">
-                            Add Email Account
-                        </button>
-                    </div>
-                    <div class="bg-white p-6 rounded-lg shadow">
-                        <h3 class="text-lg font-medium text-gray-900 mb-4">Database Settings</h3>
-                        <p class="text-sm text-gray-600">Database configuration options will be available here.</p>
-                    </div>
-                    <div class="text-center">
-                        <a href="/dashboard" class="text-blue-600 hover:text-blue-500">
-                            Back to Dashboard
-                        </a>
-                    </div>
-                </div>
-            </div>
-        </div>
-    </body>
-    </html>"#;
-    Html(html.to_string())
-}
-
-#[instrument(skip(state))]
-async fn get_pending_tasks_api(State(state): State<Arc<AppState>>) -> ServiceResult<Json<Vec<Task>>> {
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(tasks))
+}
+
+/// Task delta for the offline cache, e.g. `?since=2024-01-01T00:00:00Z`.
+#[instrument(skip(state))]
+async fn sync_tasks_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    RawQuery(query): RawQuery,
+) -> ServiceResult<Json<TaskSync>> {
+    let mut url = format!("{}/api/v1/sync/tasks", state.config.service_url("task-management"));
+    if let Some(query) = query {
+        url = format!("{}?{}", url, query);
+    }
+    let sync = session_client(&state, &cookies)
+        .get::<TaskSync>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(sync))
+}
+
+#[instrument(skip(state))]
+async fn export_pending_tasks(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Query(export): Query<ExportQuery>,
+) -> ServiceResult<Response> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+
     let url = format!(
         "{}/api/v1/tasks?status=Pending",
         state.config.service_url("task-management")
     );
-    let tasks = state
-        .http_client
+    let tasks = session_client(&state, &cookies)
         .get::<Vec<Task>>(&url)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
-    Ok(Json(tasks))
+        .map_err(common::ServiceError::HttpClient)?;
+
+    export_response(tasks, &export, "pending-tasks")
+}
+
+/// Loads a case together with its tasks, latest conversation, workflow and
+/// timeline. The lookups are independent so they run concurrently.
+async fn fetch_case_detail(
+    state: &AppState,
+    client: &HttpClient,
+    case_id: Uuid,
+) -> ServiceResult<case_page::CaseDetail> {
+    let case_url = format!("{}/api/v1/cases/{}", state.config.service_url("case-management"), case_id);
+    let history_url = format!("{}/history?limit={}", case_url, case_page::HISTORY_LIMIT);
+    let workflow_url = format!("{}/workflow", case_url);
+    let timeline_url = format!("{}/timeline?limit={}", case_url, case_page::HISTORY_LIMIT);
+    let tasks_url = format!("{}/api/v1/cases/{}/tasks", state.config.service_url("task-management"), case_id);
+
+    let (case, conversation, workflow, timeline, tasks) = tokio::try_join!(
+        client.get::<Case>(&case_url),
+        client.get::<Vec<ConversationEntry>>(&history_url),
+        client.get::<CaseWorkflow>(&workflow_url),
+        client.get::<Vec<TimelineEntry>>(&timeline_url),
+        client.get::<Vec<Task>>(&tasks_url),
+    )
+    .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(case_page::CaseDetail { case, tasks, conversation, workflow, timeline })
+}
+
+#[instrument(skip(state))]
+async fn show_board(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+) -> ServiceResult<Html<String>> {
+    let user = match get_current_user(&state, &cookies).await {
+        Some(user) => user,
+        None => {
+            return Ok(Html(r#"<script>window.location.href = '/login';</script>"#.to_string()));
+        }
+    };
+
+    let url = format!("{}/api/v1/tasks", state.config.service_url("task-management"));
+    let tasks = session_client(&state, &cookies)
+        .get::<Vec<Task>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    templates::render(&board_page::BoardPage::new(&user, &tasks))
+}
+
+#[instrument(skip(state))]
+async fn show_reports(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Query(query): Query<reports_page::ReportsQuery>,
+) -> ServiceResult<Html<String>> {
+    let user = match get_current_user(&state, &cookies).await {
+        Some(user) => user,
+        None => {
+            return Ok(Html(r#"<script>window.location.href = '/login';</script>"#.to_string()));
+        }
+    };
+    if query.days <= 0 {
+        return Err(common::ServiceError::BadRequest("days must be positive".to_string()));
+    }
+
+    let from = (chrono::Utc::now() - chrono::Duration::days(query.days))
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let tasks_url = format!(
+        "{}/api/v1/stats/tasks?group_by={}&from={}",
+        state.config.service_url("task-management"),
+        query.group_by.as_str(),
+        from,
+    );
+    let productivity_url = format!(
+        "{}/api/v1/stats/productivity?from={}",
+        state.config.service_url("case-management"),
+        from,
+    );
+
+    let client = session_client(&state, &cookies);
+    let (stats, productivity) = tokio::try_join!(
+        client.get::<TaskStats>(&tasks_url),
+        client.get::<ProductivityStats>(&productivity_url),
+    )
+    .map_err(common::ServiceError::HttpClient)?;
+
+    templates::render(&reports_page::ReportsPage::new(&user, &stats, &productivity, query.days))
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatQuery {
+    case_id: Option<Uuid>,
+}
+
+#[instrument(skip(state))]
+async fn show_chat(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Query(query): Query<ChatQuery>,
+) -> ServiceResult<Html<String>> {
+    let user = match get_current_user(&state, &cookies).await {
+        Some(user) => user,
+        None => {
+            return Ok(Html(r#"<script>window.location.href = '/login';</script>"#.to_string()));
+        }
+    };
+
+    templates::render(&chat_page::ChatPage { user: &user, case_id: query.case_id })
+}
+
+#[instrument(skip(state, request))]
+async fn send_chat_message(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Json(request): Json<chat_page::ChatRequest>,
+) -> ServiceResult<Json<chat_page::ChatReply>> {
+    let user = get_current_user(&state, &cookies)
+        .await
+        .ok_or_else(|| common::ServiceError::Unauthorized("Not logged in".to_string()))?;
+    if request.message.trim().is_empty() {
+        return Err(common::ServiceError::BadRequest("Message cannot be empty".to_string()));
+    }
+    info!("Chat message from {} for case {:?}", user.id, request.case_id);
+
+    let client = session_client(&state, &cookies);
+    let message = MessageRequest {
+        case_id: request.case_id,
+        message: request.message,
+        sender_id: user.email,
+        channel: MessageChannel::WebChat,
+        email_thread: None,
+        owner_id: None,
+        priority: None,
+        tags: Vec::new(),
+    };
+
+    let channel_url = format!("{}/api/v1/message", state.config.service_url("channel"));
+    let response = client
+        .post::<MessageRequest, MessageResponse>(&channel_url, &message)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    let task_base_url = format!("{}/api/v1/tasks", state.config.service_url("task-management"));
+    let mut tasks_created = Vec::with_capacity(response.tasks_created.len());
+    for task_id in &response.tasks_created {
+        let task = client
+            .get::<Task>(&format!("{}/{}", task_base_url, task_id))
+            .await
+            .map_err(common::ServiceError::HttpClient)?;
+        tasks_created.push(task);
+    }
+
+    Ok(Json(chat_page::ChatReply {
+        case_id: response.case_id,
+        response: response.response,
+        actions_taken: response.actions_taken,
+        tasks_created,
+    }))
+}
+
+/// Relays the agent's server-sent events so the chat page can render the
+/// reply while it is being generated.
+#[instrument(skip(state, request))]
+async fn stream_chat_message(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Json(request): Json<chat_page::ChatRequest>,
+) -> ServiceResult<Response> {
+    let user = get_current_user(&state, &cookies)
+        .await
+        .ok_or_else(|| common::ServiceError::Unauthorized("Not logged in".to_string()))?;
+    if request.message.trim().is_empty() {
+        return Err(common::ServiceError::BadRequest("Message cannot be empty".to_string()));
+    }
+    info!("Streaming chat message from {} for case {:?}", user.id, request.case_id);
+
+    let message = MessageRequest {
+        case_id: request.case_id,
+        message: request.message,
+        sender_id: user.email,
+        channel: MessageChannel::WebChat,
+        email_thread: None,
+        owner_id: None,
+        priority: None,
+        tags: Vec::new(),
+    };
+
+    let channel_url = format!("{}/api/v1/message/stream", state.config.service_url("channel"));
+    let upstream = session_client(&state, &cookies)
+        .post_stream(&channel_url, &message)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/event-stream"), (header::CACHE_CONTROL, "no-cache")],
+        Body::from_stream(upstream.bytes_stream()),
+    )
+        .into_response())
+}
+
+#[instrument(skip(state))]
+async fn show_case_detail(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(case_id): Path<Uuid>,
+) -> ServiceResult<Html<String>> {
+    let user = match get_current_user(&state, &cookies).await {
+        Some(user) => user,
+        None => {
+            return Ok(Html(r#"<script>window.location.href = '/login';</script>"#.to_string()));
+        }
+    };
+
+    let client = session_client(&state, &cookies);
+    let detail = fetch_case_detail(&state, &client, case_id).await?;
+
+    // Viewing the case reads its conversation
+    let read_url = format!("{}/api/v1/cases/{}/read", state.config.service_url("case-management"), case_id);
+    if let Err(e) = client.post::<serde_json::Value, CaseReadReceipt>(&read_url, &serde_json::json!({})).await {
+        warn!("Could not mark case {} read: {}", case_id, e);
+    }
+
+    templates::render(&case_page::CasePage::new(&user, &detail))
+}
+
+#[instrument(skip(state))]
+async fn get_case_detail_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(case_id): Path<Uuid>,
+) -> ServiceResult<Json<case_page::CaseDetail>> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+
+    let detail = fetch_case_detail(&state, &session_client(&state, &cookies), case_id).await?;
+    Ok(Json(detail))
+}
+
+#[instrument(skip(state))]
+async fn share_case_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(case_id): Path<Uuid>,
+    Json(request): Json<CreateShareLinkRequest>,
+) -> ServiceResult<Json<ShareLink>> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+
+    let url = format!("{}/api/v1/cases/{}/share", state.config.service_url("case-management"), case_id);
+    let link = session_client(&state, &cookies)
+        .post::<CreateShareLinkRequest, ShareLink>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(link))
+}
+
+#[instrument(skip(state))]
+async fn transfer_case_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(case_id): Path<Uuid>,
+    Json(request): Json<TransferCaseRequest>,
+) -> ServiceResult<Json<Case>> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+
+    let url = format!("{}/api/v1/cases/{}/transfer", state.config.service_url("case-management"), case_id);
+    let case = session_client(&state, &cookies)
+        .post::<TransferCaseRequest, Case>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(case))
+}
+
+/// The case as a Markdown note, downloaded as a file.
+#[instrument(skip(state))]
+async fn export_case_markdown_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(case_id): Path<Uuid>,
+) -> ServiceResult<Response> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+
+    let url = format!("{}/api/v1/cases/{}/export.md", state.config.service_url("case-management"), case_id);
+    let upstream = session_client(&state, &cookies)
+        .get_stream(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    let disposition = upstream
+        .headers()
+        .get(reqwest::header::CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("attachment; filename=\"case.md\"")
+        .to_string();
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/markdown; charset=utf-8".to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        Body::from_stream(upstream.bytes_stream()),
+    )
+        .into_response())
+}
+
+#[instrument(skip(state))]
+async fn export_case_to_notion_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(case_id): Path<Uuid>,
+    Json(request): Json<NotionExportRequest>,
+) -> ServiceResult<Json<NotionExport>> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+
+    let url = format!("{}/api/v1/cases/{}/export/notion", state.config.service_url("case-management"), case_id);
+    let export = session_client(&state, &cookies)
+        .post::<NotionExportRequest, NotionExport>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(export))
+}
+
+/// A case opened from a share link, read-only and without a login.
+#[instrument(skip(state, token))]
+async fn show_shared_case(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> ServiceResult<Html<String>> {
+    let url = format!("{}/api/v1/shared/{}", state.config.service_url("case-management"), token);
+    let shared = match state.http_client.get::<SharedCase>(&url).await {
+        Ok(shared) => Some(shared),
+        Err(e) => {
+            warn!("Could not open shared case: {}", e);
+            None
+        }
+    };
+    templates::render(&case_page::SharedCasePage { shared: shared.as_ref() })
+}
+
+#[instrument(skip(state))]
+async fn complete_task_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(task_id): Path<Uuid>,
+) -> ServiceResult<Json<Task>> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+
+    let url = format!("{}/api/v1/tasks/{}/complete", state.config.service_url("task-management"), task_id);
+    let task = session_client(&state, &cookies)
+        .put::<serde_json::Value, Task>(&url, &serde_json::json!({}))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(task))
+}
+
+/// Outcome of reviewing a low-confidence task, as named in the URL.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ReviewDecision {
+    Approve,
+    Reject,
+}
+
+#[instrument(skip(state))]
+async fn review_task_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path((task_id, decision)): Path<(Uuid, ReviewDecision)>,
+) -> ServiceResult<Json<Task>> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+
+    let action = match decision {
+        ReviewDecision::Approve => "approve",
+        ReviewDecision::Reject => "reject",
+    };
+    let url = format!(
+        "{}/api/v1/review-queue/{}/{}",
+        state.config.service_url("task-management"),
+        task_id,
+        action
+    );
+    let task = session_client(&state, &cookies)
+        .put::<serde_json::Value, Task>(&url, &serde_json::json!({}))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(task))
+}
+
+#[instrument(skip(state))]
+async fn update_task_status_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(task_id): Path<Uuid>,
+    Json(request): Json<UpdateTaskStatusRequest>,
+) -> ServiceResult<Json<Task>> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+
+    let url = format!("{}/api/v1/tasks/{}/status", state.config.service_url("task-management"), task_id);
+    let task = session_client(&state, &cookies)
+        .patch::<UpdateTaskStatusRequest, Task>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(task))
+}
+
+#[derive(Debug, Deserialize)]
+struct EditTaskRequest {
+    title: Option<String>,
+    priority: Option<Priority>,
+    due_date: Option<chrono::DateTime<chrono::Utc>>,
+    tags: Option<Vec<String>>,
+    estimate_minutes: Option<i32>,
+}
+
+#[instrument(skip(state))]
+async fn edit_task_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(task_id): Path<Uuid>,
+    Json(request): Json<EditTaskRequest>,
+) -> ServiceResult<Json<Task>> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+    if request.title.as_deref().is_some_and(|t| t.trim().is_empty()) {
+        return Err(common::ServiceError::BadRequest("Title cannot be empty".to_string()));
+    }
+
+    let update = UpdateTaskRequest {
+        title: request.title.map(|t| t.trim().to_string()),
+        description: None,
+        status: None,
+        priority: request.priority,
+        due_date: request.due_date,
+        tags: request.tags,
+        metadata: None,
+        estimate_minutes: request.estimate_minutes,
+    };
+    let url = format!("{}/api/v1/tasks/{}", state.config.service_url("task-management"), task_id);
+    let task = session_client(&state, &cookies)
+        .put::<UpdateTaskRequest, Task>(&url, &update)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(task))
+}
+
+#[derive(Debug, Deserialize)]
+struct SnoozeRequest {
+    /// Number of days to push the due date back by.
+    days: Option<i64>,
+    /// Explicit new due date; takes precedence over `days`.
+    until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[instrument(skip(state))]
+async fn snooze_task_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(task_id): Path<Uuid>,
+    Json(request): Json<SnoozeRequest>,
+) -> ServiceResult<Json<Task>> {
+    if get_current_user(&state, &cookies).await.is_none() {
+        return Err(common::ServiceError::Unauthorized("Not logged in".to_string()));
+    }
+
+    let client = session_client(&state, &cookies);
+    let url = format!("{}/api/v1/tasks/{}", state.config.service_url("task-management"), task_id);
+
+    let new_due_date = match request.until {
+        Some(until) => until,
+        None => {
+            let days = request.days.unwrap_or(1);
+            if days <= 0 {
+                return Err(common::ServiceError::BadRequest("Snooze days must be positive".to_string()));
+            }
+            // Overdue or undated tasks are snoozed relative to now, so
+            // snoozing always moves the task into the future.
+            let task = client
+                .get::<Task>(&url)
+                .await
+                .map_err(common::ServiceError::HttpClient)?;
+            let now = chrono::Utc::now();
+            let base = task.due_date.filter(|due| *due > now).unwrap_or(now);
+            base + chrono::Duration::days(days)
+        }
+    };
+
+    let update = UpdateTaskRequest {
+        title: None,
+        description: None,
+        status: None,
+        priority: None,
+        due_date: Some(new_due_date),
+        tags: None,
+        metadata: None,
+        estimate_minutes: None,
+    };
+    let task = client
+        .put::<UpdateTaskRequest, Task>(&url, &update)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(task))
+}
+
+#[instrument(skip(state))]
+async fn get_notifications_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+) -> ServiceResult<Json<NotificationList>> {
+    let url = format!("{}/api/v1/notifications", state.config.service_url("persistence"));
+    let notifications = session_client(&state, &cookies)
+        .get::<NotificationList>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(notifications))
+}
+
+#[instrument(skip(state))]
+async fn mark_notification_read_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<Notification>> {
+    let url = format!("{}/api/v1/notifications/{}/read", state.config.service_url("persistence"), id);
+    let notification = session_client(&state, &cookies)
+        .post::<serde_json::Value, Notification>(&url, &serde_json::json!({}))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(notification))
+}
+
+#[instrument(skip(state))]
+async fn mark_all_notifications_read_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+) -> ServiceResult<Json<serde_json::Value>> {
+    let url = format!("{}/api/v1/notifications/read-all", state.config.service_url("persistence"));
+    let result = session_client(&state, &cookies)
+        .post::<serde_json::Value, serde_json::Value>(&url, &serde_json::json!({}))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(result))
+}
+
+#[instrument(skip(state))]
+async fn get_vapid_key_api(
+    State(state): State<Arc<AppState>>,
+) -> ServiceResult<Json<VapidPublicKey>> {
+    let url = format!("{}/api/v1/push/vapid-key", state.config.service_url("persistence"));
+    let key = state.http_client
+        .get::<VapidPublicKey>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(key))
+}
+
+#[instrument(skip(state, request))]
+async fn subscribe_push_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Json(request): Json<PushSubscriptionRequest>,
+) -> ServiceResult<Json<PushSubscription>> {
+    let url = format!("{}/api/v1/push/subscriptions", state.config.service_url("persistence"));
+    let subscription = session_client(&state, &cookies)
+        .post::<PushSubscriptionRequest, PushSubscription>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(subscription))
+}
+
+#[instrument(skip(state, request))]
+async fn unsubscribe_push_api(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Json(request): Json<UnsubscribePushRequest>,
+) -> ServiceResult<Json<serde_json::Value>> {
+    let url = format!("{}/api/v1/push/unsubscribe", state.config.service_url("persistence"));
+    let result = session_client(&state, &cookies)
+        .post::<UnsubscribePushRequest, serde_json::Value>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    Ok(Json(result))
 }
 
 #[derive(Debug, Deserialize)]
@@ -546,55 +1406,60 @@ struct CallbackQuery {
     error: Option<String>,
 }
 
-#[instrument(skip(state))]
-async fn oauth_login(State(state): State<Arc<AppState>>) -> ServiceResult<Redirect> {
+#[derive(Debug, Deserialize)]
+struct ConnectMailboxQuery {
+    /// A connected mailbox to authorize again.
+    mailbox: Option<String>,
+}
+
+/// Starts connecting an Office 365 mailbox to the signed-in user, or
+/// re-authorizing one they connected.
+#[instrument(skip(state, cookies))]
+async fn oauth_login(
+    State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
+    Query(params): Query<ConnectMailboxQuery>,
+) -> ServiceResult<Redirect> {
     let oauth_manager = state.oauth_manager.as_ref()
         .ok_or_else(|| common::ServiceError::BadRequest("OAuth not configured".to_string()))?;
+    let Some(user) = get_current_user(&state, &cookies).await else {
+        return Ok(Redirect::to("/login"));
+    };
 
+    let oauth_state = state.states.new_state(EMAIL_FLOW);
     let (auth_url, auth_state) = oauth_manager
-        .get_authorization_url()
-        .map_err(|e| common::ServiceError::Internal(e.into()))?;
+        .get_authorization_url(oauth_state.clone(), user.id, params.mailbox.as_deref())
+        .map_err(common::ServiceError::Internal)?;
 
-    // Store the auth state for later retrieval in the callback
-    if let Ok(mut states) = state.oauth_states.lock() {
-        states.insert(auth_state.csrf_token.clone(), auth_state);
-    }
+    // Kept for the callback, wherever it arrives
+    state.states.save(&oauth_state, &auth_state).await?;
     
     info!("OAuth login initiated, redirecting to: {}", auth_url);
     
     Ok(Redirect::to(auth_url.as_str()))
 }
 
-#[instrument(skip(state))]
+/// Connects the authorized mailbox to the user who started connecting it.
+#[instrument(skip(state, cookies, params))]
 async fn oauth_callback(
     State(state): State<Arc<AppState>>,
+    cookies: CookieJar,
     Query(params): Query<CallbackQuery>,
 ) -> ServiceResult<Html<String>> {
     let oauth_manager = state.oauth_manager.as_ref()
         .ok_or_else(|| common::ServiceError::BadRequest("OAuth not configured".to_string()))?;
+    let failure = |heading: &str, message: String| {
+        templates::render(&templates::OAuthResultPage {
+            success: false,
+            heading: heading.to_string(),
+            message,
+            link_href: "/config",
+            link_label: "Back to Settings",
+        })
+    };
 
     if let Some(error) = params.error {
-        return Ok(Html(format!(
-            r#"
-            <!DOCTYPE html>
-            <html>
-            <head>
-                <title>OAuth Error</title>
-                <script src="https://cdn.tailwindcss.com"></script>
-            </head>
-            <body class="bg-gray-50 flex items-center justify-center min-h-screen">
-                <div class="bg-white p-8 rounded-lg shadow-md max-w-md w-full">
-                    <h1 class="text-2xl font-bold text-red-600 mb-4">Authentication Error</h1>
-                    <p class="text-gray-700 mb-4">OAuth authentication failed: {}</p>
-                    <a href="/" class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded">
-                        Back to Dashboard
-                    </a>
-                </div>
-            </body>
-            </html>
-            "#,
-            error
-        )));
+        return failure("Authentication Error", format!("OAuth authentication failed: {}", error));
     }
 
     let code = params.code
@@ -604,67 +1469,82 @@ async fn oauth_callback(
     let state_param = params.state
         .ok_or_else(|| common::ServiceError::BadRequest("Missing state parameter".to_string()))?;
     
-    let auth_state = {
-        let mut states = state.oauth_states.lock().map_err(|_| 
-            common::ServiceError::Internal(anyhow::anyhow!("Failed to lock oauth states")))?;
-        states.remove(&state_param)
-            .ok_or_else(|| common::ServiceError::BadRequest("Invalid or expired state parameter".to_string()))?
-    };
-
-    match oauth_manager.exchange_code_for_token(code, auth_state).await {
-        Ok(token_info) => {
-            // Send token to email service
-            if let Err(e) = oauth_manager.send_token_to_email_service(&token_info).await {
-                error!("Failed to send token to email service: {}", e);
-            }
+    let auth_state: oauth::AuthState = state.states.take(EMAIL_FLOW, &state_param).await?;
 
-            Ok(Html(format!(
-                r#"
-                <!DOCTYPE html>
-                <html>
-                <head>
-                    <title>OAuth Success</title>
-                    <script src="https://cdn.tailwindcss.com"></script>
-                </head>
-                <body class="bg-gray-50 flex items-center justify-center min-h-screen">
-                    <div class="bg-white p-8 rounded-lg shadow-md max-w-md w-full">
-                        <h1 class="text-2xl font-bold text-green-600 mb-4">✅ Authentication Successful!</h1>
-                        <p class="text-gray-700 mb-4">Your Office 365 email account has been connected successfully. The email collector service will now be able to read your emails and create tasks automatically.</p>
-                        <div class="space-y-2">
-                            <a href="/" class="block w-full bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded text-center">
-                                View Dashboard
-                            </a>
-                            <p class="text-sm text-gray-500 text-center">Email polling will start automatically</p>
-                        </div>
-                    </div>
-                </body>
-                </html>
-                "#
-            )))
+    // The mailbox goes to the user who started connecting it, and only
+    // while they are still signed in here.
+    match get_current_user(&state, &cookies).await {
+        Some(user) if user.id == auth_state.user_id => {}
+        _ => {
+            return failure(
+                "Sign-in Required",
+                "Sign in with the account that started connecting the mailbox, then connect it again.".to_string(),
+            )
         }
+    }
+
+    let token_info = match oauth_manager.exchange_code_for_token(code, auth_state).await {
+        Ok(token_info) => token_info,
         Err(e) => {
             error!("Token exchange failed: {}", e);
-            Ok(Html(format!(
-                r#"
-                <!DOCTYPE html>
-                <html>
-                <head>
-                    <title>OAuth Error</title>
-                    <script src="https://cdn.tailwindcss.com"></script>
-                </head>
-                <body class="bg-gray-50 flex items-center justify-center min-h-screen">
-                    <div class="bg-white p-8 rounded-lg shadow-md max-w-md w-full">
-                        <h1 class="text-2xl font-bold text-red-600 mb-4">Token Exchange Failed</h1>
-                        <p class="text-gray-700 mb-4">Failed to exchange authorization code for token: {}</p>
-                        <a href="/oauth/login" class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded">
-                            Try Again
-                        </a>
-                    </div>
-                </body>
-                </html>
-                "#,
-                e
-            )))
+            return failure("Token Exchange Failed", format!("Failed to exchange authorization code for token: {}", e));
+        }
+    };
+    let email_address = match oauth_manager.mailbox_address(&token_info.access_token).await {
+        Ok(email_address) => email_address,
+        Err(e) => {
+            error!("Mailbox lookup failed: {}", e);
+            return failure("Mailbox Not Found", "The account you signed in with has no mailbox to connect.".to_string());
+        }
+    };
+
+    let client = session_client(&state, &cookies);
+    let persistence = state.config.service_url("persistence");
+    let account = AddEmailAccountRequest {
+        email_address: email_address.clone(),
+        provider: EmailProvider::Office365,
+        oauth_token: Some(token_info.access_token),
+        oauth_refresh_token: token_info.refresh_token,
+        oauth_expires_at: token_info
+            .expires_in
+            .map(|secs| chrono::Utc::now() + chrono::Duration::seconds(secs as i64)),
+        imap_settings: None,
+        scopes: token_info.scopes,
+    };
+    let url = format!("{}/api/v1/email-accounts", persistence);
+    match client.post::<AddEmailAccountRequest, EmailAccount>(&url, &account).await {
+        Ok(_) => {}
+        Err(e) if e.status() == Some(reqwest::StatusCode::BAD_REQUEST) => {
+            return failure("Mailbox Not Connected", format!("{} is already connected by another user.", email_address));
+        }
+        Err(e) => {
+            error!("Failed to save mailbox {}: {}", email_address, e);
+            return failure("Mailbox Not Connected", "The mailbox could not be saved. Please try again.".to_string());
         }
     }
+
+    let url = format!("{}/api/v1/notifications", persistence);
+    let notification = CreateNotificationRequest {
+        kind: NotificationKind::EmailConnected,
+        title: "Email connected".to_string(),
+        message: format!("{} is connected. New emails will be turned into tasks.", email_address),
+        link: Some("/config".to_string()),
+        subject_id: None,
+    };
+    if let Err(e) = client
+        .post::<CreateNotificationRequest, Option<Notification>>(&url, &notification)
+        .await {
+        error!("Failed to record email connected notification: {}", e);
+    }
+
+    templates::render(&templates::OAuthResultPage {
+        success: true,
+        heading: "Authentication Successful!".to_string(),
+        message: format!(
+            "{} has been connected successfully. New work emails in it will now be turned into your tasks automatically.",
+            email_address
+        ),
+        link_href: "/",
+        link_label: "View Dashboard",
+    })
 }
@@ -1,12 +1,19 @@
 use anyhow::Result;
+use common::secrets::{self, Secrets};
 use oauth2::{
     basic::BasicClient, AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken,
     PkceCodeChallenge, RedirectUrl, Scope, TokenResponse, TokenUrl,
 };
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::sync::Arc;
 use url::Url;
+use uuid::Uuid;
+
+/// Microsoft Graph permissions a connected mailbox needs. A mailbox
+/// granted fewer, e.g. connected before one was added here, has to be
+/// re-authorized.
+pub const GRAPH_SCOPES: &[&str] = &["Mail.Read", "Mail.Send", "User.Read"];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OAuthConfig {
@@ -22,23 +29,55 @@ pub struct TokenInfo {
     pub refresh_token: Option<String>,
     pub expires_in: Option<u64>,
     pub token_type: String,
+    /// Granted permissions, without the resource prefix, e.g. `Mail.Read`.
+    pub scopes: Vec<String>,
 }
 
+/// An email connection waiting for its callback, kept in the state store.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthState {
-    pub csrf_token: String,
     pub pkce_verifier: String,
+    /// The signed-in user the mailbox is connected to.
+    pub user_id: Uuid,
+}
+
+/// The signed-in user of a Graph token, as far as connecting it needs.
+#[derive(Debug, Deserialize)]
+struct GraphUser {
+    mail: Option<String>,
+    #[serde(rename = "userPrincipalName")]
+    user_principal_name: Option<String>,
 }
 
 #[derive(Clone)]
 pub struct OAuthManager {
-    client: BasicClient,
     config: OAuthConfig,
+    /// Holds the client secret, which may be rotated; `config` has the one
+    /// from startup.
+    secrets: Arc<Secrets>,
     http_client: Client,
 }
 
 impl OAuthManager {
-    pub fn new(config: OAuthConfig) -> Result<Self> {
+    pub fn new(config: OAuthConfig, secrets: Arc<Secrets>) -> Result<Self> {
+        Self::build_client(&config, &config.client_secret)?;
+        Ok(Self {
+            config,
+            secrets,
+            http_client: Client::new(),
+        })
+    }
+
+    /// The OAuth client with the current client secret.
+    fn client(&self) -> Result<BasicClient> {
+        let client_secret = self
+            .secrets
+            .get(secrets::AZURE_CLIENT_SECRET)
+            .unwrap_or_else(|| self.config.client_secret.clone());
+        Self::build_client(&self.config, &client_secret)
+    }
+
+    fn build_client(config: &OAuthConfig, client_secret: &str) -> Result<BasicClient> {
         let auth_url = AuthUrl::new(format!(
             "https://login.microsoftonline.com/{}/oauth2/v2.0/authorize",
             config.tenant_id
@@ -51,34 +90,35 @@ impl OAuthManager {
 
         let client = BasicClient::new(
             ClientId::new(config.client_id.clone()),
-            Some(ClientSecret::new(config.client_secret.clone())),
+            Some(ClientSecret::new(client_secret.to_string())),
             auth_url,
             Some(token_url),
         )
         .set_redirect_uri(RedirectUrl::new(config.redirect_uri.clone())?);
 
-        Ok(Self {
-            client,
-            config,
-            http_client: Client::new(),
-        })
+        Ok(client)
     }
 
-    pub fn get_authorization_url(&self) -> Result<(Url, AuthState)> {
+    /// Where to send the browser to connect a mailbox of `user_id`, which
+    /// comes back with `state`. Re-authorizing `mailbox` signs in as it and
+    /// asks for consent again, so newly required permissions are granted.
+    pub fn get_authorization_url(&self, state: String, user_id: Uuid, mailbox: Option<&str>) -> Result<(Url, AuthState)> {
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
 
-        let (auth_url, csrf_token) = self
-            .client
-            .authorize_url(CsrfToken::new_random)
-            .add_scope(Scope::new("https://graph.microsoft.com/Mail.Read".to_string()))
-            .add_scope(Scope::new("https://graph.microsoft.com/User.Read".to_string()))
+        let client = self.client()?;
+        let mut request = client
+            .authorize_url(move || CsrfToken::new(state))
+            .add_scopes(GRAPH_SCOPES.iter().map(|scope| Scope::new(format!("https://graph.microsoft.com/{}", scope))))
             .add_scope(Scope::new("offline_access".to_string()))
-            .set_pkce_challenge(pkce_challenge)
-            .url();
+            .set_pkce_challenge(pkce_challenge);
+        if let Some(mailbox) = mailbox {
+            request = request.add_extra_param("prompt", "consent").add_extra_param("login_hint", mailbox);
+        }
+        let (auth_url, _) = request.url();
 
         let auth_state = AuthState {
-            csrf_token: csrf_token.secret().clone(),
             pkce_verifier: pkce_verifier.secret().clone(),
+            user_id,
         };
 
         Ok((auth_url, auth_state))
@@ -92,7 +132,7 @@ impl OAuthManager {
         let pkce_verifier = oauth2::PkceCodeVerifier::new(state.pkce_verifier);
 
         let token_result = self
-            .client
+            .client()?
             .exchange_code(AuthorizationCode::new(code))
             .set_pkce_verifier(pkce_verifier)
             .request_async(oauth2::reqwest::async_http_client)
@@ -107,14 +147,16 @@ impl OAuthManager {
                 .expires_in()
                 .map(|d| d.as_secs()),
             token_type: "Bearer".to_string(),
+            scopes: granted_scopes(token_result.scopes()),
         })
     }
 
+    #[allow(dead_code)]
     pub async fn refresh_token(&self, refresh_token: String) -> Result<TokenInfo> {
         let refresh_token = oauth2::RefreshToken::new(refresh_token);
 
         let token_result = self
-            .client
+            .client()?
             .exchange_refresh_token(&refresh_token)
             .request_async(oauth2::reqwest::async_http_client)
             .await?;
@@ -128,9 +170,11 @@ impl OAuthManager {
                 .expires_in()
                 .map(|d| d.as_secs()),
             token_type: "Bearer".to_string(),
+            scopes: granted_scopes(token_result.scopes()),
         })
     }
 
+    #[allow(dead_code)]
     pub async fn validate_token(&self, token: &str) -> Result<bool> {
         let response = self
             .http_client
@@ -142,28 +186,44 @@ impl OAuthManager {
         Ok(response.status().is_success())
     }
 
-    pub async fn send_token_to_email_service(&self, token: &TokenInfo) -> Result<()> {
-        let email_service_url = std::env::var("EMAIL_SERVICE_URL")
-            .unwrap_or_else(|_| "http://localhost:8007".to_string());
-
-        let mut payload = HashMap::new();
-        payload.insert("access_token", &token.access_token);
-        payload.insert("token_type", &token.token_type);
-
+    /// Address of the mailbox a token reads: its primary SMTP address, or
+    /// the user principal name for accounts without one.
+    pub async fn mailbox_address(&self, token: &str) -> Result<String> {
         let response = self
             .http_client
-            .post(&format!("{}/api/v1/oauth/token", email_service_url))
-            .json(&payload)
+            .get("https://graph.microsoft.com/v1.0/me?$select=mail,userPrincipalName")
+            .bearer_auth(token)
             .send()
             .await?;
 
         if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "Failed to send token to email service: {}",
-                response.status()
-            ));
+            return Err(anyhow::anyhow!("Failed to look up the mailbox: {}", response.status()));
         }
+        let user: GraphUser = response.json().await?;
+        user.mail
+            .or(user.user_principal_name)
+            .filter(|address| address.contains('@'))
+            .ok_or_else(|| anyhow::anyhow!("The account has no mailbox address"))
+    }
+}
 
-        Ok(())
+/// The scopes of a token response. A response without them was granted
+/// what was asked for.
+fn granted_scopes(scopes: Option<&Vec<Scope>>) -> Vec<String> {
+    match scopes {
+        Some(scopes) => scopes
+            .iter()
+            .map(|scope| scope.as_str().rsplit('/').next().unwrap_or_default().to_string())
+            .collect(),
+        None => GRAPH_SCOPES.iter().map(|scope| scope.to_string()).collect(),
     }
 }
+
+/// Required permissions `granted` is missing.
+pub fn missing_scopes(granted: &[String]) -> Vec<&'static str> {
+    GRAPH_SCOPES
+        .iter()
+        .copied()
+        .filter(|required| !granted.iter().any(|scope| scope.eq_ignore_ascii_case(required)))
+        .collect()
+}
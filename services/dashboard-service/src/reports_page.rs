@@ -0,0 +1,92 @@
+use askama::Template;
+use models::{ProductivityStats, StatsGrouping, TaskStats, UserProfile};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ReportsQuery {
+    #[serde(default)]
+    pub group_by: StatsGrouping,
+    /// How many days back the report covers.
+    #[serde(default = "default_days")]
+    pub days: i64,
+}
+
+fn default_days() -> i64 {
+    30
+}
+
+/// Range choices offered on the page, in days.
+const RANGES: [i64; 4] = [7, 30, 90, 365];
+
+#[derive(Template)]
+#[template(path = "reports.html")]
+pub struct ReportsPage<'a> {
+    user: &'a UserProfile,
+    stats: &'a TaskStats,
+    productivity: &'a ProductivityStats,
+    /// The stats serialized for the page's charts.
+    stats_json: String,
+    productivity_json: String,
+    days: i64,
+    ranges: &'static [i64],
+    groupings: [(StatsGrouping, &'static str); 3],
+}
+
+impl<'a> ReportsPage<'a> {
+    pub fn new(user: &'a UserProfile, stats: &'a TaskStats, productivity: &'a ProductivityStats, days: i64) -> Self {
+        Self {
+            user,
+            stats,
+            productivity,
+            stats_json: serde_json::to_string(stats).unwrap_or_default(),
+            productivity_json: serde_json::to_string(productivity).unwrap_or_default(),
+            days,
+            ranges: &RANGES,
+            groupings: [
+                (StatsGrouping::Day, "Day"),
+                (StatsGrouping::Week, "Week"),
+                (StatsGrouping::Month, "Month"),
+            ],
+        }
+    }
+
+    fn completion_percent(&self) -> String {
+        percent(self.stats.completion_rate)
+    }
+
+    fn average_time(&self) -> String {
+        duration(self.stats.average_hours_to_complete)
+    }
+
+    fn cycle_time(&self) -> String {
+        duration(self.productivity.mean_cycle_time_hours)
+    }
+
+    fn overdue_percent(&self) -> String {
+        percent(self.productivity.overdue_ratio)
+    }
+
+    fn email_conversion_percent(&self) -> String {
+        percent(self.productivity.email_conversion_rate)
+    }
+
+    fn is_grouping(&self, grouping: &StatsGrouping) -> bool {
+        *grouping == self.stats.group_by
+    }
+
+    fn is_range(&self, days: &i64) -> bool {
+        *days == self.days
+    }
+}
+
+fn percent(ratio: f64) -> String {
+    format!("{:.0}%", ratio * 100.0)
+}
+
+fn duration(hours: Option<f64>) -> String {
+    match hours {
+        None => "—".to_string(),
+        Some(hours) if hours < 48.0 => format!("{:.1} h", hours),
+        Some(hours) => format!("{:.1} days", hours / 24.0),
+    }
+}
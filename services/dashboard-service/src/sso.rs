@@ -0,0 +1,203 @@
+//! Sign-in with OpenID Connect providers.
+//!
+//! Google, Microsoft and one generic issuer can be enabled. The provider is
+//! discovered from its issuer on every sign-in, so rotated signing keys are
+//! picked up, and the code is exchanged with PKCE. The ID token's claims are
+//! verified here, against the nonce of the sign-in, and handed to the
+//! persistence service, which signs in the account linked to them, links
+//! them to the account with a verified email, or creates an account.
+//!
+//! Configured in the `sso` section of the service configuration, or
+//! through the environment:
+//!
+//! - `SSO_GOOGLE_CLIENT_ID` and `SSO_GOOGLE_CLIENT_SECRET`
+//! - `SSO_MICROSOFT_CLIENT_ID`, `SSO_MICROSOFT_CLIENT_SECRET` and
+//!   `SSO_MICROSOFT_TENANT` - a tenant id; `common` can't be used, as its
+//!   ID tokens are issued by each user's own tenant
+//! - `SSO_OIDC_ISSUER`, `SSO_OIDC_CLIENT_ID` and `SSO_OIDC_CLIENT_SECRET`,
+//!   with `SSO_OIDC_NAME` labelling the button (default `Single sign-on`)
+//! - `SSO_REDIRECT_BASE_URL` - the dashboard's public URL (default
+//!   `http://localhost:<port>`); register
+//!   `<base>/auth/sso/<provider>/callback` with each provider
+
+use common::{config::SsoConfig, ServiceError, ServiceResult};
+use models::SsoLoginRequest;
+use openidconnect::{
+    core::{CoreAuthenticationFlow, CoreClient, CoreProviderMetadata},
+    reqwest::async_http_client,
+    AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce, PkceCodeChallenge, PkceCodeVerifier,
+    RedirectUrl, Scope, TokenResponse,
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+#[derive(Debug, Clone)]
+pub struct Provider {
+    /// Used in URLs and recorded with linked identities.
+    pub name: &'static str,
+    pub label: String,
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Sso {
+    providers: Vec<Provider>,
+    redirect_base: String,
+}
+
+/// A sign-in waiting for the provider's callback, kept in the state store.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginState {
+    pub provider: String,
+    nonce: String,
+    pkce_verifier: String,
+    /// Links the account to the signed-in user instead of signing in.
+    pub link: bool,
+}
+
+impl Sso {
+    pub fn new(config: &SsoConfig, port: u16) -> Self {
+        let mut providers = Vec::new();
+
+        if let (Some(client_id), Some(client_secret)) = (config.google_client_id.clone(), config.google_client_secret.clone()) {
+            providers.push(Provider {
+                name: "google",
+                label: "Google".to_string(),
+                issuer: "https://accounts.google.com".to_string(),
+                client_id,
+                client_secret,
+            });
+        }
+        if let (Some(client_id), Some(client_secret), Some(tenant)) = (
+            config.microsoft_client_id.clone(),
+            config.microsoft_client_secret.clone(),
+            config.microsoft_tenant.clone(),
+        ) {
+            providers.push(Provider {
+                name: "microsoft",
+                label: "Microsoft".to_string(),
+                issuer: format!("https://login.microsoftonline.com/{}/v2.0", tenant),
+                client_id,
+                client_secret,
+            });
+        }
+        if let (Some(issuer), Some(client_id), Some(client_secret)) =
+            (config.oidc_issuer.clone(), config.oidc_client_id.clone(), config.oidc_client_secret.clone())
+        {
+            providers.push(Provider {
+                name: "oidc",
+                label: config.oidc_name.clone(),
+                issuer,
+                client_id,
+                client_secret,
+            });
+        }
+
+        Self {
+            providers,
+            redirect_base: config
+                .redirect_base_url
+                .clone()
+                .unwrap_or_else(|| format!("http://localhost:{}", port))
+                .trim_end_matches('/')
+                .to_string(),
+        }
+    }
+
+    /// The enabled providers, for sign-in buttons.
+    pub fn providers(&self) -> &[Provider] {
+        &self.providers
+    }
+
+    fn provider(&self, name: &str) -> ServiceResult<&Provider> {
+        self.providers
+            .iter()
+            .find(|provider| provider.name == name)
+            .ok_or_else(|| ServiceError::NotFound(format!("Sign-in provider {} is not enabled", name)))
+    }
+
+    async fn client(&self, provider: &Provider) -> ServiceResult<CoreClient> {
+        let issuer = IssuerUrl::new(provider.issuer.clone())
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Invalid issuer {}: {}", provider.issuer, e)))?;
+        let metadata = CoreProviderMetadata::discover_async(issuer, async_http_client)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Discovery of {} failed: {}", provider.issuer, e)))?;
+        let redirect_url = RedirectUrl::new(format!("{}/auth/sso/{}/callback", self.redirect_base, provider.name))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Invalid redirect URL: {}", e)))?;
+
+        Ok(CoreClient::from_provider_metadata(
+            metadata,
+            ClientId::new(provider.client_id.clone()),
+            Some(ClientSecret::new(provider.client_secret.clone())),
+        )
+        .set_redirect_uri(redirect_url))
+    }
+
+    /// Where to send the browser to sign in with `provider`, which comes
+    /// back with `state`, and what the callback needs.
+    pub async fn authorization_url(&self, provider: &str, link: bool, state: String) -> ServiceResult<(Url, LoginState)> {
+        let provider = self.provider(provider)?;
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let (url, _, nonce) = self
+            .client(provider)
+            .await?
+            .authorize_url(CoreAuthenticationFlow::AuthorizationCode, move || CsrfToken::new(state), Nonce::new_random)
+            .add_scope(Scope::new("email".to_string()))
+            .add_scope(Scope::new("profile".to_string()))
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+
+        let login = LoginState {
+            provider: provider.name.to_string(),
+            nonce: nonce.secret().clone(),
+            pkce_verifier: pkce_verifier.secret().clone(),
+            link,
+        };
+        Ok((url, login))
+    }
+
+    /// Exchanges the callback's code and returns the verified claims of the
+    /// ID token.
+    pub async fn verify(&self, code: String, state: LoginState) -> ServiceResult<SsoLoginRequest> {
+        let provider = self.provider(&state.provider)?;
+        let client = self.client(provider).await?;
+        let token_response = client
+            .exchange_code(AuthorizationCode::new(code))
+            .set_pkce_verifier(PkceCodeVerifier::new(state.pkce_verifier))
+            .request_async(async_http_client)
+            .await
+            .map_err(|e| ServiceError::Unauthorized(format!("Code exchange with {} failed: {}", provider.label, e)))?;
+        let id_token = token_response
+            .id_token()
+            .ok_or_else(|| ServiceError::Unauthorized(format!("{} returned no ID token", provider.label)))?;
+        let claims = id_token
+            .claims(&client.id_token_verifier(), &Nonce::new(state.nonce))
+            .map_err(|e| ServiceError::Unauthorized(format!("Invalid ID token from {}: {}", provider.label, e)))?;
+
+        // Microsoft leaves out `email` for some accounts; the username is
+        // the address then, but nothing vouches for it.
+        let (email, email_verified) = match claims.email() {
+            Some(email) => (email.as_str().to_string(), claims.email_verified().unwrap_or(false)),
+            None => (
+                claims
+                    .preferred_username()
+                    .map(|username| username.as_str().to_string())
+                    .filter(|username| username.contains('@'))
+                    .ok_or_else(|| ServiceError::Unauthorized(format!("{} did not share an email address", provider.label)))?,
+                false,
+            ),
+        };
+
+        Ok(SsoLoginRequest {
+            provider: provider.name.to_string(),
+            issuer: claims.issuer().as_str().to_string(),
+            subject: claims.subject().as_str().to_string(),
+            email,
+            email_verified,
+            full_name: claims.name().and_then(|name| name.get(None)).map(|name| name.as_str().to_string()),
+            client: Default::default(),
+        })
+    }
+}
@@ -0,0 +1,106 @@
+//! Sign-ins waiting for their OAuth callback.
+//!
+//! What a started sign-in needs back - its PKCE verifier, nonce and what
+//! the callback should do - is kept by the persistence service for
+//! [`STATE_TTL`], so the callback can arrive at any replica of the
+//! dashboard and abandoned sign-ins expire on their own. A state is taken
+//! once.
+//!
+//! The `state` sent to the provider is `<nonce>.<signature>`, the
+//! signature being an HMAC-SHA256 of the flow and the nonce keyed with
+//! `OAUTH_STATE_SECRET`, so forged values, or values of another flow, are
+//! turned away before the store is asked. Without the secret each replica
+//! signs with a random key of its own, and a sign-in has to come back to
+//! the replica that started it.
+
+use common::{
+    config::ServiceConfig,
+    http_client::HttpClient,
+    secrets::{self, Secrets},
+    ServiceError, ServiceResult,
+};
+use hmac::{Hmac, Mac};
+use models::SaveOAuthStateRequest;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::Sha256;
+use std::{sync::Arc, time::Duration};
+use tracing::warn;
+use uuid::Uuid;
+
+/// How long a started sign-in may take to come back.
+pub const STATE_TTL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone)]
+pub struct StateStore {
+    client: HttpClient,
+    persistence_url: String,
+    secrets: Arc<Secrets>,
+    /// Signs states when `OAUTH_STATE_SECRET` isn't set.
+    local_key: String,
+}
+
+impl StateStore {
+    pub fn new(config: &ServiceConfig, client: HttpClient) -> Self {
+        if config.secret_values.get(secrets::OAUTH_STATE_SECRET).is_none() {
+            warn!("OAUTH_STATE_SECRET is not set; sign-ins must return to the replica that started them");
+        }
+        Self {
+            client,
+            persistence_url: config.service_url("persistence"),
+            secrets: config.secret_values.clone(),
+            local_key: format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+        }
+    }
+
+    /// A new signed state for a sign-in of `flow`.
+    pub fn new_state(&self, flow: &str) -> String {
+        let nonce = Uuid::new_v4().simple().to_string();
+        let signature: String = self.mac(flow, &nonce).finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("{}.{}", nonce, signature)
+    }
+
+    /// Keeps `value` for the callback of the sign-in started with `state`.
+    pub async fn save<T: Serialize>(&self, state: &str, value: &T) -> ServiceResult<()> {
+        let request = SaveOAuthStateRequest {
+            state: state.to_string(),
+            payload: serde_json::to_value(value).map_err(ServiceError::Serialization)?,
+            expires_in_secs: STATE_TTL.as_secs() as i64,
+        };
+        let url = format!("{}/api/v1/oauth-states", self.persistence_url);
+        // Saving answers 204 without a body.
+        self.client.post_stream(&url, &request).await.map_err(ServiceError::HttpClient)?;
+        Ok(())
+    }
+
+    /// Takes what was saved for `state`, if it is a live state of `flow`.
+    pub async fn take<T: DeserializeOwned>(&self, flow: &str, state: &str) -> ServiceResult<T> {
+        let invalid = || ServiceError::BadRequest("Invalid or expired state parameter".to_string());
+        let (nonce, signature) = state.split_once('.').ok_or_else(invalid)?;
+        let signature = from_hex(signature).ok_or_else(invalid)?;
+        self.mac(flow, nonce).verify_slice(&signature).map_err(|_| invalid())?;
+
+        let url = format!("{}/api/v1/oauth-states/{}/take", self.persistence_url, state);
+        let payload = match self.client.post::<_, serde_json::Value>(&url, &serde_json::json!({})).await {
+            Ok(payload) => payload,
+            Err(e) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => return Err(invalid()),
+            Err(e) => return Err(ServiceError::HttpClient(e)),
+        };
+        serde_json::from_value(payload).map_err(ServiceError::Serialization)
+    }
+
+    fn mac(&self, flow: &str, nonce: &str) -> Hmac<Sha256> {
+        let key = self.secrets.get(secrets::OAUTH_STATE_SECRET).unwrap_or_else(|| self.local_key.clone());
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length");
+        mac.update(flow.as_bytes());
+        mac.update(b".");
+        mac.update(nonce.as_bytes());
+        mac
+    }
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
@@ -0,0 +1,294 @@
+use askama::Template;
+use axum::response::Html;
+use common::{ServiceError, ServiceResult};
+use models::{
+    ApiScope, ApiToken, Case, DefaultView, EmailAccount, LoginEvent, NotificationDelivery, NotificationKind, SavedFilter, SsoIdentity, Task, Theme, UserProfile,
+    WebhookIntegration, WorkloadDay,
+};
+
+use crate::sso::Provider;
+
+/// Renders a template into an HTML response, surfacing template errors as
+/// internal errors.
+pub fn render<T: Template>(template: &T) -> ServiceResult<Html<String>> {
+    template
+        .render()
+        .map(Html)
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Template error: {}", e)))
+}
+
+#[derive(Template)]
+#[template(path = "login.html")]
+pub struct LoginPage<'a> {
+    pub providers: &'a [Provider],
+}
+
+#[derive(Template)]
+#[template(path = "register.html")]
+pub struct RegisterPage;
+
+#[derive(Template)]
+#[template(path = "config.html")]
+pub struct ConfigPage<'a> {
+    pub user: &'a UserProfile,
+    pub filters: &'a [SavedFilter],
+    pub integrations: &'a [WebhookIntegration],
+    pub identities: &'a [SsoIdentity],
+    pub tokens: &'a [ApiToken],
+    /// Providers an account can be linked with.
+    pub providers: &'a [Provider],
+    pub email_accounts: &'a [EmailAccount],
+    /// Whether mailboxes can be connected.
+    pub email_enabled: bool,
+}
+
+impl ConfigPage<'_> {
+    fn theme_selected(&self, theme: Theme) -> bool {
+        self.user.preferences.theme == theme
+    }
+
+    fn view_selected(&self, view: DefaultView) -> bool {
+        self.user.preferences.default_view == view
+    }
+
+    /// Kinds that can be muted, with their labels.
+    fn notification_kinds(&self) -> [(NotificationKind, &'static str); 9] {
+        [
+            (NotificationKind::TaskAssigned, "New tasks"),
+            (NotificationKind::TaskOverdue, "Overdue reminders"),
+            (NotificationKind::CaseResolved, "Resolved cases"),
+            (NotificationKind::EmailConnected, "Email connected"),
+            (NotificationKind::SlaWarning, "SLA warnings"),
+            (NotificationKind::SlaBreached, "SLA breaches"),
+            (NotificationKind::CaseTransferred, "Cases handed to me"),
+            (NotificationKind::Mentioned, "Mentions"),
+            (NotificationKind::LlmBudgetExhausted, "LLM budget alerts"),
+        ]
+    }
+
+    fn api_scopes(&self) -> [&'static str; 4] {
+        ApiScope::ALL.map(ApiScope::as_str)
+    }
+
+    /// State of a connected mailbox, e.g. `Sign-in expired`.
+    fn mailbox_status(&self, account: &EmailAccount) -> String {
+        let missing = crate::oauth::missing_scopes(&account.scopes);
+        if !account.is_active {
+            "Disconnected".to_string()
+        } else if !missing.is_empty() {
+            format!("Needs {} permission", missing.join(", "))
+        } else if account.oauth_expires_at.is_some_and(|expires| expires <= chrono::Utc::now()) {
+            "Sign-in expired".to_string()
+        } else {
+            "Connected".to_string()
+        }
+    }
+
+    /// When Microsoft Graph lets a throttled mailbox be polled again, e.g.
+    /// `Throttled by Microsoft until 14:05 UTC`.
+    fn mailbox_throttling(&self, account: &EmailAccount) -> Option<String> {
+        account
+            .health
+            .throttled_until
+            .filter(|until| account.is_active && *until > chrono::Utc::now())
+            .map(|until| format!("Throttled by Microsoft until {}", until.format("%H:%M UTC")))
+    }
+
+    /// Whether a connected mailbox has to be authorized again to be polled.
+    fn needs_reauthorization(&self, account: &EmailAccount) -> bool {
+        account.is_active && self.mailbox_status(account) != "Connected"
+    }
+
+    /// Scopes and state of a token, e.g. `tasks:read, revoked`.
+    fn token_summary(&self, token: &ApiToken) -> String {
+        let mut parts: Vec<String> = token.scopes.iter().map(|scope| scope.as_str().to_string()).collect();
+        match (token.revoked_at, token.expires_at) {
+            (Some(_), _) => parts.push("revoked".to_string()),
+            (None, Some(expires)) if expires <= chrono::Utc::now() => parts.push("expired".to_string()),
+            (None, Some(expires)) => parts.push(format!("expires {}", expires.format("%Y-%m-%d"))),
+            (None, None) => {}
+        }
+        if let Some(used) = token.last_used_at {
+            parts.push(format!("last used {}", used.format("%Y-%m-%d")));
+        }
+        parts.join(", ")
+    }
+
+    fn kind_muted(&self, kind: &NotificationKind) -> bool {
+        self.user.preferences.notifications.muted.contains(kind)
+    }
+
+    fn digest_selected(&self) -> bool {
+        self.user.preferences.notifications.delivery == NotificationDelivery::Digest
+    }
+
+    /// `HH:MM` for a time input.
+    fn quiet_hours_bound(&self, start: bool) -> String {
+        let quiet = self.user.preferences.notifications.quiet_hours;
+        quiet.map(|quiet| if start { quiet.start } else { quiet.end }).map_or_else(String::new, |time| time.format("%H:%M").to_string())
+    }
+}
+
+/// The user's sign-in history.
+#[derive(Template)]
+#[template(path = "security.html")]
+pub struct SecurityPage<'a> {
+    pub user: &'a UserProfile,
+    pub events: &'a [LoginEvent],
+}
+
+impl SecurityPage<'_> {
+    fn failed_count(&self) -> usize {
+        self.events.iter().filter(|event| !event.succeeded).count()
+    }
+}
+
+/// Pending tasks, or the tasks of a saved filter, one page at a time as
+/// sized by the user's preferences.
+#[derive(Template)]
+#[template(path = "dashboard.html")]
+pub struct DashboardPage<'a> {
+    user: &'a UserProfile,
+    tasks: &'a [Task],
+    total: usize,
+    page: usize,
+    page_count: usize,
+    /// Tag the list is filtered by, if any.
+    tag: Option<&'a str>,
+    filters: &'a [SavedFilter],
+    active_filter: Option<&'a SavedFilter>,
+    /// Upcoming days whose estimates exceed the user's daily capacity.
+    overcommitted: Vec<&'a WorkloadDay>,
+    /// Low-confidence tasks from the AI agent awaiting approval.
+    review_queue: &'a [Task],
+    /// Open cases to look at first, most urgent first.
+    priority_inbox: &'a [Case],
+}
+
+impl<'a> DashboardPage<'a> {
+    pub fn new(
+        user: &'a UserProfile,
+        tasks: &'a [Task],
+        page: usize,
+        tag: Option<&'a str>,
+        filters: &'a [SavedFilter],
+        active_filter: Option<&'a SavedFilter>,
+        workload: &'a [WorkloadDay],
+    ) -> Self {
+        let per_page = user.preferences.tasks_per_page.max(1) as usize;
+        let page_count = tasks.len().div_ceil(per_page).max(1);
+        let page = page.clamp(1, page_count);
+        let start = (page - 1) * per_page;
+        let end = (start + per_page).min(tasks.len());
+
+        Self {
+            user,
+            tasks: &tasks[start..end],
+            total: tasks.len(),
+            page,
+            page_count,
+            tag,
+            filters,
+            active_filter,
+            overcommitted: workload
+                .iter()
+                .filter(|day| day.estimate_minutes > i64::from(user.preferences.daily_capacity_minutes))
+                .collect(),
+            review_queue: &[],
+            priority_inbox: &[],
+        }
+    }
+
+    pub fn with_review_queue(mut self, review_queue: &'a [Task]) -> Self {
+        self.review_queue = review_queue;
+        self
+    }
+
+    pub fn with_priority_inbox(mut self, priority_inbox: &'a [Case]) -> Self {
+        self.priority_inbox = priority_inbox;
+        self
+    }
+
+    fn initial(&self) -> String {
+        self.user.full_name.chars().next().unwrap_or('U').to_uppercase().collect()
+    }
+
+    fn is_active_filter(&self, filter: &SavedFilter) -> bool {
+        self.active_filter.is_some_and(|active| active.id == filter.id)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "oauth_result.html")]
+pub struct OAuthResultPage {
+    pub success: bool,
+    pub heading: String,
+    pub message: String,
+    pub link_href: &'static str,
+    pub link_label: &'static str,
+}
+
+/// Filters available to every template. Page modules bring this into scope
+/// with `use crate::templates::filters;`.
+pub mod filters {
+    use chrono::{DateTime, Utc};
+    use models::{MessageSender, Priority, StepStatus, TimelineEntry, TimelineSource};
+    use std::fmt::Debug;
+
+    /// Formats enum values the way the APIs name them, e.g. `InProgress`.
+    pub fn debug<T: Debug>(value: T) -> askama::Result<String> {
+        Ok(format!("{:?}", value))
+    }
+
+    /// Formats a number of minutes as e.g. `1h 30m`.
+    pub fn minutes<T: Copy + Into<i64>>(minutes: &T) -> askama::Result<String> {
+        let minutes: i64 = (*minutes).into();
+        Ok(match (minutes / 60, minutes % 60) {
+            (0, m) => format!("{}m", m),
+            (h, 0) => format!("{}h", h),
+            (h, m) => format!("{}h {}m", h, m),
+        })
+    }
+
+    /// Formats an optional date for an `<input type="date">` value.
+    pub fn date_input(date: &Option<DateTime<Utc>>) -> askama::Result<String> {
+        Ok(date.map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or_default())
+    }
+
+    pub fn priority_badge(priority: &Priority) -> askama::Result<&'static str> {
+        Ok(match priority {
+            Priority::Critical => "bg-red-100 text-red-800",
+            Priority::High => "bg-orange-100 text-orange-800",
+            Priority::Medium => "bg-yellow-100 text-yellow-800",
+            Priority::Low => "bg-gray-100 text-gray-700",
+        })
+    }
+
+    pub fn step_badge(status: &StepStatus) -> askama::Result<&'static str> {
+        Ok(match status {
+            StepStatus::Completed => "bg-green-100 text-green-800",
+            StepStatus::Active => "bg-blue-100 text-blue-800",
+            StepStatus::Pending => "bg-gray-100 text-gray-700",
+            StepStatus::Skipped => "bg-yellow-100 text-yellow-800",
+        })
+    }
+
+    pub fn sender_colour(sender: &MessageSender) -> askama::Result<&'static str> {
+        Ok(match sender {
+            MessageSender::User => "bg-blue-600",
+            MessageSender::Agent => "bg-green-600",
+            MessageSender::System => "bg-gray-400",
+        })
+    }
+
+    /// Messages take their sender's colour, other activity its source's.
+    pub fn timeline_colour(entry: &TimelineEntry) -> askama::Result<&'static str> {
+        match (&entry.source, &entry.sender) {
+            (TimelineSource::Conversation, Some(sender)) => sender_colour(sender),
+            (TimelineSource::Conversation, None) => Ok("bg-gray-400"),
+            (TimelineSource::Task, _) => Ok("bg-amber-500"),
+            (TimelineSource::Workflow, _) => Ok("bg-purple-500"),
+            (TimelineSource::Audit, _) => Ok("bg-gray-700"),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+// Login and registration forms. Both post JSON to the dashboard auth API and
+// show the server's error message inline on failure.
+function bindAuthForm(formId, url, toPayload, redirectTo, failureMessage) {
+    const form = document.getElementById(formId);
+    if (!form) return;
+
+    const errorMessage = document.getElementById('error-message');
+    const showError = message => {
+        errorMessage.textContent = message;
+        errorMessage.classList.remove('hidden');
+    };
+
+    form.addEventListener('submit', async (e) => {
+        e.preventDefault();
+        const formData = new FormData(e.target);
+
+        try {
+            const response = await fetch(url, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(toPayload(formData))
+            });
+
+            if (response.ok) {
+                window.location.href = redirectTo;
+            } else {
+                const error = await response.text();
+                showError(error || failureMessage);
+            }
+        } catch (error) {
+            showError('Network error');
+        }
+    });
+}
+
+bindAuthForm('loginForm', '/api/auth/login', formData => ({
+    email: formData.get('email'),
+    password: formData.get('password')
+}), '/', 'Login failed');
+
+bindAuthForm('registerForm', '/api/auth/register', formData => ({
+    full_name: formData.get('full_name'),
+    email: formData.get('email'),
+    organization: formData.get('organization') || null,
+    password: formData.get('password')
+}), '/login?registered=true', 'Registration failed');
@@ -0,0 +1,44 @@
+let dragged = null;
+
+document.querySelectorAll('[data-task-id]').forEach(card => {
+    card.addEventListener('dragstart', () => {
+        dragged = card;
+        card.classList.add('opacity-50');
+    });
+    card.addEventListener('dragend', () => card.classList.remove('opacity-50'));
+});
+
+document.querySelectorAll('[data-status]').forEach(column => {
+    column.addEventListener('dragover', e => {
+        e.preventDefault();
+        column.classList.add('ring-2', 'ring-blue-400');
+    });
+    column.addEventListener('dragleave', () => column.classList.remove('ring-2', 'ring-blue-400'));
+    column.addEventListener('drop', async e => {
+        e.preventDefault();
+        column.classList.remove('ring-2', 'ring-blue-400');
+        if (!dragged || dragged.parentElement === column.querySelector('ul')) return;
+
+        const card = dragged;
+        const origin = card.parentElement;
+        column.querySelector('ul').appendChild(card);
+        updateCounts();
+
+        const response = await fetch(`/ui/api/tasks/${card.dataset.taskId}/status`, {
+            method: 'PATCH',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ status: column.dataset.status })
+        });
+        if (!response.ok) {
+            origin.appendChild(card);
+            updateCounts();
+            alert('Could not move task');
+        }
+    });
+});
+
+function updateCounts() {
+    document.querySelectorAll('[data-status]').forEach(column => {
+        column.querySelector('[data-count]').textContent = column.querySelectorAll('[data-task-id]').length;
+    });
+}
@@ -0,0 +1,50 @@
+// Handing a case to a colleague: a read-only share link, or the case
+// itself.
+async function caseRequest(url, body) {
+    const response = await fetch(url, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(body)
+    });
+    if (!response.ok) throw new Error(`request failed (${response.status})`);
+    return response.json();
+}
+
+async function shareCase(id) {
+    const days = prompt('Share a read-only link for how many days?', '7');
+    if (days === null) return;
+    try {
+        const link = await caseRequest(`/ui/api/cases/${id}/share`, { expires_in_hours: Number(days) * 24 });
+        const url = `${location.origin}/shared/${link.token}`;
+        try {
+            await navigator.clipboard.writeText(url);
+            alert(`Link copied; it works until ${new Date(link.expires_at).toLocaleString()}.`);
+        } catch (_) {
+            prompt('Copy the share link:', url);
+        }
+    } catch (error) {
+        alert(`Could not share the case: ${error.message}`);
+    }
+}
+
+async function exportToNotion(id) {
+    try {
+        const page = await caseRequest(`/ui/api/cases/${id}/export/notion`, {});
+        if (confirm('Case exported to Notion. Open the page?')) {
+            window.open(page.url, '_blank', 'noopener');
+        }
+    } catch (error) {
+        alert(`Could not export the case: ${error.message}`);
+    }
+}
+
+async function transferCase(id) {
+    const email = prompt('Email of the colleague taking over this case and its tasks:');
+    if (!email) return;
+    try {
+        await caseRequest(`/ui/api/cases/${id}/transfer`, { email });
+        location.href = '/dashboard';
+    } catch (error) {
+        alert(`Could not hand over the case: ${error.message}`);
+    }
+}
@@ -0,0 +1,120 @@
+const messages = document.getElementById('messages');
+const input = document.getElementById('messageInput');
+const sendButton = document.getElementById('sendButton');
+let caseId = messages.dataset.caseId || null;
+
+function addBubble(text, fromUser) {
+    const bubble = document.createElement('div');
+    bubble.className = fromUser
+        ? 'ml-auto max-w-[80%] bg-blue-600 text-white rounded-xl px-4 py-2 whitespace-pre-line'
+        : 'mr-auto max-w-[80%] bg-white border border-gray-200 rounded-xl px-4 py-2 whitespace-pre-line';
+    bubble.textContent = text;
+    messages.appendChild(bubble);
+    messages.scrollTop = messages.scrollHeight;
+    return bubble;
+}
+
+function addTasks(tasks) {
+    if (!tasks.length) return;
+    const list = document.createElement('ul');
+    list.className = 'mr-auto max-w-[80%] bg-green-50 border border-green-200 rounded-xl px-4 py-2 text-sm space-y-1';
+    tasks.forEach(task => {
+        const item = document.createElement('li');
+        const link = document.createElement('a');
+        link.href = `/cases/${task.case_id}`;
+        link.className = 'text-green-800 hover:underline';
+        link.textContent = `✓ ${task.title} (${task.priority})`;
+        item.appendChild(link);
+        list.appendChild(item);
+    });
+    messages.appendChild(list);
+    messages.scrollTop = messages.scrollHeight;
+}
+
+// Applies the agent's server-sent events to the pending reply bubble.
+async function readEvents(response, pending) {
+    const reader = response.body.pipeThrough(new TextDecoderStream()).getReader();
+    let buffer = '';
+    let text = '';
+    const tasks = [];
+
+    while (true) {
+        const { value, done } = await reader.read();
+        if (done) break;
+        buffer += value;
+
+        let boundary;
+        while ((boundary = buffer.indexOf('\n\n')) !== -1) {
+            const block = buffer.slice(0, boundary);
+            buffer = buffer.slice(boundary + 2);
+
+            const data = block.split('\n')
+                .filter(line => line.startsWith('data:'))
+                .map(line => line.slice(5).trim())
+                .join('\n');
+            if (!data) continue;
+
+            const event = JSON.parse(data);
+            switch (event.type) {
+                case 'case':
+                    caseId = event.case_id;
+                    break;
+                case 'token':
+                    text += event.text;
+                    pending.textContent = text;
+                    messages.scrollTop = messages.scrollHeight;
+                    break;
+                case 'task':
+                    tasks.push(event.task);
+                    break;
+                case 'done':
+                    pending.textContent = event.response.response;
+                    addTasks(tasks);
+                    return;
+                case 'error':
+                    throw new Error(event.message);
+            }
+        }
+    }
+    throw new Error('Stream ended unexpectedly');
+}
+
+function newConversation() {
+    caseId = null;
+    messages.innerHTML = '';
+    input.focus();
+}
+
+document.getElementById('chatForm').addEventListener('submit', async e => {
+    e.preventDefault();
+    const message = input.value.trim();
+    if (!message) return;
+
+    addBubble(message, true);
+    input.value = '';
+    sendButton.disabled = true;
+    const pending = addBubble('…', false);
+
+    try {
+        const response = await fetch('/ui/api/chat/stream', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ message, case_id: caseId })
+        });
+        if (!response.ok) throw new Error(await response.text());
+        await readEvents(response, pending);
+    } catch (error) {
+        pending.textContent = 'Sorry, something went wrong. Please try again.';
+        pending.classList.add('text-red-600');
+    } finally {
+        sendButton.disabled = false;
+        input.focus();
+    }
+});
+
+input.addEventListener('keydown', e => {
+    if (e.key === 'Enter' && !e.shiftKey) {
+        e.preventDefault();
+        document.getElementById('chatForm').requestSubmit();
+    }
+});
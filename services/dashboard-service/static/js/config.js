@@ -0,0 +1,201 @@
+const preferencesForm = document.getElementById('preferencesForm');
+
+preferencesForm.addEventListener('submit', async e => {
+    e.preventDefault();
+    const fields = preferencesForm.elements;
+    const response = await fetch('/ui/api/preferences', {
+        method: 'PUT',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({
+            theme: fields.theme.value,
+            default_view: fields.default_view.value,
+            tasks_per_page: Number(fields.tasks_per_page.value),
+            daily_capacity_minutes: Number(fields.daily_capacity_minutes.value)
+        })
+    });
+    if (response.ok) {
+        location.reload();
+    } else {
+        alert('Could not save preferences');
+    }
+});
+
+const notificationsForm = document.getElementById('notificationsForm');
+
+notificationsForm.addEventListener('submit', async e => {
+    e.preventDefault();
+    const fields = notificationsForm.elements;
+    const seconds = time => time && `${time}:00`;
+    const kinds = Array.from(notificationsForm.querySelectorAll('input[name=kind]'));
+    const quiet = fields.quiet_start.value && fields.quiet_end.value
+        ? { start: seconds(fields.quiet_start.value), end: seconds(fields.quiet_end.value) }
+        : null;
+    const response = await fetch('/ui/api/preferences', {
+        method: 'PUT',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({
+            notifications: {
+                push: fields.push.checked,
+                muted: kinds.filter(kind => !kind.checked).map(kind => kind.value),
+                delivery: fields.delivery.value,
+                digest_time: seconds(fields.digest_time.value) || '08:00:00',
+                quiet_hours: quiet,
+                utc_offset_minutes: -new Date().getTimezoneOffset(),
+                weekly_summary_email: fields.weekly_summary_email.checked
+            }
+        })
+    });
+    if (response.ok) {
+        location.reload();
+    } else {
+        alert('Could not save notification settings');
+    }
+});
+
+const savedFilterForm = document.getElementById('savedFilterForm');
+const selected = select => Array.from(select.selectedOptions, option => option.value);
+
+savedFilterForm.addEventListener('submit', async e => {
+    e.preventDefault();
+    const fields = savedFilterForm.elements;
+    const response = await fetch('/ui/api/saved-filters', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({
+            name: fields.name.value,
+            filter: {
+                status: selected(fields.status),
+                priority: selected(fields.priority),
+                tags: fields.tags.value.split(',').map(tag => tag.trim()).filter(Boolean),
+                due: fields.due.value || null
+            }
+        })
+    });
+    if (response.ok) {
+        location.reload();
+    } else {
+        alert('Could not save filter');
+    }
+});
+
+document.querySelectorAll('.deleteFilter').forEach(button => {
+    button.addEventListener('click', async () => {
+        const response = await fetch(`/ui/api/saved-filters/${button.dataset.filterId}`, { method: 'DELETE' });
+        if (response.ok) {
+            location.reload();
+        } else {
+            alert('Could not delete filter');
+        }
+    });
+});
+
+const webhookIntegrationForm = document.getElementById('webhookIntegrationForm');
+
+webhookIntegrationForm.addEventListener('submit', async e => {
+    e.preventDefault();
+    const fields = webhookIntegrationForm.elements;
+    const response = await fetch('/ui/api/webhook-integrations', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({
+            name: fields.name.value,
+            mapping: {
+                sender: fields.sender.value,
+                subject: fields.subject.value || null,
+                body: fields.body.value
+            }
+        })
+    });
+    if (response.ok) {
+        const integration = await response.json();
+        alert(`Post events to the channel service at /api/v1/webhooks/${integration.token}\n\nThis address will not be shown again.`);
+        location.reload();
+    } else {
+        alert('Could not create integration');
+    }
+});
+
+document.querySelectorAll('.deleteIntegration').forEach(button => {
+    button.addEventListener('click', async () => {
+        const response = await fetch(`/ui/api/webhook-integrations/${button.dataset.integrationId}`, { method: 'DELETE' });
+        if (response.ok) {
+            location.reload();
+        } else {
+            alert('Could not delete integration');
+        }
+    });
+});
+
+document.querySelectorAll('.unlinkIdentity').forEach(button => {
+    button.addEventListener('click', async () => {
+        const response = await fetch(`/ui/api/sso-identities/${button.dataset.identityId}`, { method: 'DELETE' });
+        if (response.ok) {
+            location.reload();
+        } else {
+            alert('Could not unlink account. An account without a password keeps one sign-in provider.');
+        }
+    });
+});
+
+document.querySelectorAll('.disconnectMailbox').forEach(button => {
+    button.addEventListener('click', async () => {
+        const response = await fetch(`/ui/api/email-accounts/${button.dataset.accountId}/disconnect`, { method: 'POST' });
+        if (response.ok) {
+            location.reload();
+        } else {
+            alert('Could not disconnect the mailbox.');
+        }
+    });
+});
+
+document.querySelectorAll('.removeMailbox').forEach(button => {
+    button.addEventListener('click', async () => {
+        if (!confirm('Remove this mailbox? Its mail will be fetched again from scratch if you connect it later.')) {
+            return;
+        }
+        const response = await fetch(`/ui/api/email-accounts/${button.dataset.accountId}`, { method: 'DELETE' });
+        if (response.ok) {
+            location.reload();
+        } else {
+            alert('Could not remove the mailbox.');
+        }
+    });
+});
+
+const apiTokenForm = document.getElementById('apiTokenForm');
+
+apiTokenForm.addEventListener('submit', async e => {
+    e.preventDefault();
+    const fields = apiTokenForm.elements;
+    const scopes = [...apiTokenForm.querySelectorAll('input[name="scope"]:checked')].map(input => input.value);
+    const response = await fetch('/ui/api/api-tokens', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({
+            name: fields.name.value,
+            scopes,
+            expires_in_days: fields.expires_in_days.value ? Number(fields.expires_in_days.value) : null
+        })
+    });
+    if (response.ok) {
+        const token = await response.json();
+        alert(`Your new token:\n\n${token.token}\n\nCopy it now; it will not be shown again.`);
+        location.reload();
+    } else {
+        alert('Could not create token. Pick at least one scope.');
+    }
+});
+
+document.querySelectorAll('.revokeToken').forEach(button => {
+    button.addEventListener('click', async () => {
+        if (!confirm('Revoke this token? Scripts using it will stop working.')) {
+            return;
+        }
+        const response = await fetch(`/ui/api/api-tokens/${button.dataset.tokenId}`, { method: 'DELETE' });
+        if (response.ok) {
+            location.reload();
+        } else {
+            alert('Could not revoke token');
+        }
+    });
+});
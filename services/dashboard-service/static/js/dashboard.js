@@ -0,0 +1,38 @@
+const editDialog = document.getElementById('editDialog');
+const editFields = document.getElementById('editForm').elements;
+
+function openEdit(button) {
+    editFields.task_id.value = button.dataset.id;
+    editFields.title.value = button.dataset.title;
+    editFields.priority.value = button.dataset.priority;
+    editFields.due_date.value = button.dataset.due;
+    editFields.tags.value = button.dataset.tags;
+    editFields.estimate_minutes.value = button.dataset.estimate;
+    editDialog.showModal();
+}
+
+editDialog.addEventListener('close', () => {
+    if (editDialog.returnValue !== 'save') return;
+    const body = {
+        title: editFields.title.value,
+        priority: editFields.priority.value,
+        tags: editFields.tags.value.split(',').map(tag => tag.trim()).filter(Boolean)
+    };
+    if (editFields.estimate_minutes.value) {
+        body.estimate_minutes = Number(editFields.estimate_minutes.value);
+    }
+    if (editFields.due_date.value) {
+        body.due_date = new Date(editFields.due_date.value + 'T00:00:00Z').toISOString();
+    }
+    taskRequest(`/ui/api/tasks/${editFields.task_id.value}`, body);
+});
+
+async function logout() {
+    try {
+        await fetch('/api/auth/logout', { method: 'POST' });
+        window.location.href = '/login';
+    } catch (error) {
+        console.error('Logout failed:', error);
+        window.location.href = '/login';
+    }
+}
@@ -0,0 +1,133 @@
+// Bell menu in the page header. Polls for notifications and marks them read
+// when opened.
+(() => {
+    const POLL_INTERVAL_MS = 30000;
+
+    const button = document.getElementById('notificationsButton');
+    const badge = document.getElementById('notificationsBadge');
+    const panel = document.getElementById('notificationsPanel');
+    const list = document.getElementById('notificationsList');
+    if (!button) return;
+
+    function render({ unread_count, notifications }) {
+        badge.textContent = unread_count > 9 ? '9+' : unread_count;
+        badge.classList.toggle('hidden', unread_count === 0);
+
+        list.innerHTML = '';
+        if (!notifications.length) {
+            const empty = document.createElement('li');
+            empty.className = 'px-4 py-6 text-center text-sm text-gray-500';
+            empty.textContent = 'No notifications yet.';
+            list.appendChild(empty);
+            return;
+        }
+
+        notifications.forEach(notification => {
+            const item = document.createElement('li');
+            item.className = 'px-4 py-3 cursor-pointer hover:bg-gray-50' + (notification.read_at ? '' : ' bg-blue-50');
+
+            const title = document.createElement('p');
+            title.className = 'text-sm font-medium text-gray-900';
+            title.textContent = notification.title;
+            const message = document.createElement('p');
+            message.className = 'text-sm text-gray-600';
+            message.textContent = notification.message;
+            const time = document.createElement('p');
+            time.className = 'text-xs text-gray-400 mt-1';
+            time.textContent = new Date(notification.created_at).toLocaleString();
+
+            item.append(title, message, time);
+            item.addEventListener('click', () => open(notification));
+            list.appendChild(item);
+        });
+    }
+
+    async function refresh() {
+        try {
+            const response = await fetch('/ui/api/notifications');
+            if (response.ok) render(await response.json());
+        } catch (error) {
+            console.error('Failed to load notifications:', error);
+        }
+    }
+
+    async function open(notification) {
+        if (!notification.read_at) {
+            await fetch(`/ui/api/notifications/${notification.id}/read`, { method: 'POST' });
+        }
+        if (notification.link) {
+            window.location.href = notification.link;
+        } else {
+            refresh();
+        }
+    }
+
+    button.addEventListener('click', () => panel.classList.toggle('hidden'));
+    document.addEventListener('click', e => {
+        if (!document.getElementById('notifications').contains(e.target)) panel.classList.add('hidden');
+    });
+    document.getElementById('notificationsReadAll').addEventListener('click', async () => {
+        await fetch('/ui/api/notifications/read-all', { method: 'POST' });
+        refresh();
+    });
+
+    // Browser push: offered once the browser supports it and the user has
+    // not blocked notifications for the site.
+    const pushToggle = document.getElementById('pushToggle');
+    const pushButton = document.getElementById('pushToggleButton');
+
+    function urlBase64ToUint8Array(value) {
+        const padded = (value + '='.repeat((4 - value.length % 4) % 4)).replace(/-/g, '+').replace(/_/g, '/');
+        return Uint8Array.from(atob(padded), c => c.charCodeAt(0));
+    }
+
+    async function currentSubscription() {
+        const registration = await navigator.serviceWorker.register('/sw.js');
+        return { registration, subscription: await registration.pushManager.getSubscription() };
+    }
+
+    async function renderPushToggle() {
+        if (Notification.permission === 'denied') {
+            pushToggle.classList.add('hidden');
+            return;
+        }
+        const { subscription } = await currentSubscription();
+        pushButton.textContent = subscription ? 'Turn off browser notifications' : 'Turn on browser notifications';
+        pushToggle.classList.remove('hidden');
+    }
+
+    async function togglePush() {
+        const { registration, subscription } = await currentSubscription();
+        if (subscription) {
+            await fetch('/ui/api/push/unsubscribe', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ endpoint: subscription.endpoint }),
+            });
+            await subscription.unsubscribe();
+        } else {
+            const { public_key } = await (await fetch('/ui/api/push/vapid-key')).json();
+            const created = await registration.pushManager.subscribe({
+                userVisibleOnly: true,
+                applicationServerKey: urlBase64ToUint8Array(public_key),
+            });
+            await fetch('/ui/api/push/subscribe', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(created.toJSON()),
+            });
+        }
+        renderPushToggle();
+    }
+
+    if ('serviceWorker' in navigator && 'PushManager' in window) {
+        pushButton.addEventListener('click', () => togglePush().catch(error => {
+            console.error('Failed to update browser notifications:', error);
+            renderPushToggle();
+        }));
+        renderPushToggle().catch(error => console.error('Service worker registration failed:', error));
+    }
+
+    refresh();
+    setInterval(refresh, POLL_INTERVAL_MS);
+})();
@@ -0,0 +1,79 @@
+// Offline support for signed-in pages: registers the service worker, keeps a
+// local copy of the user's tasks in step with /ui/api/sync and replays task
+// changes made while offline once the connection is back.
+const OfflineTasks = (() => {
+    const CACHE_KEY = 'taskCache';
+    const QUEUE_KEY = 'pendingTaskActions';
+
+    function load(key, fallback) {
+        try {
+            return JSON.parse(localStorage.getItem(key)) || fallback;
+        } catch (error) {
+            return fallback;
+        }
+    }
+
+    function save(key, value) {
+        localStorage.setItem(key, JSON.stringify(value));
+    }
+
+    async function sync() {
+        const cache = load(CACHE_KEY, { since: null, tasks: {} });
+        const query = cache.since ? `?since=${encodeURIComponent(cache.since)}` : '';
+        const response = await fetch(`/ui/api/sync${query}`);
+        if (!response.ok) return;
+
+        const delta = await response.json();
+        delta.tasks.forEach(task => { cache.tasks[task.id] = task; });
+        delta.deleted_task_ids.forEach(id => { delete cache.tasks[id]; });
+        cache.since = delta.server_time;
+        save(CACHE_KEY, cache);
+    }
+
+    // Replays queued changes in order, keeping any that still fail.
+    async function flush() {
+        const queue = load(QUEUE_KEY, []);
+        const remaining = [];
+        for (const action of queue) {
+            try {
+                const response = await fetch(action.url, {
+                    method: action.method,
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify(action.body || {})
+                });
+                // Rejected changes (e.g. a task deleted elsewhere) are dropped.
+                if (response.status >= 500) remaining.push(action);
+            } catch (error) {
+                remaining.push(action);
+            }
+        }
+        save(QUEUE_KEY, remaining);
+        return queue.length - remaining.length;
+    }
+
+    function queue(method, url, body) {
+        const pending = load(QUEUE_KEY, []);
+        pending.push({ method, url, body, queued_at: new Date().toISOString() });
+        save(QUEUE_KEY, pending);
+    }
+
+    function tasks() {
+        return Object.values(load(CACHE_KEY, { tasks: {} }).tasks);
+    }
+
+    async function reconnect() {
+        const replayed = await flush();
+        await sync();
+        if (replayed > 0) location.reload();
+    }
+
+    if ('serviceWorker' in navigator) {
+        navigator.serviceWorker.register('/sw.js').catch(error => console.error('Service worker registration failed:', error));
+    }
+    window.addEventListener('online', () => reconnect().catch(error => console.error('Sync failed:', error)));
+    if (navigator.onLine) {
+        reconnect().catch(error => console.error('Sync failed:', error));
+    }
+
+    return { sync, queue, tasks };
+})();
@@ -0,0 +1,58 @@
+const reports = document.getElementById('reports');
+const stats = JSON.parse(reports.dataset.stats);
+const productivity = JSON.parse(reports.dataset.productivity);
+
+function periodLabel(period, groupBy) {
+    const date = new Date(period);
+    if (groupBy === 'month') {
+        return date.toLocaleDateString(undefined, { year: 'numeric', month: 'short', timeZone: 'UTC' });
+    }
+    return date.toLocaleDateString(undefined, { month: 'short', day: 'numeric', timeZone: 'UTC' });
+}
+
+new Chart(document.getElementById('timelineChart'), {
+    type: 'line',
+    data: {
+        labels: stats.timeline.map(row => periodLabel(row.period, stats.group_by)),
+        datasets: [
+            { label: 'Created', data: stats.timeline.map(row => row.created), borderColor: '#2563eb', backgroundColor: '#2563eb', tension: 0.3 },
+            { label: 'Completed', data: stats.timeline.map(row => row.completed), borderColor: '#16a34a', backgroundColor: '#16a34a', tension: 0.3 }
+        ]
+    },
+    options: { scales: { y: { beginAtZero: true, ticks: { precision: 0 } } } }
+});
+
+new Chart(document.getElementById('typeChart'), {
+    type: 'doughnut',
+    data: {
+        labels: stats.by_type.map(row => row.task_type),
+        datasets: [{ data: stats.by_type.map(row => row.count) }]
+    }
+});
+
+const priorityColours = { Low: '#9ca3af', Medium: '#eab308', High: '#f97316', Critical: '#dc2626' };
+
+new Chart(document.getElementById('priorityChart'), {
+    type: 'bar',
+    data: {
+        labels: stats.by_priority.map(row => row.priority),
+        datasets: [{
+            label: 'Tasks',
+            data: stats.by_priority.map(row => row.count),
+            backgroundColor: stats.by_priority.map(row => priorityColours[row.priority])
+        }]
+    },
+    options: { plugins: { legend: { display: false } }, scales: { y: { beginAtZero: true, ticks: { precision: 0 } } } }
+});
+
+new Chart(document.getElementById('casesChart'), {
+    type: 'bar',
+    data: {
+        labels: productivity.cases_per_week.map(row => periodLabel(row.period, 'week')),
+        datasets: [
+            { label: 'Opened', data: productivity.cases_per_week.map(row => row.opened), backgroundColor: '#2563eb' },
+            { label: 'Closed', data: productivity.cases_per_week.map(row => row.closed), backgroundColor: '#16a34a' }
+        ]
+    },
+    options: { scales: { y: { beginAtZero: true, ticks: { precision: 0 } } } }
+});
@@ -0,0 +1,39 @@
+// Task actions shared by the dashboard and case pages. Every action reloads
+// the page on success so server-rendered state stays authoritative. Without
+// a connection the action is queued and replayed by offline.js.
+async function taskRequest(url, body) {
+    let response;
+    try {
+        response = await fetch(url, {
+            method: 'PUT',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(body || {})
+        });
+    } catch (error) {
+        if (typeof OfflineTasks === 'undefined') throw error;
+        OfflineTasks.queue('PUT', url, body);
+        alert('You are offline. The change will be saved when you reconnect.');
+        return;
+    }
+    if (response.ok) {
+        location.reload();
+    } else {
+        alert('Could not update task');
+    }
+}
+
+function completeTask(id) {
+    taskRequest(`/ui/api/tasks/${id}/complete`);
+}
+
+function setTaskStatus(id, status) {
+    taskRequest(`/ui/api/tasks/${id}/status`, { status });
+}
+
+function snoozeTask(id, days) {
+    taskRequest(`/ui/api/tasks/${id}/snooze`, { days });
+}
+
+function reviewTask(id, decision) {
+    taskRequest(`/ui/api/review-queue/${id}/${decision}`);
+}
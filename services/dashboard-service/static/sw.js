@@ -0,0 +1,89 @@
+// Service worker for the dashboard. Caches the app shell and visited pages
+// so the dashboard opens offline, shows pushed notifications and opens their
+// page when clicked.
+const CACHE = 'task-manager-v1';
+const APP_SHELL = [
+    '/manifest.webmanifest',
+    '/static/icons/icon.svg',
+    '/static/css/theme.css',
+    '/static/js/tasks.js',
+    '/static/js/offline.js',
+    '/static/js/notifications.js',
+    '/static/js/dashboard.js',
+    '/static/js/board.js',
+];
+
+self.addEventListener('install', event => {
+    event.waitUntil(caches.open(CACHE).then(cache => cache.addAll(APP_SHELL)).then(() => self.skipWaiting()));
+});
+
+self.addEventListener('activate', event => {
+    event.waitUntil((async () => {
+        const names = await caches.keys();
+        await Promise.all(names.filter(name => name !== CACHE).map(name => caches.delete(name)));
+        await self.clients.claim();
+    })());
+});
+
+self.addEventListener('fetch', event => {
+    const request = event.request;
+    if (request.method !== 'GET') return;
+    const url = new URL(request.url);
+
+    if (request.mode === 'navigate') {
+        event.respondWith(networkFirst(request));
+    } else if (url.origin !== location.origin || url.pathname.startsWith('/static/') || url.pathname === '/manifest.webmanifest') {
+        // Scripts, styles and icons, including the Tailwind CDN.
+        event.respondWith(staleWhileRevalidate(request));
+    }
+    // API calls always go to the network; offline.js keeps its own task cache.
+});
+
+async function networkFirst(request) {
+    const cache = await caches.open(CACHE);
+    try {
+        const response = await fetch(request);
+        if (response.ok) cache.put(request, response.clone());
+        return response;
+    } catch (error) {
+        return (await cache.match(request)) || (await cache.match('/dashboard')) || Response.error();
+    }
+}
+
+async function staleWhileRevalidate(request) {
+    const cache = await caches.open(CACHE);
+    const cached = await cache.match(request);
+    const network = fetch(request).then(response => {
+        if (response.ok || response.type === 'opaque') cache.put(request, response.clone());
+        return response;
+    });
+    if (cached) {
+        network.catch(() => {});
+        return cached;
+    }
+    return network;
+}
+
+self.addEventListener('push', event => {
+    const data = event.data ? event.data.json() : {};
+    event.waitUntil(
+        self.registration.showNotification(data.title || 'Task Manager', {
+            body: data.body,
+            tag: data.id,
+            data: { id: data.id, link: data.link || '/dashboard' },
+        })
+    );
+});
+
+self.addEventListener('notificationclick', event => {
+    event.notification.close();
+    const { id, link } = event.notification.data;
+    event.waitUntil((async () => {
+        if (id) {
+            await fetch(`/ui/api/notifications/${id}/read`, { method: 'POST' }).catch(() => {});
+        }
+        const windows = await self.clients.matchAll({ type: 'window', includeUncontrolled: true });
+        const existing = windows.find(client => new URL(client.url).pathname === link);
+        return existing ? existing.focus() : self.clients.openWindow(link);
+    })());
+});
@@ -0,0 +1,154 @@
+//! Email body cleanup.
+//!
+//! Bodies are turned into plain text before they are sent on: HTML is
+//! reduced to its text, and quoted reply chains and signatures are cut off,
+//! so the LLM only sees what the sender actually wrote.  Forwarded emails
+//! keep everything below the forwarder's note, as the forwarded message is
+//! usually what the task is about.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+// Compiled once: a catch-up after downtime cleans a whole inbox at once.
+static FORWARD_SUBJECT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^\s*(fwd?|fw):").unwrap());
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<(html|body|div|p|br|table|span)\b").unwrap());
+/// Markup that carries no message text: the head, styles, scripts,
+/// comments (Outlook's conditional blocks) and images, which covers
+/// tracking pixels.
+static NO_TEXT: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        r"(?is)<head\b.*?</head>",
+        r"(?is)<style\b.*?</style>",
+        r"(?is)<script\b.*?</script>",
+        r"(?s)<!--.*?-->",
+        r"(?i)<img\b[^>]*>",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect()
+});
+static LIST_ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<li\b[^>]*>").unwrap());
+static LINE_BREAK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<br\s*/?>|</(p|div|tr|h[1-6]|table|ul|ol)>").unwrap());
+static CELL_END: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)</t[dh]>").unwrap());
+static ANY_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+static SPACES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[ \t\u{a0}]+").unwrap());
+static QUOTE_START: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)<div[^>]*id="?(divRplyFwdMsg|appendonsend)|<div[^>]*class="?gmail_quote|<blockquote[^>]*type="?cite"#)
+        .unwrap()
+});
+static ENTITY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"&(#x[0-9a-fA-F]+|#[0-9]+|[a-zA-Z]+);").unwrap());
+static REPLY_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(-{2,}\s*original message\s*-{2,}|on .+ wrote:|from:\s.+|_{10,})$").unwrap()
+});
+static SIGNATURE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^(--|sent from my .+|get outlook for .+)$").unwrap());
+static BLANK_LINES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n{3,}").unwrap());
+
+/// Plain text of an email body.  `html` tells whether the body is HTML;
+/// Graph says so in the body's content type.
+pub fn to_plain_text(body: &str, html: bool, subject: Option<&str>) -> String {
+    let forwarded = subject.is_some_and(is_forward);
+    let text = if html { html_to_text(body, forwarded) } else { body.replace("\r\n", "\n") };
+    let text = if forwarded { text } else { strip_signature(&strip_reply_chain(&text)) };
+    collapse_blank_lines(&text)
+}
+
+fn is_forward(subject: &str) -> bool {
+    FORWARD_SUBJECT.is_match(subject)
+}
+
+/// Whether a body of unknown type looks like HTML.
+pub fn looks_like_html(body: &str) -> bool {
+    HTML_TAG.is_match(body)
+}
+
+fn html_to_text(html: &str, keep_quoted: bool) -> String {
+    let mut html = html.to_string();
+
+    for pattern in NO_TEXT.iter() {
+        html = pattern.replace_all(&html, "").into_owned();
+    }
+
+    // Quoted replies: Outlook's reply header, Gmail's quote block and
+    // anything after a quoting blockquote are cut off.
+    if !keep_quoted {
+        truncate_at_quote(&mut html);
+    }
+
+    html = LIST_ITEM.replace_all(&html, "\n- ").into_owned();
+    html = LINE_BREAK.replace_all(&html, "\n").into_owned();
+    html = CELL_END.replace_all(&html, " ").into_owned();
+    html = ANY_TAG.replace_all(&html, "").into_owned();
+
+    let text = decode_entities(&html);
+    text.lines()
+        .map(|line| SPACES.replace_all(line, " ").trim().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn truncate_at_quote(html: &mut String) {
+    if let Some(found) = QUOTE_START.find(html) {
+        html.truncate(found.start());
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    ENTITY
+        .replace_all(text, |caps: &regex::Captures| {
+            let entity = &caps[1];
+            let decoded = if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(decimal) = entity.strip_prefix('#') {
+                decimal.parse().ok().and_then(char::from_u32)
+            } else {
+                match entity {
+                    "nbsp" => Some(' '),
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "ndash" => Some('–'),
+                    "mdash" => Some('—'),
+                    "hellip" => Some('…'),
+                    _ => None,
+                }
+            };
+            decoded.map_or_else(|| caps[0].to_string(), String::from)
+        })
+        .into_owned()
+}
+
+/// Cuts the text at the start of a quoted earlier message and drops
+/// `>`-quoted lines.
+fn strip_reply_chain(text: &str) -> String {
+    let mut kept = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if REPLY_HEADER.is_match(trimmed) {
+            break;
+        }
+        if !trimmed.starts_with('>') {
+            kept.push(line);
+        }
+    }
+    kept.join("\n")
+}
+
+/// Cuts the text at a signature delimiter or a mobile client's sign-off.
+fn strip_signature(text: &str) -> String {
+    let mut kept = Vec::new();
+    for line in text.lines() {
+        if SIGNATURE.is_match(line.trim()) {
+            break;
+        }
+        kept.push(line);
+    }
+    kept.join("\n")
+}
+
+fn collapse_blank_lines(text: &str) -> String {
+    BLANK_LINES.replace_all(text.trim(), "\n\n").into_owned()
+}
@@ -0,0 +1,214 @@
+//! Mail folders polled through Microsoft Graph.
+//!
+//! Folders are configured as a comma separated list, e.g.
+//! `inbox, Projects, support@example.com/inbox`, and polled in every
+//! connected mailbox.  Each entry is a folder of the connected mailbox or,
+//! when prefixed with an address and `/`, of a shared mailbox, read with
+//! the connected mailbox's token.  Folders are named by their well-known
+//! name (`inbox`, `archive`, ...), their display name or their Graph id.
+//!
+//! Folders are synced with Graph delta queries: the first sync of a folder
+//! pages through its messages and ends with a delta link, and later syncs
+//! follow that link to get only what changed since.  Delta links, kept per
+//! connected mailbox and folder, and the ids of processed messages make up
+//! the [`SyncState`], which can be kept in a file so a restart doesn't
+//! start over.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Message properties requested from Graph.
+const MESSAGE_FIELDS: &str = "id,subject,bodyPreview,body,from,isRead,receivedDateTime,importance,flag,categories,hasAttachments,internetMessageId,conversationId,internetMessageHeaders";
+
+/// Processed message ids remembered to skip messages seen again, e.g.
+/// after being marked as read or moved to another polled folder.
+const MAX_PROCESSED_IDS: usize = 5000;
+
+/// Folder names Graph resolves without a lookup.
+const WELL_KNOWN_FOLDERS: &[&str] = &[
+    "archive", "clutter", "conversationhistory", "deleteditems", "drafts", "inbox",
+    "junkemail", "outbox", "scheduled", "searchfolders", "sentitems",
+];
+
+/// A folder of the connected mailbox or of a shared mailbox.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MailFolder {
+    /// Address of a shared mailbox; `None` for the connected mailbox.
+    pub mailbox: Option<String>,
+    pub folder: String,
+}
+
+impl MailFolder {
+    /// Parses `folder` or `mailbox/folder`.  Returns `None` for blank input.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim();
+        let (mailbox, folder) = match spec.split_once('/') {
+            Some((mailbox, folder)) if mailbox.contains('@') => (Some(mailbox.trim().to_string()), folder.trim()),
+            _ => (None, spec),
+        };
+        (!folder.is_empty()).then(|| MailFolder { mailbox, folder: folder.to_string() })
+    }
+
+    /// Parses a comma separated list, skipping blank entries.
+    pub fn parse_list(list: &str) -> Vec<Self> {
+        list.split(',').filter_map(Self::parse).collect()
+    }
+
+    /// Whether Graph accepts [`folder`](Self::folder) in a URL as is.
+    /// Display names have to be looked up first.
+    pub fn is_addressable(&self) -> bool {
+        WELL_KNOWN_FOLDERS.contains(&self.folder.to_lowercase().as_str())
+            || (self.folder.len() > 40 && !self.folder.contains(' '))
+    }
+
+    fn owner_url(&self, graph_url: &str) -> String {
+        let graph_url = graph_url.trim_end_matches('/');
+        match &self.mailbox {
+            Some(mailbox) => format!("{}/users/{}", graph_url, mailbox),
+            None => format!("{}/me", graph_url),
+        }
+    }
+
+    /// URL listing the mailbox's folders with this display name, under the
+    /// Graph API root `graph_url`.
+    pub fn lookup_url(&self, graph_url: &str) -> String {
+        format!(
+            "{}/mailFolders?$filter=displayName eq '{}'&$select=id",
+            self.owner_url(graph_url),
+            self.folder.replace('\'', "''")
+        )
+    }
+
+    /// URL starting a delta sync of the folder `folder_id`.
+    pub fn delta_url(&self, graph_url: &str, folder_id: &str) -> String {
+        format!(
+            "{}/mailFolders/{}/messages/delta?$select={}",
+            self.owner_url(graph_url),
+            folder_id,
+            MESSAGE_FIELDS
+        )
+    }
+
+    /// URL of a single message in the folder's mailbox.
+    pub fn message_url(&self, graph_url: &str, message_id: &str) -> String {
+        format!("{}/messages/{}", self.owner_url(graph_url), message_id)
+    }
+
+    /// Path of a single message relative to the Graph API root, as used in
+    /// `$batch` requests.
+    pub fn message_path(&self, message_id: &str) -> String {
+        self.message_url("", message_id)
+    }
+}
+
+impl fmt::Display for MailFolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.mailbox {
+            Some(mailbox) => write!(f, "{}/{}", mailbox, self.folder),
+            None => write!(f, "{}", self.folder),
+        }
+    }
+}
+
+/// Sync state kept per folder.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FolderSync {
+    /// Graph id the folder's name resolved to.
+    pub folder_id: Option<String>,
+    /// Link returning the changes since the last completed sync.  `None`
+    /// until the first sync of the folder completes.
+    pub delta_link: Option<String>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub messages_processed: u64,
+    /// Processed messages that couldn't be marked as read yet; retried on
+    /// the next sync.
+    #[serde(default)]
+    pub unread_message_ids: Vec<String>,
+}
+
+/// A configured folder of a connected mailbox with its sync state, as
+/// reported by the API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderStatus {
+    /// The connected mailbox (`EmailAccount`) the folder is polled for.
+    /// State saved before mailboxes were connected per user has none.
+    #[serde(default)]
+    pub account_id: Uuid,
+    #[serde(flatten)]
+    pub folder: MailFolder,
+    #[serde(flatten)]
+    pub sync: FolderSync,
+}
+
+/// Sync state of all folders.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    folders: Vec<FolderStatus>,
+    /// Graph ids of processed messages, oldest first.
+    processed_message_ids: VecDeque<String>,
+}
+
+impl SyncState {
+    /// Reads the state saved at `path`, starting afresh when there is none
+    /// or it can't be read.
+    pub async fn load(path: &Path) -> Self {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring unreadable sync state {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> anyhow::Result<()> {
+        tokio::fs::write(path, serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+
+    pub fn folder(&self, account_id: Uuid, folder: &MailFolder) -> FolderSync {
+        self.folders
+            .iter()
+            .find(|status| status.account_id == account_id && &status.folder == folder)
+            .map(|status| status.sync.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_folder(&mut self, account_id: Uuid, folder: &MailFolder, sync: FolderSync) {
+        match self.folders.iter_mut().find(|status| status.account_id == account_id && &status.folder == folder) {
+            Some(status) => status.sync = sync,
+            None => self.folders.push(FolderStatus { account_id, folder: folder.clone(), sync }),
+        }
+    }
+
+    /// The folders synced so far.
+    pub fn statuses(&self) -> Vec<FolderStatus> {
+        self.folders.clone()
+    }
+
+    /// Drops the state of folders no longer polled.
+    pub fn retain_folders(&mut self, folders: &[MailFolder]) {
+        self.folders.retain(|status| folders.contains(&status.folder));
+    }
+
+    /// Drops the state of mailboxes that were removed.
+    pub fn retain_accounts(&mut self, account_ids: &[Uuid]) {
+        self.folders.retain(|status| account_ids.contains(&status.account_id));
+    }
+
+    pub fn is_processed(&self, message_id: &str) -> bool {
+        self.processed_message_ids.iter().any(|id| id == message_id)
+    }
+
+    pub fn mark_processed(&mut self, message_id: &str) {
+        self.processed_message_ids.push_back(message_id.to_string());
+        while self.processed_message_ids.len() > MAX_PROCESSED_IDS {
+            self.processed_message_ids.pop_front();
+        }
+    }
+}
@@ -0,0 +1,491 @@
+//! Meeting invitations in email.
+//!
+//! Invitations come as Graph event messages, whose event is fetched along
+//! with the message, or as iCalendar data: a `text/calendar` (`.ics`)
+//! attachment, or a calendar part inlined in the body of an email posted
+//! to the webhook.  Either way they become a [`MeetingInvite`], filed as a
+//! Meeting task instead of having tasks extracted from the invitation's
+//! text.  Cancellations and replies to invitations are left to the usual
+//! processing.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use models::MeetingInvite;
+use serde::Deserialize;
+
+/// Graph `@odata.type` of a message inviting to a meeting.
+pub const EVENT_MESSAGE_REQUEST: &str = "#microsoft.graph.eventMessageRequest";
+
+/// Whether an email body carries iCalendar data.
+pub fn has_calendar(text: &str) -> bool {
+    text.contains("BEGIN:VCALENDAR")
+}
+
+/// Whether an attachment is iCalendar data.
+pub fn is_calendar_attachment(content_type: Option<&str>, name: Option<&str>) -> bool {
+    content_type.is_some_and(|content_type| content_type.to_lowercase().starts_with("text/calendar"))
+        || name.is_some_and(|name| name.to_lowercase().ends_with(".ics"))
+}
+
+/// A Graph event, as expanded on an event message.  Times are in UTC as
+/// the collector asks for them with `Prefer: outlook.timezone="UTC"`.
+#[derive(Debug, Deserialize)]
+pub struct GraphEvent {
+    subject: Option<String>,
+    #[serde(rename = "bodyPreview")]
+    body_preview: Option<String>,
+    start: Option<GraphDateTime>,
+    end: Option<GraphDateTime>,
+    location: Option<GraphLocation>,
+    organizer: Option<GraphRecipient>,
+    #[serde(default)]
+    attendees: Vec<GraphRecipient>,
+    #[serde(rename = "iCalUId")]
+    ical_uid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphDateTime {
+    #[serde(rename = "dateTime")]
+    date_time: String,
+    #[serde(rename = "timeZone")]
+    time_zone: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphLocation {
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphRecipient {
+    #[serde(rename = "emailAddress")]
+    email_address: Option<GraphAddress>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphAddress {
+    address: Option<String>,
+}
+
+impl GraphRecipient {
+    fn address(&self) -> Option<String> {
+        self.email_address.as_ref()?.address.clone().filter(|address| !address.is_empty())
+    }
+}
+
+impl GraphDateTime {
+    fn to_utc(&self) -> Option<DateTime<Utc>> {
+        if self.time_zone.as_deref().is_some_and(|zone| !zone.eq_ignore_ascii_case("UTC")) {
+            tracing::warn!("Event time {} is in {:?}, not UTC", self.date_time, self.time_zone);
+        }
+        let local = NaiveDateTime::parse_from_str(&self.date_time, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+        Some(Utc.from_utc_datetime(&local))
+    }
+}
+
+impl GraphEvent {
+    pub fn to_invite(&self, fallback_title: Option<&str>) -> Option<MeetingInvite> {
+        let title = self.subject.as_deref().or(fallback_title).unwrap_or("Meeting").trim().to_string();
+        Some(MeetingInvite {
+            title,
+            starts_at: self.start.as_ref()?.to_utc()?,
+            ends_at: self.end.as_ref().and_then(GraphDateTime::to_utc),
+            location: self
+                .location
+                .as_ref()
+                .and_then(|location| location.display_name.clone())
+                .filter(|location| !location.trim().is_empty()),
+            organizer: self.organizer.as_ref().and_then(GraphRecipient::address),
+            attendees: self.attendees.iter().filter_map(GraphRecipient::address).collect(),
+            description: self.body_preview.clone().filter(|text| !text.trim().is_empty()),
+            uid: self.ical_uid.clone(),
+        })
+    }
+}
+
+/// A content line: `NAME;PARAM=value:VALUE`.
+#[derive(Debug)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Self> {
+        // The value starts at the first colon outside quoted parameters.
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(i),
+            _ => None,
+        })?;
+        let (head, value) = (&line[..colon], &line[colon + 1..]);
+        let mut parts = head.split(';');
+        let name = parts.next()?.trim().to_uppercase();
+        let params = parts
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| (key.trim().to_uppercase(), value.trim().trim_matches('"').to_string()))
+            .collect();
+        Some(Property { name, params, value: value.to_string() })
+    }
+
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str())
+    }
+
+    fn text(&self) -> String {
+        unescape(&self.value)
+    }
+
+    /// An `ORGANIZER` or `ATTENDEE` address.
+    fn address(&self) -> Option<String> {
+        let value = self.value.trim();
+        let address = match value.get(..7) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &value[7..],
+            _ => value,
+        };
+        (!address.is_empty()).then(|| address.to_string())
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Joins folded lines back into content lines.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), lines.last_mut()) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// A `STANDARD` or `DAYLIGHT` part of a `VTIMEZONE`: the UTC offset from
+/// its onset, yearly when it has a rule.
+#[derive(Debug, Default)]
+struct Observance {
+    onset: Option<NaiveDateTime>,
+    offset_secs: i32,
+    /// `BYMONTH` and `BYDAY` of a yearly rule, e.g. `(3, -1, Sun)` for the
+    /// last Sunday of March.
+    rule: Option<(u32, i32, Weekday)>,
+}
+
+impl Observance {
+    /// When the observance last began at or before `local`.
+    fn last_onset(&self, local: NaiveDateTime) -> Option<NaiveDateTime> {
+        let onset = self.onset?;
+        let Some((month, nth, weekday)) = self.rule else {
+            return (onset <= local).then_some(onset);
+        };
+        [local.year(), local.year() - 1].into_iter().find_map(|year| {
+            let date = nth_weekday(year, month, nth, weekday)?;
+            let at = date.and_time(onset.time());
+            (at <= local && at >= onset).then_some(at)
+        })
+    }
+}
+
+/// The `nth` `weekday` of a month; negative counts from its end.
+fn nth_weekday(year: i32, month: u32, nth: i32, weekday: Weekday) -> Option<NaiveDate> {
+    if nth > 0 {
+        let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let ahead = (7 + weekday.num_days_from_monday() - first.weekday().num_days_from_monday()) % 7;
+        let date = first + Duration::days(i64::from(ahead) + 7 * i64::from(nth - 1));
+        (date.month() == month).then_some(date)
+    } else {
+        let next_month = if month == 12 { NaiveDate::from_ymd_opt(year + 1, 1, 1)? } else { NaiveDate::from_ymd_opt(year, month + 1, 1)? };
+        let last = next_month.pred_opt()?;
+        let back = (7 + last.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+        let date = last - Duration::days(i64::from(back) + 7 * i64::from(-nth - 1));
+        (date.month() == month).then_some(date)
+    }
+}
+
+fn parse_rule(rrule: &str) -> Option<(u32, i32, Weekday)> {
+    let mut month = None;
+    let mut day = None;
+    for part in rrule.split(';') {
+        match part.split_once('=') {
+            Some(("BYMONTH", value)) => month = value.parse().ok(),
+            Some(("BYDAY", value)) => day = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    let day = day?;
+    let (nth, weekday) = day.split_at(day.len().checked_sub(2)?);
+    let weekday = match weekday {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let nth = if nth.is_empty() { 1 } else { nth.trim_start_matches('+').parse().ok()? };
+    Some((month?, nth, weekday))
+}
+
+/// `+0100`, `-0500` or `+053000` in seconds.
+fn parse_offset(offset: &str) -> Option<i32> {
+    let offset = offset.trim();
+    let (sign, digits) = match offset.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    let number = |range: std::ops::Range<usize>| digits.get(range).map_or(Some(0), |part: &str| part.parse::<i32>().ok());
+    if digits.len() < 4 {
+        return None;
+    }
+    Some(sign * (number(0..2)? * 3600 + number(2..4)? * 60 + number(4..6)?))
+}
+
+fn parse_local(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim().trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y%m%d").ok().map(|date| date.and_time(NaiveTime::MIN)))
+}
+
+/// Time zones defined in the calendar, by `TZID`.
+#[derive(Debug, Default)]
+struct TimeZones(Vec<(String, Vec<Observance>)>);
+
+impl TimeZones {
+    /// `DTSTART`/`DTEND` in UTC.  Times without a zone, or in a zone the
+    /// calendar doesn't define, are taken as UTC.
+    fn to_utc(&self, property: &Property) -> Option<DateTime<Utc>> {
+        let local = parse_local(&property.value)?;
+        if property.value.trim().ends_with('Z') {
+            return Some(Utc.from_utc_datetime(&local));
+        }
+        let Some(tzid) = property.param("TZID") else {
+            return Some(Utc.from_utc_datetime(&local));
+        };
+        let observances = self.0.iter().find(|(id, _)| id == tzid).map(|(_, observances)| observances);
+        let offset = observances.and_then(|observances| {
+            observances
+                .iter()
+                .filter_map(|observance| Some((observance.last_onset(local)?, observance.offset_secs)))
+                .max_by_key(|(onset, _)| *onset)
+                .map(|(_, offset)| offset)
+                .or_else(|| observances.first().map(|observance| observance.offset_secs))
+        });
+        if offset.is_none() {
+            tracing::warn!("Time zone {} is not defined in the invitation, assuming UTC", tzid);
+        }
+        Some(Utc.from_utc_datetime(&(local - Duration::seconds(i64::from(offset.unwrap_or(0))))))
+    }
+}
+
+/// `DURATION` such as `PT1H30M` or `P1D`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1, value),
+        None => (1, value.trim_start_matches('+')),
+    };
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match unit {
+                    'W' => Duration::weeks(n),
+                    'D' => Duration::days(n),
+                    'H' => Duration::hours(n),
+                    'M' => Duration::minutes(n),
+                    'S' => Duration::seconds(n),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(total * sign)
+}
+
+/// The first event of iCalendar data, if it invites to a meeting.
+pub fn parse_ics(text: &str) -> Option<MeetingInvite> {
+    let lines = unfold(text);
+    let mut components: Vec<String> = Vec::new();
+    let mut method = None;
+    let mut zones = TimeZones::default();
+    let mut tzid: Option<String> = None;
+    let mut observance = Observance::default();
+    let mut event: Option<Vec<Property>> = None;
+    let mut in_event = false;
+
+    for line in &lines {
+        let Some(property) = Property::parse(line) else { continue };
+        let value = property.value.trim().to_uppercase();
+        match property.name.as_str() {
+            "BEGIN" => {
+                if value == "VEVENT" && event.is_none() {
+                    in_event = true;
+                    event = Some(Vec::new());
+                }
+                if value == "STANDARD" || value == "DAYLIGHT" {
+                    observance = Observance::default();
+                }
+                components.push(value);
+                continue;
+            }
+            "END" => {
+                if value == "VEVENT" {
+                    in_event = false;
+                }
+                if value == "STANDARD" || value == "DAYLIGHT" {
+                    if let Some(tzid) = &tzid {
+                        match zones.0.iter_mut().find(|(id, _)| id == tzid) {
+                            Some((_, observances)) => observances.push(std::mem::take(&mut observance)),
+                            None => zones.0.push((tzid.clone(), vec![std::mem::take(&mut observance)])),
+                        }
+                    }
+                }
+                components.pop();
+                continue;
+            }
+            _ => {}
+        }
+        match (components.last().map(String::as_str), property.name.as_str()) {
+            (Some("VCALENDAR"), "METHOD") => method = Some(value),
+            (Some("VTIMEZONE"), "TZID") => tzid = Some(property.value.trim().to_string()),
+            (Some("STANDARD" | "DAYLIGHT"), "DTSTART") => observance.onset = parse_local(&property.value),
+            (Some("STANDARD" | "DAYLIGHT"), "TZOFFSETTO") => observance.offset_secs = parse_offset(&property.value).unwrap_or(0),
+            (Some("STANDARD" | "DAYLIGHT"), "RRULE") => observance.rule = parse_rule(&value),
+            (Some("VEVENT"), _) if in_event => event.get_or_insert_with(Vec::new).push(property),
+            _ => {}
+        }
+    }
+
+    if method.as_deref().is_some_and(|method| method != "REQUEST" && method != "PUBLISH") {
+        return None;
+    }
+    let event = event?;
+    let find = |name: &str| event.iter().find(|property| property.name == name);
+    if find("STATUS").is_some_and(|status| status.value.trim().eq_ignore_ascii_case("CANCELLED")) {
+        return None;
+    }
+    let starts_at = zones.to_utc(find("DTSTART")?)?;
+    let ends_at = find("DTEND")
+        .and_then(|end| zones.to_utc(end))
+        .or_else(|| find("DURATION").and_then(|duration| parse_duration(&duration.value)).map(|duration| starts_at + duration));
+
+    Some(MeetingInvite {
+        title: find("SUMMARY").map(Property::text).filter(|title| !title.is_empty()).unwrap_or_else(|| "Meeting".to_string()),
+        starts_at,
+        ends_at,
+        location: find("LOCATION").map(Property::text).filter(|location| !location.is_empty()),
+        organizer: find("ORGANIZER").and_then(Property::address),
+        attendees: event.iter().filter(|property| property.name == "ATTENDEE").filter_map(Property::address).collect(),
+        description: find("DESCRIPTION").map(Property::text).filter(|description| !description.is_empty()),
+        uid: find("UID").map(|uid| uid.value.trim().to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTLOOK_INVITE: &str = "BEGIN:VCALENDAR\r
+METHOD:REQUEST\r
+VERSION:2.0\r
+BEGIN:VTIMEZONE\r
+TZID:W. Europe Standard Time\r
+BEGIN:STANDARD\r
+DTSTART:16010101T030000\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=10\r
+END:STANDARD\r
+BEGIN:DAYLIGHT\r
+DTSTART:16010101T020000\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0200\r
+RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=3\r
+END:DAYLIGHT\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+ORGANIZER;CN=\"Lead, Team\":mailto:lead@company.com\r
+ATTENDEE;ROLE=REQ-PARTICIPANT;CN=Ana:mailto:ana@company.com\r
+ATTENDEE;CN=Bob:MAILTO:bob@company.com\r
+DESCRIPTION:Agenda:\\n- numbers\\, forecast\r
+UID:040000008200E00074C5B7101A82E008\r
+SUMMARY:Budget review\r
+DTSTART;TZID=W. Europe Standard Time:20240604T150000\r
+DTEND;TZID=W. Europe Standard Time:20240604T153000\r
+LOCATION:Room 4 (2nd\r
+  floor)\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn outlook_invitations_are_read_in_their_time_zone() {
+        let invite = parse_ics(OUTLOOK_INVITE).expect("an invitation");
+        assert_eq!(invite.title, "Budget review");
+        assert_eq!(invite.starts_at, "2024-06-04T13:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(invite.ends_at, Some("2024-06-04T13:30:00Z".parse().unwrap()));
+        assert_eq!(invite.location.as_deref(), Some("Room 4 (2nd floor)"));
+        assert_eq!(invite.organizer.as_deref(), Some("lead@company.com"));
+        assert_eq!(invite.attendees, ["ana@company.com", "bob@company.com"]);
+        assert_eq!(invite.description.as_deref(), Some("Agenda:\n- numbers, forecast"));
+        assert_eq!(invite.uid.as_deref(), Some("040000008200E00074C5B7101A82E008"));
+
+        // Standard time in winter.
+        let winter = OUTLOOK_INVITE.replace("20240604T", "20240110T");
+        assert_eq!(parse_ics(&winter).unwrap().starts_at, "2024-01-10T14:00:00Z".parse::<DateTime<Utc>>().unwrap());
+    }
+
+    #[test]
+    fn utc_times_and_durations_need_no_zone() {
+        let ics = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:Standup\nDTSTART:20240304T090000Z\nDURATION:PT15M\nEND:VEVENT\nEND:VCALENDAR\n";
+        let invite = parse_ics(ics).expect("an invitation");
+        assert_eq!(invite.starts_at, "2024-03-04T09:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(invite.ends_at, Some("2024-03-04T09:15:00Z".parse().unwrap()));
+        assert!(invite.attendees.is_empty());
+    }
+
+    #[test]
+    fn cancellations_are_not_invitations() {
+        assert!(parse_ics(&OUTLOOK_INVITE.replace("METHOD:REQUEST", "METHOD:CANCEL")).is_none());
+        assert!(parse_ics("BEGIN:VCALENDAR\nEND:VCALENDAR\n").is_none());
+    }
+
+    #[test]
+    fn nth_weekdays_count_from_either_end() {
+        assert_eq!(nth_weekday(2024, 3, -1, Weekday::Sun), NaiveDate::from_ymd_opt(2024, 3, 31));
+        assert_eq!(nth_weekday(2024, 3, 2, Weekday::Sun), NaiveDate::from_ymd_opt(2024, 3, 10));
+        assert_eq!(nth_weekday(2024, 11, 1, Weekday::Sun), NaiveDate::from_ymd_opt(2024, 11, 3));
+        assert_eq!(nth_weekday(2024, 2, 5, Weekday::Mon), None);
+    }
+}
@@ -2,12 +2,13 @@
 //!
 //! This microservice exposes a simple HTTP endpoint that accepts email data
 //! (for example from a webhook or another mail relay) and forwards the
-//! contents to the channel service for further processing. It also supports
-//! actively fetching emails from IMAP servers.
+//! contents to the channel service for further processing. It also polls
+//! the mailboxes users connected from the dashboard, each with its own
+//! token, and files new mail as tasks of the user who connected it.
 //!
 //! Features:
 //! - Webhook endpoint for receiving email data
-//! - IMAP client for fetching emails from email servers
+//! - Microsoft Graph polling of every connected mailbox
 //! - Automatic forwarding to channel service for AI processing
 //!
 //! The service can be configured via environment variables defined in
@@ -15,29 +16,53 @@
 
 use axum::{
     extract::State,
+    http::{HeaderMap, StatusCode},
     response::Json,
     routing::{get, post},
     Router,
 };
-use common::{config::ServiceConfig, http_client::HttpClient, HealthResponse, ServiceResult};
-use models::{MessageRequest, MessageResponse, MessageChannel};
-use serde::{Deserialize, Serialize};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{
+    auth::check_internal,
+    config::{NoiseAction, ServiceConfig},
+    http_client::HttpClient,
+    jobs::{HttpJobQueue, JobQueue, Worker},
+    logging,
+    reload::LiveConfig,
+    HealthResponse, ServiceResult,
+};
+use models::{
+    EmailAccount, EmailProvider, EmailThread, InviteRequest, MailboxHealth, MeetingInvite, MessageRequest, MessageResponse, MessageChannel, ScheduleJobRequest,
+    SendEmailRequest, UserProfile,
+};
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
-use tokio::time::interval;
 use tracing::{info, error, warn, instrument};
-use reqwest;
-use serde_json;
 use tower::ServiceBuilder;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use uuid::Uuid;
 
-/// Email server configuration for Microsoft Graph API
-#[derive(Debug, Clone)]
-struct EmailConfig {
-    username: String,
-    oauth_token: Option<String>,
-}
+mod body;
+mod folders;
+mod invite;
+mod noise;
+mod priority;
+mod throttle;
+
+use folders::{FolderStatus, FolderSync, MailFolder, SyncState};
+use noise::{Noise, NoiseRules};
+use priority::{EmailMetadata, PriorityMapping};
+use throttle::{ThrottleMetrics, Throttled};
+
+/// Kind of the recurring job that fetches new mail.
+const POLL_JOB: &str = "email.poll";
+
+/// Most requests Graph accepts in one `$batch`.
+const MAX_BATCH_REQUESTS: usize = 20;
 
 #[derive(Debug, Deserialize)]
 struct GraphMessage {
@@ -47,8 +72,73 @@ struct GraphMessage {
     body_preview: Option<String>,
     body: Option<GraphMessageBody>,
     from: Option<GraphEmailAddress>,
-    #[serde(rename = "isRead")]
+    #[serde(rename = "isRead", default)]
     is_read: bool,
+    #[serde(rename = "internetMessageId")]
+    internet_message_id: Option<String>,
+    #[serde(rename = "conversationId")]
+    conversation_id: Option<String>,
+    #[serde(rename = "internetMessageHeaders", default)]
+    internet_message_headers: Vec<GraphMessageHeader>,
+    /// `low`, `normal` or `high`.
+    importance: Option<String>,
+    flag: Option<GraphFlag>,
+    #[serde(default)]
+    categories: Vec<String>,
+    #[serde(rename = "hasAttachments", default)]
+    has_attachments: bool,
+    /// Set on messages of a derived type, such as meeting invitations.
+    #[serde(rename = "@odata.type")]
+    odata_type: Option<String>,
+    /// Set on delta entries of messages deleted or moved out of the folder.
+    #[serde(rename = "@removed")]
+    removed: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphFlag {
+    /// `notFlagged`, `flagged` or `complete`.
+    #[serde(rename = "flagStatus")]
+    flag_status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphMessageHeader {
+    name: String,
+    value: String,
+}
+
+impl GraphMessage {
+    /// Identifiers used to add replies to the case of their thread.
+    fn email_thread(&self) -> EmailThread {
+        let in_reply_to = self
+            .internet_message_headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case("In-Reply-To"))
+            .map(|header| header.value.trim().to_string());
+        EmailThread {
+            message_id: self.internet_message_id.clone(),
+            conversation_id: self.conversation_id.clone(),
+            in_reply_to,
+        }
+    }
+
+    fn sender(&self) -> Option<&str> {
+        self.from.as_ref()?.email_address.as_ref()?.address.as_deref()
+    }
+
+    fn is_meeting_request(&self) -> bool {
+        self.odata_type.as_deref() == Some(invite::EVENT_MESSAGE_REQUEST)
+    }
+
+    /// What priority rules look at.
+    fn metadata(&self) -> EmailMetadata<'_> {
+        EmailMetadata {
+            importance: self.importance.as_deref(),
+            flag_status: self.flag.as_ref().and_then(|flag| flag.flag_status.as_deref()),
+            categories: &self.categories,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -67,41 +157,70 @@ struct GraphEmailAddress {
 #[derive(Debug, Deserialize)]
 struct GraphEmail {
     address: Option<String>,
+    #[allow(dead_code)]
     name: Option<String>,
 }
 
+/// A page of a delta query.  Every page but the last links to the next
+/// one; the last carries the delta link for the next sync.
 #[derive(Debug, Deserialize)]
-struct GraphMessagesResponse {
+struct GraphDeltaPage {
     value: Vec<GraphMessage>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+    #[serde(rename = "@odata.deltaLink")]
+    delta_link: Option<String>,
 }
 
-/// OAuth token data received from dashboard service
-#[derive(Debug, Deserialize, Serialize)]
-struct OAuthTokenRequest {
-    access_token: String,
-    token_type: String,
+/// An event message with its event expanded.
+#[derive(Debug, Deserialize)]
+struct GraphEventMessage {
+    event: Option<invite::GraphEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphAttachment {
+    id: String,
+    name: Option<String>,
+    #[serde(rename = "contentType")]
+    content_type: Option<String>,
+    /// Base64; only returned when fetching a single file attachment.
+    #[serde(rename = "contentBytes")]
+    content_bytes: Option<String>,
 }
 
-/// OAuth token response
-#[derive(Debug, Serialize)]
-struct OAuthTokenResponse {
-    status: String,
-    message: String,
+#[derive(Debug, Deserialize)]
+struct GraphAttachmentsResponse {
+    value: Vec<GraphAttachment>,
 }
 
-impl EmailConfig {
-    fn from_env() -> Option<Self> {
-        let email_config = if let Ok(username) = std::env::var("IMAP_USERNAME") {
-            Some(EmailConfig {
-                username,
-                oauth_token: None,
-            })
-        } else {
-            warn!("Email configuration not found in environment variables");
-            None
-        };
-        email_config
-    }
+#[derive(Debug, Deserialize)]
+struct GraphBatchResponse {
+    responses: Vec<GraphBatchItem>,
+}
+
+/// Outcome of one request of a `$batch`, `id` being its index.
+#[derive(Debug, Deserialize)]
+struct GraphBatchItem {
+    id: String,
+    status: u16,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphFolder {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphFoldersResponse {
+    value: Vec<GraphFolder>,
+}
+
+/// Folders to poll in every connected mailbox; the inbox unless
+/// configured.
+fn configured_folders(config: &common::config::EmailConfig) -> Vec<MailFolder> {
+    let folders = MailFolder::parse_list(&config.folders.join(","));
+    if folders.is_empty() { MailFolder::parse_list("inbox") } else { folders }
 }
 
 /// Shared application state.  Holds the service configuration and an
@@ -110,7 +229,15 @@ impl EmailConfig {
 struct AppState {
     config: ServiceConfig,
     http_client: HttpClient,
-    email_config: Arc<Mutex<Option<EmailConfig>>>,
+    /// Folders polled in every connected mailbox.
+    folders: Arc<Mutex<Vec<MailFolder>>>,
+    sync_state: Arc<Mutex<SyncState>>,
+    /// File the sync state is saved to, if any.
+    sync_state_file: Option<PathBuf>,
+    /// Priority of new cases and tasks from email metadata.
+    priority_mapping: PriorityMapping,
+    /// Tells newsletters and notifications apart from work.
+    noise_rules: NoiseRules,
 }
 
 /// Schema for the incoming email payload.  Many mail providers can be
@@ -127,10 +254,15 @@ struct IncomingEmail {
     subject: Option<String>,
     /// Body of the email.
     body: String,
-    /// Optional case identifier.  If provided, the message will be
-    /// associated with an existing case.  Otherwise a new case will be
-    /// created by the AI agent.
-    case_id: Option<uuid::Uuid>,
+    /// Optional `Message-ID` header of the email.
+    message_id: Option<String>,
+    /// Optional `In-Reply-To` header.  Replies to an email that already
+    /// belongs to a case are added to that case.
+    in_reply_to: Option<String>,
+    /// Optional further headers, such as `List-Unsubscribe`, by name.
+    /// Newsletters and notifications are told apart by them.
+    #[serde(default)]
+    headers: HashMap<String, String>,
 }
 
 /// Checks if an email is work-related based on content and metadata
@@ -195,96 +327,368 @@ fn is_work_related_email(message: &GraphMessage) -> bool {
     (has_work_keywords || is_business_sender) && !is_personal
 }
 
-/// Fetches new emails from Microsoft Graph API and processes them
+/// Office 365 mailboxes users connected, with their tokens, including
+/// disconnected ones.
+async fn connected_mailboxes(state: &AppState) -> anyhow::Result<Vec<EmailAccount>> {
+    let url = format!("{}/api/v1/email-accounts/all", state.config.service_url("persistence"));
+    let accounts: Vec<EmailAccount> = state.http_client.get(&url).await?;
+    Ok(accounts.into_iter().filter(|account| account.provider == EmailProvider::Office365).collect())
+}
+
+/// Fetches new emails from the configured folders of every connected
+/// mailbox and processes them
 async fn fetch_emails(state: &AppState) -> anyhow::Result<()> {
-    let config_guard = state.email_config.lock().await;
-    let config = match config_guard.as_ref() {
-        Some(config) => config.clone(),
+    let accounts = connected_mailboxes(state).await?;
+    let folders = state.folders.lock().await.clone();
+    if !accounts.iter().any(|account| account.is_active) {
+        info!("No mailboxes are connected");
+    }
+
+    for account in accounts.iter().filter(|account| account.is_active) {
+        let Some(oauth_token) = account.oauth_token.as_deref() else {
+            warn!("Mailbox {} has no OAuth token", account.email_address);
+            continue;
+        };
+        if throttle::is_backing_off(&account.health, chrono::Utc::now()) {
+            info!("Mailbox {} is throttled until {:?}, skipping", account.email_address, account.health.throttled_until);
+            continue;
+        }
+        info!("Fetching work-related emails via Microsoft Graph API for mailbox: {}", account.email_address);
+
+        let mut health = account.health.clone();
+        throttle::record_polled(&mut health, chrono::Utc::now());
+        for folder in &folders {
+            let sync = state.sync_state.lock().await.folder(account.id, folder);
+            let (sync, throttled) = match fetch_folder(state, account, folder, sync.clone(), oauth_token).await {
+                Ok(sync) => (sync, false),
+                Err(e) => {
+                    let throttled = e.downcast_ref::<Throttled>();
+                    match throttled {
+                        Some(throttled) => {
+                            warn!("Mailbox {} is throttled: {}", account.email_address, throttled);
+                            throttle::record_throttled(&mut health, throttled, chrono::Utc::now());
+                        }
+                        None => error!("Failed to fetch emails from folder {} of {}: {}", folder, account.email_address, e),
+                    }
+                    (FolderSync { last_error: Some(e.to_string()), ..sync }, throttled.is_some())
+                }
+            };
+            state.sync_state.lock().await.set_folder(account.id, folder, sync);
+            if throttled {
+                // The rest of the mailbox's folders wait too.
+                break;
+            }
+        }
+        if let Err(e) = report_health(state, account.id, &health).await {
+            warn!("Failed to record the health of mailbox {}: {}", account.email_address, e);
+        }
+    }
+    // Disconnected mailboxes resume where they stopped when connected
+    // again; removed ones are forgotten.
+    let account_ids: Vec<Uuid> = accounts.iter().map(|account| account.id).collect();
+    state.sync_state.lock().await.retain_accounts(&account_ids);
+
+    if let Some(path) = &state.sync_state_file {
+        if let Err(e) = state.sync_state.lock().await.save(path).await {
+            warn!("Failed to save sync state to {}: {}", path.display(), e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Records how polling a connected mailbox went.
+async fn report_health(state: &AppState, account_id: Uuid, health: &MailboxHealth) -> anyhow::Result<()> {
+    let url = format!("{}/api/v1/email-accounts/{}/health", state.config.service_url("persistence"), account_id);
+    let _: MailboxHealth = state.http_client.put(&url, health).await?;
+    Ok(())
+}
+
+/// Processes the changes to `folder` of a connected mailbox since its last
+/// sync and returns the updated sync state.
+///
+/// The first sync only processes unread messages, as the old unread poll
+/// did; later syncs process every message added to the folder, including
+/// ones moved in from elsewhere.  Messages already processed, e.g. seen
+/// again after being marked as read, are skipped.  When a message fails
+/// the delta link is not advanced, so the changes are fetched again on the
+/// next sync.
+async fn fetch_folder(
+    state: &AppState,
+    account: &EmailAccount,
+    folder: &MailFolder,
+    mut sync: FolderSync,
+    oauth_token: &str,
+) -> anyhow::Result<FolderSync> {
+    let client = reqwest::Client::new();
+    let graph_url = &state.config.email.graph_url;
+
+    let folder_id = match sync.folder_id.clone() {
+        Some(folder_id) => folder_id,
+        None if folder.is_addressable() => folder.folder.clone(),
         None => {
-            warn!("No email configuration available");
-            return Ok(());
+            let lookup: GraphFoldersResponse = graph_get(&client, &folder.lookup_url(graph_url), oauth_token).await?;
+            lookup
+                .value
+                .into_iter()
+                .next()
+                .map(|found| found.id)
+                .ok_or_else(|| anyhow::anyhow!("Mail folder {} not found", folder))?
         }
     };
-    drop(config_guard);
+    sync.folder_id = Some(folder_id.clone());
 
-    let oauth_token = match &config.oauth_token {
-        Some(token) => token,
-        None => {
-            warn!("No OAuth token available for Microsoft Graph API");
-            return Ok(());
+    let initial_sync = sync.delta_link.is_none();
+    let mut messages = Vec::new();
+    let mut url = sync.delta_link.clone().unwrap_or_else(|| folder.delta_url(graph_url, &folder_id));
+    let delta_link = loop {
+        let page: GraphDeltaPage = graph_get(&client, &url, oauth_token).await?;
+        messages.extend(page.value);
+        match (page.next_link, page.delta_link) {
+            (Some(next_link), _) => url = next_link,
+            (None, Some(delta_link)) => break delta_link,
+            (None, None) => return Err(anyhow::anyhow!("Delta response for folder {} has no next or delta link", folder)),
         }
     };
 
-    info!("Fetching work-related emails via Microsoft Graph API for user: {}", config.username);
+    let messages: Vec<GraphMessage> = {
+        let sync_state = state.sync_state.lock().await;
+        messages
+            .into_iter()
+            .filter(|message| message.removed.is_none())
+            .filter(|message| !(initial_sync && message.is_read))
+            .filter(|message| !sync_state.is_processed(&message.id))
+            .collect()
+    };
+    info!("Found {} new emails in folder {}, filtering for work-related content", messages.len(), folder);
 
-    // Get unread messages from Microsoft Graph API with expanded properties for better filtering
-    let graph_url = "https://graph.microsoft.com/v1.0/me/messages?$filter=isRead eq false&$top=20&$select=id,subject,bodyPreview,body,from,receivedDateTime,importance,categories";
-    
-    let client = reqwest::Client::new();
+    // Threads are processed concurrently, the messages of a thread one
+    // after the other so replies reach their case in order.  A thread stops
+    // at its first failed message: the rest of it is left unprocessed and
+    // fetched again with the failed message on the next sync, so a reply is
+    // never filed ahead of the message it answers.
+    let outcomes: Vec<(String, MessageOutcome)> = stream::iter(email_threads(messages))
+        .map(|thread| async move {
+            let mut outcomes = Vec::with_capacity(thread.len());
+            let thread_len = thread.len();
+            for message in thread {
+                let outcome = handle_graph_message(state, account, folder, &message, oauth_token).await;
+                let failed = matches!(outcome, MessageOutcome::Failed);
+                outcomes.push((message.id, outcome));
+                if failed {
+                    if outcomes.len() < thread_len {
+                        warn!("Leaving {} later messages of the thread until the next sync", thread_len - outcomes.len());
+                    }
+                    break;
+                }
+            }
+            outcomes
+        })
+        .buffer_unordered(state.config.email.processing_concurrency.max(1))
+        .flat_map(stream::iter)
+        .collect()
+        .await;
+
+    let total_emails_checked = outcomes.len();
+    let mut work_emails_processed = 0;
+    let mut filed_for_review = 0;
+    let mut failed = false;
+    for (message_id, outcome) in outcomes {
+        sync.messages_processed += 1;
+        match outcome {
+            MessageOutcome::Skipped => {}
+            MessageOutcome::Filed => {
+                filed_for_review += 1;
+                sync.unread_message_ids.push(message_id);
+            }
+            MessageOutcome::Processed => {
+                work_emails_processed += 1;
+                sync.unread_message_ids.push(message_id);
+            }
+            MessageOutcome::Failed => {
+                work_emails_processed += 1;
+                failed = true;
+            }
+        }
+    }
+
+    info!("Email filtering complete for folder {}: {} work-related emails processed and {} filed for review out of {} total emails checked", 
+        folder, work_emails_processed, filed_for_review, total_emails_checked);
+
+    if !sync.unread_message_ids.is_empty() {
+        sync.unread_message_ids = mark_messages_as_read(&client, graph_url, folder, &sync.unread_message_ids, oauth_token).await;
+        if !sync.unread_message_ids.is_empty() {
+            warn!("{} messages in folder {} are left unread until the next sync", sync.unread_message_ids.len(), folder);
+        }
+    }
+
+    if !failed {
+        sync.delta_link = Some(delta_link);
+    }
+    sync.last_synced_at = Some(chrono::Utc::now());
+    sync.last_error = None;
+    Ok(sync)
+}
+
+/// What became of a fetched message.
+enum MessageOutcome {
+    /// Not work-related.
+    Skipped,
+    /// A newsletter or notification, filed for review.
+    Filed,
+    Processed,
+    Failed,
+}
+
+/// Groups messages by email thread, keeping the order they were fetched
+/// in within a thread and across threads.
+fn email_threads(messages: Vec<GraphMessage>) -> Vec<Vec<GraphMessage>> {
+    let mut threads: Vec<Vec<GraphMessage>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for message in messages {
+        let key = message.conversation_id.clone().unwrap_or_else(|| message.id.clone());
+        match index.get(&key) {
+            Some(&i) => threads[i].push(message),
+            None => {
+                index.insert(key, threads.len());
+                threads.push(vec![message]);
+            }
+        }
+    }
+    threads
+}
+
+/// Files a fetched message as tasks of the mailbox's user unless it isn't
+/// work-related, remembering it as processed unless that fails.
+async fn handle_graph_message(
+    state: &AppState,
+    account: &EmailAccount,
+    folder: &MailFolder,
+    message: &GraphMessage,
+    oauth_token: &str,
+) -> MessageOutcome {
+    let message_url = folder.message_url(&state.config.email.graph_url, &message.id);
+    if let Some(noise) = noise_of(state, message) {
+        info!("Filing {} for review: {}", noise.tag(), message.subject.as_deref().unwrap_or("[No Subject]"));
+        return match file_noise(message, &message_url, noise, state, account.user_id, oauth_token).await {
+            Ok(()) => {
+                state.sync_state.lock().await.mark_processed(&message.id);
+                MessageOutcome::Filed
+            }
+            Err(e) => {
+                error!("Failed to file email message {} for review: {}", message.id, e);
+                MessageOutcome::Failed
+            }
+        };
+    }
+
+    // Apply work-related filtering; meeting invitations are kept.
+    if !message.is_meeting_request() && !is_work_related_email(message) {
+        info!("Skipping non-work-related email: {}", 
+            message.subject.as_deref().unwrap_or("[No Subject]"));
+        state.sync_state.lock().await.mark_processed(&message.id);
+        return MessageOutcome::Skipped;
+    }
+
+    info!("Processing work-related email: {}", 
+        message.subject.as_deref().unwrap_or("[No Subject]"));
+
+    if let Err(e) = process_graph_message(message, &message_url, state, account.user_id, oauth_token).await {
+        error!("Failed to process email message {}: {}", message.id, e);
+        MessageOutcome::Failed
+    } else {
+        info!("Successfully processed work email message {}", message.id);
+        state.sync_state.lock().await.mark_processed(&message.id);
+        MessageOutcome::Processed
+    }
+}
+
+/// GETs a Microsoft Graph resource, turning error statuses into errors that
+/// carry the response body, or [`Throttled`] when Graph asks to slow down.
+async fn graph_get<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &str,
+    oauth_token: &str,
+) -> anyhow::Result<T> {
     let response = client
-        .get(graph_url)
+        .get(url)
         .header("Authorization", format!("Bearer {}", oauth_token))
         .header("Content-Type", "application/json")
+        // Immutable ids stay the same when a message moves between folders,
+        // so processed messages are recognised wherever they turn up.
+        // Event times come in UTC.
+        .header("Prefer", "IdType=\"ImmutableId\", outlook.timezone=\"UTC\", odata.maxpagesize=50")
         .send()
         .await?;
 
+    if let Some(throttled) = Throttled::from_response(response.status(), response.headers()) {
+        return Err(throttled.into());
+    }
     if !response.status().is_success() {
         let status = response.status();
         let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
         return Err(anyhow::anyhow!("Microsoft Graph API request failed with status {}: {}", status, error_text));
     }
 
-    let messages_response: GraphMessagesResponse = response.json().await?;
-    info!("Found {} unread emails, filtering for work-related content", messages_response.value.len());
+    Ok(response.json().await?)
+}
 
-    let mut work_emails_processed = 0;
-    let mut total_emails_checked = 0;
-
-    for message in messages_response.value {
-        total_emails_checked += 1;
-        
-        // Apply work-related filtering
-        if !is_work_related_email(&message) {
-            info!("Skipping non-work-related email: {}", 
-                message.subject.as_deref().unwrap_or("[No Subject]"));
-            continue;
-        }
-        
-        work_emails_processed += 1;
-        info!("Processing work-related email: {}", 
-            message.subject.as_deref().unwrap_or("[No Subject]"));
-        
-        if let Err(e) = process_graph_message(&message, state, oauth_token).await {
-            error!("Failed to process email message {}: {}", message.id, e);
-        } else {
-            info!("Successfully processed work email message {}", message.id);
-            // Mark message as read
-            if let Err(e) = mark_message_as_read(&message.id, oauth_token).await {
-                warn!("Failed to mark message {} as read: {}", message.id, e);
-            }
+/// Processes a single email message and forwards it to the channel service,
+/// for the user `owner_id` who connected the mailbox.  Questions the agent
+/// has about the tasks it created are sent back to the sender as a reply to
+/// the email.  Meeting invitations are filed as meetings instead.
+async fn process_graph_message(
+    message: &GraphMessage,
+    message_url: &str,
+    state: &AppState,
+    owner_id: Uuid,
+    oauth_token: &str,
+) -> anyhow::Result<()> {
+    match graph_invite(message, message_url, oauth_token).await {
+        Ok(Some(invite)) => {
+            let request = InviteRequest {
+                invite,
+                sender_id: message.sender().unwrap_or("unknown@unknown.com").to_string(),
+                email_thread: Some(message.email_thread()),
+                owner_id: Some(owner_id),
+                priority: state.priority_mapping.priority(&message.metadata()),
+                tags: message.categories.clone(),
+            };
+            return file_invite(state, &request).await;
         }
+        Ok(None) => {}
+        Err(e) => warn!("Failed to read the invitation in message {}, processing it as an email: {}", message.id, e),
     }
 
-    info!("Email filtering complete: {} work-related emails processed out of {} total emails checked", 
-        work_emails_processed, total_emails_checked);
+    let message_request = graph_message_request(message, state, owner_id);
+    let channel_url = format!("{}/api/v1/message", state.config.service_url("channel"));
+    let response = state
+        .http_client
+        .post::<MessageRequest, MessageResponse>(&channel_url, &message_request)
+        .await?;
+
+    if !response.questions.is_empty() {
+        let questions = response.questions.iter().map(|question| question.question.as_str()).collect::<Vec<_>>().join("\n");
+        if let Err(e) = reply_to_message(message_url, oauth_token, &questions).await {
+            warn!("Failed to ask follow-up questions about message {}: {}", message.id, e);
+        }
+    }
 
     Ok(())
 }
 
-/// Processes a single email message and forwards it to the channel service.
-async fn process_graph_message(message: &GraphMessage, state: &AppState, _oauth_token: &str) -> anyhow::Result<()> {
-    let sender = message.from
-        .as_ref()
-        .and_then(|from| from.email_address.as_ref())
-        .and_then(|email| email.address.as_ref())
-        .map_or("unknown@unknown.com".to_string(), |v| v.clone());
-    
+/// The request filing a fetched message for the user `owner_id`.
+fn graph_message_request(message: &GraphMessage, state: &AppState, owner_id: Uuid) -> MessageRequest {
     let subject = message.subject.as_deref().unwrap_or("[No Subject]");
     let body = message.body
         .as_ref()
-        .and_then(|b| b.content.as_ref())
-        .or(message.body_preview.as_ref())
-        .map_or("[No body content]".to_string(), |v| v.clone());
+        .and_then(|b| {
+            let html = b.content_type.as_deref().is_some_and(|t| t.eq_ignore_ascii_case("html"));
+            b.content.as_deref().map(|content| body::to_plain_text(content, html, message.subject.as_deref()))
+        })
+        .filter(|text| !text.is_empty())
+        .or_else(|| message.body_preview.clone())
+        .unwrap_or_else(|| "[No body content]".to_string());
     
     let message_text = if !subject.is_empty() && subject != "[No Subject]" {
         format!("{}\n\n{}", subject, body)
@@ -292,61 +696,254 @@ async fn process_graph_message(message: &GraphMessage, state: &AppState, _oauth_
         body
     };
     
-    let message_request = MessageRequest {
+    MessageRequest {
         case_id: None,
         message: message_text,
-        sender_id: sender,
+        sender_id: message.sender().unwrap_or("unknown@unknown.com").to_string(),
         channel: MessageChannel::Email,
-    };
-    
-    let channel_url = format!("{}/api/v1/message", state.config.service_url("channel"));
-    let _response = state
+        email_thread: Some(message.email_thread()),
+        owner_id: Some(owner_id),
+        priority: state.priority_mapping.priority(&message.metadata()),
+        // Outlook categories carry over as tags.
+        tags: message.categories.clone(),
+    }
+}
+
+/// Whether a fetched message is a newsletter or notification to file for
+/// review.  Meeting invitations never are.
+fn noise_of(state: &AppState, message: &GraphMessage) -> Option<Noise> {
+    if state.config.email.noise_action == NoiseAction::Process || message.is_meeting_request() {
+        return None;
+    }
+    let headers = message.internet_message_headers.iter().map(|header| (header.name.as_str(), header.value.as_str()));
+    state.noise_rules.classify(message.sender().unwrap_or_default(), headers)
+}
+
+/// Files a newsletter or notification in the review case of the user
+/// `owner_id`, moving it to the Archive folder if configured to.
+async fn file_noise(
+    message: &GraphMessage,
+    message_url: &str,
+    noise: Noise,
+    state: &AppState,
+    owner_id: Uuid,
+    oauth_token: &str,
+) -> anyhow::Result<()> {
+    let mut request = graph_message_request(message, state, owner_id);
+    request.tags.push(noise.tag().to_string());
+    let channel_url = format!("{}/api/v1/noise", state.config.service_url("channel"));
+    let response = state
         .http_client
-        .post::<MessageRequest, MessageResponse>(&channel_url, &message_request)
+        .post::<MessageRequest, MessageResponse>(&channel_url, &request)
         .await?;
-    
+    info!("Email filed for review under case {}", response.case_id);
+
+    if state.config.email.noise_action == NoiseAction::Archive {
+        if let Err(e) = archive_message(message_url, oauth_token).await {
+            warn!("Failed to archive message {}: {}", message.id, e);
+        }
+    }
     Ok(())
 }
 
-async fn mark_message_as_read(message_id: &str, oauth_token: &str) -> anyhow::Result<()> {
-    let graph_url = format!("https://graph.microsoft.com/v1.0/me/messages/{}", message_id);
-    
+/// Moves a message to the mailbox's Archive folder.
+async fn archive_message(message_url: &str, oauth_token: &str) -> anyhow::Result<()> {
+    let response = reqwest::Client::new()
+        .post(format!("{}/move", message_url))
+        .header("Authorization", format!("Bearer {}", oauth_token))
+        .json(&serde_json::json!({ "destinationId": "archive" }))
+        .send()
+        .await?;
+
+    if let Some(throttled) = Throttled::from_response(response.status(), response.headers()) {
+        return Err(throttled.into());
+    }
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(anyhow::anyhow!("Failed to archive message, status {}: {}", status, error_text));
+    }
+    Ok(())
+}
+
+/// The meeting invitation a message carries: the event of a Graph event
+/// message, or an `.ics` attachment.
+async fn graph_invite(message: &GraphMessage, message_url: &str, oauth_token: &str) -> anyhow::Result<Option<MeetingInvite>> {
     let client = reqwest::Client::new();
-    let update_body = serde_json::json!({
-        "isRead": true
-    });
-    
-    let response = client
-        .patch(&graph_url)
+    if message.is_meeting_request() {
+        let url = format!("{}?$expand=microsoft.graph.eventMessage/event", message_url);
+        let expanded: GraphEventMessage = graph_get(&client, &url, oauth_token).await?;
+        return Ok(expanded.event.and_then(|event| event.to_invite(message.subject.as_deref())));
+    }
+    if !message.has_attachments {
+        return Ok(None);
+    }
+
+    let url = format!("{}/attachments?$select=id,name,contentType", message_url);
+    let attachments: GraphAttachmentsResponse = graph_get(&client, &url, oauth_token).await?;
+    for attachment in attachments.value {
+        if !invite::is_calendar_attachment(attachment.content_type.as_deref(), attachment.name.as_deref()) {
+            continue;
+        }
+        let url = format!("{}/attachments/{}", message_url, attachment.id);
+        let file: GraphAttachment = graph_get(&client, &url, oauth_token).await?;
+        let Some(content) = file.content_bytes else { continue };
+        let ics = String::from_utf8_lossy(&STANDARD.decode(content.trim())?).into_owned();
+        if let Some(invite) = invite::parse_ics(&ics) {
+            return Ok(Some(invite));
+        }
+    }
+    Ok(None)
+}
+
+/// Sends a meeting invitation to the channel service to be filed as a
+/// Meeting task.
+async fn file_invite(state: &AppState, request: &InviteRequest) -> anyhow::Result<()> {
+    info!("Filing invitation to {:?} at {}", request.invite.title, request.invite.starts_at);
+    let channel_url = format!("{}/api/v1/invites", state.config.service_url("channel"));
+    let response = state
+        .http_client
+        .post::<InviteRequest, MessageResponse>(&channel_url, request)
+        .await?;
+    info!("Invitation filed under case {}", response.case_id);
+    Ok(())
+}
+
+/// Replies to the sender of a message, quoting it below `comment`.
+async fn reply_to_message(message_url: &str, oauth_token: &str, comment: &str) -> anyhow::Result<()> {
+    let response = reqwest::Client::new()
+        .post(format!("{}/reply", message_url))
         .header("Authorization", format!("Bearer {}", oauth_token))
-        .header("Content-Type", "application/json")
-        .json(&update_body)
+        .json(&serde_json::json!({ "comment": comment }))
         .send()
         .await?;
 
     if !response.status().is_success() {
         let status = response.status();
         let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(anyhow::anyhow!("Failed to mark message as read, status {}: {}", status, error_text));
+        return Err(anyhow::anyhow!("Failed to reply to message, status {}: {}", status, error_text));
     }
     Ok(())
 }
 
-/// Background task that periodically fetches emails
-async fn email_polling_task(state: Arc<AppState>) {
-    let poll_interval = 60; // Default 60 seconds polling interval
-    
-    info!("Starting email polling every {} seconds", poll_interval);
-    
-    let mut interval = tokio::time::interval(Duration::from_secs(poll_interval));
-    
-    loop {
-        interval.tick().await;
-        
-        if let Err(e) = fetch_emails(&state).await {
-            error!("Email fetching failed: {}", e);
+/// Marks processed messages of `folder` as read, in `$batch` requests of
+/// up to [`MAX_BATCH_REQUESTS`] each, and returns the ids of those that
+/// couldn't be, to be retried.  Messages that are gone count as done.
+async fn mark_messages_as_read(
+    client: &reqwest::Client,
+    graph_url: &str,
+    folder: &MailFolder,
+    message_ids: &[String],
+    oauth_token: &str,
+) -> Vec<String> {
+    let mut unread = Vec::new();
+    for chunk in message_ids.chunks(MAX_BATCH_REQUESTS) {
+        match mark_batch_as_read(client, graph_url, folder, chunk, oauth_token).await {
+            Ok(failed) => unread.extend(failed),
+            Err(e) => {
+                warn!("Failed to mark {} messages as read: {}", chunk.len(), e);
+                unread.extend(chunk.iter().cloned());
+            }
         }
     }
+    unread
+}
+
+/// Sends one `$batch` marking `message_ids` as read and returns the ids
+/// whose request failed.
+async fn mark_batch_as_read(
+    client: &reqwest::Client,
+    graph_url: &str,
+    folder: &MailFolder,
+    message_ids: &[String],
+    oauth_token: &str,
+) -> anyhow::Result<Vec<String>> {
+    let requests: Vec<serde_json::Value> = message_ids
+        .iter()
+        .enumerate()
+        .map(|(index, message_id)| {
+            serde_json::json!({
+                "id": index.to_string(),
+                "method": "PATCH",
+                "url": folder.message_path(message_id),
+                "headers": {
+                    "Content-Type": "application/json",
+                    "Prefer": "IdType=\"ImmutableId\""
+                },
+                "body": { "isRead": true }
+            })
+        })
+        .collect();
+
+    let response = client
+        .post(format!("{}/$batch", graph_url.trim_end_matches('/')))
+        .header("Authorization", format!("Bearer {}", oauth_token))
+        .json(&serde_json::json!({ "requests": requests }))
+        .send()
+        .await?;
+
+    if let Some(throttled) = Throttled::from_response(response.status(), response.headers()) {
+        return Err(throttled.into());
+    }
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(anyhow::anyhow!("Failed to mark messages as read, status {}: {}", status, error_text));
+    }
+
+    let batch: GraphBatchResponse = response.json().await?;
+    let done: Vec<usize> = batch
+        .responses
+        .iter()
+        .filter(|item| (200..300).contains(&item.status) || item.status == 404)
+        .filter_map(|item| item.id.parse().ok())
+        .collect();
+    Ok(message_ids
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !done.contains(index))
+        .map(|(_, message_id)| message_id.clone())
+        .collect())
+}
+
+/// Fetches email through the recurring `email.poll` job, so only one
+/// instance polls at a time and a missed poll is caught up after a
+/// restart.  A reloaded polling interval reschedules the job; a fetch in
+/// progress finishes first.
+fn start_email_polling(state: Arc<AppState>, live: LiveConfig) {
+    let queue: Arc<dyn JobQueue> = Arc::new(HttpJobQueue::new(state.config.clone()));
+    let mut poll_interval = live.current().email.poll_interval_secs;
+    info!("Polling email every {} seconds", poll_interval);
+
+    let service_name = state.config.service_name.clone();
+    Worker::new(queue.clone(), &service_name, &state.config.jobs)
+        .recurring(POLL_JOB, Duration::from_secs(poll_interval), move |_| {
+            let state = state.clone();
+            async move { fetch_emails(&state).await }
+        })
+        .start();
+
+    let mut changes = live.subscribe();
+    tokio::spawn(async move {
+        while changes.changed().await.is_ok() {
+            let secs = changes.borrow_and_update().email.poll_interval_secs;
+            if secs == poll_interval {
+                continue;
+            }
+            let request = ScheduleJobRequest {
+                kind: POLL_JOB.to_string(),
+                interval_secs: secs.max(1) as i64,
+                payload: serde_json::Value::Null,
+            };
+            match queue.schedule(&request).await {
+                Ok(_) => {
+                    poll_interval = secs;
+                    info!("Email polling now every {} seconds", poll_interval);
+                }
+                Err(e) => warn!("Could not change the email polling interval: {}", e),
+            }
+        }
+    });
 }
 
 #[tokio::main]
@@ -357,53 +954,55 @@ async fn main() -> anyhow::Result<()> {
     // Initialise configuration.  The service is named `email-collector-service`
     // and defaults to port 8006.  Additional options (such as
     // CHANNEL_SERVICE_URL) can be supplied via the environment.
-    let config = ServiceConfig::from_env("email-collector-service", 8006);
+    let config = ServiceConfig::load("email-collector-service", 8006).await?;
 
     // Set up tracing/structured logging.  Respect RUST_LOG if provided.
-    tracing_subscriber::fmt()
-        .with_env_filter(&config.log_level)
-        .init();
+    let live = LiveConfig::init(&config);
 
-    // Initialize email configuration from environment variables
-    let email_config = EmailConfig::from_env();
-    
-    if email_config.is_some() {
-        info!("Email fetching enabled with Microsoft Graph API configuration");
-    } else {
-        info!("Email fetching disabled - no email configuration found");
-        info!("To enable email fetching, set: IMAP_USERNAME");
-    }
+    config.start_refresh();
+
+    let folders = configured_folders(&config.email);
+    info!("Polling the folders {:?} of connected mailboxes", folders);
+
+    // Delta links and processed message ids survive restarts when a state
+    // file is configured.
+    let sync_state_file = std::env::var("GRAPH_SYNC_STATE_FILE")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+        .map(PathBuf::from);
+    let sync_state = match &sync_state_file {
+        Some(path) => SyncState::load(path).await,
+        None => SyncState::default(),
+    };
 
     let state = AppState {
         config: config.clone(),
-        http_client: HttpClient::new(),
-        email_config: Arc::new(Mutex::new(email_config)),
+        http_client: HttpClient::for_services(&config),
+        folders: Arc::new(Mutex::new(folders)),
+        sync_state: Arc::new(Mutex::new(sync_state)),
+        sync_state_file,
+        priority_mapping: PriorityMapping::parse_list(&config.email.priority_mapping),
+        noise_rules: NoiseRules::parse_list(&config.email.noise_senders),
     };
 
     let state_arc = Arc::new(state);
 
-    // Start email polling task if email configuration is available
-    {
-        let email_config_guard = state_arc.email_config.lock().await;
-        if email_config_guard.is_some() {
-            let polling_state = state_arc.clone();
-            tokio::spawn(async move {
-                email_polling_task(polling_state).await;
-            });
-        }
-    }
+    start_email_polling(state_arc.clone(), live.clone());
 
     // Build the router.  Expose a health endpoint and an email ingestion
     // endpoint.  Attach CORS and tracing layers for better observability.
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/api/v1/email", post(handle_incoming_email))
-        .route("/api/v1/oauth/token", post(handle_oauth_token))
+        .route("/api/v1/email/send", post(send_email))
+        .route("/api/v1/folders", get(list_folders).put(update_folders))
+        .route("/api/v1/metrics/throttling", get(throttling_metrics))
         .with_state(state_arc)
+        .merge(live.router())
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
+                .layer(logging::request_layers(&config.service_name))
+                .layer(config.cors.layer()),
         );
 
     // Bind and serve the HTTP server.
@@ -422,37 +1021,85 @@ async fn health_check() -> Json<HealthResponse> {
 }
 
 /// Handler for incoming email webhooks.  Constructs a [`MessageRequest`]
-/// with the email contents and dispatches it to the channel service.
-#[instrument(skip(state))]
+/// with the email contents and dispatches it to the channel service.  Only
+/// services may call it: the request is forwarded with the internal
+/// service token.
+#[instrument(skip(state, headers))]
 async fn handle_incoming_email(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(payload): Json<IncomingEmail>,
 ) -> ServiceResult<Json<MessageResponse>> {
+    check_internal(&headers, &state.config.secret_values)?;
     info!("Received incoming email: sender={}, subject={:?}", payload.sender, payload.subject);
 
+    // Meeting invitations carry their calendar part in the raw body; they
+    // are filed as meetings rather than extracted from.
+    if let Some(invite) = invite::has_calendar(&payload.body).then(|| invite::parse_ics(&payload.body)).flatten() {
+        let request = InviteRequest {
+            invite,
+            sender_id: payload.sender,
+            email_thread: Some(EmailThread {
+                message_id: payload.message_id,
+                conversation_id: None,
+                in_reply_to: payload.in_reply_to,
+            }),
+            owner_id: None,
+            priority: None,
+            tags: Vec::new(),
+        };
+        let channel_url = format!("{}/api/v1/invites", state.config.service_url("channel"));
+        let response = state
+            .http_client
+            .post::<InviteRequest, MessageResponse>(&channel_url, &request)
+            .await
+            .map_err(common::ServiceError::HttpClient)?;
+        info!("Channel service filed invitation: case_id={}", response.case_id);
+        return Ok(Json(response));
+    }
+
     // Combine subject and body into a single message.  If a subject is
     // provided, prefix it to the body separated by a newline.
+    let body = body::to_plain_text(&payload.body, body::looks_like_html(&payload.body), payload.subject.as_deref());
     let message = match payload.subject {
-        Some(ref subject) if !subject.is_empty() => format!("{}\n\n{}", subject, payload.body),
-        _ => payload.body.clone(),
+        Some(ref subject) if !subject.is_empty() => format!("{}\n\n{}", subject, body),
+        _ => body,
     };
 
-    // Construct the message request.  Pass through the optional case_id if
-    // provided by the caller.  This allows email replies to an existing
-    // case to be threaded correctly.
-    let message_request = MessageRequest {
-        case_id: payload.case_id,
+    // Construct the message request.  The case is never taken from the
+    // payload, which comes from outside; replies find their case through
+    // the email thread.
+    let mut message_request = MessageRequest {
+        case_id: None,
         message,
         sender_id: payload.sender,
         channel: MessageChannel::Email,
+        email_thread: Some(EmailThread {
+            message_id: payload.message_id,
+            conversation_id: None,
+            in_reply_to: payload.in_reply_to,
+        }),
+        owner_id: None,
+        priority: None,
+        tags: Vec::new(),
     };
 
+    // Newsletters and notifications are filed for review instead.
+    let headers = payload.headers.iter().map(|(name, value)| (name.as_str(), value.as_str()));
+    let noise = (state.config.email.noise_action != NoiseAction::Process)
+        .then(|| state.noise_rules.classify(&message_request.sender_id, headers))
+        .flatten();
+    if let Some(noise) = noise {
+        message_request.tags.push(noise.tag().to_string());
+    }
+
     // Determine the URL for the channel service.  The `service_url` helper
     // resolves service names to full URLs, allowing overrides via
     // environment variables (see ServiceConfig::service_url in shared/common).
     let channel_url = format!(
-        "{}/api/v1/message",
-        state.config.service_url("channel")
+        "{}/api/v1/{}",
+        state.config.service_url("channel"),
+        if noise.is_some() { "noise" } else { "message" }
     );
 
     // Forward the request to the channel service.  If the downstream call
@@ -462,35 +1109,285 @@ async fn handle_incoming_email(
         .http_client
         .post::<MessageRequest, MessageResponse>(&channel_url, &message_request)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     info!("Channel service responded for email: case_id={}", response.case_id);
     Ok(Json(response))
 }
 
-/// Handle OAuth token from dashboard service
-#[instrument(skip(state))]
-async fn handle_oauth_token(
+/// Sends an email from the mailbox the request's user connected, for other
+/// services such as the AI agent's weekly case summaries.  Only services
+/// may call it, and the email only goes to that user's own addresses, so
+/// it can't be used to mail anyone else.
+#[instrument(skip(state, headers, request), fields(to = %request.to))]
+async fn send_email(
     State(state): State<Arc<AppState>>,
-    Json(token_request): Json<OAuthTokenRequest>,
-) -> ServiceResult<Json<OAuthTokenResponse>> {
-    info!("Received OAuth token from dashboard service");
-    
-    // Update the email configuration with the OAuth token
-    {
-        let mut email_config_guard = state.email_config.lock().await;
-        if let Some(ref mut config) = email_config_guard.as_mut() {
-            config.oauth_token = Some(token_request.access_token.clone());
-            info!("OAuth token updated in email configuration for user: {}", config.username);
-        } else {
-            warn!("No email configuration found to update with OAuth token");
+    headers: HeaderMap,
+    Json(request): Json<SendEmailRequest>,
+) -> ServiceResult<StatusCode> {
+    check_internal(&headers, &state.config.secret_values)?;
+    let user_id = request
+        .user_id
+        .ok_or_else(|| common::ServiceError::BadRequest("The user to send as is required".to_string()))?;
+    let user_url = format!("{}/api/v1/users/{}", state.config.service_url("persistence"), user_id);
+    let user: UserProfile = state.http_client.get(&user_url).await?;
+    let mailboxes: Vec<EmailAccount> = connected_mailboxes(&state)
+        .await
+        .map_err(common::ServiceError::Internal)?
+        .into_iter()
+        .filter(|account| account.user_id == user_id && account.is_active)
+        .collect();
+
+    let to = request.to.trim();
+    let own_address = user.email.eq_ignore_ascii_case(to)
+        || mailboxes.iter().any(|account| account.email_address.eq_ignore_ascii_case(to));
+    if !own_address {
+        return Err(common::ServiceError::BadRequest("Emails only go to the user's own addresses".to_string()));
+    }
+    let oauth_token = mailboxes
+        .into_iter()
+        .find_map(|account| account.oauth_token)
+        .ok_or_else(|| common::ServiceError::BadRequest("No mailbox is connected".to_string()))?;
+
+    let url = format!("{}/me/sendMail", state.config.email.graph_url.trim_end_matches('/'));
+    let message = serde_json::json!({
+        "message": {
+            "subject": request.subject,
+            "body": { "contentType": "Text", "content": request.body },
+            "toRecipients": [{ "emailAddress": { "address": request.to } }]
+        },
+        "saveToSentItems": false
+    });
+    let response = reqwest::Client::new()
+        .post(&url)
+        .header("Authorization", format!("Bearer {}", oauth_token))
+        .json(&message)
+        .send()
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(common::ServiceError::Internal(anyhow::anyhow!(
+            "Failed to send email, status {}: {}",
+            status,
+            error_text
+        )));
+    }
+    info!("Sent email to {}", request.to);
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Folders to poll, replacing the configured list.
+#[derive(Debug, Deserialize)]
+struct UpdateFoldersRequest {
+    /// Entries such as `inbox` or `support@example.com/inbox`.
+    folders: Vec<String>,
+}
+
+/// Lists the polled folders of each connected mailbox with their sync
+/// state.  Only for other services.
+#[instrument(skip(state, headers))]
+async fn list_folders(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> ServiceResult<Json<Vec<FolderStatus>>> {
+    check_internal(&headers, &state.config.secret_values)?;
+    Ok(Json(folder_statuses(&state).await))
+}
+
+/// Replaces the folders polled in every connected mailbox.  Sync state of
+/// folders that stay in the list is kept.  Only for other services.
+#[instrument(skip(state, headers))]
+async fn update_folders(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<UpdateFoldersRequest>,
+) -> ServiceResult<Json<Vec<FolderStatus>>> {
+    check_internal(&headers, &state.config.secret_values)?;
+    let folders: Vec<MailFolder> = request.folders.iter().filter_map(|spec| MailFolder::parse(spec)).collect();
+    if folders.is_empty() {
+        return Err(common::ServiceError::BadRequest("At least one folder is required".to_string()));
+    }
+
+    info!("Polling folders: {:?}", folders);
+    *state.folders.lock().await = folders.clone();
+    state.sync_state.lock().await.retain_folders(&folders);
+
+    Ok(Json(folder_statuses(&state).await))
+}
+
+/// Throttling of the connected mailboxes by Microsoft Graph.  Only for
+/// other services.
+#[instrument(skip(state, headers))]
+async fn throttling_metrics(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> ServiceResult<Json<ThrottleMetrics>> {
+    check_internal(&headers, &state.config.secret_values)?;
+    let accounts = connected_mailboxes(&state).await.map_err(common::ServiceError::Internal)?;
+    Ok(Json(ThrottleMetrics::from_accounts(&accounts, chrono::Utc::now())))
+}
+
+async fn folder_statuses(state: &AppState) -> Vec<FolderStatus> {
+    state.sync_state.lock().await.statuses()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::testing::{MockTransport, TEST_INTERNAL_TOKEN};
+    use reqwest::Method;
+    use serde_json::json;
+
+    fn state(mock: &Arc<MockTransport>) -> Arc<AppState> {
+        Arc::new(AppState {
+            config: ServiceConfig::for_tests("email-collector-service"),
+            http_client: mock.client(),
+            folders: Arc::new(Mutex::new(Vec::new())),
+            sync_state: Arc::new(Mutex::new(SyncState::default())),
+            sync_state_file: None,
+            priority_mapping: PriorityMapping::parse_list(&[]),
+            noise_rules: NoiseRules::parse_list(&[]),
+        })
+    }
+
+    fn internal() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(common::auth::INTERNAL_TOKEN_HEADER, TEST_INTERNAL_TOKEN.parse().unwrap());
+        headers
+    }
+
+    fn email_to(to: &str) -> SendEmailRequest {
+        SendEmailRequest {
+            to: to.to_string(),
+            subject: "Weekly summary".to_string(),
+            body: "Two tasks done.".to_string(),
+            user_id: Some(Uuid::nil()),
         }
     }
-    
-    info!("OAuth token received and stored for email authentication");
-    
-    Ok(Json(OAuthTokenResponse {
-        status: "success".to_string(),
-        message: "OAuth token received and configured for email access".to_string(),
-    }))
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn only_services_send_email() {
+        let mock = MockTransport::new();
+        let sent = send_email(State(state(&mock)), HeaderMap::new(), Json(email_to("owner@company.com"))).await;
+        assert!(matches!(sent, Err(common::ServiceError::Unauthorized(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn email_only_goes_to_the_users_own_addresses() {
+        let mock = MockTransport::new();
+        mock.reply(
+            Method::GET,
+            "/api/v1/users/*",
+            json!({
+                "id": Uuid::nil(),
+                "email": "owner@company.com",
+                "full_name": "Case Owner",
+                "organization": null,
+                "is_active": true,
+                "created_at": chrono::Utc::now(),
+                "last_login": null,
+            }),
+        )
+        .reply(Method::GET, "/api/v1/email-accounts/all", json!([]));
+
+        let sent = send_email(State(state(&mock)), internal(), Json(email_to("someone@elsewhere.com"))).await;
+        assert!(matches!(sent, Err(common::ServiceError::BadRequest(_))));
+        // Their own address gets as far as looking for a mailbox to send from.
+        let sent = send_email(State(state(&mock)), internal(), Json(email_to("Owner@Company.com"))).await;
+        assert!(matches!(sent, Err(common::ServiceError::BadRequest(message)) if message == "No mailbox is connected"));
+    }
+
+    fn incoming(case_id: Uuid) -> Json<IncomingEmail> {
+        Json(
+            serde_json::from_value(json!({
+                "sender": "client@example.com",
+                "subject": "Contract renewal",
+                "body": "Please send the renewal terms by Friday.",
+                "case_id": case_id,
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn only_services_post_incoming_email_and_never_into_a_chosen_case() {
+        let mock = MockTransport::new();
+        let case_id = Uuid::new_v4();
+        mock.reply(
+            Method::POST,
+            "/api/v1/message",
+            json!({ "case_id": Uuid::new_v4(), "response": "Filed", "actions_taken": [], "tasks_created": [], "tasks_updated": [] }),
+        );
+
+        let posted = handle_incoming_email(State(state(&mock)), HeaderMap::new(), incoming(case_id)).await;
+        assert!(matches!(posted, Err(common::ServiceError::Unauthorized(_))));
+        assert!(mock.requests().is_empty());
+
+        let Json(filed) = handle_incoming_email(State(state(&mock)), internal(), incoming(case_id)).await.unwrap();
+        assert_ne!(filed.case_id, case_id);
+        let forwarded = mock.requests_to(Method::POST, "/api/v1/message");
+        assert_eq!(forwarded.len(), 1);
+        assert_eq!(forwarded[0].body["case_id"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn only_services_see_or_change_the_polled_folders() {
+        let state = state(&MockTransport::new());
+        let request = || Json(UpdateFoldersRequest { folders: vec!["Projects".to_string()] });
+
+        let changed = update_folders(State(state.clone()), HeaderMap::new(), request()).await;
+        assert!(matches!(changed, Err(common::ServiceError::Unauthorized(_))));
+        assert!(state.folders.lock().await.is_empty());
+        let listed = list_folders(State(state.clone()), HeaderMap::new()).await;
+        assert!(matches!(listed, Err(common::ServiceError::Unauthorized(_))));
+
+        let Json(changed) = update_folders(State(state.clone()), internal(), request()).await.unwrap();
+        assert_eq!(state.folders.lock().await.len(), 1);
+        let Json(listed) = list_folders(State(state), internal()).await.unwrap();
+        assert_eq!(listed.len(), changed.len());
+    }
+
+    #[tokio::test]
+    async fn only_services_see_the_throttling_metrics() {
+        let mock = MockTransport::new();
+        mock.reply(Method::GET, "/api/v1/email-accounts/all", json!([]));
+
+        let metrics = throttling_metrics(State(state(&mock)), HeaderMap::new()).await;
+        assert!(matches!(metrics, Err(common::ServiceError::Unauthorized(_))));
+        assert!(mock.requests().is_empty());
+        let Json(metrics) = throttling_metrics(State(state(&mock)), internal()).await.unwrap();
+        assert_eq!(metrics.mailboxes_throttled, 0);
+    }
+
+    fn message(id: &str, conversation_id: Option<&str>) -> GraphMessage {
+        serde_json::from_value(json!({ "id": id, "conversationId": conversation_id })).unwrap()
+    }
+
+    fn ids(threads: &[Vec<GraphMessage>]) -> Vec<Vec<&str>> {
+        threads.iter().map(|thread| thread.iter().map(|message| message.id.as_str()).collect()).collect()
+    }
+
+    #[test]
+    fn messages_are_grouped_by_thread_in_fetch_order() {
+        let threads = email_threads(vec![
+            message("a1", Some("a")),
+            message("b1", Some("b")),
+            message("a2", Some("a")),
+            message("c1", Some("c")),
+            message("b2", Some("b")),
+            message("a3", Some("a")),
+        ]);
+        assert_eq!(ids(&threads), [vec!["a1", "a2", "a3"], vec!["b1", "b2"], vec!["c1"]]);
+    }
+
+    #[test]
+    fn messages_without_a_conversation_are_threads_of_their_own() {
+        let threads = email_threads(vec![message("x", None), message("a1", Some("a")), message("y", None)]);
+        assert_eq!(ids(&threads), [vec!["x"], vec!["a1"], vec!["y"]]);
+        assert!(email_threads(Vec::new()).is_empty());
+    }
+}
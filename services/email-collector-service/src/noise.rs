@@ -0,0 +1,135 @@
+//! Newsletters and notifications.
+//!
+//! Mailing lists mark their email with `List-Unsubscribe`, `List-Id` or
+//! `Precedence: bulk`, and automated mail with `Auto-Submitted`.  Email
+//! from known senders, by default `noreply@` style addresses, counts as a
+//! notification too.  Such email isn't work to extract tasks from, but it
+//! isn't dropped either: it is filed for review (see
+//! [`common::config::NoiseAction`]).
+
+/// Senders used when none are configured.
+const DEFAULT_SENDERS: &[&str] = &["noreply@", "no-reply@", "donotreply@", "do-not-reply@", "notifications@", "mailer-daemon@"];
+
+/// What kind of noise an email is, which is also the tag it is filed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Noise {
+    Newsletter,
+    Notification,
+}
+
+impl Noise {
+    pub fn tag(self) -> &'static str {
+        match self {
+            Noise::Newsletter => "newsletter",
+            Noise::Notification => "notification",
+        }
+    }
+}
+
+/// A sender whose email is noise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SenderRule {
+    /// `news@vendor.com`
+    Address(String),
+    /// `@vendor.com`, also matching its subdomains.
+    Domain(String),
+    /// `noreply@`
+    LocalPart(String),
+}
+
+impl SenderRule {
+    fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim().to_lowercase();
+        let (local, domain) = spec.split_once('@')?;
+        match (local.is_empty(), domain.is_empty()) {
+            (false, false) => Some(SenderRule::Address(spec.clone())),
+            (true, false) => Some(SenderRule::Domain(domain.to_string())),
+            (false, true) => Some(SenderRule::LocalPart(local.to_string())),
+            (true, true) => None,
+        }
+    }
+
+    /// `sender` is lowercase.
+    fn matches(&self, sender: &str) -> bool {
+        let Some((local, domain)) = sender.rsplit_once('@') else { return false };
+        match self {
+            SenderRule::Address(address) => sender == address,
+            SenderRule::Domain(rule) => domain == rule || domain.strip_suffix(rule.as_str()).is_some_and(|sub| sub.ends_with('.')),
+            SenderRule::LocalPart(rule) => local == rule,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NoiseRules {
+    senders: Vec<SenderRule>,
+}
+
+impl NoiseRules {
+    /// Parses the configured senders, skipping malformed ones, or the
+    /// defaults when none are configured.
+    pub fn parse_list(specs: &[String]) -> Self {
+        let specs: Vec<&str> = specs.iter().map(String::as_str).filter(|spec| !spec.trim().is_empty()).collect();
+        if specs.is_empty() {
+            return NoiseRules { senders: DEFAULT_SENDERS.iter().filter_map(|spec| SenderRule::parse(spec)).collect() };
+        }
+        let senders = specs
+            .into_iter()
+            .filter_map(|spec| {
+                let rule = SenderRule::parse(spec);
+                if rule.is_none() {
+                    tracing::warn!("Ignoring malformed noise sender {:?}", spec);
+                }
+                rule
+            })
+            .collect();
+        NoiseRules { senders }
+    }
+
+    /// Whether an email from `sender` with the given headers is a
+    /// newsletter or a notification.
+    pub fn classify<'a>(&self, sender: &str, headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<Noise> {
+        let mut noise = None;
+        for (name, value) in headers {
+            let value = value.trim().to_lowercase();
+            match name.trim().to_lowercase().as_str() {
+                "list-unsubscribe" | "list-id" => return Some(Noise::Newsletter),
+                "precedence" if matches!(value.as_str(), "bulk" | "list" | "junk") => return Some(Noise::Newsletter),
+                "auto-submitted" if !value.is_empty() && value != "no" => noise = Some(Noise::Notification),
+                _ => {}
+            }
+        }
+        let sender = sender.trim().to_lowercase();
+        noise.or_else(|| self.senders.iter().any(|rule| rule.matches(&sender)).then_some(Noise::Notification))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mailing_list_headers_mark_newsletters() {
+        let rules = NoiseRules::parse_list(&[]);
+        let unsubscribe = [("List-Unsubscribe", "<mailto:leave@lists.vendor.com>")];
+        assert_eq!(rules.classify("news@vendor.com", unsubscribe), Some(Noise::Newsletter));
+        assert_eq!(rules.classify("noreply@vendor.com", [("Precedence", "Bulk")]), Some(Noise::Newsletter));
+        assert_eq!(rules.classify("jira@company.com", [("Auto-Submitted", "auto-generated")]), Some(Noise::Notification));
+        assert_eq!(rules.classify("ana@company.com", [("Auto-Submitted", "no"), ("In-Reply-To", "<1@company.com>")]), None);
+    }
+
+    #[test]
+    fn known_senders_mark_notifications() {
+        let defaults = NoiseRules::parse_list(&[]);
+        assert_eq!(defaults.classify("No-Reply@github.com", []), Some(Noise::Notification));
+        assert_eq!(defaults.classify("ana@company.com", []), None);
+
+        let specs = ["@alerts.vendor.com", "digest@company.com", "bogus"].map(str::to_string);
+        let rules = NoiseRules::parse_list(&specs);
+        assert_eq!(rules.senders.len(), 2);
+        assert_eq!(rules.classify("ops@eu.alerts.vendor.com", []), Some(Noise::Notification));
+        assert_eq!(rules.classify("ops@notalerts.vendor.com", []), None);
+        assert_eq!(rules.classify("digest@company.com", []), Some(Noise::Notification));
+        assert_eq!(rules.classify("noreply@github.com", []), None);
+    }
+}
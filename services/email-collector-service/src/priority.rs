@@ -0,0 +1,125 @@
+//! Priority of polled email from its Microsoft Graph metadata.
+//!
+//! Rules map an email's importance (`low`, `normal`, `high`), flag status
+//! (`notFlagged`, `flagged`, `complete`) or a category to a priority, e.g.
+//! `importance:high=High`, `flag:flagged=High` or `category:Red
+//! category=Critical`.  A new case for the email starts with the highest
+//! priority its rules give, and its tasks get at least that.  Without
+//! configured rules, [`DEFAULT_RULES`] apply.
+
+use models::Priority;
+
+/// Rules used when none are configured.
+const DEFAULT_RULES: &[&str] = &["importance:high=High", "importance:low=Low", "flag:flagged=High"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Importance,
+    Flag,
+    Category,
+}
+
+/// Gives emails whose `field` is `value` a priority.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PriorityRule {
+    field: Field,
+    /// Lowercase; Graph values match regardless of case.
+    value: String,
+    priority: Priority,
+}
+
+impl PriorityRule {
+    /// Parses `field:value=Priority`.  Returns `None` for anything else.
+    fn parse(spec: &str) -> Option<Self> {
+        let (matcher, priority) = spec.trim().rsplit_once('=')?;
+        let (field, value) = matcher.split_once(':')?;
+        let field = match field.trim().to_lowercase().as_str() {
+            "importance" => Field::Importance,
+            "flag" => Field::Flag,
+            "category" => Field::Category,
+            _ => return None,
+        };
+        let priority = match priority.trim().to_lowercase().as_str() {
+            "low" => Priority::Low,
+            "medium" => Priority::Medium,
+            "high" => Priority::High,
+            "critical" => Priority::Critical,
+            _ => return None,
+        };
+        let value = value.trim().to_lowercase();
+        (!value.is_empty()).then_some(PriorityRule { field, value, priority })
+    }
+}
+
+/// The metadata of an email rules look at.
+#[derive(Debug, Default)]
+pub struct EmailMetadata<'a> {
+    pub importance: Option<&'a str>,
+    pub flag_status: Option<&'a str>,
+    pub categories: &'a [String],
+}
+
+#[derive(Debug, Clone)]
+pub struct PriorityMapping {
+    rules: Vec<PriorityRule>,
+}
+
+impl PriorityMapping {
+    /// Parses the configured rules, skipping malformed ones, or the
+    /// defaults when none are configured.
+    pub fn parse_list(specs: &[String]) -> Self {
+        let specs: Vec<&str> = specs.iter().map(String::as_str).filter(|spec| !spec.trim().is_empty()).collect();
+        if specs.is_empty() {
+            return PriorityMapping { rules: DEFAULT_RULES.iter().filter_map(|spec| PriorityRule::parse(spec)).collect() };
+        }
+        let rules = specs
+            .into_iter()
+            .filter_map(|spec| {
+                let rule = PriorityRule::parse(spec);
+                if rule.is_none() {
+                    tracing::warn!("Ignoring malformed priority rule {:?}", spec);
+                }
+                rule
+            })
+            .collect();
+        PriorityMapping { rules }
+    }
+
+    /// The highest priority the rules give an email, if any matches.
+    pub fn priority(&self, email: &EmailMetadata<'_>) -> Option<Priority> {
+        let matches = |rule: &&PriorityRule| match rule.field {
+            Field::Importance => email.importance.is_some_and(|importance| importance.eq_ignore_ascii_case(&rule.value)),
+            Field::Flag => email.flag_status.is_some_and(|status| status.eq_ignore_ascii_case(&rule.value)),
+            Field::Category => email.categories.iter().any(|category| category.trim().eq_ignore_ascii_case(&rule.value)),
+        };
+        self.rules.iter().filter(matches).map(|rule| rule.priority.clone()).max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_follow_importance_and_flags() {
+        let mapping = PriorityMapping::parse_list(&[]);
+        let email = |importance, flag_status| EmailMetadata { importance, flag_status, categories: &[] };
+        assert_eq!(mapping.priority(&email(Some("high"), None)), Some(Priority::High));
+        assert_eq!(mapping.priority(&email(Some("low"), Some("notFlagged"))), Some(Priority::Low));
+        assert_eq!(mapping.priority(&email(Some("low"), Some("flagged"))), Some(Priority::High));
+        assert_eq!(mapping.priority(&email(Some("normal"), Some("complete"))), None);
+    }
+
+    #[test]
+    fn the_highest_configured_priority_wins() {
+        let specs = ["importance:high=High", "category:Red category=Critical", "bogus", "flag:flagged=Urgent"]
+            .map(str::to_string);
+        let mapping = PriorityMapping::parse_list(&specs);
+        assert_eq!(mapping.rules.len(), 2);
+
+        let categories = ["red category".to_string()];
+        let email = EmailMetadata { importance: Some("High"), flag_status: Some("flagged"), categories: &categories };
+        assert_eq!(mapping.priority(&email), Some(Priority::Critical));
+        assert_eq!(mapping.priority(&EmailMetadata { flag_status: Some("flagged"), ..Default::default() }), None);
+    }
+}
@@ -0,0 +1,173 @@
+//! Microsoft Graph throttling.
+//!
+//! Graph answers `429 Too Many Requests` or `503 Service Unavailable` when
+//! a mailbox is read too often, usually with a `Retry-After` header saying
+//! how long to wait.  A throttled mailbox isn't polled again before then;
+//! without the header, or when it keeps being throttled, the wait doubles
+//! with every throttled poll up to [`MAX_BACKOFF`].  The state is kept in
+//! the mailbox's [`MailboxHealth`] so it survives restarts and shows in the
+//! dashboard.
+
+use chrono::{DateTime, Utc};
+use models::{EmailAccount, MailboxHealth};
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Wait after the first throttled poll when Graph doesn't say.
+const BASE_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Longest a mailbox is left alone after being throttled.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Graph asked to slow down.
+#[derive(Debug)]
+pub struct Throttled {
+    pub status: StatusCode,
+    /// How long Graph asked to wait, from `Retry-After`.
+    pub retry_after: Option<Duration>,
+}
+
+impl Throttled {
+    /// The throttling in a Graph response, if it is one.
+    pub fn from_response(status: StatusCode, headers: &HeaderMap) -> Option<Self> {
+        if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+            return None;
+        }
+        let retry_after = headers
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_retry_after(value, Utc::now()));
+        Some(Throttled { status, retry_after })
+    }
+}
+
+impl fmt::Display for Throttled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.retry_after {
+            Some(retry_after) => write!(f, "Microsoft Graph throttled the request ({}), retry after {}s", self.status, retry_after.as_secs()),
+            None => write!(f, "Microsoft Graph throttled the request ({})", self.status),
+        }
+    }
+}
+
+impl std::error::Error for Throttled {}
+
+/// Parses `Retry-After`, either seconds or an HTTP date.
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((at - now).to_std().unwrap_or_default())
+}
+
+/// How long to leave a mailbox alone after its `consecutive`th throttled
+/// poll in a row: what Graph asked for, but at least the doubling backoff.
+pub fn backoff(consecutive: u32, retry_after: Option<Duration>) -> Duration {
+    let doubled = BASE_BACKOFF.saturating_mul(1 << consecutive.saturating_sub(1).min(16)).min(MAX_BACKOFF);
+    match retry_after {
+        Some(retry_after) if consecutive <= 1 => retry_after.min(MAX_BACKOFF),
+        Some(retry_after) => retry_after.max(doubled).min(MAX_BACKOFF),
+        None => doubled,
+    }
+}
+
+/// Records a throttled poll of a mailbox.
+pub fn record_throttled(health: &mut MailboxHealth, throttled: &Throttled, now: DateTime<Utc>) {
+    health.consecutive_throttles += 1;
+    health.throttled_total += 1;
+    let wait = backoff(health.consecutive_throttles, throttled.retry_after);
+    health.throttled_until = Some(now + chrono::Duration::from_std(wait).unwrap_or_default());
+    health.last_polled_at = Some(now);
+}
+
+/// Records a poll of a mailbox that wasn't throttled.
+pub fn record_polled(health: &mut MailboxHealth, now: DateTime<Utc>) {
+    health.consecutive_throttles = 0;
+    health.throttled_until = None;
+    health.last_polled_at = Some(now);
+}
+
+/// Whether a mailbox is still backing off.
+pub fn is_backing_off(health: &MailboxHealth, now: DateTime<Utc>) -> bool {
+    health.throttled_until.is_some_and(|until| until > now)
+}
+
+/// Throttling of the connected mailboxes, as reported by the metrics
+/// endpoint.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ThrottleMetrics {
+    /// Throttled Graph responses across all mailboxes, ever.
+    pub throttled_total: u64,
+    /// Mailboxes currently backing off.
+    pub mailboxes_throttled: usize,
+    pub mailboxes: Vec<MailboxThrottle>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MailboxThrottle {
+    pub account_id: Uuid,
+    pub email_address: String,
+    #[serde(flatten)]
+    pub health: MailboxHealth,
+}
+
+impl ThrottleMetrics {
+    pub fn from_accounts(accounts: &[EmailAccount], now: DateTime<Utc>) -> Self {
+        let mailboxes: Vec<MailboxThrottle> = accounts
+            .iter()
+            .map(|account| MailboxThrottle {
+                account_id: account.id,
+                email_address: account.email_address.clone(),
+                health: account.health.clone(),
+            })
+            .collect();
+        ThrottleMetrics {
+            throttled_total: mailboxes.iter().map(|mailbox| mailbox.health.throttled_total).sum(),
+            mailboxes_throttled: mailboxes.iter().filter(|mailbox| is_backing_off(&mailbox.health, now)).count(),
+            mailboxes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_after_is_seconds_or_a_date() {
+        let now = DateTime::parse_from_rfc3339("2026-10-16T12:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Fri, 16 Oct 2026 12:01:30 GMT", now), Some(Duration::from_secs(90)));
+        assert_eq!(parse_retry_after("Fri, 16 Oct 2026 11:00:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn repeated_throttling_backs_off_longer() {
+        assert_eq!(backoff(1, Some(Duration::from_secs(5))), Duration::from_secs(5));
+        assert_eq!(backoff(1, None), BASE_BACKOFF);
+        assert_eq!(backoff(3, Some(Duration::from_secs(5))), BASE_BACKOFF * 4);
+        assert_eq!(backoff(3, Some(Duration::from_secs(600))), Duration::from_secs(600));
+        assert_eq!(backoff(40, None), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn a_poll_that_goes_through_ends_the_backoff() {
+        let now = Utc::now();
+        let mut health = MailboxHealth::default();
+        let throttled = Throttled { status: StatusCode::TOO_MANY_REQUESTS, retry_after: Some(Duration::from_secs(60)) };
+        record_throttled(&mut health, &throttled, now);
+        record_throttled(&mut health, &throttled, now);
+        assert!(is_backing_off(&health, now));
+        assert_eq!((health.consecutive_throttles, health.throttled_total), (2, 2));
+
+        record_polled(&mut health, now);
+        assert!(!is_backing_off(&health, now));
+        assert_eq!((health.consecutive_throttles, health.throttled_total), (0, 2));
+    }
+}
@@ -0,0 +1,315 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, Method},
+};
+use common::{
+    auth::{check_internal, secrets_match},
+    ServiceError,
+};
+use models::{ApiScope, StaffRole, User};
+use std::sync::Arc;
+
+use crate::AppState;
+
+/// Personal access tokens start with this, which tells them from session
+/// tokens.
+pub const API_TOKEN_PREFIX: &str = "tm_pat_";
+
+/// Paths personal access tokens may use, by what they read or change: the
+/// scope needed is the resource's read scope for `GET` and its write scope
+/// otherwise. Account settings, notifications and the tokens themselves
+/// need a session.
+const TOKEN_RESOURCES: [(&str, ApiScope, ApiScope); 15] = [
+    ("/api/v1/tasks", ApiScope::TasksRead, ApiScope::TasksWrite),
+    ("/api/v1/sync/tasks", ApiScope::TasksRead, ApiScope::TasksWrite),
+    ("/api/v1/task-templates", ApiScope::TasksRead, ApiScope::TasksWrite),
+    ("/api/v1/task-types", ApiScope::TasksRead, ApiScope::TasksWrite),
+    ("/api/v1/task-feedback", ApiScope::TasksRead, ApiScope::TasksWrite),
+    ("/api/v1/saved-filters", ApiScope::TasksRead, ApiScope::TasksWrite),
+    ("/api/v1/extraction-examples", ApiScope::TasksRead, ApiScope::TasksWrite),
+    ("/api/v1/tags", ApiScope::TasksRead, ApiScope::TasksWrite),
+    ("/api/v1/stats/tasks", ApiScope::TasksRead, ApiScope::TasksWrite),
+    ("/api/v1/stats/workload", ApiScope::TasksRead, ApiScope::TasksWrite),
+    ("/api/v1/cases", ApiScope::CasesRead, ApiScope::CasesWrite),
+    ("/api/v1/stats/cases", ApiScope::CasesRead, ApiScope::CasesWrite),
+    ("/api/v1/stats/productivity", ApiScope::CasesRead, ApiScope::CasesWrite),
+    // Messages held back on their way to becoming cases
+    ("/api/v1/quarantine", ApiScope::CasesRead, ApiScope::CasesWrite),
+    ("/api/v1/failed-messages", ApiScope::CasesRead, ApiScope::CasesWrite),
+];
+
+/// The scope a personal access token needs for a request, or `None` where
+/// tokens aren't accepted.
+pub fn required_scope(method: &Method, path: &str) -> Option<ApiScope> {
+    let (_, read, write) = TOKEN_RESOURCES.iter().find(|(prefix, _, _)| {
+        path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })?;
+    Some(if method == Method::GET || method == Method::HEAD { *read } else { *write })
+}
+
+/// The user owning the session token sent as `Authorization: Bearer <token>`.
+/// Handlers that take this extractor reject requests without a valid session.
+pub struct CurrentUser(pub User);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for CurrentUser {
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        OptionalUser::from_request_parts(parts, state)
+            .await?
+            .0
+            .map(CurrentUser)
+            .ok_or_else(|| ServiceError::Unauthorized("Missing session token".to_string()))
+    }
+}
+
+/// Like [`CurrentUser`], but for endpoints that are also called service to
+/// service without a session. A token that is present must still be valid;
+/// without one the request must carry the internal service token, so `None`
+/// always means another service is calling.
+///
+/// A personal access token stands in for a session where its scopes allow
+/// the request, see [`required_scope`].
+pub struct OptionalUser(pub Option<User>);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for OptionalUser {
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let Some(header_value) = parts.headers.get(header::AUTHORIZATION) else {
+            check_internal(&parts.headers, &state.config.secret_values)?;
+            return Ok(OptionalUser(None));
+        };
+
+        let token = header_value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| ServiceError::Unauthorized("Malformed authorization header".to_string()))?;
+
+        let user = if token.starts_with(API_TOKEN_PREFIX) {
+            let required = required_scope(&parts.method, parts.uri.path())
+                .ok_or_else(|| ServiceError::Unauthorized("API tokens can't be used here; sign in instead".to_string()))?;
+            let (user, scopes) = state.db.validate_api_token(token).await?;
+            if !scopes.iter().any(|scope| scope.grants(required)) {
+                return Err(ServiceError::Unauthorized(format!("The API token lacks the {} scope", required.as_str())));
+            }
+            user
+        } else {
+            state.db.validate_session(token).await?
+        };
+        common::logging::record_user(user.id);
+        Ok(OptionalUser(Some(user)))
+    }
+}
+
+/// Guards operational endpoints such as jobs and deletions. Requests must
+/// send the `X-Admin-Token` header matching `ADMIN_API_TOKEN`; when that
+/// variable is unset the endpoints are disabled entirely. Acting on
+/// accounts needs a [`StaffUser`] instead, so the audit log knows who.
+pub struct AdminAuth;
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AdminAuth {
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let expected = state
+            .admin_token
+            .as_deref()
+            .ok_or_else(|| ServiceError::Unauthorized("Admin API is disabled".to_string()))?;
+
+        let provided = parts
+            .headers
+            .get("x-admin-token")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| ServiceError::Unauthorized("Missing admin token".to_string()))?;
+
+        if !secrets_match(provided, expected) {
+            return Err(ServiceError::Unauthorized("Invalid admin token".to_string()));
+        }
+        Ok(AdminAuth)
+    }
+}
+
+/// A signed-in member of staff, for the account support endpoints. Their
+/// role comes from `staff_roles`; handlers check it with
+/// [`require`](Self::require), and the audit log records who they are.
+pub struct StaffUser {
+    pub user: User,
+    pub role: StaffRole,
+}
+
+impl StaffUser {
+    pub fn require(&self, role: StaffRole) -> Result<(), ServiceError> {
+        if !self.role.grants(role) {
+            return Err(ServiceError::Unauthorized(format!("Needs the {} role", role.as_str())));
+        }
+        Ok(())
+    }
+
+    /// How the audit log names the acting member of staff.
+    pub fn actor(&self) -> String {
+        format!("{} ({})", self.user.email, self.user.id)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for StaffUser {
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let CurrentUser(user) = CurrentUser::from_request_parts(parts, state).await?;
+        let role = state
+            .db
+            .staff_role(user.id)
+            .await?
+            .ok_or_else(|| ServiceError::Unauthorized("Staff only".to_string()))?;
+        Ok(StaffUser { user, role })
+    }
+}
+
+/// Guards endpoints only other services call, such as the job queue's.
+/// Requests must carry the internal service token; those carrying a user's
+/// session are turned away.
+pub struct InternalCall;
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for InternalCall {
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        if parts.headers.contains_key(header::AUTHORIZATION) {
+            return Err(ServiceError::Unauthorized("Not available to user sessions".to_string()));
+        }
+        check_internal(&parts.headers, &state.config.secret_values)?;
+        Ok(InternalCall)
+    }
+}
+
+/// Proves another service sent the request, which may also carry a user's
+/// session: for what only a service can vouch for, such as the claims of
+/// an ID token the dashboard verified.
+pub struct ServiceCall;
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ServiceCall {
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        check_internal(&parts.headers, &state.config.secret_values)?;
+        Ok(ServiceCall)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use common::{auth::INTERNAL_TOKEN_HEADER, testing::TEST_INTERNAL_TOKEN};
+
+    fn parts(headers: &[(&str, &str)]) -> Parts {
+        let mut request = Request::builder().uri("/api/v1/cases");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    async fn optional_user(headers: &[(&str, &str)]) -> Result<Option<User>, ServiceError> {
+        let state = AppState::for_tests();
+        OptionalUser::from_request_parts(&mut parts(headers), &state).await.map(|user| user.0)
+    }
+
+    async fn internal_call(headers: &[(&str, &str)]) -> Result<(), ServiceError> {
+        let state = AppState::for_tests();
+        InternalCall::from_request_parts(&mut parts(headers), &state).await.map(|_| ())
+    }
+
+    async fn service_call(headers: &[(&str, &str)]) -> bool {
+        let state = AppState::for_tests();
+        ServiceCall::from_request_parts(&mut parts(headers), &state).await.is_ok()
+    }
+
+    #[tokio::test]
+    async fn requests_without_a_session_or_service_token_are_refused() {
+        assert!(matches!(optional_user(&[]).await, Err(ServiceError::Unauthorized(_))));
+        assert!(matches!(
+            optional_user(&[(INTERNAL_TOKEN_HEADER, "guessed")]).await,
+            Err(ServiceError::Unauthorized(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn only_services_get_the_internal_scope() {
+        let user = optional_user(&[(INTERNAL_TOKEN_HEADER, TEST_INTERNAL_TOKEN)]).await.unwrap();
+        assert!(user.is_none());
+        assert_eq!(crate::scope::Scope::of(user.as_ref()), crate::scope::Scope::Internal);
+    }
+
+    #[tokio::test]
+    async fn staff_endpoints_need_a_signed_in_user() {
+        let state = AppState::for_tests();
+        for headers in [&[][..], &[(INTERNAL_TOKEN_HEADER, TEST_INTERNAL_TOKEN)][..]] {
+            let staff = StaffUser::from_request_parts(&mut parts(headers), &state).await;
+            assert!(matches!(staff, Err(ServiceError::Unauthorized(_))));
+        }
+    }
+
+    #[test]
+    fn admins_can_do_what_support_can() {
+        assert!(StaffRole::Admin.grants(StaffRole::Support));
+        assert!(!StaffRole::Support.grants(StaffRole::Admin));
+    }
+
+    #[tokio::test]
+    async fn internal_endpoints_need_the_service_token() {
+        assert!(internal_call(&[]).await.is_err());
+        assert!(internal_call(&[(INTERNAL_TOKEN_HEADER, "guessed")]).await.is_err());
+        assert!(internal_call(&[(INTERNAL_TOKEN_HEADER, TEST_INTERNAL_TOKEN)]).await.is_ok());
+        assert!(
+            internal_call(&[(INTERNAL_TOKEN_HEADER, TEST_INTERNAL_TOKEN), ("authorization", "Bearer session")])
+                .await
+                .is_err(),
+            "user sessions stay out even alongside the token"
+        );
+    }
+
+    #[tokio::test]
+    async fn service_calls_need_the_token_with_or_without_a_session() {
+        assert!(!service_call(&[]).await);
+        assert!(!service_call(&[("authorization", "Bearer session")]).await);
+        assert!(!service_call(&[(INTERNAL_TOKEN_HEADER, "guessed"), ("authorization", "Bearer session")]).await);
+        assert!(service_call(&[(INTERNAL_TOKEN_HEADER, TEST_INTERNAL_TOKEN)]).await);
+        assert!(service_call(&[(INTERNAL_TOKEN_HEADER, TEST_INTERNAL_TOKEN), ("authorization", "Bearer session")]).await);
+    }
+
+    #[test]
+    fn tokens_need_the_scope_of_what_they_touch() {
+        assert_eq!(required_scope(&Method::GET, "/api/v1/tasks"), Some(ApiScope::TasksRead));
+        assert_eq!(required_scope(&Method::PUT, "/api/v1/tasks/42"), Some(ApiScope::TasksWrite));
+        assert_eq!(required_scope(&Method::GET, "/api/v1/cases/42/tasks"), Some(ApiScope::CasesRead));
+        assert_eq!(required_scope(&Method::POST, "/api/v1/cases/42/history"), Some(ApiScope::CasesWrite));
+        assert_eq!(required_scope(&Method::GET, "/api/v1/stats/workload"), Some(ApiScope::TasksRead));
+    }
+
+    #[test]
+    fn tokens_stay_out_of_account_settings() {
+        assert_eq!(required_scope(&Method::GET, "/api/v1/users/me/preferences"), None);
+        assert_eq!(required_scope(&Method::POST, "/api/v1/users/me/tokens"), None);
+        assert_eq!(required_scope(&Method::GET, "/api/v1/notifications"), None);
+        assert_eq!(required_scope(&Method::GET, "/api/v1/tasks-archive"), None, "prefixes end at a segment");
+    }
+
+    #[test]
+    fn write_scopes_include_reading() {
+        assert!(ApiScope::TasksWrite.grants(ApiScope::TasksRead));
+        assert!(!ApiScope::TasksRead.grants(ApiScope::TasksWrite));
+        assert!(!ApiScope::CasesWrite.grants(ApiScope::TasksRead));
+    }
+}
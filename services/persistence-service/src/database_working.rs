@@ -1,15 +1,54 @@
-use sqlx::{PgPool, Row};
+use sqlx::{postgres::PgRow, PgPool, Row};
 use models::{
-    Case, Task, ConversationEntry, CaseWorkflow, WorkflowStep,
-    UpdateCaseRequest, UpdateTaskRequest, StepStatus, TaskStatus,
-    User, EmailAccount, UserSession, RegisterRequest, LoginRequest,
-    LoginResponse, UserProfile, AddEmailAccountRequest, UpdateUserRequest,
-    ChangePasswordRequest, EmailProvider, ImapSettings
+    Case, Task, ConversationEntry, CaseWorkflow, WorkflowStep, EmailThread,
+    UpdateCaseRequest, UpdateTaskRequest, StepStatus, TaskStatus, CaseStatus,
+    User, UserSession, RegisterRequest, LoginRequest,
+    CaseBackup, RestoreConflictMode, RestoreSummary,
+    AccountDeletion, AccountDeletionMode, AccountDeletionStatus,
+    StatsGrouping, TaskStats, TaskStatsPeriod, TaskTypeCount, PriorityCount,
+    CaseStatsPeriod, ProductivityStats, CaseProductivity, UserProductivity, WorkloadDay,
+    Notification, NotificationKind, PushSubscription, UserPreferences,
+    TaskTemplate, TaskTemplateRequest, TagUsage, SavedFilter, SavedFilterRequest,
+    QuarantinedMessage, QuarantineStatus, TaskFeedback, FeedbackVerdict, ExtractionExample, CustomTaskType, CustomTaskTypeRequest,
+    MessageSender, CaseEvent, CaseReadReceipt, Revision, RevisionEntity, Mention, FollowUpQuestion, QuestionStatus,
+    AdminAuditEntry, EmailVerification, UserUsage,
+    Job, JobStatus, EnqueueJobRequest, ScheduleJobRequest, ClaimJobsRequest, JobResult,
+    FailedMessage, FailedMessageStatus, CreateFailedMessageRequest, FailedMessageAttempt,
+    OutboxEvent, OutboxLag, SeededUser, TaskType, Priority, WebhookIntegration,
+    SsoIdentity, SsoLoginRequest, ApiToken, ApiScope, ClientInfo, LoginEvent, EmailAccount, MailboxHealth,
+    OrganizationLlm, OrganizationLlmRequest, OrganizationLlmSettings, OrganizationMember, LlmUsage, StaffRole,
 };
 use common::{ServiceResult, ServiceError};
 use uuid::Uuid;
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
 use bcrypt::{hash, verify, DEFAULT_COST};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::scope::Scope;
+use crate::tags::normalize_tags;
+
+/// Tags of the row, selected next to `tasks.*` or `cases.*` so the row
+/// helpers can fill in `tags`.
+const TASK_TAGS: &str = "ARRAY(SELECT tag FROM task_tags WHERE task_tags.task_id = tasks.id ORDER BY tag) AS tags";
+const CASE_TAGS: &str = "ARRAY(SELECT tag FROM case_tags WHERE case_tags.case_id = cases.id ORDER BY tag) AS tags";
+
+/// Attempts a job gets unless it asks for more or fewer.
+const DEFAULT_JOB_ATTEMPTS: i32 = 5;
+/// Backoff after a job's first failure, doubling with every further one.
+const JOB_RETRY_BASE: chrono::Duration = chrono::Duration::seconds(30);
+const JOB_RETRY_MAX: chrono::Duration = chrono::Duration::hours(1);
+/// Automatic attempts at a failed message before it is abandoned.
+const FAILED_MESSAGE_ATTEMPTS: i32 = 8;
+/// Wait after a message's first failure, doubling with every further one.
+const FAILED_MESSAGE_RETRY_BASE: chrono::Duration = chrono::Duration::minutes(1);
+const FAILED_MESSAGE_RETRY_MAX: chrono::Duration = chrono::Duration::hours(6);
+/// How long a claimed message is kept from other attempts.
+const FAILED_MESSAGE_CLAIM: chrono::Duration = chrono::Duration::minutes(5);
+/// Wait after an outbox event's first failed publication, doubling with
+/// every further one.
+const OUTBOX_RETRY_BASE: chrono::Duration = chrono::Duration::seconds(5);
+const OUTBOX_RETRY_MAX: chrono::Duration = chrono::Duration::minutes(10);
 
 #[derive(Clone)]
 pub struct Database {
@@ -22,6 +61,20 @@ impl Database {
         Ok(Self { pool })
     }
 
+    /// A pool that connects on first use, for tests that never reach the
+    /// database.
+    #[cfg(test)]
+    pub fn lazy(database_url: &str) -> Self {
+        Self { pool: PgPool::connect_lazy(database_url).expect("valid database URL") }
+    }
+
+    /// Opens new connections with `database_url`, e.g. after the password
+    /// was rotated.  Open connections are kept until the pool retires them.
+    pub fn set_url(&self, database_url: &str) -> Result<(), sqlx::Error> {
+        self.pool.set_connect_options(database_url.parse()?);
+        Ok(())
+    }
+
     pub async fn migrate(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // Run migrations manually, executing each statement separately
         
@@ -175,546 +228,6095 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
-        Ok(())
-    }
+        // Add preferences to users table if it doesn't exist
+        sqlx::query(r#"
+            DO $$ 
+            BEGIN 
+                IF NOT EXISTS (SELECT 1 FROM information_schema.columns 
+                              WHERE table_name='users' AND column_name='preferences') THEN
+                    ALTER TABLE users ADD COLUMN preferences JSONB NOT NULL DEFAULT '{}';
+                END IF;
+            END $$;
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-    // User Management Operations
-    pub async fn create_user(&self, request: RegisterRequest) -> ServiceResult<User> {
-        let user_id = Uuid::new_v4();
-        let now = Utc::now();
-        let password_hash = hash(&request.password, DEFAULT_COST)
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Password hashing error: {}", e)))?;
+        // Add estimate_minutes to tasks table if it doesn't exist
+        sqlx::query(r#"
+            DO $$ 
+            BEGIN 
+                IF NOT EXISTS (SELECT 1 FROM information_schema.columns 
+                              WHERE table_name='tasks' AND column_name='estimate_minutes') THEN
+                    ALTER TABLE tasks ADD COLUMN estimate_minutes INTEGER;
+                END IF;
+            END $$;
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-        let user = User {
-            id: user_id,
-            email: request.email.clone(),
-            password_hash: password_hash.clone(),
-            full_name: request.full_name.clone(),
-            organization: request.organization.clone(),
-            is_active: true,
-            created_at: now,
-            updated_at: now,
-            last_login: None,
-            metadata: serde_json::json!({}),
-        };
+        // Add SLA timestamps to cases table if they don't exist
+        sqlx::query(r#"
+            DO $$ 
+            BEGIN 
+                IF NOT EXISTS (SELECT 1 FROM information_schema.columns 
+                              WHERE table_name='cases' AND column_name='first_response_at') THEN
+                    ALTER TABLE cases ADD COLUMN first_response_at TIMESTAMPTZ;
+                    ALTER TABLE cases ADD COLUMN resolved_at TIMESTAMPTZ;
+                END IF;
+            END $$;
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-        sqlx::query(
-            r#"
-            INSERT INTO users (id, email, password_hash, full_name, organization, is_active, created_at, updated_at, last_login, metadata)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-            "#
-        )
-        .bind(user.id)
-        .bind(&user.email)
-        .bind(&user.password_hash)
-        .bind(&user.full_name)
-        .bind(&user.organization)
-        .bind(user.is_active)
-        .bind(user.created_at)
-        .bind(user.updated_at)
-        .bind(user.last_login)
-        .bind(&user.metadata)
+        // Create account_deletions table
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS account_deletions (
+                user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                mode VARCHAR NOT NULL,
+                status VARCHAR NOT NULL,
+                requested_at TIMESTAMPTZ NOT NULL,
+                scheduled_for TIMESTAMPTZ NOT NULL,
+                completed_at TIMESTAMPTZ
+            )
+        "#)
         .execute(&self.pool)
-        .await
-        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        .await?;
 
-        Ok(user)
-    }
+        // Create notifications table
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS notifications (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                kind VARCHAR NOT NULL,
+                title VARCHAR NOT NULL,
+                message TEXT NOT NULL,
+                link VARCHAR,
+                subject_id UUID,
+                created_at TIMESTAMPTZ NOT NULL,
+                read_at TIMESTAMPTZ
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-    pub async fn authenticate_user(&self, request: LoginRequest) -> ServiceResult<User> {
-        let row = sqlx::query(
-            "SELECT id, email, password_hash, full_name, organization, is_active, created_at, updated_at, last_login, metadata FROM users WHERE email = $1 AND is_active = true"
-        )
-        .bind(&request.email)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        // NULL subjects never conflict, so only subject-bound notifications
+        // are deduplicated.
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS notifications_subject_idx ON notifications (user_id, kind, subject_id)")
+            .execute(&self.pool)
+            .await?;
 
-        let row = row.ok_or_else(|| ServiceError::NotFound("User not found or inactive".to_string()))?;
+        // Remembers deleted tasks so offline clients can drop them on sync
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS task_deletions (
+                task_id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                deleted_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-        let password_hash: String = row.get("password_hash");
-        if !verify(&request.password, &password_hash)
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Password verification error: {}", e)))? {
-            return Err(ServiceError::Unauthorized("Invalid credentials".to_string()));
-        }
+        // Create tag tables
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS task_tags (
+                task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (task_id, tag)
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-        let mut user = User {
-            id: row.get("id"),
-            email: row.get("email"),
-            password_hash,
-            full_name: row.get("full_name"),
-            organization: row.get("organization"),
-            is_active: row.get("is_active"),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-            last_login: row.get("last_login"),
-            metadata: row.get("metadata"),
-        };
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS case_tags (
+                case_id UUID NOT NULL REFERENCES cases(id) ON DELETE CASCADE,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (case_id, tag)
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-        // Update last login
-        let now = Utc::now();
-        sqlx::query("UPDATE users SET last_login = $1, updated_at = $2 WHERE id = $3")
-            .bind(now)
-            .bind(now)
-            .bind(user.id)
+        sqlx::query("CREATE INDEX IF NOT EXISTS task_tags_tag_idx ON task_tags (tag)")
             .execute(&self.pool)
-            .await
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
-
-        user.last_login = Some(now);
-        user.updated_at = now;
-        Ok(user)
-    }
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS case_tags_tag_idx ON case_tags (tag)")
+            .execute(&self.pool)
+            .await?;
+        // Conversation pages are read newest first within a case
+        sqlx::query("CREATE INDEX IF NOT EXISTS conversation_entries_case_time_idx ON conversation_entries (case_id, timestamp)")
+            .execute(&self.pool)
+            .await?;
 
-    pub async fn create_session(&self, user_id: Uuid, session_token: String, expires_at: chrono::DateTime<Utc>) -> ServiceResult<UserSession> {
-        let session_id = Uuid::new_v4();
-        let now = Utc::now();
+        // Create task_templates table
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS task_templates (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                name VARCHAR NOT NULL,
+                title VARCHAR NOT NULL,
+                description TEXT,
+                task_type VARCHAR NOT NULL,
+                priority VARCHAR NOT NULL,
+                due_in_days BIGINT,
+                checklist JSONB NOT NULL DEFAULT '[]',
+                created_at TIMESTAMPTZ NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL,
+                UNIQUE (user_id, name)
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-        let session = UserSession {
-            id: session_id,
-            user_id,
-            session_token: session_token.clone(),
-            expires_at,
-            created_at: now,
-            last_accessed: now,
-            ip_address: None,
-            user_agent: None,
-        };
+        // Create saved_filters table
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS saved_filters (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                name VARCHAR NOT NULL,
+                filter JSONB NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL,
+                UNIQUE (user_id, name)
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-        sqlx::query(
-            r#"
-            INSERT INTO user_sessions (id, user_id, session_token, expires_at, created_at, last_accessed, ip_address, user_agent)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-            "#
-        )
-        .bind(session.id)
-        .bind(session.user_id)
-        .bind(&session.session_token)
-        .bind(session.expires_at)
-        .bind(session.created_at)
-        .bind(session.last_accessed)
-        .bind(&session.ip_address)
-        .bind(&session.user_agent)
+        // Create webhook_integrations table
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS webhook_integrations (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                name VARCHAR NOT NULL,
+                token_hash VARCHAR NOT NULL UNIQUE,
+                mapping JSONB NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
         .execute(&self.pool)
-        .await
-        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        .await?;
 
-        Ok(session)
-    }
+        // Create push_subscriptions table
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS push_subscriptions (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                endpoint TEXT NOT NULL UNIQUE,
+                p256dh VARCHAR NOT NULL,
+                auth VARCHAR NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-    pub async fn validate_session(&self, session_token: &str) -> ServiceResult<User> {
-        let row = sqlx::query(
-            r#"
-            SELECT u.id, u.email, u.password_hash, u.full_name, u.organization, u.is_active, 
-                   u.created_at, u.updated_at, u.last_login, u.metadata
-            FROM users u
-            JOIN user_sessions s ON u.id = s.user_id
-            WHERE s.session_token = $1 AND s.expires_at > NOW() AND u.is_active = true
-            "#
-        )
-        .bind(session_token)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        // Holds the generated VAPID key when none is configured
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS vapid_keys (
+                id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+                private_key VARCHAR NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-        let row = row.ok_or_else(|| ServiceError::Unauthorized("Invalid or expired session".to_string()))?;
+        // Messages held back by the channel service's spam filter
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS quarantined_messages (
+                id UUID PRIMARY KEY,
+                user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+                message JSONB NOT NULL,
+                reasons JSONB NOT NULL,
+                status VARCHAR NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                reviewed_at TIMESTAMPTZ
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-        // Update last accessed time
-        let now = Utc::now();
-        sqlx::query("UPDATE user_sessions SET last_accessed = $1 WHERE session_token = $2")
-            .bind(now)
-            .bind(session_token)
-            .execute(&self.pool)
-            .await
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        // Verdicts on AI-extracted tasks, one per task. Feedback outlives
+        // its task so deleted tasks still count as examples.
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS task_feedback (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                task_id UUID NOT NULL UNIQUE,
+                verdict VARCHAR NOT NULL,
+                message TEXT NOT NULL,
+                extracted JSONB NOT NULL,
+                corrected JSONB,
+                comment TEXT,
+                created_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
 
-        Ok(User {
-            id: row.get("id"),
+        // Few-shot examples for the AI agent's extraction prompt
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS extraction_examples (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                message TEXT NOT NULL,
+                expected_tasks JSONB NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // Task types organizations add to the built-in ones
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS custom_task_types (
+                id UUID PRIMARY KEY,
+                organization VARCHAR NOT NULL,
+                name VARCHAR NOT NULL,
+                description TEXT,
+                fields JSONB NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL,
+                UNIQUE (organization, name)
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // A case's workflow once it has been changed; until then cases have
+        // the default one
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS case_workflows (
+                case_id UUID PRIMARY KEY REFERENCES cases(id) ON DELETE CASCADE,
+                id UUID NOT NULL,
+                current_step VARCHAR NOT NULL,
+                steps JSONB NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // Changes to cases, their tasks and workflows, for case timelines
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS case_events (
+                id UUID PRIMARY KEY,
+                case_id UUID NOT NULL REFERENCES cases(id) ON DELETE CASCADE,
+                actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+                kind VARCHAR NOT NULL,
+                summary TEXT NOT NULL,
+                subject_id UUID,
+                created_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS case_events_case_time_idx ON case_events (case_id, created_at)")
+            .execute(&self.pool)
+            .await?;
+
+        // Field-level history of cases and tasks; user_id is the owner
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS revisions (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                entity VARCHAR NOT NULL,
+                entity_id UUID NOT NULL,
+                actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+                changes JSONB NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS revisions_entity_idx ON revisions (entity_id, created_at)")
+            .execute(&self.pool)
+            .await?;
+
+        // When each user last read each case's conversation
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS case_reads (
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                case_id UUID NOT NULL REFERENCES cases(id) ON DELETE CASCADE,
+                last_read_at TIMESTAMPTZ NOT NULL,
+                PRIMARY KEY (user_id, case_id)
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // Administration: email verification and impersonated sessions
+        sqlx::query(r#"
+            DO $$ 
+            BEGIN 
+                IF NOT EXISTS (SELECT 1 FROM information_schema.columns 
+                              WHERE table_name='users' AND column_name='email_verified_at') THEN
+                    ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMPTZ;
+                END IF;
+                IF NOT EXISTS (SELECT 1 FROM information_schema.columns 
+                              WHERE table_name='user_sessions' AND column_name='impersonated_by') THEN
+                    ALTER TABLE user_sessions ADD COLUMN impersonated_by VARCHAR;
+                END IF;
+            END $$;
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // One outstanding verification token per user, stored hashed
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS email_verifications (
+                user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                token_hash VARCHAR UNIQUE NOT NULL,
+                expires_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // Accounts at OpenID Connect providers that sign in as a user
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS sso_identities (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                provider VARCHAR NOT NULL,
+                issuer VARCHAR NOT NULL,
+                subject VARCHAR NOT NULL,
+                email VARCHAR NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                last_login TIMESTAMPTZ,
+                UNIQUE (issuer, subject)
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // Personal access tokens, stored hashed
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS api_tokens (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                name VARCHAR NOT NULL,
+                token_hash VARCHAR NOT NULL UNIQUE,
+                scopes TEXT[] NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                expires_at TIMESTAMPTZ,
+                last_used_at TIMESTAMPTZ,
+                revoked_at TIMESTAMPTZ
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // Sign-ins, for spotting unusual ones
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS login_events (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                ip_address VARCHAR,
+                user_agent VARCHAR,
+                unusual BOOLEAN NOT NULL DEFAULT FALSE,
+                created_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_login_events_user ON login_events(user_id, created_at)")
+            .execute(&self.pool)
+            .await?;
+
+        // Failed sign-ins are recorded too
+        sqlx::query("ALTER TABLE login_events ADD COLUMN IF NOT EXISTS succeeded BOOLEAN NOT NULL DEFAULT TRUE")
+            .execute(&self.pool)
+            .await?;
+
+        // Started OAuth sign-ins, until their callback
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS oauth_states (
+                state VARCHAR PRIMARY KEY,
+                payload JSONB NOT NULL,
+                expires_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // A mailbox is connected to one user
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS email_accounts_address_idx ON email_accounts (lower(email_address))")
+            .execute(&self.pool)
+            .await?;
+
+        // Permissions granted when a mailbox was connected
+        sqlx::query("ALTER TABLE email_accounts ADD COLUMN IF NOT EXISTS scopes TEXT[] NOT NULL DEFAULT '{}'")
+            .execute(&self.pool)
+            .await?;
+
+        // How polling a mailbox goes, reported by the email collector
+        sqlx::query("ALTER TABLE email_accounts ADD COLUMN IF NOT EXISTS health JSONB NOT NULL DEFAULT '{}'")
+            .execute(&self.pool)
+            .await?;
+
+        // Administrative actions on accounts; kept when the account goes
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS admin_audit_log (
+                id UUID PRIMARY KEY,
+                action VARCHAR NOT NULL,
+                target_user_id UUID NOT NULL,
+                actor VARCHAR NOT NULL,
+                reason TEXT,
+                created_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS admin_audit_log_target_idx ON admin_audit_log (target_user_id, created_at)")
+            .execute(&self.pool)
+            .await?;
+
+        // Who may use the admin API, and in what role
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS staff_roles (
+                user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                role VARCHAR NOT NULL,
+                granted_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // Background jobs; see common::jobs
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS jobs (
+                id UUID PRIMARY KEY,
+                kind VARCHAR NOT NULL,
+                payload JSONB NOT NULL,
+                status VARCHAR NOT NULL,
+                attempts INTEGER NOT NULL,
+                max_attempts INTEGER NOT NULL,
+                run_at TIMESTAMPTZ NOT NULL,
+                interval_secs BIGINT,
+                last_error TEXT,
+                locked_by VARCHAR,
+                locked_until TIMESTAMPTZ,
+                created_at TIMESTAMPTZ NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS jobs_status_run_at_idx ON jobs (status, run_at)")
+            .execute(&self.pool)
+            .await?;
+
+        // One row per recurring kind
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS jobs_recurring_kind_idx ON jobs (kind) WHERE interval_secs IS NOT NULL")
+            .execute(&self.pool)
+            .await?;
+
+        // Messages the channel service could not get processed
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS failed_messages (
+                id UUID PRIMARY KEY,
+                user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+                message JSONB NOT NULL,
+                error TEXT NOT NULL,
+                status VARCHAR NOT NULL,
+                attempts INTEGER NOT NULL,
+                next_attempt_at TIMESTAMPTZ,
+                claimed_until TIMESTAMPTZ,
+                created_at TIMESTAMPTZ NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // Pushes waiting for quiet hours to end or the daily digest
+        sqlx::query(r#"
+            DO $$ 
+            BEGIN 
+                IF NOT EXISTS (SELECT 1 FROM information_schema.columns 
+                              WHERE table_name='notifications' AND column_name='push_held') THEN
+                    ALTER TABLE notifications ADD COLUMN push_held BOOLEAN NOT NULL DEFAULT false;
+                END IF;
+            END $$;
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS notifications_push_held_idx ON notifications (user_id) WHERE push_held")
+            .execute(&self.pool)
+            .await?;
+
+        // Users mentioned in conversation entries; see mentions.rs
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS mentions (
+                id UUID PRIMARY KEY,
+                entry_id UUID NOT NULL REFERENCES conversation_entries(id) ON DELETE CASCADE,
+                case_id UUID NOT NULL REFERENCES cases(id) ON DELETE CASCADE,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                author_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                created_at TIMESTAMPTZ NOT NULL,
+                UNIQUE (entry_id, user_id)
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS mentions_user_time_idx ON mentions (user_id, created_at)")
+            .execute(&self.pool)
+            .await?;
+
+        // Questions the AI agent asked about tasks created without key details
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS follow_up_questions (
+                id UUID PRIMARY KEY,
+                case_id UUID NOT NULL REFERENCES cases(id) ON DELETE CASCADE,
+                task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                question TEXT NOT NULL,
+                missing JSONB NOT NULL,
+                channel JSONB NOT NULL,
+                recipient VARCHAR NOT NULL,
+                status VARCHAR NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                answered_at TIMESTAMPTZ
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS follow_up_questions_case_idx ON follow_up_questions (case_id, created_at)")
+            .execute(&self.pool)
+            .await?;
+
+        // Task and case changes waiting to be published; see outbox.rs
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS outbox_events (
+                seq BIGSERIAL PRIMARY KEY,
+                id UUID NOT NULL UNIQUE,
+                event_type VARCHAR NOT NULL,
+                aggregate_id UUID NOT NULL,
+                payload JSONB NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                published_at TIMESTAMPTZ,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at TIMESTAMPTZ,
+                last_error TEXT
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS outbox_events_pending_idx ON outbox_events (seq) WHERE published_at IS NULL")
+            .execute(&self.pool)
+            .await?;
+
+        // The LLM each organization uses, and its token use by month
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS organization_llm_settings (
+                organization VARCHAR PRIMARY KEY,
+                base_url VARCHAR,
+                model VARCHAR,
+                api_key VARCHAR,
+                monthly_token_budget BIGINT,
+                alert_user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+                updated_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS llm_usage (
+                organization VARCHAR NOT NULL,
+                month DATE NOT NULL,
+                tokens BIGINT NOT NULL DEFAULT 0,
+                alerted BOOLEAN NOT NULL DEFAULT false,
+                PRIMARY KEY (organization, month)
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        // Who belongs to an organization, as an administrator added them;
+        // a user is in one at most
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS organization_members (
+                user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                organization VARCHAR NOT NULL,
+                added_at TIMESTAMPTZ NOT NULL
+            )
+        "#)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // User Management Operations
+    pub async fn create_user(&self, request: RegisterRequest) -> ServiceResult<User> {
+        let user_id = Uuid::new_v4();
+        let now = Utc::now();
+        let password_hash = hash(&request.password, DEFAULT_COST)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Password hashing error: {}", e)))?;
+
+        let user = User {
+            id: user_id,
+            email: request.email.clone(),
+            password_hash: password_hash.clone(),
+            full_name: request.full_name.clone(),
+            organization: request.organization.clone(),
+            is_active: true,
+            created_at: now,
+            updated_at: now,
+            last_login: None,
+            metadata: serde_json::json!({}),
+            preferences: UserPreferences::default(),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO users (id, email, password_hash, full_name, organization, is_active, created_at, updated_at, last_login, metadata, preferences)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#
+        )
+        .bind(user.id)
+        .bind(&user.email)
+        .bind(&user.password_hash)
+        .bind(&user.full_name)
+        .bind(&user.organization)
+        .bind(user.is_active)
+        .bind(user.created_at)
+        .bind(user.updated_at)
+        .bind(user.last_login)
+        .bind(&user.metadata)
+        .bind(serde_json::to_value(&user.preferences).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(user)
+    }
+
+    pub async fn authenticate_user(&self, request: LoginRequest) -> ServiceResult<User> {
+        let row = sqlx::query(
+            "SELECT id, email, password_hash, full_name, organization, is_active, created_at, updated_at, last_login, metadata, preferences FROM users WHERE email = $1 AND is_active = true"
+        )
+        .bind(&request.email)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        let row = row.ok_or_else(|| ServiceError::NotFound("User not found or inactive".to_string()))?;
+
+        let password_hash: String = row.get("password_hash");
+        if password_hash.is_empty() {
+            self.record_login_event(row.get("id"), &request.client, false, false).await?;
+            return Err(ServiceError::Unauthorized("This account signs in with single sign-on".to_string()));
+        }
+        if !verify(&request.password, &password_hash)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Password verification error: {}", e)))? {
+            self.record_login_event(row.get("id"), &request.client, false, false).await?;
+            return Err(ServiceError::Unauthorized("Invalid credentials".to_string()));
+        }
+
+        let mut user = User {
+            id: row.get("id"),
+            email: row.get("email"),
+            password_hash,
+            full_name: row.get("full_name"),
+            organization: row.get("organization"),
+            is_active: row.get("is_active"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            last_login: row.get("last_login"),
+            metadata: row.get("metadata"),
+            preferences: preferences_from_row(&row),
+        };
+
+        // Update last login
+        let now = Utc::now();
+        sqlx::query("UPDATE users SET last_login = $1, updated_at = $2 WHERE id = $3")
+            .bind(now)
+            .bind(now)
+            .bind(user.id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        user.last_login = Some(now);
+        user.updated_at = now;
+        Ok(user)
+    }
+
+    pub async fn create_session(
+        &self,
+        user_id: Uuid,
+        session_token: String,
+        expires_at: chrono::DateTime<Utc>,
+        client: &ClientInfo,
+    ) -> ServiceResult<UserSession> {
+        let session_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        let session = UserSession {
+            id: session_id,
+            user_id,
+            session_token: session_token.clone(),
+            expires_at,
+            created_at: now,
+            last_accessed: now,
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_sessions (id, user_id, session_token, expires_at, created_at, last_accessed, ip_address, user_agent)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#
+        )
+        .bind(session.id)
+        .bind(session.user_id)
+        .bind(&session.session_token)
+        .bind(session.expires_at)
+        .bind(session.created_at)
+        .bind(session.last_accessed)
+        .bind(&session.ip_address)
+        .bind(&session.user_agent)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(session)
+    }
+
+    pub async fn validate_session(&self, session_token: &str) -> ServiceResult<User> {
+        let row = sqlx::query(
+            r#"
+            SELECT u.id, u.email, u.password_hash, u.full_name, u.organization, u.is_active, 
+                   u.created_at, u.updated_at, u.last_login, u.metadata, u.preferences
+            FROM users u
+            JOIN user_sessions s ON u.id = s.user_id
+            WHERE s.session_token = $1 AND s.expires_at > NOW() AND u.is_active = true
+            "#
+        )
+        .bind(session_token)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        let row = row.ok_or_else(|| ServiceError::Unauthorized("Invalid or expired session".to_string()))?;
+
+        // Update last accessed time
+        let now = Utc::now();
+        sqlx::query("UPDATE user_sessions SET last_accessed = $1 WHERE session_token = $2")
+            .bind(now)
+            .bind(session_token)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(User {
+            id: row.get("id"),
+            email: row.get("email"),
+            password_hash: row.get("password_hash"),
+            full_name: row.get("full_name"),
+            organization: row.get("organization"),
+            is_active: row.get("is_active"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            last_login: row.get("last_login"),
+            metadata: row.get("metadata"),
+            preferences: preferences_from_row(&row),
+        })
+    }
+
+    pub async fn user_preferences(&self, user_id: Uuid) -> ServiceResult<UserPreferences> {
+        let row = sqlx::query("SELECT preferences FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+            .ok_or_else(|| ServiceError::NotFound(format!("User with id {} not found", user_id)))?;
+        Ok(preferences_from_row(&row))
+    }
+
+    pub async fn update_user_preferences(&self, user_id: Uuid, preferences: &UserPreferences) -> ServiceResult<UserPreferences> {
+        let value = serde_json::to_value(preferences)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+
+        let row = sqlx::query("UPDATE users SET preferences = $1, updated_at = NOW() WHERE id = $2 RETURNING preferences")
+            .bind(value)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+            .ok_or_else(|| ServiceError::NotFound(format!("User with id {} not found", user_id)))?;
+
+        Ok(preferences_from_row(&row))
+    }
+
+    // SSO Identity Operations
+    /// Signs in with the verified claims of an ID token. An identity seen
+    /// before signs in as its user. A new one is linked to the account with
+    /// its email when both the provider and the account verified the email,
+    /// and otherwise gets a new account, which has no password. An account
+    /// that never verified its email may have been registered by someone
+    /// else, who would keep its password, so it is only linked after
+    /// signing in with that password.
+    pub async fn sso_login(&self, request: &SsoLoginRequest) -> ServiceResult<User> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let linked = sqlx::query(
+            "SELECT u.* FROM users u JOIN sso_identities i ON i.user_id = u.id WHERE i.issuer = $1 AND i.subject = $2"
+        )
+        .bind(&request.issuer)
+        .bind(&request.subject)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        let now = Utc::now();
+        let user = match linked {
+            Some(row) => user_from_row(&row),
+            None => {
+                let existing = sqlx::query("SELECT * FROM users WHERE lower(email) = lower($1)")
+                    .bind(&request.email)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(db_error)?;
+                let user = match existing {
+                    Some(row) if !request.email_verified || row.get::<Option<DateTime<Utc>>, _>("email_verified_at").is_none() => {
+                        return Err(ServiceError::Unauthorized(
+                            "An account with this email exists; sign in with its password to link the provider".to_string(),
+                        ));
+                    }
+                    Some(row) => user_from_row(&row),
+                    None => {
+                        let full_name = request
+                            .full_name
+                            .clone()
+                            .filter(|name| !name.trim().is_empty())
+                            .unwrap_or_else(|| request.email.clone());
+                        let row = sqlx::query(
+                            r#"
+                            INSERT INTO users (id, email, password_hash, full_name, is_active, created_at, updated_at, metadata, preferences, email_verified_at)
+                            VALUES ($1, $2, '', $3, true, $4, $4, '{}', $5, $6)
+                            RETURNING *
+                            "#
+                        )
+                        .bind(Uuid::new_v4())
+                        .bind(&request.email)
+                        .bind(full_name)
+                        .bind(now)
+                        .bind(serde_json::to_value(UserPreferences::default())
+                            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+                        .bind(request.email_verified.then_some(now))
+                        .fetch_one(&mut *tx)
+                        .await
+                        .map_err(db_error)?;
+                        user_from_row(&row)
+                    }
+                };
+                if user.is_active {
+                    insert_sso_identity(&mut tx, user.id, request).await?;
+                }
+                user
+            }
+        };
+        if !user.is_active {
+            return Err(ServiceError::Unauthorized("Account is disabled".to_string()));
+        }
+
+        sqlx::query("UPDATE sso_identities SET last_login = $1 WHERE issuer = $2 AND subject = $3")
+            .bind(now)
+            .bind(&request.issuer)
+            .bind(&request.subject)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        let row = sqlx::query("UPDATE users SET last_login = $1, updated_at = $1 WHERE id = $2 RETURNING *")
+            .bind(now)
+            .bind(user.id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(user_from_row(&row))
+    }
+
+    /// Links a provider account to a signed-in user. Linking it again is a
+    /// no-op; an account linked to someone else is refused.
+    pub async fn link_sso_identity(&self, user_id: Uuid, request: &SsoLoginRequest) -> ServiceResult<SsoIdentity> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let existing = sqlx::query("SELECT * FROM sso_identities WHERE issuer = $1 AND subject = $2")
+            .bind(&request.issuer)
+            .bind(&request.subject)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?
+            .map(|row| sso_identity_from_row(&row));
+        let identity = match existing {
+            Some(identity) if identity.user_id == user_id => identity,
+            Some(_) => {
+                return Err(ServiceError::BadRequest(format!(
+                    "This {} account is already linked to another user",
+                    request.provider
+                )));
+            }
+            None => insert_sso_identity(&mut tx, user_id, request).await?,
+        };
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(identity)
+    }
+
+    pub async fn list_sso_identities(&self, user_id: Uuid) -> ServiceResult<Vec<SsoIdentity>> {
+        let rows = sqlx::query("SELECT * FROM sso_identities WHERE user_id = $1 ORDER BY created_at")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows.iter().map(sso_identity_from_row).collect())
+    }
+
+    /// Unlinks a provider account. The last one of an account without a
+    /// password stays, as the user could no longer sign in.
+    pub async fn delete_sso_identity(&self, user_id: Uuid, id: Uuid) -> ServiceResult<()> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM sso_identities i
+            WHERE i.user_id = $1 AND i.id = $2
+              AND (EXISTS (SELECT 1 FROM users u WHERE u.id = i.user_id AND u.password_hash <> '')
+                   OR EXISTS (SELECT 1 FROM sso_identities o WHERE o.user_id = i.user_id AND o.id <> i.id))
+            "#
+        )
+        .bind(user_id)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            let exists = self.list_sso_identities(user_id).await?.iter().any(|identity| identity.id == id);
+            return Err(if exists {
+                ServiceError::BadRequest("The account has no password; keep one sign-in provider".to_string())
+            } else {
+                ServiceError::NotFound(format!("SSO identity {} not found", id))
+            });
+        }
+        Ok(())
+    }
+
+    // API Token Operations
+    /// Saves a token with the hash of its secret, which must be set.
+    pub async fn create_api_token(&self, token: ApiToken) -> ServiceResult<ApiToken> {
+        let secret = token
+            .token
+            .as_deref()
+            .ok_or_else(|| ServiceError::Internal(anyhow::anyhow!("API token without a secret")))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO api_tokens (id, user_id, name, token_hash, scopes, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#
+        )
+        .bind(token.id)
+        .bind(token.user_id)
+        .bind(&token.name)
+        .bind(token_hash(secret))
+        .bind(token.scopes.iter().map(|scope| scope.as_str()).collect::<Vec<_>>())
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(token)
+    }
+
+    /// The user's tokens, revoked ones included, newest first.
+    pub async fn list_api_tokens(&self, user_id: Uuid) -> ServiceResult<Vec<ApiToken>> {
+        let rows = sqlx::query("SELECT * FROM api_tokens WHERE user_id = $1 ORDER BY created_at DESC")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows.iter().map(api_token_from_row).collect())
+    }
+
+    pub async fn revoke_api_token(&self, user_id: Uuid, id: Uuid) -> ServiceResult<ApiToken> {
+        let row = sqlx::query(
+            "UPDATE api_tokens SET revoked_at = NOW() WHERE user_id = $1 AND id = $2 AND revoked_at IS NULL RETURNING *"
+        )
+        .bind(user_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("API token {} not found", id)))?;
+
+        Ok(api_token_from_row(&row))
+    }
+
+    /// The active user holding a live token, and the token's scopes.
+    pub async fn validate_api_token(&self, token: &str) -> ServiceResult<(User, Vec<ApiScope>)> {
+        let row = sqlx::query(
+            r#"
+            UPDATE api_tokens t SET last_used_at = NOW()
+            FROM users u
+            WHERE t.token_hash = $1 AND u.id = t.user_id AND u.is_active = true
+              AND t.revoked_at IS NULL AND (t.expires_at IS NULL OR t.expires_at > NOW())
+            RETURNING u.*, t.scopes
+            "#
+        )
+        .bind(token_hash(token))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::Unauthorized("Invalid, expired or revoked API token".to_string()))?;
+
+        Ok((user_from_row(&row), scopes_from_row(&row)))
+    }
+
+    // Case operations
+    pub async fn create_case(&self, mut case: Case) -> ServiceResult<Case> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        case.tags = normalize_tags(&case.tags);
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO cases (id, user_id, title, description, status, priority, created_at, updated_at, assigned_to, metadata, first_response_at, resolved_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            "#
+        )
+        .bind(case.id)
+        .bind(case.user_id)
+        .bind(&case.title)
+        .bind(&case.description)
+        .bind(serde_json::to_string(&case.status).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(serde_json::to_string(&case.priority).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(case.created_at)
+        .bind(case.updated_at)
+        .bind(&case.assigned_to)
+        .bind(&case.metadata)
+        .bind(case.first_response_at)
+        .bind(case.resolved_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        replace_tags(&mut tx, "case_tags", "case_id", case.id, &case.tags).await.map_err(db_error)?;
+        insert_outbox_event(&mut tx, "case.created", case.id, &case).await?;
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(case)
+    }
+
+    pub async fn get_case(&self, id: Uuid, scope: Scope) -> ServiceResult<Case> {
+        let row = sqlx::query(&format!(
+            "SELECT id, user_id, title, description, status, priority, created_at, updated_at, assigned_to, metadata, first_response_at, resolved_at, {} FROM cases WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2)",
+            CASE_TAGS
+        ))
+        .bind(id)
+        .bind(scope.owner())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("Case with id {} not found", id)))?;
+
+        Ok(Case {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            title: row.get("title"),
+            description: row.get("description"),
+            status: serde_json::from_str(&row.get::<String, _>("status")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+            priority: serde_json::from_str(&row.get::<String, _>("priority")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            assigned_to: row.get("assigned_to"),
+            metadata: row.get("metadata"),
+            tags: row.get("tags"),
+            unread_count: None,
+            first_response_at: row.get("first_response_at"),
+            resolved_at: row.get("resolved_at"),
+            sla: None,
+        })
+    }
+
+    /// Cases whose recorded email thread `thread` belongs to: the same Graph
+    /// conversation, or a thread that already has the email or the one it
+    /// replies to. Most recently updated first.
+    pub async fn find_cases_by_email_thread(&self, scope: Scope, thread: &EmailThread) -> ServiceResult<Vec<Case>> {
+        if thread.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT *, {} FROM cases
+            WHERE ($1::uuid IS NULL OR user_id = $1)
+              AND (metadata->'email'->>'conversation_id' = $2 OR metadata->'email'->'message_ids' ?| $3)
+            ORDER BY updated_at DESC
+            "#,
+            CASE_TAGS
+        ))
+        .bind(scope.owner())
+        .bind(&thread.conversation_id)
+        .bind(thread.message_ids())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(case_from_row).collect()
+    }
+
+    /// Adds an email's identifiers to the thread kept in the case metadata
+    /// under `email`: the Graph `conversation_id` and every known
+    /// `message_ids` entry.
+    pub async fn record_email_thread(&self, id: Uuid, scope: Scope, thread: &EmailThread) -> ServiceResult<Case> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let mut metadata: serde_json::Value = sqlx::query_scalar(
+            "SELECT metadata FROM cases WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2) FOR UPDATE"
+        )
+        .bind(id)
+        .bind(scope.owner())
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| ServiceError::NotFound(format!("Case with id {} not found", id)))?;
+
+        if !metadata.is_object() {
+            metadata = serde_json::json!({});
+        }
+        let email = &mut metadata["email"];
+        if !email.is_object() {
+            *email = serde_json::json!({ "message_ids": [] });
+        }
+        if let Some(conversation_id) = &thread.conversation_id {
+            email["conversation_id"] = serde_json::json!(conversation_id);
+        }
+        let mut message_ids: Vec<String> = serde_json::from_value(email["message_ids"].take()).unwrap_or_default();
+        for message_id in thread.message_ids() {
+            if !message_ids.contains(&message_id) {
+                message_ids.push(message_id);
+            }
+        }
+        email["message_ids"] = serde_json::json!(message_ids);
+
+        sqlx::query("UPDATE cases SET metadata = $2, updated_at = NOW() WHERE id = $1")
+            .bind(id)
+            .bind(&metadata)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        self.get_case(id, scope).await
+    }
+
+    pub async fn update_case(&self, id: Uuid, scope: Scope, request: UpdateCaseRequest) -> ServiceResult<Case> {
+        // Get current case first
+        let mut case = self.get_case(id, scope).await?;
+        
+        // Update fields
+        if let Some(title) = request.title {
+            case.title = title;
+        }
+        if let Some(description) = request.description {
+            case.description = Some(description);
+        }
+        if let Some(status) = request.status {
+            case.status = status;
+        }
+        if let Some(priority) = request.priority {
+            case.priority = priority;
+        }
+        if let Some(assigned_to) = request.assigned_to {
+            case.assigned_to = Some(assigned_to);
+        }
+        if let Some(tags) = &request.tags {
+            case.tags = normalize_tags(tags);
+        }
+        self.save_case(scope, case, request.tags.is_some()).await
+    }
+
+    pub async fn get_user(&self, id: Uuid) -> ServiceResult<User> {
+        sqlx::query("SELECT * FROM users WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+            .map(|row| user_from_row(&row))
+            .ok_or_else(|| ServiceError::NotFound(format!("User with id {} not found", id)))
+    }
+
+    /// The user who owns the case.
+    pub async fn get_case_owner(&self, id: Uuid, scope: Scope) -> ServiceResult<User> {
+        sqlx::query(
+            r#"
+            SELECT u.* FROM users u
+            JOIN cases c ON c.user_id = u.id
+            WHERE c.id = $1 AND ($2::uuid IS NULL OR c.user_id = $2)
+            "#
+        )
+        .bind(id)
+        .bind(scope.owner())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .map(|row| user_from_row(&row))
+        .ok_or_else(|| ServiceError::NotFound(format!("Case with id {} not found", id)))
+    }
+
+    /// Hands case `id` with its tasks, conversation and revisions to the
+    /// active user with `email`, and returns it with its new owner. The
+    /// previous owner's offline clients see the tasks as deleted.
+    pub async fn transfer_case(&self, id: Uuid, scope: Scope, email: &str) -> ServiceResult<(Case, User)> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let owner: Uuid = sqlx::query_scalar(
+            "SELECT user_id FROM cases WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2) FOR UPDATE"
+        )
+        .bind(id)
+        .bind(scope.owner())
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| ServiceError::NotFound(format!("Case with id {} not found", id)))?;
+
+        let recipient = sqlx::query("SELECT * FROM users WHERE email = $1 AND is_active = true")
+            .bind(email)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?
+            .map(|row| user_from_row(&row))
+            .ok_or_else(|| ServiceError::NotFound(format!("No active user with email {}", email)))?;
+        if recipient.id == owner {
+            return Err(ServiceError::BadRequest("The case already belongs to that user".to_string()));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO task_deletions (task_id, user_id, deleted_at)
+            SELECT id, user_id, NOW() FROM tasks WHERE case_id = $1
+            ON CONFLICT (task_id) DO UPDATE SET user_id = EXCLUDED.user_id, deleted_at = EXCLUDED.deleted_at
+            "#
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        sqlx::query(
+            r#"
+            UPDATE revisions SET user_id = $2
+            WHERE entity_id = $1 OR entity_id IN (SELECT id FROM tasks WHERE case_id = $1)
+            "#
+        )
+        .bind(id)
+        .bind(recipient.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        sqlx::query("UPDATE conversation_entries SET user_id = $2 WHERE case_id = $1")
+            .bind(id)
+            .bind(recipient.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        sqlx::query("UPDATE follow_up_questions SET user_id = $2 WHERE case_id = $1")
+            .bind(id)
+            .bind(recipient.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        let task_rows = sqlx::query(&format!(
+            "UPDATE tasks SET user_id = $2, updated_at = NOW() WHERE case_id = $1 RETURNING *, {}",
+            TASK_TAGS
+        ))
+        .bind(id)
+        .bind(recipient.id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        for row in &task_rows {
+            let task = task_from_row(row)?;
+            insert_outbox_event(&mut tx, "task.updated", task.id, &task).await?;
+        }
+
+        let row = sqlx::query(&format!(
+            "UPDATE cases SET user_id = $2, updated_at = NOW() WHERE id = $1 RETURNING *, {}",
+            CASE_TAGS
+        ))
+        .bind(id)
+        .bind(recipient.id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        let case = case_from_row(&row)?;
+        insert_outbox_event(&mut tx, "case.updated", id, &case).await?;
+
+        tx.commit().await.map_err(db_error)?;
+        Ok((case, recipient))
+    }
+
+    /// Writes back every field of `case` a restored revision may have
+    /// changed.
+    pub async fn restore_case(&self, scope: Scope, mut case: Case) -> ServiceResult<Case> {
+        case.tags = normalize_tags(&case.tags);
+        self.save_case(scope, case, true).await
+    }
+
+    /// Writes the editable fields of `case`, and its tags when
+    /// `tags_changed`, stamping the update and SLA clocks.
+    async fn save_case(&self, scope: Scope, mut case: Case, tags_changed: bool) -> ServiceResult<Case> {
+        let id = case.id;
+        case.updated_at = Utc::now();
+
+        // SLA clocks: the first move out of Open is the response, and a
+        // resolution only stands while the case stays resolved.
+        if case.status != CaseStatus::Open && case.first_response_at.is_none() {
+            case.first_response_at = Some(case.updated_at);
+        }
+        match case.status {
+            CaseStatus::Resolved | CaseStatus::Closed | CaseStatus::Archived => {
+                case.resolved_at.get_or_insert(case.updated_at);
+            }
+            CaseStatus::Open | CaseStatus::InProgress | CaseStatus::Waiting => case.resolved_at = None,
+        }
+
+        // Update in database
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE cases 
+            SET title = $2, description = $3, status = $4, priority = $5, updated_at = $6, assigned_to = $7,
+                first_response_at = $8, resolved_at = $9
+            WHERE id = $1 AND ($10::uuid IS NULL OR user_id = $10)
+            "#
+        )
+        .bind(id)
+        .bind(&case.title)
+        .bind(&case.description)
+        .bind(serde_json::to_string(&case.status).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(serde_json::to_string(&case.priority).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(case.updated_at)
+        .bind(&case.assigned_to)
+        .bind(case.first_response_at)
+        .bind(case.resolved_at)
+        .bind(scope.owner())
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("Case with id {} not found", id)));
+        }
+
+        if tags_changed {
+            replace_tags(&mut tx, "case_tags", "case_id", id, &case.tags).await.map_err(db_error)?;
+        }
+        insert_outbox_event(&mut tx, "case.updated", id, &case).await?;
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(case)
+    }
+
+    pub async fn list_cases(&self, scope: Scope, status: Option<CaseStatus>) -> ServiceResult<Vec<Case>> {
+        let status = status
+            .map(|status| serde_json::to_string(&status))
+            .transpose()
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let rows = sqlx::query(&format!(
+            "SELECT *, {} FROM cases WHERE ($1::uuid IS NULL OR user_id = $1) AND ($2::varchar IS NULL OR status = $2) ORDER BY created_at DESC",
+            CASE_TAGS
+        ))
+        .bind(scope.owner())
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        let mut cases = Vec::new();
+        for row in rows {
+            cases.push(Case {
+                id: row.get("id"),
+                user_id: row.get("user_id"),
+                title: row.get("title"),
+                description: row.get("description"),
+                status: serde_json::from_str(&row.get::<String, _>("status"))
+                    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+                priority: serde_json::from_str(&row.get::<String, _>("priority"))
+                    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+                assigned_to: row.get("assigned_to"),
+                metadata: row.get("metadata"),
+                tags: row.get("tags"),
+                unread_count: None,
+                first_response_at: row.get("first_response_at"),
+                resolved_at: row.get("resolved_at"),
+                sla: None,
+            });
+        }
+
+        Ok(cases)
+    }
+
+    // Retention
+    /// Archives Closed cases last updated before `cutoff` and returns how
+    /// many were archived.
+    pub async fn archive_closed_cases(&self, cutoff: DateTime<Utc>) -> ServiceResult<u64> {
+        let result = sqlx::query(
+            r#"UPDATE cases SET status = '"Archived"', updated_at = NOW() WHERE status = '"Closed"' AND updated_at < $1"#
+        )
+        .bind(cutoff)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes Archived cases last updated before `cutoff`, with their
+    /// tasks and conversation, and returns how many were deleted. The
+    /// tasks are recorded as deleted for offline clients.
+    pub async fn purge_archived_cases(&self, cutoff: DateTime<Utc>) -> ServiceResult<u64> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO task_deletions (task_id, user_id, deleted_at)
+            SELECT t.id, t.user_id, NOW()
+            FROM tasks t JOIN cases c ON c.id = t.case_id
+            WHERE c.status = '"Archived"' AND c.updated_at < $1
+            ON CONFLICT (task_id) DO UPDATE SET user_id = EXCLUDED.user_id, deleted_at = EXCLUDED.deleted_at
+            "#
+        )
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM revisions WHERE entity_id IN (
+                SELECT id FROM cases WHERE status = '"Archived"' AND updated_at < $1
+                UNION ALL
+                SELECT t.id FROM tasks t JOIN cases c ON c.id = t.case_id
+                WHERE c.status = '"Archived"' AND c.updated_at < $1
+            )
+            "#
+        )
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        let result = sqlx::query(r#"DELETE FROM cases WHERE status = '"Archived"' AND updated_at < $1"#)
+            .bind(cutoff)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(result.rows_affected())
+    }
+
+    /// Moves an archived case back to Closed. Its last update becomes now,
+    /// so the next sweep does not archive it again straight away.
+    pub async fn restore_archived_case(&self, id: Uuid, scope: Scope) -> ServiceResult<Case> {
+        let case = self.get_case(id, scope).await?;
+        if case.status != CaseStatus::Archived {
+            return Err(ServiceError::BadRequest(format!("Case {} is not archived", id)));
+        }
+
+        sqlx::query(r#"UPDATE cases SET status = '"Closed"', updated_at = NOW() WHERE id = $1 AND status = '"Archived"'"#)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        self.get_case(id, scope).await
+    }
+
+    pub async fn list_cases_for_user(&self, user_id: Uuid) -> ServiceResult<Vec<Case>> {
+        let rows = sqlx::query(&format!("SELECT *, {} FROM cases WHERE user_id = $1 ORDER BY created_at ASC", CASE_TAGS))
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(case_from_row).collect()
+    }
+
+    // Task operations
+    pub async fn create_task(&self, mut task: Task) -> ServiceResult<Task> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        task.tags = normalize_tags(&task.tags);
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO tasks (id, case_id, title, description, task_type, status, priority, due_date, created_at, updated_at, completed_at, metadata, user_id, estimate_minutes)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            "#
+        )
+        .bind(task.id)
+        .bind(task.case_id)
+        .bind(&task.title)
+        .bind(&task.description)
+        .bind(serde_json::to_string(&task.task_type).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(serde_json::to_string(&task.status).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(serde_json::to_string(&task.priority).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(task.due_date)
+        .bind(task.created_at)
+        .bind(task.updated_at)
+        .bind(task.completed_at)
+        .bind(&task.metadata)
+        .bind(task.user_id)
+        .bind(task.estimate_minutes)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        replace_tags(&mut tx, "task_tags", "task_id", task.id, &task.tags).await.map_err(db_error)?;
+        insert_outbox_event(&mut tx, "task.created", task.id, &task).await?;
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(task)
+    }
+
+    pub async fn get_task(&self, id: Uuid, scope: Scope) -> ServiceResult<Task> {
+        let row = sqlx::query(&format!("SELECT *, {} FROM tasks WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2)", TASK_TAGS))
+            .bind(id)
+            .bind(scope.owner())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+            .ok_or_else(|| ServiceError::NotFound(format!("Task with id {} not found", id)))?;
+
+        Ok(Task {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            case_id: row.get("case_id"),
+            title: row.get("title"),
+            description: row.get("description"),
+            task_type: serde_json::from_str(&row.get::<String, _>("task_type")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+            status: serde_json::from_str(&row.get::<String, _>("status")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+            priority: serde_json::from_str(&row.get::<String, _>("priority")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+            due_date: row.get("due_date"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            completed_at: row.get("completed_at"),
+            metadata: row.get("metadata"),
+            tags: row.get("tags"),
+            estimate_minutes: row.get("estimate_minutes"),
+        })
+    }
+
+    pub async fn update_task(&self, id: Uuid, scope: Scope, request: UpdateTaskRequest) -> ServiceResult<Task> {
+        let mut task = self.get_task(id, scope).await?;
+        
+        if let Some(title) = request.title {
+            task.title = title;
+        }
+        if let Some(description) = request.description {
+            task.description = Some(description);
+        }
+        if let Some(status) = request.status {
+            task.status = status;
+            if matches!(task.status, models::TaskStatus::Completed) {
+                task.completed_at = Some(Utc::now());
+            }
+        }
+        if let Some(priority) = request.priority {
+            task.priority = priority;
+        }
+        if let Some(due_date) = request.due_date {
+            task.due_date = Some(due_date);
+        }
+        if let Some(tags) = &request.tags {
+            task.tags = normalize_tags(tags);
+        }
+        if let Some(metadata) = request.metadata {
+            task.metadata = metadata;
+        }
+        if let Some(estimate_minutes) = request.estimate_minutes {
+            task.estimate_minutes = Some(estimate_minutes);
+        }
+        self.save_task(scope, task, request.tags.is_some()).await
+    }
+
+    /// Writes back every field of `task` a restored revision may have
+    /// changed.
+    pub async fn restore_task(&self, scope: Scope, mut task: Task) -> ServiceResult<Task> {
+        task.tags = normalize_tags(&task.tags);
+        self.save_task(scope, task, true).await
+    }
+
+    /// Writes the editable fields of `task`, and its tags when
+    /// `tags_changed`, stamping the update.
+    async fn save_task(&self, scope: Scope, mut task: Task, tags_changed: bool) -> ServiceResult<Task> {
+        let id = task.id;
+        task.updated_at = Utc::now();
+
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE tasks 
+            SET title = $2, description = $3, status = $4, priority = $5, due_date = $6, updated_at = $7, completed_at = $8, metadata = $9, estimate_minutes = $10
+            WHERE id = $1 AND ($11::uuid IS NULL OR user_id = $11)
+            "#
+        )
+        .bind(id)
+        .bind(&task.title)
+        .bind(&task.description)
+        .bind(serde_json::to_string(&task.status).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(serde_json::to_string(&task.priority).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(task.due_date)
+        .bind(task.updated_at)
+        .bind(task.completed_at)
+        .bind(&task.metadata)
+        .bind(task.estimate_minutes)
+        .bind(scope.owner())
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("Task with id {} not found", id)));
+        }
+
+        if tags_changed {
+            replace_tags(&mut tx, "task_tags", "task_id", id, &task.tags).await.map_err(db_error)?;
+        }
+        insert_outbox_event(&mut tx, "task.updated", id, &task).await?;
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(task)
+    }
+
+    pub async fn delete_task(&self, id: Uuid, scope: Scope) -> ServiceResult<()> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let user_id: Uuid = sqlx::query_scalar("DELETE FROM tasks WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2) RETURNING user_id")
+            .bind(id)
+            .bind(scope.owner())
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| ServiceError::NotFound(format!("Task with id {} not found", id)))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO task_deletions (task_id, user_id, deleted_at) VALUES ($1, $2, NOW())
+            ON CONFLICT (task_id) DO UPDATE SET user_id = EXCLUDED.user_id, deleted_at = EXCLUDED.deleted_at
+            "#
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        sqlx::query("DELETE FROM revisions WHERE entity_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        insert_outbox_event(&mut tx, "task.deleted", id, &serde_json::Value::Null).await?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Every task of a user, soonest due first.
+    pub async fn list_tasks_for_user(&self, user_id: Uuid) -> ServiceResult<Vec<Task>> {
+        let rows = sqlx::query(&format!(
+            "SELECT *, {} FROM tasks WHERE user_id = $1 ORDER BY due_date ASC NULLS LAST, created_at DESC",
+            TASK_TAGS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(task_from_row).collect()
+    }
+
+    /// Tasks of a user changed after `since` and the ids of those deleted
+    /// since then. Without `since` every task is returned.
+    pub async fn tasks_changed_since(&self, user_id: Uuid, since: Option<DateTime<Utc>>) -> ServiceResult<(Vec<Task>, Vec<Uuid>)> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+
+        let rows = sqlx::query(&format!(
+            "SELECT *, {} FROM tasks WHERE user_id = $1 AND ($2::timestamptz IS NULL OR updated_at > $2) ORDER BY updated_at",
+            TASK_TAGS
+        ))
+        .bind(user_id)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+        let tasks = rows.iter().map(task_from_row).collect::<ServiceResult<Vec<_>>>()?;
+
+        let deleted = match since {
+            Some(since) => sqlx::query_scalar("SELECT task_id FROM task_deletions WHERE user_id = $1 AND deleted_at > $2")
+                .bind(user_id)
+                .bind(since)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(db_error)?,
+            None => Vec::new(),
+        };
+
+        Ok((tasks, deleted))
+    }
+
+    pub async fn get_tasks_for_case(&self, case_id: Uuid, scope: Scope) -> ServiceResult<Vec<Task>> {
+        let rows = sqlx::query(&format!(
+            "SELECT *, {} FROM tasks WHERE case_id = $1 AND ($2::uuid IS NULL OR user_id = $2) ORDER BY created_at DESC",
+            TASK_TAGS
+        ))
+        .bind(case_id)
+        .bind(scope.owner())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(task_from_row).collect()
+    }
+
+    /// Tasks in `scope`, optionally only those with `status`, newest first.
+    /// Tasks with the given status, tag and type, newest first; `limit`
+    /// and `offset` page through them. `tag` must already be normalized;
+    /// `task_type` matches a type's label case-insensitively.
+    pub async fn list_tasks(
+        &self,
+        scope: Scope,
+        status: Option<TaskStatus>,
+        tag: Option<&str>,
+        task_type: Option<&str>,
+        limit: Option<i64>,
+        offset: i64,
+    ) -> ServiceResult<Vec<Task>> {
+        let status = status
+            .map(|status| serde_json::to_string(&status))
+            .transpose()
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT *, {} FROM tasks
+            WHERE ($1::uuid IS NULL OR user_id = $1)
+              AND ($2::varchar IS NULL OR status = $2)
+              AND ($3::varchar IS NULL OR EXISTS (SELECT 1 FROM task_tags WHERE task_tags.task_id = tasks.id AND task_tags.tag = $3))
+              AND ($4::varchar IS NULL OR LOWER(COALESCE(task_type::jsonb ->> 'Other', task_type::jsonb #>> '{{}}')) = LOWER($4))
+            ORDER BY created_at DESC, id
+            LIMIT $5 OFFSET $6
+            "#,
+            TASK_TAGS
+        ))
+        .bind(scope.owner())
+        .bind(status)
+        .bind(tag)
+        .bind(task_type)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(task_from_row).collect()
+    }
+
+    /// The task linked to a Jira issue, kept in the task metadata under
+    /// `jira`.
+    pub async fn find_task_by_jira_issue(&self, scope: Scope, key: &str) -> ServiceResult<Task> {
+        let row = sqlx::query(&format!(
+            "SELECT *, {} FROM tasks WHERE ($1::uuid IS NULL OR user_id = $1) AND metadata->'jira'->>'key' = $2",
+            TASK_TAGS
+        ))
+        .bind(scope.owner())
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("No task is linked to Jira issue {}", key)))?;
+
+        task_from_row(&row)
+    }
+
+    // Conversation operations
+    pub async fn get_conversation_history(&self, case_id: Uuid, scope: Scope) -> ServiceResult<Vec<ConversationEntry>> {
+        self.get_conversation_page(case_id, scope, None, None, None, None).await
+    }
+
+    /// Entries of a case after `since` and before `before`, optionally only
+    /// those from `sender`, oldest first.  With a `limit` only the latest
+    /// `limit` matching entries are returned.
+    pub async fn get_conversation_page(
+        &self,
+        case_id: Uuid,
+        scope: Scope,
+        since: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+        sender: Option<&MessageSender>,
+        limit: Option<i64>,
+    ) -> ServiceResult<Vec<ConversationEntry>> {
+        let sender = sender
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM (
+                SELECT * FROM conversation_entries
+                WHERE case_id = $1
+                  AND ($2::timestamptz IS NULL OR timestamp > $2)
+                  AND ($3::timestamptz IS NULL OR timestamp < $3)
+                  AND ($4::varchar IS NULL OR sender = $4)
+                  AND EXISTS (SELECT 1 FROM cases WHERE id = $1 AND ($6::uuid IS NULL OR user_id = $6))
+                ORDER BY timestamp DESC
+                LIMIT $5
+            ) page
+            ORDER BY timestamp ASC
+            "#,
+        )
+            .bind(case_id)
+            .bind(since)
+            .bind(before)
+            .bind(sender)
+            .bind(limit)
+            .bind(scope.owner())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(ConversationEntry {
+                id: row.get("id"),
+                user_id: row.get("user_id"),
+                case_id: row.get("case_id"),
+                message: row.get("message"),
+                sender: serde_json::from_str(&row.get::<String, _>("sender")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+                timestamp: row.get("timestamp"),
+                metadata: row.get("metadata"),
+            });
+        }
+
+        Ok(entries)
+    }
+
+    pub async fn add_conversation_entry(&self, entry: ConversationEntry) -> ServiceResult<ConversationEntry> {
+        sqlx::query(
+            r#"
+            INSERT INTO conversation_entries (id, case_id, message, sender, timestamp, metadata, user_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#
+        )
+        .bind(entry.id)
+        .bind(entry.case_id)
+        .bind(&entry.message)
+        .bind(serde_json::to_string(&entry.sender).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(entry.timestamp)
+        .bind(&entry.metadata)
+        .bind(entry.user_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(entry)
+    }
+
+    /// Records the mentions of `emails` in `entry` by `author_id`, keeping
+    /// only active members of the author's organization other than the
+    /// author. Both have to be members as an administrator added them.
+    pub async fn record_mentions(
+        &self,
+        entry: &ConversationEntry,
+        author_id: Uuid,
+        emails: &[String],
+    ) -> ServiceResult<Vec<Mention>> {
+        let rows = sqlx::query(
+            r#"
+            INSERT INTO mentions (id, entry_id, case_id, user_id, author_id, created_at)
+            SELECT gen_random_uuid(), $1, $2, member.id, author.user_id, NOW()
+            FROM organization_members author
+            JOIN organization_members colleague ON colleague.organization = author.organization
+            JOIN users member ON member.id = colleague.user_id
+            WHERE author.user_id = $3
+              AND member.id <> author.user_id
+              AND member.is_active
+              AND LOWER(member.email) = ANY($4)
+            ON CONFLICT (entry_id, user_id) DO NOTHING
+            RETURNING *
+            "#
+        )
+        .bind(entry.id)
+        .bind(entry.case_id)
+        .bind(author_id)
+        .bind(emails)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Mention {
+                id: row.get("id"),
+                entry_id: row.get("entry_id"),
+                case_id: row.get("case_id"),
+                user_id: row.get("user_id"),
+                author_id: row.get("author_id"),
+                message: entry.message.clone(),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    /// Where `user_id` was mentioned, newest first.
+    pub async fn list_mentions(&self, user_id: Uuid, limit: i64) -> ServiceResult<Vec<Mention>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT m.*, e.message FROM mentions m
+            JOIN conversation_entries e ON e.id = m.entry_id
+            WHERE m.user_id = $1
+            ORDER BY m.created_at DESC
+            LIMIT $2
+            "#
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Mention {
+                id: row.get("id"),
+                entry_id: row.get("entry_id"),
+                case_id: row.get("case_id"),
+                user_id: row.get("user_id"),
+                author_id: row.get("author_id"),
+                message: row.get("message"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    // Follow-up question operations
+    pub async fn create_follow_up_question(&self, question: FollowUpQuestion) -> ServiceResult<FollowUpQuestion> {
+        let serialization_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+        sqlx::query(
+            r#"
+            INSERT INTO follow_up_questions (id, case_id, task_id, user_id, question, missing, channel, recipient, status, created_at, answered_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#
+        )
+        .bind(question.id)
+        .bind(question.case_id)
+        .bind(question.task_id)
+        .bind(question.user_id)
+        .bind(&question.question)
+        .bind(serde_json::to_value(&question.missing).map_err(serialization_error)?)
+        .bind(serde_json::to_value(&question.channel).map_err(serialization_error)?)
+        .bind(&question.recipient)
+        .bind(serde_json::to_string(&question.status).map_err(serialization_error)?)
+        .bind(question.created_at)
+        .bind(question.answered_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(question)
+    }
+
+    /// The case's questions, oldest first, optionally of one status.
+    pub async fn list_follow_up_questions(
+        &self,
+        case_id: Uuid,
+        status: Option<QuestionStatus>,
+    ) -> ServiceResult<Vec<FollowUpQuestion>> {
+        let status = status
+            .map(|status| serde_json::to_string(&status))
+            .transpose()
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM follow_up_questions
+            WHERE case_id = $1 AND ($2::varchar IS NULL OR status = $2)
+            ORDER BY created_at
+            "#
+        )
+        .bind(case_id)
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(follow_up_question_from_row).collect()
+    }
+
+    /// Marks the case's pending questions among `ids` answered and returns
+    /// them.
+    pub async fn answer_follow_up_questions(&self, case_id: Uuid, ids: &[Uuid]) -> ServiceResult<Vec<FollowUpQuestion>> {
+        let serialization_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+        let rows = sqlx::query(
+            r#"
+            UPDATE follow_up_questions SET status = $3, answered_at = NOW()
+            WHERE case_id = $1 AND id = ANY($2) AND status = $4
+            RETURNING *
+            "#
+        )
+        .bind(case_id)
+        .bind(ids)
+        .bind(serde_json::to_string(&QuestionStatus::Answered).map_err(serialization_error)?)
+        .bind(serde_json::to_string(&QuestionStatus::Pending).map_err(serialization_error)?)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(follow_up_question_from_row).collect()
+    }
+
+    // Workflow operations
+    /// The stored workflow of the case, or the default one of a case whose
+    /// workflow hasn't been changed.
+    pub async fn get_case_workflow(&self, case_id: Uuid) -> ServiceResult<CaseWorkflow> {
+        let row = sqlx::query("SELECT * FROM case_workflows WHERE case_id = $1")
+            .bind(case_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        if let Some(row) = row {
+            return case_workflow_from_row(&row);
+        }
+
+        Ok(CaseWorkflow {
+            id: Uuid::new_v4(),
+            case_id,
+            current_step: "initial".to_string(),
+            steps: vec![
+                WorkflowStep {
+                    name: "initial".to_string(),
+                    description: "Case created".to_string(),
+                    status: StepStatus::Completed,
+                    required_actions: vec![],
+                    completed_at: Some(Utc::now()),
+                },
+                WorkflowStep {
+                    name: "processing".to_string(),
+                    description: "Processing tasks".to_string(),
+                    status: StepStatus::Active,
+                    required_actions: vec!["Complete tasks".to_string()],
+                    completed_at: None,
+                },
+            ],
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        })
+    }
+
+    /// Stores the workflow of its case, replacing the previous one.
+    pub async fn update_case_workflow(&self, workflow: CaseWorkflow) -> ServiceResult<CaseWorkflow> {
+        let steps = serde_json::to_value(&workflow.steps)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let row = sqlx::query(
+            r#"
+            INSERT INTO case_workflows (case_id, id, current_step, steps, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            ON CONFLICT (case_id) DO UPDATE
+            SET current_step = EXCLUDED.current_step, steps = EXCLUDED.steps, updated_at = NOW()
+            RETURNING *
+            "#
+        )
+        .bind(workflow.case_id)
+        .bind(workflow.id)
+        .bind(&workflow.current_step)
+        .bind(steps)
+        .bind(workflow.created_at)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                ServiceError::NotFound(format!("Case with id {} not found", workflow.case_id))
+            }
+            e => ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)),
+        })?;
+
+        case_workflow_from_row(&row)
+    }
+
+    // Administration
+    /// Users whose email or name contains `search`, optionally only active
+    /// or inactive ones, newest first.
+    pub async fn search_users(&self, search: Option<&str>, active: Option<bool>, limit: i64, offset: i64) -> ServiceResult<Vec<User>> {
+        let pattern = search.map(|search| format!("%{}%", search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")));
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM users
+            WHERE ($1::varchar IS NULL OR email ILIKE $1 OR full_name ILIKE $1)
+              AND ($2::boolean IS NULL OR is_active = $2)
+            ORDER BY created_at DESC
+            LIMIT $3 OFFSET $4
+            "#
+        )
+        .bind(pattern)
+        .bind(active)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows.iter().map(user_from_row).collect())
+    }
+
+    /// Activates or deactivates an account. Deactivating also ends every
+    /// session of the user.
+    pub async fn set_user_active(&self, user_id: Uuid, active: bool, audit: &AdminAuditEntry) -> ServiceResult<User> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let row = sqlx::query("UPDATE users SET is_active = $2, updated_at = NOW() WHERE id = $1 RETURNING *")
+            .bind(user_id)
+            .bind(active)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| ServiceError::NotFound(format!("User with id {} not found", user_id)))?;
+
+        if !active {
+            sqlx::query("DELETE FROM user_sessions WHERE user_id = $1")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+
+        insert_admin_audit(&mut tx, audit).await?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(user_from_row(&row))
+    }
+
+    pub async fn user_usage(&self, user_id: Uuid) -> ServiceResult<UserUsage> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                u.id, u.email, u.is_active, u.email_verified_at, u.last_login,
+                (SELECT COUNT(*) FROM cases WHERE user_id = u.id) AS cases,
+                (SELECT COUNT(*) FROM cases WHERE user_id = u.id
+                    AND status NOT IN ('"Resolved"', '"Closed"', '"Archived"')) AS open_cases,
+                (SELECT COUNT(*) FROM tasks WHERE user_id = u.id) AS tasks,
+                (SELECT COUNT(*) FROM tasks WHERE user_id = u.id
+                    AND status NOT IN ('"Completed"', '"Cancelled"')) AS open_tasks,
+                (SELECT COUNT(*) FROM conversation_entries WHERE user_id = u.id) AS conversation_entries,
+                (SELECT COUNT(*) FROM user_sessions WHERE user_id = u.id AND expires_at > NOW()) AS active_sessions,
+                GREATEST(
+                    (SELECT MAX(updated_at) FROM cases WHERE user_id = u.id),
+                    (SELECT MAX(updated_at) FROM tasks WHERE user_id = u.id),
+                    (SELECT MAX(timestamp) FROM conversation_entries WHERE user_id = u.id)
+                ) AS last_activity
+            FROM users u
+            WHERE u.id = $1
+            "#
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("User with id {} not found", user_id)))?;
+
+        Ok(UserUsage {
+            user_id: row.get("id"),
             email: row.get("email"),
-            password_hash: row.get("password_hash"),
-            full_name: row.get("full_name"),
-            organization: row.get("organization"),
             is_active: row.get("is_active"),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
+            email_verified_at: row.get("email_verified_at"),
+            cases: row.get("cases"),
+            open_cases: row.get("open_cases"),
+            tasks: row.get("tasks"),
+            open_tasks: row.get("open_tasks"),
+            conversation_entries: row.get("conversation_entries"),
+            active_sessions: row.get("active_sessions"),
             last_login: row.get("last_login"),
-            metadata: row.get("metadata"),
+            last_activity: row.get("last_activity"),
+        })
+    }
+
+    /// Issues a new verification token for the user's email, replacing any
+    /// earlier one. Fails when the email is already verified.
+    pub async fn create_email_verification(
+        &self,
+        user_id: Uuid,
+        expires_at: DateTime<Utc>,
+        audit: &AdminAuditEntry,
+    ) -> ServiceResult<EmailVerification> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let row = sqlx::query("SELECT email, email_verified_at FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| ServiceError::NotFound(format!("User with id {} not found", user_id)))?;
+        if row.get::<Option<DateTime<Utc>>, _>("email_verified_at").is_some() {
+            return Err(ServiceError::BadRequest(format!("Email of user {} is already verified", user_id)));
+        }
+
+        let token = Uuid::new_v4().simple().to_string();
+        sqlx::query(
+            r#"
+            INSERT INTO email_verifications (user_id, token_hash, expires_at) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE SET token_hash = EXCLUDED.token_hash, expires_at = EXCLUDED.expires_at
+            "#
+        )
+        .bind(user_id)
+        .bind(token_hash(&token))
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        insert_admin_audit(&mut tx, audit).await?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(EmailVerification { user_id, email: row.get("email"), token, expires_at })
+    }
+
+    /// Marks the email of the user holding `token` verified and spends the
+    /// token.
+    pub async fn verify_email(&self, token: &str) -> ServiceResult<User> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let user_id: Uuid = sqlx::query_scalar(
+            "DELETE FROM email_verifications WHERE token_hash = $1 AND expires_at > NOW() RETURNING user_id"
+        )
+        .bind(token_hash(token))
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| ServiceError::BadRequest("Invalid or expired verification token".to_string()))?;
+
+        let row = sqlx::query("UPDATE users SET email_verified_at = NOW(), updated_at = NOW() WHERE id = $1 RETURNING *")
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(user_from_row(&row))
+    }
+
+    /// Opens a session as an active user on behalf of `audit.actor`. The
+    /// session records who is impersonating.
+    pub async fn create_impersonation_session(
+        &self,
+        user_id: Uuid,
+        expires_at: DateTime<Utc>,
+        audit: &AdminAuditEntry,
+    ) -> ServiceResult<(User, UserSession)> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let row = sqlx::query("SELECT * FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| ServiceError::NotFound(format!("User with id {} not found", user_id)))?;
+        let user = user_from_row(&row);
+        if !user.is_active {
+            return Err(ServiceError::BadRequest(format!("User {} is not active", user_id)));
+        }
+        // Impersonating staff would lend their role.
+        let staff: Option<String> = sqlx::query_scalar("SELECT role FROM staff_roles WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        if staff.is_some() {
+            return Err(ServiceError::BadRequest(format!("User {} is staff and can't be impersonated", user_id)));
+        }
+
+        let now = Utc::now();
+        let session = UserSession {
+            id: Uuid::new_v4(),
+            user_id,
+            session_token: Uuid::new_v4().to_string(),
+            expires_at,
+            created_at: now,
+            last_accessed: now,
+            ip_address: None,
+            user_agent: None,
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO user_sessions (id, user_id, session_token, expires_at, created_at, last_accessed, impersonated_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#
+        )
+        .bind(session.id)
+        .bind(session.user_id)
+        .bind(&session.session_token)
+        .bind(session.expires_at)
+        .bind(session.created_at)
+        .bind(session.last_accessed)
+        .bind(&audit.actor)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        insert_admin_audit(&mut tx, audit).await?;
+        tx.commit().await.map_err(db_error)?;
+        Ok((user, session))
+    }
+
+    /// Audit entries, newest first, optionally for one user.
+    pub async fn staff_role(&self, user_id: Uuid) -> ServiceResult<Option<StaffRole>> {
+        let role: Option<String> = sqlx::query_scalar("SELECT role FROM staff_roles WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(role.as_deref().and_then(StaffRole::from_name))
+    }
+
+    /// Grants `role` to the user, or revokes their role when `None`.
+    pub async fn set_staff_role(&self, user_id: Uuid, role: Option<StaffRole>, audit: &AdminAuditEntry) -> ServiceResult<()> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        sqlx::query("SELECT 1 FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| ServiceError::NotFound(format!("User with id {} not found", user_id)))?;
+        match role {
+            Some(role) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO staff_roles (user_id, role, granted_at) VALUES ($1, $2, NOW())
+                    ON CONFLICT (user_id) DO UPDATE SET role = EXCLUDED.role, granted_at = EXCLUDED.granted_at
+                    "#
+                )
+                .bind(user_id)
+                .bind(role.as_str())
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+            }
+            None => {
+                sqlx::query("DELETE FROM staff_roles WHERE user_id = $1")
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(db_error)?;
+            }
+        }
+
+        insert_admin_audit(&mut tx, audit).await?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(())
+    }
+
+    /// The account registered with `email`, if any, ignoring case.
+    pub async fn find_user_by_email(&self, email: &str) -> ServiceResult<Option<User>> {
+        let row = sqlx::query("SELECT * FROM users WHERE LOWER(email) = LOWER($1)")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(row.as_ref().map(user_from_row))
+    }
+
+    pub async fn list_admin_audit(&self, target_user_id: Option<Uuid>, limit: i64) -> ServiceResult<Vec<AdminAuditEntry>> {
+        let rows = sqlx::query(
+            "SELECT * FROM admin_audit_log WHERE ($1::uuid IS NULL OR target_user_id = $1) ORDER BY created_at DESC LIMIT $2"
+        )
+        .bind(target_user_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(admin_audit_from_row).collect()
+    }
+
+    // Account deletion
+    /// Records a pending deletion, deactivates the account and revokes every
+    /// session. Nothing is removed until the deletion is confirmed.
+    pub async fn request_account_deletion(
+        &self,
+        user_id: Uuid,
+        mode: AccountDeletionMode,
+        scheduled_for: chrono::DateTime<Utc>,
+    ) -> ServiceResult<AccountDeletion> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let ser_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+
+        let deletion = AccountDeletion {
+            user_id,
+            mode,
+            status: AccountDeletionStatus::Pending,
+            requested_at: Utc::now(),
+            scheduled_for,
+            completed_at: None,
+        };
+
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO account_deletions (user_id, mode, status, requested_at, scheduled_for, completed_at)
+            VALUES ($1, $2, $3, $4, $5, NULL)
+            ON CONFLICT (user_id) DO UPDATE SET mode = EXCLUDED.mode, status = EXCLUDED.status,
+                requested_at = EXCLUDED.requested_at, scheduled_for = EXCLUDED.scheduled_for, completed_at = NULL
+            "#
+        )
+        .bind(deletion.user_id)
+        .bind(serde_json::to_string(&deletion.mode).map_err(ser_error)?)
+        .bind(serde_json::to_string(&deletion.status).map_err(ser_error)?)
+        .bind(deletion.requested_at)
+        .bind(deletion.scheduled_for)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        sqlx::query("UPDATE users SET is_active = false, updated_at = $1 WHERE id = $2")
+            .bind(deletion.requested_at)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        sqlx::query("DELETE FROM user_sessions WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(deletion)
+    }
+
+    pub async fn get_account_deletion(&self, user_id: Uuid) -> ServiceResult<AccountDeletion> {
+        let row = sqlx::query("SELECT * FROM account_deletions WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        let row = row.ok_or_else(|| ServiceError::NotFound(format!("No deletion request for user {}", user_id)))?;
+        account_deletion_from_row(&row)
+    }
+
+    pub async fn list_account_deletions(&self, status: Option<AccountDeletionStatus>) -> ServiceResult<Vec<AccountDeletion>> {
+        let rows = match status {
+            Some(status) => {
+                let status_str = serde_json::to_string(&status)
+                    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+                sqlx::query("SELECT * FROM account_deletions WHERE status = $1 ORDER BY scheduled_for ASC")
+                    .bind(status_str)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+            None => {
+                sqlx::query("SELECT * FROM account_deletions ORDER BY scheduled_for ASC")
+                    .fetch_all(&self.pool)
+                    .await
+            }
+        }
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(account_deletion_from_row).collect()
+    }
+
+    /// Withdraws a pending deletion and reactivates the account.
+    pub async fn cancel_account_deletion(&self, user_id: Uuid) -> ServiceResult<AccountDeletion> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+
+        let mut deletion = self.get_account_deletion(user_id).await?;
+        if deletion.status != AccountDeletionStatus::Pending {
+            return Err(ServiceError::BadRequest("Deletion is no longer pending".to_string()));
+        }
+        deletion.status = AccountDeletionStatus::Cancelled;
+
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        sqlx::query("UPDATE account_deletions SET status = $1 WHERE user_id = $2")
+            .bind(serde_json::to_string(&deletion.status)
+                .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        sqlx::query("UPDATE users SET is_active = true, updated_at = $1 WHERE id = $2")
+            .bind(Utc::now())
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(deletion)
+    }
+
+    /// Carries out a pending deletion: the user's data is removed or
+    /// anonymized according to the requested mode, and the user row itself
+    /// is scrubbed of personal data. The scrubbed row is kept so the
+    /// deletion record remains auditable.
+    pub async fn complete_account_deletion(&self, user_id: Uuid) -> ServiceResult<AccountDeletion> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+
+        let mut deletion = self.get_account_deletion(user_id).await?;
+        if deletion.status != AccountDeletionStatus::Pending {
+            return Err(ServiceError::BadRequest("Deletion is no longer pending".to_string()));
+        }
+        let now = Utc::now();
+        deletion.status = AccountDeletionStatus::Completed;
+        deletion.completed_at = Some(now);
+
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        match deletion.mode {
+            AccountDeletionMode::Delete => {
+                // Tasks and conversation entries cascade from their case.
+                sqlx::query("DELETE FROM cases WHERE user_id = $1")
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(db_error)?;
+            }
+            AccountDeletionMode::Anonymize => {
+                sqlx::query(
+                    "UPDATE cases SET title = '[deleted]', description = NULL, assigned_to = NULL, metadata = '{}' WHERE user_id = $1"
+                )
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+
+                sqlx::query(
+                    "UPDATE tasks SET title = '[deleted]', description = NULL, metadata = '{}' WHERE case_id IN (SELECT id FROM cases WHERE user_id = $1)"
+                )
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+
+                sqlx::query(
+                    "UPDATE conversation_entries SET message = '[deleted]', metadata = '{}' WHERE case_id IN (SELECT id FROM cases WHERE user_id = $1)"
+                )
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+            }
+        }
+
+        sqlx::query("DELETE FROM email_accounts WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        sqlx::query("DELETE FROM sso_identities WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        sqlx::query("DELETE FROM api_tokens WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        sqlx::query("DELETE FROM login_events WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        sqlx::query("DELETE FROM organization_members WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        // The email column is unique, so the placeholder is derived from the id.
+        sqlx::query(
+            r#"
+            UPDATE users SET email = $1, password_hash = '', full_name = 'Deleted user', organization = NULL,
+                is_active = false, last_login = NULL, metadata = '{}', updated_at = $2
+            WHERE id = $3
+            "#
+        )
+        .bind(format!("deleted-{}@invalid", user_id))
+        .bind(now)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        sqlx::query("UPDATE account_deletions SET status = $1, completed_at = $2 WHERE user_id = $3")
+            .bind(serde_json::to_string(&deletion.status)
+                .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+            .bind(now)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(deletion)
+    }
+
+    // Backup / restore
+    pub async fn restore_backup(
+        &self,
+        user_id: Uuid,
+        cases: Vec<CaseBackup>,
+        mode: RestoreConflictMode,
+    ) -> ServiceResult<RestoreSummary> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let ser_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+
+        // On conflict the existing row is only replaced if it already belongs
+        // to the restoring user, so a crafted backup can't take over other
+        // users' records by reusing their ids.
+        let (case_conflict, task_conflict, entry_conflict, workflow_conflict) = match mode {
+            RestoreConflictMode::Skip => (
+                "ON CONFLICT (id) DO NOTHING",
+                "ON CONFLICT (id) DO NOTHING",
+                "ON CONFLICT (id) DO NOTHING",
+                "ON CONFLICT (case_id) DO NOTHING",
+            ),
+            RestoreConflictMode::Overwrite => (
+                "ON CONFLICT (id) DO UPDATE SET title = EXCLUDED.title, description = EXCLUDED.description, \
+                 status = EXCLUDED.status, priority = EXCLUDED.priority, updated_at = EXCLUDED.updated_at, \
+                 assigned_to = EXCLUDED.assigned_to, metadata = EXCLUDED.metadata, \
+                 first_response_at = EXCLUDED.first_response_at, resolved_at = EXCLUDED.resolved_at \
+                 WHERE cases.user_id = EXCLUDED.user_id",
+                "ON CONFLICT (id) DO UPDATE SET title = EXCLUDED.title, description = EXCLUDED.description, \
+                 task_type = EXCLUDED.task_type, status = EXCLUDED.status, priority = EXCLUDED.priority, \
+                 due_date = EXCLUDED.due_date, updated_at = EXCLUDED.updated_at, \
+                 completed_at = EXCLUDED.completed_at, metadata = EXCLUDED.metadata, \
+                 estimate_minutes = EXCLUDED.estimate_minutes \
+                 WHERE tasks.user_id = EXCLUDED.user_id",
+                "ON CONFLICT (id) DO UPDATE SET message = EXCLUDED.message, sender = EXCLUDED.sender, \
+                 timestamp = EXCLUDED.timestamp, metadata = EXCLUDED.metadata \
+                 WHERE conversation_entries.user_id = EXCLUDED.user_id",
+                // Workflows have no owner of their own; the insert only goes
+                // ahead for the restoring user's cases.
+                "ON CONFLICT (case_id) DO UPDATE SET id = EXCLUDED.id, current_step = EXCLUDED.current_step, \
+                 steps = EXCLUDED.steps, created_at = EXCLUDED.created_at, updated_at = EXCLUDED.updated_at",
+            ),
+        };
+
+        let mut summary = RestoreSummary::default();
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        for backup in cases {
+            let case = backup.case;
+            let result = sqlx::query(&format!(
+                r#"
+                INSERT INTO cases (id, user_id, title, description, status, priority, created_at, updated_at, assigned_to, metadata, first_response_at, resolved_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                {}
+                "#,
+                case_conflict
+            ))
+            .bind(case.id)
+            .bind(user_id)
+            .bind(&case.title)
+            .bind(&case.description)
+            .bind(serde_json::to_string(&case.status).map_err(ser_error)?)
+            .bind(serde_json::to_string(&case.priority).map_err(ser_error)?)
+            .bind(case.created_at)
+            .bind(case.updated_at)
+            .bind(&case.assigned_to)
+            .bind(&case.metadata)
+            .bind(case.first_response_at)
+            .bind(case.resolved_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+            if result.rows_affected() == 0 {
+                summary.cases_skipped += 1;
+            } else {
+                summary.cases_restored += 1;
+                replace_tags(&mut tx, "case_tags", "case_id", case.id, &normalize_tags(&case.tags)).await.map_err(db_error)?;
+            }
+
+            for task in backup.tasks {
+                let result = sqlx::query(&format!(
+                    r#"
+                    INSERT INTO tasks (id, user_id, case_id, title, description, task_type, status, priority, due_date, created_at, updated_at, completed_at, metadata, estimate_minutes)
+                    SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14
+                    WHERE EXISTS (SELECT 1 FROM cases WHERE id = $3 AND user_id = $2)
+                    {}
+                    "#,
+                    task_conflict
+                ))
+                .bind(task.id)
+                .bind(user_id)
+                .bind(case.id)
+                .bind(&task.title)
+                .bind(&task.description)
+                .bind(serde_json::to_string(&task.task_type).map_err(ser_error)?)
+                .bind(serde_json::to_string(&task.status).map_err(ser_error)?)
+                .bind(serde_json::to_string(&task.priority).map_err(ser_error)?)
+                .bind(task.due_date)
+                .bind(task.created_at)
+                .bind(task.updated_at)
+                .bind(task.completed_at)
+                .bind(&task.metadata)
+                .bind(task.estimate_minutes)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+
+                if result.rows_affected() == 0 {
+                    summary.tasks_skipped += 1;
+                } else {
+                    summary.tasks_restored += 1;
+                    replace_tags(&mut tx, "task_tags", "task_id", task.id, &normalize_tags(&task.tags)).await.map_err(db_error)?;
+                }
+            }
+
+            for entry in backup.conversation {
+                let result = sqlx::query(&format!(
+                    r#"
+                    INSERT INTO conversation_entries (id, user_id, case_id, message, sender, timestamp, metadata)
+                    SELECT $1, $2, $3, $4, $5, $6, $7
+                    WHERE EXISTS (SELECT 1 FROM cases WHERE id = $3 AND user_id = $2)
+                    {}
+                    "#,
+                    entry_conflict
+                ))
+                .bind(entry.id)
+                .bind(user_id)
+                .bind(case.id)
+                .bind(&entry.message)
+                .bind(serde_json::to_string(&entry.sender).map_err(ser_error)?)
+                .bind(entry.timestamp)
+                .bind(&entry.metadata)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+
+                if result.rows_affected() == 0 {
+                    summary.entries_skipped += 1;
+                } else {
+                    summary.entries_restored += 1;
+                }
+            }
+
+            if let Some(workflow) = backup.workflow {
+                let result = sqlx::query(&format!(
+                    r#"
+                    INSERT INTO case_workflows (case_id, id, current_step, steps, created_at, updated_at)
+                    SELECT $1, $2, $3, $4, $5, $6
+                    WHERE EXISTS (SELECT 1 FROM cases WHERE id = $1 AND user_id = $7)
+                    {}
+                    "#,
+                    workflow_conflict
+                ))
+                .bind(case.id)
+                .bind(workflow.id)
+                .bind(&workflow.current_step)
+                .bind(serde_json::to_value(&workflow.steps).map_err(ser_error)?)
+                .bind(workflow.created_at)
+                .bind(workflow.updated_at)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+
+                if result.rows_affected() == 0 {
+                    summary.workflows_skipped += 1;
+                } else {
+                    summary.workflows_restored += 1;
+                }
+            }
+        }
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(summary)
+    }
+
+    /// Aggregates the tasks in `scope` created between `from` and `to`. The timeline has a row for every period in the range,
+    /// including empty ones.
+    pub async fn task_stats(
+        &self,
+        scope: Scope,
+        group_by: StatsGrouping,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ServiceResult<TaskStats> {
+        let user_id = scope.owner();
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let de_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e));
+        let completed = serde_json::to_string(&TaskStatus::Completed)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+
+        let summary = sqlx::query(r#"
+            SELECT
+                COUNT(*) AS total,
+                COUNT(*) FILTER (WHERE status = $4) AS completed,
+                (AVG(EXTRACT(EPOCH FROM completed_at - created_at) / 3600)
+                    FILTER (WHERE completed_at IS NOT NULL))::float8 AS average_hours
+            FROM tasks
+            WHERE ($1::uuid IS NULL OR user_id = $1) AND created_at BETWEEN $2 AND $3
+        "#)
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(&completed)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        let total: i64 = summary.get("total");
+        let completed_count: i64 = summary.get("completed");
+
+        let timeline = sqlx::query(r#"
+            WITH periods AS (
+                SELECT generate_series(date_trunc($4, $2::timestamptz), date_trunc($4, $3::timestamptz), ('1 ' || $4)::interval) AS period
+            )
+            SELECT
+                p.period,
+                (SELECT COUNT(*) FROM tasks t
+                    WHERE ($1::uuid IS NULL OR t.user_id = $1)
+                      AND t.created_at BETWEEN $2 AND $3
+                      AND date_trunc($4, t.created_at) = p.period) AS created,
+                (SELECT COUNT(*) FROM tasks t
+                    WHERE ($1::uuid IS NULL OR t.user_id = $1)
+                      AND t.completed_at BETWEEN $2 AND $3
+                      AND date_trunc($4, t.completed_at) = p.period) AS completed
+            FROM periods p
+            ORDER BY p.period
+        "#)
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(group_by.as_str())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?
+        .into_iter()
+        .map(|row| TaskStatsPeriod {
+            period: row.get("period"),
+            created: row.get("created"),
+            completed: row.get("completed"),
+        })
+        .collect();
+
+        let mut by_type = Vec::new();
+        for row in sqlx::query(r#"
+            SELECT task_type, COUNT(*) AS count FROM tasks
+            WHERE ($1::uuid IS NULL OR user_id = $1) AND created_at BETWEEN $2 AND $3
+            GROUP BY task_type ORDER BY count DESC
+        "#)
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?
+        {
+            by_type.push(TaskTypeCount {
+                task_type: serde_json::from_str(&row.get::<String, _>("task_type")).map_err(de_error)?,
+                count: row.get("count"),
+            });
+        }
+
+        let mut by_priority = Vec::new();
+        for row in sqlx::query(r#"
+            SELECT priority, COUNT(*) AS count FROM tasks
+            WHERE ($1::uuid IS NULL OR user_id = $1) AND created_at BETWEEN $2 AND $3
+            GROUP BY priority ORDER BY count DESC
+        "#)
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?
+        {
+            by_priority.push(PriorityCount {
+                priority: serde_json::from_str(&row.get::<String, _>("priority")).map_err(de_error)?,
+                count: row.get("count"),
+            });
+        }
+
+        Ok(TaskStats {
+            group_by,
+            from,
+            to,
+            total,
+            completed: completed_count,
+            completion_rate: ratio(completed_count, total),
+            average_hours_to_complete: summary.get("average_hours"),
+            timeline,
+            by_type,
+            by_priority,
+        })
+    }
+
+    /// Case throughput, task cycle time, overdue share and email conversion
+    /// in `scope`. Case close times are taken from the
+    /// resolution time of cases that are Resolved, Closed or Archived, or
+    /// their last update for cases resolved before that was recorded.
+    pub async fn productivity_stats(
+        &self,
+        scope: Scope,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ServiceResult<ProductivityStats> {
+        let user_id = scope.owner();
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+
+        let cases_per_week = sqlx::query(r#"
+            WITH periods AS (
+                SELECT generate_series(date_trunc('week', $2::timestamptz), date_trunc('week', $3::timestamptz), interval '1 week') AS period
+            )
+            SELECT
+                p.period,
+                (SELECT COUNT(*) FROM cases c
+                    WHERE ($1::uuid IS NULL OR c.user_id = $1)
+                      AND c.created_at BETWEEN $2 AND $3
+                      AND date_trunc('week', c.created_at) = p.period) AS opened,
+                (SELECT COUNT(*) FROM cases c
+                    WHERE ($1::uuid IS NULL OR c.user_id = $1)
+                      AND c.status IN ('"Resolved"', '"Closed"', '"Archived"')
+                      AND COALESCE(c.resolved_at, c.updated_at) BETWEEN $2 AND $3
+                      AND date_trunc('week', COALESCE(c.resolved_at, c.updated_at)) = p.period) AS closed
+            FROM periods p
+            ORDER BY p.period
+        "#)
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?
+        .into_iter()
+        .map(|row| CaseStatsPeriod {
+            period: row.get("period"),
+            opened: row.get("opened"),
+            closed: row.get("closed"),
+        })
+        .collect();
+
+        let tasks = sqlx::query(r#"
+            SELECT
+                (AVG(EXTRACT(EPOCH FROM completed_at - created_at) / 3600)
+                    FILTER (WHERE completed_at BETWEEN $2 AND $3))::float8 AS mean_cycle_time_hours,
+                COUNT(*) FILTER (WHERE status NOT IN ('"Completed"', '"Cancelled"')) AS open_tasks,
+                COUNT(*) FILTER (WHERE status NOT IN ('"Completed"', '"Cancelled"') AND due_date < NOW()) AS overdue_tasks
+            FROM tasks
+            WHERE ($1::uuid IS NULL OR user_id = $1)
+        "#)
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        let emails = sqlx::query(r#"
+            SELECT
+                COUNT(*) AS received,
+                COUNT(*) FILTER (WHERE EXISTS (SELECT 1 FROM tasks t WHERE t.case_id = e.case_id)) AS converted
+            FROM conversation_entries e
+            WHERE ($1::uuid IS NULL OR e.user_id = $1)
+              AND e.sender = '"User"'
+              AND e.metadata->>'channel' = 'Email'
+              AND e.timestamp BETWEEN $2 AND $3
+        "#)
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        let open_tasks: i64 = tasks.get("open_tasks");
+        let overdue_tasks: i64 = tasks.get("overdue_tasks");
+        let emails_received: i64 = emails.get("received");
+        let emails_converted: i64 = emails.get("converted");
+
+        Ok(ProductivityStats {
+            from,
+            to,
+            cases_per_week,
+            mean_cycle_time_hours: tasks.get("mean_cycle_time_hours"),
+            open_tasks,
+            overdue_tasks,
+            overdue_ratio: ratio(overdue_tasks, open_tasks),
+            emails_received,
+            emails_converted,
+            email_conversion_rate: ratio(emails_converted, emails_received),
+        })
+    }
+
+    /// Task rollup for one case. Returns NotFound when the case is outside
+    /// `scope`.
+    pub async fn case_productivity(&self, case_id: Uuid, scope: Scope) -> ServiceResult<CaseProductivity> {
+        let row = sqlx::query(r#"
+            SELECT
+                c.id,
+                EXTRACT(EPOCH FROM NOW() - c.created_at)::float8 / 3600 AS age_hours,
+                COUNT(t.id) AS total_tasks,
+                COUNT(t.id) FILTER (WHERE t.status = '"Completed"') AS completed_tasks,
+                COUNT(t.id) FILTER (WHERE t.status NOT IN ('"Completed"', '"Cancelled"')) AS open_tasks,
+                COUNT(t.id) FILTER (WHERE t.status NOT IN ('"Completed"', '"Cancelled"') AND t.due_date < NOW()) AS overdue_tasks,
+                (AVG(EXTRACT(EPOCH FROM t.completed_at - t.created_at) / 3600)
+                    FILTER (WHERE t.completed_at IS NOT NULL))::float8 AS mean_cycle_time_hours,
+                GREATEST(
+                    MAX(t.updated_at),
+                    (SELECT MAX(e.timestamp) FROM conversation_entries e WHERE e.case_id = c.id)
+                ) AS last_activity
+            FROM cases c
+            LEFT JOIN tasks t ON t.case_id = c.id
+            WHERE c.id = $1 AND ($2::uuid IS NULL OR c.user_id = $2)
+            GROUP BY c.id
+        "#)
+        .bind(case_id)
+        .bind(scope.owner())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("Case with id {} not found", case_id)))?;
+
+        Ok(CaseProductivity {
+            case_id,
+            total_tasks: row.get("total_tasks"),
+            completed_tasks: row.get("completed_tasks"),
+            open_tasks: row.get("open_tasks"),
+            overdue_tasks: row.get("overdue_tasks"),
+            mean_cycle_time_hours: row.get("mean_cycle_time_hours"),
+            age_hours: row.get("age_hours"),
+            last_activity: row.get("last_activity"),
+        })
+    }
+
+    /// Per-user rollup across all accounts, for administrators.
+    pub async fn user_productivity(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> ServiceResult<Vec<UserProductivity>> {
+        let rows = sqlx::query(r#"
+            SELECT
+                u.id,
+                u.email,
+                (SELECT COUNT(*) FROM cases c
+                    WHERE c.user_id = u.id AND c.created_at BETWEEN $1 AND $2) AS cases_opened,
+                (SELECT COUNT(*) FROM cases c
+                    WHERE c.user_id = u.id AND c.status IN ('"Resolved"', '"Closed"', '"Archived"') AND COALESCE(c.resolved_at, c.updated_at) BETWEEN $1 AND $2) AS cases_closed,
+                (SELECT COUNT(*) FROM tasks t
+                    WHERE t.user_id = u.id AND t.created_at BETWEEN $1 AND $2) AS tasks_created,
+                (SELECT COUNT(*) FROM tasks t
+                    WHERE t.user_id = u.id AND t.completed_at BETWEEN $1 AND $2) AS tasks_completed,
+                (SELECT (AVG(EXTRACT(EPOCH FROM t.completed_at - t.created_at) / 3600))::float8 FROM tasks t
+                    WHERE t.user_id = u.id AND t.completed_at BETWEEN $1 AND $2) AS mean_cycle_time_hours,
+                (SELECT COUNT(*) FROM tasks t
+                    WHERE t.user_id = u.id AND t.status NOT IN ('"Completed"', '"Cancelled"')) AS open_tasks,
+                (SELECT COUNT(*) FROM tasks t
+                    WHERE t.user_id = u.id AND t.status NOT IN ('"Completed"', '"Cancelled"') AND t.due_date < NOW()) AS overdue_tasks
+            FROM users u
+            ORDER BY u.email
+        "#)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| UserProductivity {
+                user_id: row.get("id"),
+                email: row.get("email"),
+                cases_opened: row.get("cases_opened"),
+                cases_closed: row.get("cases_closed"),
+                tasks_created: row.get("tasks_created"),
+                tasks_completed: row.get("tasks_completed"),
+                mean_cycle_time_hours: row.get("mean_cycle_time_hours"),
+                open_tasks: row.get("open_tasks"),
+                overdue_tasks: row.get("overdue_tasks"),
+            })
+            .collect())
+    }
+
+    /// Open tasks due between `from` and `to` inclusive, summed per UTC day
+    /// and user, for the tasks in `scope`. Days without due tasks are left
+    /// out.
+    pub async fn workload(&self, scope: Scope, from: NaiveDate, to: NaiveDate) -> ServiceResult<Vec<WorkloadDay>> {
+        let rows = sqlx::query(r#"
+            SELECT
+                (due_date AT TIME ZONE 'UTC')::date AS day,
+                user_id,
+                COUNT(*) AS tasks,
+                COALESCE(SUM(estimate_minutes), 0)::int8 AS estimate_minutes,
+                COUNT(*) FILTER (WHERE estimate_minutes IS NULL) AS unestimated_tasks
+            FROM tasks
+            WHERE ($1::uuid IS NULL OR user_id = $1)
+              AND status NOT IN ('"Completed"', '"Cancelled"', '"NeedsReview"')
+              AND (due_date AT TIME ZONE 'UTC')::date BETWEEN $2 AND $3
+            GROUP BY day, user_id
+            ORDER BY day, user_id
+        "#)
+        .bind(scope.owner())
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| WorkloadDay {
+                date: row.get("day"),
+                user_id: row.get("user_id"),
+                tasks: row.get("tasks"),
+                estimate_minutes: row.get("estimate_minutes"),
+                unestimated_tasks: row.get("unestimated_tasks"),
+            })
+            .collect())
+    }
+
+    // Notification Operations
+
+    /// Stores a notification. Returns `None` when the user already has a
+    /// notification of the same kind for the same subject.
+    pub async fn create_notification(&self, notification: Notification) -> ServiceResult<Option<Notification>> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO notifications (id, user_id, kind, title, message, link, subject_id, created_at, read_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NULL)
+            ON CONFLICT (user_id, kind, subject_id) DO NOTHING
+            "#
+        )
+        .bind(notification.id)
+        .bind(notification.user_id)
+        .bind(serde_json::to_string(&notification.kind).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(&notification.title)
+        .bind(&notification.message)
+        .bind(&notification.link)
+        .bind(notification.subject_id)
+        .bind(notification.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok((result.rows_affected() > 0).then_some(notification))
+    }
+
+    /// Marks the pushes of these notifications as held for later.
+    pub async fn hold_pushes(&self, ids: &[Uuid]) -> ServiceResult<()> {
+        sqlx::query("UPDATE notifications SET push_held = true WHERE id = ANY($1)")
+            .bind(ids)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(())
+    }
+
+    /// Notifications whose push is held, oldest first.
+    pub async fn held_pushes(&self) -> ServiceResult<Vec<Notification>> {
+        let rows = sqlx::query("SELECT * FROM notifications WHERE push_held ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(notification_from_row).collect()
+    }
+
+    pub async fn release_pushes(&self, ids: &[Uuid]) -> ServiceResult<()> {
+        sqlx::query("UPDATE notifications SET push_held = false WHERE id = ANY($1)")
+            .bind(ids)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(())
+    }
+
+    pub async fn list_notifications(&self, user_id: Uuid, unread_only: bool, limit: i64) -> ServiceResult<Vec<Notification>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM notifications
+            WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL)
+            ORDER BY created_at DESC
+            LIMIT $3
+            "#
+        )
+        .bind(user_id)
+        .bind(unread_only)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(notification_from_row).collect()
+    }
+
+    pub async fn count_unread_notifications(&self, user_id: Uuid) -> ServiceResult<i64> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND read_at IS NULL")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))
+    }
+
+    pub async fn mark_notification_read(&self, user_id: Uuid, id: Uuid) -> ServiceResult<Notification> {
+        let row = sqlx::query(
+            r#"
+            UPDATE notifications SET read_at = COALESCE(read_at, NOW())
+            WHERE id = $1 AND user_id = $2
+            RETURNING *
+            "#
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("Notification with id {} not found", id)))?;
+
+        notification_from_row(&row)
+    }
+
+    /// Marks every unread notification of the user as read and returns how
+    /// many were changed.
+    pub async fn mark_all_notifications_read(&self, user_id: Uuid) -> ServiceResult<u64> {
+        let result = sqlx::query("UPDATE notifications SET read_at = NOW() WHERE user_id = $1 AND read_at IS NULL")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Creates a TaskOverdue notification for every open task past its due
+    /// date that has not been notified yet and returns the new notifications.
+    pub async fn notify_overdue_tasks(&self) -> ServiceResult<Vec<Notification>> {
+        let kind = serde_json::to_string(&NotificationKind::TaskOverdue)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+
+        let rows = sqlx::query(
+            r#"
+            INSERT INTO notifications (id, user_id, kind, title, message, link, subject_id, created_at, read_at)
+            SELECT gen_random_uuid(), t.user_id, $1, 'Task overdue', t.title, '/cases/' || t.case_id, t.id, NOW(), NULL
+            FROM tasks t JOIN users u ON u.id = t.user_id
+            WHERE t.status NOT IN ('"Completed"', '"Cancelled"', '"NeedsReview"') AND t.due_date < NOW()
+              AND NOT COALESCE(u.preferences->'notifications'->'muted' ? 'TaskOverdue', false)
+            ON CONFLICT (user_id, kind, subject_id) DO NOTHING
+            RETURNING *
+            "#
+        )
+        .bind(kind)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(notification_from_row).collect()
+    }
+
+    // Task Template Operations
+    pub async fn create_task_template(&self, template: TaskTemplate) -> ServiceResult<TaskTemplate> {
+        let se_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO task_templates (id, user_id, name, title, description, task_type, priority, due_in_days, checklist, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            ON CONFLICT (user_id, name) DO NOTHING
+            "#
+        )
+        .bind(template.id)
+        .bind(template.user_id)
+        .bind(&template.name)
+        .bind(&template.title)
+        .bind(&template.description)
+        .bind(serde_json::to_string(&template.task_type).map_err(se_error)?)
+        .bind(serde_json::to_string(&template.priority).map_err(se_error)?)
+        .bind(template.due_in_days)
+        .bind(serde_json::to_value(&template.checklist).map_err(se_error)?)
+        .bind(template.created_at)
+        .bind(template.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::BadRequest(format!("A template named '{}' already exists", template.name)));
+        }
+        Ok(template)
+    }
+
+    pub async fn list_task_templates(&self, user_id: Uuid) -> ServiceResult<Vec<TaskTemplate>> {
+        let rows = sqlx::query("SELECT * FROM task_templates WHERE user_id = $1 ORDER BY name")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(task_template_from_row).collect()
+    }
+
+    pub async fn get_task_template(&self, user_id: Uuid, name: &str) -> ServiceResult<TaskTemplate> {
+        let row = sqlx::query("SELECT * FROM task_templates WHERE user_id = $1 AND name = $2")
+            .bind(user_id)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+            .ok_or_else(|| ServiceError::NotFound(format!("Template '{}' not found", name)))?;
+
+        task_template_from_row(&row)
+    }
+
+    /// Replaces the template called `name`; the request may rename it.
+    pub async fn update_task_template(&self, user_id: Uuid, name: &str, request: TaskTemplateRequest) -> ServiceResult<TaskTemplate> {
+        let se_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+
+        let row = sqlx::query(
+            r#"
+            UPDATE task_templates
+            SET name = $3, title = $4, description = $5, task_type = $6, priority = $7, due_in_days = $8, checklist = $9, updated_at = NOW()
+            WHERE user_id = $1 AND name = $2
+            RETURNING *
+            "#
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(&request.name)
+        .bind(&request.title)
+        .bind(&request.description)
+        .bind(serde_json::to_string(&request.task_type).map_err(se_error)?)
+        .bind(serde_json::to_string(&request.priority).map_err(se_error)?)
+        .bind(request.due_in_days)
+        .bind(serde_json::to_value(&request.checklist).map_err(se_error)?)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                ServiceError::BadRequest(format!("A template named '{}' already exists", request.name))
+            }
+            e => ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)),
+        })?
+        .ok_or_else(|| ServiceError::NotFound(format!("Template '{}' not found", name)))?;
+
+        task_template_from_row(&row)
+    }
+
+    pub async fn delete_task_template(&self, user_id: Uuid, name: &str) -> ServiceResult<()> {
+        let result = sqlx::query("DELETE FROM task_templates WHERE user_id = $1 AND name = $2")
+            .bind(user_id)
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("Template '{}' not found", name)));
+        }
+        Ok(())
+    }
+
+    // Tag Operations
+    /// Every tag on the user's tasks and cases with how often it is used.
+    pub async fn list_tags(&self, user_id: Uuid) -> ServiceResult<Vec<TagUsage>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT tag, SUM(tasks)::bigint AS tasks, SUM(cases)::bigint AS cases
+            FROM (
+                SELECT tt.tag, 1 AS tasks, 0 AS cases
+                FROM task_tags tt JOIN tasks t ON t.id = tt.task_id
+                WHERE t.user_id = $1
+                UNION ALL
+                SELECT ct.tag, 0, 1
+                FROM case_tags ct JOIN cases c ON c.id = ct.case_id
+                WHERE c.user_id = $1
+            ) usage
+            GROUP BY tag
+            ORDER BY tag
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| TagUsage {
+                tag: row.get("tag"),
+                tasks: row.get("tasks"),
+                cases: row.get("cases"),
+            })
+            .collect())
+    }
+
+    // Saved Filter Operations
+    pub async fn create_saved_filter(&self, saved: SavedFilter) -> ServiceResult<SavedFilter> {
+        let se_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO saved_filters (id, user_id, name, filter, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (user_id, name) DO NOTHING
+            "#
+        )
+        .bind(saved.id)
+        .bind(saved.user_id)
+        .bind(&saved.name)
+        .bind(serde_json::to_value(&saved.filter).map_err(se_error)?)
+        .bind(saved.created_at)
+        .bind(saved.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::BadRequest(format!("A filter named '{}' already exists", saved.name)));
+        }
+        Ok(saved)
+    }
+
+    pub async fn list_saved_filters(&self, user_id: Uuid) -> ServiceResult<Vec<SavedFilter>> {
+        let rows = sqlx::query("SELECT * FROM saved_filters WHERE user_id = $1 ORDER BY name")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(saved_filter_from_row).collect()
+    }
+
+    pub async fn get_saved_filter(&self, user_id: Uuid, id: Uuid) -> ServiceResult<SavedFilter> {
+        let row = sqlx::query("SELECT * FROM saved_filters WHERE user_id = $1 AND id = $2")
+            .bind(user_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+            .ok_or_else(|| ServiceError::NotFound(format!("Saved filter {} not found", id)))?;
+
+        saved_filter_from_row(&row)
+    }
+
+    pub async fn update_saved_filter(&self, user_id: Uuid, id: Uuid, request: SavedFilterRequest) -> ServiceResult<SavedFilter> {
+        let se_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+
+        let row = sqlx::query(
+            r#"
+            UPDATE saved_filters
+            SET name = $3, filter = $4, updated_at = NOW()
+            WHERE user_id = $1 AND id = $2
+            RETURNING *
+            "#
+        )
+        .bind(user_id)
+        .bind(id)
+        .bind(&request.name)
+        .bind(serde_json::to_value(&request.filter).map_err(se_error)?)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                ServiceError::BadRequest(format!("A filter named '{}' already exists", request.name))
+            }
+            e => ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)),
+        })?
+        .ok_or_else(|| ServiceError::NotFound(format!("Saved filter {} not found", id)))?;
+
+        saved_filter_from_row(&row)
+    }
+
+    pub async fn delete_saved_filter(&self, user_id: Uuid, id: Uuid) -> ServiceResult<()> {
+        let result = sqlx::query("DELETE FROM saved_filters WHERE user_id = $1 AND id = $2")
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("Saved filter {} not found", id)));
+        }
+        Ok(())
+    }
+
+    // Webhook Integration Operations
+    /// Saves an integration with the hash of its token, which must be set.
+    pub async fn create_webhook_integration(&self, integration: WebhookIntegration) -> ServiceResult<WebhookIntegration> {
+        let se_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+        let token = integration
+            .token
+            .as_deref()
+            .ok_or_else(|| ServiceError::Internal(anyhow::anyhow!("Webhook integration without a token")))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_integrations (id, user_id, name, token_hash, mapping, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#
+        )
+        .bind(integration.id)
+        .bind(integration.user_id)
+        .bind(&integration.name)
+        .bind(token_hash(token))
+        .bind(serde_json::to_value(&integration.mapping).map_err(se_error)?)
+        .bind(integration.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(integration)
+    }
+
+    pub async fn list_webhook_integrations(&self, user_id: Uuid) -> ServiceResult<Vec<WebhookIntegration>> {
+        let rows = sqlx::query("SELECT * FROM webhook_integrations WHERE user_id = $1 ORDER BY name")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(webhook_integration_from_row).collect()
+    }
+
+    /// The integration a webhook URL's token belongs to.
+    pub async fn resolve_webhook_integration(&self, token: &str) -> ServiceResult<WebhookIntegration> {
+        let row = sqlx::query("SELECT * FROM webhook_integrations WHERE token_hash = $1")
+            .bind(token_hash(token))
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+            .ok_or_else(|| ServiceError::NotFound("Webhook integration not found".to_string()))?;
+
+        webhook_integration_from_row(&row)
+    }
+
+    pub async fn delete_webhook_integration(&self, user_id: Uuid, id: Uuid) -> ServiceResult<()> {
+        let result = sqlx::query("DELETE FROM webhook_integrations WHERE user_id = $1 AND id = $2")
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("Webhook integration {} not found", id)));
+        }
+        Ok(())
+    }
+
+    // Email Account Operations
+    /// Connects a mailbox to its user, or refreshes the tokens of one the
+    /// user already connected. A mailbox connected by another user is
+    /// turned away.
+    pub async fn save_email_account(&self, account: EmailAccount) -> ServiceResult<EmailAccount> {
+        let se_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+        let row = sqlx::query(
+            r#"
+            INSERT INTO email_accounts (id, user_id, email_address, provider, is_active, oauth_token,
+                                        oauth_refresh_token, oauth_expires_at, imap_settings, scopes, created_at, updated_at, metadata)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            ON CONFLICT (lower(email_address)) DO UPDATE
+            SET provider = EXCLUDED.provider, is_active = EXCLUDED.is_active, oauth_token = EXCLUDED.oauth_token,
+                oauth_refresh_token = COALESCE(EXCLUDED.oauth_refresh_token, email_accounts.oauth_refresh_token),
+                oauth_expires_at = EXCLUDED.oauth_expires_at, imap_settings = EXCLUDED.imap_settings,
+                scopes = EXCLUDED.scopes, updated_at = EXCLUDED.updated_at
+            WHERE email_accounts.user_id = EXCLUDED.user_id
+            RETURNING *
+            "#
+        )
+        .bind(account.id)
+        .bind(account.user_id)
+        .bind(&account.email_address)
+        .bind(serde_json::to_string(&account.provider).map_err(se_error)?)
+        .bind(account.is_active)
+        .bind(&account.oauth_token)
+        .bind(&account.oauth_refresh_token)
+        .bind(account.oauth_expires_at)
+        .bind(account.imap_settings.as_ref().map(serde_json::to_value).transpose().map_err(se_error)?)
+        .bind(&account.scopes)
+        .bind(account.created_at)
+        .bind(account.updated_at)
+        .bind(&account.metadata)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| {
+            ServiceError::BadRequest(format!("{} is already connected by another user", account.email_address))
+        })?;
+
+        email_account_from_row(&row)
+    }
+
+    pub async fn list_email_accounts(&self, user_id: Uuid) -> ServiceResult<Vec<EmailAccount>> {
+        let rows = sqlx::query("SELECT * FROM email_accounts WHERE user_id = $1 ORDER BY email_address")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(email_account_from_row).collect()
+    }
+
+    /// Every mailbox, with its tokens, for the email collector, which
+    /// polls the active ones and keeps the sync state of all of them.
+    pub async fn all_email_accounts(&self) -> ServiceResult<Vec<EmailAccount>> {
+        let rows = sqlx::query("SELECT * FROM email_accounts ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(email_account_from_row).collect()
+    }
+
+    /// Stops polling a mailbox of the user and forgets its tokens. The
+    /// mailbox stays listed until it is connected again or deleted.
+    pub async fn disconnect_email_account(&self, user_id: Uuid, id: Uuid) -> ServiceResult<EmailAccount> {
+        let row = sqlx::query(
+            r#"
+            UPDATE email_accounts
+            SET is_active = FALSE, oauth_token = NULL, oauth_refresh_token = NULL, oauth_expires_at = NULL,
+                updated_at = NOW()
+            WHERE id = $1 AND user_id = $2
+            RETURNING *
+            "#
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("Email account {} not found", id)))?;
+
+        email_account_from_row(&row)
+    }
+
+    /// Records how polling a mailbox goes.
+    pub async fn set_email_account_health(&self, id: Uuid, health: &MailboxHealth) -> ServiceResult<MailboxHealth> {
+        let value = serde_json::to_value(health)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let result = sqlx::query("UPDATE email_accounts SET health = $2 WHERE id = $1")
+            .bind(id)
+            .bind(value)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("Email account {} not found", id)));
+        }
+        Ok(health.clone())
+    }
+
+    pub async fn delete_email_account(&self, user_id: Uuid, id: Uuid) -> ServiceResult<()> {
+        let result = sqlx::query("DELETE FROM email_accounts WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("Email account {} not found", id)));
+        }
+        Ok(())
+    }
+
+    // Push Subscription Operations
+    /// Registers a browser for push delivery. Re-subscribing an endpoint
+    /// moves it to the current user and refreshes its keys.
+    pub async fn save_push_subscription(&self, subscription: PushSubscription) -> ServiceResult<PushSubscription> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO push_subscriptions (id, user_id, endpoint, p256dh, auth, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (endpoint) DO UPDATE
+            SET user_id = EXCLUDED.user_id, p256dh = EXCLUDED.p256dh, auth = EXCLUDED.auth
+            RETURNING *
+            "#
+        )
+        .bind(subscription.id)
+        .bind(subscription.user_id)
+        .bind(&subscription.endpoint)
+        .bind(&subscription.p256dh)
+        .bind(&subscription.auth)
+        .bind(subscription.created_at)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(push_subscription_from_row(&row))
+    }
+
+    pub async fn list_push_subscriptions(&self, user_id: Uuid) -> ServiceResult<Vec<PushSubscription>> {
+        let rows = sqlx::query("SELECT * FROM push_subscriptions WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows.iter().map(push_subscription_from_row).collect())
+    }
+
+    /// Removes a subscription. `user_id` is `None` when the push service
+    /// reported the endpoint as gone.
+    pub async fn delete_push_subscription(&self, endpoint: &str, user_id: Option<Uuid>) -> ServiceResult<bool> {
+        let result = sqlx::query("DELETE FROM push_subscriptions WHERE endpoint = $1 AND ($2::uuid IS NULL OR user_id = $2)")
+            .bind(endpoint)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns the stored VAPID private key, storing `generated` first if
+    /// there is none yet.
+    pub async fn vapid_private_key_or_insert(&self, generated: &str) -> ServiceResult<String> {
+        sqlx::query("INSERT INTO vapid_keys (private_key, created_at) VALUES ($1, NOW()) ON CONFLICT (id) DO NOTHING")
+            .bind(generated)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        sqlx::query_scalar("SELECT private_key FROM vapid_keys")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))
+    }
+
+    pub async fn create_quarantined_message(&self, message: QuarantinedMessage) -> ServiceResult<QuarantinedMessage> {
+        let serialization_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+        sqlx::query(
+            r#"
+            INSERT INTO quarantined_messages (id, user_id, message, reasons, status, created_at, reviewed_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#
+        )
+        .bind(message.id)
+        .bind(message.user_id)
+        .bind(serde_json::to_value(&message.message).map_err(serialization_error)?)
+        .bind(serde_json::to_value(&message.reasons).map_err(serialization_error)?)
+        .bind(serde_json::to_string(&message.status).map_err(serialization_error)?)
+        .bind(message.created_at)
+        .bind(message.reviewed_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(message)
+    }
+
+    /// Quarantined messages, oldest first. `user_id` limits them to one
+    /// user's messages; `None` lists every message.
+    pub async fn list_quarantined_messages(
+        &self,
+        user_id: Option<Uuid>,
+        status: Option<QuarantineStatus>,
+    ) -> ServiceResult<Vec<QuarantinedMessage>> {
+        let status = status
+            .map(|status| serde_json::to_string(&status))
+            .transpose()
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM quarantined_messages
+            WHERE ($1::uuid IS NULL OR user_id = $1) AND ($2::varchar IS NULL OR status = $2)
+            ORDER BY created_at
+            "#
+        )
+        .bind(user_id)
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(quarantined_message_from_row).collect()
+    }
+
+    /// Records the review of a pending message. Messages already reviewed
+    /// can't be reviewed again, so a message is released at most once.
+    pub async fn review_quarantined_message(
+        &self,
+        id: Uuid,
+        user_id: Option<Uuid>,
+        status: QuarantineStatus,
+    ) -> ServiceResult<QuarantinedMessage> {
+        if status == QuarantineStatus::Pending {
+            return Err(ServiceError::BadRequest("A review must release or discard the message".to_string()));
+        }
+        let serialization_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+        let row = sqlx::query(
+            r#"
+            UPDATE quarantined_messages SET status = $3, reviewed_at = NOW()
+            WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2) AND status = $4
+            RETURNING *
+            "#
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(serde_json::to_string(&status).map_err(serialization_error)?)
+        .bind(serde_json::to_string(&QuarantineStatus::Pending).map_err(serialization_error)?)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("Pending quarantined message with id {} not found", id)))?;
+
+        quarantined_message_from_row(&row)
+    }
+
+    /// Records feedback on a task, replacing earlier feedback on it.
+    pub async fn upsert_task_feedback(&self, feedback: TaskFeedback) -> ServiceResult<TaskFeedback> {
+        let verdict = serde_json::to_string(&feedback.verdict)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let row = sqlx::query(
+            r#"
+            INSERT INTO task_feedback (id, user_id, task_id, verdict, message, extracted, corrected, comment, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (task_id) DO UPDATE SET
+                verdict = EXCLUDED.verdict,
+                corrected = EXCLUDED.corrected,
+                comment = EXCLUDED.comment,
+                created_at = EXCLUDED.created_at
+            RETURNING *
+            "#
+        )
+        .bind(feedback.id)
+        .bind(feedback.user_id)
+        .bind(feedback.task_id)
+        .bind(verdict)
+        .bind(&feedback.message)
+        .bind(&feedback.extracted)
+        .bind(&feedback.corrected)
+        .bind(&feedback.comment)
+        .bind(feedback.created_at)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        task_feedback_from_row(&row)
+    }
+
+    /// Task feedback, oldest first. `user_id` limits it to one user's
+    /// feedback; `None` lists all of it.
+    pub async fn list_task_feedback(
+        &self,
+        user_id: Option<Uuid>,
+        verdict: Option<FeedbackVerdict>,
+    ) -> ServiceResult<Vec<TaskFeedback>> {
+        let verdict = verdict
+            .map(|verdict| serde_json::to_string(&verdict))
+            .transpose()
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM task_feedback
+            WHERE ($1::uuid IS NULL OR user_id = $1) AND ($2::varchar IS NULL OR verdict = $2)
+            ORDER BY created_at
+            "#
+        )
+        .bind(user_id)
+        .bind(verdict)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(task_feedback_from_row).collect()
+    }
+
+    // Custom Task Type Operations
+    pub async fn list_custom_task_types(&self, organization: &str) -> ServiceResult<Vec<CustomTaskType>> {
+        let rows = sqlx::query("SELECT * FROM custom_task_types WHERE organization = $1 ORDER BY name")
+            .bind(organization)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(custom_task_type_from_row).collect()
+    }
+
+    pub async fn create_custom_task_type(&self, task_type: CustomTaskType) -> ServiceResult<CustomTaskType> {
+        let fields = serde_json::to_value(&task_type.fields)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        sqlx::query(
+            r#"
+            INSERT INTO custom_task_types (id, organization, name, description, fields, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#
+        )
+        .bind(task_type.id)
+        .bind(&task_type.organization)
+        .bind(&task_type.name)
+        .bind(&task_type.description)
+        .bind(fields)
+        .bind(task_type.created_at)
+        .bind(task_type.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                ServiceError::BadRequest(format!("Task type '{}' already exists", task_type.name))
+            }
+            e => ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)),
+        })?;
+
+        Ok(task_type)
+    }
+
+    pub async fn update_custom_task_type(
+        &self,
+        organization: &str,
+        id: Uuid,
+        request: CustomTaskTypeRequest,
+    ) -> ServiceResult<CustomTaskType> {
+        let fields = serde_json::to_value(&request.fields)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let row = sqlx::query(
+            r#"
+            UPDATE custom_task_types
+            SET name = $3, description = $4, fields = $5, updated_at = NOW()
+            WHERE organization = $1 AND id = $2
+            RETURNING *
+            "#
+        )
+        .bind(organization)
+        .bind(id)
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(fields)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                ServiceError::BadRequest(format!("Task type '{}' already exists", request.name))
+            }
+            e => ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)),
+        })?
+        .ok_or_else(|| ServiceError::NotFound(format!("Task type with id {} not found", id)))?;
+
+        custom_task_type_from_row(&row)
+    }
+
+    pub async fn delete_custom_task_type(&self, organization: &str, id: Uuid) -> ServiceResult<()> {
+        let result = sqlx::query("DELETE FROM custom_task_types WHERE organization = $1 AND id = $2")
+            .bind(organization)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("Task type with id {} not found", id)));
+        }
+        Ok(())
+    }
+
+    // Organization LLM Operations
+    pub async fn organization_llm_settings(&self, organization: &str) -> ServiceResult<Option<OrganizationLlmSettings>> {
+        let row = sqlx::query("SELECT * FROM organization_llm_settings WHERE organization = $1")
+            .bind(organization)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(row.as_ref().map(organization_llm_settings_from_row))
+    }
+
+    pub async fn set_organization_llm_settings(
+        &self,
+        organization: &str,
+        request: &OrganizationLlmRequest,
+    ) -> ServiceResult<OrganizationLlmSettings> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO organization_llm_settings (organization, base_url, model, api_key, monthly_token_budget, alert_user_id, updated_at)
+            VALUES ($1, $2, $3, NULLIF($4, ''), $5, $6, NOW())
+            ON CONFLICT (organization) DO UPDATE
+            SET base_url = $2, model = $3,
+                api_key = CASE WHEN $4::varchar IS NULL THEN organization_llm_settings.api_key ELSE NULLIF($4, '') END,
+                monthly_token_budget = $5, alert_user_id = $6, updated_at = NOW()
+            RETURNING *
+            "#
+        )
+        .bind(organization)
+        .bind(&request.base_url)
+        .bind(&request.model)
+        .bind(&request.api_key)
+        .bind(request.monthly_token_budget)
+        .bind(request.alert_user_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                ServiceError::BadRequest("The user to alert does not exist".to_string())
+            }
+            e => ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)),
+        })?;
+
+        Ok(organization_llm_settings_from_row(&row))
+    }
+
+    pub async fn delete_organization_llm_settings(&self, organization: &str) -> ServiceResult<()> {
+        let result = sqlx::query("DELETE FROM organization_llm_settings WHERE organization = $1")
+            .bind(organization)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("Organization {} has no LLM settings", organization)));
+        }
+        Ok(())
+    }
+
+    /// Tokens the organization used this month (UTC).
+    pub async fn llm_usage(&self, organization: &str) -> ServiceResult<LlmUsage> {
+        let row = sqlx::query(
+            r#"
+            SELECT date_trunc('month', NOW() AT TIME ZONE 'UTC')::date AS month,
+                   COALESCE((SELECT tokens FROM llm_usage
+                             WHERE organization = $1 AND month = date_trunc('month', NOW() AT TIME ZONE 'UTC')::date), 0) AS tokens,
+                   (SELECT monthly_token_budget FROM organization_llm_settings WHERE organization = $1) AS monthly_token_budget
+            "#
+        )
+        .bind(organization)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(LlmUsage {
+            organization: organization.to_string(),
+            month: row.get("month"),
+            tokens_used: row.get("tokens"),
+            monthly_token_budget: row.get("monthly_token_budget"),
         })
     }
 
-    // Case operations
-    pub async fn create_case(&self, case: Case) -> ServiceResult<Case> {
+    /// The organization an administrator made the user a member of, if any.
+    /// Unlike the organization users give in their profile, it can be
+    /// trusted with the organization's settings and members.
+    pub async fn organization_of(&self, user_id: Uuid) -> ServiceResult<Option<String>> {
+        sqlx::query_scalar("SELECT organization FROM organization_members WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))
+    }
+
+    /// The LLM settings of the organization the user is a member of, if it
+    /// has any, with its usage this month.
+    pub async fn organization_llm_of(&self, user_id: Uuid) -> ServiceResult<Option<OrganizationLlm>> {
+        let Some(organization) = self.organization_of(user_id).await? else {
+            return Ok(None);
+        };
+        let Some(settings) = self.organization_llm_settings(&organization).await? else {
+            return Ok(None);
+        };
+        let usage = self.llm_usage(&organization).await?;
+        Ok(Some(OrganizationLlm { settings, usage }))
+    }
+
+    /// Makes the user a member of the organization, moving them out of any
+    /// other.
+    pub async fn add_organization_member(&self, organization: &str, user_id: Uuid) -> ServiceResult<OrganizationMember> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO organization_members (user_id, organization, added_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (user_id) DO UPDATE SET organization = $2, added_at = NOW()
+            RETURNING *
+            "#
+        )
+        .bind(user_id)
+        .bind(organization)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                ServiceError::NotFound(format!("User with id {} not found", user_id))
+            }
+            e => ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)),
+        })?;
+
+        Ok(organization_member_from_row(&row))
+    }
+
+    pub async fn remove_organization_member(&self, organization: &str, user_id: Uuid) -> ServiceResult<()> {
+        let result = sqlx::query("DELETE FROM organization_members WHERE organization = $1 AND user_id = $2")
+            .bind(organization)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("User {} is not a member of {}", user_id, organization)));
+        }
+        Ok(())
+    }
+
+    /// The organization's members, longest-standing first.
+    pub async fn list_organization_members(&self, organization: &str) -> ServiceResult<Vec<OrganizationMember>> {
+        let rows = sqlx::query("SELECT * FROM organization_members WHERE organization = $1 ORDER BY added_at, user_id")
+            .bind(organization)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows.iter().map(organization_member_from_row).collect())
+    }
+
+    /// Adds tokens to the organization's usage this month. Also returns
+    /// whether they used up its budget, which is only reported once a
+    /// month.
+    pub async fn record_llm_usage(&self, organization: &str, tokens: i64) -> ServiceResult<(LlmUsage, bool)> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO llm_usage (organization, month, tokens)
+            VALUES ($1, date_trunc('month', NOW() AT TIME ZONE 'UTC')::date, $2)
+            ON CONFLICT (organization, month) DO UPDATE SET tokens = llm_usage.tokens + $2
+            RETURNING month, tokens
+            "#
+        )
+        .bind(organization)
+        .bind(tokens.max(0))
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        let budget: Option<i64> = sqlx::query_scalar("SELECT monthly_token_budget FROM organization_llm_settings WHERE organization = $1")
+            .bind(organization)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?
+            .flatten();
+        let usage = LlmUsage {
+            organization: organization.to_string(),
+            month: row.get("month"),
+            tokens_used: row.get("tokens"),
+            monthly_token_budget: budget,
+        };
+
+        let newly_exhausted = usage.exhausted()
+            && sqlx::query("UPDATE llm_usage SET alerted = true WHERE organization = $1 AND month = $2 AND NOT alerted")
+                .bind(organization)
+                .bind(usage.month)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?
+                .rows_affected()
+                > 0;
+
+        tx.commit().await.map_err(db_error)?;
+        Ok((usage, newly_exhausted))
+    }
+
+    // Extraction Example Operations
+    pub async fn create_extraction_example(&self, example: ExtractionExample) -> ServiceResult<ExtractionExample> {
+        let expected_tasks = serde_json::to_value(&example.expected_tasks)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        sqlx::query(
+            r#"
+            INSERT INTO extraction_examples (id, user_id, message, expected_tasks, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#
+        )
+        .bind(example.id)
+        .bind(example.user_id)
+        .bind(&example.message)
+        .bind(expected_tasks)
+        .bind(example.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(example)
+    }
+
+    /// The user's examples, newest first.
+    pub async fn list_extraction_examples(&self, user_id: Uuid) -> ServiceResult<Vec<ExtractionExample>> {
+        let rows = sqlx::query("SELECT * FROM extraction_examples WHERE user_id = $1 ORDER BY created_at DESC")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(extraction_example_from_row).collect()
+    }
+
+    pub async fn delete_extraction_example(&self, user_id: Uuid, id: Uuid) -> ServiceResult<()> {
+        let result = sqlx::query("DELETE FROM extraction_examples WHERE user_id = $1 AND id = $2")
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound(format!("Extraction example with id {} not found", id)));
+        }
+        Ok(())
+    }
+
+    // Revision Operations
+    pub async fn record_revision(&self, owner_id: Uuid, revision: Revision) -> ServiceResult<Revision> {
+        sqlx::query(
+            r#"
+            INSERT INTO revisions (id, user_id, entity, entity_id, actor_id, changes, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#
+        )
+        .bind(revision.id)
+        .bind(owner_id)
+        .bind(serde_json::to_string(&revision.entity).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(revision.entity_id)
+        .bind(revision.actor_id)
+        .bind(&revision.changes)
+        .bind(revision.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(revision)
+    }
+
+    /// Revisions of a case or task, newest first.
+    pub async fn list_revisions(&self, entity: RevisionEntity, entity_id: Uuid, scope: Scope) -> ServiceResult<Vec<Revision>> {
+        let entity = serde_json::to_string(&entity)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let rows = sqlx::query(
+            "SELECT * FROM revisions WHERE entity = $1 AND entity_id = $2 AND ($3::uuid IS NULL OR user_id = $3) ORDER BY created_at DESC"
+        )
+            .bind(entity)
+            .bind(entity_id)
+            .bind(scope.owner())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(revision_from_row).collect()
+    }
+
+    // Case Event Operations
+    /// Records `event`. With `once` it is skipped, and `None` returned, when
+    /// the case already has an event of its kind.
+    pub async fn record_case_event(&self, event: CaseEvent, once: bool) -> ServiceResult<Option<CaseEvent>> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO case_events (id, case_id, actor_id, kind, summary, subject_id, created_at)
+            SELECT $1, $2, $3, $4, $5, $6, $7
+            WHERE NOT $8 OR NOT EXISTS (SELECT 1 FROM case_events WHERE case_id = $2 AND kind = $4)
+            "#
+        )
+        .bind(event.id)
+        .bind(event.case_id)
+        .bind(event.actor_id)
+        .bind(serde_json::to_string(&event.kind).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(&event.summary)
+        .bind(event.subject_id)
+        .bind(event.created_at)
+        .bind(once)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok((result.rows_affected() > 0).then_some(event))
+    }
+
+    // Read Receipt Operations
+    /// Marks a case's conversation read by `user_id` up to now. Receipts
+    /// never move back in time.
+    pub async fn mark_case_read(&self, user_id: Uuid, case_id: Uuid) -> ServiceResult<CaseReadReceipt> {
+        let last_read_at: DateTime<Utc> = sqlx::query_scalar(
+            r#"
+            INSERT INTO case_reads (user_id, case_id, last_read_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, case_id)
+            DO UPDATE SET last_read_at = GREATEST(case_reads.last_read_at, EXCLUDED.last_read_at)
+            RETURNING last_read_at
+            "#
+        )
+        .bind(user_id)
+        .bind(case_id)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(CaseReadReceipt { case_id, user_id, last_read_at })
+    }
+
+    /// Conversation entries `user_id` has not read, by case. Cases without
+    /// unread entries are left out.
+    pub async fn unread_counts(&self, user_id: Uuid) -> ServiceResult<HashMap<Uuid, i64>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT e.case_id, COUNT(*) AS unread
+            FROM conversation_entries e
+            JOIN cases c ON c.id = e.case_id
+            LEFT JOIN case_reads r ON r.case_id = e.case_id AND r.user_id = $1
+            WHERE c.user_id = $1 AND (r.last_read_at IS NULL OR e.timestamp > r.last_read_at)
+            GROUP BY e.case_id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows.iter().map(|row| (row.get("case_id"), row.get("unread"))).collect())
+    }
+
+    /// Events of a case, oldest first.
+    pub async fn list_case_events(&self, case_id: Uuid, scope: Scope) -> ServiceResult<Vec<CaseEvent>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT e.* FROM case_events e JOIN cases c ON c.id = e.case_id
+            WHERE e.case_id = $1 AND ($2::uuid IS NULL OR c.user_id = $2)
+            ORDER BY e.created_at ASC
+            "#
+        )
+            .bind(case_id)
+            .bind(scope.owner())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(case_event_from_row).collect()
+    }
+
+    // Job Operations
+    pub async fn enqueue_job(&self, request: &EnqueueJobRequest) -> ServiceResult<Job> {
+        let now = Utc::now();
+        let job = Job {
+            id: Uuid::new_v4(),
+            kind: request.kind.clone(),
+            payload: request.payload.clone(),
+            status: JobStatus::Pending,
+            attempts: 0,
+            max_attempts: request.max_attempts.unwrap_or(DEFAULT_JOB_ATTEMPTS).max(1),
+            run_at: request.run_at.unwrap_or(now),
+            interval_secs: None,
+            last_error: None,
+            locked_by: None,
+            locked_until: None,
+            created_at: now,
+            updated_at: now,
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO jobs (id, kind, payload, status, attempts, max_attempts, run_at, interval_secs, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, 0, $5, $6, NULL, $7, $7)
+            "#
+        )
+        .bind(job.id)
+        .bind(&job.kind)
+        .bind(&job.payload)
+        .bind(job_status(&job.status)?)
+        .bind(job.max_attempts)
+        .bind(job.run_at)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(job)
+    }
+
+    /// Creates the recurring job of `request.kind`, or updates its interval
+    /// and payload. A shorter interval brings the next run forward.
+    pub async fn schedule_job(&self, request: &ScheduleJobRequest) -> ServiceResult<Job> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO jobs (id, kind, payload, status, attempts, max_attempts, run_at, interval_secs, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, 0, $5, NOW(), $6, NOW(), NOW())
+            ON CONFLICT (kind) WHERE interval_secs IS NOT NULL DO UPDATE SET
+                payload = EXCLUDED.payload,
+                interval_secs = EXCLUDED.interval_secs,
+                run_at = LEAST(jobs.run_at, NOW() + make_interval(secs => EXCLUDED.interval_secs::double precision)),
+                updated_at = NOW()
+            RETURNING *
+            "#
+        )
+        .bind(Uuid::new_v4())
+        .bind(&request.kind)
+        .bind(&request.payload)
+        .bind(job_status(&JobStatus::Pending)?)
+        .bind(DEFAULT_JOB_ATTEMPTS)
+        .bind(request.interval_secs)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        job_from_row(&row)
+    }
+
+    /// Leases due jobs of the given kinds to `request.worker`, oldest first.
+    /// Jobs whose lease ran out are due again, unless that was their last
+    /// attempt, which dead-letters them.
+    pub async fn claim_jobs(&self, request: &ClaimJobsRequest) -> ServiceResult<Vec<Job>> {
+        let (pending, running, dead) = (
+            job_status(&JobStatus::Pending)?,
+            job_status(&JobStatus::Running)?,
+            job_status(&JobStatus::Dead)?,
+        );
+
+        sqlx::query(
+            r#"
+            UPDATE jobs SET status = $2, last_error = 'Lease expired', locked_by = NULL, locked_until = NULL, updated_at = NOW()
+            WHERE status = $1 AND locked_until < NOW() AND attempts >= max_attempts AND interval_secs IS NULL
+            "#
+        )
+        .bind(&running)
+        .bind(&dead)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        let rows = sqlx::query(
+            r#"
+            UPDATE jobs SET
+                status = $2,
+                attempts = attempts + 1,
+                locked_by = $3,
+                locked_until = NOW() + make_interval(secs => $4::double precision),
+                updated_at = NOW()
+            WHERE id IN (
+                SELECT id FROM jobs
+                WHERE kind = ANY($5) AND run_at <= NOW()
+                  AND (status = $1 OR (status = $2 AND locked_until < NOW()))
+                ORDER BY run_at
+                LIMIT $6
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+            "#
+        )
+        .bind(&pending)
+        .bind(&running)
+        .bind(&request.worker)
+        .bind(request.lease_secs.max(1))
+        .bind(&request.kinds)
+        .bind(request.limit.max(1))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(job_from_row).collect()
+    }
+
+    /// Records the outcome of a job held by `result.worker`. Failed jobs
+    /// are retried with exponential backoff, recurring ones at most an
+    /// interval later.
+    pub async fn finish_job(&self, id: Uuid, result: &JobResult) -> ServiceResult<Job> {
+        let mut tx = self.pool.begin().await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        let row = sqlx::query("SELECT * FROM jobs WHERE id = $1 AND status = $2 AND locked_by = $3 FOR UPDATE")
+            .bind(id)
+            .bind(job_status(&JobStatus::Running)?)
+            .bind(&result.worker)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+            .ok_or_else(|| ServiceError::NotFound(format!("Job {} is not held by {}", id, result.worker)))?;
+        let mut job = job_from_row(&row)?;
+
+        let now = Utc::now();
+        let interval = job.interval_secs.map(chrono::Duration::seconds);
+        let backoff = || {
+            let backoff = JOB_RETRY_BASE * 2i32.pow(job.attempts.clamp(1, 8) as u32 - 1);
+            backoff.min(interval.unwrap_or(JOB_RETRY_MAX)).min(JOB_RETRY_MAX)
+        };
+        job.status = JobStatus::Pending;
+        match (&result.error, interval) {
+            (None, None) => job.status = JobStatus::Succeeded,
+            (None, Some(interval)) => {
+                job.attempts = 0;
+                job.run_at = now + interval;
+                job.last_error = None;
+            }
+            (Some(_), Some(interval)) if job.attempts >= job.max_attempts => {
+                job.attempts = 0;
+                job.run_at = now + interval;
+            }
+            (Some(_), None) if job.attempts >= job.max_attempts => job.status = JobStatus::Dead,
+            (Some(_), _) => job.run_at = now + backoff(),
+        }
+        if result.error.is_some() {
+            job.last_error = result.error.clone();
+        }
+        job.locked_by = None;
+        job.locked_until = None;
+        job.updated_at = now;
+
+        sqlx::query(
+            r#"
+            UPDATE jobs SET status = $2, attempts = $3, run_at = $4, last_error = $5,
+                locked_by = NULL, locked_until = NULL, updated_at = $6
+            WHERE id = $1
+            "#
+        )
+        .bind(job.id)
+        .bind(job_status(&job.status)?)
+        .bind(job.attempts)
+        .bind(job.run_at)
+        .bind(&job.last_error)
+        .bind(job.updated_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        tx.commit().await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(job)
+    }
+
+    /// Jobs, those due soonest first.
+    pub async fn list_jobs(&self, status: Option<JobStatus>, kind: Option<&str>, limit: i64) -> ServiceResult<Vec<Job>> {
+        let status = status.as_ref().map(job_status).transpose()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM jobs
+            WHERE ($1::varchar IS NULL OR status = $1) AND ($2::varchar IS NULL OR kind = $2)
+            ORDER BY run_at
+            LIMIT $3
+            "#
+        )
+        .bind(status)
+        .bind(kind)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(job_from_row).collect()
+    }
+
+    /// Makes a dead-lettered job due again, with all its attempts.
+    pub async fn retry_job(&self, id: Uuid) -> ServiceResult<Job> {
+        let row = sqlx::query(
+            r#"
+            UPDATE jobs SET status = $2, attempts = 0, run_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND status = $3
+            RETURNING *
+            "#
+        )
+        .bind(id)
+        .bind(job_status(&JobStatus::Pending)?)
+        .bind(job_status(&JobStatus::Dead)?)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("Dead job with id {} not found", id)))?;
+
+        job_from_row(&row)
+    }
+
+    /// Deletes succeeded jobs last run before `cutoff`. Dead jobs stay
+    /// until retried.
+    pub async fn prune_jobs(&self, cutoff: DateTime<Utc>) -> ServiceResult<u64> {
+        let result = sqlx::query("DELETE FROM jobs WHERE status = $1 AND updated_at < $2")
+            .bind(job_status(&JobStatus::Succeeded)?)
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(result.rows_affected())
+    }
+
+    // Failed Message Operations
+    pub async fn create_failed_message(&self, user_id: Option<Uuid>, request: CreateFailedMessageRequest) -> ServiceResult<FailedMessage> {
+        let now = Utc::now();
+        let failed = FailedMessage {
+            id: Uuid::new_v4(),
+            user_id,
+            message: request.message,
+            error: request.error,
+            status: FailedMessageStatus::Pending,
+            attempts: 1,
+            next_attempt_at: Some(now + FAILED_MESSAGE_RETRY_BASE),
+            created_at: now,
+            updated_at: now,
+        };
+        let serialization_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+        sqlx::query(
+            r#"
+            INSERT INTO failed_messages (id, user_id, message, error, status, attempts, next_attempt_at, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8)
+            "#
+        )
+        .bind(failed.id)
+        .bind(failed.user_id)
+        .bind(serde_json::to_value(&failed.message).map_err(serialization_error)?)
+        .bind(&failed.error)
+        .bind(serde_json::to_string(&failed.status).map_err(serialization_error)?)
+        .bind(failed.attempts)
+        .bind(failed.next_attempt_at)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(failed)
+    }
+
+    /// Failed messages, newest first. `user_id` limits them to one user's
+    /// messages; `None` lists every message.
+    pub async fn list_failed_messages(
+        &self,
+        user_id: Option<Uuid>,
+        status: Option<FailedMessageStatus>,
+    ) -> ServiceResult<Vec<FailedMessage>> {
+        let status = status
+            .map(|status| serde_json::to_string(&status))
+            .transpose()
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM failed_messages
+            WHERE ($1::uuid IS NULL OR user_id = $1) AND ($2::varchar IS NULL OR status = $2)
+            ORDER BY created_at DESC
+            "#
+        )
+        .bind(user_id)
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(failed_message_from_row).collect()
+    }
+
+    /// Claims failed messages without an owner that are due for another
+    /// automatic attempt, oldest first.
+    pub async fn claim_due_failed_messages(&self, limit: i64) -> ServiceResult<Vec<FailedMessage>> {
+        let rows = sqlx::query(
+            r#"
+            UPDATE failed_messages SET claimed_until = $1
+            WHERE id IN (
+                SELECT id FROM failed_messages
+                WHERE user_id IS NULL AND status = $2 AND next_attempt_at <= NOW()
+                  AND (claimed_until IS NULL OR claimed_until < NOW())
+                ORDER BY next_attempt_at
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+            "#
+        )
+        .bind(Utc::now() + FAILED_MESSAGE_CLAIM)
+        .bind(serde_json::to_string(&FailedMessageStatus::Pending)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        rows.iter().map(failed_message_from_row).collect()
+    }
+
+    /// Claims one failed message for a retry by hand, which abandoned
+    /// messages also get. Fails while another attempt holds it.
+    pub async fn claim_failed_message(&self, id: Uuid, user_id: Option<Uuid>) -> ServiceResult<FailedMessage> {
+        let serialization_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e));
+        let row = sqlx::query(
+            r#"
+            UPDATE failed_messages SET claimed_until = $3
+            WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2) AND status IN ($4, $5)
+              AND (claimed_until IS NULL OR claimed_until < NOW())
+            RETURNING *
+            "#
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(Utc::now() + FAILED_MESSAGE_CLAIM)
+        .bind(serde_json::to_string(&FailedMessageStatus::Pending).map_err(serialization_error)?)
+        .bind(serde_json::to_string(&FailedMessageStatus::Abandoned).map_err(serialization_error)?)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("Retryable failed message with id {} not found", id)))?;
+
+        failed_message_from_row(&row)
+    }
+
+    /// Records an attempt at a claimed message. Failures wait with
+    /// exponential backoff, and after the last automatic attempt the
+    /// message is abandoned.
+    pub async fn record_failed_message_attempt(
+        &self,
+        id: Uuid,
+        user_id: Option<Uuid>,
+        attempt: FailedMessageAttempt,
+    ) -> ServiceResult<FailedMessage> {
+        let mut tx = self.pool.begin().await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        let row = sqlx::query(
+            r#"
+            SELECT * FROM failed_messages
+            WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2) AND claimed_until IS NOT NULL
+            FOR UPDATE
+            "#
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+        .ok_or_else(|| ServiceError::NotFound(format!("Claimed failed message with id {} not found", id)))?;
+        let mut failed = failed_message_from_row(&row)?;
+
+        let now = Utc::now();
+        failed.attempts += 1;
+        failed.updated_at = now;
+        match attempt.error {
+            None => {
+                failed.status = FailedMessageStatus::Reprocessed;
+                failed.next_attempt_at = None;
+            }
+            Some(error) => {
+                failed.error = error;
+                if failed.attempts >= FAILED_MESSAGE_ATTEMPTS {
+                    failed.status = FailedMessageStatus::Abandoned;
+                    failed.next_attempt_at = None;
+                } else {
+                    let backoff = FAILED_MESSAGE_RETRY_BASE * 2i32.pow(failed.attempts.clamp(1, 12) as u32 - 1);
+                    failed.status = FailedMessageStatus::Pending;
+                    failed.next_attempt_at = Some(now + backoff.min(FAILED_MESSAGE_RETRY_MAX));
+                }
+            }
+        }
+
         sqlx::query(
             r#"
-            INSERT INTO cases (id, user_id, title, description, status, priority, created_at, updated_at, assigned_to, metadata)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            UPDATE failed_messages SET error = $2, status = $3, attempts = $4, next_attempt_at = $5,
+                claimed_until = NULL, updated_at = $6
+            WHERE id = $1
             "#
         )
-        .bind(case.id)
-        .bind(case.user_id)
-        .bind(&case.title)
-        .bind(&case.description)
-        .bind(serde_json::to_string(&case.status).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
-        .bind(serde_json::to_string(&case.priority).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
-        .bind(case.created_at)
-        .bind(case.updated_at)
-        .bind(&case.assigned_to)
-        .bind(&case.metadata)
-        .execute(&self.pool)
+        .bind(failed.id)
+        .bind(&failed.error)
+        .bind(serde_json::to_string(&failed.status)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .bind(failed.attempts)
+        .bind(failed.next_attempt_at)
+        .bind(now)
+        .execute(&mut *tx)
         .await
         .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
 
-        Ok(case)
+        tx.commit().await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(failed)
+    }
+
+    // Outbox Operations
+    /// Unpublished events due for publication, in the order they happened.
+    pub async fn due_outbox_events(&self, limit: i64) -> ServiceResult<Vec<OutboxEvent>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM outbox_events
+            WHERE published_at IS NULL
+            ORDER BY seq
+            LIMIT $1
+            "#
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        // Events after one waiting out a failure wait too, to keep order.
+        let mut events = Vec::new();
+        for row in &rows {
+            let next_attempt_at: Option<DateTime<Utc>> = row.get("next_attempt_at");
+            if next_attempt_at.is_some_and(|at| at > Utc::now()) {
+                break;
+            }
+            events.push(outbox_event_from_row(row));
+        }
+        Ok(events)
+    }
+
+    pub async fn mark_outbox_published(&self, id: Uuid) -> ServiceResult<()> {
+        sqlx::query("UPDATE outbox_events SET published_at = NOW(), next_attempt_at = NULL WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(())
+    }
+
+    /// Records a failed publication of `id`; it is tried again after a
+    /// backoff that doubles with every failure.
+    pub async fn record_outbox_failure(&self, id: Uuid, error: &str) -> ServiceResult<()> {
+        let row = sqlx::query("UPDATE outbox_events SET attempts = attempts + 1, last_error = $2 WHERE id = $1 RETURNING attempts")
+            .bind(id)
+            .bind(error)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        let attempts: i32 = row.get("attempts");
+        let backoff = (OUTBOX_RETRY_BASE * 2i32.pow(attempts.clamp(1, 12) as u32 - 1)).min(OUTBOX_RETRY_MAX);
+
+        sqlx::query("UPDATE outbox_events SET next_attempt_at = $2 WHERE id = $1")
+            .bind(id)
+            .bind(Utc::now() + backoff)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(())
     }
 
-    pub async fn get_case(&self, id: Uuid, user_id: Uuid) -> ServiceResult<Case> {
+    pub async fn outbox_lag(&self) -> ServiceResult<OutboxLag> {
         let row = sqlx::query(
-            "SELECT id, user_id, title, description, status, priority, created_at, updated_at, assigned_to, metadata FROM cases WHERE id = $1 AND user_id = $2"
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM outbox_events WHERE published_at IS NULL) AS pending,
+                (SELECT MAX(published_at) FROM outbox_events) AS last_published_at,
+                head.created_at AS oldest_pending_at,
+                head.last_error,
+                COALESCE(head.attempts, 0) AS attempts
+            FROM (SELECT 1) AS one
+            LEFT JOIN LATERAL (
+                SELECT created_at, last_error, attempts FROM outbox_events
+                WHERE published_at IS NULL ORDER BY seq LIMIT 1
+            ) AS head ON TRUE
+            "#
         )
-        .bind(id)
-        .bind(user_id)
-        .fetch_optional(&self.pool)
+        .fetch_one(&self.pool)
         .await
-        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
-        .ok_or_else(|| ServiceError::NotFound(format!("Case with id {} not found", id)))?;
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
 
-        Ok(Case {
-            id: row.get("id"),
-            user_id: row.get("user_id"),
-            title: row.get("title"),
-            description: row.get("description"),
-            status: serde_json::from_str(&row.get::<String, _>("status")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-            priority: serde_json::from_str(&row.get::<String, _>("priority")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-            assigned_to: row.get("assigned_to"),
-            metadata: row.get("metadata"),
+        let oldest_pending_at: Option<DateTime<Utc>> = row.get("oldest_pending_at");
+        Ok(OutboxLag {
+            pending: row.get("pending"),
+            oldest_pending_at,
+            lag_secs: oldest_pending_at.map_or(0, |at| (Utc::now() - at).num_seconds().max(0)),
+            last_published_at: row.get("last_published_at"),
+            last_error: row.get("last_error"),
+            attempts: row.get("attempts"),
         })
     }
 
-    pub async fn update_case(&self, id: Uuid, user_id: Uuid, request: UpdateCaseRequest) -> ServiceResult<Case> {
-        // Get current case first
-        let mut case = self.get_case(id, user_id).await?;
-        
-        // Update fields
-        if let Some(title) = request.title {
-            case.title = title;
-        }
-        if let Some(description) = request.description {
-            case.description = Some(description);
-        }
-        if let Some(status) = request.status {
-            case.status = status;
-        }
-        if let Some(priority) = request.priority {
-            case.priority = priority;
-        }
-        if let Some(assigned_to) = request.assigned_to {
-            case.assigned_to = Some(assigned_to);
-        }
-        case.updated_at = Utc::now();
+    /// Deletes events published before `cutoff`.
+    pub async fn prune_outbox(&self, cutoff: DateTime<Utc>) -> ServiceResult<u64> {
+        let result = sqlx::query("DELETE FROM outbox_events WHERE published_at < $1")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(result.rows_affected())
+    }
+
+    /// Creates a synthetic user with `cases` cases of `tasks_per_case`
+    /// tasks each, and a session until `expires_at`. Statuses, priorities,
+    /// types and due dates cycle so list filters have something to select.
+    /// Rows go in directly, without outbox events or revisions. Returns the
+    /// user with the number of tasks created.
+    pub async fn seed_user(
+        &self,
+        password_hash: &str,
+        cases: u32,
+        tasks_per_case: u32,
+        expires_at: DateTime<Utc>,
+    ) -> ServiceResult<(SeededUser, i64)> {
+        let db_error = |e: sqlx::Error| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e));
+        let case_statuses = stored(&[CaseStatus::Open, CaseStatus::InProgress, CaseStatus::Waiting, CaseStatus::Resolved]);
+        let task_statuses = stored(&[TaskStatus::Pending, TaskStatus::InProgress, TaskStatus::Completed, TaskStatus::OnHold]);
+        let priorities = stored(&[Priority::Low, Priority::Medium, Priority::High, Priority::Critical]);
+        let task_types = stored(&[
+            TaskType::Work,
+            TaskType::Meeting,
+            TaskType::Communication,
+            TaskType::Research,
+            TaskType::Personal,
+            TaskType::Shopping,
+        ]);
+
+        let user_id = Uuid::new_v4();
+        let email = format!("seed-{}@seed.invalid", user_id);
+        let session_token = Uuid::new_v4().to_string();
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
 
-        // Update in database
         sqlx::query(
             r#"
-            UPDATE cases 
-            SET title = $2, description = $3, status = $4, priority = $5, updated_at = $6, assigned_to = $7
-            WHERE id = $1
+            INSERT INTO users (id, email, password_hash, full_name, organization, is_active, created_at, updated_at, metadata, preferences)
+            VALUES ($1, $2, $3, 'Seed User', NULL, TRUE, NOW(), NOW(), '{"seed": true}', $4)
             "#
         )
-        .bind(id)
-        .bind(&case.title)
-        .bind(&case.description)
-        .bind(serde_json::to_string(&case.status).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
-        .bind(serde_json::to_string(&case.priority).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
-        .bind(case.updated_at)
-        .bind(&case.assigned_to)
+        .bind(user_id)
+        .bind(&email)
+        .bind(password_hash)
+        .bind(serde_json::to_value(UserPreferences::default()).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        sqlx::query(
+            "INSERT INTO user_sessions (id, user_id, session_token, expires_at, created_at, last_accessed) VALUES ($1, $2, $3, $4, NOW(), NOW())"
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(&session_token)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        let case_ids: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            INSERT INTO cases (id, user_id, title, description, status, priority, created_at, updated_at, assigned_to, metadata)
+            SELECT gen_random_uuid(), $1, 'Seed case ' || n, 'Synthetic case for load testing',
+                   $3[1 + n % cardinality($3)], $4[1 + n % cardinality($4)],
+                   NOW() - n * INTERVAL '1 hour', NOW() - n * INTERVAL '1 minute', NULL, '{"seed": true}'
+            FROM generate_series(1, $2) AS n
+            RETURNING id
+            "#
+        )
+        .bind(user_id)
+        .bind(cases as i32)
+        .bind(&case_statuses)
+        .bind(&priorities)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        let tasks = sqlx::query(
+            r#"
+            INSERT INTO tasks (id, case_id, title, description, task_type, status, priority, due_date, created_at, updated_at, completed_at, metadata, user_id, estimate_minutes)
+            SELECT gen_random_uuid(), seeded.case_id, 'Seed task ' || seeded.n, NULL, seeded.task_type, seeded.status, seeded.priority,
+                   NOW() + (seeded.n % 30 - 10) * INTERVAL '1 day', NOW() - seeded.n * INTERVAL '1 hour', NOW(),
+                   CASE WHEN seeded.status = $5[3] THEN NOW() END, '{"seed": true}', $1, 15 * (1 + seeded.n % 8)
+            FROM (
+                SELECT c.case_id, n, $4[1 + n % cardinality($4)] AS task_type, $5[1 + n % cardinality($5)] AS status,
+                       $6[1 + (n / 3) % cardinality($6)] AS priority
+                FROM unnest($2::uuid[]) AS c(case_id), generate_series(1, $3) AS n
+            ) AS seeded
+            "#
+        )
+        .bind(user_id)
+        .bind(&case_ids)
+        .bind(tasks_per_case as i32)
+        .bind(&task_types)
+        .bind(&task_statuses)
+        .bind(&priorities)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .rows_affected();
+
+        tx.commit().await.map_err(db_error)?;
+        Ok((SeededUser { user_id, email, session_token }, tasks as i64))
+    }
+
+    pub async fn delete_expired_sessions(&self) -> ServiceResult<u64> {
+        let result = sqlx::query("DELETE FROM user_sessions WHERE expires_at < NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(result.rows_affected())
+    }
+
+    /// Clients of the user's successful sign-ins since `since`.
+    pub async fn recent_logins(&self, user_id: Uuid, since: chrono::DateTime<Utc>) -> ServiceResult<Vec<ClientInfo>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT ip_address, user_agent FROM login_events
+            WHERE user_id = $1 AND succeeded AND created_at >= $2
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ClientInfo { ip_address: row.get("ip_address"), user_agent: row.get("user_agent") })
+            .collect())
+    }
+
+    pub async fn record_login_event(&self, user_id: Uuid, client: &ClientInfo, succeeded: bool, unusual: bool) -> ServiceResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO login_events (id, user_id, ip_address, user_agent, succeeded, unusual, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, NOW())
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(&client.ip_address)
+        .bind(&client.user_agent)
+        .bind(succeeded)
+        .bind(unusual)
         .execute(&self.pool)
         .await
         .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(())
+    }
+
+    /// The user's latest sign-in attempts, newest first.
+    pub async fn list_login_events(&self, user_id: Uuid, limit: i64) -> ServiceResult<Vec<LoginEvent>> {
+        let rows = sqlx::query("SELECT * FROM login_events WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2")
+            .bind(user_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| LoginEvent {
+                id: row.get("id"),
+                succeeded: row.get("succeeded"),
+                unusual: row.get("unusual"),
+                ip_address: row.get("ip_address"),
+                user_agent: row.get("user_agent"),
+                device: None,
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    pub async fn save_oauth_state(&self, state: &str, payload: &serde_json::Value, expires_at: DateTime<Utc>) -> ServiceResult<()> {
+        sqlx::query("INSERT INTO oauth_states (state, payload, expires_at) VALUES ($1, $2, $3)")
+            .bind(state)
+            .bind(payload)
+            .bind(expires_at)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(())
+    }
+
+    /// Removes and returns a saved state; an expired one is not found.
+    pub async fn take_oauth_state(&self, state: &str) -> ServiceResult<serde_json::Value> {
+        let row = sqlx::query("DELETE FROM oauth_states WHERE state = $1 RETURNING payload, expires_at")
+            .bind(state)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
+            .filter(|row| row.get::<DateTime<Utc>, _>("expires_at") > Utc::now())
+            .ok_or_else(|| ServiceError::NotFound("OAuth state not found or expired".to_string()))?;
+        Ok(row.get("payload"))
+    }
+
+    pub async fn delete_expired_oauth_states(&self) -> ServiceResult<u64> {
+        let result = sqlx::query("DELETE FROM oauth_states WHERE expires_at < NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes sign-ins from before `cutoff`.
+    pub async fn prune_login_events(&self, cutoff: chrono::DateTime<Utc>) -> ServiceResult<u64> {
+        let result = sqlx::query("DELETE FROM login_events WHERE created_at < $1")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// `values` as stored in their VARCHAR columns.
+fn stored<T: serde::Serialize>(values: &[T]) -> Vec<String> {
+    values.iter().map(|value| serde_json::to_string(value).unwrap_or_default()).collect()
+}
+
+/// Replaces the tag set of a task or case. `table` and `owner_column` are
+/// always literals from this file.
+async fn replace_tags(
+    conn: &mut sqlx::PgConnection,
+    table: &str,
+    owner_column: &str,
+    owner_id: Uuid,
+    tags: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!("DELETE FROM {} WHERE {} = $1", table, owner_column))
+        .bind(owner_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(&format!("INSERT INTO {} ({}, tag) SELECT $1, UNNEST($2::text[])", table, owner_column))
+        .bind(owner_id)
+        .bind(tags)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Records an event for the outbox relay, inside the transaction making the
+/// change it describes.
+async fn insert_outbox_event(
+    conn: &mut sqlx::PgConnection,
+    event_type: &str,
+    aggregate_id: Uuid,
+    payload: &impl serde::Serialize,
+) -> ServiceResult<()> {
+    let payload = serde_json::to_value(payload)
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+    sqlx::query(
+        r#"
+        INSERT INTO outbox_events (id, event_type, aggregate_id, payload, created_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        "#
+    )
+    .bind(Uuid::new_v4())
+    .bind(event_type)
+    .bind(aggregate_id)
+    .bind(payload)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+    Ok(())
+}
+
+fn outbox_event_from_row(row: &PgRow) -> OutboxEvent {
+    OutboxEvent {
+        id: row.get("id"),
+        event_type: row.get("event_type"),
+        aggregate_id: row.get("aggregate_id"),
+        payload: row.get("payload"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn failed_message_from_row(row: &PgRow) -> ServiceResult<FailedMessage> {
+    let deserialization_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e));
+    Ok(FailedMessage {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        message: serde_json::from_value(row.get("message")).map_err(deserialization_error)?,
+        error: row.get("error"),
+        status: serde_json::from_str(&row.get::<String, _>("status")).map_err(deserialization_error)?,
+        attempts: row.get("attempts"),
+        next_attempt_at: row.get("next_attempt_at"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
+
+fn follow_up_question_from_row(row: &PgRow) -> ServiceResult<FollowUpQuestion> {
+    let deserialization_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e));
+    Ok(FollowUpQuestion {
+        id: row.get("id"),
+        case_id: row.get("case_id"),
+        task_id: row.get("task_id"),
+        user_id: row.get("user_id"),
+        question: row.get("question"),
+        missing: serde_json::from_value(row.get("missing")).map_err(deserialization_error)?,
+        channel: serde_json::from_value(row.get("channel")).map_err(deserialization_error)?,
+        recipient: row.get("recipient"),
+        status: serde_json::from_str(&row.get::<String, _>("status")).map_err(deserialization_error)?,
+        created_at: row.get("created_at"),
+        answered_at: row.get("answered_at"),
+    })
+}
+
+fn job_status(status: &JobStatus) -> ServiceResult<String> {
+    serde_json::to_string(status).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))
+}
+
+fn job_from_row(row: &PgRow) -> ServiceResult<Job> {
+    Ok(Job {
+        id: row.get("id"),
+        kind: row.get("kind"),
+        payload: row.get("payload"),
+        status: serde_json::from_str(&row.get::<String, _>("status"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        attempts: row.get("attempts"),
+        max_attempts: row.get("max_attempts"),
+        run_at: row.get("run_at"),
+        interval_secs: row.get("interval_secs"),
+        last_error: row.get("last_error"),
+        locked_by: row.get("locked_by"),
+        locked_until: row.get("locked_until"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
+
+fn ratio(part: i64, whole: i64) -> f64 {
+    if whole > 0 { part as f64 / whole as f64 } else { 0.0 }
+}
+
+/// Preferences stored before a field existed fall back to its default.
+/// Records an administrative action as part of the transaction making it.
+async fn insert_admin_audit(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>, entry: &AdminAuditEntry) -> ServiceResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO admin_audit_log (id, action, target_user_id, actor, reason, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#
+    )
+    .bind(entry.id)
+    .bind(serde_json::to_string(&entry.action).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
+    .bind(entry.target_user_id)
+    .bind(&entry.actor)
+    .bind(&entry.reason)
+    .bind(entry.created_at)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+    Ok(())
+}
+
+async fn insert_sso_identity(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    user_id: Uuid,
+    request: &SsoLoginRequest,
+) -> ServiceResult<SsoIdentity> {
+    let row = sqlx::query(
+        r#"
+        INSERT INTO sso_identities (id, user_id, provider, issuer, subject, email, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, NOW())
+        RETURNING *
+        "#
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(&request.provider)
+    .bind(&request.issuer)
+    .bind(&request.subject)
+    .bind(&request.email)
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+    Ok(sso_identity_from_row(&row))
+}
+
+fn api_token_from_row(row: &PgRow) -> ApiToken {
+    ApiToken {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        name: row.get("name"),
+        scopes: scopes_from_row(row),
+        created_at: row.get("created_at"),
+        expires_at: row.get("expires_at"),
+        last_used_at: row.get("last_used_at"),
+        revoked_at: row.get("revoked_at"),
+        token: None,
+    }
+}
+
+/// Scopes no longer known are left out, granting nothing.
+fn scopes_from_row(row: &PgRow) -> Vec<ApiScope> {
+    row.get::<Vec<String>, _>("scopes").iter().filter_map(|name| ApiScope::from_name(name)).collect()
+}
+
+fn sso_identity_from_row(row: &PgRow) -> SsoIdentity {
+    SsoIdentity {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        provider: row.get("provider"),
+        issuer: row.get("issuer"),
+        subject: row.get("subject"),
+        email: row.get("email"),
+        created_at: row.get("created_at"),
+        last_login: row.get("last_login"),
+    }
+}
+
+/// Verification tokens are stored as their SHA-256, in hex.
+fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn user_from_row(row: &PgRow) -> User {
+    User {
+        id: row.get("id"),
+        email: row.get("email"),
+        password_hash: row.get("password_hash"),
+        full_name: row.get("full_name"),
+        organization: row.get("organization"),
+        is_active: row.get("is_active"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        last_login: row.get("last_login"),
+        metadata: row.get("metadata"),
+        preferences: preferences_from_row(row),
+    }
+}
+
+fn admin_audit_from_row(row: &PgRow) -> ServiceResult<AdminAuditEntry> {
+    Ok(AdminAuditEntry {
+        id: row.get("id"),
+        action: serde_json::from_str(&row.get::<String, _>("action"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        target_user_id: row.get("target_user_id"),
+        actor: row.get("actor"),
+        reason: row.get("reason"),
+        created_at: row.get("created_at"),
+    })
+}
+
+fn preferences_from_row(row: &PgRow) -> UserPreferences {
+    serde_json::from_value(row.get("preferences")).unwrap_or_default()
+}
+
+fn case_from_row(row: &PgRow) -> ServiceResult<Case> {
+    Ok(Case {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        title: row.get("title"),
+        description: row.get("description"),
+        status: serde_json::from_str(&row.get::<String, _>("status"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        priority: serde_json::from_str(&row.get::<String, _>("priority"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        assigned_to: row.get("assigned_to"),
+        metadata: row.get("metadata"),
+        tags: row.get("tags"),
+        unread_count: None,
+        first_response_at: row.get("first_response_at"),
+        resolved_at: row.get("resolved_at"),
+        sla: None,
+    })
+}
+
+fn account_deletion_from_row(row: &PgRow) -> ServiceResult<AccountDeletion> {
+    Ok(AccountDeletion {
+        user_id: row.get("user_id"),
+        mode: serde_json::from_str(&row.get::<String, _>("mode"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        status: serde_json::from_str(&row.get::<String, _>("status"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        requested_at: row.get("requested_at"),
+        scheduled_for: row.get("scheduled_for"),
+        completed_at: row.get("completed_at"),
+    })
+}
+
+fn notification_from_row(row: &PgRow) -> ServiceResult<Notification> {
+    Ok(Notification {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        kind: serde_json::from_str(&row.get::<String, _>("kind"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        title: row.get("title"),
+        message: row.get("message"),
+        link: row.get("link"),
+        subject_id: row.get("subject_id"),
+        created_at: row.get("created_at"),
+        read_at: row.get("read_at"),
+    })
+}
+
+fn quarantined_message_from_row(row: &PgRow) -> ServiceResult<QuarantinedMessage> {
+    let deserialization_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e));
+    Ok(QuarantinedMessage {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        message: serde_json::from_value(row.get("message")).map_err(deserialization_error)?,
+        reasons: serde_json::from_value(row.get("reasons")).map_err(deserialization_error)?,
+        status: serde_json::from_str(&row.get::<String, _>("status")).map_err(deserialization_error)?,
+        created_at: row.get("created_at"),
+        reviewed_at: row.get("reviewed_at"),
+    })
+}
+
+fn task_feedback_from_row(row: &PgRow) -> ServiceResult<TaskFeedback> {
+    Ok(TaskFeedback {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        task_id: row.get("task_id"),
+        verdict: serde_json::from_str(&row.get::<String, _>("verdict"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        message: row.get("message"),
+        extracted: row.get("extracted"),
+        corrected: row.get("corrected"),
+        comment: row.get("comment"),
+        created_at: row.get("created_at"),
+    })
+}
+
+fn custom_task_type_from_row(row: &PgRow) -> ServiceResult<CustomTaskType> {
+    Ok(CustomTaskType {
+        id: row.get("id"),
+        organization: row.get("organization"),
+        name: row.get("name"),
+        description: row.get("description"),
+        fields: serde_json::from_value(row.get("fields"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
+
+fn organization_llm_settings_from_row(row: &PgRow) -> OrganizationLlmSettings {
+    let api_key: Option<String> = row.get("api_key");
+    OrganizationLlmSettings {
+        organization: row.get("organization"),
+        base_url: row.get("base_url"),
+        model: row.get("model"),
+        has_api_key: api_key.is_some(),
+        api_key,
+        monthly_token_budget: row.get("monthly_token_budget"),
+        alert_user_id: row.get("alert_user_id"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn organization_member_from_row(row: &PgRow) -> OrganizationMember {
+    OrganizationMember {
+        organization: row.get("organization"),
+        user_id: row.get("user_id"),
+        added_at: row.get("added_at"),
+    }
+}
+
+fn extraction_example_from_row(row: &PgRow) -> ServiceResult<ExtractionExample> {
+    Ok(ExtractionExample {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        message: row.get("message"),
+        expected_tasks: serde_json::from_value(row.get("expected_tasks"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        created_at: row.get("created_at"),
+    })
+}
+
+fn revision_from_row(row: &PgRow) -> ServiceResult<Revision> {
+    Ok(Revision {
+        id: row.get("id"),
+        entity: serde_json::from_str(&row.get::<String, _>("entity"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        entity_id: row.get("entity_id"),
+        actor_id: row.get("actor_id"),
+        changes: row.get("changes"),
+        created_at: row.get("created_at"),
+    })
+}
+
+fn case_workflow_from_row(row: &PgRow) -> ServiceResult<CaseWorkflow> {
+    Ok(CaseWorkflow {
+        id: row.get("id"),
+        case_id: row.get("case_id"),
+        current_step: row.get("current_step"),
+        steps: serde_json::from_value(row.get("steps"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
+
+fn case_event_from_row(row: &PgRow) -> ServiceResult<CaseEvent> {
+    Ok(CaseEvent {
+        id: row.get("id"),
+        case_id: row.get("case_id"),
+        actor_id: row.get("actor_id"),
+        kind: serde_json::from_str(&row.get::<String, _>("kind"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        summary: row.get("summary"),
+        subject_id: row.get("subject_id"),
+        created_at: row.get("created_at"),
+    })
+}
 
-        Ok(case)
+fn push_subscription_from_row(row: &PgRow) -> PushSubscription {
+    PushSubscription {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        endpoint: row.get("endpoint"),
+        p256dh: row.get("p256dh"),
+        auth: row.get("auth"),
+        created_at: row.get("created_at"),
     }
+}
 
-    // Task operations
-    pub async fn create_task(&self, task: Task) -> ServiceResult<Task> {
-        sqlx::query(
-            r#"
-            INSERT INTO tasks (id, case_id, title, description, task_type, status, priority, due_date, created_at, updated_at, completed_at, metadata)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
-            "#
-        )
-        .bind(task.id)
-        .bind(task.case_id)
-        .bind(&task.title)
-        .bind(&task.description)
-        .bind(serde_json::to_string(&task.task_type).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
-        .bind(serde_json::to_string(&task.status).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
-        .bind(serde_json::to_string(&task.priority).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
-        .bind(task.due_date)
-        .bind(task.created_at)
-        .bind(task.updated_at)
-        .bind(task.completed_at)
-        .bind(&task.metadata)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+fn task_from_row(row: &PgRow) -> ServiceResult<Task> {
+    Ok(Task {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        case_id: row.get("case_id"),
+        title: row.get("title"),
+        description: row.get("description"),
+        task_type: serde_json::from_str(&row.get::<String, _>("task_type"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        status: serde_json::from_str(&row.get::<String, _>("status"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        priority: serde_json::from_str(&row.get::<String, _>("priority"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        due_date: row.get("due_date"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        completed_at: row.get("completed_at"),
+        metadata: row.get("metadata"),
+        tags: row.get("tags"),
+        estimate_minutes: row.get("estimate_minutes"),
+    })
+}
 
-        Ok(task)
-    }
+fn task_template_from_row(row: &PgRow) -> ServiceResult<TaskTemplate> {
+    Ok(TaskTemplate {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        name: row.get("name"),
+        title: row.get("title"),
+        description: row.get("description"),
+        task_type: serde_json::from_str(&row.get::<String, _>("task_type"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        priority: serde_json::from_str(&row.get::<String, _>("priority"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        due_in_days: row.get("due_in_days"),
+        checklist: serde_json::from_value(row.get("checklist"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
 
-    pub async fn get_task(&self, id: Uuid) -> ServiceResult<Task> {
-        let row = sqlx::query("SELECT * FROM tasks WHERE id = $1")
-            .bind(id)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?
-            .ok_or_else(|| ServiceError::NotFound(format!("Task with id {} not found", id)))?;
+fn email_account_from_row(row: &PgRow) -> ServiceResult<EmailAccount> {
+    let de_error = |e: serde_json::Error| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e));
+    Ok(EmailAccount {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        email_address: row.get("email_address"),
+        provider: serde_json::from_str(&row.get::<String, _>("provider")).map_err(de_error)?,
+        is_active: row.get("is_active"),
+        oauth_token: row.get("oauth_token"),
+        oauth_refresh_token: row.get("oauth_refresh_token"),
+        oauth_expires_at: row.get("oauth_expires_at"),
+        imap_settings: row
+            .get::<Option<serde_json::Value>, _>("imap_settings")
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(de_error)?,
+        scopes: row.get("scopes"),
+        health: serde_json::from_value(row.get("health")).map_err(de_error)?,
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        metadata: row.get("metadata"),
+    })
+}
 
-        Ok(Task {
-            id: row.get("id"),
-            user_id: row.get("user_id"),
-            case_id: row.get("case_id"),
-            title: row.get("title"),
-            description: row.get("description"),
-            task_type: serde_json::from_str(&row.get::<String, _>("task_type")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-            status: serde_json::from_str(&row.get::<String, _>("status")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-            priority: serde_json::from_str(&row.get::<String, _>("priority")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-            due_date: row.get("due_date"),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-            completed_at: row.get("completed_at"),
-            metadata: row.get("metadata"),
-        })
-    }
+fn webhook_integration_from_row(row: &PgRow) -> ServiceResult<WebhookIntegration> {
+    Ok(WebhookIntegration {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        name: row.get("name"),
+        mapping: serde_json::from_value(row.get("mapping"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        created_at: row.get("created_at"),
+        token: None,
+    })
+}
 
-    pub async fn update_task(&self, id: Uuid, request: UpdateTaskRequest) -> ServiceResult<Task> {
-        let mut task = self.get_task(id).await?;
-        
-        if let Some(title) = request.title {
-            task.title = title;
-        }
-        if let Some(description) = request.description {
-            task.description = Some(description);
-        }
-        if let Some(status) = request.status {
-            task.status = status;
-            if matches!(task.status, models::TaskStatus::Completed) {
-                task.completed_at = Some(Utc::now());
-            }
-        }
-        if let Some(priority) = request.priority {
-            task.priority = priority;
-        }
-        if let Some(due_date) = request.due_date {
-            task.due_date = Some(due_date);
-        }
-        task.updated_at = Utc::now();
+fn saved_filter_from_row(row: &PgRow) -> ServiceResult<SavedFilter> {
+    Ok(SavedFilter {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        name: row.get("name"),
+        filter: serde_json::from_value(row.get("filter"))
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
 
-        sqlx::query(
-            r#"
-            UPDATE tasks 
-            SET title = $2, description = $3, status = $4, priority = $5, due_date = $6, updated_at = $7, completed_at = $8
-            WHERE id = $1
-            "#
-        )
-        .bind(id)
-        .bind(&task.title)
-        .bind(&task.description)
-        .bind(serde_json::to_string(&task.status).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
-        .bind(serde_json::to_string(&task.priority).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
-        .bind(task.due_date)
-        .bind(task.updated_at)
-        .bind(task.completed_at)
-        .execute(&self.pool)
+#[cfg(test)]
+mod tests {
+    use crate::scope::Scope;
+    use crate::test_db::{assert_not_found, case_of, database, member_of, task_of, task_update, user};
+    use chrono::Utc;
+    use common::ServiceError;
+    use models::{
+        AdminAction, AdminAuditEntry, ApiScope, ApiToken, CaseWorkflow, ClientInfo, ConversationEntry, EmailAccount, EmailProvider,
+        FollowUpQuestion, LoginRequest, MailboxHealth, MessageChannel, MessageSender, MissingDetail, OrganizationLlmRequest,
+        QuestionStatus, RestoreConflictMode, SsoLoginRequest, StaffRole, StepStatus, Task, TaskType, UpdateTaskRequest, User,
+        UserBackup, WebhookIntegration, WebhookMapping,
+    };
+    use uuid::Uuid;
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn backups_restore_cases_with_their_tasks_conversation_and_workflow() {
+        let db = database().await;
+        let (owner, other) = (user(&db).await, user(&db).await);
+        let case = case_of(&db, &owner).await;
+        let task = task_of(&db, &case).await;
+        db.add_conversation_entry(ConversationEntry {
+            id: Uuid::new_v4(),
+            user_id: owner.id,
+            case_id: case.id,
+            message: "Movers are booked".to_string(),
+            sender: MessageSender::User,
+            timestamp: Utc::now(),
+            metadata: serde_json::json!({}),
+        })
         .await
-        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+        .unwrap();
+        let mut workflow = db.get_case_workflow(case.id).await.unwrap();
+        workflow.current_step = "review".to_string();
+        workflow.steps[1].status = StepStatus::Completed;
+        let workflow = db.update_case_workflow(workflow).await.unwrap();
 
-        Ok(task)
+        let backup = serde_json::to_value(crate::build_backup(&db, owner.clone()).await.unwrap()).unwrap();
+        let cases = || serde_json::from_value::<UserBackup>(backup.clone()).unwrap().cases;
+        db.update_task(task.id, Scope::User(owner.id), task_update()).await.unwrap();
+        let moved_on = db.update_case_workflow(CaseWorkflow { current_step: "done".to_string(), ..workflow.clone() }).await.unwrap();
+
+        let summary = db.restore_backup(owner.id, cases(), RestoreConflictMode::Skip).await.unwrap();
+        assert_eq!((summary.cases_skipped, summary.tasks_skipped, summary.workflows_skipped), (1, 1, 1));
+        assert_eq!(db.get_case_workflow(case.id).await.unwrap().current_step, moved_on.current_step);
+
+        let summary = db.restore_backup(other.id, cases(), RestoreConflictMode::Overwrite).await.unwrap();
+        assert_eq!((summary.cases_restored, summary.workflows_restored), (0, 0), "only the owner's cases are restored");
+        assert_eq!(db.get_case_workflow(case.id).await.unwrap().current_step, moved_on.current_step);
+
+        let summary = db.restore_backup(owner.id, cases(), RestoreConflictMode::Overwrite).await.unwrap();
+        assert_eq!((summary.cases_restored, summary.tasks_restored, summary.workflows_restored), (1, 1, 1));
+        let restored = db.get_case_workflow(case.id).await.unwrap();
+        assert_eq!((restored.id, restored.current_step.as_str()), (workflow.id, "review"));
+        assert_eq!(serde_json::to_value(&restored.steps).unwrap(), serde_json::to_value(&workflow.steps).unwrap());
+        assert_eq!(db.get_task(task.id, Scope::User(owner.id)).await.unwrap().title, task.title);
+        assert_eq!(db.get_conversation_history(case.id, Scope::User(owner.id)).await.unwrap().len(), 1);
     }
 
-    pub async fn delete_task(&self, id: Uuid) -> ServiceResult<()> {
-        let result = sqlx::query("DELETE FROM tasks WHERE id = $1")
-            .bind(id)
-            .execute(&self.pool)
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn answered_questions_leave_the_pending_list() {
+        let db = database().await;
+        let owner = user(&db).await;
+        let case = case_of(&db, &owner).await;
+        let task = task_of(&db, &case).await;
+        let question = db
+            .create_follow_up_question(FollowUpQuestion {
+                id: Uuid::new_v4(),
+                case_id: case.id,
+                task_id: task.id,
+                user_id: owner.id,
+                question: format!("When is '{}' due?", task.title),
+                missing: vec![MissingDetail::DueDate],
+                channel: MessageChannel::Email,
+                recipient: owner.email.clone(),
+                status: QuestionStatus::Pending,
+                created_at: Utc::now(),
+                answered_at: None,
+            })
             .await
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+            .unwrap();
 
-        if result.rows_affected() == 0 {
-            return Err(ServiceError::NotFound(format!("Task with id {} not found", id)));
-        }
+        let pending = db.list_follow_up_questions(case.id, Some(QuestionStatus::Pending)).await.unwrap();
+        assert_eq!(pending.iter().map(|question| question.id).collect::<Vec<_>>(), [question.id]);
+        assert_eq!(pending[0].missing, [MissingDetail::DueDate]);
 
-        Ok(())
+        // Only the case's own questions are answered, and only once.
+        assert!(db.answer_follow_up_questions(Uuid::new_v4(), &[question.id]).await.unwrap().is_empty());
+        let answered = db.answer_follow_up_questions(case.id, &[question.id]).await.unwrap();
+        assert!(answered[0].answered_at.is_some());
+        assert!(db.answer_follow_up_questions(case.id, &[question.id]).await.unwrap().is_empty());
+        assert!(db.list_follow_up_questions(case.id, Some(QuestionStatus::Pending)).await.unwrap().is_empty());
+        assert_eq!(db.list_follow_up_questions(case.id, None).await.unwrap().len(), 1);
     }
 
-    pub async fn get_tasks_for_case(&self, case_id: Uuid) -> ServiceResult<Vec<Task>> {
-        let rows = sqlx::query("SELECT * FROM tasks WHERE case_id = $1 ORDER BY created_at DESC")
-            .bind(case_id)
-            .fetch_all(&self.pool)
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn webhook_integrations_are_found_by_token_and_deleted_by_owner() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let token = Uuid::new_v4().simple().to_string();
+        let integration = db
+            .create_webhook_integration(WebhookIntegration {
+                id: Uuid::new_v4(),
+                user_id: bob.id,
+                name: "Uptime alerts".to_string(),
+                mapping: WebhookMapping {
+                    sender: "$.monitor.owner".to_string(),
+                    body: "$.alert.text".to_string(),
+                    subject: None,
+                },
+                created_at: Utc::now(),
+                token: Some(token.clone()),
+            })
             .await
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+            .unwrap();
 
-        let mut tasks = Vec::new();
-        for row in rows {
-            tasks.push(Task {
-                id: row.get("id"),
-                user_id: row.get("user_id"),
-                case_id: row.get("case_id"),
-                title: row.get("title"),
-                description: row.get("description"),
-                task_type: serde_json::from_str(&row.get::<String, _>("task_type")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-                status: serde_json::from_str(&row.get::<String, _>("status")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-                priority: serde_json::from_str(&row.get::<String, _>("priority")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-                due_date: row.get("due_date"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                completed_at: row.get("completed_at"),
-                metadata: row.get("metadata"),
-            });
-        }
+        let resolved = db.resolve_webhook_integration(&token).await.unwrap();
+        assert_eq!((resolved.id, resolved.user_id), (integration.id, bob.id));
+        assert!(resolved.token.is_none());
+        assert_not_found(db.resolve_webhook_integration("guessed").await);
 
-        Ok(tasks)
+        assert!(db.list_webhook_integrations(alice.id).await.unwrap().is_empty());
+        assert_not_found(db.delete_webhook_integration(alice.id, integration.id).await);
+        db.delete_webhook_integration(bob.id, integration.id).await.unwrap();
+        assert_not_found(db.resolve_webhook_integration(&token).await);
     }
 
-    pub async fn get_all_tasks(&self) -> ServiceResult<Vec<Task>> {
-        let rows = sqlx::query("SELECT * FROM tasks ORDER BY created_at DESC")
-            .fetch_all(&self.pool)
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn jira_issues_lead_back_to_their_task() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let case = case_of(&db, &bob).await;
+        let task = task_of(&db, &case).await;
+        let key = format!("OPS-{}", task.id.as_u128() % 100_000);
+        let linked = UpdateTaskRequest {
+            metadata: Some(serde_json::json!({ "jira": { "key": key, "url": format!("https://jira.example.com/browse/{}", key) } })),
+            ..task_update()
+        };
+        db.update_task(task.id, Scope::User(bob.id), linked).await.unwrap();
+
+        assert_eq!(db.find_task_by_jira_issue(Scope::Internal, &key).await.unwrap().id, task.id);
+        assert_not_found(db.find_task_by_jira_issue(Scope::User(alice.id), &key).await);
+        assert_not_found(db.find_task_by_jira_issue(Scope::Internal, "OPS-0").await);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn task_listings_filter_and_page_in_the_database() {
+        let db = database().await;
+        let owner = user(&db).await;
+        let case = case_of(&db, &owner).await;
+        let plain = task_of(&db, &case).await;
+        for _ in 0..3 {
+            db.create_task(Task {
+                id: Uuid::new_v4(),
+                task_type: TaskType::Other("Errand".to_string()),
+                tags: vec!["Moving Day".to_string()],
+                ..plain.clone()
+            })
             .await
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+            .unwrap();
+        }
+        let scope = Scope::User(owner.id);
 
-        let mut tasks = Vec::new();
-        for row in rows {
-            tasks.push(Task {
-                id: row.get("id"),
-                user_id: row.get("user_id"),
-                case_id: row.get("case_id"),
-                title: row.get("title"),
-                description: row.get("description"),
-                task_type: serde_json::from_str(&row.get::<String, _>("task_type"))
-                    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-                status: serde_json::from_str(&row.get::<String, _>("status"))
-                    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-                priority: serde_json::from_str(&row.get::<String, _>("priority"))
-                    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-                due_date: row.get("due_date"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                completed_at: row.get("completed_at"),
-                metadata: row.get("metadata"),
-            });
+        let errands = db.list_tasks(scope, None, Some("moving-day"), Some("errand"), None, 0).await.unwrap();
+        assert_eq!(errands.len(), 3);
+        assert!(errands.iter().all(|task| task.id != plain.id));
+        let communication = db.list_tasks(scope, None, None, Some("Communication"), None, 0).await.unwrap();
+        assert_eq!(communication.iter().map(|task| task.id).collect::<Vec<_>>(), [plain.id]);
+
+        let first = db.list_tasks(scope, None, Some("moving-day"), None, Some(2), 0).await.unwrap();
+        let rest = db.list_tasks(scope, None, Some("moving-day"), None, Some(2), 2).await.unwrap();
+        assert_eq!((first.len(), rest.len()), (2, 1));
+        assert!(rest.iter().all(|task| first.iter().all(|seen| seen.id != task.id)), "pages do not overlap");
+    }
+
+    fn sso_claims(email: &str, email_verified: bool) -> SsoLoginRequest {
+        SsoLoginRequest {
+            provider: "google".to_string(),
+            issuer: "https://accounts.google.com".to_string(),
+            subject: Uuid::new_v4().to_string(),
+            email: email.to_string(),
+            email_verified,
+            full_name: Some("Single Sign-On".to_string()),
+            client: ClientInfo::default(),
         }
+    }
 
-        Ok(tasks)
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn sso_sign_ins_provision_and_link_accounts() {
+        let db = database().await;
+
+        let claims = sso_claims(&format!("{}@isolation.test", Uuid::new_v4()), true);
+        let provisioned = db.sso_login(&claims).await.unwrap();
+        assert_eq!((provisioned.email.as_str(), provisioned.full_name.as_str()), (claims.email.as_str(), "Single Sign-On"));
+        assert_eq!(db.sso_login(&claims).await.unwrap().id, provisioned.id, "known identities sign in as their user");
+        let password_login = db.authenticate_user(LoginRequest {
+            email: claims.email.clone(),
+            password: String::new(),
+            client: ClientInfo::default(),
+        }).await;
+        assert!(matches!(password_login, Err(ServiceError::Unauthorized(_))));
+        let only = db.list_sso_identities(provisioned.id).await.unwrap();
+        assert!(matches!(db.delete_sso_identity(provisioned.id, only[0].id).await, Err(ServiceError::BadRequest(_))));
+
+        let alice = user(&db).await;
+        let unverified = sso_claims(&alice.email.to_uppercase(), false);
+        assert!(matches!(db.sso_login(&unverified).await, Err(ServiceError::Unauthorized(_))));
+        let verified = sso_claims(&alice.email.to_uppercase(), true);
+        assert!(
+            matches!(db.sso_login(&verified).await, Err(ServiceError::Unauthorized(_))),
+            "not while the account's email is unverified"
+        );
+        let audit = audit(AdminAction::ResendVerification, &alice, &alice);
+        let verification = db.create_email_verification(alice.id, Utc::now() + chrono::Duration::hours(1), &audit).await.unwrap();
+        db.verify_email(&verification.token).await.unwrap();
+        assert_eq!(db.sso_login(&verified).await.unwrap().id, alice.id);
+
+        let linked = db.link_sso_identity(alice.id, &unverified).await.unwrap();
+        assert_eq!(db.sso_login(&unverified).await.unwrap().id, alice.id);
+        assert!(matches!(db.link_sso_identity(provisioned.id, &unverified).await, Err(ServiceError::BadRequest(_))));
+        assert_eq!(db.list_sso_identities(alice.id).await.unwrap().len(), 2);
+        assert_not_found(db.delete_sso_identity(provisioned.id, linked.id).await);
+        db.delete_sso_identity(alice.id, linked.id).await.unwrap();
+        assert_eq!(db.list_sso_identities(alice.id).await.unwrap().len(), 1);
     }
 
-    pub async fn get_tasks_by_status(&self, status: TaskStatus) -> ServiceResult<Vec<Task>> {
-        let status_str = serde_json::to_string(&status)
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
-        let rows = sqlx::query("SELECT * FROM tasks WHERE status = $1 ORDER BY created_at DESC")
-            .bind(status_str)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn oauth_states_are_taken_once_until_they_expire() {
+        let db = database().await;
+        let payload = serde_json::json!({ "pkce_verifier": "verifier" });
 
-        let mut tasks = Vec::new();
-        for row in rows {
-            tasks.push(Task {
-                id: row.get("id"),
-                user_id: row.get("user_id"),
-                case_id: row.get("case_id"),
-                title: row.get("title"),
-                description: row.get("description"),
-                task_type: serde_json::from_str(&row.get::<String, _>("task_type"))
-                    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-                status: serde_json::from_str(&row.get::<String, _>("status"))
-                    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-                priority: serde_json::from_str(&row.get::<String, _>("priority"))
-                    .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-                due_date: row.get("due_date"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                completed_at: row.get("completed_at"),
-                metadata: row.get("metadata"),
-            });
+        let state = Uuid::new_v4().to_string();
+        db.save_oauth_state(&state, &payload, Utc::now() + chrono::Duration::minutes(10)).await.unwrap();
+        assert_eq!(db.take_oauth_state(&state).await.unwrap(), payload);
+        assert_not_found(db.take_oauth_state(&state).await);
+
+        let expired = Uuid::new_v4().to_string();
+        db.save_oauth_state(&expired, &payload, Utc::now() - chrono::Duration::seconds(1)).await.unwrap();
+        assert_not_found(db.take_oauth_state(&expired).await);
+    }
+
+    fn mailbox(owner: &User, address: &str, token: &str) -> EmailAccount {
+        EmailAccount {
+            id: Uuid::new_v4(),
+            user_id: owner.id,
+            email_address: address.to_string(),
+            provider: EmailProvider::Office365,
+            is_active: true,
+            oauth_token: Some(token.to_string()),
+            oauth_refresh_token: None,
+            oauth_expires_at: None,
+            imap_settings: None,
+            scopes: vec!["Mail.Read".to_string()],
+            health: Default::default(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: serde_json::json!({}),
         }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn mailboxes_belong_to_the_user_who_connected_them() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let address = format!("{}@mailbox.test", Uuid::new_v4());
 
-        Ok(tasks)
+        let connected = db.save_email_account(mailbox(&alice, &address, "first")).await.unwrap();
+        let reconnected = db.save_email_account(mailbox(&alice, &address.to_uppercase(), "second")).await.unwrap();
+        assert_eq!(reconnected.id, connected.id, "connecting again refreshes the tokens");
+        assert_eq!(reconnected.oauth_token.as_deref(), Some("second"));
+        assert!(matches!(
+            db.save_email_account(mailbox(&bob, &address, "stolen")).await,
+            Err(ServiceError::BadRequest(_))
+        ));
+
+        assert_eq!(db.list_email_accounts(alice.id).await.unwrap().len(), 1);
+        assert!(db.list_email_accounts(bob.id).await.unwrap().is_empty());
+        let all = db.all_email_accounts().await.unwrap();
+        let polled = all.iter().find(|account| account.id == connected.id).expect("connected mailbox");
+        assert_eq!((polled.user_id, polled.oauth_token.as_deref()), (alice.id, Some("second")));
     }
 
-    // Conversation operations
-    pub async fn get_conversation_history(&self, case_id: Uuid) -> ServiceResult<Vec<ConversationEntry>> {
-        let rows = sqlx::query("SELECT * FROM conversation_entries WHERE case_id = $1 ORDER BY timestamp ASC")
-            .bind(case_id)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn disconnected_mailboxes_keep_no_tokens() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let address = format!("{}@mailbox.test", Uuid::new_v4());
+        let connected = db.save_email_account(mailbox(&alice, &address, "token")).await.unwrap();
 
-        let mut entries = Vec::new();
-        for row in rows {
-            entries.push(ConversationEntry {
-                id: row.get("id"),
-                user_id: row.get("user_id"),
-                case_id: row.get("case_id"),
-                message: row.get("message"),
-                sender: serde_json::from_str(&row.get::<String, _>("sender")).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Deserialization error: {}", e)))?,
-                timestamp: row.get("timestamp"),
-                metadata: row.get("metadata"),
-            });
+        assert_not_found(db.disconnect_email_account(bob.id, connected.id).await);
+        let disconnected = db.disconnect_email_account(alice.id, connected.id).await.unwrap();
+        assert!(!disconnected.is_active);
+        assert_eq!((disconnected.oauth_token, disconnected.oauth_refresh_token), (None, None));
+
+        let reconnected = db.save_email_account(mailbox(&alice, &address, "again")).await.unwrap();
+        assert_eq!(reconnected.id, connected.id);
+        assert!(reconnected.is_active);
+        assert_eq!(reconnected.scopes, ["Mail.Read"]);
+
+        let health = MailboxHealth { throttled_until: Some(Utc::now()), throttled_total: 3, ..Default::default() };
+        db.set_email_account_health(connected.id, &health).await.unwrap();
+        assert_eq!(db.list_email_accounts(alice.id).await.unwrap()[0].health, health);
+
+        assert_not_found(db.delete_email_account(bob.id, connected.id).await);
+        db.delete_email_account(alice.id, connected.id).await.unwrap();
+        assert!(db.list_email_accounts(alice.id).await.unwrap().is_empty());
+    }
+
+    fn api_token(owner: &User, expires_at: Option<chrono::DateTime<Utc>>) -> ApiToken {
+        ApiToken {
+            id: Uuid::new_v4(),
+            user_id: owner.id,
+            name: "CLI".to_string(),
+            scopes: vec![ApiScope::TasksRead],
+            created_at: Utc::now(),
+            expires_at,
+            last_used_at: None,
+            revoked_at: None,
+            token: Some(format!("tm_pat_{}", Uuid::new_v4().simple())),
         }
+    }
 
-        Ok(entries)
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn api_tokens_work_until_revoked_or_expired() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let token = db.create_api_token(api_token(&bob, None)).await.unwrap();
+        let secret = token.token.clone().unwrap();
+
+        let (holder, scopes) = db.validate_api_token(&secret).await.unwrap();
+        assert_eq!((holder.id, scopes), (bob.id, vec![ApiScope::TasksRead]));
+        let listed = db.list_api_tokens(bob.id).await.unwrap();
+        assert!(listed[0].last_used_at.is_some() && listed[0].token.is_none());
+
+        assert_not_found(db.revoke_api_token(alice.id, token.id).await);
+        assert!(db.revoke_api_token(bob.id, token.id).await.unwrap().revoked_at.is_some());
+        assert_not_found(db.revoke_api_token(bob.id, token.id).await);
+        assert!(matches!(db.validate_api_token(&secret).await, Err(ServiceError::Unauthorized(_))));
+
+        let expired = db.create_api_token(api_token(&bob, Some(Utc::now() - chrono::Duration::minutes(1)))).await.unwrap();
+        assert!(matches!(db.validate_api_token(&expired.token.unwrap()).await, Err(ServiceError::Unauthorized(_))));
+        assert_eq!(db.list_api_tokens(bob.id).await.unwrap().len(), 2);
     }
 
-    pub async fn add_conversation_entry(&self, entry: ConversationEntry) -> ServiceResult<ConversationEntry> {
-        sqlx::query(
-            r#"
-            INSERT INTO conversation_entries (id, case_id, message, sender, timestamp, metadata)
-            VALUES ($1, $2, $3, $4, $5, $6)
-            "#
-        )
-        .bind(entry.id)
-        .bind(entry.case_id)
-        .bind(&entry.message)
-        .bind(serde_json::to_string(&entry.sender).map_err(|e| ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?)
-        .bind(entry.timestamp)
-        .bind(&entry.metadata)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Database error: {}", e)))?;
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn llm_budgets_alert_once_and_keys_stay_until_removed() {
+        let db = database().await;
+        let organization = format!("org-{}", Uuid::new_v4());
+        let (admin, outsider) = (user(&db).await, member_of(&db, &organization).await);
+        db.add_organization_member(&organization, admin.id).await.unwrap();
+        let request = OrganizationLlmRequest {
+            model: Some("gpt-4o".to_string()),
+            api_key: Some("sk-org".to_string()),
+            monthly_token_budget: Some(1000),
+            alert_user_id: Some(admin.id),
+            ..Default::default()
+        };
+        db.set_organization_llm_settings(&organization, &request).await.unwrap();
 
-        Ok(entry)
+        let request = OrganizationLlmRequest { api_key: None, ..request };
+        let settings = db.set_organization_llm_settings(&organization, &request).await.unwrap();
+        assert_eq!(settings.api_key.as_deref(), Some("sk-org"));
+        assert!(
+            db.organization_llm_of(outsider.id).await.unwrap().is_none(),
+            "naming the organization on registration doesn't make a member"
+        );
+
+        let (usage, alert) = db.record_llm_usage(&organization, 600).await.unwrap();
+        assert!(!alert && !usage.exhausted());
+        let (usage, alert) = db.record_llm_usage(&organization, 600).await.unwrap();
+        assert!(alert && usage.exhausted());
+        let (usage, alert) = db.record_llm_usage(&organization, 10).await.unwrap();
+        assert_eq!((usage.tokens_used, alert), (1210, false));
+        assert_eq!(db.organization_llm_of(admin.id).await.unwrap().unwrap().usage.tokens_used, 1210);
+
+        let request = OrganizationLlmRequest { api_key: Some(String::new()), ..request };
+        let settings = db.set_organization_llm_settings(&organization, &request).await.unwrap();
+        assert!(settings.api_key.is_none() && !settings.has_api_key);
+        db.delete_organization_llm_settings(&organization).await.unwrap();
+        assert_not_found(db.delete_organization_llm_settings(&organization).await);
     }
 
-    // Workflow operations (simplified)
-    pub async fn get_case_workflow(&self, case_id: Uuid) -> ServiceResult<CaseWorkflow> {
-        // For now, return a default workflow - in production, this would be stored in DB
-        Ok(CaseWorkflow {
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn organization_members_are_added_by_administrators() {
+        let db = database().await;
+        let (organization, other) = (format!("org-{}", Uuid::new_v4()), format!("org-{}", Uuid::new_v4()));
+        let alice = user(&db).await;
+        db.set_organization_llm_settings(&organization, &OrganizationLlmRequest::default()).await.unwrap();
+
+        let claimed = member_of(&db, &organization).await;
+        assert_eq!(db.organization_of(claimed.id).await.unwrap(), None, "not by naming it in the profile");
+
+        let member = db.add_organization_member(&organization, alice.id).await.unwrap();
+        assert_eq!((member.organization.as_str(), member.user_id), (organization.as_str(), alice.id));
+        assert_eq!(db.organization_of(alice.id).await.unwrap(), Some(organization.clone()));
+        assert_eq!(db.list_organization_members(&organization).await.unwrap(), vec![member]);
+        assert_eq!(db.organization_llm_of(alice.id).await.unwrap().unwrap().settings.organization, organization);
+
+        db.add_organization_member(&other, alice.id).await.unwrap();
+        assert!(db.list_organization_members(&organization).await.unwrap().is_empty(), "one organization at most");
+        assert!(db.organization_llm_of(alice.id).await.unwrap().is_none());
+        assert_not_found(db.remove_organization_member(&organization, alice.id).await);
+        db.remove_organization_member(&other, alice.id).await.unwrap();
+        assert_not_found(db.add_organization_member(&organization, Uuid::new_v4()).await);
+        db.delete_organization_llm_settings(&organization).await.unwrap();
+    }
+
+    fn audit(action: AdminAction, target: &User, actor: &User) -> AdminAuditEntry {
+        AdminAuditEntry {
             id: Uuid::new_v4(),
-            case_id,
-            current_step: "initial".to_string(),
-            steps: vec![
-                WorkflowStep {
-                    name: "initial".to_string(),
-                    description: "Case created".to_string(),
-                    status: StepStatus::Completed,
-                    required_actions: vec![],
-                    completed_at: Some(Utc::now()),
-                },
-                WorkflowStep {
-                    name: "processing".to_string(),
-                    description: "Processing tasks".to_string(),
-                    status: StepStatus::Active,
-                    required_actions: vec!["Complete tasks".to_string()],
-                    completed_at: None,
-                },
-            ],
+            action,
+            target_user_id: target.id,
+            actor: actor.email.clone(),
+            reason: Some("on call".to_string()),
             created_at: Utc::now(),
-            updated_at: Utc::now(),
-        })
+        }
     }
 
-    pub async fn update_case_workflow(&self, workflow: CaseWorkflow) -> ServiceResult<CaseWorkflow> {
-        // For now, just return the workflow - in production, this would update the DB
-        Ok(workflow)
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn staff_roles_are_audited_and_staff_are_not_impersonated() {
+        let db = database().await;
+        let (admin, agent) = (user(&db).await, user(&db).await);
+        assert_eq!(db.staff_role(agent.id).await.unwrap(), None);
+
+        let grant = audit(AdminAction::GrantRole(StaffRole::Support), &agent, &admin);
+        db.set_staff_role(agent.id, Some(StaffRole::Support), &grant).await.unwrap();
+        assert_eq!(db.staff_role(agent.id).await.unwrap(), Some(StaffRole::Support));
+        let expires_at = Utc::now() + chrono::Duration::minutes(30);
+        let impersonate = audit(AdminAction::Impersonate, &agent, &admin);
+        assert!(matches!(
+            db.create_impersonation_session(agent.id, expires_at, &impersonate).await,
+            Err(ServiceError::BadRequest(_))
+        ));
+
+        db.set_staff_role(agent.id, None, &audit(AdminAction::RevokeRole, &agent, &admin)).await.unwrap();
+        assert_eq!(db.staff_role(agent.id).await.unwrap(), None);
+        let actions: Vec<_> = db.list_admin_audit(Some(agent.id), 10).await.unwrap().iter().map(|entry| entry.action).collect();
+        assert_eq!(actions, [AdminAction::RevokeRole, AdminAction::GrantRole(StaffRole::Support)]);
+        assert_not_found(db.set_staff_role(Uuid::new_v4(), None, &grant).await);
     }
 }
@@ -0,0 +1,124 @@
+//! The job queue on this service's own database, and the recurring jobs
+//! this service runs: overdue task reminders, case retention, expired
+//! session cleanup, the outbox relay and pruning of finished jobs and
+//! published events. Sign-in alerts are sent from here too.
+
+use axum::async_trait;
+use chrono::Utc;
+use common::config::JobsConfig;
+use common::jobs::{JobQueue, Worker};
+use common::ServiceResult;
+use models::{ClaimJobsRequest, EnqueueJobRequest, Job, JobResult, ScheduleJobRequest};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::database_working::Database;
+use crate::notifications::Notifier;
+use crate::outbox::{self, Relay};
+use crate::retention::{self, RetentionPolicy};
+use crate::security::{self, AlertMailer};
+
+/// How long succeeded jobs and published outbox events are kept, for
+/// inspection.
+const FINISHED_JOB_DAYS: i64 = 7;
+
+#[async_trait]
+impl JobQueue for Database {
+    async fn enqueue(&self, request: &EnqueueJobRequest) -> ServiceResult<Job> {
+        self.enqueue_job(request).await
+    }
+
+    async fn schedule(&self, request: &ScheduleJobRequest) -> ServiceResult<Job> {
+        self.schedule_job(request).await
+    }
+
+    async fn claim(&self, request: &ClaimJobsRequest) -> ServiceResult<Vec<Job>> {
+        self.claim_jobs(request).await
+    }
+
+    async fn finish(&self, id: Uuid, result: &JobResult) -> ServiceResult<Job> {
+        self.finish_job(id, result).await
+    }
+}
+
+pub fn start_worker(
+    db: Database,
+    notifier: Notifier,
+    relay: Relay,
+    policy: RetentionPolicy,
+    jobs: &JobsConfig,
+    mailer: AlertMailer,
+) {
+    let queue: Arc<dyn JobQueue> = Arc::new(db.clone());
+    let mut worker = Worker::new(queue, "persistence-service", jobs)
+        .recurring("notifications.overdue", Duration::from_secs(jobs.notification_sweep_secs), move |_| {
+            let notifier = notifier.clone();
+            async move {
+                notifier.notify_overdue().await?;
+                Ok(())
+            }
+        })
+        .handle(security::LOGIN_ALERT_JOB, move |job| {
+            let mailer = mailer.clone();
+            async move { mailer.send(job).await }
+        });
+
+    if policy.archive_after.is_some() || policy.purge_after.is_some() {
+        let db = db.clone();
+        worker = worker.recurring("cases.retention", Duration::from_secs(jobs.case_retention_sweep_secs), move |_| {
+            let (db, policy) = (db.clone(), policy.clone());
+            async move {
+                retention::apply(&db, &policy).await?;
+                Ok(())
+            }
+        });
+    }
+
+    let sessions_db = db.clone();
+    worker
+        .recurring("sessions.cleanup", Duration::from_secs(jobs.session_cleanup_secs), move |_| {
+            let db = sessions_db.clone();
+            async move {
+                let deleted = db.delete_expired_sessions().await?;
+                if deleted > 0 {
+                    info!("Deleted {} expired sessions", deleted);
+                }
+                let expired = db.delete_expired_oauth_states().await?;
+                if expired > 0 {
+                    info!("Deleted {} expired OAuth states", expired);
+                }
+                let pruned = db
+                    .prune_login_events(Utc::now() - chrono::Duration::days(security::HISTORY_DAYS))
+                    .await?;
+                if pruned > 0 {
+                    info!("Pruned {} old sign-ins", pruned);
+                }
+                Ok(())
+            }
+        })
+        .recurring(outbox::RELAY_JOB, Duration::from_secs(jobs.outbox_relay_secs), move |_| {
+            let relay = relay.clone();
+            async move {
+                relay.relay().await?;
+                Ok(())
+            }
+        })
+        .recurring("jobs.prune", Duration::from_secs(24 * 3600), move |_| {
+            let db = db.clone();
+            async move {
+                let cutoff = Utc::now() - chrono::Duration::days(FINISHED_JOB_DAYS);
+                let pruned = db.prune_jobs(cutoff).await?;
+                if pruned > 0 {
+                    info!("Pruned {} finished jobs", pruned);
+                }
+                let pruned = db.prune_outbox(cutoff).await?;
+                if pruned > 0 {
+                    info!("Pruned {} published outbox events", pruned);
+                }
+                Ok(())
+            }
+        })
+        .start();
+}
@@ -1,58 +1,356 @@
 use axum::{
     extract::{Path, State, Query},
-    http::StatusCode,
-    response::Json,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put, delete},
     Router,
 };
-use common::{config::ServiceConfig, HealthResponse, ServiceResult};
+use common::{config::ServiceConfig, logging, reload::LiveConfig, HealthResponse, ServiceResult};
 use models::{
-    Case, Task, ConversationEntry, CaseWorkflow,
-    UpdateCaseRequest, UpdateTaskRequest, TaskStatus,
+    Case, Task, ConversationEntry, MessageSender, CaseWorkflow, EmailThread,
+    UpdateCaseRequest, UpdateTaskRequest, TaskStatus, CaseStatus,
     RegisterRequest, LoginRequest, LoginResponse, UserProfile,
+    UserBackup, CaseBackup, RestoreConflictMode, RestoreSummary, User,
+    AccountDeletion, AccountDeletionRequest, AccountDeletionResponse, AccountDeletionStatus,
+    StatsGrouping, TaskStats, ProductivityStats, CaseProductivity, UserProductivity, WorkloadDay,
+    Notification, NotificationKind, NotificationList, CreateNotificationRequest,
+    PushSubscription, PushSubscriptionRequest, UnsubscribePushRequest, VapidPublicKey,
+    TaskSync, UserPreferences, UpdatePreferencesRequest, TaskTemplate, TaskTemplateRequest, TagUsage,
+    SavedFilter, SavedFilterRequest, QuarantinedMessage, QuarantineStatus, ReviewQuarantineRequest,
+    TaskFeedback, FeedbackVerdict, ExtractionExample, ExtractionExampleRequest,
+    CustomTaskType, CustomTaskTypeRequest, CaseEvent, CaseEventKind, CaseEventRequest, CaseReadReceipt,
+    Revision, RevisionEntity, AdminAction, AdminActionRequest, AdminAuditEntry, EmailVerification, StaffRole, StaffRoleRequest,
+    UserUsage, VerifyEmailRequest,
+    Job, JobStatus, EnqueueJobRequest, ScheduleJobRequest, ClaimJobsRequest, JobResult,
+    FailedMessage, FailedMessageStatus, CreateFailedMessageRequest, FailedMessageAttempt,
+    OutboxLag, SeedRequest, SeedResponse,
+    TransferCaseRequest, CreateShareLinkRequest, ShareLink, SharedCase, Mention,
+    FollowUpQuestion, QuestionStatus, AnswerQuestionsRequest,
+    WebhookIntegration, WebhookIntegrationRequest, ResolveWebhookRequest,
+    SsoIdentity, SsoLoginRequest, ApiToken, CreateApiTokenRequest, LoginEvent, SaveOAuthStateRequest,
+    EmailAccount, AddEmailAccountRequest, MailboxHealth,
+    OrganizationLlm, OrganizationLlmRequest, OrganizationLlmSettings, OrganizationMember, LlmAccountRequest, LlmUsage, RecordLlmUsageRequest,
 };
 use std::sync::Arc;
 use tower::ServiceBuilder;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 use uuid::Uuid;
 
+mod auth;
 mod database_working;
+mod jobs;
+mod mentions;
+mod notifications;
+mod outbox;
+mod push;
+mod retention;
+mod revisions;
+mod scope;
+mod security;
+mod seed;
+mod sharing;
+mod tags;
+#[cfg(test)]
+mod test_db;
+use auth::{AdminAuth, CurrentUser, InternalCall, OptionalUser, ServiceCall, StaffUser};
 use database_working::Database;
+use notifications::Notifier;
+use push::{PushSender, VapidKeys};
+use scope::Scope;
+use sharing::ShareLinks;
 
 #[derive(Clone)]
 struct AppState {
     config: ServiceConfig,
     db: Database,
+    notifier: Notifier,
+    admin_token: Option<String>,
+    /// How long a requested account deletion waits before it can be confirmed.
+    deletion_grace_period: chrono::Duration,
+    /// `SEED_ENABLED`: whether synthetic load-test data can be created.
+    seed_enabled: bool,
+    share_links: ShareLinks,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// A state whose database connects on first use, for exercising
+    /// extractors and handlers that never reach it.
+    fn for_tests() -> Arc<Self> {
+        let db = Database::lazy("postgres://localhost/unused");
+        let push = PushSender::new(db.clone(), VapidKeys::generate());
+        Arc::new(Self {
+            config: ServiceConfig::for_tests("persistence-service"),
+            notifier: Notifier::new(db.clone(), push),
+            db,
+            admin_token: None,
+            deletion_grace_period: chrono::Duration::days(30),
+            seed_enabled: false,
+            share_links: ShareLinks::new(None),
+        })
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
 struct TaskQuery {
     status: Option<TaskStatus>,
+    tag: Option<String>,
+    /// Type name, built-in or custom.
+    task_type: Option<String>,
+    /// Page size; every matching task is returned when omitted.
+    limit: Option<i64>,
+    #[serde(default)]
+    offset: i64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct SyncQuery {
+    since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct CaseQuery {
+    status: Option<CaseStatus>,
+    tag: Option<String>,
+    /// Archived cases are only listed when asked for, by this or by
+    /// `status=Archived`.
+    #[serde(default)]
+    include_archived: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct HistoryQuery {
+    /// Only entries after this time, for fetching what is new.
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only entries before this time, for paging back through older ones.
+    before: Option<chrono::DateTime<chrono::Utc>>,
+    sender: Option<MessageSender>,
+    /// Return only the latest `limit` matching entries.
+    limit: Option<i64>,
+}
+
+/// Largest page of conversation entries a request may ask for.
+const MAX_HISTORY_LIMIT: i64 = 500;
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum BackupFormat {
+    #[default]
+    Json,
+    Markdown,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BackupQuery {
+    #[serde(default)]
+    format: BackupFormat,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct RestoreQuery {
+    #[serde(default)]
+    conflict: RestoreConflictMode,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StatsQuery {
+    #[serde(default)]
+    group_by: StatsGrouping,
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StatsRangeQuery {
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct WorkloadQuery {
+    from: Option<chrono::NaiveDate>,
+    to: Option<chrono::NaiveDate>,
+}
+
+/// Days reported by the workload endpoint when `to` is not given.
+const DEFAULT_WORKLOAD_DAYS: i64 = 7;
+
+/// Range reported when a stats request does not give `from`.
+const DEFAULT_STATS_DAYS: i64 = 30;
+
+/// Resolves an optional stats range, defaulting to the last
+/// [`DEFAULT_STATS_DAYS`] days.
+fn stats_range(
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+) -> ServiceResult<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)> {
+    let to = to.unwrap_or_else(chrono::Utc::now);
+    let from = from.unwrap_or(to - chrono::Duration::days(DEFAULT_STATS_DAYS));
+    if from > to {
+        return Err(common::ServiceError::BadRequest("`from` must be before `to`".to_string()));
+    }
+    Ok((from, to))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct QuarantineQuery {
+    status: Option<QuarantineStatus>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct FailedMessageQuery {
+    status: Option<FailedMessageStatus>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct QuestionQuery {
+    status: Option<QuestionStatus>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ClaimQuery {
+    limit: Option<i64>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TaskFeedbackQuery {
+    verdict: Option<FeedbackVerdict>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct NotificationQuery {
+    #[serde(default)]
+    unread_only: bool,
+    #[serde(default = "default_notification_limit")]
+    limit: i64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct LoginHistoryQuery {
+    limit: Option<i64>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct MentionQuery {
+    limit: Option<i64>,
+}
+
+fn default_notification_limit() -> i64 {
+    20
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DeletionQuery {
+    status: Option<AccountDeletionStatus>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ConfirmDeletionQuery {
+    /// Lets an administrator act on a deletion before its grace period ends.
+    #[serde(default)]
+    skip_grace_period: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct UserSearchQuery {
+    /// Part of the email or full name, case-insensitive.
+    search: Option<String>,
+    active: Option<bool>,
+    limit: Option<i64>,
+    #[serde(default)]
+    offset: i64,
 }
 
+#[derive(Debug, serde::Deserialize)]
+struct AuditQuery {
+    user_id: Option<Uuid>,
+    limit: Option<i64>,
+}
+
+/// Page size of admin user searches and audit listings.
+const DEFAULT_ADMIN_PAGE: i64 = 50;
+const MAX_ADMIN_PAGE: i64 = 500;
+/// Largest page of tasks a single listing returns.
+const MAX_TASK_PAGE: i64 = 1000;
+/// How long an emailed verification token stays valid.
+const VERIFICATION_TOKEN_HOURS: i64 = 48;
+/// How long a support session opened by impersonation lasts.
+const IMPERSONATION_MINUTES: i64 = 30;
+
+/// Allowed range for the dashboard's page size.
+const MIN_TASKS_PER_PAGE: u32 = 6;
+const MAX_TASKS_PER_PAGE: u32 = 96;
+/// Allowed range for the daily capacity used by workload warnings.
+const MIN_DAILY_CAPACITY_MINUTES: u32 = 30;
+const MAX_DAILY_CAPACITY_MINUTES: u32 = 24 * 60;
+/// Furthest a user's clock can be from UTC (UTC-12 to UTC+14).
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// Bumped whenever the backup layout changes incompatibly.
+const BACKUP_VERSION: u32 = 1;
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
     
-    let config = ServiceConfig::from_env("persistence-service", 8005);
+    let config = ServiceConfig::load("persistence-service", 8005).await?;
     
-    tracing_subscriber::fmt()
-        .with_env_filter(&config.log_level)
-        .init();
+    let live = LiveConfig::init(&config);
+
+    config.start_refresh();
 
     // Initialize database
-    let database_url = config.database_url.as_ref()
-        .ok_or_else(|| anyhow::anyhow!("DATABASE_URL environment variable is required"))?;
-    
+    let database_url = config.require_database_url()?;
+
     let db = Database::new(database_url).await
         .map_err(|e| anyhow::anyhow!("Failed to initialize database: {}", e))?;
     db.migrate().await
         .map_err(|e| anyhow::anyhow!("Failed to run migrations: {}", e))?;
 
+    // Rotated database credentials apply to new connections.
+    {
+        let db = db.clone();
+        let secrets = config.secret_values.clone();
+        let mut changes = secrets.subscribe();
+        tokio::spawn(async move {
+            while changes.changed().await.is_ok() {
+                if let Some(url) = secrets.database_url() {
+                    if let Err(e) = db.set_url(&url) {
+                        warn!("Rotated database URL is invalid: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    let grace_days = std::env::var("ACCOUNT_DELETION_GRACE_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(30);
+
+    let vapid = VapidKeys::load(&db, &config.push).await
+        .map_err(|e| anyhow::anyhow!("Failed to load VAPID keys: {}", e))?;
+    let notifier = Notifier::new(db.clone(), PushSender::new(db.clone(), vapid));
+
+    jobs::start_worker(
+        db.clone(),
+        notifier.clone(),
+        outbox::Relay::new(db.clone(), &config.outbox),
+        retention::RetentionPolicy::from_env(),
+        &config.jobs,
+        security::AlertMailer::new(&config),
+    );
+
+    grant_configured_admins(&db, &config.admin.emails).await;
+
     let state = AppState {
         config: config.clone(),
         db,
+        notifier,
+        admin_token: config.admin.api_token.clone(),
+        deletion_grace_period: chrono::Duration::days(grace_days),
+        seed_enabled: config.admin.seed_enabled,
+        share_links: ShareLinks::new(config.sharing.link_secret.clone()),
     };
 
     let app = Router::new()
@@ -61,26 +359,158 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/v1/auth/register", post(register_user))
         .route("/api/v1/auth/login", post(login_user))
         .route("/api/v1/auth/validate", post(validate_session))
+        .route("/api/v1/auth/verify-email", post(verify_email))
+        .route("/api/v1/auth/sso", post(sso_login))
+        .route("/api/v1/auth/identities", get(list_sso_identities).post(link_sso_identity))
+        .route("/api/v1/auth/identities/:id", delete(delete_sso_identity))
+        .route("/api/v1/oauth-states", post(save_oauth_state))
+        .route("/api/v1/oauth-states/:state/take", post(take_oauth_state))
+        // Backup routes
+        .route("/api/v1/users/:user_id", get(get_user))
+        .route("/api/v1/users/me/backup", get(backup_user_data))
+        .route("/api/v1/users/me/restore", post(restore_user_data))
+        .route("/api/v1/users/me/delete", post(request_account_deletion))
+        .route("/api/v1/users/me/tokens", get(list_api_tokens).post(create_api_token))
+        .route("/api/v1/users/me/tokens/:id", delete(revoke_api_token))
+        .route("/api/v1/users/me/logins", get(list_logins))
+        // Preferences
+        .route("/api/v1/users/me/preferences", get(get_preferences))
+        .route("/api/v1/users/me/preferences", put(update_preferences))
+        .route("/api/v1/users/me/mentions", get(list_mentions))
+        // Admin routes
+        .route("/api/v1/admin/deletions", get(list_account_deletions))
+        .route("/api/v1/admin/deletions/:user_id/confirm", post(confirm_account_deletion))
+        .route("/api/v1/admin/deletions/:user_id/cancel", post(cancel_account_deletion))
+        .route("/api/v1/admin/users", get(search_users))
+        .route("/api/v1/admin/users/:user_id/usage", get(get_user_usage))
+        .route("/api/v1/admin/users/:user_id/deactivate", post(deactivate_user))
+        .route("/api/v1/admin/users/:user_id/activate", post(activate_user))
+        .route("/api/v1/admin/users/:user_id/resend-verification", post(resend_verification))
+        .route("/api/v1/admin/users/:user_id/impersonate", post(impersonate_user))
+        .route("/api/v1/admin/users/:user_id/role", put(set_staff_role))
+        .route("/api/v1/admin/audit", get(list_admin_audit))
+        .route("/api/v1/admin/jobs", get(list_jobs))
+        .route("/api/v1/admin/jobs/:id/retry", post(retry_job))
+        .route("/api/v1/admin/outbox", get(get_outbox_lag))
+        .route("/api/v1/admin/seed", post(seed_data))
+        .route(
+            "/api/v1/admin/organizations/:organization/llm",
+            get(get_organization_llm).put(set_organization_llm).delete(delete_organization_llm),
+        )
+        .route("/api/v1/admin/organizations/:organization/members", get(list_organization_members))
+        .route(
+            "/api/v1/admin/organizations/:organization/members/:user_id",
+            put(add_organization_member).delete(remove_organization_member),
+        )
+        // LLM settings and token accounting, for the AI agent
+        .route("/api/v1/llm/account", post(llm_account))
+        .route("/api/v1/llm/usage", post(record_llm_usage))
+        // Job queue, for workers in other services
+        .route("/api/v1/jobs", post(enqueue_job))
+        .route("/api/v1/jobs/recurring", put(schedule_job))
+        .route("/api/v1/jobs/claim", post(claim_jobs))
+        .route("/api/v1/jobs/:id/finish", post(finish_job))
         // Case routes
         .route("/api/v1/cases", post(create_case))
+        .route("/api/v1/cases", get(list_cases))
+        .route("/api/v1/cases/by-email-thread", get(find_cases_by_email_thread))
         .route("/api/v1/cases/:id", get(get_case))
         .route("/api/v1/cases/:id", put(update_case))
         .route("/api/v1/cases/:id/history", get(get_conversation_history))
         .route("/api/v1/cases/:id/history", post(add_conversation_entry))
         .route("/api/v1/cases/:id/workflow", get(get_case_workflow))
         .route("/api/v1/cases/:id/workflow", put(update_case_workflow))
+        .route("/api/v1/cases/:id/email-thread", post(record_email_thread))
+        .route("/api/v1/cases/:id/questions", get(list_follow_up_questions))
+        .route("/api/v1/cases/:id/questions", post(create_follow_up_question))
+        .route("/api/v1/cases/:id/questions/answered", post(answer_follow_up_questions))
+        .route("/api/v1/cases/:id/events", get(list_case_events))
+        .route("/api/v1/cases/:id/events", post(create_case_event))
+        .route("/api/v1/cases/:id/read", post(mark_case_read))
+        .route("/api/v1/cases/:id/restore", post(restore_archived_case))
+        .route("/api/v1/cases/:id/revisions", get(list_case_revisions))
+        .route("/api/v1/cases/:id/revisions/:revision_id/restore", post(restore_case_revision))
+        .route("/api/v1/cases/:id/transfer", post(transfer_case))
+        .route("/api/v1/cases/:id/owner", get(get_case_owner))
+        .route("/api/v1/cases/:id/share", post(create_share_link))
+        .route("/api/v1/shared/:token", get(get_shared_case))
         // Task routes
         .route("/api/v1/tasks", post(create_task))
         .route("/api/v1/tasks", get(get_tasks))
+        .route("/api/v1/tasks/by-jira-issue/:key", get(find_task_by_jira_issue))
         .route("/api/v1/tasks/:id", get(get_task))
         .route("/api/v1/tasks/:id", put(update_task))
         .route("/api/v1/tasks/:id", delete(delete_task))
+        .route("/api/v1/tasks/:id/revisions", get(list_task_revisions))
+        .route("/api/v1/tasks/:id/revisions/:revision_id/restore", post(restore_task_revision))
         .route("/api/v1/cases/:case_id/tasks", get(get_tasks_for_case))
+        .route("/api/v1/sync/tasks", get(sync_tasks))
+        .route("/api/v1/tags", get(list_tags))
+        .route("/api/v1/saved-filters", get(list_saved_filters))
+        .route("/api/v1/saved-filters", post(create_saved_filter))
+        .route("/api/v1/saved-filters/:id", get(get_saved_filter))
+        .route("/api/v1/saved-filters/:id", put(update_saved_filter))
+        .route("/api/v1/saved-filters/:id", delete(delete_saved_filter))
+        .route("/api/v1/saved-filters/:id/tasks", get(get_saved_filter_tasks))
+        // Connected mailboxes
+        .route("/api/v1/email-accounts", get(list_email_accounts).post(add_email_account))
+        .route("/api/v1/email-accounts/all", get(all_email_accounts))
+        .route("/api/v1/email-accounts/:id", delete(delete_email_account))
+        .route("/api/v1/email-accounts/:id/disconnect", post(disconnect_email_account))
+        .route("/api/v1/email-accounts/:id/health", put(set_email_account_health))
+        .route("/api/v1/webhook-integrations", get(list_webhook_integrations))
+        .route("/api/v1/webhook-integrations", post(create_webhook_integration))
+        .route("/api/v1/webhook-integrations/resolve", post(resolve_webhook_integration))
+        .route("/api/v1/webhook-integrations/:id", delete(delete_webhook_integration))
+        // Task templates
+        .route("/api/v1/task-templates", get(list_task_templates))
+        .route("/api/v1/task-templates", post(create_task_template))
+        .route("/api/v1/task-templates/:name", get(get_task_template))
+        .route("/api/v1/task-templates/:name", put(update_task_template))
+        .route("/api/v1/task-templates/:name", delete(delete_task_template))
+        // Statistics
+        .route("/api/v1/stats/tasks", get(get_task_stats))
+        .route("/api/v1/stats/productivity", get(get_productivity_stats))
+        .route("/api/v1/stats/cases/:id", get(get_case_productivity))
+        .route("/api/v1/admin/stats/users", get(get_user_productivity))
+        .route("/api/v1/stats/workload", get(get_workload))
+        // Notifications
+        .route("/api/v1/notifications", get(list_notifications))
+        .route("/api/v1/notifications", post(create_notification))
+        .route("/api/v1/notifications/read-all", post(mark_all_notifications_read))
+        .route("/api/v1/notifications/:id/read", post(mark_notification_read))
+        // Web Push
+        .route("/api/v1/push/vapid-key", get(get_vapid_public_key))
+        .route("/api/v1/push/subscriptions", post(subscribe_push))
+        .route("/api/v1/push/unsubscribe", post(unsubscribe_push))
+        // Spam quarantine
+        .route("/api/v1/quarantine", post(create_quarantined_message))
+        .route("/api/v1/quarantine", get(list_quarantined_messages))
+        .route("/api/v1/quarantine/:id/review", post(review_quarantined_message))
+        // Messages whose processing failed
+        .route("/api/v1/failed-messages", post(create_failed_message))
+        .route("/api/v1/failed-messages", get(list_failed_messages))
+        .route("/api/v1/failed-messages/claim", post(claim_due_failed_messages))
+        .route("/api/v1/failed-messages/:id/claim", post(claim_failed_message))
+        .route("/api/v1/failed-messages/:id/attempts", post(record_failed_message_attempt))
+        // Feedback on AI-extracted tasks
+        .route("/api/v1/task-feedback", post(record_task_feedback))
+        .route("/api/v1/task-feedback", get(list_task_feedback))
+        // Organization task taxonomy
+        .route("/api/v1/task-types", get(list_custom_task_types))
+        .route("/api/v1/task-types", post(create_custom_task_type))
+        .route("/api/v1/task-types/:id", put(update_custom_task_type))
+        .route("/api/v1/task-types/:id", delete(delete_custom_task_type))
+        // Few-shot extraction examples
+        .route("/api/v1/extraction-examples", get(list_extraction_examples))
+        .route("/api/v1/extraction-examples", post(create_extraction_example))
+        .route("/api/v1/extraction-examples/:id", delete(delete_extraction_example))
         .with_state(Arc::new(state))
+        .merge(live.router())
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
+                .layer(logging::request_layers(&config.service_name))
+                .layer(config.cors.layer()),
         );
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port)).await?;
@@ -112,24 +542,29 @@ async fn register_user(
         is_active: user.is_active,
         created_at: user.created_at,
         last_login: user.last_login,
+        preferences: user.preferences,
     };
     
     Ok(Json(profile))
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, headers, request))]
 async fn login_user(
     State(state): State<Arc<AppState>>,
-    Json(request): Json<LoginRequest>,
+    headers: HeaderMap,
+    Json(mut request): Json<LoginRequest>,
 ) -> ServiceResult<Json<LoginResponse>> {
     info!("User login attempt: {}", request.email);
+    request.client = security::client_info(std::mem::take(&mut request.client), &headers, &state.config.secret_values);
+    let client = request.client.clone();
     let user = state.db.authenticate_user(request).await?;
     
     // Generate session token
     let session_token = uuid::Uuid::new_v4().to_string();
     let expires_at = chrono::Utc::now() + chrono::Duration::hours(24);
     
-    let _session = state.db.create_session(user.id, session_token.clone(), expires_at).await?;
+    let _session = state.db.create_session(user.id, session_token.clone(), expires_at, &client).await?;
+    security::record_login(&state.db, &user, &client).await;
     
     let profile = UserProfile {
         id: user.id,
@@ -139,6 +574,7 @@ async fn login_user(
         is_active: user.is_active,
         created_at: user.created_at,
         last_login: user.last_login,
+        preferences: user.preferences,
     };
     
     let response = LoginResponse {
@@ -150,11 +586,77 @@ async fn login_user(
     Ok(Json(response))
 }
 
+/// Signs in with the claims of an ID token the dashboard verified, creating
+/// the account on first sign-in.
+#[instrument(skip(state, _internal, headers, request))]
+async fn sso_login(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+    headers: HeaderMap,
+    Json(mut request): Json<SsoLoginRequest>,
+) -> ServiceResult<Json<LoginResponse>> {
+    info!("SSO login attempt: {} via {}", request.email, request.provider);
+    let client = security::client_info(std::mem::take(&mut request.client), &headers, &state.config.secret_values);
+    let user = state.db.sso_login(&request).await?;
+
+    let session_token = uuid::Uuid::new_v4().to_string();
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(24);
+    state.db.create_session(user.id, session_token.clone(), expires_at, &client).await?;
+    security::record_login(&state.db, &user, &client).await;
+
+    Ok(Json(LoginResponse { user: user.into(), session_token, expires_at }))
+}
+
+#[instrument(skip(state, user))]
+async fn list_sso_identities(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> ServiceResult<Json<Vec<SsoIdentity>>> {
+    let identities = state.db.list_sso_identities(user.id).await?;
+    Ok(Json(identities))
+}
+
+/// Links the account of an ID token the dashboard verified to the session's
+/// user. Only the dashboard can vouch for the claims, so the request needs
+/// its service token as well as the session.
+#[instrument(skip(state, _service, user, request))]
+async fn link_sso_identity(
+    State(state): State<Arc<AppState>>,
+    _service: ServiceCall,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<SsoLoginRequest>,
+) -> ServiceResult<Json<SsoIdentity>> {
+    info!("Linking {} account to user {}", request.provider, user.id);
+    let identity = state.db.link_sso_identity(user.id, &request).await?;
+    Ok(Json(identity))
+}
+
+#[instrument(skip(state, user))]
+async fn delete_sso_identity(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    info!("Unlinking SSO identity {} from user {}", id, user.id);
+    state.db.delete_sso_identity(user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[derive(Debug, serde::Deserialize)]
 struct ValidateSessionRequest {
     session_token: String,
 }
 
+#[instrument(skip(state, request))]
+async fn verify_email(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> ServiceResult<Json<UserProfile>> {
+    info!("Verifying email");
+    let user = state.db.verify_email(request.token.trim()).await?;
+    Ok(Json(user.into()))
+}
+
 #[instrument(skip(state))]
 async fn validate_session(
     State(state): State<Arc<AppState>>,
@@ -171,153 +673,2347 @@ async fn validate_session(
         is_active: user.is_active,
         created_at: user.created_at,
         last_login: user.last_login,
+        preferences: user.preferences,
     };
     
     Ok(Json(profile))
 }
 
-// Case endpoints
-#[instrument(skip(state))]
-async fn create_case(
+// Preference endpoints
+#[instrument(skip(user))]
+async fn get_preferences(
+    CurrentUser(user): CurrentUser,
+) -> Json<UserPreferences> {
+    Json(user.preferences)
+}
+
+#[instrument(skip(state, user))]
+async fn update_preferences(
     State(state): State<Arc<AppState>>,
-    Json(case): Json<Case>,
-) -> ServiceResult<Json<Case>> {
-    info!("Creating case: {}", case.id);
-    let created_case = state.db.create_case(case).await?;
-    Ok(Json(created_case))
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<UpdatePreferencesRequest>,
+) -> ServiceResult<Json<UserPreferences>> {
+    info!("Updating preferences for user: {}", user.id);
+    let mut preferences = user.preferences;
+    if let Some(theme) = request.theme {
+        preferences.theme = theme;
+    }
+    if let Some(default_view) = request.default_view {
+        preferences.default_view = default_view;
+    }
+    if let Some(tasks_per_page) = request.tasks_per_page {
+        if !(MIN_TASKS_PER_PAGE..=MAX_TASKS_PER_PAGE).contains(&tasks_per_page) {
+            return Err(common::ServiceError::BadRequest(format!(
+                "tasks_per_page must be between {} and {}",
+                MIN_TASKS_PER_PAGE, MAX_TASKS_PER_PAGE
+            )));
+        }
+        preferences.tasks_per_page = tasks_per_page;
+    }
+    if let Some(daily_capacity_minutes) = request.daily_capacity_minutes {
+        if !(MIN_DAILY_CAPACITY_MINUTES..=MAX_DAILY_CAPACITY_MINUTES).contains(&daily_capacity_minutes) {
+            return Err(common::ServiceError::BadRequest(format!(
+                "daily_capacity_minutes must be between {} and {}",
+                MIN_DAILY_CAPACITY_MINUTES, MAX_DAILY_CAPACITY_MINUTES
+            )));
+        }
+        preferences.daily_capacity_minutes = daily_capacity_minutes;
+    }
+    if let Some(notifications) = request.notifications {
+        if notifications.utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES {
+            return Err(common::ServiceError::BadRequest(format!(
+                "utc_offset_minutes must be between -{0} and {0}",
+                MAX_UTC_OFFSET_MINUTES
+            )));
+        }
+        if notifications.quiet_hours.is_some_and(|quiet| quiet.start == quiet.end) {
+            return Err(common::ServiceError::BadRequest("Quiet hours must not start and end at the same time".to_string()));
+        }
+        preferences.notifications = notifications;
+    }
+
+    let saved = state.db.update_user_preferences(user.id, &preferences).await?;
+    Ok(Json(saved))
 }
 
-#[instrument(skip(state))]
-async fn get_case(
+// Backup endpoints
+#[instrument(skip(state, user))]
+async fn backup_user_data(
     State(state): State<Arc<AppState>>,
-    Path(id): Path<Uuid>,
-    // TODO: Extract user_id from session token in header
-) -> ServiceResult<Json<Case>> {
-    info!("Getting case: {}", id);
-    // TODO: For now using a placeholder user_id - this needs to be extracted from session
-    let placeholder_user_id = Uuid::new_v4();
-    let case = state.db.get_case(id, placeholder_user_id).await?;
-    Ok(Json(case))
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<BackupQuery>,
+) -> ServiceResult<Response> {
+    info!("Creating {:?} backup for user: {}", query.format, user.id);
+
+    let backup = build_backup(&state.db, user).await?;
+
+    let response = match query.format {
+        BackupFormat::Json => (
+            [(header::CONTENT_DISPOSITION, "attachment; filename=\"backup.json\"")],
+            Json(backup),
+        )
+            .into_response(),
+        BackupFormat::Markdown => (
+            [
+                (header::CONTENT_TYPE, "text/markdown; charset=utf-8"),
+                (header::CONTENT_DISPOSITION, "attachment; filename=\"backup.md\""),
+            ],
+            common::markdown::render_backup(&backup),
+        )
+            .into_response(),
+    };
+    Ok(response)
 }
 
-#[instrument(skip(state))]
-async fn update_case(
+/// Collects everything stored for `user` into a backup archive.
+async fn build_backup(db: &Database, user: User) -> ServiceResult<UserBackup> {
+    let mut cases = Vec::new();
+    let scope = Scope::User(user.id);
+    for case in db.list_cases_for_user(user.id).await? {
+        let tasks = db.get_tasks_for_case(case.id, scope).await?;
+        let conversation = db.get_conversation_history(case.id, scope).await?;
+        let workflow = db.get_case_workflow(case.id).await?;
+        cases.push(CaseBackup { case, tasks, conversation, workflow: Some(workflow) });
+    }
+
+    Ok(UserBackup {
+        version: BACKUP_VERSION,
+        exported_at: chrono::Utc::now(),
+        user: UserProfile {
+            id: user.id,
+            email: user.email,
+            full_name: user.full_name,
+            organization: user.organization,
+            is_active: user.is_active,
+            created_at: user.created_at,
+            last_login: user.last_login,
+            preferences: user.preferences,
+        },
+        cases,
+    })
+}
+
+#[instrument(skip(state, user, backup))]
+async fn restore_user_data(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<RestoreQuery>,
+    Json(backup): Json<UserBackup>,
+) -> ServiceResult<Json<RestoreSummary>> {
+    info!("Restoring backup for user {} ({:?} on conflict)", user.id, query.conflict);
+
+    if backup.version > BACKUP_VERSION {
+        return Err(common::ServiceError::BadRequest(format!(
+            "Unsupported backup version: {}",
+            backup.version
+        )));
+    }
+
+    let summary = state.db.restore_backup(user.id, backup.cases, query.conflict).await?;
+    info!("Restore finished: {:?}", summary);
+    Ok(Json(summary))
+}
+
+// Account deletion endpoints
+#[instrument(skip(state, user))]
+async fn request_account_deletion(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    request: Option<Json<AccountDeletionRequest>>,
+) -> ServiceResult<Json<AccountDeletionResponse>> {
+    let Json(request) = request.unwrap_or_default();
+    info!("Account deletion requested for user {} ({:?})", user.id, request.mode);
+
+    // Export first so the user gets their data even though the sessions are
+    // revoked by the deletion request.
+    let user_id = user.id;
+    let export = build_backup(&state.db, user).await?;
+    let scheduled_for = chrono::Utc::now() + state.deletion_grace_period;
+    let deletion = state.db.request_account_deletion(user_id, request.mode, scheduled_for).await?;
+
+    Ok(Json(AccountDeletionResponse { deletion, export }))
+}
+
+#[instrument(skip(state, _admin))]
+async fn list_account_deletions(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminAuth,
+    Query(query): Query<DeletionQuery>,
+) -> ServiceResult<Json<Vec<AccountDeletion>>> {
+    info!("Listing account deletions with status: {:?}", query.status);
+    let deletions = state.db.list_account_deletions(query.status).await?;
+    Ok(Json(deletions))
+}
+
+#[instrument(skip(state, _admin))]
+async fn confirm_account_deletion(
     State(state): State<Arc<AppState>>,
+    _admin: AdminAuth,
+    Path(user_id): Path<Uuid>,
+    Query(query): Query<ConfirmDeletionQuery>,
+) -> ServiceResult<Json<AccountDeletion>> {
+    info!("Confirming account deletion for user: {}", user_id);
+
+    let deletion = state.db.get_account_deletion(user_id).await?;
+    if deletion.scheduled_for > chrono::Utc::now() && !query.skip_grace_period {
+        return Err(common::ServiceError::BadRequest(format!(
+            "Grace period ends at {}",
+            deletion.scheduled_for.to_rfc3339()
+        )));
+    }
+
+    let deletion = state.db.complete_account_deletion(user_id).await?;
+    Ok(Json(deletion))
+}
+
+#[instrument(skip(state, _admin))]
+async fn cancel_account_deletion(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminAuth,
+    Path(user_id): Path<Uuid>,
+) -> ServiceResult<Json<AccountDeletion>> {
+    info!("Cancelling account deletion for user: {}", user_id);
+    let deletion = state.db.cancel_account_deletion(user_id).await?;
+    Ok(Json(deletion))
+}
+
+// Job endpoints
+#[derive(Debug, serde::Deserialize)]
+struct JobQuery {
+    status: Option<JobStatus>,
+    kind: Option<String>,
+    limit: Option<i64>,
+}
+
+#[instrument(skip(state, _admin))]
+async fn list_jobs(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminAuth,
+    Query(query): Query<JobQuery>,
+) -> ServiceResult<Json<Vec<Job>>> {
+    info!("Listing jobs with status {:?} and kind {:?}", query.status, query.kind);
+    let limit = query.limit.unwrap_or(DEFAULT_ADMIN_PAGE).clamp(1, MAX_ADMIN_PAGE);
+    let jobs = state.db.list_jobs(query.status, query.kind.as_deref(), limit).await?;
+    Ok(Json(jobs))
+}
+
+#[instrument(skip(state, _admin))]
+async fn retry_job(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminAuth,
     Path(id): Path<Uuid>,
-    Json(request): Json<UpdateCaseRequest>,
-) -> ServiceResult<Json<Case>> {
-    info!("Updating case: {}", id);
-    // TODO: Extract user_id from session token - using placeholder for now
-    let placeholder_user_id = Uuid::new_v4();
-    let updated_case = state.db.update_case(id, placeholder_user_id, request).await?;
-    Ok(Json(updated_case))
+) -> ServiceResult<Json<Job>> {
+    info!("Retrying dead job: {}", id);
+    let job = state.db.retry_job(id).await?;
+    Ok(Json(job))
 }
 
-#[instrument(skip(state))]
-async fn get_conversation_history(
+#[instrument(skip(state, _admin))]
+async fn get_outbox_lag(State(state): State<Arc<AppState>>, _admin: AdminAuth) -> ServiceResult<Json<OutboxLag>> {
+    let lag = state.db.outbox_lag().await?;
+    Ok(Json(lag))
+}
+
+/// Creates synthetic users, cases and tasks for a load test; see `seed`.
+#[instrument(skip(state, _admin))]
+async fn seed_data(
     State(state): State<Arc<AppState>>,
-    Path(case_id): Path<Uuid>,
-) -> ServiceResult<Json<Vec<ConversationEntry>>> {
-    info!("Getting conversation history for case: {}", case_id);
-    let history = state.db.get_conversation_history(case_id).await?;
-    Ok(Json(history))
+    _admin: AdminAuth,
+    Json(request): Json<SeedRequest>,
+) -> ServiceResult<Json<SeedResponse>> {
+    if !state.seed_enabled {
+        return Err(common::ServiceError::BadRequest("Seeding is disabled; set SEED_ENABLED=true".to_string()));
+    }
+    info!("Seeding load-test data: {:?}", request);
+    let seeded = seed::seed(&state.db, &request).await?;
+    Ok(Json(seeded))
 }
 
-#[instrument(skip(state))]
-async fn add_conversation_entry(
+/// The organization's LLM settings, without the API key, and its usage this
+/// month.
+#[instrument(skip(state, _admin))]
+async fn get_organization_llm(
     State(state): State<Arc<AppState>>,
-    Path(case_id): Path<Uuid>,
-    Json(entry): Json<ConversationEntry>,
-) -> ServiceResult<Json<ConversationEntry>> {
-    info!("Adding conversation entry for case: {}", case_id);
-    let saved_entry = state.db.add_conversation_entry(entry).await?;
-    Ok(Json(saved_entry))
+    _admin: AdminAuth,
+    Path(organization): Path<String>,
+) -> ServiceResult<Json<OrganizationLlm>> {
+    let settings = state
+        .db
+        .organization_llm_settings(&organization)
+        .await?
+        .ok_or_else(|| common::ServiceError::NotFound(format!("Organization {} has no LLM settings", organization)))?;
+    let usage = state.db.llm_usage(&organization).await?;
+    Ok(Json(OrganizationLlm { settings: redact_api_key(settings), usage }))
 }
 
-#[instrument(skip(state))]
-async fn get_case_workflow(
+#[instrument(skip(state, _admin, request))]
+async fn set_organization_llm(
     State(state): State<Arc<AppState>>,
-    Path(case_id): Path<Uuid>,
-) -> ServiceResult<Json<CaseWorkflow>> {
-    info!("Getting workflow for case: {}", case_id);
-    let workflow = state.db.get_case_workflow(case_id).await?;
-    Ok(Json(workflow))
+    _admin: AdminAuth,
+    Path(organization): Path<String>,
+    Json(mut request): Json<OrganizationLlmRequest>,
+) -> ServiceResult<Json<OrganizationLlm>> {
+    let organization = organization.trim().to_string();
+    if organization.is_empty() {
+        return Err(common::ServiceError::BadRequest("An organization name is required".to_string()));
+    }
+    request.base_url = request.base_url.map(|url| url.trim().trim_end_matches('/').to_string()).filter(|url| !url.is_empty());
+    request.model = request.model.map(|model| model.trim().to_string()).filter(|model| !model.is_empty());
+    if let Some(url) = &request.base_url {
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(common::ServiceError::BadRequest("The LLM base URL must be an http(s) URL".to_string()));
+        }
+    }
+    if request.monthly_token_budget.is_some_and(|budget| budget < 0) {
+        return Err(common::ServiceError::BadRequest("The monthly token budget can't be negative".to_string()));
+    }
+
+    info!("Updating LLM settings of organization {}", organization);
+    let settings = state.db.set_organization_llm_settings(&organization, &request).await?;
+    let usage = state.db.llm_usage(&organization).await?;
+    Ok(Json(OrganizationLlm { settings: redact_api_key(settings), usage }))
 }
 
-#[instrument(skip(state))]
-async fn update_case_workflow(
+#[instrument(skip(state, _admin))]
+async fn delete_organization_llm(
     State(state): State<Arc<AppState>>,
-    Path(case_id): Path<Uuid>,
-    Json(workflow): Json<CaseWorkflow>,
-) -> ServiceResult<Json<CaseWorkflow>> {
-    info!("Updating workflow for case: {}", case_id);
-    let updated_workflow = state.db.update_case_workflow(workflow).await?;
-    Ok(Json(updated_workflow))
+    _admin: AdminAuth,
+    Path(organization): Path<String>,
+) -> ServiceResult<StatusCode> {
+    info!("Removing LLM settings of organization {}", organization);
+    state.db.delete_organization_llm_settings(&organization).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
-// Task endpoints
-#[instrument(skip(state))]
-async fn create_task(
+/// The users that use the organization's LLM settings.
+#[instrument(skip(state, _admin))]
+async fn list_organization_members(
     State(state): State<Arc<AppState>>,
-    Json(task): Json<Task>,
-) -> ServiceResult<Json<Task>> {
-    info!("Creating task: {}", task.id);
-    let created_task = state.db.create_task(task).await?;
-    Ok(Json(created_task))
+    _admin: AdminAuth,
+    Path(organization): Path<String>,
+) -> ServiceResult<Json<Vec<OrganizationMember>>> {
+    Ok(Json(state.db.list_organization_members(&organization).await?))
 }
 
-#[instrument(skip(state))]
-async fn get_tasks(
+#[instrument(skip(state, _admin))]
+async fn add_organization_member(
     State(state): State<Arc<AppState>>,
-    Query(query): Query<TaskQuery>,
-) -> ServiceResult<Json<Vec<Task>>> {
-    info!("Getting tasks with query: {:?}", query.status);
+    _admin: AdminAuth,
+    Path((organization, user_id)): Path<(String, Uuid)>,
+) -> ServiceResult<Json<OrganizationMember>> {
+    let organization = organization.trim().to_string();
+    if organization.is_empty() {
+        return Err(common::ServiceError::BadRequest("An organization name is required".to_string()));
+    }
+    info!("Adding user {} to organization {}", user_id, organization);
+    Ok(Json(state.db.add_organization_member(&organization, user_id).await?))
+}
+
+#[instrument(skip(state, _admin))]
+async fn remove_organization_member(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminAuth,
+    Path((organization, user_id)): Path<(String, Uuid)>,
+) -> ServiceResult<StatusCode> {
+    info!("Removing user {} from organization {}", user_id, organization);
+    state.db.remove_organization_member(&organization, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// API keys are write-only outside the services.
+fn redact_api_key(settings: OrganizationLlmSettings) -> OrganizationLlmSettings {
+    OrganizationLlmSettings { api_key: None, ..settings }
+}
 
-    let tasks = match query.status {
-        Some(status) => state.db.get_tasks_by_status(status).await?,
-        None => state.db.get_all_tasks().await?,
+/// The LLM settings, API key included, of the organization a user is a
+/// member of: the user behind a session, or a user by id for requests made
+/// without one. Only other services get them.
+#[instrument(skip(state, _internal, request))]
+async fn llm_account(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+    Json(request): Json<LlmAccountRequest>,
+) -> ServiceResult<Json<Option<OrganizationLlm>>> {
+    let user_id = match (&request.session_token, request.user_id) {
+        (Some(token), _) => state.db.validate_session(token).await?.id,
+        (None, Some(user_id)) => user_id,
+        (None, None) => return Err(common::ServiceError::BadRequest("A session token or user id is required".to_string())),
     };
+    let account = state.db.organization_llm_of(user_id).await?;
+    Ok(Json(account))
+}
 
-    Ok(Json(tasks))
+/// Adds tokens the AI agent used to its organization's monthly usage, and
+/// alerts the organization's admin when they use up its budget.
+#[instrument(skip(state, _internal))]
+async fn record_llm_usage(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+    Json(request): Json<RecordLlmUsageRequest>,
+) -> ServiceResult<Json<LlmUsage>> {
+    let (usage, newly_exhausted) = state.db.record_llm_usage(&request.organization, request.tokens).await?;
+    if newly_exhausted {
+        warn!("Organization {} used up its monthly LLM token budget", request.organization);
+        let alert_user_id = state
+            .db
+            .organization_llm_settings(&request.organization)
+            .await?
+            .and_then(|settings| settings.alert_user_id);
+        if let Some(alert_user_id) = alert_user_id {
+            state.notifier.notify(
+                alert_user_id,
+                NotificationKind::LlmBudgetExhausted,
+                "LLM token budget used up",
+                format!(
+                    "{} used {} of its {} tokens this month; extraction falls back to keywords until next month",
+                    usage.organization,
+                    usage.tokens_used,
+                    usage.monthly_token_budget.unwrap_or_default(),
+                ),
+                None,
+                None,
+            )
+            .await;
+        }
+    }
+    Ok(Json(usage))
 }
 
-#[instrument(skip(state))]
-async fn get_task(
+#[instrument(skip(state, _internal))]
+async fn enqueue_job(
     State(state): State<Arc<AppState>>,
-    Path(id): Path<Uuid>,
-) -> ServiceResult<Json<Task>> {
-    info!("Getting task: {}", id);
-    let task = state.db.get_task(id).await?;
-    Ok(Json(task))
+    _internal: InternalCall,
+    Json(request): Json<EnqueueJobRequest>,
+) -> ServiceResult<Json<Job>> {
+    if request.kind.trim().is_empty() {
+        return Err(common::ServiceError::BadRequest("A job needs a kind".to_string()));
+    }
+    let job = state.db.enqueue_job(&request).await?;
+    info!("Enqueued {} job {}", job.kind, job.id);
+    Ok(Json(job))
 }
 
-#[instrument(skip(state))]
-async fn update_task(
+#[instrument(skip(state, _internal))]
+async fn schedule_job(
     State(state): State<Arc<AppState>>,
-    Path(id): Path<Uuid>,
-    Json(request): Json<UpdateTaskRequest>,
-) -> ServiceResult<Json<Task>> {
-    info!("Updating task: {}", id);
-    let updated_task = state.db.update_task(id, request).await?;
-    Ok(Json(updated_task))
+    _internal: InternalCall,
+    Json(request): Json<ScheduleJobRequest>,
+) -> ServiceResult<Json<Job>> {
+    if request.kind.trim().is_empty() || request.interval_secs < 1 {
+        return Err(common::ServiceError::BadRequest("A recurring job needs a kind and an interval of at least a second".to_string()));
+    }
+    let job = state.db.schedule_job(&request).await?;
+    info!("Scheduled {} jobs every {} seconds", job.kind, request.interval_secs);
+    Ok(Json(job))
 }
 
-#[instrument(skip(state))]
-async fn delete_task(
+#[instrument(skip(state, _internal))]
+async fn claim_jobs(
     State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+    Json(request): Json<ClaimJobsRequest>,
+) -> ServiceResult<Json<Vec<Job>>> {
+    let jobs = state.db.claim_jobs(&request).await?;
+    Ok(Json(jobs))
+}
+
+#[instrument(skip(state, _internal))]
+async fn finish_job(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
     Path(id): Path<Uuid>,
-) -> ServiceResult<StatusCode> {
-    info!("Deleting task: {}", id);
-    state.db.delete_task(id).await?;
-    Ok(StatusCode::NO_CONTENT)
+    Json(result): Json<JobResult>,
+) -> ServiceResult<Json<Job>> {
+    let job = state.db.finish_job(id, &result).await?;
+    Ok(Json(job))
 }
 
-#[instrument(skip(state))]
-async fn get_tasks_for_case(
+// Administration endpoints
+fn admin_audit(action: AdminAction, target_user_id: Uuid, staff: &StaffUser, reason: Option<String>) -> AdminAuditEntry {
+    AdminAuditEntry {
+        id: Uuid::new_v4(),
+        action,
+        target_user_id,
+        actor: staff.actor(),
+        reason: reason.map(|reason| reason.trim().to_string()).filter(|reason| !reason.is_empty()),
+        created_at: chrono::Utc::now(),
+    }
+}
+
+#[instrument(skip(state, staff))]
+async fn search_users(
     State(state): State<Arc<AppState>>,
-    Path(case_id): Path<Uuid>,
-) -> ServiceResult<Json<Vec<Task>>> {
-    info!("Getting tasks for case: {}", case_id);
-    let tasks = state.db.get_tasks_for_case(case_id).await?;
-    Ok(Json(tasks))
+    staff: StaffUser,
+    Query(query): Query<UserSearchQuery>,
+) -> ServiceResult<Json<Vec<UserProfile>>> {
+    staff.require(StaffRole::Support)?;
+    info!("Searching users: {:?}", query);
+    let limit = query.limit.unwrap_or(DEFAULT_ADMIN_PAGE).clamp(1, MAX_ADMIN_PAGE);
+    let search = query.search.as_deref().map(str::trim).filter(|search| !search.is_empty());
+    let users = state.db.search_users(search, query.active, limit, query.offset.max(0)).await?;
+    Ok(Json(users.into_iter().map(UserProfile::from).collect()))
+}
+
+#[instrument(skip(state, staff))]
+async fn get_user_usage(
+    State(state): State<Arc<AppState>>,
+    staff: StaffUser,
+    Path(user_id): Path<Uuid>,
+) -> ServiceResult<Json<UserUsage>> {
+    staff.require(StaffRole::Support)?;
+    info!("Getting usage of user: {}", user_id);
+    let usage = state.db.user_usage(user_id).await?;
+    Ok(Json(usage))
+}
+
+/// Blocks sign-in and ends the user's sessions, keeping their data.
+#[instrument(skip(state, staff))]
+async fn deactivate_user(
+    State(state): State<Arc<AppState>>,
+    staff: StaffUser,
+    Path(user_id): Path<Uuid>,
+    Json(request): Json<AdminActionRequest>,
+) -> ServiceResult<Json<UserProfile>> {
+    staff.require(StaffRole::Admin)?;
+    info!("Deactivating user {} by {}", user_id, staff.actor());
+    let audit = admin_audit(AdminAction::Deactivate, user_id, &staff, request.reason);
+    let user = state.db.set_user_active(user_id, false, &audit).await?;
+    Ok(Json(user.into()))
+}
+
+/// Reverses a deactivation. Accounts waiting for deletion are reactivated
+/// by cancelling the deletion instead.
+#[instrument(skip(state, staff))]
+async fn activate_user(
+    State(state): State<Arc<AppState>>,
+    staff: StaffUser,
+    Path(user_id): Path<Uuid>,
+    Json(request): Json<AdminActionRequest>,
+) -> ServiceResult<Json<UserProfile>> {
+    staff.require(StaffRole::Admin)?;
+    info!("Activating user {} by {}", user_id, staff.actor());
+    match state.db.get_account_deletion(user_id).await {
+        Ok(deletion) if deletion.status == AccountDeletionStatus::Pending => {
+            return Err(common::ServiceError::BadRequest(format!(
+                "User {} has a pending deletion; cancel it to reactivate the account",
+                user_id
+            )));
+        }
+        Ok(_) | Err(common::ServiceError::NotFound(_)) => {}
+        Err(e) => return Err(e),
+    }
+    let audit = admin_audit(AdminAction::Activate, user_id, &staff, request.reason);
+    let user = state.db.set_user_active(user_id, true, &audit).await?;
+    Ok(Json(user.into()))
+}
+
+/// Issues a new email verification token. Nothing here sends email, so
+/// the token is returned for the caller to deliver.
+#[instrument(skip(state, staff))]
+async fn resend_verification(
+    State(state): State<Arc<AppState>>,
+    staff: StaffUser,
+    Path(user_id): Path<Uuid>,
+    Json(request): Json<AdminActionRequest>,
+) -> ServiceResult<Json<EmailVerification>> {
+    staff.require(StaffRole::Support)?;
+    info!("Issuing email verification for user {} by {}", user_id, staff.actor());
+    let audit = admin_audit(AdminAction::ResendVerification, user_id, &staff, request.reason);
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(VERIFICATION_TOKEN_HOURS);
+    let verification = state.db.create_email_verification(user_id, expires_at, &audit).await?;
+    Ok(Json(verification))
+}
+
+/// Opens a short session as the user for support. A reason is required,
+/// and the action and the session both record who asked.
+#[instrument(skip(state, staff))]
+async fn impersonate_user(
+    State(state): State<Arc<AppState>>,
+    staff: StaffUser,
+    Path(user_id): Path<Uuid>,
+    Json(request): Json<AdminActionRequest>,
+) -> ServiceResult<Json<LoginResponse>> {
+    staff.require(StaffRole::Admin)?;
+    warn!("Impersonation of user {} requested by {}", user_id, staff.actor());
+    let audit = admin_audit(AdminAction::Impersonate, user_id, &staff, request.reason);
+    if audit.reason.is_none() {
+        return Err(common::ServiceError::BadRequest("Impersonation needs a `reason`".to_string()));
+    }
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(IMPERSONATION_MINUTES);
+    let (user, session) = state.db.create_impersonation_session(user_id, expires_at, &audit).await?;
+    Ok(Json(LoginResponse { user: user.into(), session_token: session.session_token, expires_at }))
+}
+
+/// Grants or revokes a staff role. Administrators can't change their own,
+/// so the last one can't lock everyone out.
+#[instrument(skip(state, staff))]
+async fn set_staff_role(
+    State(state): State<Arc<AppState>>,
+    staff: StaffUser,
+    Path(user_id): Path<Uuid>,
+    Json(request): Json<StaffRoleRequest>,
+) -> ServiceResult<StatusCode> {
+    staff.require(StaffRole::Admin)?;
+    if user_id == staff.user.id {
+        return Err(common::ServiceError::BadRequest("Administrators can't change their own role".to_string()));
+    }
+    warn!("Staff role of user {} set to {:?} by {}", user_id, request.role, staff.actor());
+    let action = match request.role {
+        Some(role) => AdminAction::GrantRole(role),
+        None => AdminAction::RevokeRole,
+    };
+    let audit = admin_audit(action, user_id, &staff, request.reason);
+    state.db.set_staff_role(user_id, request.role, &audit).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `admin.emails` (`ADMIN_EMAILS`): existing accounts made administrators
+/// on start, so the first administrator needn't be granted by another.
+async fn grant_configured_admins(db: &Database, emails: &[String]) {
+    for email in emails {
+        let user = match db.find_user_by_email(email).await {
+            Ok(Some(user)) => user,
+            Ok(None) => {
+                warn!("ADMIN_EMAILS lists {}, which has no account yet", email);
+                continue;
+            }
+            Err(e) => {
+                warn!("Could not look up {} from ADMIN_EMAILS: {}", email, e);
+                continue;
+            }
+        };
+        if matches!(db.staff_role(user.id).await, Ok(Some(StaffRole::Admin))) {
+            continue;
+        }
+        let audit = AdminAuditEntry {
+            id: Uuid::new_v4(),
+            action: AdminAction::GrantRole(StaffRole::Admin),
+            target_user_id: user.id,
+            actor: "ADMIN_EMAILS".to_string(),
+            reason: None,
+            created_at: chrono::Utc::now(),
+        };
+        match db.set_staff_role(user.id, Some(StaffRole::Admin), &audit).await {
+            Ok(()) => info!("Made {} an administrator from ADMIN_EMAILS", email),
+            Err(e) => warn!("Could not make {} an administrator: {}", email, e),
+        }
+    }
+}
+
+#[instrument(skip(state, staff))]
+async fn list_admin_audit(
+    State(state): State<Arc<AppState>>,
+    staff: StaffUser,
+    Query(query): Query<AuditQuery>,
+) -> ServiceResult<Json<Vec<AdminAuditEntry>>> {
+    staff.require(StaffRole::Support)?;
+    info!("Listing admin audit log: {:?}", query);
+    let limit = query.limit.unwrap_or(DEFAULT_ADMIN_PAGE).clamp(1, MAX_ADMIN_PAGE);
+    let entries = state.db.list_admin_audit(query.user_id, limit).await?;
+    Ok(Json(entries))
+}
+
+// Case endpoints
+#[instrument(skip(state, user))]
+async fn create_case(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Json(mut case): Json<Case>,
+) -> ServiceResult<Json<Case>> {
+    info!("Creating case: {}", case.id);
+    if let Some(user) = &user {
+        case.user_id = user.id;
+    }
+    let created_case = state.db.create_case(case).await?;
+    record_revision(&state, RevisionEntity::Case, created_case.id, created_case.user_id, user.as_ref(), None, &created_case).await;
+    Ok(Json(created_case))
+}
+
+#[instrument(skip(state, user))]
+async fn list_cases(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CaseQuery>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Vec<Case>>> {
+    info!("Listing cases with query: {:?}", query.status);
+    let mut cases = state.db.list_cases(Scope::of(user.as_ref()), query.status.clone()).await?;
+    if !query.include_archived && query.status != Some(CaseStatus::Archived) {
+        cases.retain(|case| case.status != CaseStatus::Archived);
+    }
+    if let Some(user) = user {
+        let unread = state.db.unread_counts(user.id).await?;
+        for case in &mut cases {
+            case.unread_count = Some(unread.get(&case.id).copied().unwrap_or(0));
+        }
+    }
+    if let Some(tag) = query.tag.as_deref().and_then(tags::normalize_tag) {
+        cases.retain(|case| case.tags.contains(&tag));
+    }
+    Ok(Json(cases))
+}
+
+/// Brings an archived case back as Closed.
+#[instrument(skip(state, user))]
+async fn restore_archived_case(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Case>> {
+    info!("Restoring archived case: {}", id);
+    let case = state.db.restore_archived_case(id, Scope::of(user.as_ref())).await?;
+    record_case_event(
+        &state,
+        id,
+        user.as_ref(),
+        CaseEventKind::CaseStatusChanged,
+        "Case restored from the archive".to_string(),
+        None,
+    )
+    .await;
+    Ok(Json(case))
+}
+
+/// Marks everything in a case's conversation so far as read by the caller.
+#[instrument(skip(state, user))]
+async fn mark_case_read(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    CurrentUser(user): CurrentUser,
+) -> ServiceResult<Json<CaseReadReceipt>> {
+    info!("Marking case {} read for user: {}", id, user.id);
+    state.db.get_case(id, Scope::User(user.id)).await?;
+    let receipt = state.db.mark_case_read(user.id, id).await?;
+    Ok(Json(receipt))
+}
+
+#[instrument(skip(state, user))]
+async fn get_case(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Case>> {
+    info!("Getting case: {}", id);
+    // Scoped to the caller when a session is forwarded; internal calls
+    // without one look the case up by id alone.
+    let case = state.db.get_case(id, Scope::of(user.as_ref())).await?;
+    Ok(Json(case))
+}
+
+/// Cases an email belongs to by its thread identifiers, for routing
+/// replies. Empty when the email starts a new thread.
+#[instrument(skip(state, user))]
+async fn find_cases_by_email_thread(
+    State(state): State<Arc<AppState>>,
+    Query(thread): Query<EmailThread>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Vec<Case>>> {
+    info!("Finding cases for email thread: {:?}", thread);
+    let cases = state.db.find_cases_by_email_thread(Scope::of(user.as_ref()), &thread).await?;
+    Ok(Json(cases))
+}
+
+#[instrument(skip(state, user))]
+async fn record_email_thread(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+    Json(thread): Json<EmailThread>,
+) -> ServiceResult<Json<Case>> {
+    info!("Recording email thread for case {}: {:?}", id, thread);
+    let case = state.db.record_email_thread(id, Scope::of(user.as_ref()), &thread).await?;
+    Ok(Json(case))
+}
+
+#[instrument(skip(state, user))]
+async fn update_case(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+    Json(request): Json<UpdateCaseRequest>,
+) -> ServiceResult<Json<Case>> {
+    info!("Updating case: {}", id);
+    let scope = Scope::of(user.as_ref());
+    let previous = state.db.get_case(id, scope).await?;
+    let previous_status = previous.status.clone();
+    let updated_case = state.db.update_case(id, scope, request).await?;
+    record_revision(&state, RevisionEntity::Case, id, updated_case.user_id, user.as_ref(), Some(&previous), &updated_case).await;
+
+    if updated_case.status != previous_status {
+        record_case_event(
+            &state,
+            id,
+            user.as_ref(),
+            CaseEventKind::CaseStatusChanged,
+            format!("Case moved from {:?} to {:?}", previous_status, updated_case.status),
+            None,
+        )
+        .await;
+    }
+
+    if updated_case.status == CaseStatus::Resolved && previous_status != CaseStatus::Resolved {
+        state.notifier.notify(
+            updated_case.user_id,
+            NotificationKind::CaseResolved,
+            "Case resolved",
+            updated_case.title.clone(),
+            Some(format!("/cases/{}", updated_case.id)),
+            Some(updated_case.id),
+        )
+        .await;
+    }
+
+    Ok(Json(updated_case))
+}
+
+/// A user's profile, for services acting for them, such as the email
+/// collector checking where it may send.
+#[instrument(skip(state, _internal))]
+async fn get_user(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+    Path(user_id): Path<Uuid>,
+) -> ServiceResult<Json<UserProfile>> {
+    let user = state.db.get_user(user_id).await?;
+    Ok(Json(user.into()))
+}
+
+/// The owner of a case, with their preferences; used by jobs that reach
+/// case owners, such as the weekly summary email.
+#[instrument(skip(state, user))]
+async fn get_case_owner(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<UserProfile>> {
+    info!("Getting owner of case: {}", id);
+    let owner = state.db.get_case_owner(id, Scope::of(user.as_ref())).await?;
+    Ok(Json(owner.into()))
+}
+
+/// Hands the caller's case to another user, who is notified.
+#[instrument(skip(state, user))]
+async fn transfer_case(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<TransferCaseRequest>,
+) -> ServiceResult<Json<Case>> {
+    info!("Transferring case {} from user {}", id, user.id);
+    let (case, recipient) = state.db.transfer_case(id, Scope::User(user.id), request.email.trim()).await?;
+    record_case_event(
+        &state,
+        id,
+        Some(&user),
+        CaseEventKind::CaseTransferred,
+        format!("Case handed from {} to {}", user.email, recipient.email),
+        None,
+    )
+    .await;
+    state.notifier.notify(
+        recipient.id,
+        NotificationKind::CaseTransferred,
+        "Case handed to you",
+        format!("{} handed you '{}'", user.full_name, case.title),
+        Some(format!("/cases/{}", id)),
+        Some(id),
+    )
+    .await;
+    Ok(Json(case))
+}
+
+/// Signs a read-only link to the caller's case; see `sharing`.
+#[instrument(skip(state, user))]
+async fn create_share_link(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<CreateShareLinkRequest>,
+) -> ServiceResult<Json<ShareLink>> {
+    info!("Creating share link for case {} by user {}", id, user.id);
+    let case = state.db.get_case(id, Scope::User(user.id)).await?;
+    let link = state.share_links.issue(&case, request.expires_in_hours)?;
+    Ok(Json(link))
+}
+
+/// The case behind a share link, for anyone holding it.
+#[instrument(skip(state, token))]
+async fn get_shared_case(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> ServiceResult<Json<SharedCase>> {
+    let not_found = || common::ServiceError::NotFound("Share link not found or expired".to_string());
+    let (case_id, expires_at) = state.share_links.claims(&token, chrono::Utc::now()).ok_or_else(not_found)?;
+    info!("Opening shared case: {}", case_id);
+    let mut case = match state.db.get_case(case_id, Scope::Internal).await {
+        Err(common::ServiceError::NotFound(_)) => return Err(not_found()),
+        result => result?,
+    };
+    if !state.share_links.verify(&token, case_id, expires_at, case.user_id)? {
+        return Err(not_found());
+    }
+
+    let mut tasks = state.db.get_tasks_for_case(case_id, Scope::User(case.user_id)).await?;
+    case.metadata = serde_json::json!({});
+    for task in &mut tasks {
+        task.metadata = serde_json::json!({});
+    }
+    Ok(Json(SharedCase { case, tasks, expires_at }))
+}
+
+#[instrument(skip(state, user))]
+async fn get_conversation_history(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+    Query(query): Query<HistoryQuery>,
+) -> ServiceResult<Json<Vec<ConversationEntry>>> {
+    info!("Getting conversation history for case: {} with {:?}", case_id, query);
+    let scope = Scope::of(user.as_ref());
+    if user.is_some() {
+        state.db.get_case(case_id, scope).await?;
+    }
+    let history = state
+        .db
+        .get_conversation_page(
+            case_id,
+            scope,
+            query.since,
+            query.before,
+            query.sender.as_ref(),
+            query.limit.map(|limit| limit.clamp(1, MAX_HISTORY_LIMIT)),
+        )
+        .await?;
+    Ok(Json(history))
+}
+
+#[instrument(skip(state, user))]
+async fn add_conversation_entry(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+    Json(mut entry): Json<ConversationEntry>,
+) -> ServiceResult<Json<ConversationEntry>> {
+    info!("Adding conversation entry for case: {}", case_id);
+    // Entries always belong to the case owner, whoever posts them.
+    let case = state.db.get_case(case_id, Scope::of(user.as_ref())).await?;
+    entry.case_id = case.id;
+    entry.user_id = case.user_id;
+    let saved_entry = state.db.add_conversation_entry(entry).await?;
+    if matches!(saved_entry.sender, MessageSender::User) {
+        let author = user.as_ref().map_or(case.user_id, |user| user.id);
+        notify_mentions(&state, &case, &saved_entry, author).await;
+    }
+    Ok(Json(saved_entry))
+}
+
+/// Stores a question the AI agent asked about a task of the case. Like
+/// conversation entries, questions belong to the case owner.
+#[instrument(skip(state, user, question))]
+async fn create_follow_up_question(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+    Json(mut question): Json<FollowUpQuestion>,
+) -> ServiceResult<Json<FollowUpQuestion>> {
+    info!("Adding follow-up question for case: {}", case_id);
+    let case = state.db.get_case(case_id, Scope::of(user.as_ref())).await?;
+    let task = state.db.get_task(question.task_id, Scope::of(user.as_ref())).await?;
+    if task.case_id != case.id {
+        return Err(common::ServiceError::BadRequest("The task belongs to another case".to_string()));
+    }
+    question.case_id = case.id;
+    question.user_id = case.user_id;
+    let question = state.db.create_follow_up_question(question).await?;
+    Ok(Json(question))
+}
+
+/// The case's follow-up questions, filtered by `?status=`.
+#[instrument(skip(state, user))]
+async fn list_follow_up_questions(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+    Query(query): Query<QuestionQuery>,
+) -> ServiceResult<Json<Vec<FollowUpQuestion>>> {
+    info!("Listing follow-up questions for case: {}", case_id);
+    let case = state.db.get_case(case_id, Scope::of(user.as_ref())).await?;
+    let questions = state.db.list_follow_up_questions(case.id, query.status).await?;
+    Ok(Json(questions))
+}
+
+#[instrument(skip(state, user))]
+async fn answer_follow_up_questions(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+    Json(request): Json<AnswerQuestionsRequest>,
+) -> ServiceResult<Json<Vec<FollowUpQuestion>>> {
+    info!("Marking {} follow-up questions answered for case: {}", request.question_ids.len(), case_id);
+    let case = state.db.get_case(case_id, Scope::of(user.as_ref())).await?;
+    let questions = state.db.answer_follow_up_questions(case.id, &request.question_ids).await?;
+    Ok(Json(questions))
+}
+
+/// Records and notifies the organization members mentioned in `entry`.
+/// They may not own the case, so the notification links to a read-only
+/// share of it when share links are enabled.
+async fn notify_mentions(state: &AppState, case: &Case, entry: &ConversationEntry, author: Uuid) {
+    let emails = mentions::parse(&entry.message);
+    if emails.is_empty() {
+        return;
+    }
+    let mentions = match state.db.record_mentions(entry, author, &emails).await {
+        Ok(mentions) => mentions,
+        Err(e) => {
+            warn!("Could not record mentions in entry {}: {}", entry.id, e);
+            return;
+        }
+    };
+    for mention in mentions {
+        let link = state.share_links.issue(case, None).ok().map(|link| format!("/shared/{}", link.token));
+        state.notifier.notify(
+            mention.user_id,
+            NotificationKind::Mentioned,
+            format!("Mentioned in '{}'", case.title),
+            entry.message.chars().take(200).collect::<String>(),
+            link,
+            Some(entry.id),
+        )
+        .await;
+    }
+}
+
+#[instrument(skip(state, user))]
+async fn get_case_workflow(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<CaseWorkflow>> {
+    info!("Getting workflow for case: {}", case_id);
+    state.db.get_case(case_id, Scope::of(user.as_ref())).await?;
+    let workflow = state.db.get_case_workflow(case_id).await?;
+    Ok(Json(workflow))
+}
+
+#[instrument(skip(state, user))]
+async fn update_case_workflow(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+    Json(workflow): Json<CaseWorkflow>,
+) -> ServiceResult<Json<CaseWorkflow>> {
+    info!("Updating workflow for case: {}", case_id);
+    state.db.get_case(case_id, Scope::of(user.as_ref())).await?;
+    let updated_workflow = state.db.update_case_workflow(CaseWorkflow { case_id, ..workflow }).await?;
+    record_case_event(
+        &state,
+        case_id,
+        user.as_ref(),
+        CaseEventKind::WorkflowStepChanged,
+        format!("Workflow moved to step '{}'", updated_workflow.current_step),
+        None,
+    )
+    .await;
+    Ok(Json(updated_workflow))
+}
+
+/// Recorded changes to a case, oldest first, for its timeline.
+#[instrument(skip(state, user))]
+async fn list_case_events(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Vec<CaseEvent>>> {
+    info!("Listing events for case: {}", case_id);
+    let scope = Scope::of(user.as_ref());
+    if user.is_some() {
+        state.db.get_case(case_id, scope).await?;
+    }
+    let events = state.db.list_case_events(case_id, scope).await?;
+    Ok(Json(events))
+}
+
+/// Records an event another service observed, such as an SLA warning.
+/// SLA events also notify the case owner. Returns `None` when `once` was
+/// asked for and the case already had an event of the kind.
+#[instrument(skip(state, user))]
+async fn create_case_event(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+    Json(request): Json<CaseEventRequest>,
+) -> ServiceResult<Json<Option<CaseEvent>>> {
+    info!("Recording {:?} event for case: {}", request.kind, case_id);
+    let case = state.db.get_case(case_id, Scope::of(user.as_ref())).await?;
+    let event = CaseEvent {
+        id: Uuid::new_v4(),
+        case_id,
+        actor_id: user.map(|u| u.id),
+        kind: request.kind,
+        summary: request.summary,
+        subject_id: request.subject_id,
+        created_at: chrono::Utc::now(),
+    };
+    let recorded = state.db.record_case_event(event, request.once).await?;
+
+    if let Some(event) = &recorded {
+        let notification = match event.kind {
+            CaseEventKind::SlaWarning => Some((NotificationKind::SlaWarning, "SLA at risk")),
+            CaseEventKind::SlaBreached => Some((NotificationKind::SlaBreached, "SLA breached")),
+            _ => None,
+        };
+        if let Some((kind, title)) = notification {
+            state.notifier.notify(
+                case.user_id,
+                kind,
+                title,
+                event.summary.clone(),
+                Some(format!("/cases/{}", case.id)),
+                Some(case.id),
+            )
+            .await;
+        }
+    }
+
+    Ok(Json(recorded))
+}
+
+/// Records the tracked fields that changed from `before` to `after`.  The
+/// change has already been made, so a failure to record it is only logged.
+async fn record_revision<T: serde::Serialize>(
+    state: &AppState,
+    entity: RevisionEntity,
+    entity_id: Uuid,
+    owner_id: Uuid,
+    actor: Option<&User>,
+    before: Option<&T>,
+    after: &T,
+) {
+    let changes = match revisions::changes(entity, before, after) {
+        Ok(Some(changes)) => changes,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Could not compute revision of {:?} {}: {}", entity, entity_id, e);
+            return;
+        }
+    };
+    let revision = Revision {
+        id: Uuid::new_v4(),
+        entity,
+        entity_id,
+        actor_id: actor.map(|user| user.id),
+        changes,
+        created_at: chrono::Utc::now(),
+    };
+    if let Err(e) = state.db.record_revision(owner_id, revision).await {
+        warn!("Could not record revision of {:?} {}: {}", entity, entity_id, e);
+    }
+}
+
+/// The revisions of an entity newer than `revision_id`, newest first.
+async fn revisions_after(
+    state: &AppState,
+    scope: Scope,
+    entity: RevisionEntity,
+    entity_id: Uuid,
+    revision_id: Uuid,
+) -> ServiceResult<Vec<Revision>> {
+    let mut revisions = state.db.list_revisions(entity, entity_id, scope).await?;
+    let position = revisions
+        .iter()
+        .position(|revision| revision.id == revision_id)
+        .ok_or_else(|| common::ServiceError::NotFound(format!("Revision with id {} not found", revision_id)))?;
+    revisions.truncate(position);
+    Ok(revisions)
+}
+
+#[instrument(skip(state, user))]
+async fn list_case_revisions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Vec<Revision>>> {
+    info!("Listing revisions of case: {}", id);
+    let scope = Scope::of(user.as_ref());
+    state.db.get_case(id, scope).await?;
+    let revisions = state.db.list_revisions(RevisionEntity::Case, id, scope).await?;
+    Ok(Json(revisions))
+}
+
+/// Brings a case back to how it was right after a revision.
+#[instrument(skip(state, user))]
+async fn restore_case_revision(
+    State(state): State<Arc<AppState>>,
+    Path((id, revision_id)): Path<(Uuid, Uuid)>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Case>> {
+    info!("Restoring case {} to revision {}", id, revision_id);
+    let scope = Scope::of(user.as_ref());
+    let current = state.db.get_case(id, scope).await?;
+    let newer = revisions_after(&state, scope, RevisionEntity::Case, id, revision_id).await?;
+    let restored = state.db.restore_case(scope, revisions::rewind(&current, &newer)?).await?;
+    record_revision(&state, RevisionEntity::Case, id, restored.user_id, user.as_ref(), Some(&current), &restored).await;
+    Ok(Json(restored))
+}
+
+#[instrument(skip(state, user))]
+async fn list_task_revisions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Vec<Revision>>> {
+    info!("Listing revisions of task: {}", id);
+    let scope = Scope::of(user.as_ref());
+    state.db.get_task(id, scope).await?;
+    let revisions = state.db.list_revisions(RevisionEntity::Task, id, scope).await?;
+    Ok(Json(revisions))
+}
+
+/// Brings a task back to how it was right after a revision.
+#[instrument(skip(state, user))]
+async fn restore_task_revision(
+    State(state): State<Arc<AppState>>,
+    Path((id, revision_id)): Path<(Uuid, Uuid)>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Task>> {
+    info!("Restoring task {} to revision {}", id, revision_id);
+    let scope = Scope::of(user.as_ref());
+    let current = state.db.get_task(id, scope).await?;
+    let newer = revisions_after(&state, scope, RevisionEntity::Task, id, revision_id).await?;
+    let mut task: Task = revisions::rewind(&current, &newer)?;
+    task.completed_at = match task.status {
+        TaskStatus::Completed => task.completed_at.or_else(|| Some(chrono::Utc::now())),
+        _ => None,
+    };
+    let restored = state.db.restore_task(scope, task).await?;
+    record_revision(&state, RevisionEntity::Task, id, restored.user_id, user.as_ref(), Some(&current), &restored).await;
+    Ok(Json(restored))
+}
+
+/// Records a change for the case timeline.  The change has already been
+/// made, so a failure to record it is only logged.
+async fn record_case_event(
+    state: &AppState,
+    case_id: Uuid,
+    actor: Option<&User>,
+    kind: CaseEventKind,
+    summary: String,
+    subject_id: Option<Uuid>,
+) {
+    let event = CaseEvent {
+        id: Uuid::new_v4(),
+        case_id,
+        actor_id: actor.map(|user| user.id),
+        kind,
+        summary,
+        subject_id,
+        created_at: chrono::Utc::now(),
+    };
+    if let Err(e) = state.db.record_case_event(event, false).await {
+        warn!("Could not record event for case {}: {}", case_id, e);
+    }
+}
+
+// Task endpoints
+#[instrument(skip(state, user))]
+async fn create_task(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Json(mut task): Json<Task>,
+) -> ServiceResult<Json<Task>> {
+    info!("Creating task: {}", task.id);
+    check_estimate(task.estimate_minutes)?;
+    if let Some(user) = &user {
+        task.user_id = user.id;
+    }
+    let created_task = state.db.create_task(task).await?;
+    record_revision(&state, RevisionEntity::Task, created_task.id, created_task.user_id, user.as_ref(), None, &created_task).await;
+
+    state.notifier.notify(
+        created_task.user_id,
+        NotificationKind::TaskAssigned,
+        "New task",
+        created_task.title.clone(),
+        Some(format!("/cases/{}", created_task.case_id)),
+        Some(created_task.id),
+    )
+    .await;
+
+    Ok(Json(created_task))
+}
+
+fn check_estimate(estimate_minutes: Option<i32>) -> ServiceResult<()> {
+    if estimate_minutes.is_some_and(|minutes| minutes < 0) {
+        return Err(common::ServiceError::BadRequest("`estimate_minutes` must not be negative".to_string()));
+    }
+    Ok(())
+}
+
+#[instrument(skip(state, user))]
+async fn get_tasks(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TaskQuery>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Vec<Task>>> {
+    info!("Getting tasks with query: {:?}", query.status);
+
+    let tag = query.tag.as_deref().and_then(tags::normalize_tag);
+    let limit = query.limit.map(|limit| limit.clamp(1, MAX_TASK_PAGE));
+    let tasks = state
+        .db
+        .list_tasks(
+            Scope::of(user.as_ref()),
+            query.status,
+            tag.as_deref(),
+            query.task_type.as_deref(),
+            limit,
+            query.offset.max(0),
+        )
+        .await?;
+
+    Ok(Json(tasks))
+}
+
+/// The task a Jira issue was created for.
+#[instrument(skip(state, user))]
+async fn find_task_by_jira_issue(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Task>> {
+    info!("Finding task of Jira issue: {}", key);
+    let task = state.db.find_task_by_jira_issue(Scope::of(user.as_ref()), &key).await?;
+    Ok(Json(task))
+}
+
+// Task template endpoints
+#[instrument(skip(state, user))]
+async fn list_task_templates(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> ServiceResult<Json<Vec<TaskTemplate>>> {
+    info!("Listing task templates for user: {}", user.id);
+    let templates = state.db.list_task_templates(user.id).await?;
+    Ok(Json(templates))
+}
+
+#[instrument(skip(state, user))]
+async fn create_task_template(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<TaskTemplateRequest>,
+) -> ServiceResult<Json<TaskTemplate>> {
+    info!("Creating task template: {}", request.name);
+    validate_template_name(&request.name)?;
+    let now = chrono::Utc::now();
+    let template = TaskTemplate {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        name: request.name,
+        title: request.title,
+        description: request.description,
+        task_type: request.task_type,
+        priority: request.priority,
+        due_in_days: request.due_in_days,
+        checklist: request.checklist,
+        created_at: now,
+        updated_at: now,
+    };
+    let created = state.db.create_task_template(template).await?;
+    Ok(Json(created))
+}
+
+#[instrument(skip(state, user))]
+async fn get_task_template(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(name): Path<String>,
+) -> ServiceResult<Json<TaskTemplate>> {
+    info!("Getting task template: {}", name);
+    let template = state.db.get_task_template(user.id, &name).await?;
+    Ok(Json(template))
+}
+
+#[instrument(skip(state, user))]
+async fn update_task_template(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(name): Path<String>,
+    Json(request): Json<TaskTemplateRequest>,
+) -> ServiceResult<Json<TaskTemplate>> {
+    info!("Updating task template: {}", name);
+    validate_template_name(&request.name)?;
+    let template = state.db.update_task_template(user.id, &name, request).await?;
+    Ok(Json(template))
+}
+
+#[instrument(skip(state, user))]
+async fn delete_task_template(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(name): Path<String>,
+) -> ServiceResult<StatusCode> {
+    info!("Deleting task template: {}", name);
+    state.db.delete_task_template(user.id, &name).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Template names appear in URLs, so they are limited to lowercase
+/// letters, digits and dashes.
+fn validate_template_name(name: &str) -> ServiceResult<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(common::ServiceError::BadRequest(
+            "Template names may only contain lowercase letters, digits and dashes".to_string(),
+        ))
+    }
+}
+
+// Saved filter endpoints
+#[instrument(skip(state, user))]
+async fn list_saved_filters(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> ServiceResult<Json<Vec<SavedFilter>>> {
+    info!("Listing saved filters for user: {}", user.id);
+    let filters = state.db.list_saved_filters(user.id).await?;
+    Ok(Json(filters))
+}
+
+#[instrument(skip(state, user))]
+async fn create_saved_filter(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<SavedFilterRequest>,
+) -> ServiceResult<Json<SavedFilter>> {
+    let request = clean_saved_filter(request)?;
+    info!("Creating saved filter: {}", request.name);
+    let now = chrono::Utc::now();
+    let saved = SavedFilter {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        name: request.name,
+        filter: request.filter,
+        created_at: now,
+        updated_at: now,
+    };
+    let created = state.db.create_saved_filter(saved).await?;
+    Ok(Json(created))
+}
+
+#[instrument(skip(state, user))]
+async fn get_saved_filter(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<SavedFilter>> {
+    info!("Getting saved filter: {}", id);
+    let saved = state.db.get_saved_filter(user.id, id).await?;
+    Ok(Json(saved))
+}
+
+#[instrument(skip(state, user))]
+async fn update_saved_filter(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+    Json(request): Json<SavedFilterRequest>,
+) -> ServiceResult<Json<SavedFilter>> {
+    info!("Updating saved filter: {}", id);
+    let request = clean_saved_filter(request)?;
+    let saved = state.db.update_saved_filter(user.id, id, request).await?;
+    Ok(Json(saved))
+}
+
+#[instrument(skip(state, user))]
+async fn delete_saved_filter(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    info!("Deleting saved filter: {}", id);
+    state.db.delete_saved_filter(user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The caller's tasks matching a saved filter, soonest due first.
+#[instrument(skip(state, user))]
+async fn get_saved_filter_tasks(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<Vec<Task>>> {
+    info!("Listing tasks for saved filter: {}", id);
+    let saved = state.db.get_saved_filter(user.id, id).await?;
+    let now = chrono::Utc::now();
+    let mut tasks = state.db.list_tasks_for_user(user.id).await?;
+    tasks.retain(|task| saved.filter.matches(task, now));
+    Ok(Json(tasks))
+}
+
+/// Trims the name and normalizes the tags so they compare equal to stored
+/// task tags.
+fn clean_saved_filter(mut request: SavedFilterRequest) -> ServiceResult<SavedFilterRequest> {
+    request.name = request.name.trim().to_string();
+    if request.name.is_empty() || request.name.chars().count() > 64 {
+        return Err(common::ServiceError::BadRequest(
+            "Filter names must be between 1 and 64 characters".to_string(),
+        ));
+    }
+    request.filter.tags = tags::normalize_tags(&request.filter.tags);
+    Ok(request)
+}
+
+/// The signed-in user's latest sign-in attempts, failed ones included.
+#[instrument(skip(state, user))]
+async fn list_logins(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<LoginHistoryQuery>,
+) -> ServiceResult<Json<Vec<LoginEvent>>> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let mut events = state.db.list_login_events(user.id, limit).await?;
+    for event in &mut events {
+        event.device = event.user_agent.as_deref().map(security::device);
+    }
+    Ok(Json(events))
+}
+
+/// Longest an OAuth sign-in may wait for its callback.
+const MAX_OAUTH_STATE_SECS: i64 = 3600;
+
+/// Keeps a started OAuth sign-in of the dashboard until its callback, on
+/// whichever replica that arrives.
+#[instrument(skip(state, _internal, request))]
+async fn save_oauth_state(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+    Json(request): Json<SaveOAuthStateRequest>,
+) -> ServiceResult<StatusCode> {
+    if request.state.is_empty() {
+        return Err(common::ServiceError::BadRequest("state must not be empty".to_string()));
+    }
+    if !(1..=MAX_OAUTH_STATE_SECS).contains(&request.expires_in_secs) {
+        return Err(common::ServiceError::BadRequest(format!(
+            "expires_in_secs must be between 1 and {}",
+            MAX_OAUTH_STATE_SECS
+        )));
+    }
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(request.expires_in_secs);
+    state.db.save_oauth_state(&request.state, &request.payload, expires_at).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Hands a saved sign-in to its callback, once.
+#[instrument(skip(state, _internal, oauth_state))]
+async fn take_oauth_state(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+    Path(oauth_state): Path<String>,
+) -> ServiceResult<Json<serde_json::Value>> {
+    let payload = state.db.take_oauth_state(&oauth_state).await?;
+    Ok(Json(payload))
+}
+
+// API token endpoints
+#[instrument(skip(state, user))]
+async fn list_api_tokens(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> ServiceResult<Json<Vec<ApiToken>>> {
+    let tokens = state.db.list_api_tokens(user.id).await?;
+    Ok(Json(tokens))
+}
+
+/// Creates a personal access token, whose secret is returned only this
+/// once. Tokens can't create tokens; this takes a session.
+#[instrument(skip(state, user))]
+async fn create_api_token(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<CreateApiTokenRequest>,
+) -> ServiceResult<Json<ApiToken>> {
+    let name = request.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(common::ServiceError::BadRequest("Token names must be between 1 and 64 characters".to_string()));
+    }
+    let mut scopes = request.scopes;
+    scopes.sort_by_key(|scope| scope.as_str());
+    scopes.dedup();
+    if scopes.is_empty() {
+        return Err(common::ServiceError::BadRequest("A token needs at least one scope".to_string()));
+    }
+    let expires_at = match request.expires_in_days {
+        Some(days) if !(1..=365).contains(&days) => {
+            return Err(common::ServiceError::BadRequest("Tokens expire in 1 to 365 days".to_string()));
+        }
+        days => days.map(|days| chrono::Utc::now() + chrono::Duration::days(days)),
+    };
+
+    info!("Creating API token {} for user {}", name, user.id);
+    let token = ApiToken {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        name,
+        scopes,
+        created_at: chrono::Utc::now(),
+        expires_at,
+        last_used_at: None,
+        revoked_at: None,
+        token: Some(format!("{}{}", auth::API_TOKEN_PREFIX, Uuid::new_v4().simple())),
+    };
+    let created = state.db.create_api_token(token).await?;
+    Ok(Json(created))
+}
+
+#[instrument(skip(state, user))]
+async fn revoke_api_token(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<ApiToken>> {
+    info!("Revoking API token {} of user {}", id, user.id);
+    let token = state.db.revoke_api_token(user.id, id).await?;
+    Ok(Json(token))
+}
+
+// Email account endpoints
+/// The mailboxes the user connected, without their tokens.
+#[instrument(skip(state, user))]
+async fn list_email_accounts(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> ServiceResult<Json<Vec<EmailAccount>>> {
+    let accounts = state.db.list_email_accounts(user.id).await?;
+    Ok(Json(accounts.into_iter().map(without_tokens).collect()))
+}
+
+/// Connects a mailbox the user authorized to the user. Connecting it again
+/// replaces its tokens.
+#[instrument(skip(state, user, request), fields(email_address = %request.email_address))]
+async fn add_email_account(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<AddEmailAccountRequest>,
+) -> ServiceResult<Json<EmailAccount>> {
+    let email_address = request.email_address.trim().to_string();
+    if !email_address.contains('@') {
+        return Err(common::ServiceError::BadRequest("A mailbox address is required".to_string()));
+    }
+    info!("Connecting mailbox {} for user {}", email_address, user.id);
+    let now = chrono::Utc::now();
+    let account = EmailAccount {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        email_address,
+        provider: request.provider,
+        is_active: true,
+        oauth_token: request.oauth_token,
+        oauth_refresh_token: request.oauth_refresh_token,
+        oauth_expires_at: request.oauth_expires_at,
+        imap_settings: request.imap_settings,
+        scopes: request.scopes,
+        health: Default::default(),
+        created_at: now,
+        updated_at: now,
+        metadata: serde_json::json!({}),
+    };
+    let saved = state.db.save_email_account(account).await?;
+    Ok(Json(without_tokens(saved)))
+}
+
+/// Stops polling a mailbox of the user and drops its tokens; connecting
+/// it again resumes where polling stopped.
+#[instrument(skip(state, user))]
+async fn disconnect_email_account(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<EmailAccount>> {
+    info!("Disconnecting mailbox {} of user {}", id, user.id);
+    let account = state.db.disconnect_email_account(user.id, id).await?;
+    Ok(Json(account))
+}
+
+/// Removes a mailbox of the user; the email collector forgets what it
+/// fetched from it on its next poll.
+#[instrument(skip(state, user))]
+async fn delete_email_account(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    info!("Removing mailbox {} of user {}", id, user.id);
+    state.db.delete_email_account(user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Every mailbox with its tokens, for the email collector, which polls the
+/// active ones.
+#[instrument(skip(state, _internal))]
+async fn all_email_accounts(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+) -> ServiceResult<Json<Vec<EmailAccount>>> {
+    Ok(Json(state.db.all_email_accounts().await?))
+}
+
+/// Records how polling a mailbox goes, for the email collector.
+#[instrument(skip(state, _internal, health))]
+async fn set_email_account_health(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+    Path(id): Path<Uuid>,
+    Json(health): Json<MailboxHealth>,
+) -> ServiceResult<Json<MailboxHealth>> {
+    Ok(Json(state.db.set_email_account_health(id, &health).await?))
+}
+
+fn without_tokens(account: EmailAccount) -> EmailAccount {
+    EmailAccount { oauth_token: None, oauth_refresh_token: None, ..account }
+}
+
+// Webhook integration endpoints
+#[instrument(skip(state, user))]
+async fn list_webhook_integrations(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> ServiceResult<Json<Vec<WebhookIntegration>>> {
+    info!("Listing webhook integrations for user: {}", user.id);
+    let integrations = state.db.list_webhook_integrations(user.id).await?;
+    Ok(Json(integrations))
+}
+
+/// Creates an integration with a new URL token, returned only this once.
+#[instrument(skip(state, user))]
+async fn create_webhook_integration(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<WebhookIntegrationRequest>,
+) -> ServiceResult<Json<WebhookIntegration>> {
+    let name = request.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(common::ServiceError::BadRequest(
+            "Integration names must be between 1 and 64 characters".to_string(),
+        ));
+    }
+    if request.mapping.sender.trim().is_empty() || request.mapping.body.trim().is_empty() {
+        return Err(common::ServiceError::BadRequest("The sender and body paths are required".to_string()));
+    }
+    info!("Creating webhook integration: {}", name);
+    let integration = WebhookIntegration {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        name,
+        mapping: request.mapping,
+        created_at: chrono::Utc::now(),
+        token: Some(Uuid::new_v4().simple().to_string()),
+    };
+    let created = state.db.create_webhook_integration(integration).await?;
+    Ok(Json(created))
+}
+
+/// The integration behind a webhook URL, for the channel service only:
+/// the integration carries its token and owner.
+#[instrument(skip(state, _internal, request))]
+async fn resolve_webhook_integration(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+    Json(request): Json<ResolveWebhookRequest>,
+) -> ServiceResult<Json<WebhookIntegration>> {
+    let integration = state.db.resolve_webhook_integration(&request.token).await?;
+    Ok(Json(integration))
+}
+
+#[instrument(skip(state, user))]
+async fn delete_webhook_integration(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    info!("Deleting webhook integration: {}", id);
+    state.db.delete_webhook_integration(user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Task delta for offline clients.
+#[instrument(skip(state, user))]
+async fn sync_tasks(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<SyncQuery>,
+) -> ServiceResult<Json<TaskSync>> {
+    info!("Syncing tasks for user {} since {:?}", user.id, query.since);
+    // Taken before querying so changes made meanwhile are picked up next time.
+    let server_time = chrono::Utc::now();
+    let (tasks, deleted_task_ids) = state.db.tasks_changed_since(user.id, query.since).await?;
+    Ok(Json(TaskSync { server_time, tasks, deleted_task_ids }))
+}
+
+#[instrument(skip(state, user))]
+async fn get_task_stats(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StatsQuery>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<TaskStats>> {
+    let (from, to) = stats_range(query.from, query.to)?;
+    info!("Getting task stats by {:?} from {} to {}", query.group_by, from, to);
+
+    let stats = state
+        .db
+        .task_stats(Scope::of(user.as_ref()), query.group_by, from, to)
+        .await?;
+    Ok(Json(stats))
+}
+
+#[instrument(skip(state, user))]
+async fn get_productivity_stats(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StatsRangeQuery>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<ProductivityStats>> {
+    let (from, to) = stats_range(query.from, query.to)?;
+    info!("Getting productivity stats from {} to {}", from, to);
+
+    let stats = state.db.productivity_stats(Scope::of(user.as_ref()), from, to).await?;
+    Ok(Json(stats))
+}
+
+#[instrument(skip(state, user))]
+async fn get_case_productivity(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<CaseProductivity>> {
+    info!("Getting productivity stats for case: {}", id);
+    let stats = state.db.case_productivity(id, Scope::of(user.as_ref())).await?;
+    Ok(Json(stats))
+}
+
+#[instrument(skip(state, _admin))]
+async fn get_user_productivity(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminAuth,
+    Query(query): Query<StatsRangeQuery>,
+) -> ServiceResult<Json<Vec<UserProductivity>>> {
+    let (from, to) = stats_range(query.from, query.to)?;
+    info!("Getting per-user productivity from {} to {}", from, to);
+
+    let rows = state.db.user_productivity(from, to).await?;
+    Ok(Json(rows))
+}
+
+/// Estimated effort of open tasks per due day and user, from today for
+/// [`DEFAULT_WORKLOAD_DAYS`] days unless a range is given.
+#[instrument(skip(state, user))]
+async fn get_workload(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<WorkloadQuery>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Vec<WorkloadDay>>> {
+    let from = query.from.unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let to = query.to.unwrap_or(from + chrono::Duration::days(DEFAULT_WORKLOAD_DAYS - 1));
+    if from > to {
+        return Err(common::ServiceError::BadRequest("`from` must be before `to`".to_string()));
+    }
+    info!("Getting workload from {} to {}", from, to);
+
+    let days = state.db.workload(Scope::of(user.as_ref()), from, to).await?;
+    Ok(Json(days))
+}
+
+// Notification endpoints
+#[instrument(skip(state, user))]
+async fn list_notifications(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<NotificationQuery>,
+) -> ServiceResult<Json<NotificationList>> {
+    info!("Listing notifications for user: {}", user.id);
+    let limit = query.limit.clamp(1, 100);
+    let (notifications, unread_count) = tokio::try_join!(
+        state.db.list_notifications(user.id, query.unread_only, limit),
+        state.db.count_unread_notifications(user.id),
+    )?;
+    Ok(Json(NotificationList { unread_count, notifications }))
+}
+
+/// Where the signed-in user was mentioned, newest first.
+#[instrument(skip(state, user))]
+async fn list_mentions(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<MentionQuery>,
+) -> ServiceResult<Json<Vec<Mention>>> {
+    info!("Listing mentions of user: {}", user.id);
+    let limit = query.limit.unwrap_or(default_notification_limit()).clamp(1, 100);
+    let mentions = state.db.list_mentions(user.id, limit).await?;
+    Ok(Json(mentions))
+}
+
+/// Records a notification for the signed-in user, e.g. from the dashboard
+/// once an email account is connected. Returns `null` for a duplicate or a
+/// muted kind.
+#[instrument(skip(state, user, request))]
+async fn create_notification(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<CreateNotificationRequest>,
+) -> ServiceResult<Json<Option<Notification>>> {
+    info!("Creating {:?} notification for user: {}", request.kind, user.id);
+    if user.preferences.notifications.muted.contains(&request.kind) {
+        return Ok(Json(None));
+    }
+    let notification = Notification {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        kind: request.kind,
+        title: request.title,
+        message: request.message,
+        link: request.link,
+        subject_id: request.subject_id,
+        created_at: chrono::Utc::now(),
+        read_at: None,
+    };
+    let created = state.db.create_notification(notification).await?;
+    Ok(Json(created))
+}
+
+#[instrument(skip(state, user))]
+async fn mark_notification_read(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<Notification>> {
+    info!("Marking notification {} read", id);
+    let notification = state.db.mark_notification_read(user.id, id).await?;
+    Ok(Json(notification))
+}
+
+#[instrument(skip(state, user))]
+async fn mark_all_notifications_read(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> ServiceResult<Json<serde_json::Value>> {
+    info!("Marking all notifications read for user: {}", user.id);
+    let updated = state.db.mark_all_notifications_read(user.id).await?;
+    Ok(Json(serde_json::json!({ "updated": updated })))
+}
+
+// Web Push endpoints
+#[instrument(skip(state))]
+async fn get_vapid_public_key(
+    State(state): State<Arc<AppState>>,
+) -> Json<VapidPublicKey> {
+    Json(VapidPublicKey {
+        public_key: state.notifier.push().vapid().public_key().to_string(),
+    })
+}
+
+#[instrument(skip(state, user, request))]
+async fn subscribe_push(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<PushSubscriptionRequest>,
+) -> ServiceResult<Json<PushSubscription>> {
+    info!("Registering push subscription for user: {}", user.id);
+    if !request.endpoint.starts_with("https://") {
+        return Err(common::ServiceError::BadRequest("Push endpoint must be an https URL".to_string()));
+    }
+    let subscription = PushSubscription {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        endpoint: request.endpoint,
+        p256dh: request.keys.p256dh,
+        auth: request.keys.auth,
+        created_at: chrono::Utc::now(),
+    };
+    let saved = state.db.save_push_subscription(subscription).await?;
+    Ok(Json(saved))
+}
+
+#[instrument(skip(state, user, request))]
+async fn unsubscribe_push(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<UnsubscribePushRequest>,
+) -> ServiceResult<Json<serde_json::Value>> {
+    info!("Removing push subscription for user: {}", user.id);
+    let removed = state.db.delete_push_subscription(&request.endpoint, Some(user.id)).await?;
+    Ok(Json(serde_json::json!({ "removed": removed })))
+}
+
+#[instrument(skip(state, user))]
+async fn get_task(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Task>> {
+    info!("Getting task: {}", id);
+    let task = state.db.get_task(id, Scope::of(user.as_ref())).await?;
+    Ok(Json(task))
+}
+
+#[instrument(skip(state, user))]
+async fn update_task(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+    Json(request): Json<UpdateTaskRequest>,
+) -> ServiceResult<Json<Task>> {
+    info!("Updating task: {}", id);
+    check_estimate(request.estimate_minutes)?;
+    let scope = Scope::of(user.as_ref());
+    let previous = state.db.get_task(id, scope).await?;
+    let previous_status = previous.status.clone();
+    let updated_task = state.db.update_task(id, scope, request).await?;
+    record_revision(&state, RevisionEntity::Task, id, updated_task.user_id, user.as_ref(), Some(&previous), &updated_task).await;
+
+    // Completions are part of the task itself; other moves are recorded
+    if updated_task.status != previous_status && updated_task.status != TaskStatus::Completed {
+        record_case_event(
+            &state,
+            updated_task.case_id,
+            user.as_ref(),
+            CaseEventKind::TaskStatusChanged,
+            format!("Task '{}' moved from {:?} to {:?}", updated_task.title, previous_status, updated_task.status),
+            Some(updated_task.id),
+        )
+        .await;
+    }
+
+    Ok(Json(updated_task))
+}
+
+#[instrument(skip(state, user))]
+async fn delete_task(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<StatusCode> {
+    info!("Deleting task: {}", id);
+    let scope = Scope::of(user.as_ref());
+    let task = state.db.get_task(id, scope).await?;
+    state.db.delete_task(id, scope).await?;
+    record_case_event(
+        &state,
+        task.case_id,
+        user.as_ref(),
+        CaseEventKind::TaskDeleted,
+        format!("Task '{}' deleted", task.title),
+        Some(task.id),
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip(state, user))]
+async fn get_tasks_for_case(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    Query(query): Query<TaskQuery>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Vec<Task>>> {
+    info!("Getting tasks for case: {}", case_id);
+    let mut tasks = state.db.get_tasks_for_case(case_id, Scope::of(user.as_ref())).await?;
+    if let Some(status) = query.status {
+        tasks.retain(|task| task.status == status);
+    }
+    if let Some(tag) = query.tag.as_deref().and_then(tags::normalize_tag) {
+        tasks.retain(|task| task.tags.contains(&tag));
+    }
+    Ok(Json(tasks))
+}
+
+/// Tags in use by the caller, for filters and autocompletion.
+#[instrument(skip(state, user))]
+async fn list_tags(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> ServiceResult<Json<Vec<TagUsage>>> {
+    info!("Listing tags for user: {}", user.id);
+    let tags = state.db.list_tags(user.id).await?;
+    Ok(Json(tags))
+}
+
+/// Holds a message back for review. The message belongs to the forwarded
+/// session's user, or else to the owner the message names, if any.
+#[instrument(skip(state, user, message))]
+async fn create_quarantined_message(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Json(mut message): Json<QuarantinedMessage>,
+) -> ServiceResult<Json<QuarantinedMessage>> {
+    info!("Quarantining message {}: {:?}", message.id, message.reasons);
+    message.user_id = user.map(|u| u.id).or(message.message.owner_id);
+    let created = state.db.create_quarantined_message(message).await?;
+    Ok(Json(created))
+}
+
+/// The caller's quarantined messages; internal calls see every message.
+#[instrument(skip(state, user))]
+async fn list_quarantined_messages(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Query(query): Query<QuarantineQuery>,
+) -> ServiceResult<Json<Vec<QuarantinedMessage>>> {
+    info!("Listing quarantined messages with status: {:?}", query.status);
+    let messages = state.db.list_quarantined_messages(user.map(|u| u.id), query.status).await?;
+    Ok(Json(messages))
+}
+
+#[instrument(skip(state, user))]
+async fn review_quarantined_message(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Path(id): Path<Uuid>,
+    Json(request): Json<ReviewQuarantineRequest>,
+) -> ServiceResult<Json<QuarantinedMessage>> {
+    info!("Reviewing quarantined message {}: {:?}", id, request.status);
+    let message = state.db.review_quarantined_message(id, user.map(|u| u.id), request.status).await?;
+    Ok(Json(message))
+}
+
+#[instrument(skip(state, user, request))]
+async fn create_failed_message(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Json(request): Json<CreateFailedMessageRequest>,
+) -> ServiceResult<Json<FailedMessage>> {
+    let failed = state.db.create_failed_message(user.map(|u| u.id), request).await?;
+    warn!("Recorded failed message {}: {}", failed.id, failed.error);
+    Ok(Json(failed))
+}
+
+/// The caller's failed messages; internal calls see every message.
+#[instrument(skip(state, user))]
+async fn list_failed_messages(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Query(query): Query<FailedMessageQuery>,
+) -> ServiceResult<Json<Vec<FailedMessage>>> {
+    info!("Listing failed messages with status: {:?}", query.status);
+    let messages = state.db.list_failed_messages(user.map(|u| u.id), query.status).await?;
+    Ok(Json(messages))
+}
+
+#[instrument(skip(state, _internal))]
+async fn claim_due_failed_messages(
+    State(state): State<Arc<AppState>>,
+    _internal: InternalCall,
+    Query(query): Query<ClaimQuery>,
+) -> ServiceResult<Json<Vec<FailedMessage>>> {
+    let messages = state.db.claim_due_failed_messages(query.limit.unwrap_or(10).clamp(1, 100)).await?;
+    Ok(Json(messages))
+}
+
+#[instrument(skip(state, user))]
+async fn claim_failed_message(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<Json<FailedMessage>> {
+    let message = state.db.claim_failed_message(id, user.map(|u| u.id)).await?;
+    Ok(Json(message))
+}
+
+#[instrument(skip(state, user))]
+async fn record_failed_message_attempt(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Path(id): Path<Uuid>,
+    Json(attempt): Json<FailedMessageAttempt>,
+) -> ServiceResult<Json<FailedMessage>> {
+    let message = state.db.record_failed_message_attempt(id, user.map(|u| u.id), attempt).await?;
+    info!("Failed message {} is now {:?} after {} attempts", id, message.status, message.attempts);
+    Ok(Json(message))
+}
+
+/// Records feedback on a task. The task-management service fills in the
+/// message and extraction; the feedback belongs to the forwarded session's
+/// user, if any.
+#[instrument(skip(state, user, feedback))]
+async fn record_task_feedback(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Json(mut feedback): Json<TaskFeedback>,
+) -> ServiceResult<Json<TaskFeedback>> {
+    info!("Recording {:?} feedback on task {}", feedback.verdict, feedback.task_id);
+    if let Some(user) = user {
+        feedback.user_id = user.id;
+    }
+    let recorded = state.db.upsert_task_feedback(feedback).await?;
+    Ok(Json(recorded))
+}
+
+/// The caller's task feedback; internal calls see all of it.
+#[instrument(skip(state, user))]
+async fn list_task_feedback(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+    Query(query): Query<TaskFeedbackQuery>,
+) -> ServiceResult<Json<Vec<TaskFeedback>>> {
+    info!("Listing task feedback with verdict: {:?}", query.verdict);
+    let feedback = state.db.list_task_feedback(user.map(|u| u.id), query.verdict).await?;
+    Ok(Json(feedback))
+}
+
+// Custom task type endpoints
+/// The caller's organization's task types; none for callers outside an
+/// organization and for internal calls.
+#[instrument(skip(state, user))]
+async fn list_custom_task_types(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(user): OptionalUser,
+) -> ServiceResult<Json<Vec<CustomTaskType>>> {
+    let Some(user) = user else {
+        return Ok(Json(Vec::new()));
+    };
+    let Some(organization) = state.db.organization_of(user.id).await? else {
+        return Ok(Json(Vec::new()));
+    };
+    info!("Listing task types for organization: {}", organization);
+    let task_types = state.db.list_custom_task_types(&organization).await?;
+    Ok(Json(task_types))
+}
+
+#[instrument(skip(state, user))]
+async fn create_custom_task_type(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<CustomTaskTypeRequest>,
+) -> ServiceResult<Json<CustomTaskType>> {
+    info!("Creating task type: {}", request.name);
+    let organization = user_organization(&state.db, &user).await?;
+    validate_custom_task_type(&request)?;
+    let now = chrono::Utc::now();
+    let task_type = CustomTaskType {
+        id: Uuid::new_v4(),
+        organization,
+        name: request.name.trim().to_string(),
+        description: request.description,
+        fields: request.fields,
+        created_at: now,
+        updated_at: now,
+    };
+    let created = state.db.create_custom_task_type(task_type).await?;
+    Ok(Json(created))
+}
+
+/// Replaces a task type. Renaming it leaves existing tasks with the old
+/// name.
+#[instrument(skip(state, user))]
+async fn update_custom_task_type(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+    Json(mut request): Json<CustomTaskTypeRequest>,
+) -> ServiceResult<Json<CustomTaskType>> {
+    info!("Updating task type: {}", id);
+    let organization = user_organization(&state.db, &user).await?;
+    validate_custom_task_type(&request)?;
+    request.name = request.name.trim().to_string();
+    let updated = state.db.update_custom_task_type(&organization, id, request).await?;
+    Ok(Json(updated))
+}
+
+#[instrument(skip(state, user))]
+async fn delete_custom_task_type(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    info!("Deleting task type: {}", id);
+    let organization = user_organization(&state.db, &user).await?;
+    state.db.delete_custom_task_type(&organization, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The organization an administrator added the user to; the one in their
+/// profile is whatever they typed in.
+async fn user_organization(db: &Database, user: &User) -> ServiceResult<String> {
+    db.organization_of(user.id)
+        .await?
+        .ok_or_else(|| common::ServiceError::BadRequest("Task types belong to an organization, and you are not in one".to_string()))
+}
+
+/// Custom types can't shadow built-in ones, and their fields need distinct
+/// names.
+fn validate_custom_task_type(request: &CustomTaskTypeRequest) -> ServiceResult<()> {
+    let name = request.name.trim();
+    if name.is_empty() || name.len() > 64 {
+        return Err(common::ServiceError::BadRequest("Task type names must be 1 to 64 characters".to_string()));
+    }
+    let built_in = ["Meeting", "Shopping", "Work", "Personal", "Research", "Communication"];
+    if built_in.iter().any(|built_in| built_in.eq_ignore_ascii_case(name)) {
+        return Err(common::ServiceError::BadRequest(format!("'{}' is a built-in task type", name)));
+    }
+    let mut field_names = std::collections::HashSet::new();
+    for field in &request.fields {
+        if field.name.trim().is_empty() || !field_names.insert(field.name.as_str()) {
+            return Err(common::ServiceError::BadRequest(format!(
+                "Metadata field names must be present and unique, got '{}'",
+                field.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Extraction example endpoints
+#[instrument(skip(state, user))]
+async fn list_extraction_examples(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> ServiceResult<Json<Vec<ExtractionExample>>> {
+    info!("Listing extraction examples for user: {}", user.id);
+    let examples = state.db.list_extraction_examples(user.id).await?;
+    Ok(Json(examples))
+}
+
+#[instrument(skip(state, user, request))]
+async fn create_extraction_example(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<ExtractionExampleRequest>,
+) -> ServiceResult<Json<ExtractionExample>> {
+    info!("Creating extraction example with {} tasks", request.expected_tasks.len());
+    validate_extraction_example(&request)?;
+    let example = ExtractionExample {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        message: request.message,
+        expected_tasks: request.expected_tasks,
+        created_at: chrono::Utc::now(),
+    };
+    let created = state.db.create_extraction_example(example).await?;
+    Ok(Json(created))
+}
+
+#[instrument(skip(state, user))]
+async fn delete_extraction_example(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<Uuid>,
+) -> ServiceResult<StatusCode> {
+    info!("Deleting extraction example: {}", id);
+    state.db.delete_extraction_example(user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Examples end up in the prompt, so each needs a message and every task
+/// at least a title.
+fn validate_extraction_example(request: &ExtractionExampleRequest) -> ServiceResult<()> {
+    if request.message.trim().is_empty() {
+        return Err(common::ServiceError::BadRequest("An example needs a message".to_string()));
+    }
+    let titled = |task: &serde_json::Value| task["title"].as_str().is_some_and(|title| !title.trim().is_empty());
+    if !request.expected_tasks.iter().all(titled) {
+        return Err(common::ServiceError::BadRequest("Every expected task needs a title".to_string()));
+    }
+    Ok(())
 }
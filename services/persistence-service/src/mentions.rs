@@ -0,0 +1,92 @@
+//! `@email` mentions in conversation entries.
+//!
+//! Entries written by users are scanned for `@someone@company.com`; each
+//! address that belongs to an active member of the author's organization,
+//! as administrators manage its members, is recorded as a [`models::Mention`] and notified. Other addresses,
+//! such as those quoted in a forwarded email, are left alone, and authors
+//! without an organization cannot mention anyone.
+
+/// Mentions noticed in one entry; the rest are ignored.
+pub const MAX_PER_ENTRY: usize = 20;
+
+/// The lowercase addresses mentioned in `message`, each once, in order.
+pub fn parse(message: &str) -> Vec<String> {
+    let mut emails: Vec<String> = Vec::new();
+    for word in message.split_whitespace() {
+        let Some(start) = word.find('@') else { continue };
+        // `@` has to start the mention, after any opening punctuation.
+        if !word[..start].chars().all(|c| matches!(c, '(' | '[' | '"' | '\'')) {
+            continue;
+        }
+        let email = word[start + 1..].trim_end_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        if is_email(&email) && !emails.contains(&email) {
+            emails.push(email);
+            if emails.len() == MAX_PER_ENTRY {
+                break;
+            }
+        }
+    }
+    emails
+}
+
+fn is_email(candidate: &str) -> bool {
+    let Some((local, domain)) = candidate.split_once('@') else { return false };
+    let allowed = |c: char| c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '+');
+    !local.is_empty()
+        && local.chars().all(allowed)
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && domain.chars().all(|c| c.is_alphanumeric() || matches!(c, '.' | '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::test_db::{added_to, case_of, database, member_of};
+    use chrono::Utc;
+    use models::{ConversationEntry, MessageSender};
+    use uuid::Uuid;
+
+    #[test]
+    fn finds_mentions_in_prose() {
+        assert_eq!(
+            parse("Thanks @Ana@Company.com, can (@bo.li@company.com) take this? cc @ana@company.com."),
+            ["ana@company.com", "bo.li@company.com"]
+        );
+    }
+
+    #[test]
+    fn ignores_plain_addresses_and_handles() {
+        assert!(parse("Mail ana@company.com or ping @ana on chat, @@company.com, @ana@localhost").is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn mentions_stay_within_the_organization() {
+        let db = database().await;
+        let organization = Uuid::new_v4().to_string();
+        let (author, colleague) = (added_to(&db, &organization).await, added_to(&db, &organization).await);
+        let outsider = added_to(&db, &Uuid::new_v4().to_string()).await;
+        let claimer = member_of(&db, &organization).await;
+        let case = case_of(&db, &author).await;
+        let entry = db
+            .add_conversation_entry(ConversationEntry {
+                id: Uuid::new_v4(),
+                user_id: author.id,
+                case_id: case.id,
+                message: format!("@{}, @{} and @{}, please have a look", colleague.email, outsider.email, claimer.email),
+                sender: MessageSender::User,
+                timestamp: Utc::now(),
+                metadata: serde_json::json!({}),
+            })
+            .await
+            .unwrap();
+
+        let emails = parse(&entry.message);
+        let mentions = db.record_mentions(&entry, author.id, &emails).await.unwrap();
+        assert_eq!(mentions.iter().map(|mention| mention.user_id).collect::<Vec<_>>(), [colleague.id]);
+        assert_eq!(db.list_mentions(colleague.id, 10).await.unwrap().len(), 1);
+        assert!(db.list_mentions(outsider.id, 10).await.unwrap().is_empty());
+        assert!(db.list_mentions(claimer.id, 10).await.unwrap().is_empty(), "not by naming it in the profile");
+    }
+}
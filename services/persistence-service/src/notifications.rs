@@ -0,0 +1,268 @@
+//! Notifications, honoring each user's [`NotificationPreferences`]: muted
+//! kinds are never recorded, and a push that should not go out yet (in
+//! quiet hours, or with digest delivery) is held on its notification. The
+//! `notifications.overdue` job releases held pushes once due, as a single
+//! summary push when a user has several.
+
+use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, Utc};
+use common::ServiceResult;
+use models::{Notification, NotificationDelivery, NotificationKind, NotificationPreferences, QuietHours};
+use std::collections::hash_map::{Entry, HashMap};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::database_working::Database;
+use crate::push::PushSender;
+
+/// Records notifications and forwards the ones worth interrupting the user
+/// for to their subscribed browsers.
+#[derive(Clone)]
+pub struct Notifier {
+    db: Database,
+    push: PushSender,
+}
+
+impl Notifier {
+    pub fn new(db: Database, push: PushSender) -> Self {
+        Self { db, push }
+    }
+
+    pub fn push(&self) -> &PushSender {
+        &self.push
+    }
+
+    /// Records a notification for `user_id` unless they muted its kind.
+    /// Failures are logged rather than returned so a notification never
+    /// fails the request that triggered it.
+    pub async fn notify(
+        &self,
+        user_id: Uuid,
+        kind: NotificationKind,
+        title: impl Into<String>,
+        message: impl Into<String>,
+        link: Option<String>,
+        subject_id: Option<Uuid>,
+    ) {
+        match self.db.user_preferences(user_id).await {
+            Ok(preferences) if preferences.notifications.muted.contains(&kind) => return,
+            Ok(_) => {}
+            Err(e) => warn!("Failed to load preferences of user {}: {}", user_id, e),
+        }
+
+        let notification = Notification {
+            id: Uuid::new_v4(),
+            user_id,
+            kind,
+            title: title.into(),
+            message: message.into(),
+            link,
+            subject_id,
+            created_at: Utc::now(),
+            read_at: None,
+        };
+
+        match self.db.create_notification(notification).await {
+            Ok(Some(notification)) => self.dispatch(vec![notification]).await,
+            Ok(None) => {}
+            Err(e) => warn!("Failed to create {:?} notification for user {}: {}", kind, user_id, e),
+        }
+    }
+
+    /// Notifies users about tasks that have become overdue since the last
+    /// time, and releases held pushes that are due; run by the
+    /// `notifications.overdue` job.
+    pub async fn notify_overdue(&self) -> ServiceResult<()> {
+        let created = self.db.notify_overdue_tasks().await?;
+        if !created.is_empty() {
+            info!("Created {} overdue task notifications", created.len());
+            self.dispatch(created).await;
+        }
+        self.release_held().await
+    }
+
+    /// Only reminders and assignments are pushed; everything else waits for
+    /// the dashboard bell. Pushes the user does not want yet are held.
+    async fn dispatch(&self, notifications: Vec<Notification>) {
+        let now = Utc::now();
+        let mut preferences = HashMap::new();
+        let mut immediate = Vec::new();
+        let mut held = Vec::new();
+        for notification in notifications.into_iter().filter(|n| pushed(n.kind)) {
+            let user_id = notification.user_id;
+            if let Entry::Vacant(entry) = preferences.entry(user_id) {
+                let loaded = self.db.user_preferences(user_id).await.map(|preferences| preferences.notifications);
+                entry.insert(loaded.unwrap_or_default());
+            }
+            let preferences = &preferences[&user_id];
+            if !preferences.push {
+                continue;
+            }
+            if push_due(preferences, notification.created_at) <= now {
+                immediate.push(notification);
+            } else {
+                held.push(notification.id);
+            }
+        }
+
+        if !held.is_empty() {
+            if let Err(e) = self.db.hold_pushes(&held).await {
+                warn!("Failed to hold {} pushes: {}", held.len(), e);
+            }
+        }
+        self.push_in_background(immediate);
+    }
+
+    /// Pushes the held notifications that are due, one summary per user
+    /// with several.
+    async fn release_held(&self) -> ServiceResult<()> {
+        let now = Utc::now();
+        let mut by_user: HashMap<Uuid, Vec<Notification>> = HashMap::new();
+        for notification in self.db.held_pushes().await? {
+            by_user.entry(notification.user_id).or_default().push(notification);
+        }
+
+        let mut released = Vec::new();
+        let mut pushes = Vec::new();
+        for (user_id, notifications) in by_user {
+            let preferences = self.db.user_preferences(user_id).await?.notifications;
+            let due: Vec<Notification> = if preferences.push {
+                notifications.into_iter().filter(|n| push_due(&preferences, n.created_at) <= now).collect()
+            } else {
+                // Push was turned off since; the bell still has them.
+                released.extend(notifications.iter().map(|n| n.id));
+                continue;
+            };
+            released.extend(due.iter().map(|n| n.id));
+            match due.len() {
+                0 => {}
+                1 => pushes.extend(due),
+                count => pushes.push(summary(user_id, count, now)),
+            }
+        }
+
+        if !released.is_empty() {
+            info!("Releasing {} held pushes as {} pushes", released.len(), pushes.len());
+            self.db.release_pushes(&released).await?;
+        }
+        self.push_in_background(pushes);
+        Ok(())
+    }
+
+    fn push_in_background(&self, notifications: Vec<Notification>) {
+        if notifications.is_empty() {
+            return;
+        }
+
+        let push = self.push.clone();
+        tokio::spawn(async move {
+            for notification in &notifications {
+                push.deliver(notification).await;
+            }
+        });
+    }
+}
+
+fn pushed(kind: NotificationKind) -> bool {
+    matches!(kind, NotificationKind::TaskAssigned | NotificationKind::TaskOverdue)
+}
+
+/// A push standing in for `count` held ones; it is not recorded.
+fn summary(user_id: Uuid, count: usize, now: DateTime<Utc>) -> Notification {
+    Notification {
+        id: Uuid::new_v4(),
+        user_id,
+        kind: NotificationKind::TaskOverdue,
+        title: format!("{} new notifications", count),
+        message: "Task reminders and assignments are waiting for you.".to_string(),
+        link: Some("/dashboard".to_string()),
+        subject_id: None,
+        created_at: now,
+        read_at: None,
+    }
+}
+
+/// When a push for a notification created at `at` may go out: at once,
+/// at the end of quiet hours, or at the next digest (itself postponed past
+/// quiet hours).
+pub fn push_due(preferences: &NotificationPreferences, at: DateTime<Utc>) -> DateTime<Utc> {
+    let offset = Duration::minutes(preferences.utc_offset_minutes.into());
+    let local = at.naive_utc() + offset;
+    let mut due = match preferences.delivery {
+        NotificationDelivery::Immediate => local,
+        NotificationDelivery::Digest => next(local, preferences.digest_time),
+    };
+    if let Some(quiet) = preferences.quiet_hours {
+        if is_quiet(quiet, due.time()) {
+            due = next(due, quiet.end);
+        }
+    }
+    (due - offset).and_utc()
+}
+
+fn is_quiet(quiet: QuietHours, time: NaiveTime) -> bool {
+    if quiet.start <= quiet.end {
+        quiet.start <= time && time < quiet.end
+    } else {
+        time >= quiet.start || time < quiet.end
+    }
+}
+
+/// The first `time` of day at or after `from`.
+fn next(from: NaiveDateTime, time: NaiveTime) -> NaiveDateTime {
+    let candidate = from.date().and_time(time);
+    if candidate >= from {
+        candidate
+    } else {
+        candidate + Duration::days(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::push_due;
+    use chrono::{DateTime, NaiveTime, TimeZone, Utc};
+    use models::{NotificationDelivery, NotificationPreferences, QuietHours};
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, hour, minute, 0).unwrap()
+    }
+
+    fn time(hour: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    fn night() -> Option<QuietHours> {
+        Some(QuietHours { start: time(22), end: time(7) })
+    }
+
+    #[test]
+    fn pushes_go_out_at_once_by_default() {
+        assert_eq!(push_due(&NotificationPreferences::default(), at(3, 0)), at(3, 0));
+    }
+
+    #[test]
+    fn quiet_hours_hold_pushes_until_they_end() {
+        let preferences = NotificationPreferences { quiet_hours: night(), ..Default::default() };
+        assert_eq!(push_due(&preferences, at(12, 0)), at(12, 0));
+        assert_eq!(push_due(&preferences, at(3, 0)), at(7, 0));
+        assert_eq!(push_due(&preferences, at(23, 0)), at(7, 0) + chrono::Duration::days(1));
+    }
+
+    #[test]
+    fn quiet_hours_follow_the_users_clock() {
+        // 22:00-07:00 at UTC+2 is 20:00-05:00 UTC.
+        let preferences = NotificationPreferences { quiet_hours: night(), utc_offset_minutes: 120, ..Default::default() };
+        assert_eq!(push_due(&preferences, at(21, 0)), at(5, 0) + chrono::Duration::days(1));
+        assert_eq!(push_due(&preferences, at(6, 0)), at(6, 0));
+    }
+
+    #[test]
+    fn digests_wait_for_the_digest_time_outside_quiet_hours() {
+        let digest = NotificationPreferences { delivery: NotificationDelivery::Digest, ..Default::default() };
+        assert_eq!(push_due(&digest, at(7, 30)), at(8, 0));
+        assert_eq!(push_due(&digest, at(8, 30)), at(8, 0) + chrono::Duration::days(1));
+
+        let late = NotificationPreferences { digest_time: time(23), quiet_hours: night(), ..digest };
+        assert_eq!(push_due(&late, at(12, 0)), at(7, 0) + chrono::Duration::days(1));
+    }
+}
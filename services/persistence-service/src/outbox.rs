@@ -0,0 +1,123 @@
+//! Publication of task and case changes.
+//!
+//! Every create, update or deletion of a task or case writes an
+//! [`OutboxEvent`] in the same transaction, so an event exists exactly when
+//! its change was committed. The `outbox.relay` job then POSTs the events,
+//! in the order they happened, to each URL in `outbox.webhook_urls`
+//! (`OUTBOX_WEBHOOK_URLS`, comma separated). An event is marked published
+//! once every URL has accepted it; until then it is retried with backoff
+//! and holds back the events after it. A retry can repeat a delivery, so
+//! consumers drop events whose `X-Event-Id` they have already seen.
+//!
+//! With `outbox.webhook_secret` (`OUTBOX_WEBHOOK_SECRET`) set, each request
+//! carries `X-Outbox-Signature: sha256=<hex HMAC-SHA256 of the body>`.
+//! Relay lag is at `GET /api/v1/admin/outbox`, and logged as a warning once
+//! it passes `outbox.lag_warn_secs` (`OUTBOX_LAG_WARN_SECS`, default 300).
+
+use common::config::OutboxConfig;
+use common::ServiceResult;
+use hmac::{Hmac, Mac};
+use models::OutboxEvent;
+use sha2::Sha256;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::database_working::Database;
+
+pub const RELAY_JOB: &str = "outbox.relay";
+
+/// Events taken per round of the relay.
+const RELAY_BATCH: i64 = 100;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct Relay {
+    db: Database,
+    client: reqwest::Client,
+    webhooks: Vec<String>,
+    secret: Option<String>,
+    warn_after: chrono::Duration,
+}
+
+impl Relay {
+    pub fn new(db: Database, config: &OutboxConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            db,
+            client,
+            webhooks: config.webhook_urls.clone(),
+            secret: config.webhook_secret.clone(),
+            warn_after: chrono::Duration::seconds(config.lag_warn_secs as i64),
+        }
+    }
+
+    /// Publishes the events due; run by the `outbox.relay` job. Stops at
+    /// the first event that cannot be published.
+    pub async fn relay(&self) -> ServiceResult<()> {
+        let mut published = 0;
+        loop {
+            let events = self.db.due_outbox_events(RELAY_BATCH).await?;
+            let batch = events.len();
+            for event in events {
+                if let Err(e) = self.publish(&event).await {
+                    warn!("Could not publish outbox event {} ({}): {}", event.id, event.event_type, e);
+                    self.db.record_outbox_failure(event.id, &e.to_string()).await?;
+                    return self.check_lag().await;
+                }
+                self.db.mark_outbox_published(event.id).await?;
+                published += 1;
+            }
+            if (batch as i64) < RELAY_BATCH {
+                break;
+            }
+        }
+        if published > 0 {
+            info!("Published {} outbox events", published);
+        }
+        self.check_lag().await
+    }
+
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), reqwest::Error> {
+        if self.webhooks.is_empty() {
+            debug!("No outbox webhooks, dropping event {}", event.id);
+            return Ok(());
+        }
+        let body = serde_json::to_vec(event).unwrap_or_default();
+        for url in &self.webhooks {
+            let mut request = self
+                .client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header("X-Event-Id", event.id.to_string())
+                .header("X-Event-Type", &event.event_type);
+            if let Some(secret) = &self.secret {
+                request = request.header("X-Outbox-Signature", format!("sha256={}", sign(secret, &body)));
+            }
+            request.body(body.clone()).send().await?.error_for_status()?;
+        }
+        Ok(())
+    }
+
+    async fn check_lag(&self) -> ServiceResult<()> {
+        let lag = self.db.outbox_lag().await?;
+        if lag.lag_secs > self.warn_after.num_seconds() {
+            warn!(
+                "Outbox relay is {}s behind with {} events pending; last error: {}",
+                lag.lag_secs,
+                lag.pending,
+                lag.last_error.as_deref().unwrap_or("none")
+            );
+        }
+        Ok(())
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
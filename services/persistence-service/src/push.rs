@@ -0,0 +1,220 @@
+use aes_gcm::{aead::Aead, Aes128Gcm, KeyInit, Nonce};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use common::{config::PushConfig, ServiceError, ServiceResult};
+use hkdf::Hkdf;
+use p256::{
+    ecdh::EphemeralSecret,
+    ecdsa::{signature::Signer, Signature, SigningKey},
+    elliptic_curve::sec1::ToEncodedPoint,
+    PublicKey,
+};
+use models::{Notification, PushSubscription};
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha256;
+use tracing::{info, warn};
+
+use crate::database_working::Database;
+
+/// How long a push service keeps an undelivered message, in seconds.
+const PUSH_TTL_SECS: u32 = 24 * 60 * 60;
+/// Lifetime of the VAPID JWT sent with each message. Push services reject
+/// tokens valid for more than 24 hours.
+const VAPID_TOKEN_HOURS: i64 = 12;
+/// Record size advertised in the aes128gcm header. Payloads are always a
+/// single record.
+const RECORD_SIZE: u32 = 4096;
+
+/// Application server identity used to sign push requests (RFC 8292).
+#[derive(Clone)]
+pub struct VapidKeys {
+    signing_key: SigningKey,
+    public_key: String,
+    subject: String,
+}
+
+impl VapidKeys {
+    /// Uses `push.vapid_private_key` (`VAPID_PRIVATE_KEY`) when set,
+    /// otherwise the key stored in the database, generating one on first
+    /// start so browser subscriptions survive restarts.
+    pub async fn load(db: &Database, config: &PushConfig) -> ServiceResult<Self> {
+        let encoded = match &config.vapid_private_key {
+            Some(key) => key.clone(),
+            None => {
+                let generated = URL_SAFE_NO_PAD.encode(SigningKey::random(&mut OsRng).to_bytes());
+                db.vapid_private_key_or_insert(&generated).await?
+            }
+        };
+
+        let bytes = URL_SAFE_NO_PAD
+            .decode(encoded.trim())
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Invalid VAPID private key: {}", e)))?;
+        let signing_key = SigningKey::from_slice(&bytes)
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Invalid VAPID private key: {}", e)))?;
+        let public_key = URL_SAFE_NO_PAD.encode(signing_key.verifying_key().to_encoded_point(false).as_bytes());
+        let subject = config.vapid_subject.clone();
+
+        Ok(Self { signing_key, public_key, subject })
+    }
+
+    /// A fresh key, without the database.
+    #[cfg(test)]
+    pub fn generate() -> Self {
+        let signing_key = SigningKey::random(&mut OsRng);
+        let public_key = URL_SAFE_NO_PAD.encode(signing_key.verifying_key().to_encoded_point(false).as_bytes());
+        Self { signing_key, public_key, subject: "mailto:admin@localhost".to_string() }
+    }
+
+    /// Application server key handed to `pushManager.subscribe()`.
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// Builds the `Authorization` header value for a push service origin.
+    fn authorization(&self, audience: &str) -> ServiceResult<String> {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"typ":"JWT","alg":"ES256"}"#);
+        let claims = serde_json::json!({
+            "aud": audience,
+            "exp": (Utc::now() + chrono::Duration::hours(VAPID_TOKEN_HOURS)).timestamp(),
+            "sub": self.subject,
+        });
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+
+        let signing_input = format!("{}.{}", header, claims);
+        let signature: Signature = self
+            .signing_key
+            .try_sign(signing_input.as_bytes())
+            .map_err(|e| ServiceError::Internal(anyhow::anyhow!("VAPID signing failed: {}", e)))?;
+
+        Ok(format!(
+            "vapid t={}.{}, k={}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+            self.public_key
+        ))
+    }
+}
+
+/// Delivers notifications to the browsers a user has subscribed.
+#[derive(Clone)]
+pub struct PushSender {
+    db: Database,
+    vapid: VapidKeys,
+    client: reqwest::Client,
+}
+
+impl PushSender {
+    pub fn new(db: Database, vapid: VapidKeys) -> Self {
+        Self { db, vapid, client: reqwest::Client::new() }
+    }
+
+    pub fn vapid(&self) -> &VapidKeys {
+        &self.vapid
+    }
+
+    /// Sends `notification` to every subscription of its user. Subscriptions
+    /// the push service reports as expired are removed.
+    pub async fn deliver(&self, notification: &Notification) {
+        let subscriptions = match self.db.list_push_subscriptions(notification.user_id).await {
+            Ok(subscriptions) => subscriptions,
+            Err(e) => {
+                warn!("Failed to load push subscriptions for user {}: {}", notification.user_id, e);
+                return;
+            }
+        };
+
+        let payload = serde_json::json!({
+            "id": notification.id,
+            "title": notification.title,
+            "body": notification.message,
+            "link": notification.link,
+        })
+        .to_string();
+
+        for subscription in subscriptions {
+            match self.send(&subscription, payload.as_bytes()).await {
+                Ok(status) if status == reqwest::StatusCode::NOT_FOUND || status == reqwest::StatusCode::GONE => {
+                    info!("Removing expired push subscription {}", subscription.id);
+                    if let Err(e) = self.db.delete_push_subscription(&subscription.endpoint, None).await {
+                        warn!("Failed to remove push subscription {}: {}", subscription.id, e);
+                    }
+                }
+                Ok(status) if !status.is_success() => {
+                    warn!("Push service rejected message for subscription {}: {}", subscription.id, status);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to push to subscription {}: {}", subscription.id, e),
+            }
+        }
+    }
+
+    async fn send(&self, subscription: &PushSubscription, payload: &[u8]) -> ServiceResult<reqwest::StatusCode> {
+        let endpoint = reqwest::Url::parse(&subscription.endpoint)
+            .map_err(|e| ServiceError::BadRequest(format!("Invalid push endpoint: {}", e)))?;
+        let authorization = self.vapid.authorization(&endpoint.origin().ascii_serialization())?;
+        let body = encrypt(subscription, payload)?;
+
+        let response = self
+            .client
+            .post(endpoint)
+            .header("Authorization", authorization)
+            .header("Content-Encoding", "aes128gcm")
+            .header("Content-Type", "application/octet-stream")
+            .header("TTL", PUSH_TTL_SECS.to_string())
+            .body(body)
+            .send()
+            .await
+            .map_err(ServiceError::HttpClient)?;
+
+        Ok(response.status())
+    }
+}
+
+/// Encrypts a payload for a subscription using the `aes128gcm` content
+/// encoding (RFC 8188) keyed as described in RFC 8291.
+fn encrypt(subscription: &PushSubscription, payload: &[u8]) -> ServiceResult<Vec<u8>> {
+    let invalid_key = |e: &dyn std::fmt::Display| ServiceError::BadRequest(format!("Invalid subscription key: {}", e));
+
+    let ua_public_bytes = URL_SAFE_NO_PAD.decode(subscription.p256dh.trim_end_matches('=')).map_err(|e| invalid_key(&e))?;
+    let ua_public = PublicKey::from_sec1_bytes(&ua_public_bytes).map_err(|e| invalid_key(&e))?;
+    let auth_secret = URL_SAFE_NO_PAD.decode(subscription.auth.trim_end_matches('=')).map_err(|e| invalid_key(&e))?;
+
+    let as_secret = EphemeralSecret::random(&mut OsRng);
+    let as_public = as_secret.public_key().to_encoded_point(false);
+    let shared_secret = as_secret.diffie_hellman(&ua_public);
+
+    // Combine the ECDH secret with the subscription's auth secret.
+    let mut key_info = b"WebPush: info\0".to_vec();
+    key_info.extend_from_slice(&ua_public_bytes);
+    key_info.extend_from_slice(as_public.as_bytes());
+    let mut ikm = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&auth_secret), shared_secret.raw_secret_bytes())
+        .expand(&key_info, &mut ikm)
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Key derivation failed: {}", e)))?;
+
+    // Derive the content encryption key and nonce from a fresh salt.
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let prk = Hkdf::<Sha256>::new(Some(&salt), &ikm);
+    let mut cek = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    prk.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+        .and_then(|_| prk.expand(b"Content-Encoding: nonce\0", &mut nonce))
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Key derivation failed: {}", e)))?;
+
+    // A single record, terminated by the last-record padding delimiter.
+    let mut record = payload.to_vec();
+    record.push(2);
+    let ciphertext = Aes128Gcm::new_from_slice(&cek)
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Encryption failed: {}", e)))?
+        .encrypt(Nonce::from_slice(&nonce), record.as_slice())
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Encryption failed: {}", e)))?;
+
+    let mut body = Vec::with_capacity(16 + 4 + 1 + as_public.len() + ciphertext.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public.len() as u8);
+    body.extend_from_slice(as_public.as_bytes());
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
@@ -0,0 +1,58 @@
+//! Case retention.
+//!
+//! Closed cases are archived once they have not changed for a while, which
+//! takes them out of case lists unless archived cases are asked for.
+//! Archived cases can be restored, and are optionally purged, together with
+//! their tasks and conversation, once they have stayed archived long
+//! enough. Both periods count from the case's last update.
+
+use chrono::{Duration, Utc};
+use common::ServiceResult;
+use tracing::info;
+
+use crate::database_working::Database;
+
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    /// Closed cases untouched for this long are archived.
+    pub archive_after: Option<Duration>,
+    /// Archived cases untouched for this long are deleted.
+    pub purge_after: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    /// `CASE_ARCHIVE_AFTER_DAYS` (default 30) and `CASE_PURGE_AFTER_DAYS`
+    /// (unset by default, so nothing is purged); `0` turns a step off.
+    pub fn from_env() -> Self {
+        let days = |name: &str, default: Option<i64>| {
+            std::env::var(name)
+                .ok()
+                .and_then(|days| days.parse::<i64>().ok())
+                .or(default)
+                .filter(|days| *days > 0)
+                .map(Duration::days)
+        };
+        Self {
+            archive_after: days("CASE_ARCHIVE_AFTER_DAYS", Some(30)),
+            purge_after: days("CASE_PURGE_AFTER_DAYS", None),
+        }
+    }
+}
+
+/// Applies `policy` once; run by the `cases.retention` job.
+pub async fn apply(db: &Database, policy: &RetentionPolicy) -> ServiceResult<()> {
+    let now = Utc::now();
+    if let Some(archive_after) = policy.archive_after {
+        let archived = db.archive_closed_cases(now - archive_after).await?;
+        if archived > 0 {
+            info!("Archived {} closed cases", archived);
+        }
+    }
+    if let Some(purge_after) = policy.purge_after {
+        let purged = db.purge_archived_cases(now - purge_after).await?;
+        if purged > 0 {
+            info!("Purged {} archived cases", purged);
+        }
+    }
+    Ok(())
+}
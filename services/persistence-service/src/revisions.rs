@@ -0,0 +1,70 @@
+//! Revision history of cases and tasks.
+//!
+//! Every change to a case or task made through the API is stored as a
+//! revision holding the old and new value of each field that changed.
+//! Restoring a revision brings the entity back to how it was right after
+//! that revision, by undoing the later ones newest first; the restore is
+//! itself recorded, so it can be undone too.
+
+use common::{ServiceError, ServiceResult};
+use models::{Revision, RevisionEntity};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+
+/// Fields of a case that revisions track.
+const CASE_FIELDS: &[&str] = &["title", "description", "status", "priority", "assigned_to", "tags"];
+
+/// Fields of a task that revisions track.
+const TASK_FIELDS: &[&str] = &[
+    "title",
+    "description",
+    "status",
+    "priority",
+    "due_date",
+    "tags",
+    "metadata",
+    "estimate_minutes",
+];
+
+fn fields(entity: RevisionEntity) -> &'static [&'static str] {
+    match entity {
+        RevisionEntity::Case => CASE_FIELDS,
+        RevisionEntity::Task => TASK_FIELDS,
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> ServiceResult<Value> {
+    serde_json::to_value(value).map_err(ServiceError::Serialization)
+}
+
+/// The tracked fields that differ between `before` and `after`, or `None`
+/// when nothing tracked changed. Without `before` every field counts as
+/// changed from null.
+pub fn changes<T: Serialize>(entity: RevisionEntity, before: Option<&T>, after: &T) -> ServiceResult<Option<Value>> {
+    let before = before.map(to_value).transpose()?.unwrap_or(Value::Null);
+    let after = to_value(after)?;
+
+    let mut changes = Map::new();
+    for field in fields(entity) {
+        let from = before.get(*field).cloned().unwrap_or(Value::Null);
+        let to = after.get(*field).cloned().unwrap_or(Value::Null);
+        if from != to {
+            changes.insert(field.to_string(), json!({ "from": from, "to": to }));
+        }
+    }
+    Ok((!changes.is_empty()).then_some(Value::Object(changes)))
+}
+
+/// `current` with the revisions in `newer`, newest first, undone.
+pub fn rewind<T: Serialize + DeserializeOwned>(current: &T, newer: &[Revision]) -> ServiceResult<T> {
+    let mut value = to_value(current)?;
+    for revision in newer {
+        let Some(changes) = revision.changes.as_object() else {
+            continue;
+        };
+        for (field, change) in changes {
+            value[field.as_str()] = change.get("from").cloned().unwrap_or(Value::Null);
+        }
+    }
+    serde_json::from_value(value).map_err(ServiceError::Serialization)
+}
@@ -0,0 +1,222 @@
+//! Tenant scoping of case and task queries.
+//!
+//! Every database operation on cases, tasks and what belongs to them
+//! (conversation, events, revisions, stats) takes a [`Scope`].  A user
+//! scope only ever matches rows that user owns, so another user's case or
+//! task reads as not found and cannot be changed.  Calls from other
+//! services without a session and the background sweeps run in
+//! [`Scope::Internal`], which has to be spelled out where it is used.
+
+use models::User;
+use uuid::Uuid;
+
+/// Whose cases and tasks a query may see.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Only rows owned by this user.
+    User(Uuid),
+    /// Every row; for service-to-service calls and sweeps.
+    Internal,
+}
+
+impl Scope {
+    /// The scope of a request: its user's, or internal without a session.
+    /// Only requests carrying the internal service token get that far
+    /// without one; see [`OptionalUser`](crate::auth::OptionalUser).
+    pub fn of(user: Option<&User>) -> Self {
+        match user {
+            Some(user) => Scope::User(user.id),
+            None => Scope::Internal,
+        }
+    }
+
+    /// The owner to filter on, bound to `($n::uuid IS NULL OR user_id = $n)`.
+    pub fn owner(self) -> Option<Uuid> {
+        match self {
+            Scope::User(id) => Some(id),
+            Scope::Internal => None,
+        }
+    }
+}
+
+/// Isolation between users, run against the database named by
+/// `TEST_DATABASE_URL`; ignored unless asked for (see [`crate::test_db`]).
+#[cfg(test)]
+mod tests {
+    use super::Scope;
+    use crate::test_db::{assert_not_found, case_of, database, task_of, task_update, user};
+    use chrono::Utc;
+    use models::{
+        CaseEvent, CaseEventKind, CaseStatus, ConversationEntry, EmailThread, MessageSender, Revision, RevisionEntity,
+        TaskStatus, UpdateCaseRequest,
+    };
+    use uuid::Uuid;
+
+    fn case_update() -> UpdateCaseRequest {
+        UpdateCaseRequest {
+            title: Some("Taken over".to_string()),
+            description: None,
+            status: Some(CaseStatus::Closed),
+            priority: None,
+            assigned_to: None,
+            tags: Some(Vec::new()),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn users_cannot_read_each_others_cases() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let case = case_of(&db, &bob).await;
+        let as_alice = Scope::User(alice.id);
+
+        assert_not_found(db.get_case(case.id, as_alice).await);
+        assert_not_found(db.case_productivity(case.id, as_alice).await);
+        let cases = db.list_cases(as_alice, None).await.unwrap();
+        assert!(cases.iter().all(|listed| listed.user_id == alice.id));
+
+        assert_eq!(db.get_case(case.id, Scope::User(bob.id)).await.unwrap().id, case.id);
+        assert_eq!(db.get_case(case.id, Scope::Internal).await.unwrap().id, case.id);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn users_cannot_change_each_others_cases() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let case = case_of(&db, &bob).await;
+        let as_alice = Scope::User(alice.id);
+
+        assert_not_found(db.update_case(case.id, as_alice, case_update()).await);
+        let mut taken = case.clone();
+        taken.user_id = alice.id;
+        taken.title = "Taken over".to_string();
+        assert_not_found(db.restore_case(as_alice, taken).await);
+        assert_not_found(db.restore_archived_case(case.id, as_alice).await);
+        let thread = EmailThread { message_id: Some("<stolen@example.com>".to_string()), ..Default::default() };
+        assert_not_found(db.record_email_thread(case.id, as_alice, &thread).await);
+
+        let unchanged = db.get_case(case.id, Scope::User(bob.id)).await.unwrap();
+        assert_eq!(unchanged.title, case.title);
+        assert_eq!(unchanged.status, CaseStatus::Open);
+        assert_eq!(unchanged.tags, case.tags);
+        assert_eq!(unchanged.metadata, case.metadata);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn users_cannot_read_each_others_tasks() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let case = case_of(&db, &bob).await;
+        let task = task_of(&db, &case).await;
+        let as_alice = Scope::User(alice.id);
+
+        assert_not_found(db.get_task(task.id, as_alice).await);
+        assert!(db.get_tasks_for_case(case.id, as_alice).await.unwrap().is_empty());
+        let tasks = db.list_tasks(as_alice, None, None, None, None, 0).await.unwrap();
+        assert!(tasks.iter().all(|listed| listed.user_id == alice.id));
+
+        assert_eq!(db.get_task(task.id, Scope::User(bob.id)).await.unwrap().id, task.id);
+        assert_eq!(db.get_tasks_for_case(case.id, Scope::User(bob.id)).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn users_cannot_change_each_others_tasks() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let case = case_of(&db, &bob).await;
+        let task = task_of(&db, &case).await;
+        let as_alice = Scope::User(alice.id);
+
+        assert_not_found(db.update_task(task.id, as_alice, task_update()).await);
+        let mut taken = task.clone();
+        taken.title = "Taken over".to_string();
+        assert_not_found(db.restore_task(as_alice, taken).await);
+        assert_not_found(db.delete_task(task.id, as_alice).await);
+
+        let unchanged = db.get_task(task.id, Scope::User(bob.id)).await.unwrap();
+        assert_eq!(unchanged.title, task.title);
+        assert_eq!(unchanged.status, TaskStatus::Pending);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn users_cannot_see_each_others_case_history() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let case = case_of(&db, &bob).await;
+        let task = task_of(&db, &case).await;
+        let now = Utc::now();
+        db.add_conversation_entry(ConversationEntry {
+            id: Uuid::new_v4(),
+            user_id: bob.id,
+            case_id: case.id,
+            message: "My account number is 1234".to_string(),
+            sender: MessageSender::User,
+            timestamp: now,
+            metadata: serde_json::json!({}),
+        })
+        .await
+        .unwrap();
+        db.record_case_event(
+            CaseEvent {
+                id: Uuid::new_v4(),
+                case_id: case.id,
+                actor_id: Some(bob.id),
+                kind: CaseEventKind::CaseStatusChanged,
+                summary: "Case moved to InProgress".to_string(),
+                subject_id: None,
+                created_at: now,
+            },
+            false,
+        )
+        .await
+        .unwrap();
+        db.record_revision(
+            bob.id,
+            Revision {
+                id: Uuid::new_v4(),
+                entity: RevisionEntity::Task,
+                entity_id: task.id,
+                actor_id: Some(bob.id),
+                changes: serde_json::json!({ "title": { "from": null, "to": task.title } }),
+                created_at: now,
+            },
+        )
+        .await
+        .unwrap();
+        let as_alice = Scope::User(alice.id);
+
+        assert!(db.get_conversation_history(case.id, as_alice).await.unwrap().is_empty());
+        assert!(db.list_case_events(case.id, as_alice).await.unwrap().is_empty());
+        assert!(db.list_revisions(RevisionEntity::Task, task.id, as_alice).await.unwrap().is_empty());
+
+        let as_bob = Scope::User(bob.id);
+        assert_eq!(db.get_conversation_history(case.id, as_bob).await.unwrap().len(), 1);
+        assert_eq!(db.list_case_events(case.id, as_bob).await.unwrap().len(), 1);
+        assert_eq!(db.list_revisions(RevisionEntity::Task, task.id, as_bob).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn transferred_case_moves_with_its_tasks() {
+        let db = database().await;
+        let (alice, bob) = (user(&db).await, user(&db).await);
+        let case = case_of(&db, &bob).await;
+        let task = task_of(&db, &case).await;
+
+        assert_not_found(db.transfer_case(case.id, Scope::User(alice.id), &alice.email).await);
+        let (transferred, recipient) = db.transfer_case(case.id, Scope::User(bob.id), &alice.email).await.unwrap();
+        assert_eq!((transferred.user_id, recipient.id), (alice.id, alice.id));
+
+        let as_alice = Scope::User(alice.id);
+        assert_eq!(db.get_task(task.id, as_alice).await.unwrap().user_id, alice.id);
+        assert_not_found(db.get_case(case.id, Scope::User(bob.id)).await);
+        assert_not_found(db.get_task(task.id, Scope::User(bob.id)).await);
+        let (_, deleted) = db.tasks_changed_since(bob.id, Some(case.created_at)).await.unwrap();
+        assert!(deleted.contains(&task.id), "gone from the previous owner's offline clients");
+    }
+}
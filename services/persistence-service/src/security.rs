@@ -0,0 +1,340 @@
+//! Where sign-ins come from, and alerts about unusual ones.
+//!
+//! Every sign-in keeps the client's IP address and user agent on its
+//! session and in `login_events`, where failed password attempts on an
+//! account are recorded too, for its login history.
+//!
+//! A sign-in is unusual when the account has signed in during the last
+//! [`HISTORY_DAYS`] days, but never from this device - the browser and
+//! operating system named by the user agent - or never from this network,
+//! the /24 (IPv4) or /48 (IPv6) the address is in. The first sign-in on
+//! record is never unusual. The owner of the account is
+//! then emailed, from the mailbox they connected, through the email
+//! collector's `POST /api/v1/email/send`, by a `security.login_alert` job.
+//!
+//! The dashboard reports the browser's address and user agent in the login
+//! request; a reported client counts only when the request carries the
+//! internal service token, since anyone else could claim a known device.
+//! Otherwise the `X-Forwarded-For` and `User-Agent` headers of the request
+//! are used; the rightmost forwarded address is the one the nearest proxy
+//! saw.
+
+use axum::http::HeaderMap;
+use chrono::Utc;
+use common::{auth::check_internal, config::ServiceConfig, http_client::HttpClient, secrets::Secrets};
+use models::{ClientInfo, EnqueueJobRequest, Job, SendEmailRequest, User};
+use std::net::IpAddr;
+use tracing::{info, warn};
+
+use crate::database_working::Database;
+
+pub const LOGIN_ALERT_JOB: &str = "security.login_alert";
+
+/// How far back sign-ins count as known devices and networks.
+pub const HISTORY_DAYS: i64 = 90;
+
+/// What is new about an unusual sign-in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unusual {
+    pub new_device: Option<String>,
+    pub new_network: Option<String>,
+}
+
+/// The client of a login request: what the dashboard reported, when the
+/// request comes from a service, or else the request's own headers.
+pub fn client_info(reported: ClientInfo, headers: &HeaderMap, secrets: &Secrets) -> ClientInfo {
+    let is_reported = reported.ip_address.is_some() || reported.user_agent.is_some();
+    if is_reported && check_internal(headers, secrets).is_ok() {
+        return reported;
+    }
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    ClientInfo {
+        ip_address: header("x-forwarded-for")
+            .and_then(|forwarded| forwarded.rsplit(',').next())
+            .map(|address| address.trim().to_string())
+            .filter(|address| address.parse::<IpAddr>().is_ok()),
+        user_agent: header("user-agent").map(str::trim).filter(|agent| !agent.is_empty()).map(str::to_string),
+    }
+}
+
+/// Browser and operating system of a user agent, e.g. `Firefox on Linux`.
+/// Clients that aren't browsers go by their product name.
+pub fn device(user_agent: &str) -> String {
+    let agent = user_agent.to_lowercase();
+    let browser = if agent.contains("edg/") {
+        "Edge"
+    } else if agent.contains("opr/") || agent.contains("opera") {
+        "Opera"
+    } else if agent.contains("firefox/") || agent.contains("fxios/") {
+        "Firefox"
+    } else if agent.contains("chrome/") || agent.contains("crios/") {
+        "Chrome"
+    } else if agent.contains("safari/") {
+        "Safari"
+    } else {
+        let product = user_agent.split(['/', ' ']).next().unwrap_or_default();
+        return if product.is_empty() { "Unknown device".to_string() } else { product.to_string() };
+    };
+    // iOS and Android user agents also name macOS and Linux.
+    let system = if agent.contains("iphone") || agent.contains("ipad") {
+        "iOS"
+    } else if agent.contains("android") {
+        "Android"
+    } else if agent.contains("windows") {
+        "Windows"
+    } else if agent.contains("mac os") || agent.contains("macintosh") {
+        "macOS"
+    } else if agent.contains("cros") {
+        "ChromeOS"
+    } else if agent.contains("linux") {
+        "Linux"
+    } else {
+        return browser.to_string();
+    };
+    format!("{} on {}", browser, system)
+}
+
+/// The network an address is in: its /24, or its /48 for IPv6.
+pub fn network(ip_address: &str) -> Option<String> {
+    match ip_address.parse::<IpAddr>().ok()? {
+        IpAddr::V4(address) => {
+            let [a, b, c, _] = address.octets();
+            Some(format!("{}.{}.{}.0/24", a, b, c))
+        }
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(address) => network(&address.to_string()),
+            None => {
+                let segments = address.segments();
+                Some(format!("{:x}:{:x}:{:x}::/48", segments[0], segments[1], segments[2]))
+            }
+        },
+    }
+}
+
+/// Whether a sign-in from `client` is unusual after the `history` of the
+/// account's recent sign-ins.
+pub fn assess(history: &[ClientInfo], client: &ClientInfo) -> Option<Unusual> {
+    if history.is_empty() {
+        return None;
+    }
+    let new_device = client.user_agent.as_deref().map(device).filter(|current| {
+        !history.iter().any(|known| known.user_agent.as_deref().map(device).as_ref() == Some(current))
+    });
+    let new_network = client.ip_address.as_deref().and_then(network).filter(|current| {
+        !history.iter().any(|known| known.ip_address.as_deref().and_then(network).as_ref() == Some(current))
+    });
+    (new_device.is_some() || new_network.is_some()).then_some(Unusual { new_device, new_network })
+}
+
+/// Records the sign-in of `user` and queues an alert when it is unusual.
+/// A failure is logged; it doesn't stop the sign-in.
+pub async fn record_login(db: &Database, user: &User, client: &ClientInfo) {
+    let since = Utc::now() - chrono::Duration::days(HISTORY_DAYS);
+    let history = match db.recent_logins(user.id, since).await {
+        Ok(history) => history,
+        Err(e) => {
+            warn!("Could not look up the sign-ins of user {}: {}", user.id, e);
+            return;
+        }
+    };
+    let unusual = assess(&history, client);
+    if let Err(e) = db.record_login_event(user.id, client, true, unusual.is_some()).await {
+        warn!("Could not record the sign-in of user {}: {}", user.id, e);
+    }
+
+    let Some(unusual) = unusual else {
+        return;
+    };
+    info!("Unusual sign-in to user {}: {:?}", user.id, unusual);
+    let email = alert_email(user, client, &unusual);
+    let request = EnqueueJobRequest {
+        kind: LOGIN_ALERT_JOB.to_string(),
+        payload: serde_json::to_value(&email).unwrap_or_default(),
+        run_at: None,
+        max_attempts: None,
+    };
+    if let Err(e) = db.enqueue_job(&request).await {
+        warn!("Could not queue the sign-in alert for user {}: {}", user.id, e);
+    }
+}
+
+fn alert_email(user: &User, client: &ClientInfo, unusual: &Unusual) -> SendEmailRequest {
+    let mut body = format!(
+        "Your Task Manager account {} was signed in to at {}",
+        user.email,
+        Utc::now().format("%Y-%m-%d %H:%M UTC")
+    );
+    match (&unusual.new_device, &unusual.new_network) {
+        (Some(device), Some(network)) => body.push_str(&format!(" from a new device ({}) on a new network ({}).", device, network)),
+        (Some(device), None) => body.push_str(&format!(" from a new device ({}).", device)),
+        (None, Some(network)) => body.push_str(&format!(" from a new network ({}).", network)),
+        (None, None) => body.push('.'),
+    }
+    if let Some(ip_address) = &client.ip_address {
+        body.push_str(&format!("\n\nIP address: {}", ip_address));
+    }
+    if let Some(user_agent) = &client.user_agent {
+        body.push_str(&format!("\nBrowser: {}", user_agent));
+    }
+    body.push_str(
+        "\n\nIf this was you, there is nothing to do. If it wasn't, change your password \
+         and let your administrator know.",
+    );
+    SendEmailRequest {
+        to: user.email.clone(),
+        subject: "New sign-in to your Task Manager account".to_string(),
+        body,
+        user_id: Some(user.id),
+    }
+}
+
+/// Sends queued sign-in alerts through the email collector.
+#[derive(Clone)]
+pub struct AlertMailer {
+    client: HttpClient,
+    send_url: String,
+}
+
+impl AlertMailer {
+    pub fn new(config: &ServiceConfig) -> Self {
+        Self {
+            client: HttpClient::for_services(config),
+            send_url: format!("{}/api/v1/email/send", config.service_url("email-collector")),
+        }
+    }
+
+    pub async fn send(&self, job: Job) -> anyhow::Result<()> {
+        let email: SendEmailRequest = serde_json::from_value(job.payload)?;
+        // The collector answers 202 without a body.
+        self.client.post_stream(&self.send_url, &email).await?;
+        info!("Sent a sign-in alert to {}", email.to);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db::{database, user};
+    use common::{auth::INTERNAL_TOKEN_HEADER, secrets::INTERNAL_SERVICE_TOKEN, testing::TEST_INTERNAL_TOKEN, ServiceError};
+    use models::LoginRequest;
+
+    const FIREFOX_LINUX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+    const CHROME_WINDOWS: &str =
+        "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
+    const SAFARI_IPHONE: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1";
+
+    fn client(ip_address: &str, user_agent: &str) -> ClientInfo {
+        ClientInfo { ip_address: Some(ip_address.to_string()), user_agent: Some(user_agent.to_string()) }
+    }
+
+    #[test]
+    fn devices_are_browser_and_system() {
+        assert_eq!(device(FIREFOX_LINUX), "Firefox on Linux");
+        assert_eq!(device(CHROME_WINDOWS), "Chrome on Windows");
+        assert_eq!(device(SAFARI_IPHONE), "Safari on iOS");
+        assert_eq!(device("curl/8.5.0"), "curl");
+        // A browser update is the same device.
+        assert_eq!(device(&CHROME_WINDOWS.replace("126.0.0.0", "127.0.0.0")), device(CHROME_WINDOWS));
+    }
+
+    #[test]
+    fn networks_are_address_prefixes() {
+        assert_eq!(network("203.0.113.57").as_deref(), Some("203.0.113.0/24"));
+        assert_eq!(network("::ffff:203.0.113.57").as_deref(), Some("203.0.113.0/24"));
+        assert_eq!(network("2001:db8:abcd:12::1").as_deref(), Some("2001:db8:abcd::/48"));
+        assert_eq!(network("not an address"), None);
+    }
+
+    #[test]
+    fn sign_ins_are_unusual_from_new_devices_or_networks() {
+        let history = vec![client("203.0.113.57", FIREFOX_LINUX)];
+
+        assert_eq!(assess(&[], &client("198.51.100.1", CHROME_WINDOWS)), None, "first sign-in");
+        assert_eq!(assess(&history, &client("203.0.113.80", FIREFOX_LINUX)), None, "same device and network");
+        assert_eq!(
+            assess(&history, &client("203.0.113.80", CHROME_WINDOWS)),
+            Some(Unusual { new_device: Some("Chrome on Windows".to_string()), new_network: None })
+        );
+        assert_eq!(
+            assess(&history, &client("198.51.100.1", FIREFOX_LINUX)),
+            Some(Unusual { new_device: None, new_network: Some("198.51.100.0/24".to_string()) })
+        );
+        assert_eq!(assess(&history, &ClientInfo::default()), None, "nothing known about the client");
+    }
+
+    #[test]
+    fn clients_reported_by_services_win_over_headers() {
+        let secrets = Secrets::fixed(&[(INTERNAL_SERVICE_TOKEN, TEST_INTERNAL_TOKEN)]);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "10.0.0.1, 198.51.100.7".parse().unwrap());
+        headers.insert("user-agent", FIREFOX_LINUX.parse().unwrap());
+        let from_headers = client("198.51.100.7", FIREFOX_LINUX);
+        let reported = client("203.0.113.57", CHROME_WINDOWS);
+
+        assert_eq!(client_info(ClientInfo::default(), &headers, &secrets), from_headers);
+        assert_eq!(client_info(reported.clone(), &headers, &secrets), from_headers, "anyone could claim a known device");
+        headers.insert(INTERNAL_TOKEN_HEADER, "guessed".parse().unwrap());
+        assert_eq!(client_info(reported.clone(), &headers, &secrets), from_headers);
+
+        headers.insert(INTERNAL_TOKEN_HEADER, TEST_INTERNAL_TOKEN.parse().unwrap());
+        assert_eq!(client_info(reported.clone(), &headers, &secrets), reported);
+    }
+
+    async fn login_alerts(db: &Database, user: &User) -> usize {
+        let jobs = db.list_jobs(None, Some(LOGIN_ALERT_JOB), 10_000).await.unwrap();
+        jobs.iter().filter(|job| job.payload["to"] == user.email.as_str()).count()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn sign_ins_from_new_devices_or_networks_are_alerted() {
+        let db = database().await;
+        let client = |ip_address: &str, user_agent: &str| ClientInfo {
+            ip_address: Some(ip_address.to_string()),
+            user_agent: Some(user_agent.to_string()),
+        };
+        let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+        let chrome = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
+
+        let alice = user(&db).await;
+        record_login(&db, &alice, &client("203.0.113.57", firefox)).await;
+        record_login(&db, &alice, &client("203.0.113.80", firefox)).await;
+        assert_eq!(login_alerts(&db, &alice).await, 0, "first sign-in and a known device and network");
+
+        record_login(&db, &alice, &client("198.51.100.7", chrome)).await;
+        assert_eq!(login_alerts(&db, &alice).await, 1);
+        let since = Utc::now() - chrono::Duration::days(HISTORY_DAYS);
+        assert_eq!(db.recent_logins(alice.id, since).await.unwrap().len(), 3);
+
+        let bob = user(&db).await;
+        record_login(&db, &bob, &client("198.51.100.7", chrome)).await;
+        assert_eq!(login_alerts(&db, &bob).await, 0, "other users' sign-ins don't count");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn login_history_includes_failed_attempts() {
+        let db = database().await;
+        let client = |ip_address: &str| ClientInfo {
+            ip_address: Some(ip_address.to_string()),
+            user_agent: Some("curl/8.5.0".to_string()),
+        };
+
+        let alice = user(&db).await;
+        let attempt = LoginRequest {
+            email: alice.email.clone(),
+            password: "wrong".to_string(),
+            client: client("198.51.100.7"),
+        };
+        assert!(matches!(db.authenticate_user(attempt).await, Err(ServiceError::Unauthorized(_))));
+        record_login(&db, &alice, &client("203.0.113.57")).await;
+
+        let history = db.list_login_events(alice.id, 10).await.unwrap();
+        let outcomes: Vec<_> = history.iter().map(|event| (event.succeeded, event.ip_address.as_deref())).collect();
+        assert_eq!(outcomes, [(true, Some("203.0.113.57")), (false, Some("198.51.100.7"))]);
+        let since = Utc::now() - chrono::Duration::days(HISTORY_DAYS);
+        assert_eq!(db.recent_logins(alice.id, since).await.unwrap().len(), 1, "failed attempts aren't known clients");
+        assert!(db.list_login_events(user(&db).await.id, 10).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,64 @@
+//! Synthetic data for load tests.
+//!
+//! With `SEED_ENABLED=true`, `POST /api/v1/admin/seed` (admin token)
+//! creates `users` users with `cases_per_user` cases of `tasks_per_case`
+//! tasks each, and answers with a session token per user. A load generator
+//! such as k6 or vegeta then replays requests with those tokens:
+//!
+//! ```sh
+//! curl -s -X POST localhost:8005/api/v1/admin/seed -H "X-Admin-Token: $ADMIN_API_TOKEN" \
+//!   -H 'Content-Type: application/json' -d '{"users": 50, "cases_per_user": 20, "tasks_per_case": 10}' \
+//!   | jq -r '.users[].session_token' > tokens.txt
+//! ```
+//!
+//! Seeded users are `seed-<id>@seed.invalid` with a random password, and
+//! their rows are marked `{"seed": true}` in `metadata`. Never enable this
+//! against a database with real users.
+
+use bcrypt::{hash, DEFAULT_COST};
+use common::{ServiceError, ServiceResult};
+use models::{SeedRequest, SeedResponse};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::database_working::Database;
+
+const MAX_USERS: u32 = 1000;
+const MAX_TASKS: u64 = 1_000_000;
+
+/// How long seeded sessions last; long enough for a soak test.
+const SESSION_HOURS: i64 = 24;
+
+pub async fn seed(db: &Database, request: &SeedRequest) -> ServiceResult<SeedResponse> {
+    let tasks = request.users as u64 * request.cases_per_user as u64 * request.tasks_per_case as u64;
+    let mut violations = Vec::new();
+    if request.users == 0 || request.users > MAX_USERS {
+        violations.push(format!("`users` must be between 1 and {}", MAX_USERS));
+    }
+    if tasks > MAX_TASKS {
+        violations.push(format!("at most {} tasks can be seeded at once, not {}", MAX_TASKS, tasks));
+    }
+    if !violations.is_empty() {
+        return Err(ServiceError::Validation(violations));
+    }
+
+    // One password for every seeded user, and never handed out: hashing
+    // is deliberately slow.
+    let password_hash = hash(Uuid::new_v4().to_string(), DEFAULT_COST)
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("Password hashing error: {}", e)))?;
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(SESSION_HOURS);
+
+    let mut users = Vec::with_capacity(request.users as usize);
+    let mut tasks = 0;
+    for _ in 0..request.users {
+        let (user, user_tasks) = db
+            .seed_user(&password_hash, request.cases_per_user, request.tasks_per_case, expires_at)
+            .await?;
+        users.push(user);
+        tasks += user_tasks;
+    }
+
+    let cases = request.users as i64 * request.cases_per_user as i64;
+    info!("Seeded {} users with {} cases and {} tasks", users.len(), cases, tasks);
+    Ok(SeedResponse { users, cases, tasks, expires_at })
+}
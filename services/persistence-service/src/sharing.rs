@@ -0,0 +1,160 @@
+//! Read-only share links for cases.
+//!
+//! `POST /api/v1/cases/:id/share` hands the owner a token of the form
+//! `<case id>.<expiry, unix seconds>.<signature>`, which
+//! `GET /api/v1/shared/:token` accepts without a session. The signature is
+//! an HMAC-SHA256 with `sharing.link_secret` (`SHARE_LINK_SECRET`) over the
+//! case, the expiry and the owner's id; the owner is not in the token
+//! itself, so transferring the case invalidates the links its previous
+//! owner gave out. Links cannot be revoked one by one: rotating the secret
+//! revokes them all. Without a secret, share links are disabled.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use common::{ServiceError, ServiceResult};
+use hmac::{Hmac, Mac};
+use models::{Case, ShareLink};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Lifetime of a link when the request does not give one.
+const DEFAULT_HOURS: i64 = 7 * 24;
+const MAX_HOURS: i64 = 30 * 24;
+
+#[derive(Clone)]
+pub struct ShareLinks {
+    secret: Option<String>,
+}
+
+impl ShareLinks {
+    pub fn new(secret: Option<String>) -> Self {
+        Self { secret }
+    }
+
+    /// A link to `case` that works for `hours`, a week by default.
+    pub fn issue(&self, case: &Case, hours: Option<i64>) -> ServiceResult<ShareLink> {
+        let hours = hours.unwrap_or(DEFAULT_HOURS);
+        if !(1..=MAX_HOURS).contains(&hours) {
+            return Err(ServiceError::Validation(vec![format!(
+                "`expires_in_hours` must be between 1 and {}",
+                MAX_HOURS
+            )]));
+        }
+        let secret = self.secret()?;
+        // Whole seconds, as the token carries them.
+        let expires_at = DateTime::from_timestamp((Utc::now() + Duration::hours(hours)).timestamp(), 0)
+            .expect("a timestamp within 30 days is valid");
+        let signature = sign(secret, case.id, expires_at, case.user_id);
+        let token = format!("{}.{}.{}", case.id.simple(), expires_at.timestamp(), signature);
+        Ok(ShareLink { case_id: case.id, token, expires_at })
+    }
+
+    /// The case and expiry a token claims, before its signature is checked
+    /// against the case owner with [`verify`](Self::verify). `None` for
+    /// anything that is not a live token.
+    pub fn claims(&self, token: &str, now: DateTime<Utc>) -> Option<(Uuid, DateTime<Utc>)> {
+        let mut parts = token.split('.');
+        let case_id = Uuid::parse_str(parts.next()?).ok()?;
+        let expires_at = DateTime::from_timestamp(parts.next()?.parse().ok()?, 0)?;
+        (expires_at > now).then_some((case_id, expires_at))
+    }
+
+    /// Whether `token` was issued for `case` while `owner` owned it.
+    pub fn verify(&self, token: &str, case_id: Uuid, expires_at: DateTime<Utc>, owner: Uuid) -> ServiceResult<bool> {
+        let secret = self.secret()?;
+        let Some(signature) = token.rsplit('.').next().and_then(|signature| URL_SAFE_NO_PAD.decode(signature).ok()) else {
+            return Ok(false);
+        };
+        Ok(mac(secret, case_id, expires_at, owner).verify_slice(&signature).is_ok())
+    }
+
+    fn secret(&self) -> ServiceResult<&str> {
+        self.secret
+            .as_deref()
+            .ok_or_else(|| ServiceError::BadRequest("Share links are disabled; set SHARE_LINK_SECRET".to_string()))
+    }
+}
+
+fn mac(secret: &str, case_id: Uuid, expires_at: DateTime<Utc>, owner: Uuid) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(format!("{}.{}.{}", case_id, expires_at.timestamp(), owner).as_bytes());
+    mac
+}
+
+fn sign(secret: &str, case_id: Uuid, expires_at: DateTime<Utc>, owner: Uuid) -> String {
+    URL_SAFE_NO_PAD.encode(mac(secret, case_id, expires_at, owner).finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShareLinks;
+    use chrono::{Duration, Utc};
+    use models::{Case, CaseStatus, Priority};
+    use uuid::Uuid;
+
+    fn case() -> Case {
+        let now = Utc::now();
+        Case {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            title: "Project kickoff".to_string(),
+            description: None,
+            status: CaseStatus::Open,
+            priority: Priority::Medium,
+            created_at: now,
+            updated_at: now,
+            assigned_to: None,
+            metadata: serde_json::json!({}),
+            tags: Vec::new(),
+            unread_count: None,
+            first_response_at: None,
+            resolved_at: None,
+            sla: None,
+        }
+    }
+
+    fn links() -> ShareLinks {
+        ShareLinks::new(Some("share-secret".to_string()))
+    }
+
+    #[test]
+    fn issued_link_opens_its_case() {
+        let case = case();
+        let link = links().issue(&case, None).unwrap();
+        let (case_id, expires_at) = links().claims(&link.token, Utc::now()).expect("live token");
+        assert_eq!((case_id, expires_at), (case.id, link.expires_at));
+        assert!(links().verify(&link.token, case_id, expires_at, case.user_id).unwrap());
+    }
+
+    #[test]
+    fn link_stops_working_after_transfer_or_expiry() {
+        let case = case();
+        let link = links().issue(&case, Some(1)).unwrap();
+        let (case_id, expires_at) = links().claims(&link.token, Utc::now()).unwrap();
+        assert!(!links().verify(&link.token, case_id, expires_at, Uuid::new_v4()).unwrap(), "new owner");
+        assert!(links().claims(&link.token, Utc::now() + Duration::hours(2)).is_none(), "expired");
+    }
+
+    #[test]
+    fn tampered_or_foreign_links_are_rejected() {
+        let case = case();
+        let link = links().issue(&case, Some(1)).unwrap();
+        let (case_id, expires_at) = links().claims(&link.token, Utc::now()).unwrap();
+
+        let later = expires_at + Duration::days(30);
+        let extended = link.token.replacen(&expires_at.timestamp().to_string(), &later.timestamp().to_string(), 1);
+        let (_, claimed) = links().claims(&extended, Utc::now()).unwrap();
+        assert!(!links().verify(&extended, case_id, claimed, case.user_id).unwrap(), "extended expiry");
+
+        let other = ShareLinks::new(Some("another-secret".to_string()));
+        assert!(!other.verify(&link.token, case_id, expires_at, case.user_id).unwrap(), "other secret");
+        assert!(links().claims("not-a-token", Utc::now()).is_none());
+    }
+
+    #[test]
+    fn links_need_a_secret_and_a_sane_lifetime() {
+        assert!(ShareLinks::new(None).issue(&case(), None).is_err());
+        assert!(links().issue(&case(), Some(0)).is_err());
+        assert!(links().issue(&case(), Some(31 * 24)).is_err());
+    }
+}
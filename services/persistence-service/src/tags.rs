@@ -0,0 +1,32 @@
+use std::collections::BTreeSet;
+
+/// Longest tag kept, in characters.
+const MAX_TAG_LEN: usize = 32;
+/// Most tags kept on a single task or case.
+const MAX_TAGS: usize = 20;
+
+/// Canonical form of a tag: lowercase with runs of whitespace turned into
+/// dashes, so `Q3 Planning` and `q3-planning` are the same tag. Returns
+/// `None` for blank input.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag: String = tag
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+        .chars()
+        .take(MAX_TAG_LEN)
+        .collect();
+    (!tag.is_empty()).then_some(tag)
+}
+
+/// Normalizes, deduplicates and sorts a tag set, keeping at most
+/// [`MAX_TAGS`] tags.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    tags.iter()
+        .filter_map(|tag| normalize_tag(tag))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .take(MAX_TAGS)
+        .collect()
+}
@@ -0,0 +1,120 @@
+//! Helpers for tests against the database named by `TEST_DATABASE_URL`.
+//! Those tests are `#[ignore]`d, so they are reported as skipped rather
+//! than passing without a database; run them with
+//! `cargo test -p persistence-service -- --include-ignored`.
+
+use crate::database_working::Database;
+use chrono::Utc;
+use common::ServiceError;
+use models::{Case, CaseStatus, Priority, RegisterRequest, Task, TaskStatus, TaskType, UpdateTaskRequest, User};
+use uuid::Uuid;
+
+/// Migrations are not safe to run concurrently, so only the first test
+/// runs them.
+static MIGRATED: tokio::sync::Mutex<bool> = tokio::sync::Mutex::const_new(false);
+
+/// The test database, migrated. Panics without `TEST_DATABASE_URL`.
+pub async fn database() -> Database {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL names the test database");
+    let db = Database::new(&url).await.expect("connect to TEST_DATABASE_URL");
+    let mut migrated = MIGRATED.lock().await;
+    if !*migrated {
+        db.migrate().await.expect("migrate");
+        *migrated = true;
+    }
+    db
+}
+
+pub async fn user(db: &Database) -> User {
+    db.create_user(RegisterRequest {
+        email: format!("{}@isolation.test", Uuid::new_v4()),
+        password: "correct horse battery staple".to_string(),
+        full_name: "Isolation Test".to_string(),
+        organization: None,
+    })
+    .await
+    .expect("create user")
+}
+
+/// A user naming `organization` in their profile, which doesn't make them
+/// a member; see [`added_to`].
+pub async fn member_of(db: &Database, organization: &str) -> User {
+    db.create_user(RegisterRequest {
+        email: format!("{}@isolation.test", Uuid::new_v4()),
+        password: "correct horse battery staple".to_string(),
+        full_name: "Isolation Test".to_string(),
+        organization: Some(organization.to_string()),
+    })
+    .await
+    .expect("create user")
+}
+
+/// A user an administrator added to `organization`.
+pub async fn added_to(db: &Database, organization: &str) -> User {
+    let user = user(db).await;
+    db.add_organization_member(organization, user.id).await.expect("add organization member");
+    user
+}
+
+pub async fn case_of(db: &Database, owner: &User) -> Case {
+    let now = Utc::now();
+    db.create_case(Case {
+        id: Uuid::new_v4(),
+        user_id: owner.id,
+        title: "Quarterly taxes".to_string(),
+        description: None,
+        status: CaseStatus::Open,
+        priority: Priority::Medium,
+        created_at: now,
+        updated_at: now,
+        assigned_to: None,
+        metadata: serde_json::json!({}),
+        tags: vec!["finance".to_string()],
+        unread_count: None,
+        first_response_at: None,
+        resolved_at: None,
+        sla: None,
+    })
+    .await
+    .expect("create case")
+}
+
+pub async fn task_of(db: &Database, case: &Case) -> Task {
+    let now = Utc::now();
+    db.create_task(Task {
+        id: Uuid::new_v4(),
+        user_id: case.user_id,
+        case_id: case.id,
+        title: "Send receipts to the accountant".to_string(),
+        description: None,
+        task_type: TaskType::Communication,
+        status: TaskStatus::Pending,
+        priority: Priority::Medium,
+        due_date: None,
+        created_at: now,
+        updated_at: now,
+        completed_at: None,
+        metadata: serde_json::json!({}),
+        tags: Vec::new(),
+        estimate_minutes: None,
+    })
+    .await
+    .expect("create task")
+}
+
+pub fn task_update() -> UpdateTaskRequest {
+    UpdateTaskRequest {
+        title: Some("Taken over".to_string()),
+        description: None,
+        status: Some(TaskStatus::Cancelled),
+        priority: None,
+        due_date: None,
+        tags: None,
+        metadata: None,
+        estimate_minutes: None,
+    }
+}
+
+pub fn assert_not_found<T: std::fmt::Debug>(result: common::ServiceResult<T>) {
+    assert!(matches!(result, Err(ServiceError::NotFound(_))), "expected NotFound, got {:?}", result);
+}
@@ -0,0 +1,262 @@
+//! Priority auto-aging.
+//!
+//! Open tasks move up one priority level when they are due soon, two once
+//! they are overdue and three once they have been overdue for a while, so
+//! an old Medium task eventually outranks a stream of new High ones. Levels
+//! are counted from the priority the task had before aging first touched
+//! it, kept in `metadata.priority_aging.original`, so repeated sweeps don't
+//! keep raising it. Priorities are only ever raised, and every change is
+//! recorded in the case's conversation history.
+
+use chrono::{DateTime, Duration, Utc};
+use common::{http_client::HttpClient, ServiceError, ServiceResult};
+use models::{ConversationEntry, MessageSender, Priority, Task, TaskStatus, UpdateTaskRequest};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::AppState;
+
+/// Thresholds that decide how far a task's priority is raised.
+#[derive(Debug, Clone)]
+pub struct AgingRule {
+    /// Tasks due within this window are raised one level.
+    pub due_soon: Duration,
+    /// Tasks overdue by more than this are raised three levels; overdue
+    /// tasks are otherwise raised two.
+    pub long_overdue: Duration,
+}
+
+/// A priority change the rule calls for.
+#[derive(Debug)]
+pub struct Escalation {
+    pub original: Priority,
+    pub to: Priority,
+    pub reason: &'static str,
+}
+
+impl AgingRule {
+    pub fn escalation(&self, task: &Task, now: DateTime<Utc>) -> Option<Escalation> {
+        if !matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress | TaskStatus::OnHold) {
+            return None;
+        }
+        let due = task.due_date?;
+
+        let (steps, reason) = if due + self.long_overdue < now {
+            (3, "long overdue")
+        } else if due < now {
+            (2, "overdue")
+        } else if due < now + self.due_soon {
+            (1, "due soon")
+        } else {
+            return None;
+        };
+
+        let original = original_priority(task).unwrap_or_else(|| task.priority.clone());
+        let to = from_level((level(&original) + steps).min(level(&Priority::Critical)));
+        (level(&to) > level(&task.priority)).then_some(Escalation { original, to, reason })
+    }
+}
+
+/// Runs the rule over every task once; run by the `tasks.aging` job.
+pub async fn sweep(state: &AppState, rule: &AgingRule) -> ServiceResult<()> {
+    let persistence = state.config.service_url("persistence");
+    // Internal call without a session, so tasks of every user are returned.
+    let tasks = state
+        .http_client
+        .get::<Vec<Task>>(&format!("{}/api/v1/tasks", persistence))
+        .await
+        .map_err(ServiceError::HttpClient)?;
+
+    let now = Utc::now();
+    let mut raised = 0;
+    for task in tasks {
+        let Some(escalation) = rule.escalation(&task, now) else {
+            continue;
+        };
+        match apply(&state.http_client, &persistence, &task, &escalation, now).await {
+            Ok(()) => raised += 1,
+            Err(e) => warn!("Failed to raise priority of task {}: {}", task.id, e),
+        }
+    }
+    if raised > 0 {
+        info!("Raised the priority of {} tasks", raised);
+    }
+    Ok(())
+}
+
+async fn apply(
+    client: &HttpClient,
+    persistence: &str,
+    task: &Task,
+    escalation: &Escalation,
+    now: DateTime<Utc>,
+) -> ServiceResult<()> {
+    let mut metadata = task.metadata.clone();
+    if !metadata.is_object() {
+        metadata = serde_json::json!({});
+    }
+    metadata["priority_aging"] = serde_json::json!({
+        "original": escalation.original,
+        "raised_at": now,
+    });
+
+    let update = UpdateTaskRequest {
+        title: None,
+        description: None,
+        status: None,
+        priority: Some(escalation.to.clone()),
+        due_date: None,
+        tags: None,
+        metadata: Some(metadata),
+        estimate_minutes: None,
+    };
+    client
+        .put::<UpdateTaskRequest, Task>(&format!("{}/api/v1/tasks/{}", persistence, task.id), &update)
+        .await
+        .map_err(ServiceError::HttpClient)?;
+
+    let entry = ConversationEntry {
+        id: Uuid::new_v4(),
+        user_id: task.user_id,
+        case_id: task.case_id,
+        message: format!(
+            "Priority of task '{}' raised from {:?} to {:?} ({})",
+            task.title, task.priority, escalation.to, escalation.reason
+        ),
+        sender: MessageSender::System,
+        timestamp: now,
+        metadata: serde_json::json!({
+            "event": "priority_aging",
+            "task_id": task.id,
+            "from": task.priority,
+            "to": escalation.to,
+        }),
+    };
+    client
+        .post::<ConversationEntry, ConversationEntry>(
+            &format!("{}/api/v1/cases/{}/history", persistence, task.case_id),
+            &entry,
+        )
+        .await
+        .map_err(ServiceError::HttpClient)?;
+
+    info!("Raised task {} from {:?} to {:?}", task.id, task.priority, escalation.to);
+    Ok(())
+}
+
+fn original_priority(task: &Task) -> Option<Priority> {
+    serde_json::from_value(task.metadata.get("priority_aging")?.get("original")?.clone()).ok()
+}
+
+fn level(priority: &Priority) -> u8 {
+    match priority {
+        Priority::Low => 0,
+        Priority::Medium => 1,
+        Priority::High => 2,
+        Priority::Critical => 3,
+    }
+}
+
+fn from_level(level: u8) -> Priority {
+    match level {
+        0 => Priority::Low,
+        1 => Priority::Medium,
+        2 => Priority::High,
+        _ => Priority::Critical,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule() -> AgingRule {
+        AgingRule { due_soon: Duration::hours(48), long_overdue: Duration::hours(72) }
+    }
+
+    fn task(priority: Priority, due_date: Option<DateTime<Utc>>) -> Task {
+        let now = Utc::now();
+        Task {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            case_id: Uuid::new_v4(),
+            title: "File the tax return".to_string(),
+            description: None,
+            task_type: models::TaskType::Work,
+            status: TaskStatus::Pending,
+            priority,
+            due_date,
+            created_at: now,
+            updated_at: now,
+            completed_at: None,
+            metadata: json!({}),
+            tags: Vec::new(),
+            estimate_minutes: None,
+        }
+    }
+
+    /// The reason and new priority of a Low task due `due` from `now`.
+    fn escalate_low(now: DateTime<Utc>, due: Duration) -> Option<(&'static str, Priority)> {
+        rule().escalation(&task(Priority::Low, Some(now + due)), now).map(|escalation| (escalation.reason, escalation.to))
+    }
+
+    #[test]
+    fn due_soon_starts_strictly_inside_the_window() {
+        let now = Utc::now();
+        assert!(escalate_low(now, Duration::hours(48)).is_none(), "exactly at the edge of the window");
+        assert_eq!(escalate_low(now, Duration::hours(48) - Duration::seconds(1)), Some(("due soon", Priority::Medium)));
+        assert_eq!(escalate_low(now, Duration::zero()), Some(("due soon", Priority::Medium)), "due right now isn't overdue yet");
+    }
+
+    #[test]
+    fn overdue_becomes_long_overdue_strictly_after_the_threshold() {
+        let now = Utc::now();
+        assert_eq!(escalate_low(now, -Duration::seconds(1)), Some(("overdue", Priority::High)));
+        assert_eq!(escalate_low(now, -Duration::hours(72)), Some(("overdue", Priority::High)));
+        assert_eq!(
+            escalate_low(now, -Duration::hours(72) - Duration::seconds(1)),
+            Some(("long overdue", Priority::Critical))
+        );
+    }
+
+    #[test]
+    fn priorities_stop_at_critical_and_never_drop() {
+        let now = Utc::now();
+        let overdue = Some(now - Duration::hours(100));
+        assert!(rule().escalation(&task(Priority::Critical, overdue), now).is_none());
+        assert_eq!(rule().escalation(&task(Priority::High, overdue), now).unwrap().to, Priority::Critical);
+
+        let mut raised_by_hand = task(Priority::Critical, Some(now + Duration::hours(1)));
+        raised_by_hand.metadata = json!({ "priority_aging": { "original": "Low" } });
+        assert!(rule().escalation(&raised_by_hand, now).is_none(), "Medium would lower it");
+    }
+
+    #[test]
+    fn levels_count_from_the_original_priority() {
+        let now = Utc::now();
+        let mut aged = task(Priority::Medium, Some(now - Duration::hours(1)));
+        aged.metadata = json!({ "priority_aging": { "original": "Low", "raised_at": now } });
+        let escalation = rule().escalation(&aged, now).unwrap();
+        assert_eq!((escalation.original, escalation.to), (Priority::Low, Priority::High));
+
+        let mut soon = task(Priority::Medium, Some(now + Duration::hours(1)));
+        soon.metadata = aged.metadata.clone();
+        assert!(rule().escalation(&soon, now).is_none(), "already raised as far as due soon goes");
+    }
+
+    #[test]
+    fn only_open_tasks_with_a_due_date_age() {
+        let now = Utc::now();
+        let overdue = Some(now - Duration::hours(1));
+        assert!(rule().escalation(&task(Priority::Low, None), now).is_none());
+        for status in [TaskStatus::Completed, TaskStatus::Cancelled, TaskStatus::NeedsReview] {
+            let closed = Task { status, ..task(Priority::Low, overdue) };
+            assert!(rule().escalation(&closed, now).is_none());
+        }
+        for status in [TaskStatus::InProgress, TaskStatus::OnHold] {
+            let open = Task { status, ..task(Priority::Low, overdue) };
+            assert!(rule().escalation(&open, now).is_some());
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! A minimal CalDAV (RFC 4791) server for the caller's tasks, so that
+//! clients such as Apple Reminders and Thunderbird can sync them and write
+//! changes back.
+//!
+//! - `/caldav/` is the calendar home and stands in for the principal;
+//!   `/.well-known/caldav` redirects there
+//! - `/caldav/tasks/` is the one VTODO collection, answering PROPFIND and
+//!   the `calendar-query` and `calendar-multiget` REPORTs
+//! - `/caldav/tasks/{id}.ics` is a task, read with GET and updated with
+//!   PUT: title, description, status, priority and due date are taken over
+//!
+//! Tasks belong to a case, so a client can't create tasks in the
+//! collection or delete them from it. Clients sign in with Basic
+//! authentication, with a personal access token as the password.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use common::auth::SessionToken;
+use models::{Task, UpdateTaskRequest};
+use uuid::Uuid;
+
+use crate::ical;
+
+pub const HOME: &str = "/caldav/";
+pub const COLLECTION: &str = "/caldav/tasks/";
+
+pub const HOME_METHODS: &str = "OPTIONS, PROPFIND";
+pub const COLLECTION_METHODS: &str = "OPTIONS, PROPFIND, REPORT";
+pub const TASK_METHODS: &str = "OPTIONS, GET, PUT";
+
+/// The caller's token as a CalDAV client sends it: as the password of
+/// Basic authentication, which is all most clients offer, or as a bearer
+/// token. Without one the request is answered with a Basic challenge, so
+/// that the client asks for credentials.
+#[derive(Debug)]
+pub struct DavSession(pub SessionToken);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for DavSession {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let value = parts.headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+        let token = value.and_then(|value| {
+            if let Some(token) = value.strip_prefix("Bearer ") {
+                return Some(token.trim().to_string());
+            }
+            let credentials = base64::engine::general_purpose::STANDARD
+                .decode(value.strip_prefix("Basic ")?.trim())
+                .ok()?;
+            let credentials = String::from_utf8(credentials).ok()?;
+            credentials.split_once(':').map(|(_, password)| password.to_string())
+        });
+
+        match token.filter(|token| !token.is_empty()) {
+            Some(token) => Ok(DavSession(SessionToken(Some(token)))),
+            None => Err((
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Basic realm=\"Tasks\", charset=\"UTF-8\"")],
+            )
+                .into_response()),
+        }
+    }
+}
+
+/// The href of a task in the collection.
+pub fn task_href(id: Uuid) -> String {
+    format!("{}{}.ics", COLLECTION, id)
+}
+
+/// The task an href or resource name, `{id}.ics`, points at, if any.
+pub fn task_id(href: &str) -> Option<Uuid> {
+    let resource = href.trim_end_matches('/').rsplit('/').next()?;
+    resource.strip_suffix(".ics")?.parse().ok()
+}
+
+/// Changes whenever the task does.
+pub fn etag(task: &Task) -> String {
+    format!("\"{}\"", task.updated_at.timestamp_micros())
+}
+
+/// Changes whenever a task is added, changed or removed, so clients know
+/// when to sync the collection again.
+fn ctag(tasks: &[Task]) -> String {
+    let latest = tasks.iter().map(|task| task.updated_at.timestamp_micros()).max().unwrap_or_default();
+    format!("{}-{}", tasks.len(), latest)
+}
+
+/// Whether PROPFIND should cover the members of a collection; clients ask
+/// with `Depth: 1`, and a missing header means infinity.
+pub fn depth_one(headers: &HeaderMap) -> bool {
+    headers.get("depth").and_then(|value| value.to_str().ok()).map(str::trim) != Some("0")
+}
+
+/// The update a VTODO written back by a client makes to `task`: only what
+/// differs is changed. Statuses and priorities are compared as iCalendar
+/// shows them, so that a client writing back an on-hold task as needing
+/// action, or a high priority as 2, leaves them alone.
+pub fn todo_update(todo: &ical::Todo, task: &Task) -> UpdateTaskRequest {
+    let status = todo
+        .status
+        .as_deref()
+        .filter(|status| *status != ical::status_to_ical(&task.status))
+        .and_then(ical::status_from_ical);
+    let priority = todo.priority.and_then(ical::priority_from_ical).filter(|priority| *priority != task.priority);
+
+    UpdateTaskRequest {
+        title: todo.summary.clone().filter(|title| !title.trim().is_empty() && *title != task.title),
+        description: todo.description.clone().filter(|description| Some(description) != task.description.as_ref()),
+        status,
+        priority,
+        due_date: todo.due.filter(|due| Some(*due) != task.due_date),
+        tags: None,
+        metadata: None,
+        estimate_minutes: None,
+    }
+}
+
+/// The PROPFIND responses of the calendar home.
+pub fn home_responses(depth_one: bool) -> Vec<String> {
+    let mut responses = vec![response(
+        HOME,
+        &format!(
+            "<d:resourcetype><d:collection/><d:principal/></d:resourcetype>\
+             <d:displayname>Tasks</d:displayname>{}",
+            principal_props()
+        ),
+    )];
+    if depth_one {
+        responses.push(response(COLLECTION, &collection_props(None)));
+    }
+    responses
+}
+
+/// The PROPFIND responses of the task collection, with every task's ETag
+/// at depth one.
+pub fn collection_responses(tasks: &[Task], depth_one: bool) -> Vec<String> {
+    let mut responses = vec![response(COLLECTION, &collection_props(Some(tasks)))];
+    if depth_one {
+        responses.extend(tasks.iter().map(|task| {
+            let props = format!(
+                "<d:resourcetype/><d:getetag>{}</d:getetag>\
+                 <d:getcontenttype>text/calendar; charset=utf-8; component=VTODO</d:getcontenttype>",
+                escape_xml(&etag(task))
+            );
+            response(&task_href(task.id), &props)
+        }));
+    }
+    responses
+}
+
+/// The REPORT responses for `body`: the tasks a `calendar-multiget` names,
+/// with a 404 for hrefs that aren't the caller's tasks, or every task for
+/// a `calendar-query`, except one asking only for events.
+pub fn report_responses(tasks: &[Task], body: &str) -> Vec<String> {
+    let calendar_data = |task: &Task| {
+        let props = format!(
+            "<d:getetag>{}</d:getetag><c:calendar-data>{}</c:calendar-data>",
+            escape_xml(&etag(task)),
+            escape_xml(&ical::render_calendar("Tasks", std::slice::from_ref(task)))
+        );
+        response(&task_href(task.id), &props)
+    };
+
+    if body.contains("calendar-multiget") {
+        return hrefs(body)
+            .into_iter()
+            .map(|href| match task_id(&href).and_then(|id| tasks.iter().find(|task| task.id == id)) {
+                Some(task) => calendar_data(task),
+                None => format!(
+                    "<d:response><d:href>{}</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>",
+                    escape_xml(&href)
+                ),
+            })
+            .collect();
+    }
+    if body.contains("\"VEVENT\"") && !body.contains("\"VTODO\"") {
+        return Vec::new();
+    }
+    tasks.iter().map(calendar_data).collect()
+}
+
+/// A 207 Multi-Status response holding `responses`.
+pub fn multistatus(responses: Vec<String>) -> Response {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\" \
+         xmlns:cs=\"http://calendarserver.org/ns/\">{}</d:multistatus>",
+        responses.concat()
+    );
+    (
+        StatusCode::MULTI_STATUS,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
+
+/// Answers OPTIONS, announcing CalDAV support.
+pub fn options(methods: &'static str) -> Response {
+    (
+        StatusCode::OK,
+        [
+            (header::ALLOW, HeaderValue::from_static(methods)),
+            (header::HeaderName::from_static("dav"), HeaderValue::from_static("1, calendar-access")),
+        ],
+    )
+        .into_response()
+}
+
+pub fn method_not_allowed(methods: &'static str) -> Response {
+    (StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, methods)]).into_response()
+}
+
+/// The answer to a PUT that would create a task, or to a task the caller
+/// doesn't have.
+pub fn creation_refused() -> Response {
+    (StatusCode::FORBIDDEN, "Tasks belong to a case and can't be created over CalDAV").into_response()
+}
+
+fn principal_props() -> String {
+    format!(
+        "<d:current-user-principal><d:href>{0}</d:href></d:current-user-principal>\
+         <c:calendar-home-set><d:href>{0}</d:href></c:calendar-home-set>",
+        HOME
+    )
+}
+
+fn collection_props(tasks: Option<&[Task]>) -> String {
+    let ctag = tasks.map(|tasks| format!("<cs:getctag>{}</cs:getctag>", escape_xml(&ctag(tasks))));
+    format!(
+        "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+         <d:displayname>Tasks</d:displayname>\
+         <c:supported-calendar-component-set><c:comp name=\"VTODO\"/></c:supported-calendar-component-set>\
+         <d:supported-report-set>\
+         <d:supported-report><d:report><c:calendar-query/></d:report></d:supported-report>\
+         <d:supported-report><d:report><c:calendar-multiget/></d:report></d:supported-report>\
+         </d:supported-report-set>\
+         <d:current-user-privilege-set>\
+         <d:privilege><d:read/></d:privilege><d:privilege><d:write-content/></d:privilege>\
+         </d:current-user-privilege-set>{}{}",
+        ctag.unwrap_or_default(),
+        principal_props()
+    )
+}
+
+fn response(href: &str, props: &str) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>{}</d:prop>\
+         <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        escape_xml(href),
+        props
+    )
+}
+
+/// The text of every `href` element in a request body, whatever prefix
+/// the client binds the DAV namespace to.
+fn hrefs(body: &str) -> Vec<String> {
+    body.split('<')
+        .filter_map(|tag| {
+            let (name, text) = tag.split_once('>')?;
+            let name = name.split_whitespace().next()?;
+            let opening = name == "href" || (name.ends_with(":href") && !name.starts_with('/'));
+            opening.then(|| text.trim().to_string())
+        })
+        .filter(|href| !href.is_empty())
+        .collect()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use models::{Priority, TaskStatus, TaskType};
+
+    fn task() -> Task {
+        Task {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            case_id: Uuid::new_v4(),
+            title: "Book movers".to_string(),
+            description: None,
+            task_type: TaskType::Work,
+            status: TaskStatus::OnHold,
+            priority: Priority::High,
+            due_date: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            completed_at: None,
+            metadata: serde_json::json!({}),
+            tags: Vec::new(),
+            estimate_minutes: None,
+        }
+    }
+
+    #[test]
+    fn unchanged_todos_leave_the_task_alone() {
+        let task = task();
+        let todo = ical::Todo {
+            summary: Some(task.title.clone()),
+            status: Some("NEEDS-ACTION".to_string()),
+            priority: Some(2),
+            ..Default::default()
+        };
+        let update = todo_update(&todo, &task);
+        assert!(update.title.is_none() && update.status.is_none() && update.priority.is_none());
+
+        let todo = ical::Todo { status: Some("COMPLETED".to_string()), priority: Some(9), ..todo };
+        let update = todo_update(&todo, &task);
+        assert_eq!((update.status, update.priority), (Some(TaskStatus::Completed), Some(Priority::Low)));
+    }
+
+    #[test]
+    fn multigets_return_the_named_tasks_and_404_for_others() {
+        let (first, second) = (task(), task());
+        let stranger = Uuid::new_v4();
+        let body = format!(
+            "<C:calendar-multiget xmlns:D=\"DAV:\" xmlns:C=\"urn:ietf:params:xml:ns:caldav\">\
+             <D:prop><D:getetag/><C:calendar-data/></D:prop>\
+             <D:href>{}</D:href><D:href>{}</D:href></C:calendar-multiget>",
+            task_href(second.id),
+            task_href(stranger)
+        );
+
+        let responses = report_responses(&[first.clone(), second.clone()], &body);
+        assert_eq!(responses.len(), 2);
+        assert!(responses[0].contains(&second.id.to_string()) && responses[0].contains("BEGIN:VTODO"));
+        assert!(responses[1].contains(&stranger.to_string()) && responses[1].contains("404 Not Found"));
+        assert!(responses.iter().all(|response| !response.contains(&first.id.to_string())));
+    }
+
+    async fn session(authorization: Option<&str>) -> Result<DavSession, Response> {
+        let mut request = axum::http::Request::builder();
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        DavSession::from_request_parts(&mut parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn clients_sign_in_with_a_token_as_password() {
+        let basic = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode("ada:pat-1"));
+        assert_eq!(session(Some(&basic)).await.unwrap().0 .0.as_deref(), Some("pat-1"));
+        assert_eq!(session(Some("Bearer pat-2")).await.unwrap().0 .0.as_deref(), Some("pat-2"));
+
+        let basic_without_password = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode("ada:"));
+        for authorization in [None, Some(basic_without_password.as_str())] {
+            let challenge = session(authorization).await.unwrap_err();
+            assert_eq!(challenge.status(), StatusCode::UNAUTHORIZED);
+            assert!(challenge.headers()[header::WWW_AUTHENTICATE].to_str().unwrap().starts_with("Basic"));
+        }
+    }
+
+    #[test]
+    fn resource_names_lead_to_task_ids() {
+        let id = Uuid::new_v4();
+        assert_eq!(task_id(&task_href(id)), Some(id));
+        assert_eq!(task_id(&format!("{}.ics", id)), Some(id));
+        assert_eq!(task_id("D1A6F0C2-REMINDER.ics"), None);
+        assert_eq!(task_id(COLLECTION), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Feedback on tasks the AI agent extracted.
+//!
+//! Tasks created from a message keep the message and the task as it was
+//! extracted in `metadata.ai_extraction`.  Feedback on such a task is
+//! stored with both, and the export turns it into labeled examples: the
+//! message, what the agent extracted and what it should have extracted.
+
+use chrono::Utc;
+use common::{ServiceError, ServiceResult};
+use models::{CreateTaskRequest, FeedbackVerdict, Task, TaskFeedback, TaskFeedbackRequest};
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Metadata key of the extraction record.
+pub const EXTRACTION_KEY: &str = "ai_extraction";
+
+/// Extraction record for a task created from `message`.
+pub fn extraction(request: &CreateTaskRequest, message: &str) -> Value {
+    json!({
+        "message": message,
+        "task": {
+            "title": request.title,
+            "description": request.description,
+            "task_type": request.task_type,
+            "priority": request.priority,
+            "due_date": request.due_date,
+            "tags": request.tags,
+            "estimate_minutes": request.estimate_minutes,
+        },
+    })
+}
+
+/// The fields of `task` the agent extracts, in the same shape.
+fn task_fields(task: &Task) -> Value {
+    json!({
+        "title": task.title,
+        "description": task.description,
+        "task_type": task.task_type,
+        "priority": task.priority,
+        "due_date": task.due_date,
+        "tags": task.tags,
+        "estimate_minutes": task.estimate_minutes,
+    })
+}
+
+/// Feedback on `task`.  An edit without corrected fields takes the task's
+/// current ones, as the user has usually already fixed the task.
+pub fn build(task: &Task, request: TaskFeedbackRequest) -> ServiceResult<TaskFeedback> {
+    let extraction = task
+        .metadata
+        .get(EXTRACTION_KEY)
+        .ok_or_else(|| ServiceError::BadRequest(format!("Task {} was not created by the AI agent", task.id)))?;
+
+    let corrected = match request.verdict {
+        FeedbackVerdict::Edited => Some(request.corrected.unwrap_or_else(|| task_fields(task))),
+        FeedbackVerdict::Accepted | FeedbackVerdict::Rejected => None,
+    };
+
+    Ok(TaskFeedback {
+        id: Uuid::new_v4(),
+        user_id: task.user_id,
+        task_id: task.id,
+        verdict: request.verdict,
+        message: extraction["message"].as_str().unwrap_or_default().to_string(),
+        extracted: extraction["task"].clone(),
+        corrected,
+        comment: request.comment,
+        created_at: Utc::now(),
+    })
+}
+
+/// A message with the task the agent should extract from it, or `None`
+/// when no task should have been created.
+#[derive(Debug, Serialize)]
+pub struct LabeledExample {
+    pub message: String,
+    pub verdict: FeedbackVerdict,
+    pub extracted: Value,
+    pub expected: Option<Value>,
+    pub comment: Option<String>,
+}
+
+impl From<TaskFeedback> for LabeledExample {
+    fn from(feedback: TaskFeedback) -> Self {
+        let expected = match feedback.verdict {
+            FeedbackVerdict::Accepted => Some(feedback.extracted.clone()),
+            FeedbackVerdict::Edited => feedback.corrected,
+            FeedbackVerdict::Rejected => None,
+        };
+        Self {
+            message: feedback.message,
+            verdict: feedback.verdict,
+            extracted: feedback.extracted,
+            expected,
+            comment: feedback.comment,
+        }
+    }
+}
+
+/// Labeled examples as JSON Lines, one example per line.
+pub fn to_jsonl(feedback: Vec<TaskFeedback>) -> ServiceResult<String> {
+    let mut body = String::new();
+    for example in feedback.into_iter().map(LabeledExample::from) {
+        body.push_str(&serde_json::to_string(&example).map_err(ServiceError::Serialization)?);
+        body.push('\n');
+    }
+    Ok(body)
+}
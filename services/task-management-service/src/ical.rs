@@ -0,0 +1,326 @@
+//! iCalendar (RFC 5545) rendering of tasks as VTODO components, and
+//! parsing of the VTODOs CalDAV clients write back.
+//!
+//! The `.ics` feeds are read-only: clients that subscribe to a calendar
+//! URL show the tasks, but changes made there are not synced back. Clients
+//! that should write back use the CalDAV collection in [`crate::caldav`].
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use common::{ServiceError, ServiceResult};
+use models::{Priority, Task, TaskStatus, TaskType};
+
+const PRODID: &str = "-//Task Manager//Task Management Service//EN";
+
+/// Renders a full VCALENDAR containing one VTODO per task.
+pub fn render_calendar(calendar_name: &str, tasks: &[Task]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(calendar_name)),
+    ];
+
+    let now = Utc::now();
+    for task in tasks {
+        lines.extend(render_todo(task, now));
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut output = String::new();
+    for line in lines {
+        output.push_str(&fold_line(&line));
+        output.push_str("\r\n");
+    }
+    output
+}
+
+fn render_todo(task: &Task, dtstamp: DateTime<Utc>) -> Vec<String> {
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", task.id),
+        format!("DTSTAMP:{}", format_timestamp(dtstamp)),
+        format!("CREATED:{}", format_timestamp(task.created_at)),
+        format!("LAST-MODIFIED:{}", format_timestamp(task.updated_at)),
+        format!("SUMMARY:{}", escape_text(&task.title)),
+        format!("STATUS:{}", status_to_ical(&task.status)),
+        format!("PRIORITY:{}", priority_to_ical(&task.priority)),
+        format!("CATEGORIES:{}", escape_text(&task_type_label(&task.task_type))),
+        format!("RELATED-TO:{}", task.case_id),
+    ];
+
+    if let Some(description) = &task.description {
+        lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+    }
+    if let Some(due_date) = task.due_date {
+        lines.push(format!("DUE:{}", format_timestamp(due_date)));
+    }
+    if let Some(completed_at) = task.completed_at {
+        lines.push(format!("COMPLETED:{}", format_timestamp(completed_at)));
+        lines.push("PERCENT-COMPLETE:100".to_string());
+    }
+
+    lines.push("END:VTODO".to_string());
+    lines
+}
+
+/// Maps a task status onto the VTODO STATUS values defined by RFC 5545.
+/// On-hold tasks and tasks awaiting review have no direct equivalent and are
+/// reported as needing action.
+pub fn status_to_ical(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Pending | TaskStatus::OnHold | TaskStatus::NeedsReview => "NEEDS-ACTION",
+        TaskStatus::InProgress => "IN-PROCESS",
+        TaskStatus::Completed => "COMPLETED",
+        TaskStatus::Cancelled => "CANCELLED",
+    }
+}
+
+/// The task status of a VTODO STATUS value; needing action is pending.
+pub fn status_from_ical(status: &str) -> Option<TaskStatus> {
+    match status.to_ascii_uppercase().as_str() {
+        "NEEDS-ACTION" => Some(TaskStatus::Pending),
+        "IN-PROCESS" => Some(TaskStatus::InProgress),
+        "COMPLETED" => Some(TaskStatus::Completed),
+        "CANCELLED" => Some(TaskStatus::Cancelled),
+        _ => None,
+    }
+}
+
+/// Maps priority onto the 1 (highest) to 9 (lowest) iCalendar scale, using
+/// the high/medium/low buckets most clients display.
+fn priority_to_ical(priority: &Priority) -> u8 {
+    match priority {
+        Priority::Critical => 1,
+        Priority::High => 3,
+        Priority::Medium => 5,
+        Priority::Low => 9,
+    }
+}
+
+/// The priority of an iCalendar PRIORITY value, by the same buckets as
+/// [`priority_to_ical`]; 0 leaves the priority undefined.
+pub fn priority_from_ical(priority: u8) -> Option<Priority> {
+    match priority {
+        1 => Some(Priority::Critical),
+        2..=4 => Some(Priority::High),
+        5 => Some(Priority::Medium),
+        6..=9 => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+/// What a task takes over from a VTODO a client writes back. Properties
+/// the client left out are `None`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Todo {
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub status: Option<String>,
+    pub priority: Option<u8>,
+    pub due: Option<DateTime<Utc>>,
+}
+
+/// Reads the first VTODO of an iCalendar object, skipping the properties
+/// of components nested in it, such as alarms. Floating and zoned due
+/// times are taken as UTC, and due dates as midnight UTC.
+pub fn parse_todo(calendar: &str) -> ServiceResult<Todo> {
+    let invalid = |reason: &str| ServiceError::BadRequest(format!("Invalid VTODO: {}", reason));
+    let unfolded = calendar.replace("\r\n ", "").replace("\r\n\t", "").replace("\n ", "").replace("\n\t", "");
+
+    let mut todo = None;
+    let mut depth = 0;
+    for line in unfolded.lines().map(|line| line.trim_end_matches('\r')) {
+        let Some((name, value)) = content_line(line) else {
+            continue;
+        };
+        match (name.as_str(), value.to_ascii_uppercase().as_str()) {
+            ("BEGIN", "VTODO") if todo.is_none() => {
+                todo = Some(Todo::default());
+                depth = 1;
+                continue;
+            }
+            ("BEGIN", _) if depth > 0 => depth += 1,
+            ("END", _) if depth > 0 => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            if todo.is_some() {
+                break;
+            }
+            continue;
+        }
+        let Some(todo) = todo.as_mut().filter(|_| depth == 1) else {
+            continue;
+        };
+        match name.as_str() {
+            "SUMMARY" => todo.summary = Some(unescape_text(value)),
+            "DESCRIPTION" => todo.description = Some(unescape_text(value)),
+            "STATUS" => todo.status = Some(value.to_ascii_uppercase()),
+            "PRIORITY" => todo.priority = Some(value.trim().parse().map_err(|_| invalid("PRIORITY is not a number"))?),
+            "DUE" => todo.due = Some(parse_timestamp(value).ok_or_else(|| invalid("DUE is not a date or time"))?),
+            _ => {}
+        }
+    }
+
+    todo.ok_or_else(|| invalid("no VTODO component"))
+}
+
+/// Splits a content line into its upper-cased name, without parameters,
+/// and its value, which starts at the first colon outside a quoted
+/// parameter.
+fn content_line(line: &str) -> Option<(String, &str)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(index, ch)| {
+        match ch {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some(index),
+            _ => {}
+        }
+        None
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let name = head.split(';').next().unwrap_or(head);
+    Some((name.to_ascii_uppercase(), value))
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if !value.contains('T') {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some(date.and_hms_opt(0, 0, 0)?.and_utc());
+    }
+    NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+fn task_type_label(task_type: &TaskType) -> String {
+    match task_type {
+        TaskType::Other(label) => label.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => text.push('\n'),
+                Some(escaped) => text.push(escaped),
+                None => text.push('\\'),
+            },
+            ch => text.push(ch),
+        }
+    }
+    text
+}
+
+/// Folds content lines longer than 75 octets as required by RFC 5545,
+/// taking care not to split multi-byte characters.
+fn fold_line(line: &str) -> String {
+    const LIMIT: usize = 75;
+    if line.len() <= LIMIT {
+        return line.to_string();
+    }
+
+    let mut folded = String::with_capacity(line.len() + line.len() / LIMIT * 3);
+    let mut current_len = 0;
+    for ch in line.chars() {
+        let ch_len = ch.len_utf8();
+        if current_len + ch_len > LIMIT {
+            folded.push_str("\r\n ");
+            // The leading space of a continuation line counts towards its length.
+            current_len = 1;
+        }
+        folded.push(ch);
+        current_len += ch_len;
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_escapes_what_rfc_5545_reserves() {
+        assert_eq!(escape_text("Plain title"), "Plain title");
+        assert_eq!(escape_text(r"a\b;c,d"), r"a\\b\;c\,d");
+        assert_eq!(escape_text("one\r\ntwo\nthree"), r"one\ntwo\nthree");
+        assert_eq!(escape_text(r"\n"), r"\\n", "a literal backslash-n stays literal");
+    }
+
+    #[test]
+    fn written_back_todos_are_read_without_their_alarms() {
+        let calendar = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:1\r\n\
+            SUMMARY:Book movers\\, then\r\n  pack\r\nDESCRIPTION:Two vans\\nmaybe three\r\n\
+            STATUS:completed\r\nPRIORITY:1\r\nDUE;VALUE=DATE:20261020\r\n\
+            BEGIN:VALARM\r\nDESCRIPTION:Reminder\r\nEND:VALARM\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+
+        let todo = parse_todo(calendar).unwrap();
+        assert_eq!(todo.summary.as_deref(), Some("Book movers, then pack"));
+        assert_eq!(todo.description.as_deref(), Some("Two vans\nmaybe three"));
+        assert_eq!((todo.status.as_deref(), todo.priority), (Some("COMPLETED"), Some(1)));
+        assert_eq!(todo.due.unwrap().to_rfc3339(), "2026-10-20T00:00:00+00:00");
+
+        let due = parse_todo("BEGIN:VTODO\nDUE;TZID=\"Europe/Berlin\":20261020T093000\nEND:VTODO\n").unwrap().due;
+        assert_eq!(due.unwrap().to_rfc3339(), "2026-10-20T09:30:00+00:00");
+        assert!(parse_todo("BEGIN:VCALENDAR\nBEGIN:VEVENT\nEND:VEVENT\nEND:VCALENDAR\n").is_err());
+        assert!(parse_todo("BEGIN:VTODO\nPRIORITY:high\nEND:VTODO\n").is_err());
+    }
+
+    #[test]
+    fn statuses_and_priorities_survive_a_round_trip() {
+        for status in [TaskStatus::Pending, TaskStatus::InProgress, TaskStatus::Completed, TaskStatus::Cancelled] {
+            assert_eq!(status_from_ical(status_to_ical(&status)), Some(status));
+        }
+        for priority in [Priority::Critical, Priority::High, Priority::Medium, Priority::Low] {
+            assert_eq!(priority_from_ical(priority_to_ical(&priority)), Some(priority));
+        }
+        assert_eq!(priority_from_ical(0), None);
+    }
+
+    #[test]
+    fn short_lines_are_not_folded() {
+        let line = "x".repeat(75);
+        assert_eq!(fold_line(&line), line);
+    }
+
+    #[test]
+    fn long_lines_fold_at_75_octets() {
+        let line = format!("SUMMARY:{}", "x".repeat(200));
+        let folded = fold_line(&line);
+        let parts: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|part| part.len() <= 75));
+        assert_eq!(parts[0].len(), 75);
+        assert!(parts[1..].iter().all(|part| part.starts_with(' ')));
+        assert_eq!(folded.replace("\r\n ", ""), line, "unfolding restores the line");
+    }
+
+    #[test]
+    fn folding_keeps_multibyte_characters_whole() {
+        let line = format!("SUMMARY:{}", "é".repeat(80));
+        let folded = fold_line(&line);
+        assert!(folded.split("\r\n").all(|part| part.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+        assert_eq!(folded.split("\r\n").next().unwrap().len(), 74, "a split character moves to the next line");
+    }
+}
@@ -0,0 +1,456 @@
+//! Parsers that turn exports from other task tools into an [`ImportPlan`].
+//!
+//! Parsing is side-effect free so the same plan can be shown as a dry-run
+//! preview or executed against the case and persistence services.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use models::{Priority, TaskStatus, TaskType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportSource {
+    Csv,
+    Todoist,
+    Trello,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    pub source: ImportSource,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportPlan {
+    pub cases: Vec<PlannedCase>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedCase {
+    pub title: String,
+    pub tasks: Vec<PlannedTask>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedTask {
+    pub title: String,
+    pub description: Option<String>,
+    pub task_type: TaskType,
+    pub status: TaskStatus,
+    pub priority: Priority,
+    pub due_date: Option<DateTime<Utc>>,
+}
+
+/// What an import created. Rows that fail are reported in `failures` and
+/// don't stop the rest; importing the file again creates duplicates of
+/// what was created, so fix the failed rows in a file of their own.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub cases_created: Vec<Uuid>,
+    pub tasks_created: Vec<Uuid>,
+    pub failures: Vec<ImportFailure>,
+    pub warnings: Vec<String>,
+}
+
+/// A task that wasn't imported, with the case it was planned in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportFailure {
+    pub case: String,
+    pub task: String,
+    pub error: String,
+}
+
+impl ImportPlan {
+    pub fn task_count(&self) -> usize {
+        self.cases.iter().map(|c| c.tasks.len()).sum()
+    }
+
+    /// Appends a task to the case with the given title, creating the case
+    /// on first use so source ordering is preserved.
+    fn push_task(&mut self, case_title: &str, task: PlannedTask) {
+        match self.cases.iter_mut().find(|c| c.title == case_title) {
+            Some(case) => case.tasks.push(task),
+            None => self.cases.push(PlannedCase {
+                title: case_title.to_string(),
+                tasks: vec![task],
+            }),
+        }
+    }
+}
+
+pub fn parse(source: ImportSource, body: &str) -> anyhow::Result<ImportPlan> {
+    match source {
+        ImportSource::Csv => parse_csv(body),
+        ImportSource::Todoist => parse_todoist(body),
+        ImportSource::Trello => parse_trello(body),
+    }
+}
+
+const DEFAULT_CASE_TITLE: &str = "Imported tasks";
+
+/// Expects a header row; recognised columns are `title` (required), `case`,
+/// `description`, `type`, `status`, `priority` and `due_date`.
+fn parse_csv(body: &str) -> anyhow::Result<ImportPlan> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body.as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()?
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let title_col = column("title").ok_or_else(|| anyhow::anyhow!("CSV import requires a 'title' column"))?;
+
+    let mut plan = ImportPlan::default();
+    for (line, record) in reader.records().enumerate() {
+        let record = record?;
+        let field = |name: &str| {
+            column(name)
+                .and_then(|idx| record.get(idx))
+                .filter(|v| !v.is_empty())
+        };
+
+        let Some(title) = record.get(title_col).filter(|t| !t.is_empty()) else {
+            plan.warnings.push(format!("Row {}: skipped, empty title", line + 2));
+            continue;
+        };
+
+        let due_date = match field("due_date") {
+            Some(raw) => {
+                let parsed = parse_date(raw);
+                if parsed.is_none() {
+                    plan.warnings.push(format!("Row {}: unrecognised due date '{}'", line + 2, raw));
+                }
+                parsed
+            }
+            None => None,
+        };
+
+        plan.push_task(
+            field("case").unwrap_or(DEFAULT_CASE_TITLE),
+            PlannedTask {
+                title: title.to_string(),
+                description: field("description").map(str::to_string),
+                task_type: field("type").map(parse_task_type).unwrap_or(TaskType::Other("Imported".to_string())),
+                status: field("status").map(parse_status).unwrap_or(TaskStatus::Pending),
+                priority: field("priority").map(parse_priority).unwrap_or(Priority::Medium),
+                due_date,
+            },
+        );
+    }
+
+    Ok(plan)
+}
+
+#[derive(Deserialize)]
+struct TodoistExport {
+    #[serde(default)]
+    projects: Vec<TodoistProject>,
+    items: Vec<TodoistItem>,
+}
+
+#[derive(Deserialize)]
+struct TodoistProject {
+    id: serde_json::Value,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct TodoistItem {
+    content: String,
+    #[serde(default)]
+    description: Option<String>,
+    project_id: Option<serde_json::Value>,
+    /// Todoist uses 1 (normal) to 4 (urgent).
+    #[serde(default)]
+    priority: Option<u8>,
+    due: Option<TodoistDue>,
+    #[serde(default)]
+    checked: bool,
+}
+
+#[derive(Deserialize)]
+struct TodoistDue {
+    date: String,
+}
+
+/// Maps a Todoist sync export: each project becomes a case.
+fn parse_todoist(body: &str) -> anyhow::Result<ImportPlan> {
+    let export: TodoistExport = serde_json::from_str(body)?;
+    let projects: HashMap<String, String> = export
+        .projects
+        .into_iter()
+        .map(|p| (json_id(&p.id), p.name))
+        .collect();
+
+    let mut plan = ImportPlan::default();
+    for item in export.items {
+        let case_title = item
+            .project_id
+            .as_ref()
+            .and_then(|id| projects.get(&json_id(id)))
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_CASE_TITLE)
+            .to_string();
+
+        let priority = match item.priority.unwrap_or(1) {
+            4 => Priority::Critical,
+            3 => Priority::High,
+            2 => Priority::Medium,
+            _ => Priority::Low,
+        };
+
+        let due_date = item.due.as_ref().and_then(|due| {
+            let parsed = parse_date(&due.date);
+            if parsed.is_none() {
+                plan.warnings.push(format!("Task '{}': unrecognised due date '{}'", item.content, due.date));
+            }
+            parsed
+        });
+
+        plan.push_task(
+            &case_title,
+            PlannedTask {
+                title: item.content,
+                description: item.description.filter(|d| !d.is_empty()),
+                task_type: TaskType::Other("Todoist".to_string()),
+                status: if item.checked { TaskStatus::Completed } else { TaskStatus::Pending },
+                priority,
+                due_date,
+            },
+        );
+    }
+
+    Ok(plan)
+}
+
+#[derive(Deserialize)]
+struct TrelloBoard {
+    name: String,
+    #[serde(default)]
+    lists: Vec<TrelloList>,
+    cards: Vec<TrelloCard>,
+}
+
+#[derive(Deserialize)]
+struct TrelloList {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrelloCard {
+    name: String,
+    #[serde(default)]
+    desc: String,
+    id_list: String,
+    due: Option<DateTime<Utc>>,
+    #[serde(default)]
+    due_complete: bool,
+    #[serde(default)]
+    closed: bool,
+}
+
+/// Maps a Trello board JSON export: the board becomes one case and list
+/// names are used to infer each card's status.
+fn parse_trello(body: &str) -> anyhow::Result<ImportPlan> {
+    let board: TrelloBoard = serde_json::from_str(body)?;
+    let lists: HashMap<&str, &str> = board
+        .lists
+        .iter()
+        .map(|l| (l.id.as_str(), l.name.as_str()))
+        .collect();
+
+    let mut plan = ImportPlan::default();
+    for card in &board.cards {
+        if card.closed {
+            plan.warnings.push(format!("Card '{}': skipped, archived in Trello", card.name));
+            continue;
+        }
+
+        let list_name = lists.get(card.id_list.as_str()).copied().unwrap_or("");
+        let status = if card.due_complete {
+            TaskStatus::Completed
+        } else {
+            parse_status(list_name)
+        };
+
+        plan.push_task(
+            &board.name,
+            PlannedTask {
+                title: card.name.clone(),
+                description: Some(card.desc.clone()).filter(|d| !d.is_empty()),
+                task_type: TaskType::Other("Trello".to_string()),
+                status,
+                priority: Priority::Medium,
+                due_date: card.due,
+            },
+        );
+    }
+
+    Ok(plan)
+}
+
+fn json_id(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn parse_date(raw: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+}
+
+fn parse_task_type(raw: &str) -> TaskType {
+    match raw.to_lowercase().as_str() {
+        "meeting" => TaskType::Meeting,
+        "shopping" => TaskType::Shopping,
+        "work" => TaskType::Work,
+        "personal" => TaskType::Personal,
+        "research" => TaskType::Research,
+        "communication" => TaskType::Communication,
+        _ => TaskType::Other(raw.to_string()),
+    }
+}
+
+/// Best-effort status inference shared by CSV values and Trello list names.
+fn parse_status(raw: &str) -> TaskStatus {
+    let raw = raw.to_lowercase();
+    if raw.contains("done") || raw.contains("complete") {
+        TaskStatus::Completed
+    } else if raw.contains("progress") || raw.contains("doing") {
+        TaskStatus::InProgress
+    } else if raw.contains("hold") || raw.contains("blocked") {
+        TaskStatus::OnHold
+    } else if raw.contains("cancel") {
+        TaskStatus::Cancelled
+    } else {
+        TaskStatus::Pending
+    }
+}
+
+fn parse_priority(raw: &str) -> Priority {
+    match raw.to_lowercase().as_str() {
+        "critical" | "urgent" => Priority::Critical,
+        "high" => Priority::High,
+        "low" => Priority::Low,
+        _ => Priority::Medium,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(plan: &ImportPlan) -> Vec<(&str, Vec<&str>)> {
+        plan.cases
+            .iter()
+            .map(|case| (case.title.as_str(), case.tasks.iter().map(|task| task.title.as_str()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn csv_rows_group_into_cases_in_file_order() {
+        let csv = "Title,Case,Description,Type,Status,Priority,Due_Date\n\
+                   Book movers,Office move,Two trucks,work,in progress,urgent,2024-06-01\n\
+                   Water plants,,,,,,\n\
+                   Label boxes,Office move,,Meeting,done,low,2024-06-03T09:30:00Z\n";
+        let plan = parse(ImportSource::Csv, csv).unwrap();
+
+        assert_eq!(
+            titles(&plan),
+            [("Office move", vec!["Book movers", "Label boxes"]), (DEFAULT_CASE_TITLE, vec!["Water plants"])]
+        );
+        let movers = &plan.cases[0].tasks[0];
+        assert_eq!(movers.description.as_deref(), Some("Two trucks"));
+        assert_eq!(movers.task_type, TaskType::Work);
+        assert_eq!((&movers.status, &movers.priority), (&TaskStatus::InProgress, &Priority::Critical));
+        assert_eq!(movers.due_date.unwrap().to_rfc3339(), "2024-06-01T00:00:00+00:00");
+        assert_eq!(plan.cases[0].tasks[1].due_date.unwrap().to_rfc3339(), "2024-06-03T09:30:00+00:00");
+
+        let plants = &plan.cases[1].tasks[0];
+        assert_eq!(plants.task_type, TaskType::Other("Imported".to_string()));
+        assert_eq!((&plants.status, &plants.priority), (&TaskStatus::Pending, &Priority::Medium));
+        assert!(plants.description.is_none());
+        assert!(plan.warnings.is_empty());
+    }
+
+    #[test]
+    fn csv_rows_without_a_title_or_with_bad_dates_are_warned_about() {
+        let plan = parse(ImportSource::Csv, "title,due_date\n,2024-06-01\nCall the bank,next week\n").unwrap();
+        assert_eq!(plan.task_count(), 1);
+        assert!(plan.cases[0].tasks[0].due_date.is_none());
+        assert_eq!(plan.warnings, ["Row 2: skipped, empty title", "Row 3: unrecognised due date 'next week'"]);
+    }
+
+    #[test]
+    fn csv_needs_a_title_column() {
+        assert!(parse(ImportSource::Csv, "name,case\nBook movers,Office move\n").is_err());
+    }
+
+    #[test]
+    fn todoist_projects_become_cases() {
+        let export = r#"{
+            "projects": [{ "id": 2203306141, "name": "Home" }, { "id": "6Jf8VQXxpwv56VQ7", "name": "Work" }],
+            "items": [
+                { "content": "Fix the tap", "project_id": 2203306141, "priority": 4, "due": { "date": "2024-06-01" } },
+                { "content": "Send report", "description": "", "project_id": "6Jf8VQXxpwv56VQ7", "priority": 2, "checked": true },
+                { "content": "Call mum", "project_id": "unknown", "due": { "date": "soon" } }
+            ]
+        }"#;
+        let plan = parse(ImportSource::Todoist, export).unwrap();
+
+        assert_eq!(
+            titles(&plan),
+            [("Home", vec!["Fix the tap"]), ("Work", vec!["Send report"]), (DEFAULT_CASE_TITLE, vec!["Call mum"])]
+        );
+        let tap = &plan.cases[0].tasks[0];
+        assert_eq!((&tap.priority, &tap.status), (&Priority::Critical, &TaskStatus::Pending));
+        assert!(tap.due_date.is_some());
+        let report = &plan.cases[1].tasks[0];
+        assert_eq!((&report.priority, &report.status), (&Priority::Medium, &TaskStatus::Completed));
+        assert!(report.description.is_none(), "empty descriptions are dropped");
+        assert_eq!(plan.cases[2].tasks[0].priority, Priority::Low);
+        assert_eq!(plan.warnings, ["Task 'Call mum': unrecognised due date 'soon'"]);
+    }
+
+    #[test]
+    fn trello_lists_set_the_status_and_archived_cards_are_skipped() {
+        let board = r#"{
+            "name": "Launch",
+            "lists": [{ "id": "l1", "name": "Doing" }, { "id": "l2", "name": "Backlog" }],
+            "cards": [
+                { "name": "Write copy", "desc": "Landing page", "idList": "l1", "due": "2024-06-01T12:00:00.000Z" },
+                { "name": "Pick a date", "idList": "l2", "dueComplete": true },
+                { "name": "Old idea", "idList": "l2", "closed": true }
+            ]
+        }"#;
+        let plan = parse(ImportSource::Trello, board).unwrap();
+
+        assert_eq!(titles(&plan), [("Launch", vec!["Write copy", "Pick a date"])]);
+        let copy = &plan.cases[0].tasks[0];
+        assert_eq!((&copy.status, copy.description.as_deref()), (&TaskStatus::InProgress, Some("Landing page")));
+        assert!(copy.due_date.is_some());
+        assert_eq!(plan.cases[0].tasks[1].status, TaskStatus::Completed);
+        assert_eq!(plan.warnings, ["Card 'Old idea': skipped, archived in Trello"]);
+    }
+
+    #[test]
+    fn malformed_json_exports_are_rejected() {
+        assert!(parse(ImportSource::Todoist, "{\"projects\": []}").is_err());
+        assert!(parse(ImportSource::Trello, "not json").is_err());
+    }
+}
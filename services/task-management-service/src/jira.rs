@@ -0,0 +1,361 @@
+//! Jira sync for Work tasks.
+//!
+//! A Work task gets a Jira issue when it is created, or when it leaves the
+//! review queue. The issue goes to the project mapped from the first of
+//! the task's tags that has one, or to the default project, and its key
+//! and link are kept in the task metadata under `jira`. Jira's webhook
+//! then reports the issue's status transitions, which are applied to the
+//! task: by status name when `JIRA_STATUS_MAP` names it, otherwise by
+//! status category (to do, in progress, done).
+//!
+//! Configured in the `jira` section of the service configuration, or
+//! through the environment:
+//!
+//! - `JIRA_BASE_URL` - the site, e.g. `https://acme.atlassian.net`; sync is
+//!   off without it
+//! - `JIRA_EMAIL` and `JIRA_API_TOKEN` - an Atlassian account and API
+//!   token; a token alone is sent as a bearer token (a Data Center
+//!   personal access token)
+//! - `JIRA_PROJECT` - key of the default project
+//! - `JIRA_PROJECT_MAP` - projects by task tag, e.g. `infra=OPS,web=WEB`
+//! - `JIRA_ISSUE_TYPE` - default `Task`
+//! - `JIRA_STATUS_MAP` - task statuses by Jira status name, e.g.
+//!   `Blocked=OnHold,Won't Do=Cancelled`
+//! - `JIRA_WEBHOOK_SECRET` - required as `?secret=` on the webhook URL;
+//!   the webhook is refused while it is unset
+
+use common::{auth::secrets_match, config::JiraConfig, http_client::HttpClient, ServiceError, ServiceResult};
+use models::{Task, TaskStatus, TaskType, UpdateTaskRequest};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use crate::AppState;
+
+#[derive(Debug, Clone, Default)]
+pub struct Jira {
+    base_url: Option<String>,
+    email: Option<String>,
+    api_token: Option<String>,
+    default_project: Option<String>,
+    /// Projects by normalized task tag, in configuration order.
+    projects: Vec<(String, String)>,
+    issue_type: String,
+    statuses: HashMap<String, TaskStatus>,
+    webhook_secret: Option<String>,
+}
+
+/// The issue of a task, as kept in its metadata.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JiraLink {
+    pub key: String,
+    pub url: String,
+}
+
+/// A Jira webhook event; only what status sync needs is read.
+#[derive(Debug, Deserialize)]
+pub struct Event {
+    #[serde(rename = "webhookEvent", default)]
+    pub webhook_event: String,
+    pub issue: Option<Issue>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Issue {
+    pub key: String,
+    pub fields: IssueFields,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueFields {
+    pub status: Option<IssueStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueStatus {
+    pub name: String,
+    #[serde(rename = "statusCategory")]
+    pub category: Option<StatusCategory>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusCategory {
+    /// `new`, `indeterminate` or `done`.
+    pub key: String,
+}
+
+#[derive(Deserialize)]
+struct CreatedIssue {
+    key: String,
+}
+
+impl Jira {
+    pub fn new(config: &JiraConfig) -> Self {
+        let pairs = |list: &[String]| -> Vec<(String, String)> {
+            list.iter()
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(from, to)| (from.trim().to_string(), to.trim().to_string()))
+                .filter(|(from, to)| !from.is_empty() && !to.is_empty())
+                .collect()
+        };
+        let statuses = pairs(&config.status_map)
+            .into_iter()
+            .filter_map(|(name, status)| match serde_json::from_value(json!(status)) {
+                Ok(status) => Some((name.to_lowercase(), status)),
+                Err(_) => {
+                    warn!("Ignoring Jira status mapping {}={}: not a task status", name, status);
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            base_url: config.base_url.as_ref().map(|url| url.trim_end_matches('/').to_string()),
+            email: config.email.clone(),
+            api_token: config.api_token.clone(),
+            default_project: config.project.clone(),
+            projects: pairs(&config.project_map).into_iter().map(|(tag, project)| (tag.to_lowercase(), project)).collect(),
+            issue_type: config.issue_type.clone(),
+            statuses,
+            webhook_secret: config.webhook_secret.clone(),
+        }
+    }
+
+    /// The project an issue for `task` goes to; `None` when the task isn't
+    /// synced.
+    pub fn project_for(&self, task: &Task) -> Option<&str> {
+        if self.base_url.is_none() || task.task_type != TaskType::Work || task.status == TaskStatus::NeedsReview {
+            return None;
+        }
+        if task.metadata.get("jira").is_some() {
+            return None;
+        }
+        task.tags
+            .iter()
+            .find_map(|tag| self.projects.iter().find(|(mapped, _)| mapped == tag).map(|(_, project)| project.as_str()))
+            .or(self.default_project.as_deref())
+    }
+
+    /// Creates the issue for `task` in `project`.
+    pub async fn create_issue(&self, client: &HttpClient, task: &Task, project: &str) -> anyhow::Result<JiraLink> {
+        let base_url = self.base_url.as_deref().ok_or_else(|| anyhow::anyhow!("Jira is not configured"))?;
+        let mut fields = json!({
+            "project": { "key": project },
+            "summary": task.title,
+            "issuetype": { "name": self.issue_type },
+            "description": task.description.clone().unwrap_or_default(),
+        });
+        if let Some(due) = task.due_date {
+            fields["duedate"] = json!(due.format("%Y-%m-%d").to_string());
+        }
+
+        let created: CreatedIssue = self
+            .client(client)
+            .post(&format!("{}/rest/api/2/issue", base_url), &json!({ "fields": fields }))
+            .await?;
+        Ok(JiraLink { url: format!("{}/browse/{}", base_url, created.key), key: created.key })
+    }
+
+    fn client(&self, client: &HttpClient) -> HttpClient {
+        match (&self.email, &self.api_token) {
+            (Some(email), Some(token)) => client.with_basic_auth(email, token),
+            (None, Some(token)) => client.with_session_token(token),
+            _ => client.clone(),
+        }
+    }
+
+    /// Rejects webhook calls without the configured secret, and every call
+    /// while none is configured.
+    pub fn check_secret(&self, secret: Option<&str>) -> ServiceResult<()> {
+        let Some(expected) = &self.webhook_secret else {
+            return Err(ServiceError::Unauthorized("The Jira webhook is not configured".to_string()));
+        };
+        match secret {
+            Some(secret) if secrets_match(secret, expected) => Ok(()),
+            _ => Err(ServiceError::Unauthorized("Invalid Jira webhook secret".to_string())),
+        }
+    }
+
+    /// The task status an issue status stands for.
+    pub fn task_status(&self, status: &IssueStatus) -> Option<TaskStatus> {
+        if let Some(mapped) = self.statuses.get(&status.name.to_lowercase()) {
+            return Some(mapped.clone());
+        }
+        match status.category.as_ref()?.key.as_str() {
+            "new" => Some(TaskStatus::Pending),
+            "indeterminate" => Some(TaskStatus::InProgress),
+            "done" => Some(TaskStatus::Completed),
+            _ => None,
+        }
+    }
+}
+
+/// Creates the issue of a new or approved task, if it is synced, and links
+/// it in the task's metadata. Jira being unavailable doesn't fail the task;
+/// the task is returned unlinked.
+pub async fn link_new_task(state: &AppState, client: &HttpClient, task: Task) -> Task {
+    let Some(project) = state.jira.project_for(&task) else {
+        return task;
+    };
+    let link = match state.jira.create_issue(&state.http_client, &task, project).await {
+        Ok(link) => link,
+        Err(e) => {
+            warn!("Could not create a Jira issue for task {}: {}", task.id, e);
+            return task;
+        }
+    };
+    info!("Task {} is Jira issue {}", task.id, link.key);
+
+    let mut metadata = task.metadata.clone();
+    metadata["jira"] = json!(link);
+    let update_request = UpdateTaskRequest {
+        title: None,
+        description: None,
+        status: None,
+        priority: None,
+        due_date: None,
+        tags: None,
+        metadata: Some(metadata),
+        estimate_minutes: None,
+    };
+    let task_url = format!("{}/api/v1/tasks/{}", state.config.service_url("persistence"), task.id);
+    match client.put::<UpdateTaskRequest, Task>(&task_url, &update_request).await {
+        Ok(linked) => linked,
+        Err(e) => {
+            warn!("Could not link task {} to Jira issue {}: {}", task.id, link.key, e);
+            task
+        }
+    }
+}
+
+/// Applies the status of an issue from a Jira webhook event to its task.
+pub async fn apply_event(state: &AppState, event: Event) -> ServiceResult<()> {
+    let Some(issue) = event.issue.filter(|_| event.webhook_event == "jira:issue_updated") else {
+        return Ok(());
+    };
+    let Some(status) = issue.fields.status.as_ref().and_then(|status| state.jira.task_status(status)) else {
+        return Ok(());
+    };
+
+    let persistence = state.config.service_url("persistence");
+    let task = match state
+        .http_client
+        .get::<Task>(&format!("{}/api/v1/tasks/by-jira-issue/{}", persistence, issue.key))
+        .await
+    {
+        Ok(task) => task,
+        Err(e) if e.status().map(|status| status.as_u16()) == Some(404) => return Ok(()),
+        Err(e) => return Err(ServiceError::HttpClient(e)),
+    };
+    if task.status == status {
+        return Ok(());
+    }
+
+    info!("Jira issue {} moves task {} to {:?}", issue.key, task.id, status);
+    let update_request = UpdateTaskRequest {
+        title: None,
+        description: None,
+        status: Some(status),
+        priority: None,
+        due_date: None,
+        tags: None,
+        metadata: None,
+        estimate_minutes: None,
+    };
+    state
+        .http_client
+        .put::<UpdateTaskRequest, Task>(&format!("{}/api/v1/tasks/{}", persistence, task.id), &update_request)
+        .await
+        .map_err(ServiceError::HttpClient)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use models::Priority;
+    use uuid::Uuid;
+
+    fn jira() -> Jira {
+        Jira::new(&JiraConfig {
+            base_url: Some("https://acme.atlassian.net/".to_string()),
+            project: Some("GEN".to_string()),
+            project_map: vec!["infra=OPS".to_string(), "web=WEB".to_string()],
+            status_map: vec!["Blocked=OnHold".to_string(), "Parked=Shelved".to_string()],
+            webhook_secret: Some("s3cret".to_string()),
+            ..JiraConfig::default()
+        })
+    }
+
+    fn task(tags: &[&str]) -> Task {
+        let now = Utc::now();
+        Task {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            case_id: Uuid::new_v4(),
+            title: "Renew the certificate".to_string(),
+            description: None,
+            task_type: TaskType::Work,
+            status: TaskStatus::Pending,
+            priority: Priority::Medium,
+            due_date: None,
+            created_at: now,
+            updated_at: now,
+            completed_at: None,
+            metadata: json!({}),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            estimate_minutes: None,
+        }
+    }
+
+    fn status(name: &str, category: Option<&str>) -> IssueStatus {
+        IssueStatus { name: name.to_string(), category: category.map(|key| StatusCategory { key: key.to_string() }) }
+    }
+
+    #[test]
+    fn project_comes_from_the_first_mapped_tag() {
+        let jira = jira();
+        assert_eq!(jira.project_for(&task(&["urgent", "web", "infra"])), Some("WEB"));
+        assert_eq!(jira.project_for(&task(&["infra"])), Some("OPS"));
+        assert_eq!(jira.project_for(&task(&["urgent"])), Some("GEN"));
+        assert_eq!(Jira { default_project: None, ..jira }.project_for(&task(&["urgent"])), None);
+    }
+
+    #[test]
+    fn only_unlinked_work_tasks_out_of_review_are_synced() {
+        let jira = jira();
+        let personal = Task { task_type: TaskType::Personal, ..task(&[]) };
+        let in_review = Task { status: TaskStatus::NeedsReview, ..task(&[]) };
+        let linked = Task { metadata: json!({ "jira": { "key": "GEN-1", "url": "" } }), ..task(&[]) };
+        assert_eq!(jira.project_for(&personal), None);
+        assert_eq!(jira.project_for(&in_review), None);
+        assert_eq!(jira.project_for(&linked), None);
+        assert_eq!(Jira { base_url: None, ..jira }.project_for(&task(&[])), None);
+    }
+
+    #[test]
+    fn issue_status_maps_by_name_then_category() {
+        let jira = jira();
+        assert_eq!(jira.task_status(&status("Blocked", Some("indeterminate"))), Some(TaskStatus::OnHold));
+        assert_eq!(jira.task_status(&status("To Do", Some("new"))), Some(TaskStatus::Pending));
+        assert_eq!(jira.task_status(&status("In Review", Some("indeterminate"))), Some(TaskStatus::InProgress));
+        assert_eq!(jira.task_status(&status("Done", Some("done"))), Some(TaskStatus::Completed));
+        assert_eq!(jira.task_status(&status("Mystery", Some("undefined"))), None);
+        assert_eq!(jira.task_status(&status("Mystery", None)), None);
+    }
+
+    #[test]
+    fn webhook_needs_the_configured_secret() {
+        let jira = jira();
+        assert!(jira.check_secret(Some("s3cret")).is_ok());
+        assert!(jira.check_secret(Some("s3cret-not")).is_err());
+        assert!(jira.check_secret(Some("")).is_err());
+        assert!(jira.check_secret(None).is_err());
+
+        let unconfigured = Jira { webhook_secret: None, ..jira };
+        assert!(unconfigured.check_secret(None).is_err());
+        assert!(unconfigured.check_secret(Some("anything")).is_err());
+    }
+}
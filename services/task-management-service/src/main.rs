@@ -1,61 +1,169 @@
 use axum::{
-    extract::{Path, State, Query},
-    http::StatusCode,
-    response::Json,
-    routing::{get, post, put, delete},
+    extract::{Path, Query, RawQuery, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Json, Redirect, Response},
+    routing::{any, get, post, put, patch, delete},
     Router,
 };
-use common::{config::ServiceConfig, http_client::HttpClient, HealthResponse, ServiceResult};
+use common::{
+    auth::{ServiceState, SessionToken},
+    config::ServiceConfig, logging, reload::LiveConfig,
+    export::{export_pages, ExportQuery},
+    http_client::HttpClient,
+    jobs::{HttpJobQueue, Worker},
+    HealthResponse, ServiceResult,
+};
 use models::{
-    Task, TaskStatus, CreateTaskRequest, UpdateTaskRequest, TaskType, Priority
+    Case, Task, TaskStatus, TaskType, CreateTaskRequest, CreateCaseRequest, UpdateTaskRequest, Priority,
+    UpdateTaskStatusRequest, TaskStats, TaskSync, WorkloadDay,
+    TaskTemplate, TaskTemplateRequest, InstantiateTemplateRequest,
+    SavedFilter, SavedFilterRequest, TaskFeedback, TaskFeedbackRequest, FeedbackVerdict, Revision,
 };
 use std::sync::Arc;
 use tower::ServiceBuilder;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 use uuid::Uuid;
 use chrono::Utc;
 
+use caldav::DavSession;
+
+mod aging;
+mod caldav;
+mod feedback;
+mod ical;
+mod import;
+mod jira;
+mod meetings;
+mod schemas;
+mod taxonomy;
+mod templates;
+
 #[derive(Clone)]
 struct AppState {
     config: ServiceConfig,
     http_client: HttpClient,
+    schemas: Arc<schemas::TaskSchemas>,
+    jira: jira::Jira,
 }
 
-#[derive(Debug, serde::Deserialize)]
+impl ServiceState for AppState {
+    fn config(&self) -> &ServiceConfig {
+        &self.config
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 struct TaskQuery {
     status: Option<TaskStatus>,
+    tag: Option<String>,
+    /// Type name, built-in or custom.
+    task_type: Option<String>,
+}
+
+/// Tasks an export loads from persistence at a time.
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+/// One page of a task listing.
+#[derive(serde::Serialize)]
+struct TaskPage<'a> {
+    #[serde(flatten)]
+    query: &'a TaskQuery,
+    limit: i64,
+    offset: i64,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
     
-    let config = ServiceConfig::from_env("task-management-service", 8003);
+    let config = ServiceConfig::load("task-management-service", 8003).await?;
     
-    tracing_subscriber::fmt()
-        .with_env_filter(&config.log_level)
-        .init();
+    let live = LiveConfig::init(&config);
 
-    let state = AppState {
+    config.start_refresh();
+
+    let state = Arc::new(AppState {
         config: config.clone(),
-        http_client: HttpClient::new(),
+        http_client: HttpClient::for_services(&config),
+        schemas: Arc::new(schemas::TaskSchemas::from_env()?),
+        jira: jira::Jira::new(&config.jira),
+    });
+
+    let env_number = |name: &str, default: i64| {
+        std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
     };
+    let aging_enabled = std::env::var("PRIORITY_AGING_ENABLED")
+        .map(|value| value != "false" && value != "0")
+        .unwrap_or(true);
+    if aging_enabled {
+        let rule = aging::AgingRule {
+            due_soon: chrono::Duration::hours(env_number("PRIORITY_AGING_DUE_SOON_HOURS", 48)),
+            long_overdue: chrono::Duration::hours(env_number("PRIORITY_AGING_OVERDUE_HOURS", 72)),
+        };
+        let every = std::time::Duration::from_secs(env_number("PRIORITY_AGING_INTERVAL_SECS", 3600).max(1) as u64);
+        let aging_state = state.clone();
+        Worker::new(Arc::new(HttpJobQueue::new(config.clone())), &config.service_name, &config.jobs)
+            .recurring("tasks.aging", every, move |_| {
+                let (state, rule) = (aging_state.clone(), rule.clone());
+                async move {
+                    aging::sweep(&state, &rule).await?;
+                    Ok(())
+                }
+            })
+            .start();
+    }
 
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/api/v1/cases/:case_id/tasks", get(get_tasks_for_case))
         .route("/api/v1/cases/:case_id/tasks", post(create_task))
+        .route("/api/v1/cases/:case_id/calendar.ics", get(export_case_calendar))
         .route("/api/v1/tasks", get(get_tasks))
+        .route("/api/v1/tasks/calendar.ics", get(export_calendar))
+        .route("/api/v1/tasks/export", get(export_tasks))
+        .route("/api/v1/import", post(run_import))
+        .route("/api/v1/import/preview", post(preview_import))
         .route("/api/v1/tasks/:id", get(get_task))
         .route("/api/v1/tasks/:id", put(update_task))
         .route("/api/v1/tasks/:id", delete(delete_task))
         .route("/api/v1/tasks/:id/complete", put(complete_task))
-        .with_state(Arc::new(state))
+        .route("/api/v1/tasks/:id/status", patch(update_task_status))
+        .route("/api/v1/tasks/:id/revisions", get(list_task_revisions))
+        .route("/api/v1/tasks/:id/revisions/:revision_id/restore", post(restore_task_revision))
+        .route("/api/v1/review-queue", get(get_review_queue))
+        .route("/api/v1/review-queue/:id/approve", put(approve_reviewed_task))
+        .route("/api/v1/review-queue/:id/reject", put(reject_reviewed_task))
+        .route("/api/v1/tasks/:id/feedback", post(record_task_feedback))
+        .route("/api/v1/jira/webhook", post(handle_jira_webhook))
+        .route("/api/v1/task-feedback", get(list_task_feedback))
+        .route("/api/v1/task-feedback/export", get(export_task_feedback))
+        .route("/api/v1/stats/tasks", get(get_task_stats))
+        .route("/api/v1/stats/workload", get(get_workload))
+        .route("/api/v1/sync/tasks", get(sync_tasks))
+        .route("/api/v1/task-templates", get(list_templates))
+        .route("/api/v1/task-templates", post(create_template))
+        .route("/api/v1/task-templates/:name", get(get_template))
+        .route("/api/v1/task-templates/:name", put(update_template))
+        .route("/api/v1/task-templates/:name", delete(delete_template))
+        .route("/api/v1/tasks/from-template/:name", post(create_task_from_template))
+        .route("/api/v1/saved-filters", get(list_saved_filters))
+        .route("/api/v1/saved-filters", post(create_saved_filter))
+        .route("/api/v1/saved-filters/:id", get(get_saved_filter))
+        .route("/api/v1/saved-filters/:id", put(update_saved_filter))
+        .route("/api/v1/saved-filters/:id", delete(delete_saved_filter))
+        .route("/api/v1/saved-filters/:id/tasks", get(get_saved_filter_tasks))
+        .route("/.well-known/caldav", any(|| async { Redirect::permanent(caldav::HOME) }))
+        .route("/caldav", any(caldav_home))
+        .route("/caldav/", any(caldav_home))
+        .route("/caldav/tasks", any(caldav_collection))
+        .route("/caldav/tasks/", any(caldav_collection))
+        .route("/caldav/tasks/:resource", any(caldav_task))
+        .with_state(state)
+        .merge(live.router())
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
+                .layer(logging::request_layers(&config.service_name))
+                .layer(config.cors.layer()),
         );
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port)).await?;
@@ -70,140 +178,311 @@ async fn health_check() -> Json<HealthResponse> {
     Json(HealthResponse::new("task-management-service"))
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, session))]
 async fn get_tasks_for_case(
     State(state): State<Arc<AppState>>,
     Path(case_id): Path<Uuid>,
+    session: SessionToken,
+    Query(query): Query<TaskQuery>,
 ) -> ServiceResult<Json<Vec<Task>>> {
     info!("Getting tasks for case: {}", case_id);
 
     let persistence_url = format!("{}/api/v1/cases/{}/tasks", state.config.service_url("persistence"), case_id);
-    let tasks = state
-        .http_client
-        .get::<Vec<Task>>(&persistence_url)
+    let tasks = session
+        .client(&state.http_client)
+        .get_with_query::<Vec<Task>, _>(&persistence_url, &query)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(Json(tasks))
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, session))]
 async fn create_task(
     State(state): State<Arc<AppState>>,
     Path(case_id): Path<Uuid>,
+    session: SessionToken,
     Json(request): Json<CreateTaskRequest>,
 ) -> ServiceResult<Json<Task>> {
     info!("Creating task for case {}: {:?}", case_id, request);
 
+    let client = session.client(&state.http_client);
+
+    // Tasks belong to the owner of their case. Fetching the case through the
+    // forwarded session also rejects cases the caller can't see.
+    let case_url = format!("{}/api/v1/cases/{}", state.config.service_url("persistence"), case_id);
+    let case = client
+        .get::<Case>(&case_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    let mut metadata = match request.metadata.clone() {
+        Some(metadata @ serde_json::Value::Object(_)) => metadata,
+        Some(_) => return Err(common::ServiceError::BadRequest("Task metadata must be an object".to_string())),
+        None => serde_json::json!({}),
+    };
+
+    let persistence_base = state.config.service_url("persistence");
+    let mut task_type = request.task_type.clone();
+    let mut violations = taxonomy::check(&taxonomy::load(&client, &persistence_base).await?, &mut task_type, &metadata);
+
+    if let Some(confidence) = request.confidence {
+        metadata["ai_confidence"] = serde_json::json!(confidence);
+    }
+    if let Some(message) = &request.source_message {
+        metadata[feedback::EXTRACTION_KEY] = feedback::extraction(&request, message);
+    }
+
     let now = Utc::now();
     let task_id = Uuid::new_v4();
-    let task = Task {
+    let mut task = Task {
         id: task_id,
-        user_id: Uuid::new_v4(), // TODO: Extract from session token
+        user_id: case.user_id,
         case_id,
         title: request.title,
         description: request.description,
-        task_type: request.task_type,
-        status: TaskStatus::Pending,
+        task_type,
+        status: if request.needs_review { TaskStatus::NeedsReview } else { TaskStatus::Pending },
         priority: request.priority,
         due_date: request.due_date,
         created_at: now,
         updated_at: now,
         completed_at: None,
-        metadata: serde_json::json!({}),
+        metadata,
+        tags: request.tags,
+        estimate_minutes: request.estimate_minutes,
     };
 
+    // Tasks from the AI agent that break the taxonomy or their type's
+    // schema are held for review rather than lost.
+    violations.extend(state.schemas.check(&task));
+    if !violations.is_empty() {
+        if request.source_message.is_none() {
+            return Err(common::ServiceError::Validation(violations));
+        }
+        task.metadata["validation_issues"] = serde_json::json!(violations);
+        task.status = TaskStatus::NeedsReview;
+    }
+
     let persistence_url = format!("{}/api/v1/tasks", state.config.service_url("persistence"));
-    let saved_task = state
-        .http_client
+    if task.task_type == TaskType::Meeting && task.due_date.is_some() {
+        let existing = client
+            .get::<Vec<Task>>(&persistence_url)
+            .await
+            .map_err(common::ServiceError::HttpClient)?;
+        let conflicts = meetings::find_conflicts(&task, &existing);
+        if !conflicts.is_empty() {
+            info!("Meeting {} overlaps {} other meetings", task.id, conflicts.len());
+            task.metadata["meeting_conflicts"] = serde_json::to_value(&conflicts)
+                .map_err(|e| common::ServiceError::Internal(anyhow::anyhow!("Serialization error: {}", e)))?;
+        }
+    }
+
+    let saved_task = client
         .post::<Task, Task>(&persistence_url, &task)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
+    let saved_task = jira::link_new_task(&state, &client, saved_task).await;
 
     info!("Task created with ID: {}", saved_task.id);
     Ok(Json(saved_task))
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, session))]
 async fn get_tasks(
     State(state): State<Arc<AppState>>,
     Query(query): Query<TaskQuery>,
+    session: SessionToken,
 ) -> ServiceResult<Json<Vec<Task>>> {
     info!("Getting tasks with query: {:?}", query.status);
 
-    let base_url = format!("{}/api/v1/tasks", state.config.service_url("persistence"));
-    let url = if let Some(status) = query.status {
-        let status_str = format!("{:?}", status);
-        format!("{}?status={}", base_url, status_str)
-    } else {
-        base_url
-    };
-
-    let tasks = state
-        .http_client
-        .get::<Vec<Task>>(&url)
+    let url = format!("{}/api/v1/tasks", state.config.service_url("persistence"));
+    let tasks = session
+        .client(&state.http_client)
+        .get_with_query::<Vec<Task>, _>(&url, &query)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(Json(tasks))
 }
 
-#[instrument(skip(state))]
+/// Task statistics for the caller. The query (`group_by`, `from`, `to`) is
+/// passed to persistence unchanged.
+#[instrument(skip(state, session))]
+async fn get_task_stats(
+    State(state): State<Arc<AppState>>,
+    RawQuery(query): RawQuery,
+    session: SessionToken,
+) -> ServiceResult<Json<TaskStats>> {
+    info!("Getting task stats: {:?}", query);
+
+    let mut url = format!("{}/api/v1/stats/tasks", state.config.service_url("persistence"));
+    if let Some(query) = query {
+        url = format!("{}?{}", url, query);
+    }
+
+    let stats = session
+        .client(&state.http_client)
+        .get::<TaskStats>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(stats))
+}
+
+/// Estimated effort of the caller's open tasks per due day. The query
+/// (`from`, `to`) is passed to persistence unchanged.
+#[instrument(skip(state, session))]
+async fn get_workload(
+    State(state): State<Arc<AppState>>,
+    RawQuery(query): RawQuery,
+    session: SessionToken,
+) -> ServiceResult<Json<Vec<WorkloadDay>>> {
+    info!("Getting workload: {:?}", query);
+
+    let mut url = format!("{}/api/v1/stats/workload", state.config.service_url("persistence"));
+    if let Some(query) = query {
+        url = format!("{}?{}", url, query);
+    }
+
+    let workload = session
+        .client(&state.http_client)
+        .get::<Vec<WorkloadDay>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(workload))
+}
+
+#[instrument(skip(state, session))]
+async fn sync_tasks(
+    State(state): State<Arc<AppState>>,
+    RawQuery(query): RawQuery,
+    session: SessionToken,
+) -> ServiceResult<Json<TaskSync>> {
+    info!("Syncing tasks: {:?}", query);
+
+    let mut url = format!("{}/api/v1/sync/tasks", state.config.service_url("persistence"));
+    if let Some(query) = query {
+        url = format!("{}?{}", url, query);
+    }
+
+    let sync = session
+        .client(&state.http_client)
+        .get::<TaskSync>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(sync))
+}
+
+#[instrument(skip(state, session))]
 async fn get_task(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    session: SessionToken,
 ) -> ServiceResult<Json<Task>> {
     info!("Getting task: {}", id);
 
     let persistence_url = format!("{}/api/v1/tasks/{}", state.config.service_url("persistence"), id);
-    let task = state
-        .http_client
+    let task = session
+        .client(&state.http_client)
         .get::<Task>(&persistence_url)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(Json(task))
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, session))]
 async fn update_task(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    session: SessionToken,
     Json(request): Json<UpdateTaskRequest>,
 ) -> ServiceResult<Json<Task>> {
     info!("Updating task {}: {:?}", id, request);
 
+    let client = session.client(&state.http_client);
     let persistence_url = format!("{}/api/v1/tasks/{}", state.config.service_url("persistence"), id);
-    let updated_task = state
-        .http_client
+
+    // Only violations the update introduces are rejected, so tasks from
+    // before a schema existed can still be edited.
+    let mut task = client
+        .get::<Task>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    let existing = state.schemas.check(&task);
+    let mut violations = Vec::new();
+    if let Some(metadata) = &request.metadata {
+        let types = taxonomy::load(&client, &state.config.service_url("persistence")).await?;
+        violations = taxonomy::check(&types, &mut task.task_type, metadata);
+    }
+    apply_update(&mut task, &request);
+    violations.extend(state.schemas.check(&task).into_iter().filter(|violation| !existing.contains(violation)));
+    if !violations.is_empty() {
+        return Err(common::ServiceError::Validation(violations));
+    }
+
+    let updated_task = client
         .put::<UpdateTaskRequest, Task>(&persistence_url, &request)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(Json(updated_task))
 }
 
-#[instrument(skip(state))]
+/// `task` as it will be after `request`, for validation.
+fn apply_update(task: &mut Task, request: &UpdateTaskRequest) {
+    if let Some(title) = &request.title {
+        task.title.clone_from(title);
+    }
+    if request.description.is_some() {
+        task.description.clone_from(&request.description);
+    }
+    if let Some(status) = &request.status {
+        task.status = status.clone();
+    }
+    if let Some(priority) = &request.priority {
+        task.priority = priority.clone();
+    }
+    if request.due_date.is_some() {
+        task.due_date = request.due_date;
+    }
+    if let Some(tags) = &request.tags {
+        task.tags.clone_from(tags);
+    }
+    if let Some(metadata) = &request.metadata {
+        task.metadata.clone_from(metadata);
+    }
+    if request.estimate_minutes.is_some() {
+        task.estimate_minutes = request.estimate_minutes;
+    }
+}
+
+#[instrument(skip(state, session))]
 async fn delete_task(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    session: SessionToken,
 ) -> ServiceResult<StatusCode> {
     info!("Deleting task: {}", id);
 
     let persistence_url = format!("{}/api/v1/tasks/{}", state.config.service_url("persistence"), id);
-    state
-        .http_client
+    session
+        .client(&state.http_client)
         .delete(&persistence_url)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state, session))]
 async fn complete_task(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    session: SessionToken,
 ) -> ServiceResult<Json<Task>> {
     info!("Completing task: {}", id);
 
@@ -213,14 +492,1020 @@ async fn complete_task(
         status: Some(TaskStatus::Completed),
         priority: None,
         due_date: None,
+        tags: None,
+        metadata: None,
+        estimate_minutes: None,
     };
 
     let persistence_url = format!("{}/api/v1/tasks/{}", state.config.service_url("persistence"), id);
-    let updated_task = state
-        .http_client
+    let updated_task = session
+        .client(&state.http_client)
         .put::<UpdateTaskRequest, Task>(&persistence_url, &update_request)
         .await
-        .map_err(|e| common::ServiceError::HttpClient(e))?;
+        .map_err(common::ServiceError::HttpClient)?;
 
     Ok(Json(updated_task))
 }
+
+/// The task's revisions, newest first.
+#[instrument(skip(state, session))]
+async fn list_task_revisions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    session: SessionToken,
+) -> ServiceResult<Json<Vec<Revision>>> {
+    info!("Listing revisions of task: {}", id);
+
+    let persistence_url = format!("{}/api/v1/tasks/{}/revisions", state.config.service_url("persistence"), id);
+    let revisions = session
+        .client(&state.http_client)
+        .get::<Vec<Revision>>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(revisions))
+}
+
+/// Rolls the task back to how it was right after the given revision.
+#[instrument(skip(state, session))]
+async fn restore_task_revision(
+    State(state): State<Arc<AppState>>,
+    Path((id, revision_id)): Path<(Uuid, Uuid)>,
+    session: SessionToken,
+) -> ServiceResult<Json<Task>> {
+    info!("Restoring task {} to revision {}", id, revision_id);
+
+    let persistence_url = format!(
+        "{}/api/v1/tasks/{}/revisions/{}/restore",
+        state.config.service_url("persistence"),
+        id,
+        revision_id
+    );
+    let task = session
+        .client(&state.http_client)
+        .post::<serde_json::Value, Task>(&persistence_url, &serde_json::json!({}))
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(task))
+}
+
+#[instrument(skip(state, session))]
+async fn update_task_status(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    session: SessionToken,
+    Json(request): Json<UpdateTaskStatusRequest>,
+) -> ServiceResult<Json<Task>> {
+    info!("Setting status of task {} to {:?}", id, request.status);
+
+    let update_request = UpdateTaskRequest {
+        title: None,
+        description: None,
+        status: Some(request.status),
+        priority: None,
+        due_date: None,
+        tags: None,
+        metadata: None,
+        estimate_minutes: None,
+    };
+
+    let persistence_url = format!("{}/api/v1/tasks/{}", state.config.service_url("persistence"), id);
+    let updated_task = session
+        .client(&state.http_client)
+        .put::<UpdateTaskRequest, Task>(&persistence_url, &update_request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(updated_task))
+}
+
+/// The caller's tasks the AI agent extracted with low confidence, waiting
+/// to be approved or rejected.
+#[instrument(skip(state, session))]
+async fn get_review_queue(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+) -> ServiceResult<Json<Vec<Task>>> {
+    info!("Getting review queue");
+
+    let query = TaskQuery { status: Some(TaskStatus::NeedsReview), tag: None, task_type: None };
+    let url = format!("{}/api/v1/tasks", state.config.service_url("persistence"));
+    let tasks = session
+        .client(&state.http_client)
+        .get_with_query::<Vec<Task>, _>(&url, &query)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(tasks))
+}
+
+/// Accepts a task from the review queue, making it a regular pending task.
+#[instrument(skip(state, session))]
+async fn approve_reviewed_task(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    session: SessionToken,
+) -> ServiceResult<Json<Task>> {
+    info!("Approving reviewed task: {}", id);
+    resolve_review(&state, &session, id, TaskStatus::Pending, FeedbackVerdict::Accepted).await.map(Json)
+}
+
+/// Rejects a task from the review queue. It is kept as cancelled rather
+/// than deleted, so the case still shows what was extracted.
+#[instrument(skip(state, session))]
+async fn reject_reviewed_task(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    session: SessionToken,
+) -> ServiceResult<Json<Task>> {
+    info!("Rejecting reviewed task: {}", id);
+    resolve_review(&state, &session, id, TaskStatus::Cancelled, FeedbackVerdict::Rejected).await.map(Json)
+}
+
+async fn resolve_review(
+    state: &AppState,
+    session: &SessionToken,
+    id: Uuid,
+    status: TaskStatus,
+    verdict: FeedbackVerdict,
+) -> ServiceResult<Task> {
+    let client = session.client(&state.http_client);
+    let persistence_url = format!("{}/api/v1/tasks/{}", state.config.service_url("persistence"), id);
+    let task = client
+        .get::<Task>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+    if task.status != TaskStatus::NeedsReview {
+        return Err(common::ServiceError::BadRequest(format!("Task {} is not awaiting review", id)));
+    }
+
+    let update_request = UpdateTaskRequest {
+        title: None,
+        description: None,
+        status: Some(status),
+        priority: None,
+        due_date: None,
+        tags: None,
+        metadata: None,
+        estimate_minutes: None,
+    };
+    let updated_task = client
+        .put::<UpdateTaskRequest, Task>(&persistence_url, &update_request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    // The review doubles as feedback on the extraction. Failing to record
+    // it shouldn't undo the review.
+    let request = TaskFeedbackRequest { verdict, corrected: None, comment: None };
+    if let Err(e) = save_feedback(state, session, &task, request).await {
+        warn!("Could not record review of task {} as feedback: {}", id, e);
+    }
+
+    Ok(jira::link_new_task(state, &client, updated_task).await)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct JiraWebhookQuery {
+    secret: Option<String>,
+}
+
+/// Jira's issue events. Status transitions of issues created for tasks
+/// move their task along; other events are acknowledged and ignored.
+#[instrument(skip(state, query, event))]
+async fn handle_jira_webhook(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<JiraWebhookQuery>,
+    Json(event): Json<jira::Event>,
+) -> ServiceResult<StatusCode> {
+    state.jira.check_secret(query.secret.as_deref())?;
+    jira::apply_event(&state, event).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Feedback on a task the AI agent created: `Accepted`, `Rejected` or
+/// `Edited` with the corrected fields. Later feedback replaces earlier.
+#[instrument(skip(state, session))]
+async fn record_task_feedback(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    session: SessionToken,
+    Json(request): Json<TaskFeedbackRequest>,
+) -> ServiceResult<Json<TaskFeedback>> {
+    info!("Recording {:?} feedback on task {}", request.verdict, id);
+
+    let persistence_url = format!("{}/api/v1/tasks/{}", state.config.service_url("persistence"), id);
+    let task = session
+        .client(&state.http_client)
+        .get::<Task>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    save_feedback(&state, &session, &task, request).await.map(Json)
+}
+
+async fn save_feedback(
+    state: &AppState,
+    session: &SessionToken,
+    task: &Task,
+    request: TaskFeedbackRequest,
+) -> ServiceResult<TaskFeedback> {
+    let feedback = feedback::build(task, request)?;
+    let url = format!("{}/api/v1/task-feedback", state.config.service_url("persistence"));
+    session
+        .client(&state.http_client)
+        .post::<TaskFeedback, TaskFeedback>(&url, &feedback)
+        .await
+        .map_err(common::ServiceError::HttpClient)
+}
+
+/// The caller's task feedback (optional `?verdict=`).
+#[instrument(skip(state, session))]
+async fn list_task_feedback(
+    State(state): State<Arc<AppState>>,
+    RawQuery(query): RawQuery,
+    session: SessionToken,
+) -> ServiceResult<Json<Vec<TaskFeedback>>> {
+    info!("Listing task feedback: {:?}", query);
+
+    let feedback = fetch_task_feedback(&state, &session, query).await?;
+    Ok(Json(feedback))
+}
+
+/// The caller's task feedback as labeled examples in JSON Lines, for
+/// prompt tuning or picking few-shot examples.
+#[instrument(skip(state, session))]
+async fn export_task_feedback(
+    State(state): State<Arc<AppState>>,
+    RawQuery(query): RawQuery,
+    session: SessionToken,
+) -> ServiceResult<Response> {
+    info!("Exporting task feedback: {:?}", query);
+
+    let feedback = fetch_task_feedback(&state, &session, query).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"task-feedback.jsonl\""),
+        ],
+        feedback::to_jsonl(feedback)?,
+    )
+        .into_response())
+}
+
+async fn fetch_task_feedback(
+    state: &AppState,
+    session: &SessionToken,
+    query: Option<String>,
+) -> ServiceResult<Vec<TaskFeedback>> {
+    let mut url = format!("{}/api/v1/task-feedback", state.config.service_url("persistence"));
+    if let Some(query) = query {
+        url = format!("{}?{}", url, query);
+    }
+    session
+        .client(&state.http_client)
+        .get::<Vec<TaskFeedback>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)
+}
+
+#[instrument(skip(state, session))]
+async fn export_tasks(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Query(query): Query<TaskQuery>,
+    Query(export): Query<ExportQuery>,
+) -> ServiceResult<Response> {
+    info!("Exporting tasks as {:?} with query: {:?}", export.format, query.status);
+
+    let url = format!("{}/api/v1/tasks", state.config.service_url("persistence"));
+    let client = session.client(&state.http_client);
+    let pages = futures::stream::try_unfold(Some(0), move |offset| {
+        let (client, url, query) = (client.clone(), url.clone(), query.clone());
+        async move {
+            let Some(offset) = offset else {
+                return Ok(None);
+            };
+            let page = TaskPage { query: &query, limit: EXPORT_PAGE_SIZE, offset };
+            let tasks = client
+                .get_with_query::<Vec<Task>, _>(&url, &page)
+                .await
+                .map_err(common::ServiceError::HttpClient)?;
+            let next = (tasks.len() as i64 == EXPORT_PAGE_SIZE).then_some(offset + EXPORT_PAGE_SIZE);
+            Ok(Some((tasks, next)))
+        }
+    });
+
+    export_pages(pages, &export, "tasks").await
+}
+
+#[instrument(skip(state, session))]
+async fn export_calendar(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Query(query): Query<TaskQuery>,
+) -> ServiceResult<Response> {
+    info!("Exporting tasks as iCalendar with query: {:?}", query.status);
+
+    let url = format!("{}/api/v1/tasks", state.config.service_url("persistence"));
+    let tasks = session
+        .client(&state.http_client)
+        .get_with_query::<Vec<Task>, _>(&url, &query)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(calendar_response(ical::render_calendar("Tasks", &tasks)))
+}
+
+#[instrument(skip(state, session))]
+async fn export_case_calendar(
+    State(state): State<Arc<AppState>>,
+    Path(case_id): Path<Uuid>,
+    session: SessionToken,
+) -> ServiceResult<Response> {
+    info!("Exporting tasks for case {} as iCalendar", case_id);
+
+    let persistence_url = format!("{}/api/v1/cases/{}/tasks", state.config.service_url("persistence"), case_id);
+    let tasks = session
+        .client(&state.http_client)
+        .get::<Vec<Task>>(&persistence_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    let calendar_name = format!("Case {}", case_id);
+    Ok(calendar_response(ical::render_calendar(&calendar_name, &tasks)))
+}
+
+/// CalDAV on the calendar home, where clients find the task collection.
+#[instrument(skip(_session, headers))]
+async fn caldav_home(method: Method, headers: HeaderMap, _session: DavSession) -> Response {
+    match method.as_str() {
+        "OPTIONS" => caldav::options(caldav::HOME_METHODS),
+        "PROPFIND" => caldav::multistatus(caldav::home_responses(caldav::depth_one(&headers))),
+        _ => caldav::method_not_allowed(caldav::HOME_METHODS),
+    }
+}
+
+/// CalDAV on the collection of the caller's tasks.
+#[instrument(skip(state, headers, session, body))]
+async fn caldav_collection(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    headers: HeaderMap,
+    DavSession(session): DavSession,
+    body: String,
+) -> ServiceResult<Response> {
+    let responses = match method.as_str() {
+        "OPTIONS" => return Ok(caldav::options(caldav::COLLECTION_METHODS)),
+        "PROPFIND" => caldav::collection_responses(&caldav_tasks(&state, &session).await?, caldav::depth_one(&headers)),
+        "REPORT" => caldav::report_responses(&caldav_tasks(&state, &session).await?, &body),
+        _ => return Ok(caldav::method_not_allowed(caldav::COLLECTION_METHODS)),
+    };
+    Ok(caldav::multistatus(responses))
+}
+
+/// CalDAV on one of the caller's tasks: GET reads it as a VTODO and PUT
+/// writes a client's changes back. `If-Match` is honored, so a client
+/// holding an outdated copy gets 412 rather than overwriting newer
+/// changes.
+#[instrument(skip(state, headers, session, body))]
+async fn caldav_task(
+    State(state): State<Arc<AppState>>,
+    Path(resource): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+    DavSession(session): DavSession,
+    body: String,
+) -> ServiceResult<Response> {
+    match method.as_str() {
+        "GET" | "PUT" => {}
+        "OPTIONS" => return Ok(caldav::options(caldav::TASK_METHODS)),
+        _ => return Ok(caldav::method_not_allowed(caldav::TASK_METHODS)),
+    }
+
+    let task = match caldav::task_id(&resource) {
+        Some(id) => {
+            let url = format!("{}/api/v1/tasks/{}", state.config.service_url("persistence"), id);
+            match session.client(&state.http_client).get::<Task>(&url).await {
+                Ok(task) => Some(task),
+                Err(e) if e.status().map(|status| status.as_u16()) == Some(404) => None,
+                Err(e) => return Err(common::ServiceError::HttpClient(e)),
+            }
+        }
+        None => None,
+    };
+    let Some(task) = task else {
+        return match method.as_str() {
+            "PUT" => Ok(caldav::creation_refused()),
+            _ => Err(common::ServiceError::NotFound(format!("Task {}", resource))),
+        };
+    };
+    let etag = caldav::etag(&task);
+
+    if method.as_str() == "GET" {
+        let body = ical::render_calendar(&task.title, std::slice::from_ref(&task));
+        let mut response = calendar_response(body);
+        response.headers_mut().insert(header::ETAG, header::HeaderValue::from_str(&etag).expect("ETags are ASCII"));
+        return Ok(response);
+    }
+
+    let if_match = headers.get(header::IF_MATCH).and_then(|value| value.to_str().ok()).map(str::trim);
+    if if_match.is_some_and(|if_match| if_match != "*" && if_match != etag) {
+        return Ok(StatusCode::PRECONDITION_FAILED.into_response());
+    }
+    if headers.get(header::IF_NONE_MATCH).is_some_and(|value| value == "*") {
+        return Ok(StatusCode::PRECONDITION_FAILED.into_response());
+    }
+
+    let update = caldav::todo_update(&ical::parse_todo(&body)?, &task);
+    let Json(updated) = update_task(State(state), Path(task.id), session, Json(update)).await?;
+    info!("Task {} written back by a CalDAV client", updated.id);
+    // The stored task differs from what the client sent, so no ETag is
+    // returned and the client reads the task again.
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Every task of the caller, for the CalDAV collection.
+async fn caldav_tasks(state: &AppState, session: &SessionToken) -> ServiceResult<Vec<Task>> {
+    let url = format!("{}/api/v1/tasks", state.config.service_url("persistence"));
+    session
+        .client(&state.http_client)
+        .get::<Vec<Task>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)
+}
+
+fn calendar_response(body: String) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
+
+#[instrument(skip(body))]
+async fn preview_import(
+    Query(query): Query<import::ImportQuery>,
+    body: String,
+) -> ServiceResult<Json<import::ImportPlan>> {
+    info!("Previewing {:?} import", query.source);
+
+    let plan = import::parse(query.source, &body)
+        .map_err(|e| common::ServiceError::BadRequest(format!("Invalid import file: {}", e)))?;
+
+    info!("Import preview: {} cases, {} tasks", plan.cases.len(), plan.task_count());
+    Ok(Json(plan))
+}
+
+#[instrument(skip(state, session, body))]
+async fn run_import(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Query(query): Query<import::ImportQuery>,
+    body: String,
+) -> ServiceResult<Json<import::ImportResult>> {
+    info!("Running {:?} import", query.source);
+
+    let plan = import::parse(query.source, &body)
+        .map_err(|e| common::ServiceError::BadRequest(format!("Invalid import file: {}", e)))?;
+
+    let mut result = import::ImportResult {
+        warnings: plan.warnings,
+        ..Default::default()
+    };
+
+    let client = session.client(&state.http_client);
+    let case_url = format!("{}/api/v1/cases", state.config.service_url("case-management"));
+    let task_url = format!("{}/api/v1/tasks", state.config.service_url("persistence"));
+
+    for planned_case in plan.cases {
+        let create_case_request = CreateCaseRequest {
+            title: planned_case.title.clone(),
+            description: Some(format!("Imported from {:?}", query.source)),
+            priority: Priority::Medium,
+            assigned_to: None,
+            tags: Vec::new(),
+            language: None,
+            urgency: None,
+            user_id: None,
+        };
+        let case = match client.post::<CreateCaseRequest, Case>(&case_url, &create_case_request).await {
+            Ok(case) => case,
+            Err(e) => {
+                warn!("Import could not create case '{}': {}", planned_case.title, e);
+                let error = format!("Could not create the case: {}", e);
+                result.failures.extend(planned_case.tasks.into_iter().map(|task| import::ImportFailure {
+                    case: planned_case.title.clone(),
+                    task: task.title,
+                    error: error.clone(),
+                }));
+                continue;
+            }
+        };
+        result.cases_created.push(case.id);
+
+        for planned_task in planned_case.tasks {
+            let now = Utc::now();
+            let completed_at = matches!(planned_task.status, TaskStatus::Completed).then_some(now);
+            let task = Task {
+                id: Uuid::new_v4(),
+                user_id: case.user_id,
+                case_id: case.id,
+                title: planned_task.title,
+                description: planned_task.description,
+                task_type: planned_task.task_type,
+                status: planned_task.status,
+                priority: planned_task.priority,
+                due_date: planned_task.due_date,
+                created_at: now,
+                updated_at: now,
+                completed_at,
+                metadata: serde_json::json!({ "import_source": format!("{:?}", query.source) }),
+                tags: Vec::new(),
+                estimate_minutes: None,
+            };
+
+            match client.post::<Task, Task>(&task_url, &task).await {
+                Ok(saved_task) => result.tasks_created.push(saved_task.id),
+                Err(e) => {
+                    warn!("Import could not create task '{}': {}", task.title, e);
+                    result.failures.push(import::ImportFailure {
+                        case: planned_case.title.clone(),
+                        task: task.title,
+                        error: format!("Could not create the task: {}", e),
+                    });
+                }
+            }
+        }
+    }
+
+    info!(
+        "Import finished: {} cases, {} tasks created, {} tasks failed",
+        result.cases_created.len(),
+        result.tasks_created.len(),
+        result.failures.len()
+    );
+    Ok(Json(result))
+}
+
+#[instrument(skip(state, session))]
+async fn list_templates(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+) -> ServiceResult<Json<Vec<TaskTemplate>>> {
+    info!("Listing task templates");
+
+    let url = format!("{}/api/v1/task-templates", state.config.service_url("persistence"));
+    let templates = session
+        .client(&state.http_client)
+        .get::<Vec<TaskTemplate>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(templates))
+}
+
+#[instrument(skip(state, session))]
+async fn create_template(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<TaskTemplateRequest>,
+) -> ServiceResult<Json<TaskTemplate>> {
+    info!("Creating task template: {}", request.name);
+
+    let url = format!("{}/api/v1/task-templates", state.config.service_url("persistence"));
+    let template = session
+        .client(&state.http_client)
+        .post::<TaskTemplateRequest, TaskTemplate>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(template))
+}
+
+#[instrument(skip(state, session))]
+async fn get_template(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    session: SessionToken,
+) -> ServiceResult<Json<TaskTemplate>> {
+    info!("Getting task template: {}", name);
+
+    let url = format!("{}/api/v1/task-templates/{}", state.config.service_url("persistence"), name);
+    let template = session
+        .client(&state.http_client)
+        .get::<TaskTemplate>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(template))
+}
+
+#[instrument(skip(state, session))]
+async fn update_template(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    session: SessionToken,
+    Json(request): Json<TaskTemplateRequest>,
+) -> ServiceResult<Json<TaskTemplate>> {
+    info!("Updating task template: {}", name);
+
+    let url = format!("{}/api/v1/task-templates/{}", state.config.service_url("persistence"), name);
+    let template = session
+        .client(&state.http_client)
+        .put::<TaskTemplateRequest, TaskTemplate>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(template))
+}
+
+#[instrument(skip(state, session))]
+async fn delete_template(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    session: SessionToken,
+) -> ServiceResult<StatusCode> {
+    info!("Deleting task template: {}", name);
+
+    let url = format!("{}/api/v1/task-templates/{}", state.config.service_url("persistence"), name);
+    session
+        .client(&state.http_client)
+        .delete(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip(state, session))]
+async fn list_saved_filters(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+) -> ServiceResult<Json<Vec<SavedFilter>>> {
+    info!("Listing saved filters");
+
+    let url = format!("{}/api/v1/saved-filters", state.config.service_url("persistence"));
+    let filters = session
+        .client(&state.http_client)
+        .get::<Vec<SavedFilter>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(filters))
+}
+
+#[instrument(skip(state, session))]
+async fn create_saved_filter(
+    State(state): State<Arc<AppState>>,
+    session: SessionToken,
+    Json(request): Json<SavedFilterRequest>,
+) -> ServiceResult<Json<SavedFilter>> {
+    info!("Creating saved filter: {}", request.name);
+
+    let url = format!("{}/api/v1/saved-filters", state.config.service_url("persistence"));
+    let saved = session
+        .client(&state.http_client)
+        .post::<SavedFilterRequest, SavedFilter>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(saved))
+}
+
+#[instrument(skip(state, session))]
+async fn get_saved_filter(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    session: SessionToken,
+) -> ServiceResult<Json<SavedFilter>> {
+    info!("Getting saved filter: {}", id);
+
+    let url = format!("{}/api/v1/saved-filters/{}", state.config.service_url("persistence"), id);
+    let saved = session
+        .client(&state.http_client)
+        .get::<SavedFilter>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(saved))
+}
+
+#[instrument(skip(state, session))]
+async fn update_saved_filter(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    session: SessionToken,
+    Json(request): Json<SavedFilterRequest>,
+) -> ServiceResult<Json<SavedFilter>> {
+    info!("Updating saved filter: {}", id);
+
+    let url = format!("{}/api/v1/saved-filters/{}", state.config.service_url("persistence"), id);
+    let saved = session
+        .client(&state.http_client)
+        .put::<SavedFilterRequest, SavedFilter>(&url, &request)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(saved))
+}
+
+#[instrument(skip(state, session))]
+async fn delete_saved_filter(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    session: SessionToken,
+) -> ServiceResult<StatusCode> {
+    info!("Deleting saved filter: {}", id);
+
+    let url = format!("{}/api/v1/saved-filters/{}", state.config.service_url("persistence"), id);
+    session
+        .client(&state.http_client)
+        .delete(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip(state, session))]
+async fn get_saved_filter_tasks(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    session: SessionToken,
+) -> ServiceResult<Json<Vec<Task>>> {
+    info!("Listing tasks for saved filter: {}", id);
+
+    let url = format!("{}/api/v1/saved-filters/{}/tasks", state.config.service_url("persistence"), id);
+    let tasks = session
+        .client(&state.http_client)
+        .get::<Vec<Task>>(&url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    Ok(Json(tasks))
+}
+
+/// Creates a task from one of the caller's templates, in the given case or
+/// in a new case named after the task.
+#[instrument(skip(state, session))]
+async fn create_task_from_template(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    session: SessionToken,
+    Json(request): Json<InstantiateTemplateRequest>,
+) -> ServiceResult<Json<Task>> {
+    info!("Creating task from template: {}", name);
+
+    let client = session.client(&state.http_client);
+
+    let template_url = format!("{}/api/v1/task-templates/{}", state.config.service_url("persistence"), name);
+    let template = client
+        .get::<TaskTemplate>(&template_url)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    let now = Utc::now();
+    let filled = templates::instantiate(&template, &request.values, now)
+        .map_err(common::ServiceError::BadRequest)?;
+
+    let case = match request.case_id {
+        Some(case_id) => {
+            let case_url = format!("{}/api/v1/cases/{}", state.config.service_url("persistence"), case_id);
+            client.get::<Case>(&case_url).await
+        }
+        None => {
+            let create_case_request = CreateCaseRequest {
+                title: filled.title.clone(),
+                description: Some(format!("Created from template '{}'", template.name)),
+                priority: template.priority.clone(),
+                assigned_to: None,
+                tags: Vec::new(),
+                language: None,
+                urgency: None,
+                user_id: None,
+            };
+            let case_url = format!("{}/api/v1/cases", state.config.service_url("case-management"));
+            client.post::<CreateCaseRequest, Case>(&case_url, &create_case_request).await
+        }
+    }
+    .map_err(common::ServiceError::HttpClient)?;
+
+    let task = Task {
+        id: Uuid::new_v4(),
+        user_id: case.user_id,
+        case_id: case.id,
+        title: filled.title,
+        description: filled.description,
+        task_type: template.task_type,
+        status: TaskStatus::Pending,
+        priority: template.priority,
+        due_date: filled.due_date,
+        created_at: now,
+        updated_at: now,
+        completed_at: None,
+        metadata: filled.metadata,
+        tags: Vec::new(),
+        estimate_minutes: None,
+    };
+
+    let persistence_url = format!("{}/api/v1/tasks", state.config.service_url("persistence"));
+    let saved_task = client
+        .post::<Task, Task>(&persistence_url, &task)
+        .await
+        .map_err(common::ServiceError::HttpClient)?;
+
+    info!("Task {} created from template {}", saved_task.id, template.name);
+    Ok(Json(saved_task))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::testing::MockTransport;
+    use reqwest::Method;
+    use serde_json::{json, Value};
+
+    fn state(mock: &Arc<MockTransport>) -> Arc<AppState> {
+        Arc::new(AppState {
+            config: ServiceConfig::for_tests("task-management-service"),
+            http_client: mock.client(),
+            schemas: Arc::new(schemas::TaskSchemas::from_env().unwrap()),
+            jira: jira::Jira::default(),
+        })
+    }
+
+    fn case() -> Value {
+        json!({
+            "id": Uuid::new_v4(),
+            "user_id": Uuid::nil(),
+            "title": "Office move",
+            "description": null,
+            "status": "Open",
+            "priority": "Medium",
+            "created_at": Utc::now(),
+            "updated_at": Utc::now(),
+            "assigned_to": null,
+            "metadata": {},
+        })
+    }
+
+    fn task() -> Value {
+        json!({
+            "id": Uuid::new_v4(),
+            "user_id": Uuid::nil(),
+            "case_id": Uuid::new_v4(),
+            "title": "Book movers",
+            "description": null,
+            "task_type": "Work",
+            "status": "Pending",
+            "priority": "Medium",
+            "due_date": null,
+            "created_at": Utc::now(),
+            "updated_at": Utc::now(),
+            "completed_at": null,
+            "metadata": {},
+        })
+    }
+
+    const CSV: &str = "title,case\nBook movers,Office move\nLabel boxes,Office move\nOrder desks,Furniture\n";
+
+    async fn import(state: Arc<AppState>) -> import::ImportResult {
+        let query = import::ImportQuery { source: import::ImportSource::Csv };
+        run_import(State(state), signed_in(), Query(query), CSV.to_string()).await.unwrap().0
+    }
+
+    #[tokio::test]
+    async fn failed_cases_are_reported_per_task_and_the_import_goes_on() {
+        let mock = MockTransport::new();
+        mock.reply_with_status(Method::POST, "/api/v1/cases", 500, json!({ "error": "down" }));
+        mock.reply(Method::POST, "/api/v1/tasks", task());
+
+        let result = import(state(&mock)).await;
+        assert!(result.cases_created.is_empty() && result.tasks_created.is_empty());
+        let failed: Vec<(&str, &str)> =
+            result.failures.iter().map(|failure| (failure.case.as_str(), failure.task.as_str())).collect();
+        assert_eq!(failed, [("Office move", "Book movers"), ("Office move", "Label boxes"), ("Furniture", "Order desks")]);
+        assert_eq!(mock.requests_to(Method::POST, "/api/v1/cases").len(), 2, "every case is tried");
+    }
+
+    #[tokio::test]
+    async fn failed_tasks_are_reported_and_the_import_goes_on() {
+        let mock = MockTransport::new();
+        mock.reply(Method::POST, "/api/v1/cases", case());
+        mock.reply_with_status(Method::POST, "/api/v1/tasks", 500, json!({ "error": "down" }));
+
+        let result = import(state(&mock)).await;
+        assert_eq!(result.cases_created.len(), 2);
+        assert_eq!(result.failures.len(), 3);
+        assert!(result.failures.iter().all(|failure| failure.error.starts_with("Could not create the task")));
+
+        mock.reply(Method::POST, "/api/v1/tasks", task());
+        let result = import(state(&mock)).await;
+        assert_eq!((result.tasks_created.len(), result.failures.len()), (3, 0));
+        assert!(mock.requests().iter().all(|request| request.bearer.as_deref() == Some("session-1")));
+    }
+
+    fn signed_in() -> SessionToken {
+        SessionToken(Some("session-1".to_string()))
+    }
+
+    fn any_task() -> TaskQuery {
+        TaskQuery { status: None, tag: None, task_type: None }
+    }
+
+    #[tokio::test]
+    async fn tasks_of_a_case_are_read_with_the_callers_session() {
+        let mock = MockTransport::new();
+        mock.reply(Method::GET, "/api/v1/cases/*/tasks", json!([task()]));
+
+        let Json(tasks) = get_tasks_for_case(State(state(&mock)), Path(Uuid::new_v4()), signed_in(), Query(any_task()))
+            .await
+            .unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert!(mock.requests().iter().all(|request| request.bearer.as_deref() == Some("session-1")));
+    }
+
+    #[tokio::test]
+    async fn calendar_feeds_only_hold_the_callers_tasks() {
+        let mock = MockTransport::new();
+        mock.reply(Method::GET, "/api/v1/tasks", json!([task()]))
+            .reply(Method::GET, "/api/v1/cases/*/tasks", json!([task()]));
+
+        export_calendar(State(state(&mock)), signed_in(), Query(any_task())).await.unwrap();
+        export_case_calendar(State(state(&mock)), Path(Uuid::new_v4()), signed_in()).await.unwrap();
+        assert_eq!(mock.requests().len(), 2);
+        assert!(mock.requests().iter().all(|request| request.bearer.as_deref() == Some("session-1")));
+    }
+
+    #[tokio::test]
+    async fn exports_only_hold_the_callers_tasks() {
+        let mock = MockTransport::new();
+        mock.reply(Method::GET, "/api/v1/tasks", json!([task()]));
+
+        let export = ExportQuery { format: Default::default(), columns: None };
+        export_tasks(State(state(&mock)), signed_in(), Query(any_task()), Query(export)).await.unwrap();
+        let requests = mock.requests_to(Method::GET, "/api/v1/tasks");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].bearer.as_deref(), Some("session-1"));
+    }
+
+    async fn write_back(mock: &Arc<MockTransport>, resource: &str, headers: HeaderMap, body: &str) -> Response {
+        let session = DavSession(signed_in());
+        caldav_task(State(state(mock)), Path(resource.to_string()), axum::http::Method::PUT, headers, session, body.to_string())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn caldav_clients_write_changes_back_to_their_tasks() {
+        let mock = MockTransport::new();
+        let stored = task();
+        mock.reply(Method::GET, "/api/v1/tasks/*", &stored).reply(Method::PUT, "/api/v1/tasks/*", &stored);
+        let resource = format!("{}.ics", stored["id"].as_str().unwrap());
+        let completed = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:Book movers\r\nSTATUS:COMPLETED\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+
+        let response = write_back(&mock, &resource, HeaderMap::new(), completed).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let writes = mock.requests_to(Method::PUT, "/api/v1/tasks/*");
+        assert_eq!(writes.len(), 1);
+        assert_eq!(writes[0].body["status"], "Completed");
+        assert_eq!(writes[0].body["title"], Value::Null, "an unchanged title is left alone");
+        assert!(mock.requests().iter().all(|request| request.bearer.as_deref() == Some("session-1")));
+
+        let mut outdated = HeaderMap::new();
+        outdated.insert(header::IF_MATCH, "\"1\"".parse().unwrap());
+        let response = write_back(&mock, &resource, outdated, completed).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(mock.requests_to(Method::PUT, "/api/v1/tasks/*").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn caldav_clients_cannot_create_tasks() {
+        let mock = MockTransport::new();
+        mock.reply_with_status(Method::GET, "/api/v1/tasks/*", 404, json!({ "error": "Not found" }));
+        let todo = "BEGIN:VTODO\r\nSUMMARY:New reminder\r\nEND:VTODO\r\n";
+
+        for resource in ["7C1D-REMINDER.ics".to_string(), format!("{}.ics", Uuid::new_v4())] {
+            let response = write_back(&mock, &resource, HeaderMap::new(), todo).await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        assert!(mock.requests_to(Method::PUT, "/api/v1/tasks/*").is_empty());
+    }
+
+    #[tokio::test]
+    async fn exports_page_through_the_tasks() {
+        let mock = MockTransport::new();
+        let full_page = vec![task(); EXPORT_PAGE_SIZE as usize];
+        mock.reply_to_query(Method::GET, "/api/v1/tasks", "limit=500&offset=0", &full_page)
+            .reply_to_query(Method::GET, "/api/v1/tasks", "limit=500&offset=500", json!([task()]));
+
+        let export = ExportQuery { format: Default::default(), columns: Some("title".to_string()) };
+        let response = export_tasks(State(state(&mock)), signed_in(), Query(any_task()), Query(export)).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.iter().filter(|byte| **byte == b'\n').count(), 1 + EXPORT_PAGE_SIZE as usize + 1);
+        let offsets: Vec<_> =
+            mock.requests_to(Method::GET, "/api/v1/tasks").into_iter().map(|request| request.query).collect();
+        assert_eq!(offsets, [Some("limit=500&offset=0".to_string()), Some("limit=500&offset=500".to_string())]);
+    }
+}
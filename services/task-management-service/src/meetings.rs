@@ -0,0 +1,45 @@
+//! Double-booking detection for meetings.
+//!
+//! A meeting starts at its due date and lasts for its estimate, or
+//! [`DEFAULT_MEETING_MINUTES`] without one. Two open meetings of the same
+//! user conflict when those spans overlap.
+
+use chrono::{DateTime, Duration, Utc};
+use models::{MeetingConflict, Task, TaskStatus, TaskType};
+
+/// Assumed length of a meeting that has no estimate.
+pub const DEFAULT_MEETING_MINUTES: i64 = 60;
+
+/// Start and end of an open meeting, `None` for other tasks and meetings
+/// without a date.
+fn span(task: &Task) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    if task.task_type != TaskType::Meeting || matches!(task.status, TaskStatus::Completed | TaskStatus::Cancelled) {
+        return None;
+    }
+    let start = task.due_date?;
+    let minutes = task.estimate_minutes.map_or(DEFAULT_MEETING_MINUTES, i64::from).max(1);
+    Some((start, start + Duration::minutes(minutes)))
+}
+
+/// Meetings among `others` of the same user that overlap `meeting`, in
+/// start order.
+pub fn find_conflicts(meeting: &Task, others: &[Task]) -> Vec<MeetingConflict> {
+    let Some((start, end)) = span(meeting) else {
+        return Vec::new();
+    };
+    let mut conflicts: Vec<MeetingConflict> = others
+        .iter()
+        .filter(|other| other.id != meeting.id && other.user_id == meeting.user_id)
+        .filter_map(|other| {
+            let (other_start, other_end) = span(other)?;
+            (other_start < end && start < other_end).then(|| MeetingConflict {
+                task_id: other.id,
+                title: other.title.clone(),
+                starts_at: other_start,
+                ends_at: other_end,
+            })
+        })
+        .collect();
+    conflicts.sort_by_key(|conflict| conflict.starts_at);
+    conflicts
+}
@@ -0,0 +1,97 @@
+//! JSON Schemas for tasks by type.
+//!
+//! A task type may have a JSON Schema that its tasks, as the API returns
+//! them, must satisfy.  By default meetings need a due date and shopping
+//! tasks a list of items in `metadata.items`.  `TASK_SCHEMAS_FILE` names a
+//! JSON file mapping type names (built-in or custom) to schemas that add to
+//! or replace these; mapping a type to `null` removes its schema.
+
+use anyhow::Context;
+use jsonschema::JSONSchema;
+use models::Task;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+pub struct TaskSchemas {
+    /// Compiled schemas by lowercase type name.
+    schemas: HashMap<String, JSONSchema>,
+}
+
+fn default_schemas() -> Vec<(&'static str, Value)> {
+    vec![
+        (
+            "Meeting",
+            json!({
+                "properties": { "due_date": { "type": "string", "format": "date-time" } },
+                "required": ["due_date"]
+            }),
+        ),
+        (
+            "Shopping",
+            json!({
+                "properties": {
+                    "metadata": {
+                        "type": "object",
+                        "properties": {
+                            "items": { "type": "array", "items": { "type": "string" }, "minItems": 1 }
+                        },
+                        "required": ["items"]
+                    }
+                }
+            }),
+        ),
+    ]
+}
+
+impl TaskSchemas {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut sources: HashMap<String, Value> = default_schemas()
+            .into_iter()
+            .map(|(name, schema)| (name.to_lowercase(), schema))
+            .collect();
+
+        if let Ok(path) = std::env::var("TASK_SCHEMAS_FILE") {
+            let contents = std::fs::read_to_string(&path).with_context(|| format!("Reading {}", path))?;
+            let overrides: HashMap<String, Value> =
+                serde_json::from_str(&contents).with_context(|| format!("Parsing {}", path))?;
+            for (name, schema) in overrides {
+                if schema.is_null() {
+                    sources.remove(&name.to_lowercase());
+                } else {
+                    sources.insert(name.to_lowercase(), schema);
+                }
+            }
+        }
+
+        let mut schemas = HashMap::new();
+        for (name, source) in sources {
+            let schema = JSONSchema::compile(&source)
+                .map_err(|e| anyhow::anyhow!("Invalid schema for task type '{}': {}", name, e))?;
+            schemas.insert(name, schema);
+        }
+        Ok(Self { schemas })
+    }
+
+    /// Violations of the schema for `task`'s type, each prefixed with the
+    /// offending field; empty when the type has no schema or the task fits.
+    pub fn check(&self, task: &Task) -> Vec<String> {
+        let Some(schema) = self.schemas.get(&task.task_type.label().to_lowercase()) else {
+            return Vec::new();
+        };
+        let instance = match serde_json::to_value(task) {
+            Ok(instance) => instance,
+            Err(e) => return vec![format!("task: {}", e)],
+        };
+        let result = match schema.validate(&instance) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|error| {
+                    let path = error.instance_path.to_string();
+                    let field = path.trim_start_matches('/').replace('/', ".");
+                    format!("{}: {}", if field.is_empty() { "task" } else { &field }, error)
+                })
+                .collect(),
+        };
+        result
+    }
+}
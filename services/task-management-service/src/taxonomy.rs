@@ -0,0 +1,42 @@
+//! Organization task types.
+//!
+//! Organizations may add their own task types to the built-in ones.  A task
+//! of a custom type is `TaskType::Other(name)`, and once an organization
+//! has types of its own, `Other` names must be one of them and the task's
+//! metadata has to fit the type's fields.  Organizations without custom
+//! types keep free-form `Other` names.
+
+use common::{http_client::HttpClient, ServiceError, ServiceResult};
+use models::{CustomTaskType, TaskType};
+
+/// The task types of the session user's organization.  Internal calls and
+/// users outside an organization get none.
+pub async fn load(client: &HttpClient, persistence_url: &str) -> ServiceResult<Vec<CustomTaskType>> {
+    client
+        .get::<Vec<CustomTaskType>>(&format!("{}/api/v1/task-types", persistence_url))
+        .await
+        .map_err(ServiceError::HttpClient)
+}
+
+/// Problems with a task of `task_type` carrying `metadata`; empty when it
+/// fits.  A custom type named in another case is corrected to the defined
+/// name.
+pub fn check(types: &[CustomTaskType], task_type: &mut TaskType, metadata: &serde_json::Value) -> Vec<String> {
+    let TaskType::Other(name) = task_type else {
+        return Vec::new();
+    };
+    if types.is_empty() {
+        return Vec::new();
+    }
+    match types.iter().find(|custom| custom.name.eq_ignore_ascii_case(name)) {
+        Some(custom) => {
+            name.clone_from(&custom.name);
+            custom.check_metadata(metadata)
+        }
+        None => vec![format!(
+            "Unknown task type '{}'; expected a built-in type or one of: {}",
+            name,
+            types.iter().map(|custom| custom.name.as_str()).collect::<Vec<_>>().join(", ")
+        )],
+    }
+}
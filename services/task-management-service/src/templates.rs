@@ -0,0 +1,81 @@
+//! Instantiation of [`TaskTemplate`]s into concrete task fields.
+//!
+//! Placeholders are written `{{name}}`. Besides the values supplied by the
+//! caller, `{{date}}` (today, `YYYY-MM-DD`) and `{{week}}` (ISO week number)
+//! are always available so recurring templates such as a weekly report need
+//! no input.
+
+use chrono::{DateTime, Datelike, Duration, Utc};
+use models::TaskTemplate;
+use std::collections::{BTreeSet, HashMap};
+
+/// Task fields produced from a template.
+#[derive(Debug)]
+pub struct TemplateTask {
+    pub title: String,
+    pub description: Option<String>,
+    pub due_date: Option<DateTime<Utc>>,
+    pub metadata: serde_json::Value,
+}
+
+/// Fills in a template. Fails with the names of any placeholders that have
+/// no value.
+pub fn instantiate(
+    template: &TaskTemplate,
+    values: &HashMap<String, String>,
+    now: DateTime<Utc>,
+) -> Result<TemplateTask, String> {
+    let mut values = values.clone();
+    values.entry("date".to_string()).or_insert_with(|| now.format("%Y-%m-%d").to_string());
+    values.entry("week".to_string()).or_insert_with(|| now.iso_week().week().to_string());
+
+    let mut missing = BTreeSet::new();
+    let title = render(&template.title, &values, &mut missing);
+    let description = template.description.as_deref().map(|d| render(d, &values, &mut missing));
+    let checklist: Vec<_> = template
+        .checklist
+        .iter()
+        .map(|item| serde_json::json!({ "text": render(item, &values, &mut missing), "done": false }))
+        .collect();
+
+    if !missing.is_empty() {
+        let names: Vec<_> = missing.into_iter().collect();
+        return Err(format!("Missing values for placeholders: {}", names.join(", ")));
+    }
+
+    Ok(TemplateTask {
+        title,
+        description,
+        due_date: template.due_in_days.map(|days| now + Duration::days(days)),
+        metadata: serde_json::json!({
+            "template": template.name,
+            "checklist": checklist,
+        }),
+    })
+}
+
+/// Replaces every `{{name}}` in `text`, recording names without a value.
+fn render(text: &str, values: &HashMap<String, String>, missing: &mut BTreeSet<String>) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+
+        let name = rest[start + 2..start + 2 + len].trim();
+        match values.get(name) {
+            Some(value) => output.push_str(value),
+            None => {
+                missing.insert(name.to_string());
+                output.push_str(&rest[start..start + len + 4]);
+            }
+        }
+        rest = &rest[start + len + 4..];
+    }
+
+    output.push_str(rest);
+    output
+}
@@ -0,0 +1,103 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap},
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+
+use crate::config::ServiceConfig;
+use crate::http_client::HttpClient;
+use crate::secrets::{self, Secrets};
+use crate::ServiceError;
+
+/// Carries the `INTERNAL_SERVICE_TOKEN` on calls between services.
+pub const INTERNAL_TOKEN_HEADER: &str = "x-internal-token";
+
+/// A service's shared state, as the extractors here need it.
+pub trait ServiceState: Send + Sync {
+    fn config(&self) -> &ServiceConfig;
+}
+
+/// Whether `provided` is `expected`, in time that doesn't depend on where
+/// they differ, or on the length of `expected`.
+pub fn secrets_match(provided: &str, expected: &str) -> bool {
+    let tag = |value: &str| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(expected.as_bytes()).expect("HMAC takes keys of any length");
+        mac.update(value.as_bytes());
+        mac
+    };
+    tag(expected).verify_slice(&tag(provided).finalize().into_bytes()).is_ok()
+}
+
+/// Checks that a request comes from another service: it carries the
+/// `INTERNAL_SERVICE_TOKEN` the services share.  While no token is
+/// configured no request passes.
+pub fn check_internal(headers: &HeaderMap, secrets: &Secrets) -> Result<(), ServiceError> {
+    let Some(expected) = secrets.get(secrets::INTERNAL_SERVICE_TOKEN).filter(|token| !token.is_empty()) else {
+        tracing::warn!("Refusing an internal call: INTERNAL_SERVICE_TOKEN is not set");
+        return Err(ServiceError::Unauthorized("Service calls are not configured".to_string()));
+    };
+    let provided = headers
+        .get(INTERNAL_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| ServiceError::Unauthorized("Missing session token".to_string()))?;
+    if !secrets_match(provided, &expected) {
+        return Err(ServiceError::Unauthorized("Invalid service token".to_string()));
+    }
+    Ok(())
+}
+
+/// The caller's session token, taken from `Authorization: Bearer <token>`.
+/// It may also be a personal access token.
+///
+/// Services don't validate the token themselves; they forward it so the
+/// persistence service can scope reads and writes to the session's user,
+/// and check a personal access token's scopes.
+/// Requests without a token are internal service calls, and must carry the
+/// internal service token; see [`check_internal`].
+#[derive(Clone, Default)]
+pub struct SessionToken(pub Option<String>);
+
+impl std::fmt::Debug for SessionToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let token = self.0.as_ref().map(|_| "<redacted>");
+        f.debug_tuple("SessionToken").field(&token).finish()
+    }
+}
+
+impl SessionToken {
+    /// Returns a client that forwards this token, or a plain clone of
+    /// `client` when there is none.
+    pub fn client(&self, client: &HttpClient) -> HttpClient {
+        match &self.0 {
+            Some(token) => client.with_session_token(token),
+            None => client.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<Arc<S>> for SessionToken
+where
+    S: ServiceState + 'static,
+{
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<S>) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(header::AUTHORIZATION) else {
+            check_internal(&parts.headers, &state.config().secret_values)?;
+            return Ok(SessionToken(None));
+        };
+
+        value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(|token| SessionToken(Some(token.to_string())))
+            .ok_or_else(|| ServiceError::Unauthorized("Malformed authorization header".to_string()))
+    }
+}
@@ -1,36 +1,1202 @@
+//! Service configuration.
+//!
+//! Settings are layered: built-in defaults, then the JSON file named by
+//! `SERVICE_CONFIG_FILE`, then environment variables.  The file holds the
+//! settings all services share at the top level and each service's own
+//! under its name:
+//!
+//! ```json
+//! {
+//!   "log_level": "info",
+//!   "cors": { "allowed_origins": ["https://tasks.example.com"] },
+//!   "persistence-service": { "database": { "url": "postgres://localhost/task_agent" } }
+//! }
+//! ```
+//!
+//! Every setting can still be given by its environment variable, so
+//! deployments without a file keep working.  Secrets may then be replaced
+//! by a secrets manager (see [`crate::secrets`]).  [`ServiceConfig::load`]
+//! checks the result and fails startup naming the setting at fault.
+
+use crate::discovery::{DiscoveryConfig, ServiceRegistry};
+use crate::logging::LogFormat;
+use crate::secrets::{self, Secrets, SecretsConfig};
+use axum::http::HeaderValue;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use uuid::Uuid;
+
+/// Names the JSON config file.
+pub const CONFIG_FILE_VAR: &str = "SERVICE_CONFIG_FILE";
 
-#[derive(Clone, Debug)]
+/// Other services by the short name passed to
+/// [`ServiceConfig::service_url`], with their URL variable and default.
+/// The variables take a comma-separated list for several instances.
+const SERVICES: &[(&str, &str, &str)] = &[
+    ("channel", "CHANNEL_SERVICE_URL", "http://localhost:8001"),
+    ("case-management", "CASE_MANAGEMENT_SERVICE_URL", "http://localhost:8002"),
+    ("task-management", "TASK_MANAGEMENT_SERVICE_URL", "http://localhost:8003"),
+    ("ai-agent", "AI_AGENT_SERVICE_URL", "http://localhost:8004"),
+    ("persistence", "PERSISTENCE_SERVICE_URL", "http://localhost:8005"),
+    ("email-collector", "EMAIL_SERVICE_URL", "http://localhost:8007"),
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ServiceConfig {
+    #[serde(skip)]
     pub service_name: String,
     pub port: u16,
-    pub database_url: Option<String>,
-    pub openai_api_key: Option<String>,
     pub log_level: String,
+    /// `LOG_FORMAT`: `text` or `json`.
+    pub log_format: LogFormat,
+    pub database: DatabaseConfig,
+    pub llm: LlmConfig,
+    pub email: EmailConfig,
+    pub agent: AgentConfig,
+    pub whatsapp: WhatsAppConfig,
+    pub voice: VoiceConfig,
+    pub sso: SsoConfig,
+    pub jira: JiraConfig,
+    pub notion: NotionConfig,
+    pub sharing: SharingConfig,
+    pub push: PushConfig,
+    pub outbox: OutboxConfig,
+    pub jobs: JobsConfig,
+    pub admin: AdminConfig,
+    pub cors: CorsConfig,
+    /// Base URLs of the other services by short name; a URL or a list of
+    /// them.  Seeds for [`discovery`](Self::discovery).
+    #[serde(deserialize_with = "one_or_many")]
+    pub services: BTreeMap<String, Vec<String>>,
+    pub discovery: DiscoveryConfig,
+    /// The instances of the other services, built from `services` and
+    /// `discovery` by [`load`](Self::load).
+    #[serde(skip)]
+    pub registry: Arc<ServiceRegistry>,
+    /// Where secrets come from besides the environment.
+    pub secrets: SecretsConfig,
+    /// The current secrets, which may rotate while the service runs.
+    #[serde(skip)]
+    pub secret_values: Arc<Secrets>,
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    let services = BTreeMap::<String, OneOrMany>::deserialize(deserializer)?;
+    Ok(services
+        .into_iter()
+        .map(|(name, urls)| match urls {
+            OneOrMany::One(url) => (name, vec![url]),
+            OneOrMany::Many(urls) => (name, urls),
+        })
+        .collect())
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// `DATABASE_URL`
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmConfig {
+    /// `LLM_PROVIDER`: where message content may be sent.
+    pub provider: LlmProvider,
+    /// `OPENAI_API_KEY`; without one the AI agent falls back to rule-based
+    /// extraction.  Never sent to a local model.
+    pub api_key: Option<String>,
+    /// `OPENAI_MODEL`
+    pub model: String,
+    /// `LLM_MAX_INPUT_TOKENS`; longer messages are summarized first.
+    pub max_input_tokens: Option<usize>,
+    /// `OPENAI_BASE_URL`, for an OpenAI-compatible endpoint or a mock.
+    pub base_url: String,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            provider: LlmProvider::default(),
+            api_key: None,
+            model: "gpt-3.5-turbo".to_string(),
+            max_input_tokens: None,
+            base_url: "https://api.openai.com/v1".to_string(),
+        }
+    }
+}
+
+/// The model the AI agent uses.  Deployments that must not send email
+/// content to external APIs use a local model or none at all.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmProvider {
+    /// OpenAI, or the OpenAI-compatible API at `llm.base_url`, with
+    /// `llm.api_key`.  Organizations may configure their own.
+    #[default]
+    OpenAi,
+    /// An Ollama or llama.cpp server at `llm.base_url`, serving its
+    /// OpenAI-compatible API without a key.  Organization settings are
+    /// ignored.
+    Local,
+    /// No model: the rule-based extractor handles every message.
+    Rules,
+}
+
+impl LlmProvider {
+    /// Whether message content stays within the deployment.
+    pub fn is_offline(self) -> bool {
+        self != LlmProvider::OpenAi
+    }
+}
+
+impl std::str::FromStr for LlmProvider {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "openai" => Ok(LlmProvider::OpenAi),
+            "local" | "ollama" | "llamacpp" | "llama.cpp" => Ok(LlmProvider::Local),
+            "rules" | "offline" => Ok(LlmProvider::Rules),
+            other => Err(format!("unknown LLM provider '{}', expected openai, local or rules", other)),
+        }
+    }
+}
+
+/// How the email collector reads connected mailboxes and the Azure app the
+/// dashboard connects them with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailConfig {
+    /// `IMAP_USERNAME`.  No longer used, as users connect their mailboxes
+    /// from the dashboard; kept so existing configurations still load.
+    pub username: Option<String>,
+    /// `GRAPH_MAIL_FOLDERS`, comma-separated in the environment.
+    pub folders: Vec<String>,
+    /// `EMAIL_POLL_INTERVAL_SECS`
+    pub poll_interval_secs: u64,
+    /// `EMAIL_PROCESSING_CONCURRENCY`, how many email threads of a folder
+    /// are processed at once.
+    pub processing_concurrency: usize,
+    /// `EMAIL_PRIORITY_MAPPING`, comma-separated in the environment: how
+    /// an email's importance, flag and categories set the priority of its
+    /// case and tasks, e.g. `importance:high=High, category:Red=Critical`.
+    /// The email collector's defaults apply when empty.
+    pub priority_mapping: Vec<String>,
+    /// `EMAIL_NOISE_ACTION`: what becomes of newsletters and notifications.
+    pub noise_action: NoiseAction,
+    /// `EMAIL_NOISE_SENDERS`, comma-separated in the environment: senders
+    /// whose email is noise, by address (`news@vendor.com`), domain
+    /// (`@vendor.com`) or local part (`noreply@`).  The email collector's
+    /// defaults apply when empty.
+    pub noise_senders: Vec<String>,
+    /// `GRAPH_BASE_URL`, the Microsoft Graph API root.
+    pub graph_url: String,
+    /// `AZURE_CLIENT_ID`, `AZURE_CLIENT_SECRET` and `AZURE_TENANT_ID`;
+    /// all three or none.
+    pub azure: Option<AzureConfig>,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            username: None,
+            folders: Vec::new(),
+            poll_interval_secs: 60,
+            processing_concurrency: 4,
+            priority_mapping: Vec::new(),
+            noise_action: NoiseAction::default(),
+            noise_senders: Vec::new(),
+            graph_url: "https://graph.microsoft.com/v1.0".to_string(),
+            azure: None,
+        }
+    }
+}
+
+/// What the email collector does with newsletters and notifications, told
+/// apart by their `List-Unsubscribe` and similar headers or their sender.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoiseAction {
+    /// File them in a low-priority review case of the mailbox's user
+    /// instead of extracting tasks.
+    #[default]
+    Review,
+    /// File them for review and move them to the mailbox's Archive folder.
+    Archive,
+    /// Treat them like any other email.
+    Process,
+}
+
+impl std::str::FromStr for NoiseAction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "review" => Ok(NoiseAction::Review),
+            "archive" => Ok(NoiseAction::Archive),
+            "process" => Ok(NoiseAction::Process),
+            other => Err(format!("unknown noise action '{}', expected review, archive or process", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AzureConfig {
+    pub client_id: String,
+    /// May come from the secrets manager instead.
+    #[serde(default)]
+    pub client_secret: String,
+    pub tenant_id: String,
+}
+
+/// How the AI agent extracts tasks and when it sends summaries.  Unset
+/// numbers take the agent's defaults.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentConfig {
+    /// `AI_REVIEW_CONFIDENCE_THRESHOLD`: tasks extracted with less
+    /// confidence wait for review.
+    pub review_confidence_threshold: Option<f32>,
+    /// `AI_MAX_FEW_SHOT_EXAMPLES`
+    pub max_few_shot_examples: Option<usize>,
+    /// `AI_MAX_AGENT_STEPS`: tool calls the agent may make for a message.
+    pub max_steps: Option<usize>,
+    /// `AI_PII_REDACTION`, comma-separated in the environment: the kinds of
+    /// personal data redacted before content goes to an external model, or
+    /// `none`.
+    pub pii_redaction: Option<Vec<String>>,
+    /// `AI_IMPORTANT_SENDERS`, comma-separated in the environment:
+    /// addresses and `@domain`s whose cases are scored as more urgent.
+    pub important_senders: Vec<String>,
+    /// `WEEKLY_SUMMARY_ENABLED`
+    pub weekly_summary_enabled: bool,
+    /// `WEEKLY_SUMMARY_INTERVAL_SECS`, which is also how long a summary
+    /// covers.
+    pub weekly_summary_interval_secs: u64,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            review_confidence_threshold: None,
+            max_few_shot_examples: None,
+            max_steps: None,
+            pii_redaction: None,
+            important_senders: Vec::new(),
+            weekly_summary_enabled: true,
+            weekly_summary_interval_secs: 7 * 24 * 3600,
+        }
+    }
+}
+
+/// The WhatsApp Business channel of the channel service.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WhatsAppConfig {
+    /// `WHATSAPP_VERIFY_TOKEN`, entered when subscribing the webhook.
+    pub verify_token: Option<String>,
+    /// `WHATSAPP_APP_SECRET`; events are rejected without it.
+    pub app_secret: Option<String>,
+    /// `WHATSAPP_ACCESS_TOKEN`; no replies are sent without it.
+    pub access_token: Option<String>,
+    /// `WHATSAPP_API_URL`
+    pub api_url: String,
+    /// `WHATSAPP_REPLY_TEMPLATE`: an approved template with one body
+    /// parameter, for replies outside the 24 hour service window.
+    pub reply_template: Option<String>,
+    /// `WHATSAPP_TEMPLATE_LANGUAGE`
+    pub template_language: String,
+    /// `WHATSAPP_OWNERS`, `phone_number_id=user id` pairs, comma-separated
+    /// in the environment: the user whose cases the messages to each
+    /// business number go to.  Messages to other numbers are ignored.
+    pub owners: BTreeMap<String, Uuid>,
+}
+
+impl Default for WhatsAppConfig {
+    fn default() -> Self {
+        Self {
+            verify_token: None,
+            app_secret: None,
+            access_token: None,
+            api_url: "https://graph.facebook.com/v19.0".to_string(),
+            reply_template: None,
+            template_language: "en".to_string(),
+            owners: BTreeMap::new(),
+        }
+    }
+}
+
+/// Transcription of voice notes by the channel service.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VoiceConfig {
+    /// `TRANSCRIPTION_PROVIDER`
+    pub provider: TranscriptionProvider,
+    /// `TRANSCRIPTION_URL`, the local Whisper server's endpoint.
+    pub local_url: String,
+    /// `TRANSCRIPTION_MODEL`, the OpenAI model.
+    pub model: String,
+    /// `VOICE_MAX_UPLOAD_BYTES`, the largest recording accepted.
+    pub max_upload_bytes: usize,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            provider: TranscriptionProvider::default(),
+            local_url: "http://localhost:8080/inference".to_string(),
+            model: "whisper-1".to_string(),
+            // OpenAI's limit.
+            max_upload_bytes: 25 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptionProvider {
+    /// OpenAI's Whisper API, with the key and base URL of `llm`.
+    #[default]
+    OpenAi,
+    /// A Whisper server such as whisper.cpp's at `voice.local_url`.
+    Local,
+}
+
+impl std::str::FromStr for TranscriptionProvider {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "openai" => Ok(TranscriptionProvider::OpenAi),
+            "local" => Ok(TranscriptionProvider::Local),
+            other => Err(format!("unknown transcription provider '{}', expected openai or local", other)),
+        }
+    }
+}
+
+/// Sign-in with OpenID Connect providers on the dashboard.  A provider is
+/// enabled once all of its settings are given.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SsoConfig {
+    /// `SSO_GOOGLE_CLIENT_ID`
+    pub google_client_id: Option<String>,
+    /// `SSO_GOOGLE_CLIENT_SECRET`
+    pub google_client_secret: Option<String>,
+    /// `SSO_MICROSOFT_CLIENT_ID`
+    pub microsoft_client_id: Option<String>,
+    /// `SSO_MICROSOFT_CLIENT_SECRET`
+    pub microsoft_client_secret: Option<String>,
+    /// `SSO_MICROSOFT_TENANT`, a tenant id.
+    pub microsoft_tenant: Option<String>,
+    /// `SSO_OIDC_ISSUER`
+    pub oidc_issuer: Option<String>,
+    /// `SSO_OIDC_CLIENT_ID`
+    pub oidc_client_id: Option<String>,
+    /// `SSO_OIDC_CLIENT_SECRET`
+    pub oidc_client_secret: Option<String>,
+    /// `SSO_OIDC_NAME`, the label of its button.
+    pub oidc_name: String,
+    /// `SSO_REDIRECT_BASE_URL`, the dashboard's public URL;
+    /// `http://localhost:<port>` when unset.
+    pub redirect_base_url: Option<String>,
+}
+
+impl Default for SsoConfig {
+    fn default() -> Self {
+        Self {
+            google_client_id: None,
+            google_client_secret: None,
+            microsoft_client_id: None,
+            microsoft_client_secret: None,
+            microsoft_tenant: None,
+            oidc_issuer: None,
+            oidc_client_id: None,
+            oidc_client_secret: None,
+            oidc_name: "Single sign-on".to_string(),
+            redirect_base_url: None,
+        }
+    }
+}
+
+/// Jira sync of Work tasks by the task management service.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JiraConfig {
+    /// `JIRA_BASE_URL`, e.g. `https://acme.atlassian.net`; sync is off
+    /// without it.
+    pub base_url: Option<String>,
+    /// `JIRA_EMAIL`
+    pub email: Option<String>,
+    /// `JIRA_API_TOKEN`; sent as a bearer token without `email`.
+    pub api_token: Option<String>,
+    /// `JIRA_PROJECT`, key of the default project.
+    pub project: Option<String>,
+    /// `JIRA_PROJECT_MAP`, comma-separated in the environment: projects by
+    /// task tag, e.g. `infra=OPS`.
+    pub project_map: Vec<String>,
+    /// `JIRA_ISSUE_TYPE`
+    pub issue_type: String,
+    /// `JIRA_STATUS_MAP`, comma-separated in the environment: task
+    /// statuses by Jira status name, e.g. `Blocked=OnHold`.
+    pub status_map: Vec<String>,
+    /// `JIRA_WEBHOOK_SECRET`; the webhook is refused without it.
+    pub webhook_secret: Option<String>,
+}
+
+impl Default for JiraConfig {
+    fn default() -> Self {
+        Self {
+            base_url: None,
+            email: None,
+            api_token: None,
+            project: None,
+            project_map: Vec::new(),
+            issue_type: "Task".to_string(),
+            status_map: Vec::new(),
+            webhook_secret: None,
+        }
+    }
+}
+
+/// Export of cases to Notion by the case management service.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotionConfig {
+    /// `NOTION_API_TOKEN`; export is off without it.
+    pub api_token: Option<String>,
+    /// `NOTION_DATABASE_ID`, used when a request names no database.
+    pub database_id: Option<String>,
+    /// `NOTION_TITLE_PROPERTY`
+    pub title_property: String,
+    /// `NOTION_API_URL`
+    pub api_url: String,
+}
+
+impl Default for NotionConfig {
+    fn default() -> Self {
+        Self {
+            api_token: None,
+            database_id: None,
+            title_property: "Name".to_string(),
+            api_url: "https://api.notion.com/v1".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SharingConfig {
+    /// `SHARE_LINK_SECRET`, which signs case share links; they are
+    /// disabled without it.
+    pub link_secret: Option<String>,
+}
+
+/// Web push of notifications by the persistence service.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PushConfig {
+    /// `VAPID_PRIVATE_KEY`, a base64url encoded P-256 scalar; one is kept
+    /// in the database when unset.
+    pub vapid_private_key: Option<String>,
+    /// `VAPID_SUBJECT`
+    pub vapid_subject: String,
+}
+
+impl Default for PushConfig {
+    fn default() -> Self {
+        Self { vapid_private_key: None, vapid_subject: "mailto:admin@localhost".to_string() }
+    }
+}
+
+/// Publication of task and case changes by the persistence service.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutboxConfig {
+    /// `OUTBOX_WEBHOOK_URLS`, comma-separated in the environment.
+    pub webhook_urls: Vec<String>,
+    /// `OUTBOX_WEBHOOK_SECRET`, which signs each delivery.
+    pub webhook_secret: Option<String>,
+    /// `OUTBOX_LAG_WARN_SECS`
+    pub lag_warn_secs: u64,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self { webhook_urls: Vec::new(), webhook_secret: None, lag_warn_secs: 300 }
+    }
+}
+
+/// The job workers, and how often the recurring jobs run.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    /// `JOB_POLL_SECS`, how often a worker checks the queue.
+    pub poll_secs: u64,
+    /// `JOB_LEASE_SECS`, how long a job may run before another worker
+    /// takes it over.
+    pub lease_secs: u64,
+    /// `NOTIFICATION_SWEEP_SECS`
+    pub notification_sweep_secs: u64,
+    /// `CASE_RETENTION_SWEEP_SECS`
+    pub case_retention_sweep_secs: u64,
+    /// `SESSION_CLEANUP_SECS`
+    pub session_cleanup_secs: u64,
+    /// `OUTBOX_RELAY_SECS`
+    pub outbox_relay_secs: u64,
+    /// `FAILED_MESSAGE_RETRY_SECS`
+    pub failed_message_retry_secs: u64,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            poll_secs: 5,
+            lease_secs: 300,
+            notification_sweep_secs: 300,
+            case_retention_sweep_secs: 3600,
+            session_cleanup_secs: 3600,
+            outbox_relay_secs: 5,
+            failed_message_retry_secs: 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// `ADMIN_API_TOKEN`, sent as `X-Admin-Token` to the admin endpoints;
+    /// they are off without it.
+    pub api_token: Option<String>,
+    /// `ADMIN_EMAILS`, comma-separated in the environment: accounts made
+    /// administrators on start.
+    pub emails: Vec<String>,
+    /// `SEED_ENABLED`, for the demo data endpoint.
+    pub seed_enabled: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// `CORS_ALLOWED_ORIGINS`, comma-separated in the environment.  Any
+    /// origin is allowed when empty.
+    pub allowed_origins: Vec<String>,
+}
+
+impl CorsConfig {
+    pub fn layer(&self) -> CorsLayer {
+        if self.allowed_origins.is_empty() {
+            return CorsLayer::permissive();
+        }
+        let origins = self
+            .allowed_origins
+            .iter()
+            .filter_map(|origin| HeaderValue::from_str(origin).ok())
+            .collect::<Vec<_>>();
+        CorsLayer::permissive().allow_origin(AllowOrigin::list(origins))
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Config file {path}: {message}")]
+    File { path: String, message: String },
+
+    #[error("Invalid {setting}: {message}")]
+    Invalid { setting: String, message: String },
+
+    #[error("{service} requires {setting}")]
+    Missing { service: String, setting: String },
+
+    #[error("Loading secrets: {0}")]
+    Secrets(String),
+}
+
+impl ConfigError {
+    fn invalid(setting: &str, message: impl ToString) -> Self {
+        ConfigError::Invalid { setting: setting.to_string(), message: message.to_string() }
+    }
 }
 
 impl ServiceConfig {
-    pub fn from_env(service_name: &str, default_port: u16) -> Self {
+    fn defaults(service_name: &str, default_port: u16) -> Self {
         Self {
             service_name: service_name.to_string(),
-            port: env::var("PORT")
-                .unwrap_or_else(|_| default_port.to_string())
-                .parse()
-                .unwrap_or(default_port),
-            database_url: env::var("DATABASE_URL").ok(),
-            openai_api_key: env::var("OPENAI_API_KEY").ok(),
-            log_level: env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string()),
+            port: default_port,
+            log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            database: DatabaseConfig::default(),
+            llm: LlmConfig::default(),
+            email: EmailConfig::default(),
+            agent: AgentConfig::default(),
+            whatsapp: WhatsAppConfig::default(),
+            voice: VoiceConfig::default(),
+            sso: SsoConfig::default(),
+            jira: JiraConfig::default(),
+            notion: NotionConfig::default(),
+            sharing: SharingConfig::default(),
+            push: PushConfig::default(),
+            outbox: OutboxConfig::default(),
+            jobs: JobsConfig::default(),
+            admin: AdminConfig::default(),
+            cors: CorsConfig::default(),
+            services: SERVICES
+                .iter()
+                .map(|(name, _, url)| (name.to_string(), vec![url.to_string()]))
+                .collect(),
+            discovery: DiscoveryConfig::default(),
+            registry: Arc::default(),
+            secrets: SecretsConfig::default(),
+            secret_values: Arc::default(),
+        }
+    }
+
+    /// The defaults of `service_name`, without reading the environment:
+    /// the other services at their default local URLs, sharing
+    /// [`TEST_INTERNAL_TOKEN`](crate::testing::TEST_INTERNAL_TOKEN).
+    #[cfg(feature = "test-util")]
+    pub fn for_tests(service_name: &str) -> Self {
+        let mut config = Self::defaults(service_name, 0);
+        config.registry = Arc::new(ServiceRegistry::new(&config.discovery, config.services.clone()));
+        config.secret_values = Arc::new(Secrets::fixed(&[(
+            secrets::INTERNAL_SERVICE_TOKEN,
+            crate::testing::TEST_INTERNAL_TOKEN,
+        )]));
+        config
+    }
+
+    /// Loads and validates the configuration of `service_name`.
+    pub async fn load(service_name: &str, default_port: u16) -> Result<Self, ConfigError> {
+        let mut config = Self::defaults(service_name, default_port);
+        if let Some(path) = var(CONFIG_FILE_VAR) {
+            config.apply_file(&path)?;
+        }
+        config.apply_env()?;
+        config.apply_secrets().await?;
+        config.validate()?;
+        config.registry = Arc::new(ServiceRegistry::new(&config.discovery, config.services.clone()));
+        Ok(config)
+    }
+
+    /// Layers the file's shared settings, then this service's section, over
+    /// the current ones.  Sections of other services are skipped.
+    fn apply_file(&mut self, path: &str) -> Result<(), ConfigError> {
+        let file_error = |message: String| ConfigError::File { path: path.to_string(), message };
+        let contents = std::fs::read_to_string(path).map_err(|e| file_error(e.to_string()))?;
+        let Value::Object(mut file) = serde_json::from_str(&contents).map_err(|e| file_error(e.to_string()))?
+        else {
+            return Err(file_error("expected a JSON object".to_string()));
+        };
+
+        let own = file.remove(&self.service_name);
+        file.retain(|key, _| !key.ends_with("-service"));
+        let mut layered = serde_json::to_value(&*self).map_err(|e| file_error(e.to_string()))?;
+        merge(&mut layered, Value::Object(file));
+        if let Some(own) = own {
+            merge(&mut layered, own);
+        }
+
+        let service_name = std::mem::take(&mut self.service_name);
+        *self = serde_path_to_error::deserialize(layered).map_err(|e| file_error(e.to_string()))?;
+        self.service_name = service_name;
+        Ok(())
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(port) = var("PORT") {
+            self.port = port.parse().map_err(|e| ConfigError::invalid("PORT", e))?;
+        }
+        if let Some(level) = var("RUST_LOG") {
+            self.log_level = level;
+        }
+        if let Some(format) = var("LOG_FORMAT") {
+            self.log_format = format.parse().map_err(|e: String| ConfigError::invalid("LOG_FORMAT", e))?;
+        }
+        if let Some(url) = var("DATABASE_URL") {
+            self.database.url = Some(url);
+        }
+        if let Some(provider) = var("LLM_PROVIDER") {
+            self.llm.provider = provider.parse().map_err(|e: String| ConfigError::invalid("LLM_PROVIDER", e))?;
+        }
+        if let Some(key) = var("OPENAI_API_KEY") {
+            self.llm.api_key = Some(key);
+        }
+        if let Some(model) = var("OPENAI_MODEL") {
+            self.llm.model = model;
+        }
+        if let Some(tokens) = var("LLM_MAX_INPUT_TOKENS") {
+            let tokens = tokens.parse().map_err(|e| ConfigError::invalid("LLM_MAX_INPUT_TOKENS", e))?;
+            self.llm.max_input_tokens = Some(tokens);
+        }
+        if let Some(url) = var("OPENAI_BASE_URL") {
+            self.llm.base_url = url;
+        }
+        if let Some(username) = var("IMAP_USERNAME") {
+            self.email.username = Some(username);
+        }
+        if let Some(folders) = var("GRAPH_MAIL_FOLDERS") {
+            self.email.folders = list(&folders);
+        }
+        if let Some(secs) = var("EMAIL_POLL_INTERVAL_SECS") {
+            let secs = secs.parse().map_err(|e| ConfigError::invalid("EMAIL_POLL_INTERVAL_SECS", e))?;
+            self.email.poll_interval_secs = secs;
+        }
+        if let Some(concurrency) = var("EMAIL_PROCESSING_CONCURRENCY") {
+            let concurrency = concurrency.parse().map_err(|e| ConfigError::invalid("EMAIL_PROCESSING_CONCURRENCY", e))?;
+            self.email.processing_concurrency = concurrency;
+        }
+        if let Some(mapping) = var("EMAIL_PRIORITY_MAPPING") {
+            self.email.priority_mapping = list(&mapping);
+        }
+        if let Some(action) = var("EMAIL_NOISE_ACTION") {
+            self.email.noise_action = action.parse().map_err(|e: String| ConfigError::invalid("EMAIL_NOISE_ACTION", e))?;
+        }
+        if let Some(senders) = var("EMAIL_NOISE_SENDERS") {
+            self.email.noise_senders = list(&senders);
+        }
+        if let Some(url) = var("GRAPH_BASE_URL") {
+            self.email.graph_url = url;
+        }
+        let azure = [var("AZURE_CLIENT_ID"), var("AZURE_CLIENT_SECRET"), var("AZURE_TENANT_ID")];
+        if azure.iter().any(Option::is_some) {
+            let [client_id, client_secret, tenant_id] = azure;
+            let current = self.email.azure.take();
+            let current = current.as_ref();
+            let pick = |value: Option<String>, current: Option<&String>| {
+                value.or_else(|| current.cloned()).unwrap_or_default()
+            };
+            self.email.azure = Some(AzureConfig {
+                client_id: pick(client_id, current.map(|azure| &azure.client_id)),
+                client_secret: pick(client_secret, current.map(|azure| &azure.client_secret)),
+                tenant_id: pick(tenant_id, current.map(|azure| &azure.tenant_id)),
+            });
+        }
+        if let Some(threshold) = var("AI_REVIEW_CONFIDENCE_THRESHOLD") {
+            let threshold = threshold.parse().map_err(|e| ConfigError::invalid("AI_REVIEW_CONFIDENCE_THRESHOLD", e))?;
+            self.agent.review_confidence_threshold = Some(threshold);
+        }
+        if let Some(examples) = var("AI_MAX_FEW_SHOT_EXAMPLES") {
+            let examples = examples.parse().map_err(|e| ConfigError::invalid("AI_MAX_FEW_SHOT_EXAMPLES", e))?;
+            self.agent.max_few_shot_examples = Some(examples);
+        }
+        if let Some(steps) = var("AI_MAX_AGENT_STEPS") {
+            let steps = steps.parse().map_err(|e| ConfigError::invalid("AI_MAX_AGENT_STEPS", e))?;
+            self.agent.max_steps = Some(steps);
+        }
+        if let Some(kinds) = var("AI_PII_REDACTION") {
+            self.agent.pii_redaction = Some(list(&kinds));
+        }
+        if let Some(senders) = var("AI_IMPORTANT_SENDERS") {
+            self.agent.important_senders = list(&senders);
+        }
+        if let Some(enabled) = var("WEEKLY_SUMMARY_ENABLED") {
+            let enabled = enabled.parse().map_err(|e| ConfigError::invalid("WEEKLY_SUMMARY_ENABLED", e))?;
+            self.agent.weekly_summary_enabled = enabled;
+        }
+        if let Some(secs) = var("WEEKLY_SUMMARY_INTERVAL_SECS") {
+            let secs = secs.parse().map_err(|e| ConfigError::invalid("WEEKLY_SUMMARY_INTERVAL_SECS", e))?;
+            self.agent.weekly_summary_interval_secs = secs;
+        }
+        if let Some(token) = var("WHATSAPP_VERIFY_TOKEN") {
+            self.whatsapp.verify_token = Some(token);
+        }
+        if let Some(secret) = var("WHATSAPP_APP_SECRET") {
+            self.whatsapp.app_secret = Some(secret);
+        }
+        if let Some(token) = var("WHATSAPP_ACCESS_TOKEN") {
+            self.whatsapp.access_token = Some(token);
+        }
+        if let Some(url) = var("WHATSAPP_API_URL") {
+            self.whatsapp.api_url = url;
+        }
+        if let Some(template) = var("WHATSAPP_REPLY_TEMPLATE") {
+            self.whatsapp.reply_template = Some(template);
+        }
+        if let Some(language) = var("WHATSAPP_TEMPLATE_LANGUAGE") {
+            self.whatsapp.template_language = language;
+        }
+        if let Some(owners) = var("WHATSAPP_OWNERS") {
+            self.whatsapp.owners = list(&owners)
+                .iter()
+                .map(|pair| {
+                    let (number, owner) = pair
+                        .split_once('=')
+                        .ok_or_else(|| ConfigError::invalid("WHATSAPP_OWNERS", format!("expected number=user id, got '{}'", pair)))?;
+                    let owner = owner.trim().parse().map_err(|e| ConfigError::invalid("WHATSAPP_OWNERS", e))?;
+                    Ok((number.trim().to_string(), owner))
+                })
+                .collect::<Result<_, ConfigError>>()?;
+        }
+        if let Some(provider) = var("TRANSCRIPTION_PROVIDER") {
+            self.voice.provider = provider.parse().map_err(|e: String| ConfigError::invalid("TRANSCRIPTION_PROVIDER", e))?;
+        }
+        if let Some(url) = var("TRANSCRIPTION_URL") {
+            self.voice.local_url = url;
+        }
+        if let Some(model) = var("TRANSCRIPTION_MODEL") {
+            self.voice.model = model;
+        }
+        if let Some(bytes) = var("VOICE_MAX_UPLOAD_BYTES") {
+            let bytes = bytes.parse().map_err(|e| ConfigError::invalid("VOICE_MAX_UPLOAD_BYTES", e))?;
+            self.voice.max_upload_bytes = bytes;
+        }
+        let sso = [
+            ("SSO_GOOGLE_CLIENT_ID", &mut self.sso.google_client_id),
+            ("SSO_GOOGLE_CLIENT_SECRET", &mut self.sso.google_client_secret),
+            ("SSO_MICROSOFT_CLIENT_ID", &mut self.sso.microsoft_client_id),
+            ("SSO_MICROSOFT_CLIENT_SECRET", &mut self.sso.microsoft_client_secret),
+            ("SSO_MICROSOFT_TENANT", &mut self.sso.microsoft_tenant),
+            ("SSO_OIDC_ISSUER", &mut self.sso.oidc_issuer),
+            ("SSO_OIDC_CLIENT_ID", &mut self.sso.oidc_client_id),
+            ("SSO_OIDC_CLIENT_SECRET", &mut self.sso.oidc_client_secret),
+            ("SSO_REDIRECT_BASE_URL", &mut self.sso.redirect_base_url),
+        ];
+        for (variable, setting) in sso {
+            if let Some(value) = var(variable) {
+                *setting = Some(value.trim().to_string());
+            }
+        }
+        if let Some(name) = var("SSO_OIDC_NAME") {
+            self.sso.oidc_name = name;
+        }
+        let jira = [
+            ("JIRA_BASE_URL", &mut self.jira.base_url),
+            ("JIRA_EMAIL", &mut self.jira.email),
+            ("JIRA_API_TOKEN", &mut self.jira.api_token),
+            ("JIRA_PROJECT", &mut self.jira.project),
+            ("JIRA_WEBHOOK_SECRET", &mut self.jira.webhook_secret),
+        ];
+        for (variable, setting) in jira {
+            if let Some(value) = var(variable) {
+                *setting = Some(value.trim().to_string());
+            }
+        }
+        if let Some(projects) = var("JIRA_PROJECT_MAP") {
+            self.jira.project_map = list(&projects);
+        }
+        if let Some(issue_type) = var("JIRA_ISSUE_TYPE") {
+            self.jira.issue_type = issue_type.trim().to_string();
+        }
+        if let Some(statuses) = var("JIRA_STATUS_MAP") {
+            self.jira.status_map = list(&statuses);
+        }
+        if let Some(token) = var("NOTION_API_TOKEN") {
+            self.notion.api_token = Some(token.trim().to_string());
+        }
+        if let Some(database_id) = var("NOTION_DATABASE_ID") {
+            self.notion.database_id = Some(database_id.trim().to_string());
+        }
+        if let Some(property) = var("NOTION_TITLE_PROPERTY") {
+            self.notion.title_property = property.trim().to_string();
+        }
+        if let Some(url) = var("NOTION_API_URL") {
+            self.notion.api_url = url;
         }
+        if let Some(secret) = var("SHARE_LINK_SECRET") {
+            self.sharing.link_secret = Some(secret);
+        }
+        if let Some(key) = var("VAPID_PRIVATE_KEY") {
+            self.push.vapid_private_key = Some(key);
+        }
+        if let Some(subject) = var("VAPID_SUBJECT") {
+            self.push.vapid_subject = subject;
+        }
+        if let Some(urls) = var("OUTBOX_WEBHOOK_URLS") {
+            self.outbox.webhook_urls = list(&urls);
+        }
+        if let Some(secret) = var("OUTBOX_WEBHOOK_SECRET") {
+            self.outbox.webhook_secret = Some(secret);
+        }
+        if let Some(secs) = var("OUTBOX_LAG_WARN_SECS") {
+            let secs = secs.parse().map_err(|e| ConfigError::invalid("OUTBOX_LAG_WARN_SECS", e))?;
+            self.outbox.lag_warn_secs = secs;
+        }
+        let jobs = [
+            ("JOB_POLL_SECS", &mut self.jobs.poll_secs),
+            ("JOB_LEASE_SECS", &mut self.jobs.lease_secs),
+            ("NOTIFICATION_SWEEP_SECS", &mut self.jobs.notification_sweep_secs),
+            ("CASE_RETENTION_SWEEP_SECS", &mut self.jobs.case_retention_sweep_secs),
+            ("SESSION_CLEANUP_SECS", &mut self.jobs.session_cleanup_secs),
+            ("OUTBOX_RELAY_SECS", &mut self.jobs.outbox_relay_secs),
+            ("FAILED_MESSAGE_RETRY_SECS", &mut self.jobs.failed_message_retry_secs),
+        ];
+        for (variable, setting) in jobs {
+            if let Some(secs) = var(variable) {
+                *setting = secs.parse().map_err(|e| ConfigError::invalid(variable, e))?;
+            }
+        }
+        if let Some(token) = var("ADMIN_API_TOKEN") {
+            self.admin.api_token = Some(token);
+        }
+        if let Some(emails) = var("ADMIN_EMAILS") {
+            self.admin.emails = list(&emails);
+        }
+        if let Some(enabled) = var("SEED_ENABLED") {
+            let enabled = enabled.parse().map_err(|e| ConfigError::invalid("SEED_ENABLED", e))?;
+            self.admin.seed_enabled = enabled;
+        }
+        if let Some(origins) = var("CORS_ALLOWED_ORIGINS") {
+            self.cors.allowed_origins = list(&origins);
+        }
+        for (name, variable, _) in SERVICES {
+            if let Some(urls) = var(variable) {
+                self.services.insert(name.to_string(), list(&urls));
+            }
+        }
+        if let Some(backend) = var("SERVICE_DISCOVERY") {
+            self.discovery.backend = backend.parse().map_err(|e: String| ConfigError::invalid("SERVICE_DISCOVERY", e))?;
+        }
+        if let Some(url) = var("CONSUL_URL") {
+            self.discovery.consul_url = url;
+        }
+        if let Some(secs) = var("SERVICE_DISCOVERY_REFRESH_SECS") {
+            let secs = secs.parse().map_err(|e| ConfigError::invalid("SERVICE_DISCOVERY_REFRESH_SECS", e))?;
+            self.discovery.refresh_secs = secs;
+        }
+        if let Some(enabled) = var("SERVICE_HEALTH_CHECKS") {
+            let enabled = enabled.parse().map_err(|e| ConfigError::invalid("SERVICE_HEALTH_CHECKS", e))?;
+            self.discovery.health_checks = enabled;
+        }
+        if let Some(backend) = var("SECRETS_BACKEND") {
+            self.secrets.backend = backend.parse().map_err(|e: String| ConfigError::invalid("SECRETS_BACKEND", e))?;
+        }
+        if let Some(addr) = var("VAULT_ADDR") {
+            self.secrets.vault_addr = addr;
+        }
+        if let Some(path) = var("VAULT_SECRET_PATH") {
+            self.secrets.vault_path = Some(path);
+        }
+        if let Some(region) = var("AWS_REGION") {
+            self.secrets.aws_region = Some(region);
+        }
+        if let Some(secret_id) = var("AWS_SECRET_ID") {
+            self.secrets.aws_secret_id = Some(secret_id);
+        }
+        if let Some(secs) = var("SECRETS_REFRESH_SECS") {
+            let secs = secs.parse().map_err(|e| ConfigError::invalid("SECRETS_REFRESH_SECS", e))?;
+            self.secrets.refresh_secs = secs;
+        }
+        Ok(())
     }
 
+    /// Fetches the secrets, with the settings so far as the fallback, and
+    /// puts their current values in place.
+    async fn apply_secrets(&mut self) -> Result<(), ConfigError> {
+        let mut fallback = HashMap::new();
+        let settings = [
+            (secrets::OPENAI_API_KEY, self.llm.api_key.clone()),
+            (secrets::AZURE_CLIENT_SECRET, self.email.azure.as_ref().map(|azure| azure.client_secret.clone())),
+            (secrets::DATABASE_URL, self.database.url.clone()),
+            (secrets::DATABASE_PASSWORD, var(secrets::DATABASE_PASSWORD)),
+            (secrets::TOKEN_ENCRYPTION_KEY, var(secrets::TOKEN_ENCRYPTION_KEY)),
+            (secrets::OAUTH_STATE_SECRET, var(secrets::OAUTH_STATE_SECRET)),
+            (secrets::INTERNAL_SERVICE_TOKEN, var(secrets::INTERNAL_SERVICE_TOKEN)),
+        ];
+        for (name, value) in settings {
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                fallback.insert(name.to_string(), value);
+            }
+        }
+
+        let values = Secrets::load(&self.secrets, fallback)
+            .await
+            .map_err(|e| ConfigError::Secrets(e.to_string()))?;
+        self.llm.api_key = values.get(secrets::OPENAI_API_KEY);
+        self.database.url = values.database_url();
+        if let Some(azure) = &mut self.email.azure {
+            azure.client_secret = values.get(secrets::AZURE_CLIENT_SECRET).unwrap_or_default();
+        }
+        self.secret_values = Arc::new(values);
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.port == 0 {
+            return Err(ConfigError::invalid("port (PORT)", "must be between 1 and 65535"));
+        }
+        if let Some(url) = &self.database.url {
+            check_url(url, &["postgres", "postgresql"], "database.url (DATABASE_URL)")?;
+        }
+        if self.llm.model.trim().is_empty() {
+            return Err(ConfigError::invalid("llm.model (OPENAI_MODEL)", "must not be empty"));
+        }
+        if self.llm.max_input_tokens == Some(0) {
+            return Err(ConfigError::invalid("llm.max_input_tokens (LLM_MAX_INPUT_TOKENS)", "must be positive"));
+        }
+        check_url(&self.llm.base_url, &["http", "https"], "llm.base_url (OPENAI_BASE_URL)")?;
+        if self.llm.provider == LlmProvider::Local && self.llm.base_url == LlmConfig::default().base_url {
+            return Err(ConfigError::invalid(
+                "llm.base_url (OPENAI_BASE_URL)",
+                "must point at the local model server with llm.provider local, e.g. http://localhost:11434/v1",
+            ));
+        }
+        check_url(&self.email.graph_url, &["http", "https"], "email.graph_url (GRAPH_BASE_URL)")?;
+        if self.email.poll_interval_secs == 0 {
+            return Err(ConfigError::invalid(
+                "email.poll_interval_secs (EMAIL_POLL_INTERVAL_SECS)",
+                "must be positive",
+            ));
+        }
+        if self.email.processing_concurrency == 0 {
+            return Err(ConfigError::invalid(
+                "email.processing_concurrency (EMAIL_PROCESSING_CONCURRENCY)",
+                "must be positive",
+            ));
+        }
+        if let Some(azure) = &self.email.azure {
+            for (setting, value) in [
+                ("email.azure.client_id (AZURE_CLIENT_ID)", &azure.client_id),
+                ("email.azure.client_secret (AZURE_CLIENT_SECRET)", &azure.client_secret),
+                ("email.azure.tenant_id (AZURE_TENANT_ID)", &azure.tenant_id),
+            ] {
+                if value.trim().is_empty() {
+                    return Err(ConfigError::invalid(setting, "must not be empty"));
+                }
+            }
+        }
+        if let Some(threshold) = self.agent.review_confidence_threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err(ConfigError::invalid(
+                    "agent.review_confidence_threshold (AI_REVIEW_CONFIDENCE_THRESHOLD)",
+                    "must be between 0 and 1",
+                ));
+            }
+        }
+        if self.agent.max_steps == Some(0) {
+            return Err(ConfigError::invalid("agent.max_steps (AI_MAX_AGENT_STEPS)", "must be positive"));
+        }
+        if self.agent.weekly_summary_interval_secs == 0 {
+            return Err(ConfigError::invalid(
+                "agent.weekly_summary_interval_secs (WEEKLY_SUMMARY_INTERVAL_SECS)",
+                "must be positive",
+            ));
+        }
+        check_url(&self.whatsapp.api_url, &["http", "https"], "whatsapp.api_url (WHATSAPP_API_URL)")?;
+        check_url(&self.voice.local_url, &["http", "https"], "voice.local_url (TRANSCRIPTION_URL)")?;
+        if let Some(url) = &self.sso.redirect_base_url {
+            check_url(url, &["http", "https"], "sso.redirect_base_url (SSO_REDIRECT_BASE_URL)")?;
+        }
+        if let Some(url) = &self.jira.base_url {
+            check_url(url, &["http", "https"], "jira.base_url (JIRA_BASE_URL)")?;
+        }
+        check_url(&self.notion.api_url, &["http", "https"], "notion.api_url (NOTION_API_URL)")?;
+        for url in &self.outbox.webhook_urls {
+            check_url(url, &["http", "https"], "outbox.webhook_urls (OUTBOX_WEBHOOK_URLS)")?;
+        }
+        for (setting, secs) in [
+            ("jobs.poll_secs (JOB_POLL_SECS)", self.jobs.poll_secs),
+            ("jobs.lease_secs (JOB_LEASE_SECS)", self.jobs.lease_secs),
+            ("jobs.notification_sweep_secs (NOTIFICATION_SWEEP_SECS)", self.jobs.notification_sweep_secs),
+            ("jobs.case_retention_sweep_secs (CASE_RETENTION_SWEEP_SECS)", self.jobs.case_retention_sweep_secs),
+            ("jobs.session_cleanup_secs (SESSION_CLEANUP_SECS)", self.jobs.session_cleanup_secs),
+            ("jobs.outbox_relay_secs (OUTBOX_RELAY_SECS)", self.jobs.outbox_relay_secs),
+            ("jobs.failed_message_retry_secs (FAILED_MESSAGE_RETRY_SECS)", self.jobs.failed_message_retry_secs),
+        ] {
+            if secs == 0 {
+                return Err(ConfigError::invalid(setting, "must be positive"));
+            }
+        }
+        for origin in &self.cors.allowed_origins {
+            check_url(origin, &["http", "https"], "cors.allowed_origins (CORS_ALLOWED_ORIGINS)")?;
+            HeaderValue::from_str(origin)
+                .map_err(|e| ConfigError::invalid("cors.allowed_origins (CORS_ALLOWED_ORIGINS)", e))?;
+        }
+        for (name, urls) in &self.services {
+            let setting = match SERVICES.iter().find(|(known, _, _)| known == name) {
+                Some((_, variable, _)) => format!("services.{} ({})", name, variable),
+                None => format!("services.{}", name),
+            };
+            if urls.is_empty() {
+                return Err(ConfigError::invalid(&setting, "needs at least one URL"));
+            }
+            for url in urls {
+                check_url(url, &["http", "https"], &setting)?;
+            }
+        }
+        check_url(&self.discovery.consul_url, &["http", "https"], "discovery.consul_url (CONSUL_URL)")?;
+        check_url(&self.secrets.vault_addr, &["http", "https"], "secrets.vault_addr (VAULT_ADDR)")?;
+        if self.discovery.refresh_secs == 0 {
+            return Err(ConfigError::invalid(
+                "discovery.refresh_secs (SERVICE_DISCOVERY_REFRESH_SECS)",
+                "must be positive",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The database URL, for services that cannot run without one.
+    pub fn require_database_url(&self) -> Result<&str, ConfigError> {
+        self.database.url.as_deref().ok_or_else(|| ConfigError::Missing {
+            service: self.service_name.clone(),
+            setting: "database.url (DATABASE_URL)".to_string(),
+        })
+    }
+
+    /// A base URL of `service`, taking its healthy instances in turn.
     pub fn service_url(&self, service: &str) -> String {
-        match service {
-            "channel" => env::var("CHANNEL_SERVICE_URL").unwrap_or_else(|_| "http://localhost:8001".to_string()),
-            "case-management" => env::var("CASE_MANAGEMENT_SERVICE_URL").unwrap_or_else(|_| "http://localhost:8002".to_string()),
-            "task-management" => env::var("TASK_MANAGEMENT_SERVICE_URL").unwrap_or_else(|_| "http://localhost:8003".to_string()),
-            "ai-agent" => env::var("AI_AGENT_SERVICE_URL").unwrap_or_else(|_| "http://localhost:8004".to_string()),
-            "persistence" => env::var("PERSISTENCE_SERVICE_URL").unwrap_or_else(|_| "http://localhost:8005".to_string()),
-            _ => format!("http://localhost:8000"),
+        self.registry
+            .url(service)
+            .unwrap_or_else(|| "http://localhost:8000".to_string())
+    }
+
+    /// Starts keeping the instances of the other services and the secrets
+    /// up to date in the background.
+    pub fn start_refresh(&self) {
+        self.registry.start();
+        self.secret_values.start();
+    }
+}
+
+/// A set, non-blank environment variable.
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Checks `url` parses with one of `schemes`.  The URL itself is left out
+/// of the error, as database URLs carry passwords.
+fn check_url(url: &str, schemes: &[&str], setting: &str) -> Result<(), ConfigError> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| ConfigError::invalid(setting, e))?;
+    if !schemes.contains(&parsed.scheme()) {
+        return Err(ConfigError::invalid(setting, format!("should start with {}://", schemes.join(":// or "))));
+    }
+    Ok(())
+}
+
+/// Merges `layer` into `base`: objects key by key, anything else replaced.
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
         }
+        (base, layer) => *base = layer,
     }
 }
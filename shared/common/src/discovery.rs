@@ -0,0 +1,304 @@
+//! Finding the instances of other services.
+//!
+//! Each service names its peers by the short names of
+//! [`ServiceConfig::service_url`](crate::config::ServiceConfig::service_url)
+//! and gets one or more seed URLs for each from the configuration.  A
+//! [`Discovery`] backend turns the seeds into the current instances:
+//!
+//! - `static` uses the seeds as they are;
+//! - `dns` resolves each seed's host to all of its addresses, for
+//!   plain-HTTP services behind a name with several A/AAAA records;
+//! - `consul` asks Consul for the passing instances of `<name>-service`.
+//!
+//! The [`ServiceRegistry`] refreshes the instances in the background,
+//! probes their `/health` endpoints and hands out the healthy ones in
+//! turn.  Lookups never wait on the network: until the first refresh, and
+//! whenever a backend fails or finds nothing, the last known instances (at
+//! first the seeds) are used.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscoveryBackend {
+    #[default]
+    Static,
+    Dns,
+    Consul,
+}
+
+impl std::str::FromStr for DiscoveryBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "static" => Ok(DiscoveryBackend::Static),
+            "dns" => Ok(DiscoveryBackend::Dns),
+            "consul" => Ok(DiscoveryBackend::Consul),
+            other => Err(format!("unknown backend '{}', expected static, dns or consul", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    /// `SERVICE_DISCOVERY`
+    pub backend: DiscoveryBackend,
+    /// `CONSUL_URL`
+    pub consul_url: String,
+    /// `SERVICE_DISCOVERY_REFRESH_SECS`; how often instances are looked up
+    /// and probed.
+    pub refresh_secs: u64,
+    /// `SERVICE_HEALTH_CHECKS`; whether instances failing `/health` are
+    /// passed over.
+    pub health_checks: bool,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            backend: DiscoveryBackend::Static,
+            consul_url: "http://localhost:8500".to_string(),
+            refresh_secs: 15,
+            health_checks: true,
+        }
+    }
+}
+
+/// Looks up the instances of a service.
+#[async_trait]
+pub trait Discovery: Send + Sync {
+    /// Base URLs of the instances of `service`, given its configured
+    /// `seeds`.
+    async fn instances(&self, service: &str, seeds: &[String]) -> anyhow::Result<Vec<String>>;
+}
+
+pub struct StaticDiscovery;
+
+#[async_trait]
+impl Discovery for StaticDiscovery {
+    async fn instances(&self, _service: &str, seeds: &[String]) -> anyhow::Result<Vec<String>> {
+        Ok(seeds.to_vec())
+    }
+}
+
+pub struct DnsDiscovery;
+
+#[async_trait]
+impl Discovery for DnsDiscovery {
+    async fn instances(&self, _service: &str, seeds: &[String]) -> anyhow::Result<Vec<String>> {
+        let mut instances = Vec::new();
+        for seed in seeds {
+            let url = reqwest::Url::parse(seed)?;
+            let host = url.host_str().ok_or_else(|| anyhow::anyhow!("{} has no host", seed))?;
+            let port = url.port_or_known_default().unwrap_or(80);
+            for address in tokio::net::lookup_host((host, port)).await? {
+                let mut instance = url.clone();
+                if instance.set_ip_host(address.ip()).is_ok() {
+                    instances.push(instance.as_str().trim_end_matches('/').to_string());
+                }
+            }
+        }
+        instances.dedup();
+        Ok(instances)
+    }
+}
+
+pub struct ConsulDiscovery {
+    client: reqwest::Client,
+    consul_url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulEntry {
+    node: ConsulNode,
+    service: ConsulService,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulNode {
+    address: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulService {
+    address: String,
+    port: u16,
+}
+
+#[async_trait]
+impl Discovery for ConsulDiscovery {
+    async fn instances(&self, service: &str, _seeds: &[String]) -> anyhow::Result<Vec<String>> {
+        let url = format!("{}/v1/health/service/{}-service?passing=true", self.consul_url, service);
+        let entries: Vec<ConsulEntry> = self.client.get(&url).send().await?.error_for_status()?.json().await?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| {
+                let address = if entry.service.address.is_empty() { entry.node.address } else { entry.service.address };
+                format!("http://{}:{}", address, entry.service.port)
+            })
+            .collect())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instance {
+    pub url: String,
+    pub healthy: bool,
+}
+
+pub struct ServiceRegistry {
+    backend: DiscoveryBackend,
+    discovery: Box<dyn Discovery>,
+    seeds: BTreeMap<String, Vec<String>>,
+    instances: RwLock<HashMap<String, Vec<Instance>>>,
+    next: AtomicUsize,
+    refresh: Duration,
+    health_checks: bool,
+    client: reqwest::Client,
+}
+
+impl std::fmt::Debug for ServiceRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServiceRegistry")
+            .field("seeds", &self.seeds)
+            .field("instances", &self.instances)
+            .finish()
+    }
+}
+
+impl Default for ServiceRegistry {
+    fn default() -> Self {
+        Self::new(&DiscoveryConfig::default(), BTreeMap::new())
+    }
+}
+
+impl ServiceRegistry {
+    pub fn new(config: &DiscoveryConfig, seeds: BTreeMap<String, Vec<String>>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(HEALTH_TIMEOUT)
+            .build()
+            .expect("Failed to create HTTP client");
+        let discovery: Box<dyn Discovery> = match config.backend {
+            DiscoveryBackend::Static => Box::new(StaticDiscovery),
+            DiscoveryBackend::Dns => Box::new(DnsDiscovery),
+            DiscoveryBackend::Consul => Box::new(ConsulDiscovery {
+                client: client.clone(),
+                consul_url: config.consul_url.trim_end_matches('/').to_string(),
+            }),
+        };
+        let instances = seeds
+            .iter()
+            .map(|(service, urls)| {
+                let instances = urls.iter().map(|url| Instance { url: url.clone(), healthy: true }).collect();
+                (service.clone(), instances)
+            })
+            .collect();
+        Self {
+            backend: config.backend,
+            discovery,
+            seeds,
+            instances: RwLock::new(instances),
+            next: AtomicUsize::new(0),
+            refresh: Duration::from_secs(config.refresh_secs.max(1)),
+            health_checks: config.health_checks,
+            client,
+        }
+    }
+
+    /// The next healthy instance of `service`, in turn.  When none is
+    /// healthy every known instance takes its turn, so a failed health
+    /// check never leaves a service with nowhere to go.
+    pub fn url(&self, service: &str) -> Option<String> {
+        let instances = self.instances.read().unwrap_or_else(|e| e.into_inner());
+        let known = instances.get(service)?;
+        let healthy: Vec<&Instance> = known.iter().filter(|instance| instance.healthy).collect();
+        let candidates: Vec<&Instance> = if healthy.is_empty() { known.iter().collect() } else { healthy };
+        if candidates.is_empty() {
+            return None;
+        }
+        let turn = self.next.fetch_add(1, Ordering::Relaxed);
+        Some(candidates[turn % candidates.len()].url.clone())
+    }
+
+    /// Whether `url` points at a configured or discovered instance of any
+    /// service, so requests to it may carry credentials meant only for
+    /// the services.
+    pub fn is_instance(&self, url: &str) -> bool {
+        let on = |base: &str| {
+            let base = base.trim_end_matches('/');
+            url.strip_prefix(base)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || rest.starts_with('?'))
+        };
+        if self.seeds.values().flatten().any(|seed| on(seed)) {
+            return true;
+        }
+        let instances = self.instances.read().unwrap_or_else(|e| e.into_inner());
+        instances.values().flatten().any(|instance| on(&instance.url))
+    }
+
+    /// Keeps the instances up to date in the background.  Only needed when
+    /// instances can change or health checks are on.
+    pub fn start(self: &Arc<Self>) {
+        if self.backend == DiscoveryBackend::Static && !self.health_checks {
+            return;
+        }
+        let registry = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(registry.refresh);
+            loop {
+                interval.tick().await;
+                registry.refresh().await;
+            }
+        });
+    }
+
+    /// Looks up and probes the instances of every service once.
+    pub async fn refresh(&self) {
+        for (service, seeds) in &self.seeds {
+            let urls = match self.discovery.instances(service, seeds).await {
+                Ok(urls) if !urls.is_empty() => urls,
+                Ok(_) => {
+                    warn!("No instances of {} found; keeping the last known ones", service);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Looking up {} failed: {}; keeping the last known ones", service, e);
+                    continue;
+                }
+            };
+
+            let mut instances = Vec::with_capacity(urls.len());
+            for url in urls {
+                let healthy = !self.health_checks || self.probe(&url).await;
+                instances.push(Instance { url, healthy });
+            }
+
+            let mut known = self.instances.write().unwrap_or_else(|e| e.into_inner());
+            let previous = known.insert(service.clone(), instances.clone());
+            if previous.as_ref() != Some(&instances) {
+                let healthy = instances.iter().filter(|instance| instance.healthy).count();
+                info!("{}: {} of {} instances healthy", service, healthy, instances.len());
+            }
+        }
+    }
+
+    async fn probe(&self, url: &str) -> bool {
+        match self.client.get(format!("{}/health", url)).send().await {
+            Ok(response) => response.status().is_success(),
+            Err(_) => false,
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use axum::{
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures::{Stream, StreamExt};
+use models::{Case, Task, TaskType};
+use serde::Deserialize;
+
+use crate::{ServiceError, ServiceResult};
+
+const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+}
+
+/// Query parameters shared by all export endpoints. Services extract this
+/// alongside their own list filters.
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Comma-separated list of columns; all columns are exported when omitted.
+    pub columns: Option<String>,
+}
+
+/// A record that can be written as a row of a tabular export.
+pub trait Exportable {
+    fn columns() -> &'static [&'static str];
+    fn value(&self, column: &str) -> String;
+}
+
+impl Exportable for Task {
+    fn columns() -> &'static [&'static str] {
+        &[
+            "id", "case_id", "title", "description", "task_type", "status", "priority",
+            "due_date", "created_at", "updated_at", "completed_at",
+        ]
+    }
+
+    fn value(&self, column: &str) -> String {
+        match column {
+            "id" => self.id.to_string(),
+            "case_id" => self.case_id.to_string(),
+            "title" => self.title.clone(),
+            "description" => self.description.clone().unwrap_or_default(),
+            "task_type" => match &self.task_type {
+                TaskType::Other(label) => label.clone(),
+                other => format!("{:?}", other),
+            },
+            "status" => format!("{:?}", self.status),
+            "priority" => format!("{:?}", self.priority),
+            "due_date" => self.due_date.map(|d| d.to_rfc3339()).unwrap_or_default(),
+            "created_at" => self.created_at.to_rfc3339(),
+            "updated_at" => self.updated_at.to_rfc3339(),
+            "completed_at" => self.completed_at.map(|d| d.to_rfc3339()).unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+impl Exportable for Case {
+    fn columns() -> &'static [&'static str] {
+        &[
+            "id", "title", "description", "status", "priority", "assigned_to",
+            "created_at", "updated_at",
+        ]
+    }
+
+    fn value(&self, column: &str) -> String {
+        match column {
+            "id" => self.id.to_string(),
+            "title" => self.title.clone(),
+            "description" => self.description.clone().unwrap_or_default(),
+            "status" => format!("{:?}", self.status),
+            "priority" => format!("{:?}", self.priority),
+            "assigned_to" => self.assigned_to.clone().unwrap_or_default(),
+            "created_at" => self.created_at.to_rfc3339(),
+            "updated_at" => self.updated_at.to_rfc3339(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Resolves the requested column list against the columns supported by `T`,
+/// rejecting unknown names so typos don't silently produce empty columns.
+pub fn select_columns<T: Exportable>(requested: Option<&str>) -> ServiceResult<Vec<&'static str>> {
+    let available = T::columns();
+    let Some(requested) = requested.filter(|r| !r.trim().is_empty()) else {
+        return Ok(available.to_vec());
+    };
+
+    requested
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(|column| {
+            available
+                .iter()
+                .find(|c| **c == column)
+                .copied()
+                .ok_or_else(|| ServiceError::BadRequest(format!("Unknown export column: {}", column)))
+        })
+        .collect()
+}
+
+/// Renders `rows` in the requested format as a downloadable response.
+///
+/// CSV is streamed row by row; XLSX has to be assembled in memory because
+/// the workbook is a zip archive.
+pub fn export_response<T>(rows: Vec<T>, query: &ExportQuery, file_stem: &str) -> ServiceResult<Response>
+where
+    T: Exportable + Send + 'static,
+{
+    let columns = select_columns::<T>(query.columns.as_deref())?;
+
+    match query.format {
+        ExportFormat::Csv => {
+            let header_row = csv_header(&columns);
+            let body_rows = rows.into_iter().map(move |row| {
+                csv_record(columns.iter().map(|c| row.value(c)))
+                    .map_err(|e| std::io::Error::other(e.to_string()))
+            });
+            let stream = futures::stream::iter(std::iter::once(header_row).chain(body_rows));
+            Ok(csv_response(Body::from_stream(stream), file_stem))
+        }
+        ExportFormat::Xlsx => Ok(xlsx_response(xlsx_workbook(&rows, &columns)?, file_stem)),
+    }
+}
+
+/// Renders the rows of `pages` as they arrive, for exports too large to
+/// load at once.
+///
+/// The first page is awaited before responding, so a failure to load it
+/// is still reported as an error response. As CSV, every later page is
+/// written to the body as soon as it is loaded, and a failure cuts the
+/// download short. XLSX collects all pages first, as in
+/// [`export_response`].
+pub async fn export_pages<T, S>(pages: S, query: &ExportQuery, file_stem: &str) -> ServiceResult<Response>
+where
+    T: Exportable + Send + 'static,
+    S: Stream<Item = ServiceResult<Vec<T>>> + Send + 'static,
+{
+    let columns = select_columns::<T>(query.columns.as_deref())?;
+    let mut pages = Box::pin(pages);
+
+    match query.format {
+        ExportFormat::Csv => {
+            let first = pages.next().await.transpose()?.unwrap_or_default();
+            let header_row = csv_header(&columns);
+            let body_pages = futures::stream::once(async move { Ok(first) })
+                .chain(pages)
+                .map(move |page| {
+                    page.and_then(|rows| csv_rows(&rows, &columns))
+                        .map_err(|e| std::io::Error::other(e.to_string()))
+                });
+            let stream = futures::stream::once(async move { header_row }).chain(body_pages);
+            Ok(csv_response(Body::from_stream(stream), file_stem))
+        }
+        ExportFormat::Xlsx => {
+            let mut rows = Vec::new();
+            while let Some(page) = pages.next().await {
+                rows.extend(page?);
+            }
+            Ok(xlsx_response(xlsx_workbook(&rows, &columns)?, file_stem))
+        }
+    }
+}
+
+fn csv_response(body: Body, file_stem: &str) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, attachment(file_stem, "csv")),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+fn xlsx_response(bytes: Vec<u8>, file_stem: &str) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, XLSX_CONTENT_TYPE.to_string()),
+            (header::CONTENT_DISPOSITION, attachment(file_stem, "xlsx")),
+        ],
+        bytes,
+    )
+        .into_response()
+}
+
+fn attachment(file_stem: &str, extension: &str) -> String {
+    format!("attachment; filename=\"{}.{}\"", file_stem, extension)
+}
+
+fn csv_header(columns: &[&str]) -> std::io::Result<Vec<u8>> {
+    csv_record(columns.iter().map(|c| c.to_string())).map_err(|e| std::io::Error::other(e.to_string()))
+}
+
+/// One page of rows as consecutive CSV records.
+fn csv_rows<T: Exportable>(rows: &[T], columns: &[&str]) -> ServiceResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for row in rows {
+        bytes.extend(csv_record(columns.iter().map(|c| row.value(c)))?);
+    }
+    Ok(bytes)
+}
+
+fn csv_record(fields: impl Iterator<Item = String>) -> ServiceResult<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(fields)
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("CSV encoding error: {}", e)))?;
+    writer
+        .into_inner()
+        .map_err(|e| ServiceError::Internal(anyhow::anyhow!("CSV encoding error: {}", e)))
+}
+
+fn xlsx_workbook<T: Exportable>(rows: &[T], columns: &[&str]) -> ServiceResult<Vec<u8>> {
+    let xlsx_error = |e: rust_xlsxwriter::XlsxError| {
+        ServiceError::Internal(anyhow::anyhow!("XLSX encoding error: {}", e))
+    };
+
+    let mut workbook = rust_xlsxwriter::Workbook::new();
+    let sheet = workbook.add_worksheet();
+    let bold = rust_xlsxwriter::Format::new().set_bold();
+
+    for (col, name) in columns.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *name, &bold).map_err(xlsx_error)?;
+    }
+    for (row_idx, row) in rows.iter().enumerate() {
+        for (col, name) in columns.iter().enumerate() {
+            sheet
+                .write_string(row_idx as u32 + 1, col as u16, row.value(name))
+                .map_err(xlsx_error)?;
+        }
+    }
+
+    workbook.save_to_buffer().map_err(xlsx_error)
+}
@@ -1,10 +1,57 @@
-use reqwest::Client;
+use crate::auth::INTERNAL_TOKEN_HEADER;
+use crate::config::ServiceConfig;
+use crate::discovery::ServiceRegistry;
+use crate::logging;
+use crate::secrets::{self, Secrets};
+use async_trait::async_trait;
+use reqwest::{Client, Method, Request, RequestBuilder, Response};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use std::time::Duration;
 
+/// Sends the requests an [`HttpClient`] builds. The network in production;
+/// tests swap in [`MockTransport`](crate::testing::MockTransport) with the
+/// `test-util` feature.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn execute(&self, request: Request) -> Result<Response, reqwest::Error>;
+}
+
+#[async_trait]
+impl Transport for Client {
+    async fn execute(&self, request: Request) -> Result<Response, reqwest::Error> {
+        Client::execute(self, request).await
+    }
+}
+
 #[derive(Clone)]
 pub struct HttpClient {
+    /// Builds requests only; they are sent through `transport`.
     client: Client,
+    transport: Arc<dyn Transport>,
+    auth: Option<Auth>,
+    services: Option<ServiceCredentials>,
+}
+
+/// Sends the internal service token, but only to the services' own
+/// instances; third-party APIs never see it.
+#[derive(Clone)]
+struct ServiceCredentials {
+    registry: Arc<ServiceRegistry>,
+    secrets: Arc<Secrets>,
+}
+
+/// Credentials sent with every request.
+#[derive(Clone)]
+enum Auth {
+    Bearer(String),
+    Basic { username: String, password: String },
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HttpClient {
@@ -14,20 +61,94 @@ impl HttpClient {
             .build()
             .expect("Failed to create HTTP client");
 
-        Self { client }
+        Self { client: client.clone(), transport: Arc::new(client), auth: None, services: None }
+    }
+
+    /// A client for a service: its calls to the other services of
+    /// `config` carry the `INTERNAL_SERVICE_TOKEN`, so they are accepted as
+    /// internal calls.
+    pub fn for_services(config: &ServiceConfig) -> Self {
+        Self::new().with_service_credentials(config)
+    }
+
+    /// Returns a client that sends the internal service token of `config`
+    /// to its services, sharing the connection pool with `self`.
+    pub fn with_service_credentials(&self, config: &ServiceConfig) -> Self {
+        Self {
+            services: Some(ServiceCredentials {
+                registry: config.registry.clone(),
+                secrets: config.secret_values.clone(),
+            }),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a client that doesn't send the internal service token, for
+    /// calls made for an anonymous visitor.
+    pub fn without_service_credentials(&self) -> Self {
+        Self { services: None, ..self.clone() }
+    }
+
+    /// A client whose requests go through `transport` instead of the
+    /// network.
+    pub fn with_transport(transport: Arc<dyn Transport>) -> Self {
+        Self { client: Client::new(), transport, auth: None, services: None }
+    }
+
+    /// Returns a client that forwards the given session token as a bearer
+    /// token, so downstream services act on behalf of that user. The
+    /// underlying connection pool is shared with `self`.
+    pub fn with_session_token(&self, session_token: &str) -> Self {
+        Self { auth: Some(Auth::Bearer(session_token.to_string())), ..self.clone() }
+    }
+
+    /// Returns a client that signs in to a third-party API with HTTP basic
+    /// authentication, sharing the connection pool with `self`.
+    pub fn with_basic_auth(&self, username: &str, password: &str) -> Self {
+        Self {
+            auth: Some(Auth::Basic { username: username.to_string(), password: password.to_string() }),
+            ..self.clone()
+        }
+    }
+
+    async fn send(&self, builder: RequestBuilder) -> Result<Response, reqwest::Error> {
+        self.transport.execute(builder.build()?).await?.error_for_status()
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let mut builder = self.client.request(method, url);
+        if let Some(request_id) = logging::current_request_id() {
+            builder = builder.header(logging::REQUEST_ID_HEADER, request_id);
+        }
+        if let Some(services) = &self.services {
+            if services.registry.is_instance(url) {
+                if let Some(token) = services.secrets.get(secrets::INTERNAL_SERVICE_TOKEN) {
+                    builder = builder.header(INTERNAL_TOKEN_HEADER, token);
+                }
+            }
+        }
+        match &self.auth {
+            Some(Auth::Bearer(token)) => builder.bearer_auth(token),
+            Some(Auth::Basic { username, password }) => builder.basic_auth(username, Some(password)),
+            None => builder,
+        }
     }
 
     pub async fn get<T>(&self, url: &str) -> Result<T, reqwest::Error>
     where
         T: for<'de> Deserialize<'de>,
     {
-        self.client
-            .get(url)
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<T>()
-            .await
+        self.send(self.request(Method::GET, url)).await?.json::<T>().await
+    }
+
+    /// Like [`get`](Self::get) with `query` url-encoded onto the URL. `None`
+    /// fields are left out.
+    pub async fn get_with_query<T, Q>(&self, url: &str, query: &Q) -> Result<T, reqwest::Error>
+    where
+        T: for<'de> Deserialize<'de>,
+        Q: Serialize + ?Sized,
+    {
+        self.send(self.request(Method::GET, url).query(query)).await?.json::<T>().await
     }
 
     pub async fn post<T, U>(&self, url: &str, body: &T) -> Result<U, reqwest::Error>
@@ -35,14 +156,22 @@ impl HttpClient {
         T: Serialize,
         U: for<'de> Deserialize<'de>,
     {
-        self.client
-            .post(url)
-            .json(body)
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<U>()
-            .await
+        self.send(self.request(Method::POST, url).json(body)).await?.json::<U>().await
+    }
+
+    /// Sends a GET and returns the raw response once the status is known to
+    /// be successful, for bodies that aren't JSON, such as file downloads.
+    pub async fn get_stream(&self, url: &str) -> Result<Response, reqwest::Error> {
+        self.send(self.request(Method::GET, url)).await
+    }
+
+    /// Sends a POST and returns the raw response once the status is known
+    /// to be successful, so streaming bodies can be forwarded as they arrive.
+    pub async fn post_stream<T>(&self, url: &str, body: &T) -> Result<Response, reqwest::Error>
+    where
+        T: Serialize,
+    {
+        self.send(self.request(Method::POST, url).json(body)).await
     }
 
     pub async fn put<T, U>(&self, url: &str, body: &T) -> Result<U, reqwest::Error>
@@ -50,18 +179,19 @@ impl HttpClient {
         T: Serialize,
         U: for<'de> Deserialize<'de>,
     {
-        self.client
-            .put(url)
-            .json(body)
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<U>()
-            .await
+        self.send(self.request(Method::PUT, url).json(body)).await?.json::<U>().await
+    }
+
+    pub async fn patch<T, U>(&self, url: &str, body: &T) -> Result<U, reqwest::Error>
+    where
+        T: Serialize,
+        U: for<'de> Deserialize<'de>,
+    {
+        self.send(self.request(Method::PATCH, url).json(body)).await?.json::<U>().await
     }
 
     pub async fn delete(&self, url: &str) -> Result<(), reqwest::Error> {
-        self.client.delete(url).send().await?.error_for_status()?;
+        self.send(self.request(Method::DELETE, url)).await?;
         Ok(())
     }
 }
@@ -0,0 +1,199 @@
+//! Background jobs.
+//!
+//! Jobs live in the persistence service's database, so scheduled work
+//! survives restarts and can be inspected and retried through its admin
+//! API.  A [`Worker`] claims the kinds of job it has handlers for, runs
+//! them and reports the outcome:
+//!
+//! - a job that fails is retried with exponential backoff, and after
+//!   `max_attempts` failures is dead-lettered until retried by hand;
+//! - a recurring job runs again `interval_secs` after each run, and keeps
+//!   its schedule however often it fails;
+//! - a claimed job is leased to its worker; if the worker goes away the
+//!   lease runs out and another worker picks the job up.
+//!
+//! Services reach the queue over HTTP through [`HttpJobQueue`]; the
+//! persistence service implements [`JobQueue`] on its database directly.
+
+use crate::config::{JobsConfig, ServiceConfig};
+use crate::http_client::HttpClient;
+use crate::{ServiceError, ServiceResult};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use models::{ClaimJobsRequest, EnqueueJobRequest, Job, JobResult, ScheduleJobRequest};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// How many jobs a worker runs at once.
+const CLAIM_LIMIT: i64 = 4;
+
+#[async_trait]
+pub trait JobQueue: Send + Sync {
+    async fn enqueue(&self, request: &EnqueueJobRequest) -> ServiceResult<Job>;
+    async fn schedule(&self, request: &ScheduleJobRequest) -> ServiceResult<Job>;
+    async fn claim(&self, request: &ClaimJobsRequest) -> ServiceResult<Vec<Job>>;
+    async fn finish(&self, id: Uuid, result: &JobResult) -> ServiceResult<Job>;
+}
+
+/// The persistence service's job API.
+pub struct HttpJobQueue {
+    config: ServiceConfig,
+    http_client: HttpClient,
+}
+
+impl HttpJobQueue {
+    pub fn new(config: ServiceConfig) -> Self {
+        let http_client = HttpClient::for_services(&config);
+        Self { config, http_client }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/v1/jobs{}", self.config.service_url("persistence"), path)
+    }
+}
+
+#[async_trait]
+impl JobQueue for HttpJobQueue {
+    async fn enqueue(&self, request: &EnqueueJobRequest) -> ServiceResult<Job> {
+        Ok(self.http_client.post(&self.url(""), request).await?)
+    }
+
+    async fn schedule(&self, request: &ScheduleJobRequest) -> ServiceResult<Job> {
+        Ok(self.http_client.put(&self.url("/recurring"), request).await?)
+    }
+
+    async fn claim(&self, request: &ClaimJobsRequest) -> ServiceResult<Vec<Job>> {
+        Ok(self.http_client.post(&self.url("/claim"), request).await?)
+    }
+
+    async fn finish(&self, id: Uuid, result: &JobResult) -> ServiceResult<Job> {
+        self.http_client
+            .post(&self.url(&format!("/{}/finish", id)), result)
+            .await
+            .map_err(|e| match e.status() {
+                Some(reqwest::StatusCode::NOT_FOUND) => ServiceError::NotFound(format!("Job {} is not held", id)),
+                _ => ServiceError::HttpClient(e),
+            })
+    }
+}
+
+type Handler = Arc<dyn Fn(Job) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
+/// Runs jobs of the kinds it has handlers for.
+pub struct Worker {
+    queue: Arc<dyn JobQueue>,
+    name: String,
+    handlers: HashMap<String, Handler>,
+    recurring: Vec<ScheduleJobRequest>,
+    poll: Duration,
+    lease: Duration,
+}
+
+impl Worker {
+    /// `jobs.poll_secs` sets how often the queue is checked and
+    /// `jobs.lease_secs` how long a job may run before another worker takes
+    /// it over.
+    pub fn new(queue: Arc<dyn JobQueue>, service_name: &str, jobs: &JobsConfig) -> Self {
+        Self {
+            queue,
+            name: format!("{}-{}", service_name, Uuid::new_v4()),
+            handlers: HashMap::new(),
+            recurring: Vec::new(),
+            poll: Duration::from_secs(jobs.poll_secs),
+            lease: Duration::from_secs(jobs.lease_secs),
+        }
+    }
+
+    /// Runs jobs of `kind` with `handler`.  An error fails the attempt.
+    pub fn handle<F, Fut>(mut self, kind: &str, handler: F) -> Self
+    where
+        F: Fn(Job) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.handlers.insert(kind.to_string(), Arc::new(move |job| Box::pin(handler(job))));
+        self
+    }
+
+    /// Like [`handle`](Self::handle), and schedules `kind` to run every
+    /// `every`.
+    pub fn recurring<F, Fut>(mut self, kind: &str, every: Duration, handler: F) -> Self
+    where
+        F: Fn(Job) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.recurring.push(ScheduleJobRequest {
+            kind: kind.to_string(),
+            interval_secs: every.as_secs().max(1) as i64,
+            payload: serde_json::Value::Null,
+        });
+        self.handle(kind, handler)
+    }
+
+    /// Schedules the recurring jobs, waiting for the queue if need be, then
+    /// runs jobs in the background.
+    pub fn start(self) {
+        if self.handlers.is_empty() {
+            return;
+        }
+        tokio::spawn(async move {
+            for request in &self.recurring {
+                while let Err(e) = self.queue.schedule(request).await {
+                    warn!("Could not schedule {} jobs, trying again: {}", request.kind, e);
+                    tokio::time::sleep(self.poll).await;
+                }
+            }
+            info!("Worker {} running {} job kinds", self.name, self.handlers.len());
+            self.run().await;
+        });
+    }
+
+    async fn run(self) {
+        let request = ClaimJobsRequest {
+            worker: self.name.clone(),
+            kinds: self.handlers.keys().cloned().collect(),
+            limit: CLAIM_LIMIT,
+            lease_secs: self.lease.as_secs() as i64,
+        };
+        let mut interval = tokio::time::interval(self.poll);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let jobs = match self.queue.claim(&request).await {
+                Ok(jobs) => jobs,
+                Err(e) => {
+                    warn!("Could not claim jobs: {}", e);
+                    continue;
+                }
+            };
+            futures::future::join_all(jobs.into_iter().map(|job| self.run_job(job))).await;
+        }
+    }
+
+    async fn run_job(&self, job: Job) {
+        let Some(handler) = self.handlers.get(&job.kind) else {
+            return;
+        };
+        let (id, kind) = (job.id, job.kind.clone());
+        let error = match handler(job).await {
+            Ok(()) => None,
+            Err(e) => {
+                error!("Job {} ({}) failed: {:#}", id, kind, e);
+                Some(format!("{:#}", e))
+            }
+        };
+        let result = JobResult { worker: self.name.clone(), error };
+        match self.queue.finish(id, &result).await {
+            Ok(job) if job.status == models::JobStatus::Dead => {
+                error!("Job {} ({}) dead-lettered after {} attempts", id, kind, job.attempts)
+            }
+            Ok(_) => {}
+            // The lease ran out and the job is someone else's now.
+            Err(ServiceError::NotFound(_)) => warn!("Job {} ({}) was taken over by another worker", id, kind),
+            Err(e) => warn!("Could not record the outcome of job {} ({}): {}", id, kind, e),
+        }
+    }
+}
@@ -3,11 +3,20 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use models::ApiError;
 use serde_json::json;
 
+pub mod auth;
 pub mod config;
+pub mod discovery;
+pub mod export;
+pub mod jobs;
+pub mod markdown;
+pub mod reload;
+pub mod secrets;
 pub mod http_client;
+pub mod logging;
+#[cfg(feature = "test-util")]
+pub mod testing;
 
 // Common error handling
 #[derive(thiserror::Error, Debug)]
@@ -29,6 +38,10 @@ pub enum ServiceError {
     
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
+
+    /// Data that breaks validation rules, one message per violation.
+    #[error("Validation failed: {}", .0.join("; "))]
+    Validation(Vec<String>),
     
     #[error("Internal error: {0}")]
     Internal(#[from] anyhow::Error),
@@ -58,6 +71,16 @@ impl IntoResponse for ServiceError {
             ServiceError::Unauthorized(ref message) => {
                 (StatusCode::UNAUTHORIZED, message.as_str())
             }
+            ServiceError::Validation(ref violations) => {
+                let body = Json(json!({
+                    "error": {
+                        "code": StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                        "message": "Validation failed",
+                        "violations": violations
+                    }
+                }));
+                return (StatusCode::UNPROCESSABLE_ENTITY, body).into_response();
+            }
             ServiceError::Internal(ref e) => {
                 tracing::error!("Internal error: {:?}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
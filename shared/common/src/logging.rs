@@ -0,0 +1,271 @@
+//! Logging setup and per-request log fields.
+//!
+//! Logs are human-readable text by default; `log_format = "json"`
+//! (`LOG_FORMAT=json`) writes one JSON object per line for Loki or ELK,
+//! with `timestamp`, `level`, `service`, `target`, `message`, the event's
+//! fields and those of the spans it happened in, flattened.
+//!
+//! [`request_layers`] gives every request a span with `service`,
+//! `request_id`, `method` and `uri`, plus `case_id` for paths under
+//! `/cases/<id>` and `user_id` once the session is resolved (see
+//! [`record_user`]).  The request id is taken from the `X-Request-Id`
+//! header or made up, returned in the response and sent along on calls to
+//! other services through [`HttpClient`](crate::http_client::HttpClient),
+//! so a message can be followed across services.
+
+use axum::http::{HeaderValue, Request, Response};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service, ServiceBuilder};
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::trace::{MakeSpan, TraceLayer};
+use tracing::field::{Empty, Field, Visit};
+use tracing::span::Record;
+use tracing::{Event, Span, Subscriber};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("unknown log format '{}', expected text or json", other)),
+        }
+    }
+}
+
+/// Installs the global subscriber.  The returned handle changes the level
+/// filter while running.
+pub fn init(service: &str, level: &str, format: LogFormat) -> reload::Handle<EnvFilter, Registry> {
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(level));
+    let text = (format == LogFormat::Text).then(tracing_subscriber::fmt::layer);
+    let json = (format == LogFormat::Json).then(|| {
+        tracing_subscriber::fmt::layer()
+            .fmt_fields(JsonFields)
+            .event_format(JsonFormat { service: service.to_string() })
+    });
+    tracing_subscriber::registry().with(filter).with(text).with(json).init();
+    handle
+}
+
+/// The request id of the request being handled, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Adds the user to the current request's log fields.
+pub fn record_user(user_id: Uuid) {
+    Span::current().record("user_id", tracing::field::display(user_id));
+}
+
+pub type RequestLayers = ServiceBuilder<
+    tower::layer::util::Stack<
+        TraceLayer<SharedClassifier<ServerErrorsAsFailures>, RequestSpan>,
+        tower::layer::util::Stack<RequestIdLayer, tower::layer::util::Identity>,
+    >,
+>;
+
+/// Request ids and request spans, in place of a bare `TraceLayer`.
+pub fn request_layers(service: &str) -> RequestLayers {
+    ServiceBuilder::new()
+        .layer(RequestIdLayer)
+        .layer(TraceLayer::new_for_http().make_span_with(RequestSpan { service: Arc::from(service) }))
+}
+
+#[derive(Clone, Debug)]
+struct RequestId(String);
+
+#[derive(Clone, Copy, Debug)]
+pub struct RequestIdLayer;
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RequestIdService<S> {
+    inner: S,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for RequestIdService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>> + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let request_id = request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|id| !id.is_empty() && id.len() <= 128)
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        request.extensions_mut().insert(RequestId(request_id.clone()));
+
+        let response = self.inner.call(request);
+        Box::pin(REQUEST_ID.scope(request_id.clone(), async move {
+            let mut response = response.await?;
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                response.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+            Ok(response)
+        }))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RequestSpan {
+    service: Arc<str>,
+}
+
+impl<B> MakeSpan<B> for RequestSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let request_id = request.extensions().get::<RequestId>().map(|id| id.0.as_str()).unwrap_or_default();
+        let span = tracing::info_span!(
+            "request",
+            service = %self.service,
+            request_id = %request_id,
+            method = %request.method(),
+            uri = %request.uri(),
+            user_id = Empty,
+            case_id = Empty,
+        );
+        if let Some(case_id) = case_id(request.uri().path()) {
+            span.record("case_id", tracing::field::display(case_id));
+        }
+        span
+    }
+}
+
+/// The id following `cases/` in a path.
+fn case_id(path: &str) -> Option<Uuid> {
+    let mut segments = path.split('/');
+    segments.find(|segment| *segment == "cases")?;
+    segments.next()?.parse().ok()
+}
+
+/// One JSON object per event; see the module docs.
+struct JsonFormat {
+    service: String,
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert("timestamp".to_string(), Value::from(chrono::Utc::now().to_rfc3339()));
+        object.insert("level".to_string(), Value::from(metadata.level().as_str()));
+        object.insert("service".to_string(), Value::from(self.service.as_str()));
+        object.insert("target".to_string(), Value::from(metadata.target()));
+
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                let extensions = span.extensions();
+                let Some(fields) = extensions.get::<FormattedFields<N>>() else {
+                    continue;
+                };
+                if let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(fields) {
+                    object.extend(fields);
+                }
+            }
+        }
+        event.record(&mut JsonVisitor(&mut object));
+
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+/// Keeps span fields as a JSON object, so [`JsonFormat`] can merge them.
+struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(&self, mut writer: Writer<'writer>, fields: R) -> fmt::Result {
+        let mut object = Map::new();
+        fields.record(&mut JsonVisitor(&mut object));
+        write!(writer, "{}", Value::Object(object))
+    }
+
+    fn add_fields(&self, current: &mut FormattedFields<Self>, fields: &Record<'_>) -> fmt::Result {
+        let mut object = match serde_json::from_str(&current.fields) {
+            Ok(Value::Object(object)) => object,
+            _ => Map::new(),
+        };
+        fields.record(&mut JsonVisitor(&mut object));
+        current.fields = Value::Object(object).to_string();
+        Ok(())
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), Value::from(format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+}
+
@@ -0,0 +1,85 @@
+use models::{CaseBackup, ConversationEntry, Task, TaskStatus, TaskType, UserBackup};
+
+/// Renders a whole backup as a single human-readable Markdown document.
+pub fn render_backup(backup: &UserBackup) -> String {
+    let mut out = format!(
+        "# Task Manager backup for {}\n\nExported {} • {} cases\n",
+        backup.user.full_name,
+        backup.exported_at.to_rfc3339(),
+        backup.cases.len()
+    );
+
+    for case in &backup.cases {
+        out.push('\n');
+        out.push_str(&render_case(case, 2));
+    }
+    out
+}
+
+/// Renders one case with its tasks and conversation. `heading_level` is the
+/// level used for the case title; sections are nested one level below it.
+pub fn render_case(case: &CaseBackup, heading_level: usize) -> String {
+    let h = "#".repeat(heading_level);
+    let sub = "#".repeat(heading_level + 1);
+
+    let mut out = format!(
+        "{} {}\n\n- **Status:** {:?}\n- **Priority:** {:?}\n- **Created:** {}\n",
+        h,
+        case.case.title,
+        case.case.status,
+        case.case.priority,
+        case.case.created_at.to_rfc3339()
+    );
+    if let Some(assignee) = &case.case.assigned_to {
+        out.push_str(&format!("- **Assigned to:** {}\n", assignee));
+    }
+    if let Some(description) = &case.case.description {
+        out.push_str(&format!("\n{}\n", description));
+    }
+
+    out.push_str(&format!("\n{} Tasks\n\n", sub));
+    if case.tasks.is_empty() {
+        out.push_str("_No tasks._\n");
+    }
+    for task in &case.tasks {
+        out.push_str(&render_task(task));
+    }
+
+    out.push_str(&format!("\n{} Conversation\n\n", sub));
+    if case.conversation.is_empty() {
+        out.push_str("_No messages._\n");
+    }
+    for entry in &case.conversation {
+        out.push_str(&render_entry(entry));
+    }
+
+    out
+}
+
+fn render_task(task: &Task) -> String {
+    let checkbox = if matches!(task.status, TaskStatus::Completed) { "x" } else { " " };
+    let task_type = match &task.task_type {
+        TaskType::Other(label) => label.clone(),
+        other => format!("{:?}", other),
+    };
+
+    let mut line = format!(
+        "- [{}] {} ({}, {:?} priority",
+        checkbox, task.title, task_type, task.priority
+    );
+    if let Some(due) = task.due_date {
+        line.push_str(&format!(", due {}", due.format("%Y-%m-%d")));
+    }
+    line.push_str(")\n");
+    line
+}
+
+fn render_entry(entry: &ConversationEntry) -> String {
+    let quoted = entry.message.lines().collect::<Vec<_>>().join("\n  > ");
+    format!(
+        "- **{:?}** ({}):\n  > {}\n",
+        entry.sender,
+        entry.timestamp.format("%Y-%m-%d %H:%M"),
+        quoted
+    )
+}
@@ -0,0 +1,233 @@
+//! Reloading runtime settings without a restart.
+//!
+//! On `SIGHUP` or `POST /api/v1/admin/reload` the configuration is loaded
+//! again and its tunables take effect right away: the log level, the LLM
+//! model and input budget, and the email polling interval.  Work in
+//! progress is not interrupted; code that uses a tunable reads it from
+//! [`LiveConfig::current`] each time, or waits on
+//! [`LiveConfig::subscribe`].  Other changed settings are reported as
+//! needing a restart and left as they were.
+//!
+//! `PUT /api/v1/admin/log-level` changes just the log level, until the
+//! next reload or restart.  Both endpoints take the `X-Admin-Token` header
+//! matching `admin.api_token` (`ADMIN_API_TOKEN`) and are disabled without
+//! it.
+
+use crate::config::{ConfigError, ServiceConfig};
+use crate::{logging, ServiceError, ServiceResult};
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    routing::{post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex};
+use tracing::{error, info, warn};
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// The settings a reload applies, as named in the config file.
+const TUNABLES: &[&str] = &["log_level", "llm.model", "llm.max_input_tokens", "email.poll_interval_secs"];
+
+#[derive(Debug, Serialize)]
+pub struct ReloadReport {
+    /// Tunables that took new values.
+    pub applied: Vec<String>,
+    /// Changed settings that only take effect after a restart.
+    pub needs_restart: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct LiveConfig {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: watch::Sender<Arc<ServiceConfig>>,
+    log_filter: reload::Handle<EnvFilter, Registry>,
+    admin_token: Option<String>,
+    /// Reloads one at a time.
+    reloading: Mutex<()>,
+}
+
+impl LiveConfig {
+    /// Sets up logging (see [`logging::init`]) and starts listening for
+    /// `SIGHUP`.  Call once, in place of initializing `tracing_subscriber`.
+    pub fn init(config: &ServiceConfig) -> Self {
+        let log_filter = logging::init(&config.service_name, &config.log_level, config.log_format);
+
+        let live = Self {
+            inner: Arc::new(Inner {
+                config: watch::channel(Arc::new(config.clone())).0,
+                log_filter,
+                admin_token: config.admin.api_token.clone(),
+                reloading: Mutex::new(()),
+            }),
+        };
+        live.listen_for_sighup();
+        live
+    }
+
+    /// The configuration with the latest tunables.
+    pub fn current(&self) -> Arc<ServiceConfig> {
+        self.inner.config.borrow().clone()
+    }
+
+    /// Changes whenever a reload applies new tunables.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<ServiceConfig>> {
+        self.inner.config.subscribe()
+    }
+
+    /// Loads the configuration again and applies its tunables.  An invalid
+    /// configuration is rejected and the current one kept.
+    pub async fn reload(&self) -> Result<ReloadReport, ConfigError> {
+        let _reloading = self.inner.reloading.lock().await;
+        let current = self.current();
+        let loaded = ServiceConfig::load(&current.service_name, current.port).await?;
+
+        let (old, new) = (settings(&current), settings(&loaded));
+        let mut report = ReloadReport { applied: Vec::new(), needs_restart: Vec::new() };
+        for (name, value) in &new {
+            if old.iter().any(|(old_name, old_value)| old_name == name && old_value == value) {
+                continue;
+            }
+            if TUNABLES.contains(&name.as_str()) {
+                report.applied.push(name.clone());
+            } else {
+                report.needs_restart.push(name.clone());
+            }
+        }
+
+        if report.applied.iter().any(|name| name == "log_level") {
+            if let Err(e) = self.inner.log_filter.reload(EnvFilter::new(&loaded.log_level)) {
+                error!("Could not change the log level: {}", e);
+            }
+        }
+        let mut updated = (*current).clone();
+        updated.log_level = loaded.log_level.clone();
+        updated.llm.model = loaded.llm.model.clone();
+        updated.llm.max_input_tokens = loaded.llm.max_input_tokens;
+        updated.email.poll_interval_secs = loaded.email.poll_interval_secs;
+        if !report.applied.is_empty() {
+            self.inner.config.send_replace(Arc::new(updated));
+        }
+
+        info!("Configuration reloaded; applied: [{}]", report.applied.join(", "));
+        if !report.needs_restart.is_empty() {
+            warn!("Changed settings need a restart: {}", report.needs_restart.join(", "));
+        }
+        Ok(report)
+    }
+
+    fn listen_for_sighup(&self) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let live = self.clone();
+            tokio::spawn(async move {
+                let mut hangups = match signal(SignalKind::hangup()) {
+                    Ok(hangups) => hangups,
+                    Err(e) => {
+                        warn!("Cannot listen for SIGHUP: {}", e);
+                        return;
+                    }
+                };
+                while hangups.recv().await.is_some() {
+                    if let Err(e) = live.reload().await {
+                        error!("Reload failed, keeping the current configuration: {}", e);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Changes the log level filter, e.g. `debug` or
+    /// `info,persistence_service=trace`.
+    pub fn set_log_level(&self, level: &str) -> Result<(), ConfigError> {
+        let filter = EnvFilter::try_new(level).map_err(|e| ConfigError::Invalid {
+            setting: "log level".to_string(),
+            message: e.to_string(),
+        })?;
+        self.inner.log_filter.reload(filter).map_err(|e| ConfigError::Invalid {
+            setting: "log level".to_string(),
+            message: e.to_string(),
+        })?;
+        let mut updated = (*self.current()).clone();
+        updated.log_level = level.to_string();
+        self.inner.config.send_replace(Arc::new(updated));
+        info!("Log level set to {}", level);
+        Ok(())
+    }
+
+    /// `POST /api/v1/admin/reload` and `PUT /api/v1/admin/log-level`, to
+    /// merge into a service's router.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/api/v1/admin/reload", post(reload_config))
+            .route("/api/v1/admin/log-level", put(set_log_level))
+            .with_state(self.clone())
+    }
+
+    fn check_admin(&self, headers: &HeaderMap) -> ServiceResult<()> {
+        let expected = self
+            .inner
+            .admin_token
+            .as_deref()
+            .ok_or_else(|| ServiceError::Unauthorized("Admin API is disabled".to_string()))?;
+        let provided = headers
+            .get("x-admin-token")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| ServiceError::Unauthorized("Missing admin token".to_string()))?;
+        if provided != expected {
+            return Err(ServiceError::Unauthorized("Invalid admin token".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogLevel {
+    pub level: String,
+}
+
+async fn set_log_level(
+    State(live): State<LiveConfig>,
+    headers: HeaderMap,
+    Json(request): Json<LogLevel>,
+) -> ServiceResult<Json<LogLevel>> {
+    live.check_admin(&headers)?;
+    live.set_log_level(request.level.trim())
+        .map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+    Ok(Json(LogLevel { level: live.current().log_level.clone() }))
+}
+
+async fn reload_config(State(live): State<LiveConfig>, headers: HeaderMap) -> ServiceResult<Json<ReloadReport>> {
+    live.check_admin(&headers)?;
+
+    let report = live
+        .reload()
+        .await
+        .map_err(|e| ServiceError::BadRequest(format!("Configuration not reloaded: {}", e)))?;
+    Ok(Json(report))
+}
+
+/// Every setting by its dotted name, sections flattened one level.
+fn settings(config: &ServiceConfig) -> Vec<(String, Value)> {
+    let Ok(Value::Object(top)) = serde_json::to_value(config) else {
+        return Vec::new();
+    };
+    let mut settings = Vec::new();
+    for (section, value) in top {
+        match value {
+            Value::Object(fields) if section != "services" => {
+                for (field, value) in fields {
+                    settings.push((format!("{}.{}", section, field), value));
+                }
+            }
+            value => settings.push((section, value)),
+        }
+    }
+    settings
+}
@@ -0,0 +1,392 @@
+//! Secrets from a secrets manager.
+//!
+//! The OpenAI key, the Azure client secret, the database credentials, the
+//! token-encryption key, the OAuth state secret and the internal service
+//! token can come from HashiCorp
+//! Vault (a KV secret) or AWS Secrets Manager (a JSON secret) instead of
+//! the environment.  Both hold one object keyed by the names below; a name the store does not
+//! have falls back to its environment variable or config file setting.
+//!
+//! [`Secrets::start`] fetches them again every `refresh_secs`, so rotated
+//! values are picked up without a restart.  Code that keeps a secret
+//! around, like the database pool, listens on [`Secrets::subscribe`];
+//! everything else reads [`Secrets::get`] when it needs the value.
+//!
+//! The credentials for the store itself (`VAULT_TOKEN`,
+//! `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`)
+//! are only read from the environment.
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+pub const OPENAI_API_KEY: &str = "OPENAI_API_KEY";
+pub const AZURE_CLIENT_SECRET: &str = "AZURE_CLIENT_SECRET";
+pub const DATABASE_URL: &str = "DATABASE_URL";
+/// Replaces the password in `DATABASE_URL`, for stores that rotate only
+/// the password.
+pub const DATABASE_PASSWORD: &str = "DATABASE_PASSWORD";
+pub const TOKEN_ENCRYPTION_KEY: &str = "TOKEN_ENCRYPTION_KEY";
+/// Signs the `state` of the dashboard's OAuth and SSO sign-ins.
+pub const OAUTH_STATE_SECRET: &str = "OAUTH_STATE_SECRET";
+/// Shared by the services and sent with their calls to each other; see
+/// [`auth::check_internal`](crate::auth::check_internal).
+pub const INTERNAL_SERVICE_TOKEN: &str = "INTERNAL_SERVICE_TOKEN";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretsBackend {
+    #[default]
+    Env,
+    Vault,
+    Aws,
+}
+
+impl std::str::FromStr for SecretsBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "env" => Ok(SecretsBackend::Env),
+            "vault" => Ok(SecretsBackend::Vault),
+            "aws" => Ok(SecretsBackend::Aws),
+            other => Err(format!("unknown backend '{}', expected env, vault or aws", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretsConfig {
+    /// `SECRETS_BACKEND`
+    pub backend: SecretsBackend,
+    /// `VAULT_ADDR`
+    pub vault_addr: String,
+    /// `VAULT_SECRET_PATH`, the API path of the secret, e.g.
+    /// `secret/data/tasks` for a KV version 2 mount.
+    pub vault_path: Option<String>,
+    /// `AWS_REGION`
+    pub aws_region: Option<String>,
+    /// `AWS_SECRET_ID`, the name or ARN of the secret.
+    pub aws_secret_id: Option<String>,
+    /// `SECRETS_REFRESH_SECS`
+    pub refresh_secs: u64,
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            backend: SecretsBackend::Env,
+            vault_addr: "http://127.0.0.1:8200".to_string(),
+            vault_path: None,
+            aws_region: None,
+            aws_secret_id: None,
+            refresh_secs: 300,
+        }
+    }
+}
+
+/// Fetches the current secrets by name.
+#[async_trait]
+pub trait SecretStore: Send + Sync {
+    async fn fetch(&self) -> anyhow::Result<HashMap<String, String>>;
+}
+
+/// Nothing beyond the fallback values.
+pub struct EnvStore;
+
+#[async_trait]
+impl SecretStore for EnvStore {
+    async fn fetch(&self) -> anyhow::Result<HashMap<String, String>> {
+        Ok(HashMap::new())
+    }
+}
+
+pub struct VaultStore {
+    client: reqwest::Client,
+    addr: String,
+    path: String,
+    token: String,
+}
+
+#[async_trait]
+impl SecretStore for VaultStore {
+    async fn fetch(&self) -> anyhow::Result<HashMap<String, String>> {
+        let url = format!("{}/v1/{}", self.addr, self.path);
+        let body: Value = self
+            .client
+            .get(&url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        // KV version 2 nests the secret one level deeper than version 1.
+        let data = match body.pointer("/data/data") {
+            Some(Value::Object(_)) => &body["data"]["data"],
+            _ => &body["data"],
+        };
+        strings(data)
+    }
+}
+
+pub struct AwsStore {
+    client: reqwest::Client,
+    region: String,
+    secret_id: String,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetSecretValueResponse {
+    secret_string: Option<String>,
+}
+
+#[async_trait]
+impl SecretStore for AwsStore {
+    async fn fetch(&self) -> anyhow::Result<HashMap<String, String>> {
+        let host = format!("secretsmanager.{}.amazonaws.com", self.region);
+        let body = serde_json::json!({ "SecretId": self.secret_id }).to_string();
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        // Signature Version 4; headers sorted by name.
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host.clone()),
+            ("x-amz-date", amz_date.clone()),
+            ("x-amz-target", "secretsmanager.GetSecretValue".to_string()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        headers.sort_by(|a, b| a.0.cmp(b.0));
+        let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value)).collect();
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+        let canonical_request = format!(
+            "POST\n/\n\n{}\n{}\n{}",
+            canonical_headers,
+            signed_headers,
+            hex(&Sha256::digest(body.as_bytes()))
+        );
+        let scope = format!("{}/{}/secretsmanager/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut key = hmac(format!("AWS4{}", self.secret_access_key).as_bytes(), date.as_bytes());
+        for part in [self.region.as_str(), "secretsmanager", "aws4_request"] {
+            key = hmac(&key, part.as_bytes());
+        }
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id, scope, signed_headers, signature
+        );
+
+        let mut request = self.client.post(format!("https://{}/", host)).header("Authorization", authorization);
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        let response: GetSecretValueResponse =
+            request.body(body).send().await?.error_for_status()?.json().await?;
+        let secret = response
+            .secret_string
+            .ok_or_else(|| anyhow::anyhow!("Secret {} has no SecretString", self.secret_id))?;
+        strings(&serde_json::from_str(&secret)?)
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The string values of a JSON object.
+fn strings(value: &Value) -> anyhow::Result<HashMap<String, String>> {
+    let object = value.as_object().ok_or_else(|| anyhow::anyhow!("Secret is not a JSON object"))?;
+    Ok(object
+        .iter()
+        .filter_map(|(name, value)| value.as_str().map(|value| (name.clone(), value.to_string())))
+        .collect())
+}
+
+fn required_env(name: &str) -> anyhow::Result<String> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("{} is required", name))
+}
+
+pub struct Secrets {
+    backend: SecretsBackend,
+    store: Box<dyn SecretStore>,
+    fallback: HashMap<String, String>,
+    values: RwLock<HashMap<String, String>>,
+    refresh: Duration,
+    changed: watch::Sender<u64>,
+}
+
+impl std::fmt::Debug for Secrets {
+    /// Names only; values never end up in logs.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let values = self.values.read().unwrap_or_else(|e| e.into_inner());
+        let mut names: Vec<&String> = values.keys().collect();
+        names.sort();
+        f.debug_struct("Secrets").field("backend", &self.backend).field("names", &names).finish()
+    }
+}
+
+impl Default for Secrets {
+    fn default() -> Self {
+        Self::with_store(SecretsBackend::Env, Box::new(EnvStore), HashMap::new(), Duration::from_secs(300))
+    }
+}
+
+impl Secrets {
+    fn with_store(
+        backend: SecretsBackend,
+        store: Box<dyn SecretStore>,
+        fallback: HashMap<String, String>,
+        refresh: Duration,
+    ) -> Self {
+        Self {
+            backend,
+            store,
+            values: RwLock::new(fallback.clone()),
+            fallback,
+            refresh,
+            changed: watch::channel(0).0,
+        }
+    }
+
+    /// Connects to the configured store and fetches the secrets once.
+    /// `fallback` holds the values from the environment and config file.
+    pub async fn load(config: &SecretsConfig, fallback: HashMap<String, String>) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder().timeout(Duration::from_secs(10)).build()?;
+        let store: Box<dyn SecretStore> = match config.backend {
+            SecretsBackend::Env => Box::new(EnvStore),
+            SecretsBackend::Vault => Box::new(VaultStore {
+                client,
+                addr: config.vault_addr.trim_end_matches('/').to_string(),
+                path: config
+                    .vault_path
+                    .clone()
+                    .ok_or_else(|| anyhow::anyhow!("VAULT_SECRET_PATH is required"))?
+                    .trim_matches('/')
+                    .to_string(),
+                token: required_env("VAULT_TOKEN")?,
+            }),
+            SecretsBackend::Aws => Box::new(AwsStore {
+                client,
+                region: config.aws_region.clone().ok_or_else(|| anyhow::anyhow!("AWS_REGION is required"))?,
+                secret_id: config
+                    .aws_secret_id
+                    .clone()
+                    .ok_or_else(|| anyhow::anyhow!("AWS_SECRET_ID is required"))?,
+                access_key_id: required_env("AWS_ACCESS_KEY_ID")?,
+                secret_access_key: required_env("AWS_SECRET_ACCESS_KEY")?,
+                session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+            }),
+        };
+        let secrets = Self::with_store(
+            config.backend,
+            store,
+            fallback,
+            Duration::from_secs(config.refresh_secs.max(1)),
+        );
+        secrets.refresh().await?;
+        Ok(secrets)
+    }
+
+    /// Fixed values, without a store.
+    #[cfg(feature = "test-util")]
+    pub fn fixed(values: &[(&str, &str)]) -> Self {
+        let values = values.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        Self::with_store(SecretsBackend::Env, Box::new(EnvStore), values, Duration::from_secs(300))
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.values.read().unwrap_or_else(|e| e.into_inner()).get(name).cloned()
+    }
+
+    /// `DATABASE_URL` with the password from `DATABASE_PASSWORD`, if any.
+    pub fn database_url(&self) -> Option<String> {
+        let url = self.get(DATABASE_URL)?;
+        let Some(password) = self.get(DATABASE_PASSWORD) else {
+            return Some(url);
+        };
+        let Ok(mut parsed) = reqwest::Url::parse(&url) else {
+            return Some(url);
+        };
+        match parsed.set_password(Some(&password)) {
+            Ok(()) => Some(parsed.to_string()),
+            Err(()) => Some(url),
+        }
+    }
+
+    /// Changes whenever a refresh brings a rotated secret.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.changed.subscribe()
+    }
+
+    /// Fetches the secrets again every `refresh_secs` in the background.
+    /// A failed fetch keeps the current values.
+    pub fn start(self: &Arc<Self>) {
+        if self.backend == SecretsBackend::Env {
+            return;
+        }
+        let secrets = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(secrets.refresh);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = secrets.refresh().await {
+                    warn!("Refreshing secrets failed, keeping the current ones: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn refresh(&self) -> anyhow::Result<()> {
+        let mut fetched = self.fallback.clone();
+        fetched.extend(self.store.fetch().await?);
+
+        let rotated: Vec<String> = {
+            let mut values = self.values.write().unwrap_or_else(|e| e.into_inner());
+            let mut rotated: Vec<String> = fetched
+                .iter()
+                .filter(|(name, value)| values.get(*name) != Some(*value))
+                .map(|(name, _)| name.clone())
+                .collect();
+            rotated.sort();
+            *values = fetched;
+            rotated
+        };
+        if !rotated.is_empty() {
+            info!("Secrets updated: {}", rotated.join(", "));
+            self.changed.send_modify(|version| *version += 1);
+        }
+        Ok(())
+    }
+}
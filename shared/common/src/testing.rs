@@ -0,0 +1,152 @@
+//! Test doubles for handlers, behind the `test-util` feature: a
+//! [`MockTransport`] that answers [`HttpClient`] requests from canned
+//! replies, so a handler can be run without the services it calls.
+//!
+//! ```ignore
+//! let mock = MockTransport::new();
+//! mock.reply(Method::POST, "/api/v1/cases", &case);
+//! mock.reply(Method::POST, "/api/v1/cases/*/history", &entry);
+//! let client = mock.client();
+//! // ... run the handler with `client` ...
+//! assert_eq!(mock.requests_to(Method::POST, "/api/v1/cases").len(), 1);
+//! ```
+
+use crate::http_client::{HttpClient, Transport};
+use async_trait::async_trait;
+use reqwest::{header, Method, Request, Response};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+/// The internal service token of
+/// [`ServiceConfig::for_tests`](crate::config::ServiceConfig::for_tests).
+pub const TEST_INTERNAL_TOKEN: &str = "test-internal-token";
+
+/// A request the mock received.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub path: String,
+    pub query: Option<String>,
+    /// The forwarded session, if any.
+    pub bearer: Option<String>,
+    /// The JSON body; `Null` without one.
+    pub body: Value,
+}
+
+struct Route {
+    method: Method,
+    path: String,
+    /// Only requests with exactly this query match; any do when `None`.
+    query: Option<String>,
+    status: u16,
+    body: Value,
+}
+
+#[derive(Default)]
+pub struct MockTransport {
+    routes: Mutex<Vec<Route>>,
+    requests: Mutex<Vec<RecordedRequest>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// An [`HttpClient`] whose requests go to this mock.
+    pub fn client(self: &Arc<Self>) -> HttpClient {
+        HttpClient::with_transport(self.clone())
+    }
+
+    /// Answers `method` requests to `path` with `body` and a 200. A `*`
+    /// segment in `path` matches any one segment; the query is ignored.
+    /// Later replies for the same request take precedence.
+    pub fn reply(&self, method: Method, path: &str, body: impl Serialize) -> &Self {
+        self.reply_with_status(method, path, 200, body)
+    }
+
+    pub fn reply_with_status(&self, method: Method, path: &str, status: u16, body: impl Serialize) -> &Self {
+        let body = serde_json::to_value(body).expect("mock reply is serializable");
+        self.routes.lock().unwrap().push(Route { method, path: path.to_string(), query: None, status, body });
+        self
+    }
+
+    /// Answers like [`reply`](Self::reply), but only requests whose query
+    /// string is exactly `query`, as for the pages of a listing.
+    pub fn reply_to_query(&self, method: Method, path: &str, query: &str, body: impl Serialize) -> &Self {
+        let body = serde_json::to_value(body).expect("mock reply is serializable");
+        let query = Some(query.to_string());
+        self.routes.lock().unwrap().push(Route { method, path: path.to_string(), query, status: 200, body });
+        self
+    }
+
+    /// Every request received, in order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The requests received for `method` and `path`, matched as in
+    /// [`reply`](Self::reply).
+    pub fn requests_to(&self, method: Method, path: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.method == method && path_matches(path, &request.path))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn execute(&self, request: Request) -> Result<Response, reqwest::Error> {
+        let bearer = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_string);
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .and_then(|bytes| serde_json::from_slice(bytes).ok())
+            .unwrap_or(Value::Null);
+        let recorded = RecordedRequest {
+            method: request.method().clone(),
+            path: request.url().path().to_string(),
+            query: request.url().query().map(str::to_string),
+            bearer,
+            body,
+        };
+
+        let (status, body) = self
+            .routes
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|route| {
+                route.method == recorded.method
+                    && path_matches(&route.path, &recorded.path)
+                    && (route.query.is_none() || route.query == recorded.query)
+            })
+            .map(|route| (route.status, route.body.clone()))
+            .unwrap_or_else(|| {
+                let error = format!("no mock reply for {} {}", recorded.method, recorded.path);
+                (404, serde_json::json!({ "error": error }))
+            });
+        self.requests.lock().unwrap().push(recorded);
+
+        let response = http::Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .expect("mock response is valid");
+        Ok(Response::from(response))
+    }
+}
+
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.trim_end_matches('/').split('/').collect();
+    let path: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    pattern.len() == path.len() && pattern.iter().zip(&path).all(|(expected, actual)| *expected == "*" || expected == actual)
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -20,6 +20,156 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
     pub last_login: Option<DateTime<Utc>>,
     pub metadata: serde_json::Value,
+    pub preferences: UserPreferences,
+}
+
+/// Dashboard settings chosen by the user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserPreferences {
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub default_view: DefaultView,
+    #[serde(default = "default_tasks_per_page")]
+    pub tasks_per_page: u32,
+    /// Estimated minutes of work per day above which the dashboard warns
+    /// that the day is overcommitted.
+    #[serde(default = "default_daily_capacity_minutes")]
+    pub daily_capacity_minutes: u32,
+    #[serde(default)]
+    pub notifications: NotificationPreferences,
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default(),
+            default_view: DefaultView::default(),
+            tasks_per_page: default_tasks_per_page(),
+            daily_capacity_minutes: default_daily_capacity_minutes(),
+            notifications: NotificationPreferences::default(),
+        }
+    }
+}
+
+fn default_tasks_per_page() -> u32 {
+    24
+}
+
+fn default_daily_capacity_minutes() -> u32 {
+    480
+}
+
+/// Which notifications the user gets, and when pushes may reach them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationPreferences {
+    /// Browser push for task assignments and overdue reminders.
+    #[serde(default = "default_push")]
+    pub push: bool,
+    /// Kinds left out of the bell menu and push altogether.
+    #[serde(default)]
+    pub muted: Vec<NotificationKind>,
+    #[serde(default)]
+    pub delivery: NotificationDelivery,
+    /// Local time of the daily digest push.
+    #[serde(default = "default_digest_time")]
+    pub digest_time: NaiveTime,
+    /// Pushes falling in this window wait until it ends.
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// The user's clock in minutes east of UTC, for quiet hours and the
+    /// digest time.
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    /// Email the weekly summaries of the user's open cases.
+    #[serde(default)]
+    pub weekly_summary_email: bool,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            push: default_push(),
+            muted: Vec::new(),
+            delivery: NotificationDelivery::default(),
+            digest_time: default_digest_time(),
+            quiet_hours: None,
+            utc_offset_minutes: 0,
+            weekly_summary_email: false,
+        }
+    }
+}
+
+fn default_push() -> bool {
+    true
+}
+
+fn default_digest_time() -> NaiveTime {
+    NaiveTime::from_hms_opt(8, 0, 0).expect("valid time")
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotificationDelivery {
+    /// Each push as it happens.
+    #[default]
+    Immediate,
+    /// One push a day at `digest_time` summing up the rest.
+    Digest,
+}
+
+/// A daily window, in the user's local time, that may span midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Follow the operating system setting.
+    #[default]
+    System,
+}
+
+impl Theme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::System => "system",
+        }
+    }
+}
+
+/// Page the dashboard opens on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DefaultView {
+    #[default]
+    List,
+    Board,
+}
+
+impl DefaultView {
+    pub fn path(&self) -> &'static str {
+        match self {
+            DefaultView::List => "/dashboard",
+            DefaultView::Board => "/board",
+        }
+    }
+}
+
+/// Partial update of [`UserPreferences`]; omitted fields are unchanged.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdatePreferencesRequest {
+    pub theme: Option<Theme>,
+    pub default_view: Option<DefaultView>,
+    pub tasks_per_page: Option<u32>,
+    pub daily_capacity_minutes: Option<u32>,
+    /// Replaces the notification preferences as a whole.
+    #[serde(default)]
+    pub notifications: Option<NotificationPreferences>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,12 +183,33 @@ pub struct EmailAccount {
     pub oauth_refresh_token: Option<String>,
     pub oauth_expires_at: Option<DateTime<Utc>>,
     pub imap_settings: Option<ImapSettings>,
+    /// Permissions the user granted when connecting the mailbox.
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub health: MailboxHealth,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub metadata: serde_json::Value,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How polling a connected mailbox goes, as reported by the email
+/// collector.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MailboxHealth {
+    pub last_polled_at: Option<DateTime<Utc>>,
+    /// Microsoft Graph asked to slow down; the mailbox isn't polled again
+    /// before this.
+    pub throttled_until: Option<DateTime<Utc>>,
+    /// Polls in a row that were throttled; each one backs off longer.
+    #[serde(default)]
+    pub consecutive_throttles: u32,
+    /// Throttled Graph responses for the mailbox, ever.
+    #[serde(default)]
+    pub throttled_total: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EmailProvider {
     Office365,
     Gmail,
@@ -78,18 +249,200 @@ pub struct Case {
     pub updated_at: DateTime<Utc>,
     pub assigned_to: Option<String>,
     pub metadata: serde_json::Value,
+    /// Free-form labels, lowercase and sorted.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Conversation entries the requesting user has not read yet; only set
+    /// on case lists fetched with a session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unread_count: Option<i64>,
+    /// When the case first left `Open`.
+    #[serde(default)]
+    pub first_response_at: Option<DateTime<Utc>>,
+    /// When the case was last resolved or closed; cleared on reopening.
+    #[serde(default)]
+    pub resolved_at: Option<DateTime<Utc>>,
+    /// Standing against the SLA for its priority, computed by case
+    /// management.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sla: Option<CaseSla>,
+}
+
+impl Case {
+    /// How urgent the case looked when it was opened, if it was scored.
+    pub fn urgency(&self) -> Option<CaseUrgency> {
+        self.metadata.get("urgency").and_then(|urgency| serde_json::from_value(urgency.clone()).ok())
+    }
+}
+
+/// The AI agent's estimate of how soon a new case needs attention. Kept in
+/// the case's `metadata.urgency`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CaseUrgency {
+    /// From 0 (routine) to 100.
+    pub score: u8,
+    pub reasons: Vec<String>,
+}
+
+/// Target response and resolution times for cases of a priority.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlaPolicy {
+    pub priority: Priority,
+    pub response_minutes: i64,
+    pub resolution_minutes: i64,
+}
+
+/// How a case stands against its SLA.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseSla {
+    /// The worse of the response and resolution statuses.
+    pub status: SlaStatus,
+    pub response_status: SlaStatus,
+    pub response_due: DateTime<Utc>,
+    pub resolution_status: SlaStatus,
+    pub resolution_due: DateTime<Utc>,
+}
+
+/// Ordered from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlaStatus {
+    /// The target was reached in time.
+    Met,
+    OnTrack,
+    /// Most of the time allowed has passed.
+    AtRisk,
+    Breached,
+}
+
+/// When a user last read a case's conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseReadReceipt {
+    pub case_id: Uuid,
+    pub user_id: Uuid,
+    pub last_read_at: DateTime<Utc>,
+}
+
+/// A user `@`-mentioned in a conversation entry by a member of their
+/// organization.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mention {
+    pub id: Uuid,
+    pub entry_id: Uuid,
+    pub case_id: Uuid,
+    /// The user mentioned.
+    pub user_id: Uuid,
+    pub author_id: Uuid,
+    /// The entry's text.
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A clarifying question the AI agent asked about a task it created
+/// without key details. It waits on the case until the next message there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowUpQuestion {
+    pub id: Uuid,
+    pub case_id: Uuid,
+    pub task_id: Uuid,
+    /// Owner of the case; assigned by persistence.
+    pub user_id: Uuid,
+    pub question: String,
+    pub missing: Vec<MissingDetail>,
+    /// Where the message the task came from arrived, and who sent it; the
+    /// question is asked there.
+    pub channel: MessageChannel,
+    pub recipient: String,
+    pub status: QuestionStatus,
+    pub created_at: DateTime<Utc>,
+    pub answered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MissingDetail {
+    /// An urgent task with no due date.
+    DueDate,
+    /// A meeting with no start time.
+    MeetingTime,
+    /// A meeting with no attendees.
+    Attendees,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuestionStatus {
+    Pending,
+    /// A later message on the case was taken as the answer.
+    Answered,
+}
+
+/// Marks questions answered once the agent has seen the message that
+/// answers them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnswerQuestionsRequest {
+    pub question_ids: Vec<Uuid>,
+}
+
+/// Hands a case, with its tasks and conversation, to another user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferCaseRequest {
+    /// Email of the account taking the case over.
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateShareLinkRequest {
+    /// How long the link works; a week when not given.
+    #[serde(default)]
+    pub expires_in_hours: Option<i64>,
+}
+
+/// A signed token giving read-only access to one case until it expires.
+/// The dashboard serves it at `/shared/<token>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub case_id: Uuid,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// What a share link shows: the case and its tasks, without their
+/// metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedCase {
+    pub case: Case,
+    pub tasks: Vec<Task>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Where to put a case exported to Notion.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotionExportRequest {
+    /// The Notion database the page is added to; the configured default
+    /// when omitted.
+    #[serde(default)]
+    pub database_id: Option<String>,
 }
 
+/// The Notion page a case was exported to.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotionExport {
+    pub page_id: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CaseStatus {
     Open,
     InProgress,
     Waiting,
     Resolved,
     Closed,
+    /// Closed and out of case lists; set by the retention sweep.
+    Archived,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Ordered from lowest to highest.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Priority {
     Low,
     Medium,
@@ -112,9 +465,35 @@ pub struct Task {
     pub updated_at: DateTime<Utc>,
     pub completed_at: Option<DateTime<Utc>>,
     pub metadata: serde_json::Value,
+    /// Free-form labels, lowercase and sorted.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Expected effort, when known.
+    #[serde(default)]
+    pub estimate_minutes: Option<i32>,
+}
+
+impl Task {
+    /// Overlapping meetings found when this meeting was created.
+    pub fn meeting_conflicts(&self) -> Vec<MeetingConflict> {
+        self.metadata
+            .get("meeting_conflicts")
+            .and_then(|conflicts| serde_json::from_value(conflicts.clone()).ok())
+            .unwrap_or_default()
+    }
 }
 
+/// An open meeting of the same user that overlaps a newly created one. Kept
+/// in the new task's `metadata.meeting_conflicts`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeetingConflict {
+    pub task_id: Uuid,
+    pub title: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TaskType {
     Meeting,
     Shopping,
@@ -125,13 +504,26 @@ pub enum TaskType {
     Other(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl TaskType {
+    /// The type's name: the variant name, or an `Other` type's label.
+    pub fn label(&self) -> String {
+        match self {
+            TaskType::Other(label) => label.clone(),
+            other => format!("{:?}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TaskStatus {
     Pending,
     InProgress,
     Completed,
     Cancelled,
     OnHold,
+    /// Extracted by the AI agent with low confidence; waits in the review
+    /// queue until someone approves or rejects it.
+    NeedsReview,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -179,21 +571,144 @@ pub enum StepStatus {
     Skipped,
 }
 
+/// A recorded change to a case, its tasks or its workflow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseEvent {
+    pub id: Uuid,
+    pub case_id: Uuid,
+    /// User who made the change; `None` for internal calls.
+    pub actor_id: Option<Uuid>,
+    pub kind: CaseEventKind,
+    pub summary: String,
+    /// Task the event is about, if any.
+    pub subject_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaseEventKind {
+    CaseStatusChanged,
+    TaskStatusChanged,
+    TaskDeleted,
+    WorkflowStepChanged,
+    SlaWarning,
+    SlaBreached,
+    CaseTransferred,
+}
+
+/// A recorded change to a case or task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    pub id: Uuid,
+    pub entity: RevisionEntity,
+    pub entity_id: Uuid,
+    /// User who made the change; `None` for internal calls.
+    pub actor_id: Option<Uuid>,
+    /// Changed fields as `{"field": {"from": old, "to": new}}`; `from` is
+    /// null for the revision that created the entity.
+    pub changes: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RevisionEntity {
+    Case,
+    Task,
+}
+
+/// A case event reported by another service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseEventRequest {
+    pub kind: CaseEventKind,
+    pub summary: String,
+    pub subject_id: Option<Uuid>,
+    /// Record the event only if the case has none of this kind yet.
+    #[serde(default)]
+    pub once: bool,
+}
+
+/// One entry of a case's activity timeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    pub timestamp: DateTime<Utc>,
+    pub source: TimelineSource,
+    pub summary: String,
+    /// Message text for conversation entries.
+    pub detail: Option<String>,
+    /// Who sent a conversation entry.
+    pub sender: Option<MessageSender>,
+    /// Task the entry is about, if any.
+    pub subject_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimelineSource {
+    Conversation,
+    Task,
+    Workflow,
+    Audit,
+}
+
 // API Request/Response models
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageRequest {
     pub case_id: Option<Uuid>,
     pub message: String,
     pub sender_id: String,
     pub channel: MessageChannel,
+    /// Identifiers of the email the message came from, used to add replies
+    /// to the case of their thread.
+    #[serde(default)]
+    pub email_thread: Option<EmailThread>,
+    /// Owner of a case opened for a message that comes without a session,
+    /// from an integration the user set up. A session's user takes
+    /// precedence.
+    #[serde(default)]
+    pub owner_id: Option<Uuid>,
+    /// Priority the message's source gives it, e.g. an email marked as
+    /// important. A new case starts with it and tasks get at least it.
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    /// Labels the message's source gives it, e.g. an email's categories,
+    /// added to a new case and to the tasks created for the message.
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Identifies an email within its thread. Cases remember these under
+/// `metadata.email`, so later replies find the case again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmailThread {
+    /// `Message-ID` header (Graph `internetMessageId`).
+    pub message_id: Option<String>,
+    /// Graph `conversationId`, shared by every message of a thread.
+    pub conversation_id: Option<String>,
+    /// `In-Reply-To` header: the `Message-ID` this email answers.
+    pub in_reply_to: Option<String>,
+}
+
+impl EmailThread {
+    /// The `Message-ID`s this email is known by or replies to.
+    pub fn message_ids(&self) -> Vec<String> {
+        self.message_id.iter().chain(&self.in_reply_to).cloned().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conversation_id.is_none() && self.message_ids().is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MessageChannel {
     Bot,
     Email,
     WebChat,
     API,
+    /// Transcribed voice notes.
+    Voice,
+    WhatsApp,
+    /// Events from a generic webhook integration.
+    Webhook,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -203,6 +718,242 @@ pub struct MessageResponse {
     pub actions_taken: Vec<String>,
     pub tasks_created: Vec<Uuid>,
     pub tasks_updated: Vec<Uuid>,
+    /// Questions about created tasks, also asked in `response`. Channels
+    /// that cannot show the reply, such as email, send them on.
+    #[serde(default)]
+    pub questions: Vec<FollowUpQuestion>,
+}
+
+/// The reply to a voice note: what was heard, and the agent's reply to it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoiceNoteResponse {
+    pub transcript: String,
+    #[serde(flatten)]
+    pub response: MessageResponse,
+}
+
+/// A meeting transcript to turn into minutes and tasks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptRequest {
+    pub transcript: String,
+    /// Title of the meeting, for the minutes and a new case.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// The case to file the action items under; a new case when not given.
+    #[serde(default)]
+    pub case_id: Option<Uuid>,
+    /// When the meeting took place; relative due dates count from it.
+    #[serde(default)]
+    pub held_at: Option<DateTime<Utc>>,
+}
+
+/// Minutes of a meeting, with the tasks created for its action items.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeetingMinutes {
+    pub case_id: Uuid,
+    pub title: String,
+    pub summary: String,
+    pub decisions: Vec<String>,
+    pub action_items: Vec<ActionItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionItem {
+    pub title: String,
+    /// Who took the item on, as named in the meeting.
+    pub owner: Option<String>,
+    pub due_date: Option<DateTime<Utc>>,
+    /// The task created for the item.
+    pub task_id: Option<Uuid>,
+}
+
+/// A meeting invitation found in an email, from a Graph event message or
+/// iCalendar data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeetingInvite {
+    pub title: String,
+    pub starts_at: DateTime<Utc>,
+    #[serde(default)]
+    pub ends_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub location: Option<String>,
+    /// Address of the organizer.
+    #[serde(default)]
+    pub organizer: Option<String>,
+    /// Addresses of the attendees.
+    #[serde(default)]
+    pub attendees: Vec<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// iCalendar `UID`, shared by the invitation and its updates.
+    #[serde(default)]
+    pub uid: Option<String>,
+}
+
+/// A meeting invitation to file as a Meeting task, instead of extracting
+/// tasks from the invitation's text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteRequest {
+    pub invite: MeetingInvite,
+    pub sender_id: String,
+    /// Identifiers of the email the invitation came with; updates of the
+    /// invitation join the case of its thread.
+    #[serde(default)]
+    pub email_thread: Option<EmailThread>,
+    /// Owner of a new case, as for [`MessageRequest::owner_id`].
+    #[serde(default)]
+    pub owner_id: Option<Uuid>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A plain text email sent from a connected mailbox by the email
+/// collector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendEmailRequest {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    /// The user whose connected mailbox sends the email.
+    #[serde(default)]
+    pub user_id: Option<Uuid>,
+}
+
+/// Events emitted while a message is processed in streaming mode. Each is
+/// sent as one server-sent event whose name is the `type` tag.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentStreamEvent {
+    /// The case the message was filed under; sent before any tokens.
+    Case { case_id: Uuid },
+    /// A fragment of the agent's reply text.
+    Token { text: String },
+    /// A task created from the message.
+    Task { task: Task },
+    /// Processing finished; carries the same summary as the non-streaming API.
+    Done { response: MessageResponse },
+    Error { message: String },
+}
+
+impl AgentStreamEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AgentStreamEvent::Case { .. } => "case",
+            AgentStreamEvent::Token { .. } => "token",
+            AgentStreamEvent::Task { .. } => "task",
+            AgentStreamEvent::Done { .. } => "done",
+            AgentStreamEvent::Error { .. } => "error",
+        }
+    }
+}
+
+/// A message the channel service's spam filter held back from the AI
+/// agent until it is reviewed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuarantinedMessage {
+    pub id: Uuid,
+    /// Owner of the session the message came with; `None` for messages
+    /// from webhooks and collectors.
+    pub user_id: Option<Uuid>,
+    pub message: MessageRequest,
+    /// Why the filter held the message back.
+    pub reasons: Vec<String>,
+    pub status: QuarantineStatus,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuarantineStatus {
+    Pending,
+    /// Sent on to the AI agent after review.
+    Released,
+    Discarded,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReviewQuarantineRequest {
+    pub status: QuarantineStatus,
+}
+
+/// A message whose processing failed because the AI agent or persistence
+/// was unavailable, kept so it can be processed again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedMessage {
+    pub id: Uuid,
+    /// Owner of the session the message came with; `None` for messages
+    /// from webhooks and collectors, which are retried automatically.
+    pub user_id: Option<Uuid>,
+    pub message: MessageRequest,
+    /// The latest failure.
+    pub error: String,
+    pub status: FailedMessageStatus,
+    /// Processing attempts, the original one included.
+    pub attempts: i32,
+    /// When the next automatic attempt is due.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailedMessageStatus {
+    Pending,
+    Reprocessed,
+    /// Out of automatic attempts; can still be retried by hand.
+    Abandoned,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateFailedMessageRequest {
+    pub message: MessageRequest,
+    pub error: String,
+}
+
+/// The outcome of processing a failed message again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FailedMessageAttempt {
+    /// `None` when processing succeeded.
+    pub error: Option<String>,
+}
+
+/// A user's verdict on a task the AI agent extracted, kept with the
+/// message it came from as a labeled example for prompt tuning.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskFeedback {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub task_id: Uuid,
+    pub verdict: FeedbackVerdict,
+    /// Message the task was extracted from.
+    pub message: String,
+    /// The task as the agent extracted it.
+    pub extracted: serde_json::Value,
+    /// The task as it should have been extracted; set for edits.
+    pub corrected: Option<serde_json::Value>,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeedbackVerdict {
+    Accepted,
+    Rejected,
+    /// The task was right to create but needed changes.
+    Edited,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskFeedbackRequest {
+    pub verdict: FeedbackVerdict,
+    /// Corrected task fields for an edit; the task's current fields when
+    /// omitted.
+    #[serde(default)]
+    pub corrected: Option<serde_json::Value>,
+    #[serde(default)]
+    pub comment: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -211,6 +962,18 @@ pub struct CreateCaseRequest {
     pub description: Option<String>,
     pub priority: Priority,
     pub assigned_to: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// ISO 639-1 code of the language the case is conducted in, kept in
+    /// the case's `metadata.language`.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Kept in the case's `metadata.urgency`.
+    #[serde(default)]
+    pub urgency: Option<CaseUrgency>,
+    /// Owner of the case when it is created without a session.
+    #[serde(default)]
+    pub user_id: Option<Uuid>,
 }
 
 // User Management Request/Response Models
@@ -226,6 +989,32 @@ pub struct RegisterRequest {
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
+    /// The browser signing in, as seen by the dashboard.
+    #[serde(default)]
+    pub client: ClientInfo,
+}
+
+/// Where a sign-in comes from, kept on its session.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientInfo {
+    #[serde(default)]
+    pub ip_address: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+/// A sign-in attempt on an account, for its owner's login history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginEvent {
+    pub id: Uuid,
+    pub succeeded: bool,
+    /// From a device or network the account hadn't used lately.
+    pub unusual: bool,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    /// Browser and operating system, e.g. `Firefox on Linux`.
+    pub device: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -244,24 +1033,154 @@ pub struct UserProfile {
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub last_login: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub preferences: UserPreferences,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AddEmailAccountRequest {
-    pub email_address: String,
-    pub provider: EmailProvider,
-    pub oauth_token: Option<String>,
-    pub oauth_refresh_token: Option<String>,
-    pub imap_settings: Option<ImapSettings>,
+impl From<User> for UserProfile {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            full_name: user.full_name,
+            organization: user.organization,
+            is_active: user.is_active,
+            created_at: user.created_at,
+            last_login: user.last_login,
+            preferences: user.preferences,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UpdateUserRequest {
+/// An account at an OpenID Connect provider that signs in as a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SsoIdentity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// The dashboard's name for the provider, e.g. `google`.
+    pub provider: String,
+    pub issuer: String,
+    /// The provider's stable id for the account, its `sub` claim.
+    pub subject: String,
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+    pub last_login: Option<DateTime<Utc>>,
+}
+
+/// The verified claims of an ID token, to sign in with or to link to the
+/// session's user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SsoLoginRequest {
+    pub provider: String,
+    pub issuer: String,
+    pub subject: String,
+    pub email: String,
+    /// Whether the provider vouches for the email. An unverified email
+    /// never links to an existing account.
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default)]
     pub full_name: Option<String>,
-    pub organization: Option<String>,
+    #[serde(default)]
+    pub client: ClientInfo,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// What a personal access token may do. Write scopes include reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ApiScope {
+    #[serde(rename = "tasks:read")]
+    TasksRead,
+    #[serde(rename = "tasks:write")]
+    TasksWrite,
+    #[serde(rename = "cases:read")]
+    CasesRead,
+    #[serde(rename = "cases:write")]
+    CasesWrite,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 4] = [ApiScope::TasksRead, ApiScope::TasksWrite, ApiScope::CasesRead, ApiScope::CasesWrite];
+
+    /// The scope's name, as in the JSON.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiScope::TasksRead => "tasks:read",
+            ApiScope::TasksWrite => "tasks:write",
+            ApiScope::CasesRead => "cases:read",
+            ApiScope::CasesWrite => "cases:write",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == name)
+    }
+
+    /// Whether holding this scope allows what `required` allows.
+    pub fn grants(self, required: ApiScope) -> bool {
+        self == required
+            || matches!(
+                (self, required),
+                (ApiScope::TasksWrite, ApiScope::TasksRead) | (ApiScope::CasesWrite, ApiScope::CasesRead)
+            )
+    }
+}
+
+/// A personal access token, for the CLI and scripts. It is sent like a
+/// session token, as `Authorization: Bearer <token>`, and is limited to its
+/// scopes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// The secret, only returned when created. It is stored hashed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    /// Days until the token expires; it doesn't without.
+    #[serde(default)]
+    pub expires_in_days: Option<i64>,
+}
+
+/// A started OAuth sign-in, kept until its callback takes it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveOAuthStateRequest {
+    /// The `state` parameter sent to the provider.
+    pub state: String,
+    pub payload: serde_json::Value,
+    pub expires_in_secs: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddEmailAccountRequest {
+    pub email_address: String,
+    pub provider: EmailProvider,
+    pub oauth_token: Option<String>,
+    pub oauth_refresh_token: Option<String>,
+    #[serde(default)]
+    pub oauth_expires_at: Option<DateTime<Utc>>,
+    pub imap_settings: Option<ImapSettings>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateUserRequest {
+    pub full_name: Option<String>,
+    pub organization: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ChangePasswordRequest {
     pub current_password: String,
     pub new_password: String,
@@ -274,6 +1193,8 @@ pub struct UpdateCaseRequest {
     pub status: Option<CaseStatus>,
     pub priority: Option<Priority>,
     pub assigned_to: Option<String>,
+    /// Replaces the whole tag set when present.
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -283,6 +1204,24 @@ pub struct CreateTaskRequest {
     pub task_type: TaskType,
     pub priority: Priority,
     pub due_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub estimate_minutes: Option<i32>,
+    /// Extraction confidence reported by the AI agent, kept in the task's
+    /// `metadata.ai_confidence`.
+    #[serde(default)]
+    pub confidence: Option<f32>,
+    /// Creates the task as [`TaskStatus::NeedsReview`] instead of pending.
+    #[serde(default)]
+    pub needs_review: bool,
+    /// Message the AI agent extracted the task from. Kept with the task
+    /// so feedback on it can be labeled with the message.
+    #[serde(default)]
+    pub source_message: Option<String>,
+    /// Initial metadata, e.g. the fields of a custom task type.
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -292,6 +1231,885 @@ pub struct UpdateTaskRequest {
     pub status: Option<TaskStatus>,
     pub priority: Option<Priority>,
     pub due_date: Option<DateTime<Utc>>,
+    /// Replaces the whole tag set when present.
+    pub tags: Option<Vec<String>>,
+    /// Replaces the task metadata when present.
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    #[serde(default)]
+    pub estimate_minutes: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateTaskStatusRequest {
+    pub status: TaskStatus,
+}
+
+// Backup / restore models
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserBackup {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub user: UserProfile,
+    pub cases: Vec<CaseBackup>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CaseBackup {
+    pub case: Case,
+    pub tasks: Vec<Task>,
+    pub conversation: Vec<ConversationEntry>,
+    #[serde(default)]
+    pub workflow: Option<CaseWorkflow>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreConflictMode {
+    /// Keep the existing record when an id is already present.
+    #[default]
+    Skip,
+    /// Replace the existing record with the one from the backup.
+    Overwrite,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RestoreSummary {
+    pub cases_restored: usize,
+    pub cases_skipped: usize,
+    pub tasks_restored: usize,
+    pub tasks_skipped: usize,
+    pub entries_restored: usize,
+    pub entries_skipped: usize,
+    #[serde(default)]
+    pub workflows_restored: usize,
+    #[serde(default)]
+    pub workflows_skipped: usize,
+}
+
+// Account deletion models
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountDeletionMode {
+    /// Remove the user's cases, tasks and conversations entirely.
+    #[default]
+    Delete,
+    /// Keep the records for aggregate reporting but strip their content.
+    Anonymize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AccountDeletionStatus {
+    Pending,
+    Completed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountDeletion {
+    pub user_id: Uuid,
+    pub mode: AccountDeletionMode,
+    pub status: AccountDeletionStatus,
+    pub requested_at: DateTime<Utc>,
+    /// Earliest time an administrator may confirm the deletion.
+    pub scheduled_for: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AccountDeletionRequest {
+    #[serde(default)]
+    pub mode: AccountDeletionMode,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountDeletionResponse {
+    pub deletion: AccountDeletion,
+    /// Everything held about the user at the time of the request.
+    pub export: UserBackup,
+}
+
+// Administration models
+/// What a member of staff may do on the admin API. Support looks up
+/// accounts and reissues verifications; administrators also block,
+/// reactivate and impersonate accounts, and grant roles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum StaffRole {
+    Support,
+    Admin,
+}
+
+impl StaffRole {
+    pub const ALL: [StaffRole; 2] = [StaffRole::Support, StaffRole::Admin];
+
+    /// The role's name, as in the JSON.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StaffRole::Support => "Support",
+            StaffRole::Admin => "Admin",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|role| role.as_str() == name)
+    }
+
+    /// Whether holding this role allows what `required` allows.
+    pub fn grants(self, required: StaffRole) -> bool {
+        self >= required
+    }
+}
+
+/// Why an account is being acted on, recorded in the admin audit log with
+/// the signed-in member of staff acting.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminActionRequest {
+    pub reason: Option<String>,
+}
+
+/// Grants a staff role, or revokes it with `role: null`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StaffRoleRequest {
+    pub role: Option<StaffRole>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdminAction {
+    Deactivate,
+    Activate,
+    ResendVerification,
+    Impersonate,
+    GrantRole(StaffRole),
+    RevokeRole,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminAuditEntry {
+    pub id: Uuid,
+    pub action: AdminAction,
+    pub target_user_id: Uuid,
+    pub actor: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What an account holds and how recently it was used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserUsage {
+    pub user_id: Uuid,
+    pub email: String,
+    pub is_active: bool,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub cases: i64,
+    pub open_cases: i64,
+    pub tasks: i64,
+    pub open_tasks: i64,
+    pub conversation_entries: i64,
+    pub active_sessions: i64,
+    pub last_login: Option<DateTime<Utc>>,
+    /// Latest case, task or conversation change.
+    pub last_activity: Option<DateTime<Utc>>,
+}
+
+/// Synthetic data to create for a load test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedRequest {
+    pub users: u32,
+    pub cases_per_user: u32,
+    pub tasks_per_case: u32,
+}
+
+/// A synthetic user, signed in so a load generator can act as them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeededUser {
+    pub user_id: Uuid,
+    pub email: String,
+    pub session_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedResponse {
+    pub users: Vec<SeededUser>,
+    pub cases: i64,
+    pub tasks: i64,
+    /// When the sessions expire.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A fresh email verification token. Only its hash is stored, so this is
+/// the one chance to deliver it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailVerification {
+    pub user_id: Uuid,
+    pub email: String,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+// Statistics models
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsGrouping {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl StatsGrouping {
+    /// Field name understood by PostgreSQL's `date_trunc`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatsGrouping::Day => "day",
+            StatsGrouping::Week => "week",
+            StatsGrouping::Month => "month",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskStatsPeriod {
+    /// Start of the day, week or month.
+    pub period: DateTime<Utc>,
+    pub created: i64,
+    pub completed: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskTypeCount {
+    pub task_type: TaskType,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriorityCount {
+    pub priority: Priority,
+    pub count: i64,
+}
+
+/// Aggregate task figures for the tasks created between `from` and `to`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskStats {
+    pub group_by: StatsGrouping,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub total: i64,
+    pub completed: i64,
+    /// Share of `total` that has been completed, between 0 and 1.
+    pub completion_rate: f64,
+    /// Mean time from creation to completion, for tasks completed in range.
+    pub average_hours_to_complete: Option<f64>,
+    pub timeline: Vec<TaskStatsPeriod>,
+    pub by_type: Vec<TaskTypeCount>,
+    pub by_priority: Vec<PriorityCount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseStatsPeriod {
+    /// Start of the week (Monday).
+    pub period: DateTime<Utc>,
+    pub opened: i64,
+    /// Cases resolved or closed during the week.
+    pub closed: i64,
+}
+
+/// Productivity rollup for a user, or for everyone when requested without a
+/// session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductivityStats {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub cases_per_week: Vec<CaseStatsPeriod>,
+    /// Mean time from creation to completion of tasks completed in range.
+    pub mean_cycle_time_hours: Option<f64>,
+    /// Tasks that are neither completed nor cancelled, as of now.
+    pub open_tasks: i64,
+    /// Open tasks whose due date has passed.
+    pub overdue_tasks: i64,
+    pub overdue_ratio: f64,
+    /// Inbound email messages received in range.
+    pub emails_received: i64,
+    /// Of those, emails whose case produced at least one task.
+    pub emails_converted: i64,
+    pub email_conversion_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseProductivity {
+    pub case_id: Uuid,
+    pub total_tasks: i64,
+    pub completed_tasks: i64,
+    pub open_tasks: i64,
+    pub overdue_tasks: i64,
+    pub mean_cycle_time_hours: Option<f64>,
+    pub age_hours: f64,
+    /// Most recent conversation entry or task update.
+    pub last_activity: Option<DateTime<Utc>>,
+}
+
+/// One row of the cross-user report used by administrators and BI tools.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserProductivity {
+    pub user_id: Uuid,
+    pub email: String,
+    pub cases_opened: i64,
+    pub cases_closed: i64,
+    pub tasks_created: i64,
+    pub tasks_completed: i64,
+    pub mean_cycle_time_hours: Option<f64>,
+    pub open_tasks: i64,
+    pub overdue_tasks: i64,
+}
+
+/// Open tasks due on one day for one user, with their summed estimates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadDay {
+    /// UTC calendar day of the due dates.
+    pub date: NaiveDate,
+    pub user_id: Uuid,
+    pub tasks: i64,
+    pub estimate_minutes: i64,
+    /// Tasks counted in `tasks` that have no estimate.
+    pub unestimated_tasks: i64,
+}
+
+// Notification models
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotificationKind {
+    TaskAssigned,
+    TaskOverdue,
+    CaseResolved,
+    EmailConnected,
+    SlaWarning,
+    SlaBreached,
+    CaseTransferred,
+    Mentioned,
+    /// An organization used up its monthly LLM token budget.
+    LlmBudgetExhausted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub message: String,
+    /// Dashboard page to open when the notification is clicked.
+    pub link: Option<String>,
+    /// Task or case the notification is about. A user receives at most one
+    /// notification of each kind per subject.
+    pub subject_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateNotificationRequest {
+    pub kind: NotificationKind,
+    pub title: String,
+    pub message: String,
+    pub link: Option<String>,
+    pub subject_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationList {
+    pub unread_count: i64,
+    pub notifications: Vec<Notification>,
+}
+
+/// Tasks changed since a client's last sync, for offline use.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskSync {
+    /// Pass back as `since` on the next sync.
+    pub server_time: DateTime<Utc>,
+    pub tasks: Vec<Task>,
+    pub deleted_task_ids: Vec<Uuid>,
+}
+
+// Task template models
+/// Reusable task definition. `title`, `description` and `checklist` may
+/// contain `{{placeholder}}`s filled in when the template is used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskTemplate {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// Unique per user, e.g. `weekly-report`.
+    pub name: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub task_type: TaskType,
+    pub priority: Priority,
+    /// Due date relative to when the template is used.
+    pub due_in_days: Option<i64>,
+    pub checklist: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskTemplateRequest {
+    pub name: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub task_type: TaskType,
+    pub priority: Priority,
+    pub due_in_days: Option<i64>,
+    #[serde(default)]
+    pub checklist: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InstantiateTemplateRequest {
+    /// Case to add the task to; a new case is opened when omitted.
+    pub case_id: Option<Uuid>,
+    /// Values for the template's placeholders.
+    #[serde(default)]
+    pub values: std::collections::HashMap<String, String>,
+}
+
+// Web Push models
+/// A browser push subscription, in the shape of `PushSubscription.toJSON()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushSubscriptionRequest {
+    pub endpoint: String,
+    pub keys: PushSubscriptionKeys,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushSubscriptionKeys {
+    /// Browser's P-256 public key, base64url encoded.
+    pub p256dh: String,
+    /// Browser's authentication secret, base64url encoded.
+    pub auth: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushSubscription {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnsubscribePushRequest {
+    pub endpoint: String,
+}
+
+/// VAPID application server key browsers need to create a subscription.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VapidPublicKey {
+    pub public_key: String,
+}
+
+// Tag models
+/// A tag in use by a user and how many tasks and cases carry it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagUsage {
+    pub tag: String,
+    pub tasks: i64,
+    pub cases: i64,
+}
+
+// Saved filter models
+/// A named task query kept per user, e.g. "Today" or "Waiting on others".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedFilter {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub filter: TaskFilter,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedFilterRequest {
+    pub name: String,
+    pub filter: TaskFilter,
+}
+
+/// Conditions a task must meet. Each list matches any of its values and is
+/// ignored when empty, except `tags`, where a task needs every tag.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TaskFilter {
+    #[serde(default)]
+    pub status: Vec<TaskStatus>,
+    #[serde(default)]
+    pub task_type: Vec<TaskType>,
+    #[serde(default)]
+    pub priority: Vec<Priority>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub due: Option<DueWindow>,
+}
+
+/// Due date ranges, measured in UTC. `Today` and `ThisWeek` include
+/// overdue tasks so nothing late drops out of those lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DueWindow {
+    Overdue,
+    Today,
+    /// Due within the next seven days.
+    ThisWeek,
+    NoDueDate,
+}
+
+impl TaskFilter {
+    pub fn matches(&self, task: &Task, now: DateTime<Utc>) -> bool {
+        (self.status.is_empty() || self.status.contains(&task.status))
+            && (self.task_type.is_empty() || self.task_type.contains(&task.task_type))
+            && (self.priority.is_empty() || self.priority.contains(&task.priority))
+            && self.tags.iter().all(|tag| task.tags.contains(tag))
+            && self.due.is_none_or(|window| window.matches(task.due_date, now))
+    }
+}
+
+impl DueWindow {
+    pub fn matches(self, due_date: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+        let end_of_today = (now.date_naive() + chrono::Days::new(1))
+            .and_hms_opt(0, 0, 0)
+            .map(|midnight| midnight.and_utc())
+            .unwrap_or(now);
+        match (self, due_date) {
+            (DueWindow::NoDueDate, due) => due.is_none(),
+            (_, None) => false,
+            (DueWindow::Overdue, Some(due)) => due < now,
+            (DueWindow::Today, Some(due)) => due < end_of_today,
+            (DueWindow::ThisWeek, Some(due)) => due < now + chrono::Duration::days(7),
+        }
+    }
+}
+
+// Task taxonomy models
+/// A task type an organization defined on top of the built-in ones. Tasks
+/// of this type are `TaskType::Other(name)`, and their metadata has to fit
+/// `fields`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomTaskType {
+    pub id: Uuid,
+    pub organization: String,
+    pub name: String,
+    /// Shown to the LLM to tell when the type applies.
+    pub description: Option<String>,
+    pub fields: Vec<MetadataField>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomTaskTypeRequest {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<MetadataField>,
+}
+
+/// A metadata entry tasks of a custom type carry, e.g. a contract's
+/// counterparty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataField {
+    pub name: String,
+    pub kind: MetadataFieldKind,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MetadataFieldKind {
+    Text,
+    Number,
+    Boolean,
+    /// An RFC 3339 timestamp or `YYYY-MM-DD` date.
+    Date,
+}
+
+impl CustomTaskType {
+    /// Problems with `metadata` for a task of this type; empty when it
+    /// fits. Entries the type doesn't define are left alone.
+    pub fn check_metadata(&self, metadata: &serde_json::Value) -> Vec<String> {
+        let mut problems = Vec::new();
+        for field in &self.fields {
+            let value = match metadata.get(&field.name) {
+                None | Some(serde_json::Value::Null) => {
+                    if field.required {
+                        problems.push(format!("{} tasks need '{}'", self.name, field.name));
+                    }
+                    continue;
+                }
+                Some(value) => value,
+            };
+            let fits = match field.kind {
+                MetadataFieldKind::Text => value.is_string(),
+                MetadataFieldKind::Number => value.is_number(),
+                MetadataFieldKind::Boolean => value.is_boolean(),
+                MetadataFieldKind::Date => value.as_str().is_some_and(|date| {
+                    DateTime::parse_from_rfc3339(date).is_ok()
+                        || chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok()
+                }),
+            };
+            if !fits {
+                problems.push(format!("'{}' of {} tasks must be {:?}", field.name, self.name, field.kind));
+            }
+        }
+        problems
+    }
+}
+
+// Organization LLM models
+/// The LLM an organization's requests go to instead of the service's, and
+/// how many tokens it may use a month. Set by an administrator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizationLlmSettings {
+    pub organization: String,
+    /// Root of an OpenAI-compatible API; the service's when unset.
+    pub base_url: Option<String>,
+    /// The service's model when unset.
+    pub model: Option<String>,
+    /// The service's key when neither it nor `base_url` is set. Only
+    /// returned to other services.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub has_api_key: bool,
+    /// Tokens the organization may use per calendar month (UTC); no limit
+    /// when unset.
+    pub monthly_token_budget: Option<i64>,
+    /// Member notified when the month's budget is used up.
+    pub alert_user_id: Option<Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrganizationLlmRequest {
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    /// Keeps the current key when unset; an empty key removes it.
+    pub api_key: Option<String>,
+    pub monthly_token_budget: Option<i64>,
+    pub alert_user_id: Option<Uuid>,
+}
+
+/// Tokens an organization used in a month.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LlmUsage {
+    pub organization: String,
+    /// First day of the month.
+    pub month: NaiveDate,
+    pub tokens_used: i64,
+    pub monthly_token_budget: Option<i64>,
+}
+
+impl LlmUsage {
+    /// Whether the month's budget is used up.
+    pub fn exhausted(&self) -> bool {
+        self.monthly_token_budget.is_some_and(|budget| self.tokens_used >= budget)
+    }
+}
+
+/// A user an administrator added to an organization. Only members use the
+/// organization's LLM settings; the free-text organization users give on
+/// registration doesn't count.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OrganizationMember {
+    pub organization: String,
+    pub user_id: Uuid,
+    pub added_at: DateTime<Utc>,
+}
+
+/// An organization's LLM settings with its usage this month.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizationLlm {
+    pub settings: OrganizationLlmSettings,
+    pub usage: LlmUsage,
+}
+
+/// Asks for the [`OrganizationLlm`] of the user with a session or, for
+/// requests made without one, of the user by id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LlmAccountRequest {
+    pub session_token: Option<String>,
+    pub user_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordLlmUsageRequest {
+    pub organization: String,
+    pub tokens: i64,
+}
+
+// Extraction example models
+/// A message with the tasks that should be extracted from it. A user's
+/// examples are shown to the LLM as few-shot examples, teaching it the
+/// user's vocabulary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionExample {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub message: String,
+    /// Tasks in the shape the LLM extracts them, e.g.
+    /// `{"title": ..., "task_type": "Work", "priority": "High"}`.
+    pub expected_tasks: Vec<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtractionExampleRequest {
+    pub message: String,
+    /// Empty for a message no task should be extracted from.
+    #[serde(default)]
+    pub expected_tasks: Vec<serde_json::Value>,
+}
+
+// Webhook integration models
+/// Where a webhook integration finds the parts of a message in the JSON it
+/// receives. Paths are JSONPath (`$.alert.labels.service`,
+/// `$.answers[0].text`) or the same without the leading `$.`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookMapping {
+    pub sender: String,
+    pub body: String,
+    #[serde(default)]
+    pub subject: Option<String>,
+}
+
+/// A user's inbound webhook, e.g. for a form tool or a monitoring system.
+/// Events posted to it become messages of the user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookIntegration {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub mapping: WebhookMapping,
+    pub created_at: DateTime<Utc>,
+    /// The secret in the integration's URL, only returned when created.
+    /// It is stored hashed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookIntegrationRequest {
+    pub name: String,
+    pub mapping: WebhookMapping,
+}
+
+/// Looks up the integration a webhook token belongs to.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolveWebhookRequest {
+    pub token: String,
+}
+
+// Background job models
+/// A unit of background work, kept in the database so it survives
+/// restarts. Recurring jobs (`interval_secs` set) are one row per kind that
+/// goes back to `Pending` after every run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: Uuid,
+    /// What to do, e.g. `email.poll`; workers claim the kinds they handle.
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub status: JobStatus,
+    /// Runs started, counting from the last success for recurring jobs.
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// Earliest time the job may run.
+    pub run_at: DateTime<Utc>,
+    pub interval_secs: Option<i64>,
+    pub last_error: Option<String>,
+    /// Worker holding the job while it runs, until `locked_until`.
+    pub locked_by: Option<String>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Succeeded,
+    /// Failed `max_attempts` times; only runs again when retried by hand.
+    Dead,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnqueueJobRequest {
+    pub kind: String,
+    #[serde(default)]
+    pub payload: serde_json::Value,
+    /// Defaults to now.
+    pub run_at: Option<DateTime<Utc>>,
+    pub max_attempts: Option<i32>,
+}
+
+/// Creates the recurring job of a kind, or changes its interval.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScheduleJobRequest {
+    pub kind: String,
+    pub interval_secs: i64,
+    #[serde(default)]
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClaimJobsRequest {
+    pub worker: String,
+    pub kinds: Vec<String>,
+    pub limit: i64,
+    /// How long the worker holds the jobs before others may take them over.
+    pub lease_secs: i64,
+}
+
+/// The outcome of a claimed job, reported by the worker that ran it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobResult {
+    pub worker: String,
+    /// `None` when the job succeeded.
+    pub error: Option<String>,
+}
+
+/// A change to a task or case, recorded in the same transaction as the
+/// change itself and published afterwards. Delivered at least once;
+/// consumers drop repeats by `id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEvent {
+    pub id: Uuid,
+    /// `task.created`, `task.updated`, `task.deleted`, `case.created` or
+    /// `case.updated`.
+    pub event_type: String,
+    /// The task or case changed.
+    pub aggregate_id: Uuid,
+    /// The task or case after the change; `null` for deletions.
+    pub payload: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How far publication of outbox events is behind.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutboxLag {
+    pub pending: i64,
+    /// Creation time of the oldest event not yet published.
+    pub oldest_pending_at: Option<DateTime<Utc>>,
+    /// Age of that event, in seconds.
+    pub lag_secs: i64,
+    pub last_published_at: Option<DateTime<Utc>>,
+    /// Why the next event to publish failed, if it did.
+    pub last_error: Option<String>,
+    pub attempts: i32,
 }
 
 // Error types
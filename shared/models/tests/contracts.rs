@@ -0,0 +1,399 @@
+//! Contract tests for the JSON the services exchange.
+//!
+//! The services only talk through the types of this crate, so their wire
+//! format is the contract between producer and consumer: task management
+//! posts a [`Task`] that persistence reads, persistence answers with a
+//! [`Task`] that task management reads, and so on. Each contract is
+//! snapshotted under `tests/contracts/`; a change to a model that alters
+//! its JSON fails here until the snapshot is updated on purpose:
+//!
+//! ```sh
+//! UPDATE_CONTRACTS=1 cargo test -p models --test contracts
+//! ```
+//!
+//! Persistence also stores enums as their JSON, so the enum snapshot doubles
+//! as the format of the rows already written.
+//!
+//! The `consumers_accept_*` tests pin payloads producers actually send,
+//! written by hand: they have to keep deserializing whatever the snapshots
+//! become.
+
+use chrono::{DateTime, TimeZone, Utc};
+use models::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+fn id(n: u128) -> Uuid {
+    Uuid::from_u128(n)
+}
+
+fn at(hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 3, 1, hour, 0, 0).unwrap()
+}
+
+/// Compares the JSON of `value` with the snapshot `name`, and checks the
+/// snapshot reads back into `T` unchanged.
+fn contract<T: Serialize + DeserializeOwned>(name: &str, value: &T) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/contracts").join(format!("{}.json", name));
+    let actual = serde_json::to_value(value).unwrap();
+
+    if std::env::var_os("UPDATE_CONTRACTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, serde_json::to_string_pretty(&actual).unwrap() + "\n").unwrap();
+        return;
+    }
+
+    let snapshot = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("read {} (run with UPDATE_CONTRACTS=1 to create it): {}", path.display(), e));
+    let snapshot: Value = serde_json::from_str(&snapshot).unwrap();
+    assert_eq!(
+        actual, snapshot,
+        "the JSON of `{}` changed; if every consumer handles it, run with UPDATE_CONTRACTS=1",
+        name
+    );
+
+    let read_back: T = serde_json::from_value(snapshot.clone())
+        .unwrap_or_else(|e| panic!("consumers can no longer read `{}`: {}", name, e));
+    assert_eq!(serde_json::to_value(read_back).unwrap(), snapshot, "`{}` does not round-trip", name);
+}
+
+fn task() -> Task {
+    Task {
+        id: id(1),
+        user_id: id(2),
+        case_id: id(3),
+        title: "Send the budget review".to_string(),
+        description: Some("Before the board meeting".to_string()),
+        task_type: TaskType::Other("Finance".to_string()),
+        status: TaskStatus::InProgress,
+        priority: Priority::High,
+        due_date: Some(at(17)),
+        created_at: at(9),
+        updated_at: at(10),
+        completed_at: None,
+        metadata: json!({ "ai_confidence": 0.9 }),
+        tags: vec!["budget".to_string(), "q1".to_string()],
+        estimate_minutes: Some(45),
+    }
+}
+
+fn case() -> Case {
+    Case {
+        id: id(3),
+        user_id: id(2),
+        title: "Project kickoff".to_string(),
+        description: None,
+        status: CaseStatus::Waiting,
+        priority: Priority::Medium,
+        created_at: at(9),
+        updated_at: at(11),
+        assigned_to: Some("lead@company.com".to_string()),
+        metadata: json!({ "email": { "message_id": "<kickoff@company.com>" } }),
+        tags: vec!["kickoff".to_string()],
+        unread_count: Some(2),
+        first_response_at: Some(at(10)),
+        resolved_at: None,
+        sla: None,
+    }
+}
+
+fn message() -> MessageRequest {
+    MessageRequest {
+        case_id: None,
+        message: "Please send the budget review by Friday.".to_string(),
+        sender_id: "lead@company.com".to_string(),
+        channel: MessageChannel::Email,
+        email_thread: Some(EmailThread {
+            message_id: Some("<kickoff@company.com>".to_string()),
+            conversation_id: Some("AAQkAD".to_string()),
+            in_reply_to: None,
+        }),
+        owner_id: None,
+        priority: None,
+        tags: Vec::new(),
+    }
+}
+
+#[test]
+fn task_contract() {
+    contract("task", &task());
+}
+
+#[test]
+fn update_task_request_contract() {
+    contract(
+        "update_task_request",
+        &UpdateTaskRequest {
+            title: None,
+            description: None,
+            status: Some(TaskStatus::Completed),
+            priority: Some(Priority::Low),
+            due_date: None,
+            tags: Some(vec!["done".to_string()]),
+            metadata: None,
+            estimate_minutes: Some(30),
+        },
+    );
+}
+
+#[test]
+fn case_contract() {
+    contract("case", &case());
+}
+
+#[test]
+fn update_case_request_contract() {
+    contract(
+        "update_case_request",
+        &UpdateCaseRequest {
+            title: Some("Project kickoff (moved)".to_string()),
+            description: None,
+            status: Some(CaseStatus::Resolved),
+            priority: None,
+            assigned_to: None,
+            tags: None,
+        },
+    );
+}
+
+#[test]
+fn conversation_entry_contract() {
+    contract(
+        "conversation_entry",
+        &ConversationEntry {
+            id: id(4),
+            user_id: id(2),
+            case_id: id(3),
+            message: "I've added these to your list.".to_string(),
+            sender: MessageSender::Agent,
+            timestamp: at(9),
+            metadata: json!({ "tasks_created": 2 }),
+        },
+    );
+}
+
+#[test]
+fn case_event_request_contract() {
+    contract(
+        "case_event_request",
+        &CaseEventRequest {
+            kind: CaseEventKind::SlaWarning,
+            summary: "Response due in 1h".to_string(),
+            subject_id: Some(id(3)),
+            once: true,
+        },
+    );
+}
+
+#[test]
+fn message_contracts() {
+    contract("message_request", &message());
+    contract(
+        "message_response",
+        &MessageResponse {
+            case_id: id(3),
+            response: "I've added these to your list.".to_string(),
+            actions_taken: vec!["Created case".to_string()],
+            tasks_created: vec![id(1)],
+            tasks_updated: Vec::new(),
+            questions: vec![FollowUpQuestion {
+                id: id(6),
+                case_id: id(3),
+                task_id: id(1),
+                user_id: id(2),
+                question: "When should 'Budget review' take place?".to_string(),
+                missing: vec![MissingDetail::MeetingTime],
+                channel: MessageChannel::Email,
+                recipient: "lead@company.com".to_string(),
+                status: QuestionStatus::Pending,
+                created_at: at(9),
+                answered_at: None,
+            }],
+        },
+    );
+}
+
+#[test]
+fn failed_message_contracts() {
+    contract(
+        "create_failed_message_request",
+        &CreateFailedMessageRequest { message: message(), error: "connection refused".to_string() },
+    );
+    contract(
+        "failed_message",
+        &FailedMessage {
+            id: id(5),
+            user_id: None,
+            message: message(),
+            error: "connection refused".to_string(),
+            status: FailedMessageStatus::Pending,
+            attempts: 2,
+            next_attempt_at: Some(at(12)),
+            created_at: at(9),
+            updated_at: at(10),
+        },
+    );
+}
+
+#[test]
+fn outbox_event_contract() {
+    contract(
+        "outbox_event",
+        &OutboxEvent {
+            id: id(6),
+            event_type: "task.updated".to_string(),
+            aggregate_id: id(1),
+            payload: serde_json::to_value(task()).unwrap(),
+            created_at: at(10),
+        },
+    );
+}
+
+#[test]
+fn enum_contract() {
+    contract(
+        "enums",
+        &json!({
+            "TaskType": [
+                TaskType::Meeting,
+                TaskType::Shopping,
+                TaskType::Work,
+                TaskType::Personal,
+                TaskType::Research,
+                TaskType::Communication,
+                TaskType::Other("Finance".to_string()),
+            ],
+            "TaskStatus": [
+                TaskStatus::Pending,
+                TaskStatus::InProgress,
+                TaskStatus::Completed,
+                TaskStatus::Cancelled,
+                TaskStatus::OnHold,
+                TaskStatus::NeedsReview,
+            ],
+            "Priority": [Priority::Low, Priority::Medium, Priority::High, Priority::Critical],
+            "CaseStatus": [
+                CaseStatus::Open,
+                CaseStatus::InProgress,
+                CaseStatus::Waiting,
+                CaseStatus::Resolved,
+                CaseStatus::Closed,
+                CaseStatus::Archived,
+            ],
+            "MessageChannel": [
+                MessageChannel::Bot,
+                MessageChannel::Email,
+                MessageChannel::WebChat,
+                MessageChannel::API,
+                MessageChannel::Voice,
+                MessageChannel::WhatsApp,
+                MessageChannel::Webhook,
+            ],
+            "MessageSender": [MessageSender::User, MessageSender::Agent, MessageSender::System],
+            "CaseEventKind": [
+                CaseEventKind::CaseStatusChanged,
+                CaseEventKind::TaskStatusChanged,
+                CaseEventKind::TaskDeleted,
+                CaseEventKind::WorkflowStepChanged,
+                CaseEventKind::SlaWarning,
+                CaseEventKind::SlaBreached,
+                CaseEventKind::CaseTransferred,
+            ],
+            "FailedMessageStatus": [
+                FailedMessageStatus::Pending,
+                FailedMessageStatus::Reprocessed,
+                FailedMessageStatus::Abandoned,
+            ],
+            "ApiScope": ApiScope::ALL,
+        }),
+    );
+}
+
+/// Tasks and cases written before tags, estimates and SLA tracking, as
+/// older rows and older services still produce them.
+#[test]
+fn consumers_accept_tasks_and_cases_without_later_fields() {
+    let task: Task = serde_json::from_value(json!({
+        "id": id(1),
+        "user_id": id(2),
+        "case_id": id(3),
+        "title": "Send the budget review",
+        "description": null,
+        "task_type": "Work",
+        "status": "Pending",
+        "priority": "Medium",
+        "due_date": null,
+        "created_at": at(9),
+        "updated_at": at(9),
+        "completed_at": null,
+        "metadata": {},
+    }))
+    .expect("task without tags or estimate");
+    assert!(task.tags.is_empty());
+    assert_eq!(task.estimate_minutes, None);
+
+    let case: Case = serde_json::from_value(json!({
+        "id": id(3),
+        "user_id": id(2),
+        "title": "Project kickoff",
+        "description": null,
+        "status": "Open",
+        "priority": "Medium",
+        "created_at": at(9),
+        "updated_at": at(9),
+        "assigned_to": null,
+        "metadata": {},
+    }))
+    .expect("case without tags or SLA fields");
+    assert!(case.tags.is_empty() && case.first_response_at.is_none() && case.sla.is_none());
+}
+
+/// Preferences saved before notification settings existed.
+#[test]
+fn consumers_accept_preferences_without_notification_settings() {
+    let preferences: UserPreferences = serde_json::from_value(json!({
+        "theme": "Dark",
+        "default_view": "Board",
+        "tasks_per_page": 24,
+    }))
+    .expect("preferences without notification settings");
+    assert!(preferences.notifications.push && preferences.notifications.muted.is_empty());
+    assert_eq!(preferences.notifications.delivery, NotificationDelivery::Immediate);
+    assert!(preferences.notifications.quiet_hours.is_none());
+}
+
+/// Partial updates as task and case management send them: only the
+/// fields that change.
+#[test]
+fn consumers_accept_partial_updates() {
+    let update: UpdateTaskRequest = serde_json::from_value(json!({ "status": "Completed" })).expect("status-only task update");
+    assert_eq!(update.status, Some(TaskStatus::Completed));
+    assert!(update.title.is_none() && update.tags.is_none() && update.metadata.is_none());
+
+    let update: UpdateCaseRequest = serde_json::from_value(json!({ "status": "InProgress" })).expect("status-only case update");
+    assert_eq!(update.status, Some(CaseStatus::InProgress));
+}
+
+/// Messages from the channel service before email threading, and case
+/// events before `once`.
+#[test]
+fn consumers_accept_messages_and_events_without_later_fields() {
+    let message: MessageRequest = serde_json::from_value(json!({
+        "case_id": null,
+        "message": "Buy milk",
+        "sender_id": "telegram:42",
+        "channel": "Bot",
+    }))
+    .expect("message without email thread");
+    assert!(message.email_thread.is_none());
+
+    let event: CaseEventRequest = serde_json::from_value(json!({
+        "kind": "TaskDeleted",
+        "summary": "Task deleted",
+        "subject_id": null,
+    }))
+    .expect("case event without once");
+    assert!(!event.once);
+}
@@ -0,0 +1,65 @@
+//! Request latency of the persistence service against seeded data: the
+//! task list query and session validation, which every authenticated
+//! request goes through.
+//!
+//! Benchmarks run release builds of the services, against the database in
+//! `TEST_DATABASE_URL` (skipped without it):
+//!
+//! ```sh
+//! cargo build --release -p persistence-service
+//! TEST_DATABASE_URL=postgres://postgres@localhost/tasks_test cargo bench -p e2e --bench load
+//! ```
+//!
+//! For sustained load, seed through the same endpoint and drive the
+//! service with k6 or vegeta instead; see the persistence `seed` module.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use e2e::Stack;
+use serde_json::json;
+
+/// Other users' rows make the tables realistic for the queries.
+const SEED_USERS: u32 = 20;
+const CASES_PER_USER: u32 = 20;
+const TASKS_PER_CASE: u32 = 25;
+
+fn persistence(c: &mut Criterion) {
+    if std::env::var_os("TEST_DATABASE_URL").is_none() {
+        eprintln!("TEST_DATABASE_URL is not set; skipping");
+        return;
+    }
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let stack = runtime.block_on(Stack::start_with(&["persistence-service"]));
+    let seeded = runtime.block_on(stack.seed(SEED_USERS, CASES_PER_USER, TASKS_PER_CASE));
+    let session = seeded["users"][0]["session_token"].as_str().expect("seeded session").to_string();
+    let persistence = stack.url("persistence-service");
+
+    let get = |path: &str| {
+        let request = stack.http.get(format!("{}{}", persistence, path)).bearer_auth(&session);
+        async move {
+            let response = request.send().await.and_then(|response| response.error_for_status()).expect("request");
+            response.bytes().await.expect("response body")
+        }
+    };
+
+    let mut group = c.benchmark_group("persistence");
+    group.bench_function("task_list", |b| b.iter(|| runtime.block_on(get("/api/v1/tasks"))));
+    group.bench_function("task_list_by_status", |b| b.iter(|| runtime.block_on(get("/api/v1/tasks?status=Pending"))));
+    group.bench_function("session_validation", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                stack
+                    .http
+                    .post(format!("{}/api/v1/auth/validate", persistence))
+                    .json(&json!({ "session_token": session }))
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .expect("validate session")
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, persistence);
+criterion_main!(benches);
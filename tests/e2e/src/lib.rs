@@ -0,0 +1,402 @@
+//! Harness for end-to-end tests across the services.
+//!
+//! [`Stack::start`] runs the channel, AI agent, case management, task
+//! management, persistence and email collector services as child
+//! processes on free local ports, against the database named by
+//! `TEST_DATABASE_URL`. OpenAI and Microsoft Graph are replaced by
+//! wiremock servers the test programs ([`Stack::openai`],
+//! [`Stack::graph`]). Services can be stopped and started again to inject
+//! failures.
+//!
+//! The service binaries are taken from the target directory the tests run
+//! from, so build the workspace first:
+//!
+//! ```sh
+//! cargo build --workspace
+//! TEST_DATABASE_URL=postgres://postgres@localhost/tasks_test cargo test -p e2e -- --include-ignored
+//! ```
+//!
+//! The tests are `#[ignore]`d, so a plain `cargo test` reports them as
+//! skipped rather than passed; run them with `-- --include-ignored`. Asked
+//! for without `TEST_DATABASE_URL`, they fail.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use tokio::sync::MutexGuard;
+use uuid::Uuid;
+use wiremock::matchers::{body_string_contains, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Stacks share the database, and with it the recurring jobs, so only one
+/// runs at a time.
+static RUNNING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The services, by binary name, with the short name other services use
+/// for them (see `common::config`).
+const SERVICES: &[(&str, &str)] = &[
+    ("persistence-service", "persistence"),
+    ("task-management-service", "task-management"),
+    ("case-management-service", "case-management"),
+    ("ai-agent-service", "ai-agent"),
+    ("channel-service", "channel"),
+    ("email-collector-service", "email-collector"),
+];
+
+/// `ADMIN_API_TOKEN` of the services.
+pub const ADMIN_TOKEN: &str = "e2e-admin";
+
+/// `INTERNAL_SERVICE_TOKEN` of the services.
+pub const INTERNAL_TOKEN: &str = "e2e-internal";
+
+pub struct Stack {
+    pub openai: MockServer,
+    pub graph: MockServer,
+    pub http: reqwest::Client,
+    /// Token of the mailbox [`sign_in_mailbox`](Self::sign_in_mailbox)
+    /// connects; the Graph mocks only answer it, not mailboxes left behind
+    /// in the database by earlier runs.
+    graph_token: String,
+    ports: HashMap<&'static str, u16>,
+    env: Vec<(String, String)>,
+    children: HashMap<&'static str, Child>,
+    state_dir: PathBuf,
+    _running: MutexGuard<'static, ()>,
+}
+
+impl Stack {
+    /// Starts every service and waits until they are healthy. Panics
+    /// without `TEST_DATABASE_URL`.
+    pub async fn start() -> Self {
+        let all: Vec<&'static str> = SERVICES.iter().map(|(binary, _)| *binary).collect();
+        Self::start_with(&all).await
+    }
+
+    /// Like [`start`](Self::start), but only runs `binaries`; the others
+    /// still get ports, so configured URLs stay valid.
+    pub async fn start_with(binaries: &[&'static str]) -> Self {
+        let database_url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL names the test database");
+        let running = RUNNING.lock().await;
+
+        let openai = MockServer::start().await;
+        let graph = MockServer::start().await;
+        let ports: HashMap<_, _> = SERVICES.iter().map(|(binary, _)| (*binary, free_port())).collect();
+        let state_dir = std::env::temp_dir().join(format!("e2e-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&state_dir).expect("create state directory");
+
+        let mut env = vec![
+            ("DATABASE_URL".to_string(), database_url),
+            ("RUST_LOG".to_string(), std::env::var("E2E_LOG").unwrap_or_else(|_| "warn".to_string())),
+            ("SERVICE_HEALTH_CHECKS".to_string(), "false".to_string()),
+            ("OPENAI_API_KEY".to_string(), "sk-e2e".to_string()),
+            ("OPENAI_BASE_URL".to_string(), openai.uri()),
+            ("GRAPH_BASE_URL".to_string(), format!("{}/v1.0", graph.uri())),
+            ("EMAIL_POLL_INTERVAL_SECS".to_string(), "1".to_string()),
+            ("GRAPH_SYNC_STATE_FILE".to_string(), state_dir.join("sync.json").display().to_string()),
+            ("JOB_POLL_SECS".to_string(), "1".to_string()),
+            ("JOB_LEASE_SECS".to_string(), "10".to_string()),
+            ("SPAM_FILTER_ENABLED".to_string(), "false".to_string()),
+            ("ADMIN_API_TOKEN".to_string(), ADMIN_TOKEN.to_string()),
+            ("INTERNAL_SERVICE_TOKEN".to_string(), INTERNAL_TOKEN.to_string()),
+            ("SEED_ENABLED".to_string(), "true".to_string()),
+        ];
+        for (binary, name) in SERVICES {
+            let variable = format!("{}_SERVICE_URL", name.to_uppercase().replace('-', "_"));
+            let variable = if *name == "email-collector" { "EMAIL_SERVICE_URL".to_string() } else { variable };
+            env.push((variable, format!("http://127.0.0.1:{}", ports[binary])));
+        }
+
+        let mut stack = Self {
+            openai,
+            graph,
+            http: reqwest::Client::new(),
+            graph_token: format!("graph-{}", Uuid::new_v4()),
+            ports,
+            env,
+            children: HashMap::new(),
+            state_dir,
+            _running: running,
+        };
+        // Persistence first: it migrates the database the others use.
+        for (binary, _) in SERVICES.iter().filter(|(binary, _)| binaries.contains(binary)) {
+            stack.start_service(binary).await;
+        }
+        stack
+    }
+
+    /// Base URL of `binary`, e.g. `persistence-service`.
+    pub fn url(&self, binary: &str) -> String {
+        format!("http://127.0.0.1:{}", self.ports[binary])
+    }
+
+    /// Starts `binary` on its port and waits until it is healthy.
+    pub async fn start_service(&mut self, binary: &'static str) {
+        let child = Command::new(binary_path(binary))
+            .env_clear()
+            .env("PATH", std::env::var("PATH").unwrap_or_default())
+            .envs(self.env.iter().map(|(name, value)| (name, value)))
+            .env("PORT", self.ports[binary].to_string())
+            .stdin(Stdio::null())
+            .spawn()
+            .unwrap_or_else(|e| panic!("start {} (built with `cargo build --workspace`?): {}", binary, e));
+        self.children.insert(binary, child);
+
+        let health = format!("{}/health", self.url(binary));
+        let healthy = eventually(STARTUP_TIMEOUT, || async {
+            let response = self.http.get(&health).send().await.ok()?;
+            response.status().is_success().then_some(())
+        })
+        .await;
+        assert!(healthy.is_some(), "{} did not become healthy", binary);
+    }
+
+    /// Kills `binary`, as if it crashed.
+    pub fn stop_service(&mut self, binary: &str) {
+        if let Some(mut child) = self.children.remove(binary) {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    /// Has the agent call `create_task` with each of `tasks` in its first
+    /// chat completion, and answer `reply` once the tools' results are in.
+    /// Completions are streamed, as the agent asks for.
+    pub async fn mock_openai_agent(&self, tasks: &[Value], reply: &str) {
+        let calls: Vec<Value> = tasks
+            .iter()
+            .enumerate()
+            .map(|(index, task)| json!({
+                "index": index,
+                "id": format!("call-{}", index),
+                "type": "function",
+                "function": { "name": "create_task", "arguments": task.to_string() }
+            }))
+            .collect();
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains(r#""role":"tool""#))
+            .respond_with(event_stream(json!({ "role": "assistant", "content": reply })))
+            .with_priority(1)
+            .mount(&self.openai)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(event_stream(json!({ "role": "assistant", "tool_calls": calls })))
+            .mount(&self.openai)
+            .await;
+    }
+
+    /// Serves `messages` as the inbox's first delta page, and accepts
+    /// marking them read in `$batch` requests.
+    pub async fn mock_inbox(&self, messages: Vec<Value>) {
+        let authorization = format!("Bearer {}", self.graph_token);
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/mailFolders/inbox/messages/delta"))
+            .and(header("authorization", authorization.as_str()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": messages,
+                "@odata.deltaLink": format!("{}/v1.0/me/mailFolders/inbox/messages/delta?token=next", self.graph.uri()),
+            })))
+            .mount(&self.graph)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1.0/$batch"))
+            .and(header("authorization", authorization.as_str()))
+            .respond_with(|request: &wiremock::Request| {
+                let batch: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+                let responses: Vec<Value> = batch["requests"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|item| json!({ "id": item["id"], "status": 200, "body": {} }))
+                    .collect();
+                ResponseTemplate::new(200).set_body_json(json!({ "responses": responses }))
+            })
+            .mount(&self.graph)
+            .await;
+    }
+
+    /// Connects a mailbox to the user of `session`, as the dashboard does
+    /// after consent; the email collector polls it from its next run.
+    pub async fn sign_in_mailbox(&self, session: &str) {
+        self.http
+            .post(format!("{}/api/v1/email-accounts", self.url("persistence-service")))
+            .bearer_auth(session)
+            .json(&json!({
+                "email_address": format!("{}@company.com", Uuid::new_v4()),
+                "provider": "Office365",
+                "oauth_token": self.graph_token,
+                "oauth_refresh_token": null,
+                "imap_settings": null,
+            }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .expect("connect a mailbox");
+    }
+
+    /// Registers a new user and signs in, returning the session token.
+    pub async fn sign_up(&self) -> String {
+        let persistence = self.url("persistence-service");
+        let email = format!("{}@e2e.test", Uuid::new_v4());
+        let password = "correct horse battery staple";
+        self.http
+            .post(format!("{}/api/v1/auth/register", persistence))
+            .json(&json!({ "email": email, "password": password, "full_name": "E2E Test", "organization": null }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .expect("register a user");
+        let login: Value = self
+            .http
+            .post(format!("{}/api/v1/auth/login", persistence))
+            .json(&json!({ "email": email, "password": password }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .expect("sign in")
+            .json()
+            .await
+            .expect("login response");
+        login["session_token"].as_str().expect("session token").to_string()
+    }
+
+    /// Creates synthetic users, cases and tasks through the persistence
+    /// seed endpoint, returning its response.
+    pub async fn seed(&self, users: u32, cases_per_user: u32, tasks_per_case: u32) -> Value {
+        self.http
+            .post(format!("{}/api/v1/admin/seed", self.url("persistence-service")))
+            .header("X-Admin-Token", ADMIN_TOKEN)
+            .json(&json!({ "users": users, "cases_per_user": cases_per_user, "tasks_per_case": tasks_per_case }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .expect("seed load-test data")
+            .json()
+            .await
+            .expect("seed response")
+    }
+
+    /// POSTs `body` to `path` of `binary` with the session, returning the
+    /// JSON response.
+    pub async fn post(&self, binary: &str, path: &str, session: &str, body: &Value) -> reqwest::Result<Value> {
+        self.http
+            .post(format!("{}{}", self.url(binary), path))
+            .bearer_auth(session)
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    /// GETs `path` from the persistence service with the session.
+    pub async fn persistence_get(&self, path: &str, session: &str) -> reqwest::Result<Value> {
+        self.http
+            .get(format!("{}{}", self.url("persistence-service"), path))
+            .bearer_auth(session)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    /// The session's cases recorded for the email with `message_id`,
+    /// waiting up to `timeout` for there to be one.
+    pub async fn cases_for_email(&self, message_id: &str, session: &str, timeout: Duration) -> Vec<Value> {
+        let path = format!("/api/v1/cases/by-email-thread?message_id={}", urlencode(message_id));
+        eventually(timeout, || async {
+            let cases = self.persistence_get(&path, session).await.ok()?;
+            cases.as_array().filter(|cases| !cases.is_empty()).cloned()
+        })
+        .await
+        .unwrap_or_default()
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        for (_, mut child) in self.children.drain() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        let _ = std::fs::remove_dir_all(&self.state_dir);
+    }
+}
+
+/// A streamed chat completion of a single `delta`.
+fn event_stream(delta: Value) -> ResponseTemplate {
+    let chunk = json!({ "choices": [{ "delta": delta }] });
+    ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_string(format!("data: {}\n\ndata: [DONE]\n\n", chunk))
+}
+
+/// A Graph message as the delta query returns it.
+pub fn graph_message(subject: &str, body: &str, from: &str) -> Value {
+    json!({
+        "id": Uuid::new_v4().to_string(),
+        "subject": subject,
+        "bodyPreview": body,
+        "body": { "contentType": "text", "content": body },
+        "from": { "emailAddress": { "address": from, "name": from } },
+        "isRead": false,
+        "internetMessageId": format!("<{}@e2e.test>", Uuid::new_v4()),
+        "conversationId": Uuid::new_v4().to_string(),
+        "internetMessageHeaders": [],
+    })
+}
+
+/// Polls `check` until it returns something or `timeout` passes.
+pub async fn eventually<T, F, Fut>(timeout: Duration, mut check: F) -> Option<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<T>>,
+{
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if let Some(value) = check().await {
+            return Some(value);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return None;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .expect("find a free port")
+}
+
+/// The service binaries sit next to the `deps` directory the test binary
+/// runs from.
+fn binary_path(binary: &str) -> PathBuf {
+    let test_binary = std::env::current_exe().expect("locate the test binary");
+    let target_dir = test_binary
+        .parent()
+        .and_then(|deps| deps.parent())
+        .expect("test binary is in a target directory");
+    target_dir.join(format!("{}{}", binary, std::env::consts::EXE_SUFFIX))
+}
+
+fn urlencode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,190 @@
+//! The email → case → tasks pipeline across all services; see the `e2e`
+//! crate docs for how to run it.
+
+use e2e::{eventually, graph_message, Stack};
+use serde_json::{json, Value};
+use std::time::Duration;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const PIPELINE_TIMEOUT: Duration = Duration::from_secs(30);
+
+const REPLY: &str = "I've added these to your list.";
+
+/// `create_task` arguments of high priority work tasks.
+fn extraction(titles: &[&str]) -> Vec<Value> {
+    titles
+        .iter()
+        .map(|title| json!({
+            "title": title,
+            "task_type": "Work",
+            "priority": "High",
+            "confidence": 0.95,
+        }))
+        .collect()
+}
+
+async fn tasks_of(stack: &Stack, case: &Value, session: &str) -> Vec<Value> {
+    let path = format!("/api/v1/cases/{}/tasks", case["id"].as_str().expect("case id"));
+    let tasks = stack.persistence_get(&path, session).await.expect("list tasks of the case");
+    tasks.as_array().cloned().unwrap_or_default()
+}
+
+/// An email as the email collector hands it to the channel service.
+fn email(message_id: &str, subject: &str, body: &str) -> Value {
+    json!({
+        "case_id": null,
+        "message": format!("{}\n\n{}", subject, body),
+        "sender_id": "lead@company.com",
+        "channel": "Email",
+        "email_thread": { "message_id": message_id, "conversation_id": null, "in_reply_to": null },
+    })
+}
+
+fn titles(tasks: &[Value]) -> Vec<&str> {
+    let mut titles: Vec<&str> = tasks.iter().filter_map(|task| task["title"].as_str()).collect();
+    titles.sort();
+    titles
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL and the built services"]
+async fn email_becomes_a_case_with_its_tasks() {
+    let stack = Stack::start().await;
+    stack
+        .mock_openai_agent(&extraction(&["Send the budget review", "Book the kickoff meeting"]), REPLY)
+        .await;
+    let session = stack.sign_up().await;
+
+    let message_id = format!("<{}@e2e.test>", Uuid::new_v4());
+    let body = "Please send the budget review by Friday and book the kickoff meeting.";
+    let response = stack
+        .post("channel-service", "/api/v1/email", &session, &email(&message_id, "Project kickoff", body))
+        .await
+        .expect("email processed");
+
+    let cases = stack.cases_for_email(&message_id, &session, PIPELINE_TIMEOUT).await;
+    assert_eq!(cases.len(), 1, "one case for the email");
+    assert_eq!(cases[0]["id"], response["case_id"]);
+    let tasks = tasks_of(&stack, &cases[0], &session).await;
+    assert_eq!(titles(&tasks), ["Book the kickoff meeting", "Send the budget review"]);
+    assert!(tasks.iter().all(|task| task["priority"] == "High"));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL and the built services"]
+async fn polled_email_becomes_a_case_with_its_tasks() {
+    let stack = Stack::start().await;
+    stack
+        .mock_openai_agent(&extraction(&["Send the budget review", "Book the kickoff meeting"]), REPLY)
+        .await;
+    let session = stack.sign_up().await;
+    let email = graph_message(
+        "Project kickoff",
+        "Please send the budget review by Friday and book the kickoff meeting.",
+        "lead@company.com",
+    );
+    stack.mock_inbox(vec![email.clone()]).await;
+
+    stack.sign_in_mailbox(&session).await;
+
+    let message_id = email["internetMessageId"].as_str().unwrap();
+    let cases = stack.cases_for_email(message_id, &session, PIPELINE_TIMEOUT).await;
+    assert_eq!(cases.len(), 1, "one case for the email");
+    // The case is found by its email thread before the tasks are added.
+    let tasks = eventually(PIPELINE_TIMEOUT, || async {
+        Some(tasks_of(&stack, &cases[0], &session).await).filter(|tasks| tasks.len() == 2)
+    })
+    .await
+    .unwrap_or_default();
+    assert_eq!(titles(&tasks), ["Book the kickoff meeting", "Send the budget review"]);
+
+    // Processed mail is marked read in the mailbox, batched.
+    let email_path = format!("/me/messages/{}", email["id"].as_str().unwrap());
+    let marked_read = eventually(PIPELINE_TIMEOUT, || async {
+        let requests = stack.graph.received_requests().await?;
+        requests
+            .iter()
+            .filter(|request| request.method.as_str() == "POST" && request.url.path() == "/v1.0/$batch")
+            .filter_map(|request| serde_json::from_slice::<Value>(&request.body).ok())
+            .any(|batch| {
+                batch["requests"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .any(|item| item["method"] == "PATCH" && item["url"] == email_path.as_str())
+            })
+            .then_some(())
+    })
+    .await;
+    assert!(marked_read.is_some(), "email marked read");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL and the built services"]
+async fn llm_outage_falls_back_to_rule_based_extraction() {
+    let stack = Stack::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&stack.openai)
+        .await;
+    let session = stack.sign_up().await;
+
+    let message_id = format!("<{}@e2e.test>", Uuid::new_v4());
+    let body = "Please review the quarterly report and call the auditor tomorrow.";
+    stack
+        .post("channel-service", "/api/v1/email", &session, &email(&message_id, "Quarterly report", body))
+        .await
+        .expect("email processed despite the LLM outage");
+
+    let cases = stack.cases_for_email(&message_id, &session, PIPELINE_TIMEOUT).await;
+    assert_eq!(cases.len(), 1, "one case for the email");
+    assert!(!stack.openai.received_requests().await.unwrap_or_default().is_empty(), "the LLM was tried");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL and the built services"]
+async fn message_survives_an_ai_agent_outage() {
+    let mut stack = Stack::start().await;
+    stack.mock_openai_agent(&extraction(&["Renew the support contract"]), REPLY).await;
+    let session = stack.sign_up().await;
+    stack.stop_service("ai-agent-service");
+
+    let message_id = format!("<{}@e2e.test>", Uuid::new_v4());
+    let body = "Renew the support contract before it lapses.";
+    let response = stack
+        .post("channel-service", "/api/v1/email", &session, &email(&message_id, "Support contract", body))
+        .await
+        .expect("email accepted while the AI agent is down");
+    assert_eq!(response["case_id"], Uuid::nil().to_string(), "no case yet");
+
+    let failed = stack
+        .persistence_get("/api/v1/failed-messages?status=Pending", &session)
+        .await
+        .expect("list failed messages");
+    let failed = failed.as_array().and_then(|failed| failed.first()).cloned().expect("email dead-lettered");
+
+    stack.start_service("ai-agent-service").await;
+    let retry_path = format!("/api/v1/failed-messages/{}/retry", failed["id"].as_str().unwrap());
+    stack
+        .post("channel-service", &retry_path, &session, &Value::Null)
+        .await
+        .expect("retry the failed message");
+
+    let cases = stack.cases_for_email(&message_id, &session, PIPELINE_TIMEOUT).await;
+    assert_eq!(cases.len(), 1, "one case once retried");
+    assert_eq!(titles(&tasks_of(&stack, &cases[0], &session).await), ["Renew the support contract"]);
+
+    let reprocessed = stack
+        .persistence_get("/api/v1/failed-messages?status=Reprocessed", &session)
+        .await
+        .expect("list failed messages");
+    assert!(
+        reprocessed
+            .as_array()
+            .is_some_and(|reprocessed| reprocessed.iter().any(|message| message["id"] == failed["id"])),
+        "failed message marked reprocessed"
+    );
+}
@@ -0,0 +1,65 @@
+//! Endpoints only other services may call, reached over the network the
+//! way a visitor would; see the `e2e` crate docs for how to run it.
+
+use e2e::{Stack, INTERNAL_TOKEN};
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL and the built services"]
+async fn mailbox_tokens_are_only_given_to_services() {
+    let stack = Stack::start_with(&["persistence-service"]).await;
+    let session = stack.sign_up().await;
+    let url = format!("{}/api/v1/email-accounts/all", stack.url("persistence-service"));
+    let status = |request: reqwest::RequestBuilder| async move { request.send().await.expect("request sent").status() };
+
+    assert_eq!(status(stack.http.get(&url)).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(stack.http.get(&url).bearer_auth(&session)).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(stack.http.get(&url).header("x-internal-token", "guessed")).await, StatusCode::UNAUTHORIZED);
+    let with_session = stack.http.get(&url).header("x-internal-token", INTERNAL_TOKEN).bearer_auth(&session);
+    assert_eq!(status(with_session).await, StatusCode::UNAUTHORIZED, "not with a user's session");
+
+    let from_service = stack.http.get(&url).header("x-internal-token", INTERNAL_TOKEN);
+    assert_eq!(status(from_service).await, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL and the built services"]
+async fn organization_llm_keys_are_only_given_to_services() {
+    let stack = Stack::start_with(&["persistence-service"]).await;
+    let session = stack.sign_up().await;
+    let url = format!("{}/api/v1/llm/account", stack.url("persistence-service"));
+    let body = json!({ "session_token": session, "user_id": null });
+    let status = |request: reqwest::RequestBuilder| async move { request.send().await.expect("request sent").status() };
+
+    assert_eq!(status(stack.http.post(&url).json(&body)).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(stack.http.post(&url).json(&body).bearer_auth(&session)).await, StatusCode::UNAUTHORIZED);
+    let from_service = stack.http.post(&url).json(&body).header("x-internal-token", INTERNAL_TOKEN);
+    assert_eq!(status(from_service).await, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL and the built services"]
+async fn webhook_tokens_are_only_resolved_for_services() {
+    let stack = Stack::start_with(&["persistence-service"]).await;
+    let session = stack.sign_up().await;
+    let integration: serde_json::Value = stack
+        .http
+        .post(format!("{}/api/v1/webhook-integrations", stack.url("persistence-service")))
+        .bearer_auth(&session)
+        .json(&json!({ "name": "Forms", "mapping": { "sender": "email", "body": "message" } }))
+        .send()
+        .await
+        .expect("integration created")
+        .json()
+        .await
+        .expect("integration returned");
+    let url = format!("{}/api/v1/webhook-integrations/resolve", stack.url("persistence-service"));
+    let body = json!({ "token": integration["token"] });
+    let status = |request: reqwest::RequestBuilder| async move { request.send().await.expect("request sent").status() };
+
+    assert_eq!(status(stack.http.post(&url).json(&body)).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(stack.http.post(&url).json(&body).bearer_auth(&session)).await, StatusCode::UNAUTHORIZED);
+    let from_service = stack.http.post(&url).json(&body).header("x-internal-token", INTERNAL_TOKEN);
+    assert_eq!(status(from_service).await, StatusCode::OK);
+}